@@ -0,0 +1,649 @@
+#[cfg(test)]
+#[path = "html_tests.rs"]
+mod tests;
+
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::prelude::*;
+
+pub const DEFAULT_MESSAGES_PER_PAGE: usize = 1000;
+
+const INDEX_FILE_NAME: &str = "index.html";
+const STYLE_FILE_NAME: &str = "style.css";
+const CHATS_DIR_NAME: &str = "chats";
+const MEDIA_DIR_NAME: &str = "media";
+
+/// Relative path from a chat page to the export root
+const CHAT_PAGE_TO_ROOT: &str = "../..";
+
+const BATCH_SIZE: usize = 1000;
+const REPLY_SNIPPET_LEN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlExportSummary {
+    pub index_file: PathBuf,
+    pub chat_count: usize,
+    pub message_count: usize,
+    pub files_copied: usize,
+    /// Files referenced by messages which weren't found in the dataset root
+    pub files_missing: usize,
+}
+
+/// Exports a whole dataset (or a single chat of it, if chat ID is given) into a self-contained static HTML
+/// in the given directory, which should either not exist or be empty.
+///
+/// Each chat is written into its own directory, paginated into `messages.html`, `messages2.html`, etc.
+/// Media files are copied into the export directory, retaining their paths relative to the dataset root.
+pub fn export_html(dao: &dyn ChatHistoryDao,
+                   ds_uuid: &PbUuid,
+                   chat_id_option: Option<ChatId>,
+                   out_dir: &Path,
+                   messages_per_page: usize) -> Result<HtmlExportSummary> {
+    ensure!(messages_per_page > 0, "Messages per page should be positive!");
+    measure(|| {
+        if out_dir.exists() {
+            ensure!(out_dir.is_dir(), "{} is not a directory!", out_dir.display());
+            ensure!(fs::read_dir(out_dir)?.next().is_none(), "Directory {} is not empty!", out_dir.display());
+        } else {
+            fs::create_dir_all(out_dir)?;
+        }
+
+        let cwds = match chat_id_option {
+            Some(chat_id) => vec![dao.chat_option(ds_uuid, *chat_id)?
+                .with_context(|| format!("Chat {} not found!", *chat_id))?],
+            None => dao.chats(ds_uuid)?,
+        };
+
+        let mut exporter = HtmlExporter {
+            dao,
+            ds_root: dao.dataset_root(ds_uuid)?,
+            users: dao.users(ds_uuid)?.into_iter().map(|u| (u.id, u)).collect(),
+            out_dir,
+            messages_per_page,
+            copied_files: HashSet::new(),
+            message_count: 0,
+            files_missing: 0,
+        };
+
+        fs::write(out_dir.join(STYLE_FILE_NAME), STYLE)?;
+        for cwd in cwds.iter() {
+            exporter.export_chat(cwd)?;
+        }
+        let index_file = out_dir.join(INDEX_FILE_NAME);
+        fs::write(&index_file, exporter.render_index(dao, ds_uuid, &cwds)?)?;
+
+        Ok(HtmlExportSummary {
+            index_file,
+            chat_count: cwds.len(),
+            message_count: exporter.message_count,
+            files_copied: exporter.copied_files.len(),
+            files_missing: exporter.files_missing,
+        })
+    }, |_, t| log::info!("Exported HTML to {} in {t} ms", out_dir.display()))
+}
+
+struct HtmlExporter<'a> {
+    dao: &'a dyn ChatHistoryDao,
+    ds_root: DatasetRoot,
+    users: HashMap<i64, User>,
+    out_dir: &'a Path,
+    messages_per_page: usize,
+    /// Relative paths of files that were already copied
+    copied_files: HashSet<String>,
+    message_count: usize,
+    files_missing: usize,
+}
+
+/// What we need to know to render a reference to a message, possibly located on another page
+struct MessageRef {
+    page: usize,
+    internal_id: i64,
+    from_id: i64,
+    snippet: String,
+}
+
+impl HtmlExporter<'_> {
+    fn export_chat(&mut self, cwd: &ChatWithDetails) -> EmptyRes {
+        let chat = &cwd.chat;
+        let chat_dir = self.out_dir.join(chat_dir_rel_path(chat));
+        fs::create_dir_all(&chat_dir)?;
+
+        // First pass: remember where each message is, so that replies and pins could link to it
+        let mut refs: HashMap<i64, MessageRef> = HashMap::new();
+        let mut total = 0_usize;
+        loop {
+            let batch = self.dao.scroll_messages(chat, total, BATCH_SIZE)?;
+            if batch.is_empty() { break; }
+            for (i, msg) in batch.iter().enumerate() {
+                if let Some(source_id) = msg.source_id_option {
+                    refs.insert(source_id, MessageRef {
+                        page: (total + i) / self.messages_per_page,
+                        internal_id: msg.internal_id,
+                        from_id: msg.from_id,
+                        snippet: truncate_to(msg.searchable_string.clone(), REPLY_SNIPPET_LEN),
+                    });
+                }
+            }
+            total += batch.len();
+        }
+
+        let page_count = total.div_ceil(self.messages_per_page).max(1);
+        for page in 0..page_count {
+            let msgs = self.dao.scroll_messages(chat, page * self.messages_per_page, self.messages_per_page)?;
+            let mut body = String::new();
+            let nav = render_pagination(page, page_count);
+            body.push_str(&nav);
+            for msg in msgs.iter() {
+                self.render_message(&mut body, msg, cwd, &refs)?;
+            }
+            body.push_str(&nav);
+            let html = render_page(&chat.qualified_name(), &format!("{CHAT_PAGE_TO_ROOT}/{STYLE_FILE_NAME}"),
+                                   &format!("{CHAT_PAGE_TO_ROOT}/{INDEX_FILE_NAME}"), &body);
+            fs::write(chat_dir.join(page_file_name(page)), html)?;
+            self.message_count += msgs.len();
+        }
+        Ok(())
+    }
+
+    fn render_index(&mut self, dao: &dyn ChatHistoryDao, ds_uuid: &PbUuid, cwds: &[ChatWithDetails]) -> Result<String> {
+        let ds = dao.datasets()?.into_iter().find(|ds| &ds.uuid == ds_uuid).context("Dataset not found!")?;
+        let exported_ids: HashSet<i64> = cwds.iter().map(|cwd| cwd.chat.id).collect();
+        let mut body = String::new();
+        writeln!(body, r#"<h1>{}</h1>"#, escape(&ds.alias))?;
+        writeln!(body, r#"<ul class="chats">"#)?;
+        // Sub-chats are listed under their main chat, if it's exported too
+        let main_cwds = cwds.iter().filter(|cwd| cwd.chat.main_chat_id.is_none_or(|id| !exported_ids.contains(&id)));
+        for main_cwd in main_cwds {
+            self.render_index_entry(&mut body, &main_cwd.chat)?;
+            let sub_cwds = cwds.iter().filter(|cwd| cwd.chat.main_chat_id == Some(main_cwd.chat.id)).collect_vec();
+            if !sub_cwds.is_empty() {
+                writeln!(body, r#"<ul class="sub-chats">"#)?;
+                for sub_cwd in sub_cwds {
+                    self.render_index_entry(&mut body, &sub_cwd.chat)?;
+                }
+                writeln!(body, "</ul>")?;
+            }
+        }
+        writeln!(body, "</ul>")?;
+        Ok(render_page(&ds.alias, STYLE_FILE_NAME, INDEX_FILE_NAME, &body))
+    }
+
+    fn render_index_entry(&mut self, out: &mut String, chat: &Chat) -> EmptyRes {
+        write!(out, "<li>")?;
+        if let Some(img_href) = chat.img_path_option.as_deref().and_then(|p| self.copy_file(p, ".")) {
+            write!(out, r#"<img class="chat-img" src="{}" alt="">"#, escape(&img_href))?;
+        }
+        writeln!(out, r#"<a href="{}/{}">{}</a> <span class="details">({} messages)</span></li>"#,
+                 escape(&url_escape(&chat_dir_rel_path(chat))), page_file_name(0),
+                 escape(&name_or_unnamed(&chat.name_option)), chat.msg_count)?;
+        Ok(())
+    }
+
+    fn render_message(&mut self,
+                      out: &mut String,
+                      msg: &Message,
+                      cwd: &ChatWithDetails,
+                      refs: &HashMap<i64, MessageRef>) -> EmptyRes {
+        let is_service = matches!(msg.typed(), message::Typed::Service(_));
+        writeln!(out, r#"<div class="message{}" id="message{}">"#, if is_service { " service" } else { "" }, msg.internal_id)?;
+        write!(out, r#"<div class="header"><span class="from">{}</span> <span class="date">{}</span>"#,
+               escape(&self.user_name(msg.from_id)), format_timestamp(msg.timestamp))?;
+
+        match msg.typed() {
+            message::Typed::Regular(mr) => {
+                if let Some(edit_ts) = mr.edit_timestamp_option {
+                    write!(out, r#" <span class="edited">(edited {})</span>"#, format_timestamp(edit_ts))?;
+                }
+                if mr.is_deleted {
+                    write!(out, r#" <span class="deleted">(deleted)</span>"#)?;
+                }
+                if let Some(ref author) = mr.author_signature_option {
+                    write!(out, r#" <span class="author">{}</span>"#, escape(author))?;
+                }
+                if let Some(views) = mr.views_option {
+                    write!(out, r#" <span class="views">{views} views</span>"#)?;
+                }
+                writeln!(out, "</div>")?;
+                if let Some(ref name) = mr.forward_from_name_option {
+                    writeln!(out, r#"<div class="forwarded">Forwarded from <b>{}</b></div>"#, escape(name))?;
+                }
+                if let Some(reply_to_id) = mr.reply_to_message_id_option {
+                    self.render_message_ref(out, "reply", "In reply to", refs.get(&reply_to_id))?;
+                }
+                for content in mr.contents.iter() {
+                    self.render_content(out, content)?;
+                }
+                render_rich_text(out, &msg.text)?;
+                if !mr.reactions.is_empty() {
+                    write!(out, r#"<div class="reactions">"#)?;
+                    for reaction in mr.reactions.iter() {
+                        let emoji = reaction.emoji_option.as_deref().unwrap_or("[Custom emoji]");
+                        let from = reaction.from_ids.iter().map(|id| self.user_name(*id)).join(", ");
+                        write!(out, r#"<span class="reaction" title="{}">{} {}</span>"#,
+                               escape(&from), escape(emoji), reaction.count)?;
+                    }
+                    writeln!(out, "</div>")?;
+                }
+            }
+            message_service_pat!(ms) => {
+                writeln!(out, "</div>")?;
+                self.render_service(out, ms, msg, cwd, refs)?;
+            }
+            message_service_pat_unreachable!() => unreachable!(),
+        }
+        writeln!(out, "</div>")?;
+        Ok(())
+    }
+
+    fn render_service(&mut self,
+                      out: &mut String,
+                      ms: &message_service::SealedValueOptional,
+                      msg: &Message,
+                      cwd: &ChatWithDetails,
+                      refs: &HashMap<i64, MessageRef>) -> EmptyRes {
+        use message_service::SealedValueOptional::*;
+        macro_rules! system {
+            ($($arg:tt)*) => { writeln!(out, r#"<div class="system">({})</div>"#, format!($($arg)*))? };
+        }
+        match ms {
+            PhoneCall(v) => {
+                let duration = match v.duration_sec_option {
+                    Some(d) if d < 60 => format!(" ({d} sec)"),
+                    Some(d) => format!(" ({:02}:{:02}:{:02})", d / 3600, d / 60 % 60, d % 60),
+                    None => String::new(),
+                };
+                let discard_reason = match v.discard_reason_option.as_deref() {
+                    Some(reason) if reason != "hangup" => format!(" ({})", escape(reason)),
+                    _ => String::new(),
+                };
+                system!("Call{duration}{discard_reason}");
+                self.render_members(out, &v.members, cwd)?;
+            }
+            SuggestProfilePhoto(v) => {
+                system!("Suggested profile photo");
+                self.render_photo(out, &v.photo)?;
+            }
+            PinMessage(v) => {
+                self.render_message_ref(out, "pin", "Pinned message", refs.get(&v.message_source_id))?;
+            }
+            ClearHistory(_) => system!("History cleared"),
+            BlockUser(v) => system!("User has been {}blocked", if v.is_blocked { "" } else { "un" }),
+            StatusTextChanged(_) => system!("Status"),
+            Notice(_) => system!("Notice"),
+            GroupCreate(v) => {
+                system!("Created group <b>{}</b>", escape(&v.title));
+                self.render_members(out, &v.members, cwd)?;
+            }
+            GroupEditTitle(v) => system!("Changed group title to <b>{}</b>", escape(&v.title)),
+            GroupEditPhoto(v) => {
+                system!("Changed group photo");
+                self.render_photo(out, &v.photo)?;
+            }
+            GroupDeletePhoto(_) => system!("Deleted group photo"),
+            GroupInviteMembers(v) => {
+                if v.members.len() == 1 && v.members[0] == self.user_name(msg.from_id) {
+                    system!("Joined group");
+                } else {
+                    system!("Invited members");
+                    self.render_members(out, &v.members, cwd)?;
+                }
+            }
+            GroupRemoveMembers(v) => {
+                if v.members.len() == 1 && v.members[0] == self.user_name(msg.from_id) {
+                    system!("Left group");
+                } else {
+                    system!("Removed members");
+                    self.render_members(out, &v.members, cwd)?;
+                }
+            }
+            GroupMigrateFrom(v) => system!("Migrated from <b>{}</b>", escape(&v.title)),
+            GroupMigrateTo(_) => system!("Migrated to another group"),
+        }
+        // Status and notices (among others) store their text in the message itself
+        render_rich_text(out, &msg.text)?;
+        Ok(())
+    }
+
+    fn render_members(&self, out: &mut String, members: &[String], cwd: &ChatWithDetails) -> EmptyRes {
+        if members.is_empty() { return Ok(()); }
+        write!(out, r#"<ul class="members">"#)?;
+        for name in members {
+            // Resolved members are highlighted
+            let class = if cwd.resolve_member(name).is_some() { "member" } else { "member unknown" };
+            write!(out, r#"<li class="{class}">{}</li>"#, escape(name))?;
+        }
+        writeln!(out, "</ul>")?;
+        Ok(())
+    }
+
+    fn render_message_ref(&self, out: &mut String, class: &str, title: &str, msg_ref: Option<&MessageRef>) -> EmptyRes {
+        match msg_ref {
+            Some(r) => writeln!(out, r##"<div class="{class}">{title}: <a href="{}#message{}"><b>{}</b> {}</a></div>"##,
+                                page_file_name(r.page), r.internal_id, escape(&self.user_name(r.from_id)), escape(&r.snippet))?,
+            None => writeln!(out, r#"<div class="{class}">{title}: <i>(message not found)</i></div>"#)?,
+        }
+        Ok(())
+    }
+
+    fn render_content(&mut self, out: &mut String, content: &Content) -> EmptyRes {
+        use content::SealedValueOptional::*;
+        writeln!(out, r#"<div class="content">"#)?;
+        match content.sealed_value_optional.as_ref().context("Content is empty!")? {
+            Sticker(v) => {
+                let href = self.copy_file_opt(&v.path_option).or_else(|| self.copy_file_opt(&v.thumbnail_path_option));
+                match href {
+                    Some(href) if v.mime_type_option.as_deref().is_some_and(|m| m.starts_with("video/")) =>
+                        write!(out, r#"<video class="sticker" src="{}" autoplay loop muted></video>"#, escape(&href))?,
+                    Some(href) =>
+                        write!(out, r#"<img class="sticker" src="{}" alt="{}">"#,
+                               escape(&href), escape(v.emoji_option.as_deref().unwrap_or("")))?,
+                    None =>
+                        write!(out, r#"<div class="placeholder">[Sticker{}]</div>"#,
+                               v.emoji_option.as_deref().map(|e| format!(" {}", escape(e))).unwrap_or_default())?,
+                }
+            }
+            Photo(v) => self.render_photo(out, v)?,
+            VoiceMsg(v) => {
+                let duration = format_duration_suffix(v.duration_sec_option);
+                match self.copy_file_opt(&v.path_option) {
+                    Some(href) => write!(out, r#"<audio controls src="{}" type="{}"></audio>{duration}"#,
+                                         escape(&href), escape(&v.mime_type))?,
+                    None => write!(out, r#"<div class="placeholder">[Voice message{duration}]</div>"#)?,
+                }
+            }
+            Audio(v) => {
+                let title = [v.performer_option.as_deref(), v.title_option.as_deref(), v.file_name_option.as_deref()]
+                    .into_iter().flatten().next().map(escape).unwrap_or("Audio".to_owned());
+                let duration = format_duration_suffix(v.duration_sec_option);
+                writeln!(out, r#"<div class="title">{title}{duration}</div>"#)?;
+                match self.copy_file_opt(&v.path_option) {
+                    Some(href) => write!(out, r#"<audio controls src="{}" type="{}"></audio>"#,
+                                         escape(&href), escape(&v.mime_type))?,
+                    None => write!(out, r#"<div class="placeholder">[Audio not downloaded]</div>"#)?,
+                }
+            }
+            VideoMsg(v) => {
+                let poster = self.copy_file_opt(&v.thumbnail_path_option);
+                let duration = format_duration_suffix(v.duration_sec_option);
+                match self.copy_file_opt(&v.path_option) {
+                    Some(href) => write!(out, r#"<video class="video-msg" controls src="{}"{}></video>"#,
+                                         escape(&href), poster_attr(poster))?,
+                    None => write!(out, r#"<div class="placeholder">[Video message{duration}]</div>"#)?,
+                }
+                if v.is_one_time { write!(out, r#"<div class="one-time">(one-time)</div>"#)?; }
+            }
+            Video(v) => {
+                if let Some(title) = [v.performer_option.as_deref(), v.title_option.as_deref()].into_iter().flatten().next() {
+                    writeln!(out, r#"<div class="title">{}</div>"#, escape(title))?;
+                }
+                let poster = self.copy_file_opt(&v.thumbnail_path_option);
+                let duration = format_duration_suffix(v.duration_sec_option);
+                match self.copy_file_opt(&v.path_option) {
+                    Some(href) => write!(out, r#"<video controls src="{}" width="{}" height="{}"{}></video>"#,
+                                         escape(&href), v.width, v.height, poster_attr(poster))?,
+                    None => write!(out, r#"<div class="placeholder">[Video{duration}]</div>"#)?,
+                }
+                if v.is_one_time { write!(out, r#"<div class="one-time">(one-time)</div>"#)?; }
+            }
+            File(v) => {
+                let name = v.file_name_option.as_deref().or(v.path_option.as_deref()).unwrap_or("File");
+                if let Some(thumb_href) = self.copy_file_opt(&v.thumbnail_path_option) {
+                    write!(out, r#"<img class="thumbnail" src="{}" alt="">"#, escape(&thumb_href))?;
+                }
+                match self.copy_file_opt(&v.path_option) {
+                    Some(href) => write!(out, r#"<a class="file" href="{}">{}</a>"#, escape(&href), escape(name))?,
+                    None => write!(out, r#"<div class="placeholder">[File {}]</div>"#, escape(name))?,
+                }
+            }
+            Location(v) => {
+                for line in [v.title_option.as_deref(), v.address_option.as_deref()].into_iter().flatten() {
+                    writeln!(out, r#"<div class="title">{}</div>"#, escape(line))?;
+                }
+                write!(out, r#"<a class="location" href="https://www.openstreetmap.org/?mlat={lat}&amp;mlon={lon}#map=16/{lat}/{lon}">Location: {lat}, {lon}</a>"#,
+                       lat = escape(&v.lat_str), lon = escape(&v.lon_str))?;
+                if let Some(duration) = v.duration_sec_option {
+                    write!(out, r#"<div class="details">(live for {duration} sec)</div>"#)?;
+                }
+            }
+            Poll(v) => write!(out, r#"<div class="poll">Poll: <b>{}</b></div>"#, escape(&v.question))?,
+            SharedContact(v) => {
+                let name = [v.first_name_option.as_deref(), v.last_name_option.as_deref()].into_iter().flatten().join(" ");
+                write!(out, r#"<div class="contact">Shared contact: <b>{}</b>"#, escape(&name_or_unnamed_str(Some(&name).filter(|n| !n.is_empty()).map(|n| n.as_str()))))?;
+                if let Some(ref phone) = v.phone_number_option {
+                    write!(out, " ({})", escape(phone))?;
+                }
+                if let Some(href) = self.copy_file_opt(&v.vcard_path_option) {
+                    write!(out, r#" <a href="{}">vCard</a>"#, escape(&href))?;
+                }
+                write!(out, "</div>")?;
+            }
+            TodoList(v) => {
+                if let Some(ref title) = v.title_option {
+                    writeln!(out, r#"<div class="title">{}</div>"#, escape(title))?;
+                }
+                write!(out, r#"<ul class="todo">"#)?;
+                for item in v.items.iter() {
+                    let checked = if item.state == Selected::Yes as i32 { " checked" } else { "" };
+                    write!(out, r#"<li><input type="checkbox" disabled{checked}> {}</li>"#, escape(&item.text))?;
+                }
+                write!(out, "</ul>")?;
+            }
+        }
+        writeln!(out, "</div>")?;
+        Ok(())
+    }
+
+    fn render_photo(&mut self, out: &mut String, v: &ContentPhoto) -> EmptyRes {
+        match self.copy_file_opt(&v.path_option) {
+            Some(href) => write!(out, r#"<img class="photo" src="{}" width="{}" height="{}" alt="" loading="lazy">"#,
+                                 escape(&href), v.width, v.height)?,
+            None => write!(out, r#"<div class="placeholder">[Photo]</div>"#)?,
+        }
+        if v.is_one_time { write!(out, r#"<div class="one-time">(one-time)</div>"#)?; }
+        Ok(())
+    }
+
+    fn user_name(&self, id: i64) -> String {
+        self.users.get(&id).map(|u| u.pretty_name()).unwrap_or_else(|| format!("User #{id}"))
+    }
+
+    fn copy_file_opt(&mut self, rel_path_option: &Option<String>) -> Option<String> {
+        rel_path_option.as_deref().and_then(|p| self.copy_file(p, CHAT_PAGE_TO_ROOT))
+    }
+
+    /// Copies a file (if it exists) to the export media directory.
+    /// Returns URL-escaped path to it, relative to the page which is located at `page_to_root` relative to export root.
+    fn copy_file(&mut self, rel_path: &str, page_to_root: &str) -> Option<String> {
+        if !is_safe_rel_path(rel_path) {
+            log::warn!("Not exporting file with a suspicious path {rel_path}");
+            self.files_missing += 1;
+            return None;
+        }
+        let src = self.ds_root.to_absolute(rel_path);
+        if !src.is_file() {
+            self.files_missing += 1;
+            return None;
+        }
+        if !self.copied_files.contains(rel_path) {
+            let dst = self.out_dir.join(MEDIA_DIR_NAME).join(rel_path);
+            let res = dst.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::copy(&src, &dst));
+            if let Err(e) = res {
+                log::warn!("Failed to copy {} to {}: {e}", src.display(), dst.display());
+                self.files_missing += 1;
+                return None;
+            }
+            self.copied_files.insert(rel_path.to_owned());
+        }
+        Some(format!("{page_to_root}/{MEDIA_DIR_NAME}/{}", url_escape(rel_path)))
+    }
+}
+
+//
+// Rendering helpers
+//
+
+fn render_rich_text(out: &mut String, rtes: &[RichTextElement]) -> EmptyRes {
+    use rich_text_element::Val;
+    if rtes.is_empty() { return Ok(()); }
+    write!(out, r#"<div class="text">"#)?;
+    for rte in rtes {
+        match rte.val.as_ref().context("Rich text element is empty!")? {
+            Val::Plain(v) => write!(out, "{}", escape(&v.text))?,
+            Val::Bold(v) => write!(out, "<b>{}</b>", escape(&v.text))?,
+            Val::Italic(v) => write!(out, "<i>{}</i>", escape(&v.text))?,
+            Val::Underline(v) => write!(out, "<u>{}</u>", escape(&v.text))?,
+            Val::Strikethrough(v) => write!(out, "<s>{}</s>", escape(&v.text))?,
+            Val::Link(v) if v.hidden => {}
+            Val::Link(v) if !is_safe_href(&v.href) =>
+                write!(out, "{}", escape(v.text_option.as_deref().unwrap_or(&v.href)))?,
+            Val::Link(v) => write!(out, r#"<a href="{}">{}</a>"#,
+                                   escape(&v.href), escape(v.text_option.as_deref().unwrap_or(&v.href)))?,
+            Val::PrefmtInline(v) => write!(out, "<code>{}</code>", escape(&v.text))?,
+            Val::PrefmtBlock(v) => match v.language_option {
+                Some(ref lang) => write!(out, r#"<pre><code class="language-{}">{}</code></pre>"#,
+                                         escape(lang), escape(&v.text))?,
+                None => write!(out, "<pre><code>{}</code></pre>", escape(&v.text))?,
+            },
+            Val::Blockquote(v) => write!(out, "<blockquote>{}</blockquote>", escape(&v.text))?,
+            Val::Spoiler(v) => write!(out, r#"<span class="spoiler">{}</span>"#, escape(&v.text))?,
+        }
+    }
+    writeln!(out, "</div>")?;
+    Ok(())
+}
+
+fn render_pagination(page: usize, page_count: usize) -> String {
+    if page_count <= 1 { return String::new(); }
+    let prev = if page > 0 {
+        format!(r#"<a href="{}">&laquo; Previous</a>"#, page_file_name(page - 1))
+    } else { String::new() };
+    let next = if page + 1 < page_count {
+        format!(r#"<a href="{}">Next &raquo;</a>"#, page_file_name(page + 1))
+    } else { String::new() };
+    format!(r#"<div class="pagination">{prev} <span>Page {} of {page_count}</span> {next}</div>"#, page + 1)
+}
+
+fn render_page(title: &str, style_href: &str, index_href: &str, body: &str) -> String {
+    format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<link rel="stylesheet" href="{style_href}">
+</head>
+<body>
+<div class="nav"><a href="{index_href}">All chats</a></div>
+<div class="page">
+<h2>{title}</h2>
+{body}
+</div>
+</body>
+</html>
+"#, title = escape(title))
+}
+
+fn chat_dir_rel_path(chat: &Chat) -> String {
+    format!("{CHATS_DIR_NAME}/chat_{}", chat.id)
+}
+
+fn page_file_name(page: usize) -> String {
+    if page == 0 { "messages.html".to_owned() } else { format!("messages{}.html", page + 1) }
+}
+
+pub(crate) fn format_timestamp(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .map(|dt| dt.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| format!("@{ts}"))
+}
+
+fn format_duration_suffix(duration_sec_option: Option<i32>) -> String {
+    duration_sec_option.map(|d| format!(" ({d} sec)")).unwrap_or_default()
+}
+
+fn poster_attr(poster_href: Option<String>) -> String {
+    poster_href.map(|p| format!(r#" poster="{}""#, escape(&p))).unwrap_or_default()
+}
+
+/// Whether a file path stays within its root, i.e. is relative and has no `..` components.
+fn is_safe_rel_path(rel_path: &str) -> bool {
+    !rel_path.is_empty()
+        && Path::new(rel_path).components().all(|c| matches!(c, std::path::Component::Normal(_)))
+        // Backslashes are treated as separators in URLs
+        && rel_path.split(['/', '\\']).all(|part| part != "..")
+}
+
+/// Whether a link is safe to be clicked in exported page, e.g. not a `javascript:` one.
+fn is_safe_href(href: &str) -> bool {
+    const SAFE_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tg"];
+    href.split_once(':').is_some_and(|(scheme, _)| SAFE_SCHEMES.contains(&scheme.trim().to_ascii_lowercase().as_str()))
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Percent-encodes characters that have special meaning in a relative URL path
+fn url_escape(path: &str) -> String {
+    let mut res = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '~' | '/' => res.push(c),
+            '\\' => res.push('/'),
+            c => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    res.push_str(&format!("%{b:02X}"));
+                }
+            }
+        }
+    }
+    res
+}
+
+const STYLE: &str = r#"body { font-family: sans-serif; margin: 0; background: #f5f5f5; }
+.nav { padding: 8px 16px; background: #fff; border-bottom: 1px solid #ddd; }
+.page { max-width: 800px; margin: 0 auto; padding: 16px; }
+.chats li { margin: 6px 0; list-style: none; }
+.chat-img { width: 32px; height: 32px; border-radius: 50%; vertical-align: middle; margin-right: 8px; object-fit: cover; }
+.details { color: #888; font-size: 0.9em; }
+.pagination { text-align: center; margin: 12px 0; }
+.pagination span { margin: 0 12px; }
+.message { background: #fff; border-radius: 6px; padding: 8px 12px; margin: 6px 0; }
+.message.service { background: #eef; }
+.header .from { font-weight: bold; }
+.header .date, .header .edited, .header .deleted, .header .author, .header .views { color: #888; font-size: 0.85em; }
+.forwarded, .reply, .pin { border-left: 3px solid #8ab; padding-left: 8px; margin: 4px 0; color: #555; }
+.reply a, .pin a { color: inherit; text-decoration: none; }
+.text { white-space: pre-wrap; word-wrap: break-word; margin-top: 4px; }
+.system { font-style: italic; color: #555; }
+.content { margin: 4px 0; }
+.content img.photo, .content video { max-width: 100%; height: auto; }
+.sticker { max-width: 160px; max-height: 160px; }
+.video-msg { width: 240px; height: 240px; border-radius: 50%; object-fit: cover; }
+.thumbnail { max-width: 64px; max-height: 64px; vertical-align: middle; margin-right: 8px; }
+.placeholder, .one-time { color: #888; font-style: italic; }
+.spoiler { background: #444; color: #444; }
+.spoiler:hover { color: #fff; }
+.reactions { margin-top: 4px; }
+.reaction { display: inline-block; background: #eef; border-radius: 12px; padding: 1px 8px; margin-right: 4px; }
+.members { margin: 4px 0; }
+.member.unknown { color: #888; }
+.todo { list-style: none; padding-left: 8px; }
+blockquote { border-left: 3px solid #ccc; margin: 4px 0; padding-left: 8px; }
+pre { background: #f0f0f0; padding: 8px; overflow-x: auto; }
+"#;
@@ -0,0 +1,157 @@
+#![allow(unused_imports)]
+use super::*;
+
+use crate::prelude::*;
+use crate::utils::test_utils::*;
+
+use pretty_assertions::{assert_eq, assert_ne};
+use rand::prelude::*;
+
+#[test]
+fn export_paginated_chat() -> EmptyRes {
+    let (dao_holder, ds_uuid) = create_test_dao();
+    let out_tmp_dir = TmpDir::new();
+    let out_dir = out_tmp_dir.path.join("export");
+
+    let summary = export_html(dao_holder.dao.as_ref(), &ds_uuid, None, &out_dir, 2)?;
+    assert_eq!(summary.index_file, out_dir.join(INDEX_FILE_NAME));
+    assert_eq!(summary.chat_count, 2);
+    assert_eq!(summary.message_count, 6);
+    // Photo and chat image (same file is used for both chats)
+    assert_eq!(summary.files_copied, 2);
+    assert_eq!(summary.files_missing, 1);
+
+    assert!(out_dir.join(STYLE_FILE_NAME).is_file());
+    assert!(out_dir.join("media/photos/my photo.jpg").is_file());
+
+    let index = fs::read_to_string(&summary.index_file)?;
+    assert!(index.contains(r#"<a href="chats/chat_1/messages.html">Chat A</a>"#));
+    assert!(index.contains(r#"<a href="chats/chat_2/messages.html">Chat B</a>"#));
+
+    let chat_dir = out_dir.join("chats/chat_1");
+    let pages = (0..3).map(|page| fs::read_to_string(chat_dir.join(page_file_name(page)))).collect::<std::io::Result<Vec<_>>>()?;
+    assert!(!chat_dir.join(page_file_name(3)).exists());
+
+    // Pagination
+    assert!(pages[0].contains(r#"<a href="messages2.html">Next &raquo;</a>"#));
+    assert!(pages[1].contains(r#"<a href="messages.html">&laquo; Previous</a>"#));
+    assert!(pages[1].contains(r#"<a href="messages3.html">Next &raquo;</a>"#));
+    assert!(!pages[2].contains("Next &raquo;"));
+
+    // Media
+    assert!(pages[0].contains(r#"<img class="photo" src="../../media/photos/my%20photo.jpg" width="10" height="20""#));
+    assert!(pages[2].contains("[Photo]"));
+
+    // Rich text is escaped
+    assert!(pages[1].contains("<b>&lt;script&gt;</b>"));
+    assert!(pages[1].contains(r#"<a href="https://example.com/?a=1&amp;b=2">link</a>evil"#));
+    assert!(!pages[1].contains("javascript:"));
+
+    // Reply to a message on a previous page
+    assert!(pages[1].contains(r##"In reply to: <a href="messages.html#message200"><b>User 1</b> Hello there, 2!"##));
+
+    // Service message
+    assert!(pages[2].contains("(Created group <b>Chat A</b>)"));
+    assert!(pages[2].contains(r#"<li class="member">User 1</li><li class="member unknown">Someone</li>"#));
+
+    Ok(())
+}
+
+#[test]
+fn export_single_chat() -> EmptyRes {
+    let (dao_holder, ds_uuid) = create_test_dao();
+    let out_tmp_dir = TmpDir::new();
+
+    let summary = export_html(dao_holder.dao.as_ref(), &ds_uuid, Some(ChatId(2)), &out_tmp_dir.path, 100)?;
+    assert_eq!(summary.chat_count, 1);
+    assert_eq!(summary.message_count, 0);
+    assert!(out_tmp_dir.path.join("chats/chat_2/messages.html").is_file());
+    assert!(!out_tmp_dir.path.join("chats/chat_1").exists());
+
+    // Directory is no longer empty
+    assert!(export_html(dao_holder.dao.as_ref(), &ds_uuid, Some(ChatId(2)), &out_tmp_dir.path, 100).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn escaping() {
+    assert_eq!(escape(r#"<a href="x">'&'</a>"#), "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;");
+    assert_eq!(url_escape("a b/c#d/ё.txt"), "a%20b/c%23d/%D1%91.txt");
+}
+
+#[test]
+fn unsafe_paths_and_links() {
+    assert!(is_safe_rel_path("photos/my photo.jpg"));
+    assert!(is_safe_rel_path("photos/..jpg"));
+    assert!(!is_safe_rel_path(""));
+    assert!(!is_safe_rel_path("../outside.jpg"));
+    assert!(!is_safe_rel_path("photos/../../outside.jpg"));
+    assert!(!is_safe_rel_path("photos\\..\\..\\outside.jpg"));
+    assert!(!is_safe_rel_path("/etc/passwd"));
+
+    assert!(is_safe_href("https://example.com"));
+    assert!(is_safe_href("HTTP://example.com"));
+    assert!(is_safe_href("mailto:me@example.com"));
+    assert!(is_safe_href("tg://resolve?domain=example"));
+    assert!(!is_safe_href("javascript:alert(1)"));
+    assert!(!is_safe_href(" JavaScript:alert(1)"));
+    assert!(!is_safe_href("data:text/html,<script>alert(1)</script>"));
+    assert!(!is_safe_href("example.com"));
+}
+
+//
+// Helpers
+//
+
+fn create_test_dao() -> (InMemoryDaoHolder, PbUuid) {
+    let users = (1..=2).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+
+    let mut msgs = (1..=5).map(|idx| create_regular_message(idx, idx % 2 + 1)).collect_vec();
+    for m in msgs.iter_mut() {
+        if let Some(message::Typed::Regular(mr)) = m.typed.as_mut() {
+            mr.reply_to_message_id_option = None;
+        }
+    }
+    if let Some(message::Typed::Regular(mr)) = msgs[2].typed.as_mut() {
+        mr.reply_to_message_id_option = Some(2);
+    }
+    msgs[3].text = vec![
+        RichText::make_bold("<script>".to_owned()),
+        RichText::make_link(Some("link".to_owned()), "https://example.com/?a=1&b=2".to_owned()),
+        RichText::make_link(Some("evil".to_owned()), "javascript:alert(1)".to_owned()),
+    ];
+    msgs.push(Message {
+        internal_id: 600,
+        source_id_option: Some(6),
+        typed: Some(message_service!(message_service::SealedValueOptional::GroupCreate(MessageServiceGroupCreate {
+            title: "Chat A".to_owned(),
+            members: vec!["User 1".to_owned(), "Someone".to_owned()],
+        }))),
+        text: vec![],
+        ..msgs[4].clone()
+    });
+
+    let cwms = vec![
+        ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 1, "A", vec![1, 2], msgs.len()), messages: msgs },
+        ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 2, "B", vec![1, 2], 0), messages: vec![] },
+    ];
+
+    let dao_holder = create_dao("One", users, cwms, |ds_root, m| {
+        let photo_path = match m.source_id_option {
+            Some(2) => {
+                let path = ds_root.0.join("photos/my photo.jpg");
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                create_named_file(&path, b"photo");
+                Some(ds_root.to_relative(&path).unwrap())
+            }
+            Some(5) => Some("photos/missing.jpg".to_owned()),
+            _ => None,
+        };
+        if let (Some(path), Some(message::Typed::Regular(mr))) = (photo_path, m.typed.as_mut()) {
+            mr.contents = vec![content!(Photo { path_option: Some(path), width: 10, height: 20, ..Default::default() })];
+        }
+    }, rng().random());
+    let ds_uuid = dao_holder.dao.ds_uuid();
+    (dao_holder, ds_uuid)
+}
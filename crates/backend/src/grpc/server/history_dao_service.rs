@@ -3,6 +3,8 @@ use std::sync::Mutex;
 
 use tonic::Request;
 
+use chat_history_manager_dao::{IntegrityIssue, IntegrityIssueKind, MessageSearchQuery};
+
 use crate::protobuf::history::history_dao_service_server::HistoryDaoService;
 
 use super::*;
@@ -186,6 +188,27 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
         })
     }
 
+    async fn search(&self, req: Request<SearchRequest>) -> TonicResult<SearchResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let result = dao.search_messages(&MessageSearchQuery {
+                ds_uuid: req.ds_uuid.clone(),
+                text: req.query.clone(),
+                chat_id_option: req.chat_id_option.map(ChatId),
+                from_id_option: req.from_id_option.map(UserId),
+                timestamp_from_option: req.timestamp_from_option.map(Timestamp),
+                timestamp_to_option: req.timestamp_to_option.map(Timestamp),
+                offset: usize::try_from(req.offset).context("Offset should not be negative!")?,
+                limit: usize::try_from(req.limit).context("Limit should not be negative!")?,
+            })?;
+            Ok(SearchResponse {
+                total_count: result.total_count as i32,
+                hits: result.hits.into_iter()
+                    .map(|h| SearchHit { chat_id: *h.chat_id, message: h.message, fragment: h.fragment })
+                    .collect_vec(),
+            })
+        })
+    }
+
     async fn is_loaded(&self, req: Request<IsLoadedRequest>) -> TonicResult<IsLoadedResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
             Ok(IsLoadedResponse {
@@ -194,6 +217,24 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
         })
     }
 
+    async fn export_html(&self, req: Request<ExportHtmlRequest>) -> TonicResult<ExportHtmlResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let messages_per_page = match req.messages_per_page_option {
+                Some(n) => usize::try_from(n).context("Messages per page should be positive!")?,
+                None => crate::DEFAULT_MESSAGES_PER_PAGE,
+            };
+            let summary = crate::export_html(dao, &req.ds_uuid, req.chat_id_option.map(ChatId),
+                                             Path::new(&req.output_dir), messages_per_page)?;
+            Ok(ExportHtmlResponse {
+                index_file: path_to_str(&summary.index_file)?.to_owned(),
+                chat_count: summary.chat_count as i32,
+                message_count: summary.message_count as i32,
+                files_copied: summary.files_copied as i32,
+                files_missing: summary.files_missing as i32,
+            })
+        })
+    }
+
     //
     // Mutable DAO endpoints
     //
@@ -266,4 +307,66 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
             Ok(Empty {})
         })
     }
+
+    async fn verify(&self, req: Request<VerifyRequest>) -> TonicResult<VerifyResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, {
+            let fix_kinds = req.fix_types().map(integrity_issue_kind).collect_vec();
+            let (issues, fixed_count) = dao.as_verifiable()?.verify_and_fix(&req.ds_uuid, &fix_kinds)?;
+            Ok(VerifyResponse {
+                issues: issues.into_iter().map(|issue| issue.into()).collect_vec(),
+                fixed_count: fixed_count as i32,
+            })
+        })
+    }
+}
+
+fn integrity_issue_kind(tpe: IntegrityIssueType) -> IntegrityIssueKind {
+    match tpe {
+        IntegrityIssueType::MissingFile => IntegrityIssueKind::MissingFile,
+        IntegrityIssueType::OrphanFile => IntegrityIssueKind::OrphanFile,
+        IntegrityIssueType::NonMemberAuthor => IntegrityIssueKind::NonMemberAuthor,
+        IntegrityIssueType::DanglingReply => IntegrityIssueKind::DanglingReply,
+        IntegrityIssueType::DanglingPin => IntegrityIssueKind::DanglingPin,
+        IntegrityIssueType::WrongMessageCount => IntegrityIssueKind::WrongMessageCount,
+        IntegrityIssueType::EmptySearchableString => IntegrityIssueKind::EmptySearchableString,
+    }
+}
+
+impl From<IntegrityIssue> for IntegrityIssuePb {
+    fn from(issue: IntegrityIssue) -> Self {
+        let mut pb = IntegrityIssuePb {
+            tpe: match issue.kind() {
+                IntegrityIssueKind::MissingFile => IntegrityIssueType::MissingFile,
+                IntegrityIssueKind::OrphanFile => IntegrityIssueType::OrphanFile,
+                IntegrityIssueKind::NonMemberAuthor => IntegrityIssueType::NonMemberAuthor,
+                IntegrityIssueKind::DanglingReply => IntegrityIssueType::DanglingReply,
+                IntegrityIssueKind::DanglingPin => IntegrityIssueType::DanglingPin,
+                IntegrityIssueKind::WrongMessageCount => IntegrityIssueType::WrongMessageCount,
+                IntegrityIssueKind::EmptySearchableString => IntegrityIssueType::EmptySearchableString,
+            } as i32,
+            description: issue.to_string(),
+            fixable: issue.is_fixable(),
+            path_option: None,
+            chat_id_option: None,
+            user_id_option: None,
+            message_internal_id_option: None,
+        };
+        match issue {
+            IntegrityIssue::MissingFile { path } | IntegrityIssue::OrphanFile { path } =>
+                pb.path_option = Some(path),
+            IntegrityIssue::NonMemberAuthor { chat_id, user_id, .. } => {
+                pb.chat_id_option = Some(*chat_id);
+                pb.user_id_option = Some(*user_id);
+            }
+            IntegrityIssue::DanglingReply { chat_id, internal_id, .. } |
+            IntegrityIssue::DanglingPin { chat_id, internal_id, .. } |
+            IntegrityIssue::EmptySearchableString { chat_id, internal_id } => {
+                pb.chat_id_option = Some(*chat_id);
+                pb.message_internal_id_option = Some(*internal_id);
+            }
+            IntegrityIssue::WrongMessageCount { chat_id, .. } =>
+                pb.chat_id_option = Some(*chat_id),
+        }
+        pb
+    }
 }
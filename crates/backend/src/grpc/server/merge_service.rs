@@ -1,12 +1,17 @@
 use std::fs;
+use std::path::PathBuf;
 
 use tonic::Request;
 
 use path_dedot::*;
 
 use crate::merge::analyzer::*;
+use crate::merge::auto_merge::{auto_merge_datasets, AutoMergePolicy, AutoMergeSummary};
 use crate::merge::merger;
 use crate::merge::merger::{ChatMergeDecision, MessagesMergeDecision, UserMergeDecision};
+use crate::merge::multi_merge::{merge_many_datasets, SnapshotToMerge};
+use crate::merge::report::*;
+use crate::merge::session::{ensure_unchanged, MergeSessionStore};
 use crate::protobuf::history::merge_service_server::*;
 
 use super::*;
@@ -15,15 +20,30 @@ use super::*;
 impl MergeService for Arc<ChatHistoryManagerServer> {
     async fn analyze(&self, req: Request<AnalyzeRequest>) -> TonicResult<AnalyzeResponse> {
         self.process_merge_service_request(req, |_, req, m_dao, m_ds, s_dao, s_ds| {
-            let analyzer = DatasetDiffAnalyzer::create(m_dao, &m_ds, s_dao, &s_ds)?;
+            let create_analyzer = || ok(DatasetDiffAnalyzer::create(m_dao, &m_ds, s_dao, &s_ds)?
+                .with_alignment(alignment(req.fuzzy_timestamp_tolerance_sec)));
+            let analyzer = create_analyzer()?;
             let mut analysis = Vec::with_capacity(req.chat_id_pairs.len());
             for pair @ ChatIdPair { master_chat_id, slave_chat_id } in req.chat_id_pairs.iter() {
                 let m_cwd = m_dao.chat_option(&m_ds.uuid, *master_chat_id)?
                     .with_context(|| format!("Master chat {} not found!", *master_chat_id))?;
                 let s_cwd = s_dao.chat_option(&s_ds.uuid, *slave_chat_id)?
                     .with_context(|| format!("Slave chat {} not found!", *slave_chat_id))?;
-                let analyzed =
-                    analyzer.analyze(&m_cwd, &s_cwd, &s_cwd.chat.qualified_name(), req.force_conflicts)?;
+                let title = s_cwd.chat.qualified_name();
+                let (analyzed, time_shift) =
+                    match analyzer.analyze_detecting_time_shift(&m_cwd, &s_cwd, &title, req.force_conflicts)? {
+                        ChatAnalysisOutcome::Analyzed(analyzed) =>
+                            (analyzed, None),
+                        ChatAnalysisOutcome::TimeShifted(shift) if req.apply_time_shift() => {
+                            log::info!("Compensating time shift of {} sec in chat {title}", shift.slave_ahead_by_sec);
+                            let analyzed = create_analyzer()?
+                                .with_slave_time_shift(shift.slave_ahead_by_sec)
+                                .analyze(&m_cwd, &s_cwd, &title, req.force_conflicts)?;
+                            (analyzed, Some(time_shift_pb(shift, true)))
+                        }
+                        ChatAnalysisOutcome::TimeShifted(shift) =>
+                            (vec![], Some(time_shift_pb(shift, false))),
+                    };
                 let sections = analyzed.into_iter().map(|a| {
                     let mut res = AnalysisSection {
                         tpe: 0,
@@ -63,100 +83,422 @@ impl MergeService for Arc<ChatHistoryManagerServer> {
                     };
                     res
                 }).collect_vec();
-                analysis.push(ChatAnalysis { chat_ids: pair.clone(), sections })
+                analysis.push(ChatAnalysis { chat_ids: pair.clone(), sections, time_shift })
             }
             Ok(analysis)
         }, |analysis| Ok(AnalyzeResponse { analysis })).await
     }
 
     async fn merge(&self, req: Request<MergeRequest>) -> TonicResult<MergeResponse> {
+        if req.get_ref().in_place() {
+            return self.process_request_blocking(req, |self_clone, req| self_clone.merge_in_place(&req)).await;
+        }
         self.process_merge_service_request(req, |self_clone, req, m_dao, m_ds, s_dao, s_ds| {
-            let sqlite_dao_dir = Path::new(&req.new_database_dir);
-            let sqlite_dao_dir = sqlite_dao_dir.parse_dot()?;
-            if !sqlite_dao_dir.exists() {
-                if sqlite_dao_dir.parent().is_none_or(|p| p.exists()) {
-                    fs::create_dir(&sqlite_dao_dir)?;
-                } else {
-                    bail!("Parent directory of {} does not exist!", sqlite_dao_dir.display());
-                }
-            }
-            let user_merges = req.user_merges.iter().map(|um|
-                ok(match UserMergeType::try_from(um.tpe)? {
-                    UserMergeType::Retain => UserMergeDecision::Retain(UserId(um.user_id)),
-                    UserMergeType::Add => UserMergeDecision::Add(UserId(um.user_id)),
-                    UserMergeType::DontAdd => UserMergeDecision::DontAdd(UserId(um.user_id)),
-                    UserMergeType::Replace => UserMergeDecision::Replace(UserId(um.user_id)),
-                    UserMergeType::MatchOrDontReplace => UserMergeDecision::MatchOrDontReplace(UserId(um.user_id)),
-                })
-            ).try_collect()?;
-            let chat_merges = req.chat_merges.iter().map(|cm|
-                ok(match ChatMergeType::try_from(cm.tpe)? {
-                    ChatMergeType::Retain => ChatMergeDecision::Retain { master_chat_id: ChatId(cm.chat_id) },
-                    ChatMergeType::DontMerge => ChatMergeDecision::DontMerge { chat_id: ChatId(cm.chat_id) },
-                    ChatMergeType::Add => ChatMergeDecision::Add { slave_chat_id: ChatId(cm.chat_id) },
-                    ChatMergeType::DontAdd => ChatMergeDecision::DontAdd { slave_chat_id: ChatId(cm.chat_id) },
-                    ChatMergeType::Merge => {
-                        use MessageMergeType as MMT;
-                        use MessagesMergeDecision as MMD;
-                        let message_merges = cm.message_merges.iter().map(|mm| {
-                            let range = &mm.range;
-                            ok(match MessageMergeType::try_from(mm.tpe)? {
-                                MMT::Match => MMD::Match(MergeAnalysisSectionMatch {
-                                    first_master_msg_id: MasterInternalId(range.first_master_msg_id),
-                                    last_master_msg_id: MasterInternalId(range.last_master_msg_id),
-                                    first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
-                                    last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
-                                }),
-                                MMT::Retain => MMD::Retain(MergeAnalysisSectionRetention {
-                                    first_master_msg_id: MasterInternalId(range.first_master_msg_id),
-                                    last_master_msg_id: MasterInternalId(range.last_master_msg_id),
-                                }),
-                                MMT::Add => MMD::Add(MergeAnalysisSectionAddition {
-                                    first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
-                                    last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
-                                }),
-                                MMT::DontAdd => MMD::DontAdd(MergeAnalysisSectionAddition {
-                                    first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
-                                    last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
-                                }),
-                                MMT::Replace => MMD::Replace(MergeAnalysisSectionConflict {
-                                    first_master_msg_id: MasterInternalId(range.first_master_msg_id),
-                                    last_master_msg_id: MasterInternalId(range.last_master_msg_id),
-                                    first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
-                                    last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
-                                }),
-                                MMT::DontReplace => MMD::DontReplace(MergeAnalysisSectionConflict {
-                                    first_master_msg_id: MasterInternalId(range.first_master_msg_id),
-                                    last_master_msg_id: MasterInternalId(range.last_master_msg_id),
-                                    first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
-                                    last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
-                                }),
-                            })
-                        }).try_collect()?;
-                        ChatMergeDecision::Merge { chat_id: ChatId(cm.chat_id), message_merges }
+            let sqlite_dao_dir = prepare_database_dir(&req.new_database_dir)?;
+            let (user_merges, chat_merges, slave_time_shifts) = merge_decisions(&req.user_merges, &req.chat_merges)?;
+            let (dao, ds, report) = merger::merge_datasets(&sqlite_dao_dir,
+                                                           m_dao, &m_ds,
+                                                           s_dao, &s_ds,
+                                                           user_merges, chat_merges,
+                                                           &slave_time_shifts)?;
+            Ok((self_clone, dao, ds, report))
+        }, |(self_clone, dao, ds, report)| {
+            Ok(MergeResponse {
+                new_file: self_clone.register_merged(dao)?,
+                new_ds_uuid: ds.uuid,
+                report: Some(report.into()),
+            })
+        }).await
+    }
+
+    async fn merge_many(&self, req: Request<MergeManyRequest>) -> TonicResult<MergeManyResponse> {
+        self.process_request_blocking(req, |self_clone, req| {
+            let policy = MergePolicy::try_from(req.policy)?.into();
+            let (dao, ds, reports) = {
+                let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
+
+                // Several snapshots might come from the same DAO, it should only be locked once
+                let mut daos: IndexMap<&DaoKey, _> = IndexMap::new();
+                for snapshot in req.snapshots.iter() {
+                    if !daos.contains_key(&snapshot.dao_key) {
+                        let dao = loaded_daos.get(&snapshot.dao_key)
+                            .with_context(|| format!("DAO {} not found", snapshot.dao_key))?;
+                        daos.insert(&snapshot.dao_key, read_or_status(dao)?);
                     }
-                })
-            ).try_collect()?;
-            let (dao, ds) = merger::merge_datasets(&sqlite_dao_dir,
-                                                   m_dao, &m_ds,
-                                                   s_dao, &s_ds,
-                                                   user_merges, chat_merges)?;
-            let key = path_to_str(&dao.db_file)?.to_owned();
-            Ok((self_clone, key, DaoRwLock::new(Box::new(dao)), ds))
-        }, |(self_clone, key, dao_lock, ds): (Self, DaoKey, DaoRwLock, Dataset)| {
-            let dao = read_or_status(&dao_lock)?;
-            let name = dao.name().to_owned();
-            let storage_path = path_to_str(dao.storage_path())?.to_owned();
-            drop(dao);
-            write_or_status(&self_clone.loaded_daos)?.insert(key.clone(), dao_lock);
+                }
+                let mut datasets = Vec::with_capacity(req.snapshots.len());
+                for snapshot in req.snapshots.iter() {
+                    let ds = daos[&snapshot.dao_key].datasets()?.into_iter().find(|ds| ds.uuid == snapshot.ds_uuid)
+                        .with_context(|| format!("Dataset {} not found in {}!", snapshot.ds_uuid.value, snapshot.dao_key))?;
+                    datasets.push(ds);
+                }
+                let snapshots = req.snapshots.iter().zip(datasets.iter())
+                    .map(|(snapshot, ds)| SnapshotToMerge {
+                        dao: &**daos[&snapshot.dao_key] as &dyn ChatHistoryDao,
+                        ds,
+                        slave_time_shifts: snapshot.slave_time_shifts.iter()
+                            .map(|shift| (ChatId(shift.chat_id), shift.slave_ahead_by_sec))
+                            .collect(),
+                    })
+                    .collect_vec();
+
+                let sqlite_dao_dir = prepare_database_dir(&req.new_database_dir)?;
+                merge_many_datasets(&sqlite_dao_dir, &snapshots, policy,
+                                    alignment(req.fuzzy_timestamp_tolerance_sec))?
+            };
+            Ok(MergeManyResponse {
+                new_file: self_clone.register_merged(dao)?,
+                new_ds_uuid: ds.uuid,
+                reports: reports.into_iter().map(|r| r.into()).collect(),
+            })
+        }).await
+    }
+
+    async fn auto_merge(&self, req: Request<AutoMergeRequest>) -> TonicResult<AutoMergeResponse> {
+        self.process_merge_service_request(req, |self_clone, req, m_dao, m_ds, s_dao, s_ds| {
+            let sqlite_dao_dir = prepare_database_dir(&req.new_database_dir)?;
+            let policy = MergePolicy::try_from(req.policy)?.into();
+            let alignment = alignment(req.fuzzy_timestamp_tolerance_sec);
+            let (dao, ds, report, summary) =
+                auto_merge_datasets(&sqlite_dao_dir, m_dao, &m_ds, s_dao, &s_ds, policy, alignment)?;
+            Ok((self_clone, dao, ds, report, summary))
+        }, |(self_clone, dao, ds, report, summary)| {
+            Ok(AutoMergeResponse {
+                new_file: self_clone.register_merged(dao)?,
+                new_ds_uuid: ds.uuid,
+                summary: summary.into(),
+                report: report.into(),
+            })
+        }).await
+    }
+
+    async fn create_merge_session(&self, req: Request<CreateMergeSessionRequest>) -> TonicResult<MergeSession> {
+        self.process_merge_service_request(req, |_, req, m_dao, m_ds, s_dao, s_ds| {
+            MergeSessionStore::new(m_dao.storage_path())
+                .create(&req.master_dao_key, m_dao, &m_ds.uuid, &req.slave_dao_key, s_dao, &s_ds.uuid, req.analysis)
+        }, Ok).await
+    }
+
+    async fn update_merge_session(&self, req: Request<UpdateMergeSessionRequest>) -> TonicResult<MergeSession> {
+        let key = req.get_ref().master_dao_key.clone();
+        self.process_request_with_dao(req, key, |_, req, m_dao| {
+            MergeSessionStore::new(m_dao.storage_path())
+                .update(&req.session_id, req.analysis, req.user_merges, req.chat_merges)
+        }).await
+    }
+
+    async fn list_merge_sessions(&self, req: Request<ListMergeSessionsRequest>) -> TonicResult<ListMergeSessionsResponse> {
+        let key = req.get_ref().master_dao_key.clone();
+        self.process_request_with_dao(req, key, |_, _, m_dao| {
+            Ok(ListMergeSessionsResponse { sessions: MergeSessionStore::new(m_dao.storage_path()).list()? })
+        }).await
+    }
+
+    async fn resume_merge_session(&self, req: Request<MergeSessionRequest>) -> TonicResult<MergeSession> {
+        self.process_request_blocking(req, |self_clone, req| {
+            self_clone.with_merge_session(&req.master_dao_key, &req.session_id, |_, session, _, _, _, _| Ok(session))
+        }).await
+    }
+
+    async fn execute_merge_session(&self, req: Request<ExecuteMergeSessionRequest>) -> TonicResult<MergeResponse> {
+        self.process_request_blocking(req, |self_clone, req| {
+            let (dao, ds, report) = self_clone.with_merge_session(
+                &req.master_dao_key, &req.session_id,
+                |store, session, m_dao, m_ds, s_dao, s_ds| {
+                    let sqlite_dao_dir = prepare_database_dir(&req.new_database_dir)?;
+                    let (user_merges, chat_merges, slave_time_shifts) =
+                        merge_decisions(&session.user_merges, &session.chat_merges)?;
+                    let merged = merger::merge_datasets(&sqlite_dao_dir,
+                                                        m_dao, &m_ds,
+                                                        s_dao, &s_ds,
+                                                        user_merges, chat_merges,
+                                                        &slave_time_shifts)?;
+                    store.delete(&session.id)?;
+                    Ok(merged)
+                })?;
             Ok(MergeResponse {
-                new_file: LoadedFile { key, name, storage_path },
-                new_ds_uuid: ds.uuid.clone(),
+                new_file: self_clone.register_merged(dao)?,
+                new_ds_uuid: ds.uuid,
+                report: Some(report.into()),
             })
         }).await
     }
 }
 
+impl From<MergePolicy> for AutoMergePolicy {
+    fn from(value: MergePolicy) -> Self {
+        match value {
+            MergePolicy::PreferMaster => AutoMergePolicy::PreferMaster,
+            MergePolicy::PreferSlave => AutoMergePolicy::PreferSlave,
+            MergePolicy::PreferNewer => AutoMergePolicy::PreferNewer,
+            MergePolicy::KeepBoth => AutoMergePolicy::KeepBoth,
+            MergePolicy::AddOnly => AutoMergePolicy::AddOnly,
+        }
+    }
+}
+
+impl From<AutoMergePolicy> for MergePolicy {
+    fn from(value: AutoMergePolicy) -> Self {
+        match value {
+            AutoMergePolicy::PreferMaster => MergePolicy::PreferMaster,
+            AutoMergePolicy::PreferSlave => MergePolicy::PreferSlave,
+            AutoMergePolicy::PreferNewer => MergePolicy::PreferNewer,
+            AutoMergePolicy::KeepBoth => MergePolicy::KeepBoth,
+            AutoMergePolicy::AddOnly => MergePolicy::AddOnly,
+        }
+    }
+}
+
+impl From<AutoMergeSummary> for AutoMergeSummaryPb {
+    fn from(value: AutoMergeSummary) -> Self {
+        AutoMergeSummaryPb {
+            effective_policy: MergePolicy::from(value.effective_policy) as i32,
+            users_retained: value.users_retained as i32,
+            users_added: value.users_added as i32,
+            users_kept: value.users_kept as i32,
+            users_replaced: value.users_replaced as i32,
+            chats_retained: value.chats_retained as i32,
+            chats_added: value.chats_added as i32,
+            merged_chats: value.merged_chats.into_iter().map(|cs| ChatMergeSummaryPb {
+                chat_id: *cs.chat_id,
+                chat_name: cs.chat_name,
+                matched_msgs: cs.matched_msgs as i32,
+                retained_msgs: cs.retained_msgs as i32,
+                added_msgs: cs.added_msgs as i32,
+                conflicts: cs.conflicts as i32,
+                conflicting_master_msgs: cs.conflicting_master_msgs as i32,
+                conflicting_slave_msgs: cs.conflicting_slave_msgs as i32,
+            }).collect_vec(),
+        }
+    }
+}
+
+impl From<MergeReport> for MergeReportPb {
+    fn from(value: MergeReport) -> Self {
+        let range_pb = |r: MessageRangeReport| MessageRangeReportPb {
+            msg_count: r.msg_count as i32,
+            first_source_id: r.first_source_id,
+            first_timestamp: r.first_timestamp,
+            last_source_id: r.last_source_id,
+            last_timestamp: r.last_timestamp,
+        };
+        MergeReportPb {
+            master_ds_uuid: PbUuid { value: value.master_ds_uuid },
+            slave_ds_uuid: PbUuid { value: value.slave_ds_uuid },
+            new_ds_uuid: PbUuid { value: value.new_ds_uuid },
+            in_place: value.in_place,
+            merged_at: value.merged_at,
+            users: value.users.into_iter().map(|u| UserMergeReportPb {
+                user_id: u.user_id,
+                name: u.name,
+                tpe: match u.tpe {
+                    UserMergeKind::Add => UserMergeType::Add,
+                    UserMergeKind::Replace => UserMergeType::Replace,
+                } as i32,
+            }).collect_vec(),
+            chats: value.chats.into_iter().map(|c| ChatMergeReportPb {
+                chat_id: c.chat_id,
+                chat_name: c.chat_name,
+                tpe: match c.tpe {
+                    ChatMergeKind::Retain => ChatMergeType::Retain,
+                    ChatMergeKind::Add => ChatMergeType::Add,
+                    ChatMergeKind::DontAdd => ChatMergeType::DontAdd,
+                    ChatMergeKind::Merge => ChatMergeType::Merge,
+                    ChatMergeKind::DontMerge => ChatMergeType::DontMerge,
+                } as i32,
+                matched_msgs: c.matched_msgs as i32,
+                retained_msgs: c.retained_msgs as i32,
+                added_msgs: c.added_msgs as i32,
+                replaced_msgs: c.replaced_msgs as i32,
+                skipped_msgs: c.skipped_msgs as i32,
+                sections: c.sections.into_iter().map(|s| MessagesSectionReportPb {
+                    tpe: match s.tpe {
+                        MessagesMergeKind::Match => MessageMergeType::Match,
+                        MessagesMergeKind::Retain => MessageMergeType::Retain,
+                        MessagesMergeKind::Add => MessageMergeType::Add,
+                        MessagesMergeKind::DontAdd => MessageMergeType::DontAdd,
+                        MessagesMergeKind::Replace => MessageMergeType::Replace,
+                        MessagesMergeKind::DontReplace => MessageMergeType::DontReplace,
+                    } as i32,
+                    master_range: s.master_range.map(range_pb),
+                    slave_range: s.slave_range.map(range_pb),
+                }).collect_vec(),
+                copied_files: c.copied_files,
+                missing_files: c.missing_files.into_iter()
+                    .map(|f| MissingFilePb { in_master: f.in_master, path: f.path })
+                    .collect_vec(),
+            }).collect_vec(),
+        }
+    }
+}
+
+/// User and chat merge decisions, along with time shifts of slave chats.
+type MergeDecisions = (Vec<UserMergeDecision>, Vec<ChatMergeDecision>, HashMap<ChatId, i64>);
+
+/// Converts merge decisions received from the client.
+fn merge_decisions(user_merges: &[UserMerge], chat_merges: &[ChatMerge]) -> Result<MergeDecisions> {
+    let slave_time_shifts = chat_merges.iter()
+        .filter_map(|cm| cm.slave_time_shift_sec.map(|shift| (ChatId(cm.chat_id), shift)))
+        .collect();
+    let user_merges = user_merges.iter().map(|um|
+        ok(match UserMergeType::try_from(um.tpe)? {
+            UserMergeType::Retain => UserMergeDecision::Retain(UserId(um.user_id)),
+            UserMergeType::Add => UserMergeDecision::Add(UserId(um.user_id)),
+            UserMergeType::DontAdd => UserMergeDecision::DontAdd(UserId(um.user_id)),
+            UserMergeType::Replace => UserMergeDecision::Replace(UserId(um.user_id)),
+            UserMergeType::MatchOrDontReplace => UserMergeDecision::MatchOrDontReplace(UserId(um.user_id)),
+        })
+    ).try_collect()?;
+    let chat_merges = chat_merges.iter().map(|cm|
+        ok(match ChatMergeType::try_from(cm.tpe)? {
+            ChatMergeType::Retain => ChatMergeDecision::Retain { master_chat_id: ChatId(cm.chat_id) },
+            ChatMergeType::DontMerge => ChatMergeDecision::DontMerge { chat_id: ChatId(cm.chat_id) },
+            ChatMergeType::Add => ChatMergeDecision::Add { slave_chat_id: ChatId(cm.chat_id) },
+            ChatMergeType::DontAdd => ChatMergeDecision::DontAdd { slave_chat_id: ChatId(cm.chat_id) },
+            ChatMergeType::Merge => {
+                use MessageMergeType as MMT;
+                use MessagesMergeDecision as MMD;
+                let message_merges = cm.message_merges.iter().map(|mm| {
+                    let range = &mm.range;
+                    ok(match MessageMergeType::try_from(mm.tpe)? {
+                        MMT::Match => MMD::Match(MergeAnalysisSectionMatch {
+                            first_master_msg_id: MasterInternalId(range.first_master_msg_id),
+                            last_master_msg_id: MasterInternalId(range.last_master_msg_id),
+                            first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
+                            last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
+                        }),
+                        MMT::Retain => MMD::Retain(MergeAnalysisSectionRetention {
+                            first_master_msg_id: MasterInternalId(range.first_master_msg_id),
+                            last_master_msg_id: MasterInternalId(range.last_master_msg_id),
+                        }),
+                        MMT::Add => MMD::Add(MergeAnalysisSectionAddition {
+                            first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
+                            last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
+                        }),
+                        MMT::DontAdd => MMD::DontAdd(MergeAnalysisSectionAddition {
+                            first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
+                            last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
+                        }),
+                        MMT::Replace => MMD::Replace(MergeAnalysisSectionConflict {
+                            first_master_msg_id: MasterInternalId(range.first_master_msg_id),
+                            last_master_msg_id: MasterInternalId(range.last_master_msg_id),
+                            first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
+                            last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
+                        }),
+                        MMT::DontReplace => MMD::DontReplace(MergeAnalysisSectionConflict {
+                            first_master_msg_id: MasterInternalId(range.first_master_msg_id),
+                            last_master_msg_id: MasterInternalId(range.last_master_msg_id),
+                            first_slave_msg_id: SlaveInternalId(range.first_slave_msg_id),
+                            last_slave_msg_id: SlaveInternalId(range.last_slave_msg_id),
+                        }),
+                    })
+                }).try_collect()?;
+                ChatMergeDecision::Merge { chat_id: ChatId(cm.chat_id), message_merges }
+            }
+        })
+    ).try_collect()?;
+    Ok((user_merges, chat_merges, slave_time_shifts))
+}
+
+fn time_shift_pb(shift: TimeShift, applied: bool) -> TimeShiftPb {
+    TimeShiftPb {
+        slave_ahead_by_sec: shift.slave_ahead_by_sec,
+        shifted_msgs: shift.shifted_msgs as i32,
+        paired_msgs: shift.paired_msgs as i32,
+        applied,
+    }
+}
+
+fn alignment(fuzzy_timestamp_tolerance_sec: Option<i64>) -> MessageAlignment {
+    match fuzzy_timestamp_tolerance_sec {
+        Some(timestamp_tolerance_sec) => MessageAlignment::Fuzzy { timestamp_tolerance_sec },
+        None => MessageAlignment::Strict,
+    }
+}
+
+/// Resolves a directory for the new database, creating it if necessary.
+fn prepare_database_dir(dir: &str) -> Result<PathBuf> {
+    let sqlite_dao_dir = Path::new(dir).parse_dot()?.to_path_buf();
+    if !sqlite_dao_dir.exists() {
+        if sqlite_dao_dir.parent().is_none_or(|p| p.exists()) {
+            fs::create_dir(&sqlite_dao_dir)?;
+        } else {
+            bail!("Parent directory of {} does not exist!", sqlite_dao_dir.display());
+        }
+    }
+    Ok(sqlite_dao_dir)
+}
+
+impl ChatHistoryManagerServer {
+    /// Loads merge session stored alongside the master database, and passes it along with both of its DAOs
+    /// and datasets, which should still be loaded and unchanged since the session was created.
+    fn with_merge_session<R>(
+        &self,
+        master_dao_key: &str,
+        session_id: &str,
+        f: impl FnOnce(&MergeSessionStore, MergeSession,
+                       &dyn ChatHistoryDao, Dataset,
+                       &dyn ChatHistoryDao, Dataset) -> Result<R>,
+    ) -> Result<R> {
+        let loaded_daos = read_or_status(&self.loaded_daos)?;
+        let get_dao = |key: &str| {
+            let dao = loaded_daos.get(key).with_context(|| format!("Database with key {key} is not loaded!"))?;
+            ok(read_or_status(dao)?)
+        };
+        let get_ds = |dao: &dyn ChatHistoryDao, ds_uuid: &PbUuid| {
+            dao.datasets()?.into_iter().find(|ds| &ds.uuid == ds_uuid)
+                .with_context(|| format!("Dataset {} not found in {}!", ds_uuid.value, dao.name()))
+        };
+
+        let m_dao = get_dao(master_dao_key)?;
+        let store = MergeSessionStore::new(m_dao.storage_path());
+        let session = store.load(session_id)?;
+        let s_dao = get_dao(&session.slave_dao_key)?;
+        let m_ds = get_ds(&**m_dao, &session.master_ds_uuid)?;
+        let s_ds = get_ds(&**s_dao, &session.slave_ds_uuid)?;
+        ensure_unchanged(&session, &**m_dao, &**s_dao)?;
+        f(&store, session, &**m_dao, m_ds, &**s_dao, s_ds)
+    }
+
+    /// Merges slave dataset right into the master database, which is locked for writing in the meantime.
+    fn merge_in_place(&self, req: &MergeRequest) -> Result<MergeResponse> {
+        ensure!(req.master_dao_key != req.slave_dao_key, "Cannot merge datasets of the same database in place!");
+        let loaded_daos = read_or_status(&self.loaded_daos)?;
+        let m_dao = loaded_daos.get(&req.master_dao_key).context("Master DAO not found")?;
+        let s_dao = loaded_daos.get(&req.slave_dao_key).context("Slave DAO not found")?;
+
+        let mut m_dao = write_or_status(m_dao)?;
+        let s_dao = read_or_status(s_dao)?;
+
+        let m_ds = m_dao.datasets()?.into_iter().find(|ds| ds.uuid == req.master_ds_uuid)
+            .context("Master dataset not found!")?;
+        let s_ds = s_dao.datasets()?.into_iter().find(|ds| ds.uuid == req.slave_ds_uuid)
+            .context("Slave dataset not found!")?;
+
+        let (user_merges, chat_merges, slave_time_shifts) = merge_decisions(&req.user_merges, &req.chat_merges)?;
+        let report = merger::merge_in_place(m_dao.as_mutable()?, &m_ds,
+                                            &**s_dao, &s_ds,
+                                            user_merges, chat_merges,
+                                            &slave_time_shifts)?;
+
+        let new_file = LoadedFile {
+            key: req.master_dao_key.clone(),
+            name: m_dao.name().to_owned(),
+            storage_path: path_to_str(m_dao.storage_path())?.to_owned(),
+        };
+        Ok(MergeResponse { new_file, new_ds_uuid: m_ds.uuid, report: Some(report.into()) })
+    }
+
+    /// Makes the merge result available as a loaded file.
+    fn register_merged(&self, dao: SqliteDao) -> Result<LoadedFile> {
+        let key = path_to_str(&dao.db_file)?.to_owned();
+        let name = dao.name().to_owned();
+        let storage_path = path_to_str(dao.storage_path())?.to_owned();
+        write_or_status(&self.loaded_daos)?.insert(key.clone(), DaoRwLock::new(Box::new(dao)));
+        Ok(LoadedFile { key, name, storage_path })
+    }
+}
+
 trait MergeServiceHelper {
     async fn process_merge_service_request<Q, R1, R2, Process, Finalize>(&self,
                                                                          req: Request<Q>,
@@ -230,3 +572,5 @@ macro_rules! merge_req_impl {
 }
 merge_req_impl!(AnalyzeRequest);
 merge_req_impl!(MergeRequest);
+merge_req_impl!(AutoMergeRequest);
+merge_req_impl!(CreateMergeSessionRequest);
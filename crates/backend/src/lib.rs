@@ -7,11 +7,17 @@ use crate::loader::Loader;
 mod protobuf;
 mod loader;
 mod merge;
+mod export;
 mod grpc;
 mod utils;
 
 pub use grpc::client::debug_request_myself;
 pub use grpc::server::start_user_input_server;
+pub use export::html::{HtmlExportSummary, DEFAULT_MESSAGES_PER_PAGE};
+pub use merge::analyzer::{MergeAnalysisSection, MessageAlignment};
+pub use merge::auto_merge::{AnalyzedChat, AutoMergePolicy, AutoMergeSummary, ChatMergeSummary};
+pub use merge::multi_merge::SnapshotToMerge;
+pub use merge::report::MergeReport;
 
 pub mod prelude {
     pub use std::collections::{HashMap, HashSet};
@@ -27,15 +33,96 @@ pub mod prelude {
 // Entry points
 //
 
+thread_local! {
+    static LOADER: Loader = Loader::new(&ReqwestHttpClient);
+}
+
 pub fn parse_file(path: &str, feedback_client: &dyn FeedbackClientSync) -> Result<Box<InMemoryDao>> {
-    thread_local! {
-        static LOADER: Loader = Loader::new(&ReqwestHttpClient);
-    }
     LOADER.with(|loader| {
         loader.parse(Path::new(path), feedback_client)
     })
 }
 
+/// Opens an internal Sqlite DB, or parses a foreign history otherwise.
+pub fn load_file(path: &Path, feedback_client: &dyn FeedbackClientSync) -> Result<Box<dyn ChatHistoryDao>> {
+    LOADER.with(|loader| {
+        loader.load(path, feedback_client)
+    })
+}
+
+/// Imports a history into a database, returning imported datasets.
+/// Internal Sqlite DB is copied over, while a foreign history is written into the database while it's being parsed,
+/// without ever being kept in memory in full.
+pub fn import_file(path: &Path, feedback_client: &dyn FeedbackClientSync, dst_dao: &mut SqliteDao) -> Result<Vec<Dataset>> {
+    let old_ds_uuids = dst_dao.datasets()?.into_iter().map(|ds| ds.uuid).collect_vec();
+    let new_datasets = |dst_dao: &SqliteDao| -> Result<Vec<Dataset>> {
+        Ok(dst_dao.datasets()?.into_iter().filter(|ds| !old_ds_uuids.contains(&ds.uuid)).collect_vec())
+    };
+    if path_file_name(path)? == SqliteDao::FILENAME {
+        // Cleans up partially copied datasets by itself
+        let src_dao = SqliteDao::load(path)?;
+        let src_ds_uuids = src_dao.datasets()?.into_iter().map(|ds| ds.uuid).collect_vec();
+        dst_dao.copy_datasets_from(&src_dao, &src_ds_uuids)?;
+        return new_datasets(dst_dao);
+    }
+    let res = LOADER.with(|loader| {
+        loader.parse_into(path, feedback_client, &mut dst_dao.sink()?)
+    });
+    if let Err(e) = res {
+        // Don't leave partially imported datasets behind, cleanup failure shouldn't hide the original error
+        let cleanup_res = new_datasets(dst_dao).and_then(|datasets| {
+            datasets.into_iter().try_for_each(|ds| dst_dao.delete_dataset(ds.uuid))
+        });
+        if let Err(cleanup_e) = cleanup_res {
+            log::error!("Failed to clean up partially imported datasets: {cleanup_e:?}");
+        }
+        return Err(e);
+    }
+    new_datasets(dst_dao)
+}
+
+/// Analyzes all chats present in both datasets.
+pub fn analyze_datasets(master_dao: &dyn ChatHistoryDao,
+                        master_ds: &Dataset,
+                        slave_dao: &dyn ChatHistoryDao,
+                        slave_ds: &Dataset,
+                        force_conflicts: bool,
+                        alignment: MessageAlignment) -> Result<Vec<AnalyzedChat>> {
+    merge::auto_merge::analyze_common_chats(master_dao, master_ds, slave_dao, slave_ds, force_conflicts, alignment,
+                                            &HashMap::new())
+}
+
+/// Merges two datasets into a new database in the given directory, without asking the user for any decisions.
+/// Returns merge report (also saved next to the new database) along with a summary of decisions made.
+pub fn auto_merge_datasets(sqlite_dao_dir: &Path,
+                           master_dao: &dyn ChatHistoryDao,
+                           master_ds: &Dataset,
+                           slave_dao: &dyn ChatHistoryDao,
+                           slave_ds: &Dataset,
+                           policy: AutoMergePolicy,
+                           alignment: MessageAlignment) -> Result<(SqliteDao, Dataset, MergeReport, AutoMergeSummary)> {
+    merge::auto_merge::auto_merge_datasets(sqlite_dao_dir, master_dao, master_ds, slave_dao, slave_ds, policy, alignment)
+}
+
+/// Merges an ordered list of snapshots (oldest first) into a single dataset of a new database in the given directory,
+/// making decisions according to the policy only. Returns a merge report for every snapshot but the first,
+/// these are also saved next to the new database.
+pub fn merge_many_datasets(sqlite_dao_dir: &Path,
+                           snapshots: &[SnapshotToMerge],
+                           policy: AutoMergePolicy,
+                           alignment: MessageAlignment) -> Result<(SqliteDao, Dataset, Vec<MergeReport>)> {
+    merge::multi_merge::merge_many_datasets(sqlite_dao_dir, snapshots, policy, alignment)
+}
+
+/// Exports a dataset (or a single chat of it) as a static HTML into the given empty directory.
+pub fn export_html(dao: &dyn ChatHistoryDao,
+                   ds_uuid: &PbUuid,
+                   chat_id_option: Option<ChatId>,
+                   out_dir: &Path,
+                   messages_per_page: usize) -> Result<HtmlExportSummary> {
+    export::html::export_html(dao, ds_uuid, chat_id_option, out_dir, messages_per_page)
+}
+
 pub async fn start_server(port: u16, remote_port: u16) -> EmptyRes {
     let loader = Loader::new(&ReqwestHttpClient);
     grpc::server::start_server(port, remote_port, loader).await
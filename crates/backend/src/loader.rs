@@ -12,6 +12,7 @@ impl Loader {
         Loader {
             loaders: vec![
                 Box::new(TelegramDataLoader),
+                Box::new(TelegramHtmlDataLoader),
                 Box::new(TgKeeperDataLoader {
                     config: TgKeeperDataLoaderConfig {
                         load_generic_files: false,
@@ -19,8 +20,11 @@ impl Loader {
                     }
                 }),
                 Box::new(WhatsAppAndroidDataLoader),
+                Box::new(WhatsAppIosDataLoader),
                 Box::new(WhatsAppTextDataLoader),
                 Box::new(SignalDataLoader),
+                Box::new(SignalAndroidDataLoader),
+                Box::new(DiscordDataLoader),
                 Box::new(TinderAndroidDataLoader { http_client }),
                 Box::new(BadooAndroidDataLoader { http_client }),
                 Box::new(MailRuAgentDataLoader),
@@ -40,16 +44,25 @@ impl Loader {
 
     /// Parses a history in a foreign format
     pub fn parse(&self, path: &Path, feedback_client: &dyn FeedbackClientSync) -> Result<Box<InMemoryDao>> {
+        self.find_loader(path)?.load(feedback_client, path)
+    }
+
+    /// Parses a history in a foreign format, pushing it into the sink while it's being parsed
+    pub fn parse_into(&self, path: &Path, feedback_client: &dyn FeedbackClientSync, sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        self.find_loader(path)?.load_into(feedback_client, path, sink)
+    }
+
+    fn find_loader(&self, path: &Path) -> Result<&dyn DataLoader> {
         ensure!(path.exists(), "File not found");
-        let (named_errors, loads): (Vec<_>, Vec<_>) =
+        let (named_errors, loaders): (Vec<_>, Vec<_>) =
             self.loaders.iter()
                 .partition_map(|loader| match loader.looks_about_right(path) {
-                    Ok(()) => Either::Right(|| loader.load(feedback_client, path)),
+                    Ok(()) => Either::Right(loader.as_ref()),
                     Err(why) => Either::Left((loader.name(), why)),
                 });
-        match loads.first() {
-            Some(load) =>
-                load(),
+        match loaders.first() {
+            Some(loader) =>
+                Ok(*loader),
             None => {
                 // Report why everyone rejected the file.
                 err!("No loader accepted the file:\n{}",
@@ -1,2 +1,6 @@
 pub mod analyzer;
+pub mod auto_merge;
 pub mod merger;
+pub mod multi_merge;
+pub mod report;
+pub mod session;
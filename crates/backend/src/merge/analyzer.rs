@@ -6,12 +6,51 @@ use crate::prelude::*;
 #[path = "analyzer_tests.rs"]
 mod tests;
 
+mod fuzzy;
+mod time_shift;
+
+#[derive(Clone)]
 pub struct DatasetDiffAnalyzer<'a> {
     m_dao: &'a dyn ChatHistoryDao,
     m_root: DatasetRoot,
 
     s_dao: &'a dyn ChatHistoryDao,
     s_root: DatasetRoot,
+
+    alignment: MessageAlignment,
+
+    /// Slave timestamps are shifted back by this much in memory
+    slave_time_shift_sec: i64,
+}
+
+/// How master and slave messages are aligned against each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageAlignment {
+    /// Messages are walked in lockstep, relying on exact timestamps and (if present) source IDs
+    #[default]
+    Strict,
+    /// For chats whose messages have no source IDs, messages are aligned using a diff over their senders,
+    /// normalized texts and timestamps, the latter allowed to differ by up to the given number of seconds.
+    /// Chats with source IDs are still aligned strictly.
+    Fuzzy { timestamp_tolerance_sec: i64 },
+}
+
+/// Offset of slave message timestamps relative to their master counterparts, consistent across a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeShift {
+    /// Negative if slave is behind master
+    pub slave_ahead_by_sec: i64,
+    /// How many of the paired messages are offset by exactly this much
+    pub shifted_msgs: usize,
+    /// How many master and slave messages could be paired against each other
+    pub paired_msgs: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatAnalysisOutcome {
+    Analyzed(Vec<MergeAnalysisSection>),
+    /// Slave messages are shifted in time relative to master ones, so analysis makes no sense until that's fixed
+    TimeShifted(TimeShift),
 }
 
 impl<'a> DatasetDiffAnalyzer<'a> {
@@ -23,10 +62,27 @@ impl<'a> DatasetDiffAnalyzer<'a> {
     ) -> Result<Self> {
         let m_root = m_dao.dataset_root(&m_ds.uuid)?;
         let s_root = s_dao.dataset_root(&s_ds.uuid)?;
-        Ok(DatasetDiffAnalyzer { m_dao, m_root, s_dao, s_root })
+        Ok(DatasetDiffAnalyzer {
+            m_dao,
+            m_root,
+            s_dao,
+            s_root,
+            alignment: MessageAlignment::Strict,
+            slave_time_shift_sec: 0,
+        })
     }
 
-    /// Note that we can only detect conflicts if data source supports source IDs.
+    pub fn with_alignment(self, alignment: MessageAlignment) -> Self {
+        DatasetDiffAnalyzer { alignment, ..self }
+    }
+
+    /// Compensate for slave being ahead of master by given number of seconds (see `TimeShift`).
+    /// Slave messages are shifted in memory only, merger has to be told to do the same.
+    pub fn with_slave_time_shift(self, slave_ahead_by_sec: i64) -> Self {
+        DatasetDiffAnalyzer { slave_time_shift_sec: slave_ahead_by_sec, ..self }
+    }
+
+    /// Note that we can only detect conflicts if data source supports source IDs, or if fuzzy alignment is used.
     /// If `force_conflicts` is set, everything starting at first mismatch and ending just before trailing match
     /// (if any) will be merged into a single conflict if possible
     pub fn analyze(
@@ -36,23 +92,75 @@ impl<'a> DatasetDiffAnalyzer<'a> {
         title: &str,
         force_conflicts: bool,
     ) -> Result<Vec<MergeAnalysisSection>> {
+        match self.analyze_detecting_time_shift(master_cwd, slave_cwd, title, force_conflicts)? {
+            ChatAnalysisOutcome::Analyzed(analysis) => Ok(analysis),
+            ChatAnalysisOutcome::TimeShifted(shift) => {
+                let ahead_behind = if shift.slave_ahead_by_sec > 0 { "ahead of" } else { "behind" };
+                let diff_sec = shift.slave_ahead_by_sec.abs();
+                bail!("Time shift detected between datasets! Slave is {} master by {} sec ({} hrs) in {} of {} messages",
+                    ahead_behind, diff_sec, diff_sec / 3600, shift.shifted_msgs, shift.paired_msgs);
+            }
+        }
+    }
+
+    /// Same as `analyze`, but a consistent time shift between slave and master is reported rather than failing
+    /// the analysis, so that it could be re-run using `with_slave_time_shift`.
+    pub fn analyze_detecting_time_shift(
+        &self,
+        master_cwd: &ChatWithDetails,
+        slave_cwd: &ChatWithDetails,
+        title: &str,
+        force_conflicts: bool,
+    ) -> Result<ChatAnalysisOutcome> {
         measure(|| {
-            let mut analysis = self.analyze_inner(
-                AnalysisContext {
-                    mm_stream: messages_stream(self.m_dao, &master_cwd.chat, MasterMessage, |m| m.0.internal_id())?,
-                    m_cwd: master_cwd,
-                    sm_stream: messages_stream(self.s_dao, &slave_cwd.chat, SlaveMessage, |m| m.0.internal_id())?,
-                    s_cwd: slave_cwd,
+            let has_source_ids = self.has_source_ids(master_cwd, slave_cwd)?;
+            let outcome = match self.alignment {
+                MessageAlignment::Fuzzy { timestamp_tolerance_sec } if !has_source_ids =>
+                    ChatAnalysisOutcome::Analyzed(self.analyze_fuzzy(master_cwd, slave_cwd, timestamp_tolerance_sec)?),
+                _ => self.analyze_inner(
+                    AnalysisContext {
+                        mm_stream: messages_stream(self.m_dao, &master_cwd.chat, 0, MasterMessage, |m| m.0.internal_id())?,
+                        m_cwd: master_cwd,
+                        sm_stream: messages_stream(self.s_dao, &slave_cwd.chat, self.slave_time_shift_sec,
+                                                   SlaveMessage, |m| m.0.internal_id())?,
+                        s_cwd: slave_cwd,
+                    }
+                )?,
+            };
+            Ok(match outcome {
+                ChatAnalysisOutcome::Analyzed(analysis)
+                if !has_source_ids && is_disjoint(&analysis) => {
+                    // Without source IDs, time shift manifests itself as chats not overlapping at all
+                    match self.detect_time_shift(master_cwd, slave_cwd)? {
+                        Some(shift) => ChatAnalysisOutcome::TimeShifted(shift),
+                        None => ChatAnalysisOutcome::Analyzed(analysis),
+                    }
                 }
-            )?;
-            if force_conflicts {
-                analysis = enforce_conflicts(analysis)?;
-            }
-            Ok(analysis)
+                ChatAnalysisOutcome::Analyzed(analysis) if force_conflicts =>
+                    ChatAnalysisOutcome::Analyzed(enforce_conflicts(analysis)?),
+                outcome => outcome,
+            })
         }, |_, t| log::info!("Chat {title} analyzed in {t} ms"))
     }
 
-    fn analyze_inner(&self, mut cx: AnalysisContext) -> Result<Vec<MergeAnalysisSection>> {
+    /// Whether first messages of both chats have source IDs, which we assume to mean the whole source supports them.
+    fn has_source_ids(&self, master_cwd: &ChatWithDetails, slave_cwd: &ChatWithDetails) -> Result<bool> {
+        let has_source_id = |dao: &dyn ChatHistoryDao, chat: &Chat| -> Result<bool> {
+            Ok(dao.first_messages(chat, 1)?.first().is_none_or(|m| m.source_id_option.is_some()))
+        };
+        Ok(has_source_id(self.m_dao, &master_cwd.chat)? && has_source_id(self.s_dao, &slave_cwd.chat)?)
+    }
+
+    /// Loads all slave chat messages into memory, compensating for time shift
+    fn all_slave_messages(&self, slave_cwd: &ChatWithDetails) -> Result<Vec<Message>> {
+        let mut sms = self.s_dao.first_messages(&slave_cwd.chat, usize::MAX)?;
+        for sm in sms.iter_mut() {
+            sm.timestamp -= self.slave_time_shift_sec;
+        }
+        Ok(sms)
+    }
+
+    fn analyze_inner(&self, mut cx: AnalysisContext) -> Result<ChatAnalysisOutcome> {
         use AnalysisState::*;
         use InProgressState::*;
 
@@ -103,6 +211,10 @@ impl<'a> DatasetDiffAnalyzer<'a> {
                             !compare(&mm, sm)?.is_conflict()
                         };
                         if is_timestamp_diff {
+                            if let Some(shift) = self.detect_time_shift(cx.m_cwd, cx.s_cwd)? {
+                                return Ok(ChatAnalysisOutcome::TimeShifted(shift));
+                            }
+
                             let (ahead_behind, diff_sec) = {
                                 let ts_diff = sm.timestamp - mm.timestamp;
                                 if ts_diff > 0 {
@@ -206,10 +318,18 @@ impl<'a> DatasetDiffAnalyzer<'a> {
             }
         };
 
-        Ok(acc)
+        Ok(ChatAnalysisOutcome::Analyzed(acc))
     }
 }
 
+/// Whether both master and slave have messages, but none of them match or conflict
+fn is_disjoint(analysis: &[MergeAnalysisSection]) -> bool {
+    use MergeAnalysisSection::*;
+    analysis.iter().any(|a| matches!(a, Retention(_))) &&
+        analysis.iter().any(|a| matches!(a, Addition(_))) &&
+        analysis.iter().all(|a| matches!(a, Retention(_) | Addition(_)))
+}
+
 /// Everything starting at first mismatch and ending just before trailing match (if any) will be merged into
 /// a single conflict if possible
 fn enforce_conflicts(analysis: Vec<MergeAnalysisSection>) -> Result<Vec<MergeAnalysisSection>> {
@@ -417,28 +537,32 @@ impl AnalysisContext<'_> {
 
 const BATCH_SIZE: usize = 1000;
 
+/// Timestamps of streamed messages are shifted back by `time_shift_sec`
 fn messages_stream<'a, T: WithTypedId>(
     dao: &'a dyn ChatHistoryDao,
     chat: &'a Chat,
+    time_shift_sec: i64,
     wrap: fn(Message) -> T,
     unwrap_id: fn(&T) -> MessageInternalId,
 ) -> Result<BatchedMessageIterator<'a, T>> {
     let mut res = BatchedMessageIterator {
         dao,
         chat,
+        time_shift_sec,
         wrap,
         unwrap_id,
         saved_batch: dao.first_messages(chat, BATCH_SIZE)?.into_iter(),
         next_option: None,
         last_id_option: None,
     };
-    res.next_option = res.saved_batch.next().map(res.wrap);
+    res.next_option = res.saved_batch.next().map(|m| res.shift_and_wrap(m));
     Ok(res)
 }
 
 struct BatchedMessageIterator<'a, T: WithTypedId> {
     dao: &'a dyn ChatHistoryDao,
     chat: &'a Chat,
+    time_shift_sec: i64,
     wrap: fn(Message) -> T,
     unwrap_id: fn(&T) -> MessageInternalId,
     saved_batch: std::vec::IntoIter<Message>,
@@ -450,6 +574,11 @@ impl<T: WithTypedId> BatchedMessageIterator<'_, T> {
     fn peek(&self) -> Option<&T> {
         self.next_option.as_ref()
     }
+
+    fn shift_and_wrap(&self, mut msg: Message) -> T {
+        msg.timestamp -= self.time_shift_sec;
+        (self.wrap)(msg)
+    }
 }
 
 impl<T: WithTypedId> Iterator for BatchedMessageIterator<'_, T> {
@@ -461,7 +590,7 @@ impl<T: WithTypedId> Iterator for BatchedMessageIterator<'_, T> {
             match self.saved_batch.next() {
                 Some(next) => {
                     // Iterator still has elements, cache it and be happy.
-                    self.next_option = Some((self.wrap)(next));
+                    self.next_option = Some(self.shift_and_wrap(next));
                 }
                 None => {
                     // Iterator exhausted, time to preload next batch.
@@ -469,7 +598,7 @@ impl<T: WithTypedId> Iterator for BatchedMessageIterator<'_, T> {
                     match msgs {
                         Ok(msgs) => {
                             self.saved_batch = msgs.into_iter();
-                            self.next_option = self.saved_batch.next().map(self.wrap);
+                            self.next_option = self.saved_batch.next().map(|m| self.shift_and_wrap(m));
                         }
                         Err(e) => return Some(Err(e))
                     }
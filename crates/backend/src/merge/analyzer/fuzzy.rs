@@ -0,0 +1,284 @@
+use std::ops::Range;
+
+use super::*;
+
+#[cfg(test)]
+#[path = "fuzzy_tests.rs"]
+mod tests;
+
+/// Edit distance between master and slave messages of a single segment, beyond which they are considered
+/// too different to be aligned and the whole segment is reported as a conflict.
+/// Keeps memory used by the diff in check, as it grows quadratically with the edit distance.
+const MAX_SEGMENT_EDIT_DISTANCE: isize = 2000;
+
+impl DatasetDiffAnalyzer<'_> {
+    /// Aligns messages using a diff over their senders, normalized texts and timestamps.
+    ///
+    /// Chats are split into segments separated by periods of silence longer than timestamp tolerance
+    /// (aligned messages can't span those), and each segment is diffed separately.
+    /// Aligned messages that aren't actually equal (e.g. their timestamps differ), as well as runs of unaligned
+    /// master and slave messages overlapping in time, become conflicts.
+    ///
+    /// Note that both chats are loaded into memory whole.
+    pub(super) fn analyze_fuzzy(
+        &self,
+        master_cwd: &ChatWithDetails,
+        slave_cwd: &ChatWithDetails,
+        timestamp_tolerance_sec: i64,
+    ) -> Result<Vec<MergeAnalysisSection>> {
+        let mms = self.m_dao.first_messages(&master_cwd.chat, usize::MAX)?;
+        let sms = self.all_slave_messages(slave_cwd)?;
+        let m_keys = mms.iter().map(AlignmentKey::new).collect_vec();
+        let s_keys = sms.iter().map(AlignmentKey::new).collect_vec();
+
+        let is_conflict = |mm: &Message, sm: &Message| -> Result<bool> {
+            Ok(EntityCmpTuple::new(mm, &self.m_root, master_cwd)
+                .compare(&EntityCmpTuple::new(sm, &self.s_root, slave_cwd))?
+                .is_conflict())
+        };
+
+        let mut acc = SectionsAccumulator::default();
+        for (m_range, s_range) in split_into_segments(&mms, &sms, timestamp_tolerance_sec) {
+            let (m_seg, s_seg) = (&mms[m_range.clone()], &sms[s_range.clone()]);
+            let (m_seg_keys, s_seg_keys) = (&m_keys[m_range], &s_keys[s_range]);
+            let ops_option = diff(m_seg.len(), s_seg.len(), |i, j| {
+                m_seg_keys[i].is_aligned_with(&s_seg_keys[j], timestamp_tolerance_sec)
+            });
+            let Some(ops) = ops_option else {
+                log::warn!("Messages starting at master #{} / slave #{} are too different to be aligned",
+                           m_seg.first().map(|m| m.internal_id).unwrap_or(*NO_INTERNAL_ID),
+                           s_seg.first().map(|m| m.internal_id).unwrap_or(*NO_INTERNAL_ID));
+                acc.push_hunk(&m_seg.iter().collect_vec(), &s_seg.iter().collect_vec(), i64::MAX);
+                continue;
+            };
+
+            let mut hunk_mms: Vec<&Message> = vec![];
+            let mut hunk_sms: Vec<&Message> = vec![];
+            for op in ops {
+                match op {
+                    DiffOp::Equal(i, j) => {
+                        acc.push_hunk(&hunk_mms, &hunk_sms, timestamp_tolerance_sec);
+                        hunk_mms.clear();
+                        hunk_sms.clear();
+                        let (mm, sm) = (&m_seg[i], &s_seg[j]);
+                        let kind = if is_conflict(mm, sm)? { SectionKind::Conflict } else { SectionKind::Match };
+                        acc.push(kind, Some(mm), Some(sm));
+                    }
+                    DiffOp::Delete(i) => hunk_mms.push(&m_seg[i]),
+                    DiffOp::Insert(j) => hunk_sms.push(&s_seg[j]),
+                }
+            }
+            acc.push_hunk(&hunk_mms, &hunk_sms, timestamp_tolerance_sec);
+        }
+        Ok(acc.into_sections())
+    }
+}
+
+/// Part of a message used to align it against messages of the other side.
+struct AlignmentKey {
+    from_id: i64,
+    text: String,
+    timestamp: i64,
+}
+
+impl AlignmentKey {
+    fn new(msg: &Message) -> Self {
+        AlignmentKey {
+            from_id: msg.from_id,
+            text: msg.searchable_string.split_whitespace().join(" ").to_lowercase(),
+            timestamp: msg.timestamp,
+        }
+    }
+
+    fn is_aligned_with(&self, other: &AlignmentKey, timestamp_tolerance_sec: i64) -> bool {
+        self.from_id == other.from_id &&
+            (self.timestamp - other.timestamp).abs() <= timestamp_tolerance_sec &&
+            self.text == other.text
+    }
+}
+
+/// Splits both message sequences at the points where neither side has any messages for longer than tolerance.
+fn split_into_segments(mms: &[Message], sms: &[Message], timestamp_tolerance_sec: i64) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut res = vec![];
+    let (mut m_idx, mut s_idx) = (0, 0);
+    let (mut m_start, mut s_start) = (0, 0);
+    let mut max_ts_option: Option<i64> = None;
+    loop {
+        let (next_ts, is_master) = match (mms.get(m_idx), sms.get(s_idx)) {
+            (None, None) => break,
+            (Some(mm), None) => (mm.timestamp, true),
+            (None, Some(sm)) => (sm.timestamp, false),
+            (Some(mm), Some(sm)) if mm.timestamp <= sm.timestamp => (mm.timestamp, true),
+            (Some(_), Some(sm)) => (sm.timestamp, false),
+        };
+        if let Some(max_ts) = max_ts_option && next_ts - max_ts > timestamp_tolerance_sec {
+            res.push((m_start..m_idx, s_start..s_idx));
+            (m_start, s_start) = (m_idx, s_idx);
+        }
+        max_ts_option = Some(max_ts_option.map_or(next_ts, |max_ts| max_ts.max(next_ts)));
+        if is_master { m_idx += 1 } else { s_idx += 1 }
+    }
+    if m_start < mms.len() || s_start < sms.len() {
+        res.push((m_start..mms.len(), s_start..sms.len()));
+    }
+    res
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffOp {
+    Equal(usize, usize),
+    /// Only in master
+    Delete(usize),
+    /// Only in slave
+    Insert(usize),
+}
+
+/// Myers diff, finding the shortest edit script turning master sequence of length `n` into slave sequence
+/// of length `m`. Returns `None` if edit distance exceeds `MAX_SEGMENT_EDIT_DISTANCE`.
+fn diff(n: usize, m: usize, is_equal: impl Fn(usize, usize) -> bool) -> Option<Vec<DiffOp>> {
+    let (n, m) = (n as isize, m as isize);
+    let max = n + m;
+    let offset = max + 1;
+    let mut v = vec![0_isize; (2 * max + 3) as usize];
+    // For every D, furthest reaching X on diagonals -D-1..=D+1 before this D step
+    let mut trace: Vec<Vec<isize>> = vec![];
+    for d in 0..=max {
+        if d > MAX_SEGMENT_EDIT_DISTANCE {
+            return None;
+        }
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = |k: isize| (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y >= 0 && y < m && is_equal(x as usize, y as usize) {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+    unreachable!("Edit distance can't exceed the total length")
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<DiffOp> {
+    let mut res = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            res.push(DiffOp::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            res.push(if x == prev_x { DiffOp::Insert((y - 1) as usize) } else { DiffOp::Delete((x - 1) as usize) });
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    res.reverse();
+    res
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionKind { Match, Retention, Addition, Conflict }
+
+struct SectionInProgress {
+    kind: SectionKind,
+    master_ids: Option<(MasterInternalId, MasterInternalId)>,
+    slave_ids: Option<(SlaveInternalId, SlaveInternalId)>,
+}
+
+/// Collects messages in order, merging consecutive messages of the same kind into a single section.
+#[derive(Default)]
+struct SectionsAccumulator {
+    sections: Vec<SectionInProgress>,
+}
+
+impl SectionsAccumulator {
+    fn push(&mut self, kind: SectionKind, mm: Option<&Message>, sm: Option<&Message>) {
+        if self.sections.last().is_none_or(|s| s.kind != kind) {
+            self.sections.push(SectionInProgress { kind, master_ids: None, slave_ids: None });
+        }
+        let section = self.sections.last_mut().unwrap();
+        if let Some(mm) = mm {
+            let id = MasterInternalId(mm.internal_id);
+            section.master_ids = Some((section.master_ids.map_or(id, |ids| ids.0), id));
+        }
+        if let Some(sm) = sm {
+            let id = SlaveInternalId(sm.internal_id);
+            section.slave_ids = Some((section.slave_ids.map_or(id, |ids| ids.0), id));
+        }
+    }
+
+    /// Adds messages present only on one of the sides.
+    /// If both sides have messages overlapping in time, they're considered a conflict,
+    /// otherwise they're interleaved as retentions and additions according to their timestamps.
+    fn push_hunk(&mut self, mms: &[&Message], sms: &[&Message], timestamp_tolerance_sec: i64) {
+        let ts_range = |msgs: &[&Message]| {
+            msgs.iter().map(|m| m.timestamp).minmax().into_option()
+        };
+        if let (Some((m_min, m_max)), Some((s_min, s_max))) = (ts_range(mms), ts_range(sms)) {
+            let tolerance = timestamp_tolerance_sec;
+            if m_min.saturating_sub(tolerance) <= s_max && s_min.saturating_sub(tolerance) <= m_max {
+                mms.iter().for_each(|mm| self.push(SectionKind::Conflict, Some(mm), None));
+                sms.iter().for_each(|sm| self.push(SectionKind::Conflict, None, Some(sm)));
+                return;
+            }
+        }
+        let (mut mms, mut sms) = (mms.iter().peekable(), sms.iter().peekable());
+        loop {
+            match (mms.peek(), sms.peek()) {
+                (None, None) => break,
+                (Some(mm), Some(sm)) if mm.timestamp <= sm.timestamp =>
+                    self.push(SectionKind::Retention, mms.next().copied(), None),
+                (Some(_), None) =>
+                    self.push(SectionKind::Retention, mms.next().copied(), None),
+                (_, Some(_)) =>
+                    self.push(SectionKind::Addition, None, sms.next().copied()),
+            }
+        }
+    }
+
+    fn into_sections(self) -> Vec<MergeAnalysisSection> {
+        self.sections.into_iter().map(|s| {
+            use MergeAnalysisSection::*;
+            let (first_master_msg_id, last_master_msg_id) = s.master_ids.unzip();
+            let (first_slave_msg_id, last_slave_msg_id) = s.slave_ids.unzip();
+            match s.kind {
+                SectionKind::Match => Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: first_master_msg_id.unwrap(),
+                    last_master_msg_id: last_master_msg_id.unwrap(),
+                    first_slave_msg_id: first_slave_msg_id.unwrap(),
+                    last_slave_msg_id: last_slave_msg_id.unwrap(),
+                }),
+                SectionKind::Retention => Retention(MergeAnalysisSectionRetention {
+                    first_master_msg_id: first_master_msg_id.unwrap(),
+                    last_master_msg_id: last_master_msg_id.unwrap(),
+                }),
+                SectionKind::Addition => Addition(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: first_slave_msg_id.unwrap(),
+                    last_slave_msg_id: last_slave_msg_id.unwrap(),
+                }),
+                SectionKind::Conflict => Conflict(MergeAnalysisSectionConflict {
+                    first_master_msg_id: first_master_msg_id.unwrap(),
+                    last_master_msg_id: last_master_msg_id.unwrap(),
+                    first_slave_msg_id: first_slave_msg_id.unwrap(),
+                    last_slave_msg_id: last_slave_msg_id.unwrap(),
+                }),
+            }
+        }).collect_vec()
+    }
+}
@@ -0,0 +1,116 @@
+#![allow(unused_imports)]
+use super::*;
+
+use crate::prelude::*;
+use crate::utils::test_utils::*;
+
+use pretty_assertions::assert_eq;
+use rand::prelude::*;
+
+#[test]
+fn diff_ops() {
+    let diff_str = |a: &str, b: &str| {
+        let (a, b) = (a.chars().collect_vec(), b.chars().collect_vec());
+        diff(a.len(), b.len(), |i, j| a[i] == b[j]).unwrap()
+    };
+    use DiffOp::*;
+    assert_eq!(diff_str("", ""), vec![]);
+    assert_eq!(diff_str("abc", "abc"), vec![Equal(0, 0), Equal(1, 1), Equal(2, 2)]);
+    assert_eq!(diff_str("abc", ""), vec![Delete(0), Delete(1), Delete(2)]);
+    assert_eq!(diff_str("", "ab"), vec![Insert(0), Insert(1)]);
+    assert_eq!(diff_str("abcd", "acde"), vec![Equal(0, 0), Delete(1), Equal(2, 1), Equal(3, 2), Insert(3)]);
+}
+
+#[test]
+fn segments() {
+    let msgs = |timestamps: &[i64]| timestamps.iter().map(|&timestamp| Message { timestamp, ..Default::default() }).collect_vec();
+    assert_eq!(split_into_segments(&msgs(&[]), &msgs(&[]), 10), vec![]);
+    assert_eq!(split_into_segments(&msgs(&[0, 5, 100]), &msgs(&[3, 200, 205]), 10), vec![
+        (0..2, 0..1),
+        (2..3, 1..1),
+        (3..3, 1..3),
+    ]);
+}
+
+/**
+ * ```text
+ * Master messages - 1  2  3  4   5   6
+ * Slave messages  -       3  4** 5*  6  7  8
+ * ```
+ * (* - different text, ** - timestamp differs within tolerance)
+ */
+#[test]
+fn fuzzy_alignment_without_source_ids() -> EmptyRes {
+    let users = vec![create_user(&ZERO_PB_UUID, 1), create_user(&ZERO_PB_UUID, 2)];
+    let msgs = (1..=8).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let m_msgs = msgs.cloned([1, 2, 3, 4, 5, 6].map(src_id));
+    let mut s_msgs = msgs.cloned([3, 4, 5, 6, 7, 8].map(src_id)).changed(|id| *id == 5);
+    s_msgs[1].timestamp += 30;
+
+    let create = |name: &str, msgs: Vec<Message>| {
+        let cwm = ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 1, "A", vec![1, 2], msgs.len()), messages: msgs };
+        create_dao(name, users.clone(), vec![cwm], |_, m| m.source_id_option = None, rng().random())
+    };
+    let m_dao = create("One", m_msgs);
+    let s_dao = create("Two", s_msgs);
+    let (m_ds, s_ds) = (m_dao.dao.dataset(), s_dao.dao.dataset());
+    let m_cwd = m_dao.dao.chats(&m_ds.uuid)?.remove(0);
+    let s_cwd = s_dao.dao.chats(&s_ds.uuid)?.remove(0);
+
+    let m_id = |idx: i64| MasterInternalId(idx * 100);
+    let s_id = |idx: i64| SlaveInternalId(idx * 100);
+
+    let analyzer = DatasetDiffAnalyzer::create(m_dao.dao.as_ref(), &m_ds, s_dao.dao.as_ref(), &s_ds)?
+        .with_alignment(MessageAlignment::Fuzzy { timestamp_tolerance_sec: 60 });
+    let analysis = analyzer.analyze(&m_cwd, &s_cwd, "", false)?;
+    assert_eq!(analysis, vec![
+        MergeAnalysisSection::Retention(MergeAnalysisSectionRetention {
+            first_master_msg_id: m_id(1),
+            last_master_msg_id: m_id(2),
+        }),
+        MergeAnalysisSection::Match(MergeAnalysisSectionMatch {
+            first_master_msg_id: m_id(3),
+            last_master_msg_id: m_id(3),
+            first_slave_msg_id: s_id(3),
+            last_slave_msg_id: s_id(3),
+        }),
+        MergeAnalysisSection::Conflict(MergeAnalysisSectionConflict {
+            first_master_msg_id: m_id(4),
+            last_master_msg_id: m_id(5),
+            first_slave_msg_id: s_id(4),
+            last_slave_msg_id: s_id(5),
+        }),
+        MergeAnalysisSection::Match(MergeAnalysisSectionMatch {
+            first_master_msg_id: m_id(6),
+            last_master_msg_id: m_id(6),
+            first_slave_msg_id: s_id(6),
+            last_slave_msg_id: s_id(6),
+        }),
+        MergeAnalysisSection::Addition(MergeAnalysisSectionAddition {
+            first_slave_msg_id: s_id(7),
+            last_slave_msg_id: s_id(8),
+        }),
+    ]);
+
+    // With a smaller tolerance, shifted message is no longer aligned
+    let analyzer = analyzer.with_alignment(MessageAlignment::Fuzzy { timestamp_tolerance_sec: 10 });
+    let analysis = analyzer.analyze(&m_cwd, &s_cwd, "", false)?;
+    assert_eq!(analysis[2..5], [
+        MergeAnalysisSection::Retention(MergeAnalysisSectionRetention {
+            first_master_msg_id: m_id(4),
+            last_master_msg_id: m_id(4),
+        }),
+        MergeAnalysisSection::Addition(MergeAnalysisSectionAddition {
+            first_slave_msg_id: s_id(4),
+            last_slave_msg_id: s_id(4),
+        }),
+        MergeAnalysisSection::Conflict(MergeAnalysisSectionConflict {
+            first_master_msg_id: m_id(5),
+            last_master_msg_id: m_id(5),
+            first_slave_msg_id: s_id(5),
+            last_slave_msg_id: s_id(5),
+        }),
+    ]);
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use super::*;
+
+use std::hash::BuildHasher;
+
+/// Minimum number of paired messages for time shift to be considered.
+const MIN_PAIRED_MSGS: usize = 2;
+
+/// How many messages are sampled from either end of a chat.
+const SAMPLE_SIZE: usize = 1000;
+
+impl DatasetDiffAnalyzer<'_> {
+    /// Pairs master and slave messages (by source IDs, or by sender and text unique within a chat)
+    /// and looks for an offset between their timestamps shared by the majority of pairs.
+    ///
+    /// Only whole minute offsets are considered, as this is what timezone and clock mismatches look like.
+    /// To keep memory bounded, only messages at both ends of master chat are paired, slave chat is scrolled through
+    /// to find their counterparts. Should there be not enough pairs (e.g. slave is entirely within master),
+    /// the same is done the other way round.
+    pub fn detect_time_shift(
+        &self,
+        master_cwd: &ChatWithDetails,
+        slave_cwd: &ChatWithDetails,
+    ) -> Result<Option<TimeShift>> {
+        let mut pairs = pair_timestamps(&sample(self.m_dao, &master_cwd.chat)?, self.s_dao, &slave_cwd.chat)?;
+        if pairs.len() < MIN_PAIRED_MSGS {
+            pairs = pair_timestamps(&sample(self.s_dao, &slave_cwd.chat)?, self.m_dao, &master_cwd.chat)?
+                .into_iter()
+                .map(|(s_ts, m_ts)| (m_ts, s_ts))
+                .collect_vec();
+        }
+
+        let offsets = pairs.into_iter()
+            .map(|(m_ts, s_ts)| s_ts - self.slave_time_shift_sec - m_ts)
+            .collect_vec();
+        let paired_msgs = offsets.len();
+        if paired_msgs < MIN_PAIRED_MSGS {
+            return Ok(None);
+        }
+
+        let (slave_ahead_by_sec, shifted_msgs) = offsets.into_iter().counts().into_iter()
+            .max_by_key(|(_, count)| *count)
+            .expect("Offsets are not empty");
+        let is_consistent = slave_ahead_by_sec != 0 &&
+            slave_ahead_by_sec % 60 == 0 &&
+            shifted_msgs * 2 > paired_msgs;
+        Ok(is_consistent.then_some(TimeShift { slave_ahead_by_sec, shifted_msgs, paired_msgs }))
+    }
+}
+
+/// Up to `SAMPLE_SIZE` first and last messages of a chat.
+fn sample(dao: &dyn ChatHistoryDao, chat: &Chat) -> Result<Vec<Message>> {
+    let mut msgs = dao.first_messages(chat, SAMPLE_SIZE)?;
+    if let Some(last_first_id) = msgs.last().map(|m| m.internal_id) && msgs.len() == SAMPLE_SIZE {
+        let last_msgs = dao.last_messages(chat, SAMPLE_SIZE)?;
+        msgs.extend(last_msgs.into_iter().filter(|m| m.internal_id > last_first_id));
+    }
+    Ok(msgs)
+}
+
+/// Pairs timestamps of sampled messages with those of their counterparts in a chat, which is scrolled through
+/// in batches. Only messages whose pairing keys are unique on both sides are paired.
+fn pair_timestamps(sample: &[Message], dao: &dyn ChatHistoryDao, chat: &Chat) -> Result<Vec<(i64, i64)>> {
+    let sample_timestamps = unique_timestamps(sample);
+    let mut timestamps = HashMap::with_capacity(sample_timestamps.len());
+    let mut offset = 0;
+    loop {
+        let msgs = dao.scroll_messages(chat, offset, BATCH_SIZE)?;
+        for msg in msgs.iter() {
+            if let Some(key) = pairing_key(msg) && sample_timestamps.contains_key(&key) {
+                record_timestamp(&mut timestamps, key, msg.timestamp);
+            }
+        }
+        if msgs.len() < BATCH_SIZE {
+            break;
+        }
+        offset += msgs.len();
+    }
+    Ok(sample_timestamps.into_iter()
+        .filter_map(|(key, sample_ts)| Some((sample_ts?, (*timestamps.get(&key)?)?)))
+        .collect_vec())
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum PairingKey {
+    SourceId(i64),
+    Content { from_id: i64, text_hash: u64 },
+}
+
+fn pairing_key(msg: &Message) -> Option<PairingKey> {
+    match msg.source_id_option {
+        Some(source_id) => Some(PairingKey::SourceId(source_id)),
+        None if !msg.searchable_string.is_empty() =>
+            Some(PairingKey::Content { from_id: msg.from_id, text_hash: hasher().hash_one(&msg.searchable_string) }),
+        None => None,
+    }
+}
+
+/// Timestamp is replaced with `None` if the key is ambiguous
+fn record_timestamp(timestamps: &mut HashMap<PairingKey, Option<i64>>, key: PairingKey, timestamp: i64) {
+    timestamps.entry(key)
+        .and_modify(|ts| *ts = None)
+        .or_insert(Some(timestamp));
+}
+
+/// Timestamps of messages by their pairing key, `None` if the key is ambiguous
+fn unique_timestamps(msgs: &[Message]) -> HashMap<PairingKey, Option<i64>> {
+    let mut res = HashMap::new();
+    for msg in msgs {
+        if let Some(key) = pairing_key(msg) {
+            record_timestamp(&mut res, key, msg.timestamp);
+        }
+    }
+    res
+}
@@ -642,6 +642,133 @@ fn timestamp_diff() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn timestamp_diff_detected_and_compensated() -> EmptyRes {
+    let msgs = create_messages(MAX_MSG_ID);
+    let msgs_a = msgs.clone();
+    let msgs_b = msgs.iter().cloned().enumerate().map(|(idx, mut m)| {
+        // Some of the changed messages are off by a different amount, that shouldn't prevent detection
+        if idx % 100 == 50 {
+            m.text = vec![RichText::make_plain(format!("Changed {idx}"))];
+            m.timestamp += 7200 + 1;
+        } else {
+            m.timestamp += 3 * 3600;
+        }
+        m
+    }).collect_vec();
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+    let analysis = analyzer(&helper).analyze_detecting_time_shift(helper.m.cwd(), helper.s.cwd(), "", false)?;
+    let total_msgs = *MAX_MSG_ID as usize + 1;
+    // Only first and last 1000 messages are sampled
+    let sampled_msgs = 2000;
+    let shift = TimeShift {
+        slave_ahead_by_sec: 3 * 3600,
+        shifted_msgs: sampled_msgs - sampled_msgs / 100,
+        paired_msgs: sampled_msgs,
+    };
+    assert_eq!(analysis, ChatAnalysisOutcome::TimeShifted(shift));
+
+    let analysis = analyzer(&helper).with_slave_time_shift(shift.slave_ahead_by_sec)
+        .analyze(helper.m.cwd(), helper.s.cwd(), "", false)?;
+    assert_eq!(analysis.len(), total_msgs / 100 * 2 + 1);
+    assert_eq!(analysis[..3], [
+        Match(MergeAnalysisSectionMatch {
+            first_master_msg_id: helper.m.msgs[&src_id(0)].typed_id(),
+            last_master_msg_id: helper.m.msgs[&src_id(49)].typed_id(),
+            first_slave_msg_id: helper.s.msgs[&src_id(0)].typed_id(),
+            last_slave_msg_id: helper.s.msgs[&src_id(49)].typed_id(),
+        }),
+        Conflict(MergeAnalysisSectionConflict {
+            first_master_msg_id: helper.m.msgs[&src_id(50)].typed_id(),
+            last_master_msg_id: helper.m.msgs[&src_id(50)].typed_id(),
+            first_slave_msg_id: helper.s.msgs[&src_id(50)].typed_id(),
+            last_slave_msg_id: helper.s.msgs[&src_id(50)].typed_id(),
+        }),
+        Match(MergeAnalysisSectionMatch {
+            first_master_msg_id: helper.m.msgs[&src_id(51)].typed_id(),
+            last_master_msg_id: helper.m.msgs[&src_id(149)].typed_id(),
+            first_slave_msg_id: helper.s.msgs[&src_id(51)].typed_id(),
+            last_slave_msg_id: helper.s.msgs[&src_id(149)].typed_id(),
+        }),
+    ]);
+    Ok(())
+}
+
+/// Master messages sampled from both ends of the chat are not in slave, so it's the other way round
+#[test]
+fn timestamp_diff_slave_within_master() -> EmptyRes {
+    let msgs = create_messages(MAX_MSG_ID);
+    let msgs_a = msgs.clone();
+    let msgs_b = msgs[1200..1800].iter().cloned().map(|mut m| {
+        m.timestamp -= 3600;
+        m
+    }).collect_vec();
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+    let shift = analyzer(&helper).detect_time_shift(helper.m.cwd(), helper.s.cwd())?;
+    assert_eq!(shift, Some(TimeShift {
+        slave_ahead_by_sec: -3600,
+        shifted_msgs: 600,
+        paired_msgs: 600,
+    }));
+    Ok(())
+}
+
+#[test]
+fn timestamp_diff_inconsistent() -> EmptyRes {
+    let msgs = create_messages(src_id(9));
+    let msgs_a = msgs.clone();
+    let msgs_b = msgs.iter().cloned().map(|mut m| {
+        m.timestamp += 3600 + *m.source_id_option.as_ref().unwrap();
+        m
+    }).collect_vec();
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+    let analysis_res = analyzer(&helper).analyze_detecting_time_shift(helper.m.cwd(), helper.s.cwd(), "", false);
+    assert!(analysis_res.is_err());
+    let msg = error_message(&analysis_res.err().unwrap());
+    assert!(msg.contains("Time shift detected"));
+    Ok(())
+}
+
+#[test]
+fn timestamp_diff_without_source_ids() -> EmptyRes {
+    let users = (1..=MAX_USER_ID).map(|id| create_user(&ZERO_PB_UUID, id as i64)).collect_vec();
+    let msgs = create_messages(src_id(9));
+    let create = |name: &str, msgs: Vec<Message>| {
+        let cwm = ChatWithMessages {
+            chat: create_group_chat(&ZERO_PB_UUID, 1, "A", users.iter().map(|u| u.id).collect_vec(), msgs.len()),
+            messages: msgs,
+        };
+        create_dao(name, users.clone(), vec![cwm], |_, m| m.source_id_option = None, rng().random())
+    };
+    let m_dao = create("One", msgs.clone());
+    let s_dao = create("Two", msgs.into_iter().map(|mut m| {
+        m.timestamp -= 3600;
+        m
+    }).collect_vec());
+    let (m_ds, s_ds) = (m_dao.dao.dataset(), s_dao.dao.dataset());
+    let m_cwd = m_dao.dao.chats(&m_ds.uuid)?.remove(0);
+    let s_cwd = s_dao.dao.chats(&s_ds.uuid)?.remove(0);
+
+    let analyzer = DatasetDiffAnalyzer::create(m_dao.dao.as_ref(), &m_ds, s_dao.dao.as_ref(), &s_ds)?;
+    let analysis = analyzer.analyze_detecting_time_shift(&m_cwd, &s_cwd, "", false)?;
+    assert_eq!(analysis, ChatAnalysisOutcome::TimeShifted(TimeShift {
+        slave_ahead_by_sec: -3600,
+        shifted_msgs: 10,
+        paired_msgs: 10,
+    }));
+
+    let analysis = analyzer.with_slave_time_shift(-3600).analyze(&m_cwd, &s_cwd, "", false)?;
+    assert_eq!(analysis, vec![
+        Match(MergeAnalysisSectionMatch {
+            first_master_msg_id: MasterInternalId(0),
+            last_master_msg_id: MasterInternalId(900),
+            first_slave_msg_id: SlaveInternalId(0),
+            last_slave_msg_id: SlaveInternalId(900),
+        }),
+    ]);
+    Ok(())
+}
+
 /// Difference in file name only should be a match
 #[test]
 fn file_name_diff() -> EmptyRes {
@@ -720,6 +847,9 @@ fn present_absent_not_downloaded() -> EmptyRes {
                 contents: vec![
                     content!(Photo { ..photo.clone() })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }
         } else {
             message_service!(message_service::SealedValueOptional::GroupEditPhoto(
@@ -0,0 +1,300 @@
+#[cfg(test)]
+#[path = "auto_merge_tests.rs"]
+mod tests;
+
+use crate::merge::analyzer::*;
+use crate::merge::merger::*;
+use crate::merge::report::MergeReport;
+use crate::prelude::*;
+
+/// Preset used to produce merge decisions without asking the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoMergePolicy {
+    /// Everything new in slave is added, conflicting users and messages are kept as they were in master
+    PreferMaster,
+    /// Everything new in slave is added, conflicting users and messages are replaced by their slave versions
+    PreferSlave,
+    /// Acts as either `PreferMaster` or `PreferSlave`, whichever dataset is newer (i.e. has the latest message).
+    /// If both end at the same time, slave is considered newer.
+    PreferNewer,
+    /// Everything new in slave is added, for conflicting messages both versions are kept (master first).
+    /// Conflicting users are kept as they were in master.
+    KeepBoth,
+    /// Everything new in slave is added, but nothing in master is changed - conflicting messages are kept as
+    /// they were in master, and even matching messages don't take any content from their slave versions.
+    AddOnly,
+}
+
+/// What decisions were made by an automatic merge, for the user to review afterwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoMergeSummary {
+    /// Policy the decisions were made with, `PreferNewer` is resolved to either `PreferMaster` or `PreferSlave`
+    pub effective_policy: AutoMergePolicy,
+    pub users_retained: usize,
+    pub users_added: usize,
+    /// Users present in both datasets, kept as they were in master
+    pub users_kept: usize,
+    pub users_replaced: usize,
+    pub chats_retained: usize,
+    pub chats_added: usize,
+    pub merged_chats: Vec<ChatMergeSummary>,
+}
+
+/// Message counts for a chat present in both datasets.
+/// Conflicts are resolved according to the summary effective policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMergeSummary {
+    pub chat_id: ChatId,
+    pub chat_name: String,
+    pub matched_msgs: usize,
+    pub retained_msgs: usize,
+    pub added_msgs: usize,
+    pub conflicts: usize,
+    pub conflicting_master_msgs: usize,
+    pub conflicting_slave_msgs: usize,
+}
+
+/// Chat present in both datasets, along with its merge analysis.
+pub struct AnalyzedChat {
+    pub master_cwd: ChatWithDetails,
+    pub slave_cwd: ChatWithDetails,
+    pub sections: Vec<MergeAnalysisSection>,
+}
+
+/// Analyzes all chats present (by ID) in both master and slave datasets, in master chats order.
+/// Slave chats are shifted in time according to `slave_time_shifts` (see `DatasetDiffAnalyzer::with_slave_time_shift`).
+pub fn analyze_common_chats(
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    force_conflicts: bool,
+    alignment: MessageAlignment,
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<Vec<AnalyzedChat>> {
+    let analyzer = DatasetDiffAnalyzer::create(master_dao, master_ds, slave_dao, slave_ds)?.with_alignment(alignment);
+    let mut slave_cwds: HashMap<ChatId, ChatWithDetails> =
+        slave_dao.chats(&slave_ds.uuid)?.into_iter().map(|cwd| (cwd.id(), cwd)).collect();
+    let mut res = vec![];
+    for master_cwd in master_dao.chats(&master_ds.uuid)? {
+        let Some(slave_cwd) = slave_cwds.remove(&master_cwd.id()) else { continue };
+        let sections = match slave_time_shifts.get(&master_cwd.id()) {
+            Some(&shift) => analyzer.clone().with_slave_time_shift(shift),
+            None => analyzer.clone(),
+        }.analyze(&master_cwd, &slave_cwd, &slave_cwd.chat.qualified_name(), force_conflicts)?;
+        res.push(AnalyzedChat { master_cwd, slave_cwd, sections });
+    }
+    Ok(res)
+}
+
+/// Produces user and chat merge decisions covering every user and chat of both datasets,
+/// suitable for `merger::merge_datasets` given the same `slave_time_shifts`.
+pub fn make_decisions(
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    policy: AutoMergePolicy,
+    alignment: MessageAlignment,
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<(Vec<UserMergeDecision>, Vec<ChatMergeDecision>)> {
+    let (user_merges, chat_merges, _) =
+        make_decisions_with_summary(master_dao, master_ds, slave_dao, slave_ds, policy, alignment, slave_time_shifts)?;
+    Ok((user_merges, chat_merges))
+}
+
+/// Same as `make_decisions`, also summarizing what was decided.
+pub fn make_decisions_with_summary(
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    policy: AutoMergePolicy,
+    alignment: MessageAlignment,
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<(Vec<UserMergeDecision>, Vec<ChatMergeDecision>, AutoMergeSummary)> {
+    let policy = match policy {
+        AutoMergePolicy::PreferNewer if is_master_newer(master_dao, master_ds, slave_dao, slave_ds)? =>
+            AutoMergePolicy::PreferMaster,
+        AutoMergePolicy::PreferNewer =>
+            AutoMergePolicy::PreferSlave,
+        policy => policy,
+    };
+    let mut summary = AutoMergeSummary {
+        effective_policy: policy,
+        users_retained: 0,
+        users_added: 0,
+        users_kept: 0,
+        users_replaced: 0,
+        chats_retained: 0,
+        chats_added: 0,
+        merged_chats: vec![],
+    };
+
+    let master_ds_root = master_dao.dataset_root(&master_ds.uuid)?;
+    let slave_ds_root = slave_dao.dataset_root(&slave_ds.uuid)?;
+
+    // Users
+    let master_users = master_dao.users(&master_ds.uuid)?;
+    let mut slave_users: HashMap<UserId, User> =
+        slave_dao.users(&slave_ds.uuid)?.into_iter().map(|u| (u.id(), u)).collect();
+    let mut user_merges = Vec::with_capacity(master_users.len() + slave_users.len());
+    for mu in master_users.iter() {
+        let decision = match slave_users.remove(&mu.id()) {
+            None => {
+                summary.users_retained += 1;
+                UserMergeDecision::Retain(mu.id())
+            }
+            Some(mut su) => {
+                su.ds_uuid = mu.ds_uuid.clone();
+                let is_equal = EntityCmpTuple::new_without_cwd(mu, &master_ds_root)
+                    .compare(&EntityCmpTuple::new_without_cwd(&su, &slave_ds_root))?
+                    .is_eq();
+                match policy {
+                    AutoMergePolicy::PreferSlave if !is_equal => {
+                        summary.users_replaced += 1;
+                        UserMergeDecision::Replace(mu.id())
+                    }
+                    _ => {
+                        summary.users_kept += 1;
+                        UserMergeDecision::MatchOrDontReplace(mu.id())
+                    }
+                }
+            }
+        };
+        user_merges.push(decision);
+    }
+    // Preserving slave users order
+    for su in slave_dao.users(&slave_ds.uuid)? {
+        if slave_users.contains_key(&su.id()) {
+            summary.users_added += 1;
+            user_merges.push(UserMergeDecision::Add(su.id()));
+        }
+    }
+
+    // Chats
+    let analyzed: HashMap<ChatId, AnalyzedChat> =
+        analyze_common_chats(master_dao, master_ds, slave_dao, slave_ds, false, alignment, slave_time_shifts)?
+            .into_iter().map(|ac| (ac.master_cwd.id(), ac)).collect();
+    let mut chat_merges = vec![];
+    for master_cwd in master_dao.chats(&master_ds.uuid)? {
+        let chat_id = master_cwd.id();
+        let decision = match analyzed.get(&chat_id) {
+            None => {
+                summary.chats_retained += 1;
+                ChatMergeDecision::Retain { master_chat_id: chat_id }
+            }
+            Some(ac) => {
+                summary.merged_chats.push(summarize_chat(master_dao, slave_dao, ac)?);
+                ChatMergeDecision::Merge {
+                    chat_id,
+                    message_merges: ac.sections.iter().cloned().flat_map(|section| decide_messages(section, policy)).collect_vec(),
+                }
+            }
+        };
+        chat_merges.push(decision);
+    }
+    for slave_cwd in slave_dao.chats(&slave_ds.uuid)? {
+        if !analyzed.contains_key(&slave_cwd.id()) {
+            summary.chats_added += 1;
+            chat_merges.push(ChatMergeDecision::Add { slave_chat_id: slave_cwd.id() });
+        }
+    }
+
+    Ok((user_merges, chat_merges, summary))
+}
+
+fn is_master_newer(
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+) -> Result<bool> {
+    let latest_timestamp = |dao: &dyn ChatHistoryDao, ds: &Dataset| -> Result<Option<i64>> {
+        Ok(dao.chats(&ds.uuid)?.iter().filter_map(|cwd| cwd.last_msg_option.as_ref().map(|m| m.timestamp)).max())
+    };
+    Ok(latest_timestamp(master_dao, master_ds)? > latest_timestamp(slave_dao, slave_ds)?)
+}
+
+/// Resolved policy should be passed, i.e. not `PreferNewer`.
+fn decide_messages(section: MergeAnalysisSection, policy: AutoMergePolicy) -> Vec<MessagesMergeDecision> {
+    match section {
+        MergeAnalysisSection::Match(v) if policy == AutoMergePolicy::AddOnly =>
+            vec![MessagesMergeDecision::Retain(MergeAnalysisSectionRetention {
+                first_master_msg_id: v.first_master_msg_id,
+                last_master_msg_id: v.last_master_msg_id,
+            })],
+        MergeAnalysisSection::Match(v) => vec![MessagesMergeDecision::Match(v)],
+        MergeAnalysisSection::Retention(v) => vec![MessagesMergeDecision::Retain(v)],
+        MergeAnalysisSection::Addition(v) => vec![MessagesMergeDecision::Add(v)],
+        MergeAnalysisSection::Conflict(v) => match policy {
+            AutoMergePolicy::PreferSlave => vec![MessagesMergeDecision::Replace(v)],
+            AutoMergePolicy::KeepBoth => vec![
+                MessagesMergeDecision::Retain(MergeAnalysisSectionRetention {
+                    first_master_msg_id: v.first_master_msg_id,
+                    last_master_msg_id: v.last_master_msg_id,
+                }),
+                MessagesMergeDecision::Add(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: v.first_slave_msg_id,
+                    last_slave_msg_id: v.last_slave_msg_id,
+                }),
+            ],
+            AutoMergePolicy::PreferMaster | AutoMergePolicy::AddOnly | AutoMergePolicy::PreferNewer =>
+                vec![MessagesMergeDecision::DontReplace(v)],
+        },
+    }
+}
+
+fn summarize_chat(
+    master_dao: &dyn ChatHistoryDao,
+    slave_dao: &dyn ChatHistoryDao,
+    ac: &AnalyzedChat,
+) -> Result<ChatMergeSummary> {
+    let master_len = |first: MasterInternalId, last: MasterInternalId|
+        master_dao.messages_slice_len(&ac.master_cwd.chat, first.generalize(), last.generalize());
+    let slave_len = |first: SlaveInternalId, last: SlaveInternalId|
+        slave_dao.messages_slice_len(&ac.slave_cwd.chat, first.generalize(), last.generalize());
+    let mut res = ChatMergeSummary {
+        chat_id: ac.master_cwd.id(),
+        chat_name: ac.master_cwd.chat.qualified_name(),
+        matched_msgs: 0,
+        retained_msgs: 0,
+        added_msgs: 0,
+        conflicts: 0,
+        conflicting_master_msgs: 0,
+        conflicting_slave_msgs: 0,
+    };
+    for section in ac.sections.iter() {
+        match section {
+            MergeAnalysisSection::Match(v) =>
+                res.matched_msgs += master_len(v.first_master_msg_id, v.last_master_msg_id)?,
+            MergeAnalysisSection::Retention(v) =>
+                res.retained_msgs += master_len(v.first_master_msg_id, v.last_master_msg_id)?,
+            MergeAnalysisSection::Addition(v) =>
+                res.added_msgs += slave_len(v.first_slave_msg_id, v.last_slave_msg_id)?,
+            MergeAnalysisSection::Conflict(v) => {
+                res.conflicts += 1;
+                res.conflicting_master_msgs += master_len(v.first_master_msg_id, v.last_master_msg_id)?;
+                res.conflicting_slave_msgs += slave_len(v.first_slave_msg_id, v.last_slave_msg_id)?;
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Analyzes datasets and merges them according to the given policy, see `merger::merge_datasets`.
+pub fn auto_merge_datasets(
+    sqlite_dao_dir: &Path,
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    policy: AutoMergePolicy,
+    alignment: MessageAlignment,
+) -> Result<(SqliteDao, Dataset, MergeReport, AutoMergeSummary)> {
+    let (user_merges, chat_merges, summary) =
+        make_decisions_with_summary(master_dao, master_ds, slave_dao, slave_ds, policy, alignment, &HashMap::new())?;
+    let (new_dao, new_ds, report) = merge_datasets(sqlite_dao_dir, master_dao, master_ds, slave_dao, slave_ds,
+                                                   user_merges, chat_merges, &HashMap::new())?;
+    Ok((new_dao, new_ds, report, summary))
+}
@@ -0,0 +1,300 @@
+#![allow(unused_imports)]
+use super::*;
+
+use crate::merge::report::ChatMergeKind;
+use crate::prelude::*;
+use crate::utils::test_utils::*;
+
+use pretty_assertions::{assert_eq, assert_ne};
+use rand::prelude::*;
+
+/**
+ * ```text
+ * Master users    - 1  2  3
+ * Slave users     - 1  2* 4
+ * Master chats    - 1  2
+ * Slave chats     - 1  3
+ * Master messages - 1  2  3  4
+ * Slave messages  -    2  3* 4  5
+ * ```
+ */
+#[test]
+fn decisions_prefer_master() -> EmptyRes {
+    let helper = TestHelper::new();
+    let (user_merges, chat_merges) = helper.make_decisions(AutoMergePolicy::PreferMaster)?;
+
+    assert_eq!(user_merges, vec![
+        UserMergeDecision::MatchOrDontReplace(UserId(1)),
+        UserMergeDecision::MatchOrDontReplace(UserId(2)),
+        UserMergeDecision::Retain(UserId(3)),
+        UserMergeDecision::Add(UserId(4)),
+    ]);
+    assert_eq!(chat_merges, vec![
+        ChatMergeDecision::Merge {
+            chat_id: ChatId(1),
+            message_merges: vec![
+                MessagesMergeDecision::Retain(helper.retention(1)),
+                MessagesMergeDecision::Match(helper.matched(2)),
+                MessagesMergeDecision::DontReplace(helper.conflict(3)),
+                MessagesMergeDecision::Match(helper.matched(4)),
+                MessagesMergeDecision::Add(helper.addition(5)),
+            ],
+        },
+        ChatMergeDecision::Retain { master_chat_id: ChatId(2) },
+        ChatMergeDecision::Add { slave_chat_id: ChatId(3) },
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn decisions_prefer_slave() -> EmptyRes {
+    let helper = TestHelper::new();
+    let (user_merges, chat_merges) = helper.make_decisions(AutoMergePolicy::PreferSlave)?;
+
+    assert_eq!(user_merges, vec![
+        UserMergeDecision::MatchOrDontReplace(UserId(1)),
+        UserMergeDecision::Replace(UserId(2)),
+        UserMergeDecision::Retain(UserId(3)),
+        UserMergeDecision::Add(UserId(4)),
+    ]);
+    assert_eq!(chat_merges, vec![
+        ChatMergeDecision::Merge {
+            chat_id: ChatId(1),
+            message_merges: vec![
+                MessagesMergeDecision::Retain(helper.retention(1)),
+                MessagesMergeDecision::Match(helper.matched(2)),
+                MessagesMergeDecision::Replace(helper.conflict(3)),
+                MessagesMergeDecision::Match(helper.matched(4)),
+                MessagesMergeDecision::Add(helper.addition(5)),
+            ],
+        },
+        ChatMergeDecision::Retain { master_chat_id: ChatId(2) },
+        ChatMergeDecision::Add { slave_chat_id: ChatId(3) },
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn decisions_keep_both() -> EmptyRes {
+    let helper = TestHelper::new();
+    let (_, chat_merges) = helper.make_decisions(AutoMergePolicy::KeepBoth)?;
+
+    let conflict = helper.conflict(3);
+    assert_eq!(chat_merges[0], ChatMergeDecision::Merge {
+        chat_id: ChatId(1),
+        message_merges: vec![
+            MessagesMergeDecision::Retain(helper.retention(1)),
+            MessagesMergeDecision::Match(helper.matched(2)),
+            MessagesMergeDecision::Retain(MergeAnalysisSectionRetention {
+                first_master_msg_id: conflict.first_master_msg_id,
+                last_master_msg_id: conflict.last_master_msg_id,
+            }),
+            MessagesMergeDecision::Add(MergeAnalysisSectionAddition {
+                first_slave_msg_id: conflict.first_slave_msg_id,
+                last_slave_msg_id: conflict.last_slave_msg_id,
+            }),
+            MessagesMergeDecision::Match(helper.matched(4)),
+            MessagesMergeDecision::Add(helper.addition(5)),
+        ],
+    });
+
+    Ok(())
+}
+
+#[test]
+fn decisions_add_only() -> EmptyRes {
+    let helper = TestHelper::new();
+    let (user_merges, chat_merges) = helper.make_decisions(AutoMergePolicy::AddOnly)?;
+
+    assert!(!user_merges.iter().any(|um| matches!(um, UserMergeDecision::Replace(_))));
+    let as_retention = |v: MergeAnalysisSectionMatch| MergeAnalysisSectionRetention {
+        first_master_msg_id: v.first_master_msg_id,
+        last_master_msg_id: v.last_master_msg_id,
+    };
+    assert_eq!(chat_merges[0], ChatMergeDecision::Merge {
+        chat_id: ChatId(1),
+        message_merges: vec![
+            MessagesMergeDecision::Retain(helper.retention(1)),
+            MessagesMergeDecision::Retain(as_retention(helper.matched(2))),
+            MessagesMergeDecision::DontReplace(helper.conflict(3)),
+            MessagesMergeDecision::Retain(as_retention(helper.matched(4))),
+            MessagesMergeDecision::Add(helper.addition(5)),
+        ],
+    });
+
+    Ok(())
+}
+
+#[test]
+fn decisions_prefer_newer() -> EmptyRes {
+    let helper = TestHelper::new();
+
+    // Slave has message 5 which master doesn't, so it's newer
+    let (_, _, summary) = make_decisions_with_summary(
+        helper.m_dao.dao.as_ref(), &helper.m_ds, helper.s_dao.dao.as_ref(), &helper.s_ds,
+        AutoMergePolicy::PreferNewer, MessageAlignment::Strict, &HashMap::new())?;
+    assert_eq!(summary.effective_policy, AutoMergePolicy::PreferSlave);
+
+    let (_, _, summary) = make_decisions_with_summary(
+        helper.s_dao.dao.as_ref(), &helper.s_ds, helper.m_dao.dao.as_ref(), &helper.m_ds,
+        AutoMergePolicy::PreferNewer, MessageAlignment::Strict, &HashMap::new())?;
+    assert_eq!(summary.effective_policy, AutoMergePolicy::PreferMaster);
+
+    Ok(())
+}
+
+#[test]
+fn auto_merge_prefer_slave() -> EmptyRes {
+    let helper = TestHelper::new();
+    let new_dao_tmpdir = TmpDir::new();
+    let (new_dao, new_ds, report, summary) = auto_merge_datasets(
+        &new_dao_tmpdir.path,
+        helper.m_dao.dao.as_ref(), &helper.m_ds,
+        helper.s_dao.dao.as_ref(), &helper.s_ds,
+        AutoMergePolicy::PreferSlave,
+        MessageAlignment::Strict,
+    )?;
+
+    assert_eq!(summary, AutoMergeSummary {
+        effective_policy: AutoMergePolicy::PreferSlave,
+        users_retained: 1,
+        users_added: 1,
+        users_kept: 1,
+        users_replaced: 1,
+        chats_retained: 1,
+        chats_added: 1,
+        merged_chats: vec![ChatMergeSummary {
+            chat_id: ChatId(1),
+            chat_name: "'Chat A' (#1)".to_owned(),
+            matched_msgs: 2,
+            retained_msgs: 1,
+            added_msgs: 1,
+            conflicts: 1,
+            conflicting_master_msgs: 1,
+            conflicting_slave_msgs: 1,
+        }],
+    });
+
+    assert_eq!(report.new_ds_uuid, new_ds.uuid.value);
+    assert_eq!(report.chats.iter().map(|c| (c.chat_id, c.tpe)).collect_vec(), vec![
+        (1, ChatMergeKind::Merge), (2, ChatMergeKind::Retain), (3, ChatMergeKind::Add),
+    ]);
+    assert_eq!(report.chats[0].replaced_msgs, 1);
+
+    let new_users = new_dao.users(&new_ds.uuid)?;
+    assert_eq!(new_users.iter().map(|u| u.id).collect_vec(), vec![1, 2, 3, 4]);
+    assert_eq!(new_users[1].first_name_option, helper.s_dao.dao.users_single_ds()[1].first_name_option);
+
+    let new_chats = new_dao.chats(&new_ds.uuid)?.into_iter().sorted_by_key(|cwd| cwd.chat.id).collect_vec();
+    assert_eq!(new_chats.iter().map(|cwd| cwd.chat.id).collect_vec(), vec![1, 2, 3]);
+
+    let new_msgs = new_dao.first_messages(&new_chats[0].chat, usize::MAX)?;
+    assert_eq!(new_msgs.iter().map(|m| m.searchable_string.as_str()).collect_vec(), vec![
+        "Hello there, 1! Hey, 1!",
+        "Hello there, 2! Hey, 2!",
+        "Different message 3 Hey, 3!",
+        "Hello there, 4! Hey, 4!",
+        "Hello there, 5! Hey, 5!",
+    ]);
+
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+struct TestHelper {
+    m_dao: InMemoryDaoHolder,
+    m_ds: Dataset,
+    m_msgs: Vec<Message>,
+    s_dao: InMemoryDaoHolder,
+    s_ds: Dataset,
+    s_msgs: Vec<Message>,
+}
+
+impl TestHelper {
+    fn new() -> Self {
+        let users = (1..=4).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+        let m_users = users[0..3].to_vec();
+        let s_users = vec![
+            users[0].clone(),
+            User { first_name_option: Some("Changed".to_owned()), ..users[1].clone() },
+            users[3].clone(),
+        ];
+
+        let msgs = (1..=5).map(|idx| create_regular_message(idx, 1)).collect_vec();
+        let m_msgs = msgs.cloned([1, 2, 3, 4].map(src_id));
+        let s_msgs = msgs.cloned([2, 3, 4, 5].map(src_id)).changed(|id| *id == 3);
+
+        let m_cwms = vec![
+            ChatWithMessages {
+                chat: create_group_chat(&ZERO_PB_UUID, 1, "A", vec![1, 2], m_msgs.len()),
+                messages: m_msgs,
+            },
+            ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 2, "B", vec![1, 3], 0), messages: vec![] },
+        ];
+        let s_cwms = vec![
+            ChatWithMessages {
+                chat: create_group_chat(&ZERO_PB_UUID, 1, "A", vec![1, 2], s_msgs.len()),
+                messages: s_msgs,
+            },
+            ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 3, "C", vec![1, 4], 0), messages: vec![] },
+        ];
+
+        let m_dao = create_dao("One", m_users, m_cwms, |_, _| {}, rng().random());
+        let s_dao = create_dao("Two", s_users, s_cwms, |_, _| {}, rng().random());
+        let m_ds = m_dao.dao.dataset();
+        let s_ds = s_dao.dao.dataset();
+        let m_msgs = m_dao.dao.cwms_single_ds().remove(0).messages;
+        let s_msgs = s_dao.dao.cwms_single_ds().remove(0).messages;
+        TestHelper { m_dao, m_ds, m_msgs, s_dao, s_ds, s_msgs }
+    }
+
+    fn make_decisions(&self, policy: AutoMergePolicy) -> Result<(Vec<UserMergeDecision>, Vec<ChatMergeDecision>)> {
+        make_decisions(self.m_dao.dao.as_ref(), &self.m_ds, self.s_dao.dao.as_ref(), &self.s_ds, policy,
+                       MessageAlignment::Strict, &HashMap::new())
+    }
+
+    fn m_id(&self, src_id: i64) -> MasterInternalId {
+        MasterInternalId(self.m_msgs.iter().find(|m| *m.source_id() == src_id).unwrap().internal_id)
+    }
+
+    fn s_id(&self, src_id: i64) -> SlaveInternalId {
+        SlaveInternalId(self.s_msgs.iter().find(|m| *m.source_id() == src_id).unwrap().internal_id)
+    }
+
+    fn matched(&self, src_id: i64) -> MergeAnalysisSectionMatch {
+        MergeAnalysisSectionMatch {
+            first_master_msg_id: self.m_id(src_id),
+            last_master_msg_id: self.m_id(src_id),
+            first_slave_msg_id: self.s_id(src_id),
+            last_slave_msg_id: self.s_id(src_id),
+        }
+    }
+
+    fn retention(&self, src_id: i64) -> MergeAnalysisSectionRetention {
+        MergeAnalysisSectionRetention {
+            first_master_msg_id: self.m_id(src_id),
+            last_master_msg_id: self.m_id(src_id),
+        }
+    }
+
+    fn addition(&self, src_id: i64) -> MergeAnalysisSectionAddition {
+        MergeAnalysisSectionAddition {
+            first_slave_msg_id: self.s_id(src_id),
+            last_slave_msg_id: self.s_id(src_id),
+        }
+    }
+
+    fn conflict(&self, src_id: i64) -> MergeAnalysisSectionConflict {
+        MergeAnalysisSectionConflict {
+            first_master_msg_id: self.m_id(src_id),
+            last_master_msg_id: self.m_id(src_id),
+            first_slave_msg_id: self.s_id(src_id),
+            last_slave_msg_id: self.s_id(src_id),
+        }
+    }
+}
@@ -3,6 +3,7 @@
 mod tests;
 
 use crate::merge::analyzer::*;
+use crate::merge::report::*;
 use crate::prelude::*;
 
 const BATCH_SIZE: usize = 1000;
@@ -10,6 +11,10 @@ const BATCH_SIZE: usize = 1000;
 /// Creates a new database containing dataset merged according to supplied merge decisions, as well as the rest of
 /// `master_dao` datasets copied as-is.
 /// user_merges and chat_merges should contain decisions for ALL users and chats.
+/// Messages of merged slave chats are shifted back in time according to `slave_time_shifts`, these should be the same
+/// as used during analysis (see `DatasetDiffAnalyzer::with_slave_time_shift`).
+/// Merge report is saved next to the new database.
+#[allow(clippy::too_many_arguments)]
 pub fn merge_datasets(
     sqlite_dao_dir: &Path,
     master_dao: &dyn ChatHistoryDao,
@@ -18,60 +23,131 @@ pub fn merge_datasets(
     slave_ds: &Dataset,
     user_merges: Vec<UserMergeDecision>,
     chat_merges: Vec<ChatMergeDecision>,
-) -> Result<(SqliteDao, Dataset)> {
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<(SqliteDao, Dataset, MergeReport)> {
     measure(|| {
-        fn get_users_and_cwds(dao: &dyn ChatHistoryDao, ds_uuid: &PbUuid)
-                              -> Result<(HashMap<UserId, User>, HashMap<ChatId, ChatWithDetails>)> {
-            Ok((dao.users(ds_uuid)?.into_iter().map(|u| (u.id(), u)).collect(),
-                dao.chats(ds_uuid)?.into_iter().map(|cwd| (cwd.id(), cwd)).collect()))
-        }
-
-        let (master_users, master_cwds) = get_users_and_cwds(master_dao, &master_ds.uuid)?;
-        let (slave_users, slave_cwds) = get_users_and_cwds(slave_dao, &slave_ds.uuid)?;
-
-        // Input validity check: users
-        let master_user_id_merges = user_merges.iter().filter_map(|m| m.master_user_id_option()).collect_vec();
-        for uid in master_users.keys() {
-            ensure!(master_user_id_merges.contains(uid), "Master user {} wasn't mentioned in merges", uid.0);
-        }
-        ensure!(master_users.len() == master_user_id_merges.len(), "User merges contained more master users than actually exist?");
-
-        let slave_user_id_merges = user_merges.iter().filter_map(|m| m.slave_user_id_option()).collect_vec();
-        for uid in slave_users.keys() {
-            ensure!(slave_user_id_merges.contains(uid), "Slave user {} wasn't mentioned in merges", uid.0);
-        }
-        ensure!(slave_users.len() == slave_user_id_merges.len(), "User merges contained more slave users than actually exist?");
-
-        // Input validity check: chats
-        let master_chat_id_merges = chat_merges.iter().filter_map(|m| m.master_chat_id_option()).collect_vec();
-        for cid in master_cwds.keys() {
-            ensure!(master_chat_id_merges.contains(cid), "Master chat {} wasn't mentioned in merges", cid.0);
-        }
-        ensure!(master_cwds.len() == master_chat_id_merges.len(), "Chat merges contained more master chats than actually exist?");
-
-        let slave_chat_id_merges = chat_merges.iter().filter_map(|m| m.slave_chat_id_option()).collect_vec();
-        for cid in slave_cwds.keys() {
-            ensure!(slave_chat_id_merges.contains(cid), "Slave chat {} wasn't mentioned in merges", cid.0);
-        }
-        ensure!(slave_cwds.len() == slave_chat_id_merges.len(), "Chat merges contained more slave chats than actually exist?");
-
-        // Actual logic
         let sqlite_dao_file = sqlite_dao_dir.join(SqliteDao::FILENAME);
         let mut new_dao = SqliteDao::create(&sqlite_dao_file)?;
-        let master = DaoMergeEntities { dao: master_dao, ds: master_ds, users: master_users, cwds: master_cwds };
-        let slave = DaoMergeEntities { dao: slave_dao, ds: slave_ds, users: slave_users, cwds: slave_cwds };
-        let new_dataset = merge_inner(&mut new_dao, master, slave, user_merges, chat_merges)?;
+        let (new_dataset, report) = merge_into(&mut new_dao, master_dao, master_ds, slave_dao, slave_ds,
+                                     user_merges, chat_merges, slave_time_shifts)?;
         let other_master_dataset_uuids = master_dao.datasets()?
             .into_iter()
             .map(|ds| ds.uuid)
             .filter(|ds_uuid| ds_uuid != &master_ds.uuid)
             .collect_vec();
         new_dao.copy_datasets_from(master_dao, &other_master_dataset_uuids)?;
-        new_dao.vacuum()?;
-        Ok((new_dao, new_dataset))
+        report.save(sqlite_dao_dir)?;
+        Ok((new_dao, new_dataset, report))
     }, |_, t| log::info!("Datasets merged in {t} ms"))
 }
 
+/// Writes a dataset merged according to supplied merge decisions into an existing DAO.
+/// Nothing else is copied, see `merge_datasets` for the requirements on merge decisions.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn merge_into(
+    new_dao: &mut dyn MutableChatHistoryDao,
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    user_merges: Vec<UserMergeDecision>,
+    chat_merges: Vec<ChatMergeDecision>,
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<(Dataset, MergeReport)> {
+    let ((master_users, master_cwds), (slave_users, slave_cwds)) =
+        load_and_validate(master_dao, master_ds, slave_dao, slave_ds, &user_merges, &chat_merges)?;
+
+    // Actual logic
+    let master = DaoMergeEntities { dao: master_dao, ds: master_ds, users: master_users, cwds: master_cwds };
+    let slave = DaoMergeEntities { dao: slave_dao, ds: slave_ds, users: slave_users, cwds: slave_cwds };
+    merge_inner(new_dao, master, slave, user_merges, chat_merges, slave_time_shifts)
+}
+
+/// Merges slave dataset into the master one right within the master database, instead of creating a new database.
+/// Master messages keep their internal IDs, only new messages are inserted and only new media files are copied.
+/// Since internal IDs define messages order, master messages following the inserted ones are moved after them.
+/// Master database is backed up beforehand, and all the changes are made in a single transaction.
+/// Chat images are kept as they are in master.
+/// See `merge_datasets` for the requirements on merge decisions.
+/// Merge report is saved next to the master database.
+pub fn merge_in_place(
+    master_dao: &mut dyn MutableChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    user_merges: Vec<UserMergeDecision>,
+    chat_merges: Vec<ChatMergeDecision>,
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<MergeReport> {
+    measure(|| {
+        let ((master_users, master_cwds), (slave_users, slave_cwds)) =
+            load_and_validate(master_dao, master_ds, slave_dao, slave_ds, &user_merges, &chat_merges)?;
+        let slave = DaoMergeEntities { dao: slave_dao, ds: slave_ds, users: slave_users, cwds: slave_cwds };
+
+        // Backup has to be complete before anything is changed
+        master_dao.backup()?.join().map_err(|_| anyhow!("Backup thread panicked!"))?;
+
+        let mut report = MergeReport::new(master_ds, slave_ds, master_ds, true);
+        let master = DaoMergeEntities { dao: &*master_dao, ds: master_ds, users: master_users, cwds: master_cwds };
+        let plan = plan_in_place_merge(&master, &slave, &user_merges, &chat_merges, slave_time_shifts, &mut report)?;
+        let master_users = master.users;
+
+        master_dao.in_place_merge(&master_ds.uuid, &mut |target| {
+            apply_in_place_merge(target, &master_users, &slave, &user_merges, &plan, &mut report)
+        })?;
+        report.save(master_dao.storage_path())?;
+        Ok(report)
+    }, |_, t| log::info!("Datasets merged in place in {t} ms"))
+}
+
+type UsersAndCwds = (HashMap<UserId, User>, HashMap<ChatId, ChatWithDetails>);
+
+/// Loads users and chats of both datasets, making sure merge decisions mention all of them.
+fn load_and_validate(
+    master_dao: &dyn ChatHistoryDao,
+    master_ds: &Dataset,
+    slave_dao: &dyn ChatHistoryDao,
+    slave_ds: &Dataset,
+    user_merges: &[UserMergeDecision],
+    chat_merges: &[ChatMergeDecision],
+) -> Result<(UsersAndCwds, UsersAndCwds)> {
+    fn get_users_and_cwds(dao: &dyn ChatHistoryDao, ds_uuid: &PbUuid) -> Result<UsersAndCwds> {
+        Ok((dao.users(ds_uuid)?.into_iter().map(|u| (u.id(), u)).collect(),
+            dao.chats(ds_uuid)?.into_iter().map(|cwd| (cwd.id(), cwd)).collect()))
+    }
+
+    let (master_users, master_cwds) = get_users_and_cwds(master_dao, &master_ds.uuid)?;
+    let (slave_users, slave_cwds) = get_users_and_cwds(slave_dao, &slave_ds.uuid)?;
+
+    // Input validity check: users
+    let master_user_id_merges = user_merges.iter().filter_map(|m| m.master_user_id_option()).collect_vec();
+    for uid in master_users.keys() {
+        ensure!(master_user_id_merges.contains(uid), "Master user {} wasn't mentioned in merges", uid.0);
+    }
+    ensure!(master_users.len() == master_user_id_merges.len(), "User merges contained more master users than actually exist?");
+
+    let slave_user_id_merges = user_merges.iter().filter_map(|m| m.slave_user_id_option()).collect_vec();
+    for uid in slave_users.keys() {
+        ensure!(slave_user_id_merges.contains(uid), "Slave user {} wasn't mentioned in merges", uid.0);
+    }
+    ensure!(slave_users.len() == slave_user_id_merges.len(), "User merges contained more slave users than actually exist?");
+
+    // Input validity check: chats
+    let master_chat_id_merges = chat_merges.iter().filter_map(|m| m.master_chat_id_option()).collect_vec();
+    for cid in master_cwds.keys() {
+        ensure!(master_chat_id_merges.contains(cid), "Master chat {} wasn't mentioned in merges", cid.0);
+    }
+    ensure!(master_cwds.len() == master_chat_id_merges.len(), "Chat merges contained more master chats than actually exist?");
+
+    let slave_chat_id_merges = chat_merges.iter().filter_map(|m| m.slave_chat_id_option()).collect_vec();
+    for cid in slave_cwds.keys() {
+        ensure!(slave_chat_id_merges.contains(cid), "Slave chat {} wasn't mentioned in merges", cid.0);
+    }
+    ensure!(slave_cwds.len() == slave_chat_id_merges.len(), "Chat merges contained more slave chats than actually exist?");
+
+    Ok(((master_users, master_cwds), (slave_users, slave_cwds)))
+}
+
 struct DaoMergeEntities<'a> {
     dao: &'a dyn ChatHistoryDao,
     ds: &'a Dataset,
@@ -80,17 +156,19 @@ struct DaoMergeEntities<'a> {
 }
 
 fn merge_inner(
-    new_dao: &mut SqliteDao,
+    new_dao: &mut dyn MutableChatHistoryDao,
     master: DaoMergeEntities,
     slave: DaoMergeEntities,
     user_merges: Vec<UserMergeDecision>,
     chat_merges: Vec<ChatMergeDecision>,
-) -> Result<Dataset> {
+    slave_time_shifts: &HashMap<ChatId, i64>,
+) -> Result<(Dataset, MergeReport)> {
     let new_ds = Dataset {
         uuid: PbUuid::random(),
         alias: format!("{} (merged)", master.ds.alias),
     };
     let new_ds = new_dao.insert_dataset(new_ds)?;
+    let mut report = MergeReport::new(master.ds, slave.ds, &new_ds, false);
 
     let master_ds_root = master.dao.dataset_root(&master.ds.uuid)?;
     let slave_ds_root = slave.dao.dataset_root(&slave.ds.uuid)?;
@@ -117,11 +195,25 @@ fn merge_inner(
                     &master_ds_root
                 };
 
+                // Preserve sub-chat relation (e.g. combined chats or forum topics) if slave doesn't have one
+                if chat_to_insert.chat.main_chat_id.is_none() {
+                    chat_to_insert.chat.main_chat_id = master.cwds[chat_id].chat.main_chat_id;
+                }
+
                 Some((chat_to_insert, ds_root, cm))
             }
         }
     }).collect_vec();
 
+    // Sub-chats whose main chat was skipped become main chats themselves
+    let inserted_chat_ids: HashSet<i64> = chat_inserts.iter().map(|(cwd, _, _)| cwd.chat.id).collect();
+    let chat_inserts = chat_inserts.into_iter().map(|(mut cwd, ds_root, cm)| {
+        if cwd.chat.main_chat_id.is_some_and(|id| !inserted_chat_ids.contains(&id)) {
+            cwd.chat.main_chat_id = None;
+        }
+        (cwd, ds_root, cm)
+    }).collect_vec();
+
     // Users
     let selected_chat_members: HashSet<i64> =
         chat_inserts.iter().flat_map(|(cwd, _, _)| cwd.chat.member_ids.clone()).collect();
@@ -143,16 +235,20 @@ fn merge_inner(
             UserMergeDecision::MatchOrDontReplace(user_id) =>
                 Some((master.users[&user_id].clone(),
                       iter_pps_slave!(user_id).chain(iter_pps_master!(user_id)).collect_vec())),
-            UserMergeDecision::Add(user_id) =>
+            UserMergeDecision::Add(user_id) => {
+                report.add_user(&slave.users[&user_id], UserMergeKind::Add);
                 Some((slave.users[&user_id].clone(),
-                      iter_pps_slave!(user_id).collect_vec())),
+                      iter_pps_slave!(user_id).collect_vec()))
+            }
             UserMergeDecision::DontAdd(user_id) if selected_chat_members.contains(&user_id.0) =>
                 bail!("Cannot skip user {} because it's used in a chat that wasn't skipped", user_id.0),
             UserMergeDecision::DontAdd(_) =>
                 None,
-            UserMergeDecision::Replace(user_id) =>
+            UserMergeDecision::Replace(user_id) => {
+                report.add_user(&slave.users[&user_id], UserMergeKind::Replace);
                 Some((slave.users[&user_id].clone(),
-                      iter_pps_slave!(user_id).chain(iter_pps_master!(user_id)).collect_vec())),
+                      iter_pps_slave!(user_id).chain(iter_pps_master!(user_id)).collect_vec()))
+            }
         };
         if let Some((mut user, profile_pics)) = user_to_insert_option {
             user.ds_uuid = new_ds.uuid.clone();
@@ -168,21 +264,7 @@ fn merge_inner(
     for (mut cwd, chat_ds_root, cm) in chat_inserts {
         cwd.chat.ds_uuid = new_ds.uuid.clone();
 
-        // For merged personal chats, name should match whatever user name was chosen
-        if cwd.chat.tpe == ChatType::Personal as i32 {
-            let interlocutors = cwd.members.iter().filter(|u| u.id != master_self.id).collect_vec();
-            if interlocutors.len() > 1 {
-                bail!("Personal chat {} has multiple other members: {:?}",
-                      cwd.chat.qualified_name(), interlocutors.iter().map(|u| u.id).collect_vec())
-            }
-            // Could happen e.g. if other members never wrote anything.
-            if !interlocutors.is_empty() {
-                let final_user = final_users.iter().find(|u| u.id == interlocutors[0].id).with_context(||
-                format!("User {} not found among final users! Personal chat should've been skipped",
-                        interlocutors[0].id))?;
-                cwd.chat.name_option = final_user.pretty_name_option();
-            }
-        }
+        fixup_personal_chat_name(&mut cwd, master_self.id, &final_users)?;
 
         let mut new_chat = new_dao.insert_chat(cwd.chat.clone(), chat_ds_root)?;
 
@@ -191,143 +273,565 @@ fn merge_inner(
 
         // Messages
         let mut msg_count = 0;
-        match cm {
-            ChatMergeDecision::Retain { .. } =>
-                msg_count += copy_all_messages(master.dao, master_cwd!(),
-                                               &master_ds_root, new_dao, &new_chat,
-                                               &final_users)?,
-            ChatMergeDecision::DontMerge { .. } =>
-                msg_count += copy_all_messages(master.dao, master_cwd!(),
-                                               &master_ds_root, new_dao, &new_chat,
-                                               &final_users)?,
-            ChatMergeDecision::Add { .. } =>
-                msg_count += copy_all_messages(slave.dao, slave_cwd!(),
-                                               &slave_ds_root, new_dao, &new_chat,
-                                               &final_users)?,
+        let chat_report = match cm {
+            ChatMergeDecision::Retain { .. } => {
+                let mut chat_report = ChatMergeReport::new(&new_chat, ChatMergeKind::Retain);
+                chat_report.add_whole_chat(MessagesMergeKind::Retain, master.dao, master_cwd!())?;
+                msg_count += copy_all_messages(master.dao, master_cwd!(), &master_ds_root,
+                                               &final_users, &mut chat_report, true,
+                                               &mut |batch| new_dao.insert_messages(batch, &new_chat, &master_ds_root))?;
+                chat_report
+            }
+            ChatMergeDecision::DontMerge { .. } => {
+                let mut chat_report = ChatMergeReport::new(&new_chat, ChatMergeKind::DontMerge);
+                chat_report.add_whole_chat(MessagesMergeKind::Retain, master.dao, master_cwd!())?;
+                msg_count += copy_all_messages(master.dao, master_cwd!(), &master_ds_root,
+                                               &final_users, &mut chat_report, true,
+                                               &mut |batch| new_dao.insert_messages(batch, &new_chat, &master_ds_root))?;
+                chat_report
+            }
+            ChatMergeDecision::Add { .. } => {
+                let mut chat_report = ChatMergeReport::new(&new_chat, ChatMergeKind::Add);
+                chat_report.add_whole_chat(MessagesMergeKind::Add, slave.dao, slave_cwd!())?;
+                msg_count += copy_all_messages(slave.dao, slave_cwd!(), &slave_ds_root,
+                                               &final_users, &mut chat_report, false,
+                                               &mut |batch| new_dao.insert_messages(batch, &new_chat, &slave_ds_root))?;
+                chat_report
+            }
             ChatMergeDecision::DontAdd { .. } =>
                 unreachable!(),
             ChatMergeDecision::Merge { message_merges, .. } => {
-                let master_cwd = master_cwd!();
-                let slave_cwd = slave_cwd!();
+                let mut chat_report = ChatMergeReport::new(&new_chat, ChatMergeKind::Merge);
+                let master_side = MergedChatSide { dao: master.dao, ds_root: &master_ds_root, cwd: master_cwd!() };
+                let slave_side = MergedChatSide { dao: slave.dao, ds_root: &slave_ds_root, cwd: slave_cwd!() };
+                let slave_time_shift_sec = slave_time_shifts.get(&cwd.id()).copied().unwrap_or(0);
+
+                for merge_decision in message_merges {
+                    chat_report.add_section(merge_decision,
+                                            master.dao, &master_side.cwd.chat,
+                                            slave.dao, &slave_side.cwd.chat, slave_time_shift_sec)?;
+                    let merged = merge_messages(merge_decision, &master_side, &slave_side, slave_time_shift_sec)?;
+                    for (source, msgs) in merged {
+                        let side = match source {
+                            Source::Master | Source::UpdatedMaster => &master_side,
+                            Source::Slave => &slave_side,
+                        };
+
+                        msg_count += msgs.len();
+                        chat_report.add_missing_files(&msgs, side.ds_root, source != Source::Slave);
+                        for batch in &msgs.into_iter().chunks(BATCH_SIZE) {
+                            let mut batch = batch.collect_vec();
+                            for m in batch.iter_mut() {
+                                fixup_members(m, &final_users, side.cwd)?;
+                            }
+                            let copied_files = new_dao.insert_messages(batch, &new_chat, side.ds_root)?;
+                            chat_report.add_copied_files(copied_files, source != Source::Slave);
+                        }
+                    }
+                }
+                chat_report
+            }
+        };
+        new_chat.msg_count = msg_count as i32;
+        new_dao.update_chat(new_chat.id(), new_chat)?;
+        report.chats.push(chat_report);
+    }
+    add_skipped_chats_to_report(&mut report, &slave, &chat_merges)?;
+
+    Ok((new_ds, report))
+}
+
+/// In-place merge, planned upfront since master dataset can't be read while it's being written.
+struct InPlaceMergePlan<'a> {
+    myself_id: i64,
+    master_ds_root: DatasetRoot,
+    slave_ds_root: DatasetRoot,
+    final_users: Vec<User>,
+    chats: Vec<InPlaceChatPlan<'a>>,
+}
+
+enum InPlaceChatPlan<'a> {
+    Add {
+        chat: Chat,
+        slave_cwd: &'a ChatWithDetails,
+        report_idx: usize,
+    },
+    Merge {
+        chat: Chat,
+        changes: Vec<InPlaceMessageChange>,
+        report_idx: usize,
+    },
+}
 
-                #[derive(Clone, Copy, PartialEq)]
-                enum Source { Master, Slave }
+/// Change to messages of a merged chat, in chat order.
+enum InPlaceMessageChange {
+    /// Master messages (of this chat) with internal IDs in range that stay as they are
+    Keep(MessageInternalId, MessageInternalId),
+    /// Master message to be overwritten in place, keeping its internal ID
+    Update(Message, Source),
+    /// Master messages (of this chat) with internal IDs in range that are gone
+    Delete(MessageInternalId, MessageInternalId),
+    /// New slave messages
+    Insert(Vec<Message>),
+}
+
+impl InPlaceMessageChange {
+    fn push_keep(changes: &mut Vec<InPlaceMessageChange>, id: MessageInternalId) {
+        match changes.last_mut() {
+            Some(InPlaceMessageChange::Keep(_, last)) => *last = id,
+            _ => changes.push(InPlaceMessageChange::Keep(id, id)),
+        }
+    }
+}
+
+fn plan_in_place_merge<'a>(
+    master: &DaoMergeEntities,
+    slave: &'a DaoMergeEntities,
+    user_merges: &[UserMergeDecision],
+    chat_merges: &[ChatMergeDecision],
+    slave_time_shifts: &HashMap<ChatId, i64>,
+    report: &mut MergeReport,
+) -> Result<InPlaceMergePlan<'a>> {
+    let master_ds_root = master.dao.dataset_root(&master.ds.uuid)?;
+    let slave_ds_root = slave.dao.dataset_root(&slave.ds.uuid)?;
+
+    // Master chats are never removed, so only added chats extend the set of chats and their members
+    let added_slave_cwds = chat_merges.iter().filter_map(|cm| match cm {
+        ChatMergeDecision::Add { slave_chat_id } => Some(&slave.cwds[slave_chat_id]),
+        _ => None,
+    }).collect_vec();
+    let final_chat_ids: HashSet<i64> =
+        master.cwds.keys().map(|id| id.0).chain(added_slave_cwds.iter().map(|cwd| cwd.id().0)).collect();
+
+    // Users
+    let selected_chat_members: HashSet<i64> = chat_merges.iter().flat_map(|cm| match cm {
+        ChatMergeDecision::Retain { master_chat_id } => master.cwds[master_chat_id].chat.member_ids.clone(),
+        ChatMergeDecision::DontMerge { chat_id } => master.cwds[chat_id].chat.member_ids.clone(),
+        ChatMergeDecision::Add { slave_chat_id } => slave.cwds[slave_chat_id].chat.member_ids.clone(),
+        ChatMergeDecision::DontAdd { .. } => vec![],
+        ChatMergeDecision::Merge { chat_id, .. } => slave.cwds[chat_id].chat.member_ids.clone(),
+    }).collect();
+    let master_self = master.dao.myself(&master.ds.uuid)?;
+    let slave_self = slave.dao.myself(&slave.ds.uuid)?;
+    ensure!(master_self.id == slave_self.id, "Myself of merged datasets doesn't match!");
+    let mut final_users = Vec::with_capacity(user_merges.len());
+    for um in user_merges {
+        match um {
+            UserMergeDecision::Retain(user_id) | UserMergeDecision::MatchOrDontReplace(user_id) =>
+                final_users.push(master.users[user_id].clone()),
+            UserMergeDecision::Add(user_id) => {
+                report.add_user(&slave.users[user_id], UserMergeKind::Add);
+                let mut user = slave.users[user_id].clone();
+                user.ds_uuid = master.ds.uuid.clone();
+                final_users.push(user);
+            }
+            UserMergeDecision::Replace(user_id) => {
+                report.add_user(&slave.users[user_id], UserMergeKind::Replace);
+                let mut user = slave.users[user_id].clone();
+                user.ds_uuid = master.ds.uuid.clone();
+                final_users.push(user);
+            }
+            UserMergeDecision::DontAdd(user_id) if selected_chat_members.contains(&user_id.0) =>
+                bail!("Cannot skip user {} because it's used in a chat that wasn't skipped", user_id.0),
+            UserMergeDecision::DontAdd(_) => { /* NOOP */ }
+        }
+    }
+
+    // Chats
+    let mut chats = vec![];
+    for cm in chat_merges {
+        match cm {
+            ChatMergeDecision::Retain { master_chat_id } => {
+                let mut chat_report = ChatMergeReport::new(&master.cwds[master_chat_id].chat, ChatMergeKind::Retain);
+                chat_report.add_whole_chat(MessagesMergeKind::Retain, master.dao, &master.cwds[master_chat_id])?;
+                report.chats.push(chat_report);
+            }
+            ChatMergeDecision::DontMerge { chat_id } => {
+                let mut chat_report = ChatMergeReport::new(&master.cwds[chat_id].chat, ChatMergeKind::DontMerge);
+                chat_report.add_whole_chat(MessagesMergeKind::Retain, master.dao, &master.cwds[chat_id])?;
+                report.chats.push(chat_report);
+            }
+            ChatMergeDecision::DontAdd { .. } => {
+                /* NOOP */
+            }
+            ChatMergeDecision::Add { slave_chat_id } => {
+                let slave_cwd = &slave.cwds[slave_chat_id];
+                let mut cwd = slave_cwd.clone();
+                cwd.chat.ds_uuid = master.ds.uuid.clone();
+                if cwd.chat.main_chat_id.is_some_and(|id| !final_chat_ids.contains(&id)) {
+                    cwd.chat.main_chat_id = None;
+                }
+                fixup_personal_chat_name(&mut cwd, master_self.id, &final_users)?;
+
+                let mut chat_report = ChatMergeReport::new(&cwd.chat, ChatMergeKind::Add);
+                chat_report.add_whole_chat(MessagesMergeKind::Add, slave.dao, slave_cwd)?;
+                chats.push(InPlaceChatPlan::Add { chat: cwd.chat, slave_cwd, report_idx: report.chats.len() });
+                report.chats.push(chat_report);
+            }
+            ChatMergeDecision::Merge { chat_id, message_merges } => {
+                let master_cwd = &master.cwds[chat_id];
+                let slave_cwd = &slave.cwds[chat_id];
+                let mut cwd = slave_cwd.clone();
+                cwd.chat.ds_uuid = master.ds.uuid.clone();
+                cwd.chat.img_path_option = master_cwd.chat.img_path_option.clone();
+                if cwd.chat.main_chat_id.is_none() {
+                    cwd.chat.main_chat_id = master_cwd.chat.main_chat_id;
+                }
+                if cwd.chat.main_chat_id.is_some_and(|id| !final_chat_ids.contains(&id)) {
+                    cwd.chat.main_chat_id = None;
+                }
+                fixup_personal_chat_name(&mut cwd, master_self.id, &final_users)?;
+
+                // Note: As with a regular merge, we might be loading too much into memory at once.
+                let master_side = MergedChatSide { dao: master.dao, ds_root: &master_ds_root, cwd: master_cwd };
+                let slave_side = MergedChatSide { dao: slave.dao, ds_root: &slave_ds_root, cwd: slave_cwd };
+                let slave_time_shift_sec = slave_time_shifts.get(chat_id).copied().unwrap_or(0);
+                let mut chat_report = ChatMergeReport::new(&cwd.chat, ChatMergeKind::Merge);
+                let mut msg_count = 0;
+                let mut changes = vec![];
+
+                // Master message is kept unless it's changed, including by members fixup
+                let keep_or_update = |mut m: Message, source: Source, changes: &mut Vec<InPlaceMessageChange>,
+                                          chat_report: &mut ChatMergeReport| -> EmptyRes {
+                    let original_option = (source == Source::Master).then(|| m.clone());
+                    fixup_members(&mut m, &final_users, if source == Source::Slave { slave_cwd } else { master_cwd })?;
+                    if original_option.is_some_and(|original| original == m) {
+                        InPlaceMessageChange::push_keep(changes, m.internal_id());
+                    } else {
+                        let ds_root = if source == Source::Slave { &slave_ds_root } else { &master_ds_root };
+                        chat_report.add_missing_files(std::slice::from_ref(&m), ds_root, source != Source::Slave);
+                        changes.push(InPlaceMessageChange::Update(m, source));
+                    }
+                    Ok(())
+                };
+                let insert = |mut msgs: Vec<Message>, changes: &mut Vec<InPlaceMessageChange>,
+                              chat_report: &mut ChatMergeReport| -> EmptyRes {
+                    if msgs.is_empty() { return Ok(()); }
+                    for m in msgs.iter_mut() {
+                        fixup_members(m, &final_users, slave_cwd)?;
+                    }
+                    chat_report.add_missing_files(&msgs, &slave_ds_root, false);
+                    changes.push(InPlaceMessageChange::Insert(msgs));
+                    Ok(())
+                };
 
                 for merge_decision in message_merges {
-                    let inserts: Vec<(Source, Vec<Message>)> = match merge_decision {
+                    chat_report.add_section(merge_decision, master.dao, &master_cwd.chat,
+                                            slave.dao, &slave_cwd.chat, slave_time_shift_sec)?;
+                    match merge_decision {
                         MessagesMergeDecision::Match(v) => {
-                            // While messages match, our matching rules allow either master or slave
-                            // to have missing content.
-                            // We keep master messages (updated with some data from slave) unless slave has new content.
-                            //
-                            // Note: We might be loading too much into memory at once!
-                            // However, messages memory footprint is pretty small, so this isn't a big concern now.
-                            let master_msgs =
-                                master.dao.messages_slice(&master_cwd.chat,
-                                                          v.first_master_msg_id.generalize(),
-                                                          v.last_master_msg_id.generalize())?;
-                            let slave_msgs =
-                                slave.dao.messages_slice(&slave_cwd.chat,
-                                                         v.first_slave_msg_id.generalize(),
-                                                         v.last_slave_msg_id.generalize())?;
+                            let master_msgs = master_side.messages_slice(v.first_master_msg_id.generalize(),
+                                                                         v.last_master_msg_id.generalize(), 0)?;
+                            let slave_msgs = slave_side.messages_slice(v.first_slave_msg_id.generalize(),
+                                                                       v.last_slave_msg_id.generalize(),
+                                                                       slave_time_shift_sec)?;
                             assert!(master_msgs.len() == slave_msgs.len());
-
-                            let grouped_total_msgs = master_msgs.into_iter().zip(slave_msgs)
-                                .map(|(mm, sm)| {
-                                    let m_tup = EntityCmpTuple::new(&mm, &master_ds_root, &master_cwd);
-                                    let s_tup = EntityCmpTuple::new(&sm, &slave_ds_root, &slave_cwd);
-                                    match m_tup.compare(&s_tup).expect("Comparison should not fail during merge!") {
-                                        EntityCmpResult::Equal => {
-                                            let mut mm = mm;
-                                            // TODO: Do we still need this?
-                                            update_with_slave_data(&mut mm, &sm);
-                                            (mm, Source::Master)
-                                        }
-                                        EntityCmpResult::LeftHasMore =>
-                                            (mm, Source::Master),
-                                        EntityCmpResult::RightHasMore =>
-                                            (sm, Source::Slave),
-                                        EntityCmpResult::Conflict =>
-                                            unreachable!("Messages are supposed to be matching! {:?} vs {:?}", mm, sm)
-                                    }
-                                })
-                                .chunk_by(|(_m, src)| *src);
-
-                            let mut data_grouped = Vec::new();
-                            for (source, group) in &grouped_total_msgs {
-                                data_grouped.push((source, group.into_iter().map(|msg_ds| msg_ds.0).collect_vec()));
+                            msg_count += master_msgs.len();
+                            for (mm, sm) in master_msgs.into_iter().zip(slave_msgs) {
+                                let master_internal_id = mm.internal_id;
+                                // Slave message taking place of master one keeps its internal ID
+                                let (mut m, source) = merge_matching_messages(mm, sm, &master_side, &slave_side);
+                                m.internal_id = master_internal_id;
+                                keep_or_update(m, source, &mut changes, &mut chat_report)?;
                             }
-                            data_grouped
                         }
-                        MessagesMergeDecision::Retain(v) => {
-                            let msgs = master.dao.messages_slice(&master_cwd.chat,
-                                                                 v.first_master_msg_id.generalize(),
-                                                                 v.last_master_msg_id.generalize())?;
-                            vec![(Source::Master, msgs)]
+                        MessagesMergeDecision::Retain(MergeAnalysisSectionRetention { first_master_msg_id, last_master_msg_id }) |
+                        MessagesMergeDecision::DontReplace(MergeAnalysisSectionConflict { first_master_msg_id, last_master_msg_id, .. }) => {
+                            let master_msgs = master_side.messages_slice(first_master_msg_id.generalize(),
+                                                                         last_master_msg_id.generalize(), 0)?;
+                            msg_count += master_msgs.len();
+                            for mm in master_msgs {
+                                keep_or_update(mm, Source::Master, &mut changes, &mut chat_report)?;
+                            }
                         }
                         MessagesMergeDecision::Add(v) => {
-                            let msgs = slave.dao.messages_slice(&slave_cwd.chat,
-                                                                v.first_slave_msg_id.generalize(),
-                                                                v.last_slave_msg_id.generalize())?;
-                            vec![(Source::Slave, msgs)]
+                            let slave_msgs = slave_side.messages_slice(v.first_slave_msg_id.generalize(),
+                                                                       v.last_slave_msg_id.generalize(),
+                                                                       slave_time_shift_sec)?;
+                            msg_count += slave_msgs.len();
+                            insert(slave_msgs, &mut changes, &mut chat_report)?;
                         }
                         MessagesMergeDecision::DontAdd(_) => {
                             // Skip these messages
-                            vec![]
                         }
                         MessagesMergeDecision::Replace(v) => {
-                            // Treat exactly as Add
-                            // TODO: Should we analyze content and make sure nothing is lost?
-                            let msgs = slave.dao.messages_slice(&slave_cwd.chat,
-                                                                v.first_slave_msg_id.generalize(),
-                                                                v.last_slave_msg_id.generalize())?;
-                            vec![(Source::Slave, msgs)]
-                        }
-                        MessagesMergeDecision::DontReplace(v) => {
-                            // Treat exactly as Retain
-                            let msgs = master.dao.messages_slice(&master_cwd.chat,
-                                                                 v.first_master_msg_id.generalize(),
-                                                                 v.last_master_msg_id.generalize())?;
-                            vec![(Source::Master, msgs)]
+                            changes.push(InPlaceMessageChange::Delete(v.first_master_msg_id.generalize(),
+                                                                      v.last_master_msg_id.generalize()));
+                            let slave_msgs = slave_side.messages_slice(v.first_slave_msg_id.generalize(),
+                                                                       v.last_slave_msg_id.generalize(),
+                                                                       slave_time_shift_sec)?;
+                            msg_count += slave_msgs.len();
+                            insert(slave_msgs, &mut changes, &mut chat_report)?;
                         }
-                    };
+                    }
+                }
 
-                    for (source, msgs) in inserts {
-                        let ds_root = match source {
-                            Source::Master => &master_ds_root,
-                            Source::Slave => &slave_ds_root,
-                        };
-                        let cwd = match source {
-                            Source::Master => master_cwd,
-                            Source::Slave => slave_cwd
-                        };
+                cwd.chat.msg_count = msg_count as i32;
+                chats.push(InPlaceChatPlan::Merge { chat: cwd.chat, changes, report_idx: report.chats.len() });
+                report.chats.push(chat_report);
+            }
+        }
+    }
+    add_skipped_chats_to_report(report, slave, chat_merges)?;
 
-                        msg_count += msgs.len();
-                        for batch in &msgs.into_iter().chunks(BATCH_SIZE) {
-                            let mut batch = batch.collect_vec();
-                            for m in batch.iter_mut() {
-                                fixup_members(m, &final_users, cwd)?;
+    Ok(InPlaceMergePlan { myself_id: master_self.id, master_ds_root, slave_ds_root, final_users, chats })
+}
+
+fn apply_in_place_merge(
+    target: &mut dyn InPlaceMergeTarget,
+    master_users: &HashMap<UserId, User>,
+    slave: &DaoMergeEntities,
+    user_merges: &[UserMergeDecision],
+    plan: &InPlaceMergePlan,
+    report: &mut MergeReport,
+) -> EmptyRes {
+    // Users
+    let pps_master = |user_id: &UserId|
+        master_users[user_id].profile_pictures.iter().map(|pp| pp.to_absolute(&plan.master_ds_root)).collect_vec();
+    let pps_slave = |user_id: &UserId|
+        slave.users[user_id].profile_pictures.iter().map(|pp| pp.to_absolute(&plan.slave_ds_root)).collect_vec();
+    let final_user = |user_id: &UserId| plan.final_users.iter().find(|u| u.id() == *user_id).expect("User not among final users!").clone();
+    for um in user_merges {
+        // Slave pictures always go before master pics, same as in a regular merge
+        match um {
+            UserMergeDecision::Retain(_) | UserMergeDecision::DontAdd(_) => { /* NOOP */ }
+            UserMergeDecision::MatchOrDontReplace(user_id) => {
+                if !slave.users[user_id].profile_pictures.is_empty() {
+                    let profile_pics = dedup_profile_pics(pps_slave(user_id).into_iter().chain(pps_master(user_id)).collect())?;
+                    target.replace_user_profile_pics(*user_id, profile_pics)?;
+                }
+            }
+            UserMergeDecision::Add(user_id) => {
+                let user = final_user(user_id);
+                let is_myself = user.id == plan.myself_id;
+                target.insert_user(user, is_myself)?;
+                target.replace_user_profile_pics(*user_id, dedup_profile_pics(pps_slave(user_id))?)?;
+            }
+            UserMergeDecision::Replace(user_id) => {
+                target.update_user(final_user(user_id))?;
+                let profile_pics = dedup_profile_pics(pps_slave(user_id).into_iter().chain(pps_master(user_id)).collect())?;
+                target.replace_user_profile_pics(*user_id, profile_pics)?;
+            }
+        }
+    }
+
+    // Chats
+    for chat_plan in plan.chats.iter() {
+        match chat_plan {
+            InPlaceChatPlan::Add { chat, slave_cwd, report_idx } => {
+                let chat = target.insert_chat(chat.clone(), &plan.slave_ds_root)?;
+                copy_all_messages(slave.dao, slave_cwd, &plan.slave_ds_root, &plan.final_users,
+                                  &mut report.chats[*report_idx], false,
+                                  &mut |batch| target.insert_messages(batch, &chat, &plan.slave_ds_root))?;
+            }
+            InPlaceChatPlan::Merge { chat, changes, report_idx } => {
+                let chat_report = &mut report.chats[*report_idx];
+                let ds_root_of = |source: Source|
+                    if source == Source::Slave { &plan.slave_ds_root } else { &plan.master_ds_root };
+
+                // Once anything is appended, the rest of master messages has to be moved after it to keep the order
+                let mut appended = false;
+                for change in changes {
+                    match change {
+                        InPlaceMessageChange::Keep(first_id, last_id) => {
+                            if appended {
+                                target.move_messages_to_end(chat, *first_id, *last_id)?;
                             }
-                            new_dao.insert_messages(batch, &new_chat, ds_root)?;
+                        }
+                        InPlaceMessageChange::Update(m, source) => {
+                            let internal_id = m.internal_id();
+                            let copied_files = target.update_message(m.clone(), chat, ds_root_of(*source))?;
+                            chat_report.add_copied_files(copied_files, *source != Source::Slave);
+                            if appended {
+                                target.move_messages_to_end(chat, internal_id, internal_id)?;
+                            }
+                        }
+                        InPlaceMessageChange::Delete(first_id, last_id) => {
+                            target.delete_messages(chat, *first_id, *last_id)?;
+                        }
+                        InPlaceMessageChange::Insert(msgs) => {
+                            for batch in msgs.chunks(BATCH_SIZE) {
+                                let copied_files = target.insert_messages(batch.to_vec(), chat, &plan.slave_ds_root)?;
+                                chat_report.add_copied_files(copied_files, false);
+                            }
+                            appended = true;
                         }
                     }
                 }
+                target.update_chat(chat.clone())?;
             }
         }
-        new_chat.msg_count = msg_count as i32;
-        new_dao.update_chat(new_chat.id(), new_chat)?;
     }
+    Ok(())
+}
+
+/// Slave chats that weren't added don't appear in the merge result, but should still be reported.
+fn add_skipped_chats_to_report(report: &mut MergeReport, slave: &DaoMergeEntities, chat_merges: &[ChatMergeDecision]) -> EmptyRes {
+    for cm in chat_merges {
+        if let ChatMergeDecision::DontAdd { slave_chat_id } = cm {
+            let slave_cwd = &slave.cwds[slave_chat_id];
+            let mut chat_report = ChatMergeReport::new(&slave_cwd.chat, ChatMergeKind::DontAdd);
+            chat_report.add_whole_chat(MessagesMergeKind::DontAdd, slave.dao, slave_cwd)?;
+            report.chats.push(chat_report);
+        }
+    }
+    Ok(())
+}
 
-    Ok(new_ds)
+/// Chat being merged, as seen from either master or slave side.
+struct MergedChatSide<'a> {
+    dao: &'a dyn ChatHistoryDao,
+    ds_root: &'a DatasetRoot,
+    cwd: &'a ChatWithDetails,
 }
 
+impl MergedChatSide<'_> {
+    /// Messages with internal IDs in the given range (inclusive), shifted back in time by `time_shift_sec`.
+    fn messages_slice(&self, first_id: MessageInternalId, last_id: MessageInternalId,
+                      time_shift_sec: i64) -> Result<Vec<Message>> {
+        let mut msgs = self.dao.messages_slice(&self.cwd.chat, first_id, last_id)?;
+        for m in msgs.iter_mut() {
+            m.timestamp -= time_shift_sec;
+        }
+        Ok(msgs)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Master,
+    /// Master message updated with some data from slave
+    UpdatedMaster,
+    Slave,
+}
+
+/// Resolves a single messages merge decision into the resulting messages, grouped by their source.
+/// Slave messages are shifted back in time by `slave_time_shift_sec`.
+fn merge_messages(
+    merge_decision: &MessagesMergeDecision,
+    master: &MergedChatSide,
+    slave: &MergedChatSide,
+    slave_time_shift_sec: i64,
+) -> Result<Vec<(Source, Vec<Message>)>> {
+    let master_messages_slice = |first_id: MasterInternalId, last_id: MasterInternalId| {
+        master.messages_slice(first_id.generalize(), last_id.generalize(), 0)
+    };
+    let slave_messages_slice = |first_id: SlaveInternalId, last_id: SlaveInternalId| {
+        slave.messages_slice(first_id.generalize(), last_id.generalize(), slave_time_shift_sec)
+    };
+
+    Ok(match merge_decision {
+        MessagesMergeDecision::Match(v) => {
+            // While messages match, our matching rules allow either master or slave
+            // to have missing content.
+            // We keep master messages (updated with some data from slave) unless slave has new content.
+            //
+            // Note: We might be loading too much into memory at once!
+            // However, messages memory footprint is pretty small, so this isn't a big concern now.
+            let master_msgs = master_messages_slice(v.first_master_msg_id, v.last_master_msg_id)?;
+            let slave_msgs = slave_messages_slice(v.first_slave_msg_id, v.last_slave_msg_id)?;
+            assert!(master_msgs.len() == slave_msgs.len());
+
+            let grouped_total_msgs = master_msgs.into_iter().zip(slave_msgs)
+                .map(|(mm, sm)| merge_matching_messages(mm, sm, master, slave))
+                .chunk_by(|(_m, src)| *src);
+
+            let mut data_grouped = Vec::new();
+            for (source, group) in &grouped_total_msgs {
+                data_grouped.push((source, group.into_iter().map(|msg_ds| msg_ds.0).collect_vec()));
+            }
+            data_grouped
+        }
+        MessagesMergeDecision::Retain(v) => {
+            let msgs = master_messages_slice(v.first_master_msg_id, v.last_master_msg_id)?;
+            vec![(Source::Master, msgs)]
+        }
+        MessagesMergeDecision::Add(v) => {
+            let msgs = slave_messages_slice(v.first_slave_msg_id, v.last_slave_msg_id)?;
+            vec![(Source::Slave, msgs)]
+        }
+        MessagesMergeDecision::DontAdd(_) => {
+            // Skip these messages
+            vec![]
+        }
+        MessagesMergeDecision::Replace(v) => {
+            // Treat exactly as Add
+            // TODO: Should we analyze content and make sure nothing is lost?
+            let msgs = slave_messages_slice(v.first_slave_msg_id, v.last_slave_msg_id)?;
+            vec![(Source::Slave, msgs)]
+        }
+        MessagesMergeDecision::DontReplace(v) => {
+            // Treat exactly as Retain
+            let msgs = master_messages_slice(v.first_master_msg_id, v.last_master_msg_id)?;
+            vec![(Source::Master, msgs)]
+        }
+    })
+}
+
+/// Resolves a pair of matching messages into the resulting message.
+/// While messages match, our matching rules allow either master or slave to have missing content.
+/// We keep master message (updated with some data from slave) unless slave has new content.
+fn merge_matching_messages(mm: Message, sm: Message, master: &MergedChatSide, slave: &MergedChatSide) -> (Message, Source) {
+    let m_tup = EntityCmpTuple::new(&mm, master.ds_root, master.cwd);
+    let s_tup = EntityCmpTuple::new(&sm, slave.ds_root, slave.cwd);
+    match m_tup.compare(&s_tup).expect("Comparison should not fail during merge!") {
+        EntityCmpResult::Equal => {
+            let mut updated_mm = mm.clone();
+            // TODO: Do we still need this?
+            update_with_slave_data(&mut updated_mm, &sm);
+            if updated_mm == mm {
+                (mm, Source::Master)
+            } else {
+                (updated_mm, Source::UpdatedMaster)
+            }
+        }
+        EntityCmpResult::LeftHasMore => {
+            let mut updated_mm = mm.clone();
+            merge_views_and_reactions(&mut updated_mm, &sm);
+            if updated_mm == mm {
+                (mm, Source::Master)
+            } else {
+                (updated_mm, Source::UpdatedMaster)
+            }
+        }
+        EntityCmpResult::RightHasMore => {
+            let mut sm = sm;
+            merge_views_and_reactions(&mut sm, &mm);
+            (sm, Source::Slave)
+        }
+        EntityCmpResult::Conflict =>
+            unreachable!("Messages are supposed to be matching! {:?} vs {:?}", mm, sm)
+    }
+}
+
+/// For merged personal chats, name should match whatever user name was chosen.
+fn fixup_personal_chat_name(cwd: &mut ChatWithDetails, myself_id: i64, final_users: &[User]) -> EmptyRes {
+    if cwd.chat.tpe == ChatType::Personal as i32 {
+        let interlocutors = cwd.members.iter().filter(|u| u.id != myself_id).collect_vec();
+        if interlocutors.len() > 1 {
+            bail!("Personal chat {} has multiple other members: {:?}",
+                  cwd.chat.qualified_name(), interlocutors.iter().map(|u| u.id).collect_vec())
+        }
+        // Could happen e.g. if other members never wrote anything.
+        if !interlocutors.is_empty() {
+            let final_user = final_users.iter().find(|u| u.id == interlocutors[0].id).with_context(||
+            format!("User {} not found among final users! Personal chat should've been skipped",
+                    interlocutors[0].id))?;
+            cwd.chat.name_option = final_user.pretty_name_option();
+        }
+    }
+    Ok(())
+}
+
+/// Copies all messages of a chat in batches, each passed to `insert` which returns the source files it copied.
 fn copy_all_messages(
     src_dao: &dyn ChatHistoryDao,
     src_cwd: &ChatWithDetails,
     src_ds_root: &DatasetRoot,
-    dst_dao: &mut SqliteDao,
-    dst_chat: &Chat,
     final_users: &[User],
+    chat_report: &mut ChatMergeReport,
+    from_master: bool,
+    insert: &mut dyn FnMut(Vec<Message>) -> Result<Vec<String>>,
 ) -> Result<usize> {
     let mut offset = 0_usize;
     let mut msg_count = 0_usize;
@@ -335,10 +839,12 @@ fn copy_all_messages(
         let mut batch = src_dao.scroll_messages(&src_cwd.chat, offset, BATCH_SIZE)?;
         if batch.is_empty() { break; }
         msg_count += batch.len();
+        chat_report.add_missing_files(&batch, src_ds_root, from_master);
         for m in batch.iter_mut() {
             fixup_members(m, final_users, src_cwd)?;
         }
-        dst_dao.insert_messages(batch, dst_chat, src_ds_root)?;
+        let copied_files = insert(batch)?;
+        chat_report.add_copied_files(copied_files, from_master);
         offset += BATCH_SIZE;
     }
     Ok(msg_count)
@@ -390,11 +896,13 @@ fn fixup_members(msg: &mut Message, final_users: &[User], cwd: &ChatWithDetails)
 /// * Source message ID
 /// * File name (if present)
 ///
+/// Views count and reactions are combined, see `merge_views_and_reactions`.
 ///
 /// Messages are assumed to be matching.
 /// Rationale for file name is that newer version may reveal more accurate info.
 fn update_with_slave_data(mm: &mut Message, sm: &Message) {
     mm.source_id_option = sm.source_id_option;
+    merge_views_and_reactions(mm, sm);
     match (mm.typed_mut(), sm.typed()) {
         (message::Typed::Regular(mmr), message::Typed::Regular(smr)) => {
             mmr.reply_to_message_id_option = smr.reply_to_message_id_option;
@@ -416,6 +924,31 @@ fn update_with_slave_data(mm: &mut Message, sm: &Message) {
     }
 }
 
+/// Views count and reactions accumulate over time, so for matching messages we keep the most of both.
+/// Reactions are matched by their emoji, reacting users are combined.
+fn merge_views_and_reactions(target: &mut Message, other: &Message) {
+    let (message::Typed::Regular(target), message::Typed::Regular(other)) = (target.typed_mut(), other.typed()) else {
+        return;
+    };
+    target.views_option = target.views_option.max(other.views_option);
+    for r in other.reactions.iter() {
+        let existing = target.reactions.iter_mut().find(|tr| {
+            tr.emoji_option == r.emoji_option && tr.custom_emoji_id_option == r.custom_emoji_id_option
+        });
+        match existing {
+            Some(existing) => {
+                for from_id in r.from_ids.iter() {
+                    if !existing.from_ids.contains(from_id) {
+                        existing.from_ids.push(*from_id);
+                    }
+                }
+                existing.count = existing.count.max(r.count).max(existing.from_ids.len() as i32);
+            }
+            None => target.reactions.push(r.clone()),
+        }
+    }
+}
+
 /// Deduplicate profile pictures vec by content. Skips subsequent elements, ignoring framing.
 fn dedup_profile_pics(profile_pics: Vec<AbsoluteProfilePicture>) -> Result<Vec<AbsoluteProfilePicture>> {
     let mut seen = HashSet::new();
@@ -433,7 +966,7 @@ fn dedup_profile_pics(profile_pics: Vec<AbsoluteProfilePicture>) -> Result<Vec<A
     Ok(res)
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum UserMergeDecision {
     /// Only in master
     Retain(UserId),
@@ -472,7 +1005,7 @@ impl UserMergeDecision {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum ChatMergeDecision {
     /// Only in master
     Retain { master_chat_id: ChatId },
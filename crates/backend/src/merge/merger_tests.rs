@@ -200,6 +200,61 @@ fn merge_multiple_datasets() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn merge_sub_chats() -> EmptyRes {
+    let users = (1..=2).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let member_ids = users.iter().map(|u| u.id).collect_vec();
+    let cwm = |id: i64, main_chat_id: Option<i64>| ChatWithMessages {
+        chat: Chat {
+            main_chat_id,
+            ..create_group_chat(&ZERO_PB_UUID, id, "Chat", member_ids.clone(), 0)
+        },
+        messages: vec![],
+    };
+
+    let helper = MergerHelper::new_from_daos(
+        create_dao("One", users.clone(), vec![
+            cwm(1, None),
+            cwm(2, Some(1)),
+            cwm(3, Some(1)),
+        ], |_, _| {}, rng().random()),
+        create_dao("Two", users.clone(), vec![
+            cwm(1, None),
+            cwm(2, None),
+            cwm(3, Some(1)),
+            cwm(4, None),
+            cwm(5, Some(4)),
+        ], |_, _| {}, rng().random()),
+    );
+
+    let (new_dao, new_ds, _tmpdir) = merge(
+        &helper,
+        dont_replace_both_users(),
+        vec![
+            ChatMergeDecision::Merge { chat_id: ChatId(1), message_merges: vec![] },
+            ChatMergeDecision::Merge { chat_id: ChatId(2), message_merges: vec![] },
+            ChatMergeDecision::Merge { chat_id: ChatId(3), message_merges: vec![] },
+            ChatMergeDecision::DontAdd { slave_chat_id: ChatId(4) },
+            ChatMergeDecision::Add { slave_chat_id: ChatId(5) },
+        ],
+    );
+
+    let main_chat_ids = new_dao.chats(&new_ds.uuid)?.into_iter()
+        .map(|cwd| (cwd.chat.id, cwd.chat.main_chat_id))
+        .sorted()
+        .collect_vec();
+    assert_eq!(main_chat_ids, vec![
+        (1, None),
+        // Master relation is preserved
+        (2, Some(1)),
+        (3, Some(1)),
+        // Main chat was not added
+        (5, None),
+    ]);
+
+    Ok(())
+}
+
 #[test]
 fn merge_chats_match_single_message() -> EmptyRes {
     let mut msg = create_regular_message(1, 1);
@@ -731,8 +786,8 @@ fn merge_chats_merge_all_modes() -> EmptyRes {
             ],
         }
     ];
-    let (new_dao, new_ds, _tmpdir) =
-        merge(&helper, dont_replace_both_users(), chat_merges);
+    let (new_dao, new_ds, report, tmpdir) =
+        merge_reported(&helper, dont_replace_both_users(), chat_merges, &HashMap::new());
     let new_ds_root = new_dao.dataset_root(&new_ds.uuid)?;
 
     let new_chats = new_dao.chats(&new_ds.uuid)?;
@@ -743,6 +798,45 @@ fn merge_chats_merge_all_modes() -> EmptyRes {
     assert_eq!(new_messages.len(), 5);
     assert_eq!(new_chat.msg_count, 5);
 
+    // Report
+    assert_eq!(report.new_ds_uuid, new_ds.uuid.value);
+    assert!(report.users.is_empty());
+    assert_eq!(report.chats.len(), 1);
+    let chat_report = &report.chats[0];
+    assert_eq!(chat_report.tpe, ChatMergeKind::Merge);
+    assert_eq!((chat_report.matched_msgs, chat_report.retained_msgs, chat_report.added_msgs,
+                chat_report.replaced_msgs, chat_report.skipped_msgs), (1, 2, 1, 1, 2));
+    assert_eq!(chat_report.sections.iter().map(|s| s.tpe).collect_vec(), vec![
+        MessagesMergeKind::Retain, MessagesMergeKind::Add, MessagesMergeKind::DontAdd,
+        MessagesMergeKind::Match, MessagesMergeKind::DontReplace, MessagesMergeKind::Replace,
+    ]);
+    let replace_section = &chat_report.sections[5];
+    let master_msg6 = &helper.m.msgs[&src_id(6)].0;
+    assert_eq!(replace_section.master_range, Some(MessageRangeReport {
+        msg_count: 1,
+        first_source_id: master_msg6.source_id_option,
+        first_timestamp: master_msg6.timestamp,
+        last_source_id: master_msg6.source_id_option,
+        last_timestamp: master_msg6.timestamp,
+    }));
+    let expected_copied_files = [2, 6].into_iter()
+        .flat_map(|id| helper.s.msgs[&src_id(id)].0.files_relative().into_iter().map(|p| p.to_owned()))
+        .collect_vec();
+    assert!(!expected_copied_files.is_empty());
+    assert_eq!(chat_report.copied_files, expected_copied_files);
+    assert!(chat_report.missing_files.is_empty());
+
+    let report_files = tmpdir.path.read_dir()?
+        .map(|e| e.unwrap().file_name().to_str().unwrap().to_owned())
+        .filter(|name| name.starts_with("merge_report_"))
+        .sorted()
+        .collect_vec();
+    assert_eq!(report_files.len(), 2);
+    assert!(report_files[0].ends_with(".html"));
+    assert!(report_files[1].ends_with(".json"));
+    let json = fs::read_to_string(tmpdir.path.join(&report_files[1]))?;
+    assert!(json.contains(r#""tpe": "dont_replace""#));
+
     let expected = vec![
         EntityCmpTuple::new(&helper.m.msgs[&src_id(1)].0, &helper.m.ds_root, helper.m.cwd()),
         EntityCmpTuple::new(&helper.s.msgs[&src_id(2)].0, &helper.s.ds_root, helper.s.cwd()),
@@ -759,6 +853,62 @@ fn merge_chats_merge_all_modes() -> EmptyRes {
     Ok(())
 }
 
+/**
+ * ```text
+ * Master messages - 1
+ * Slave messages  - 1  2c 3c
+ * Result messages - 1  2c 3c
+ * ```
+ * `Match(1), Add(2, 3)`, where messages 2 and 3 have different files with the same content.
+ * Files of message 3 are deduplicated into the ones of message 2, so they aren't reported as copied.
+ */
+#[test]
+fn merge_chats_report_deduplicated_files() -> EmptyRes {
+    let msgs = (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let helper = MergerHelper::new(
+        2, msgs.cloned([1].map(src_id)), msgs.cloned([1, 2, 3].map(src_id)),
+        &|is_master: bool, ds_root: &DatasetRoot, msg: &mut Message| {
+            if !is_master && msg.source_id().0 != 1 {
+                amend_with_content(msg, ContentMode::Full, ds_root, 42)
+            }
+        },
+        rng().random(),
+        rng().random()
+    );
+
+    let chat_merges = vec![
+        ChatMergeDecision::Merge {
+            chat_id: helper.m.cwd().id(),
+            message_merges: vec![
+                MessagesMergeDecision::Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: helper.m.msgs[&src_id(1)].typed_id(),
+                    last_master_msg_id: helper.m.msgs[&src_id(1)].typed_id(),
+                    first_slave_msg_id: helper.s.msgs[&src_id(1)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(1)].typed_id(),
+                }),
+                MessagesMergeDecision::Add(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: helper.s.msgs[&src_id(2)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                }),
+            ],
+        }
+    ];
+    let (new_dao, new_ds, report, _tmpdir) =
+        merge_reported(&helper, dont_replace_both_users(), chat_merges, &HashMap::new());
+
+    let new_chats = new_dao.chats(&new_ds.uuid)?;
+    let new_messages = new_dao.first_messages(&new_chats[0].chat, usize::MAX)?;
+    assert_eq!(new_messages.len(), 3);
+    assert_eq!(new_messages[1].files_relative(), new_messages[2].files_relative());
+
+    let slave_msg2_files = helper.s.msgs[&src_id(2)].0.files_relative();
+    assert_eq!(slave_msg2_files.len(), 2);
+    assert_ne!(slave_msg2_files, helper.s.msgs[&src_id(3)].0.files_relative());
+    assert_eq!(report.chats[0].copied_files, slave_msg2_files.into_iter().map(|p| p.to_owned()).collect_vec());
+
+    Ok(())
+}
+
 /// `Replace(1, n/2-1), DontReplace(n/2, ns)`
 #[test]
 fn merge_chats_merge_a_lot_of_messages() -> EmptyRes {
@@ -1182,6 +1332,139 @@ fn merge_chats_content_appended_on_match() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn merge_chats_reactions_appended_on_match() -> EmptyRes {
+    let msgs = (1..=4).map(|idx| create_regular_message(idx as usize, 1)).collect_vec();
+
+    // Master messages: odd messages have no reactions, even messages have fewer reactions
+    // Slave messages: have all reactions present
+    let helper = MergerHelper::new(
+        2, msgs.clone(), msgs,
+        &|is_master: bool, _ds_root: &DatasetRoot, msg: &mut Message| {
+            let source_id = msg.source_id_option.unwrap();
+            let message_regular_pat! { reactions, .. } = msg.typed_mut() else { unreachable!() };
+            *reactions = match (is_master, source_id % 2) {
+                (true, 1) => vec![],
+                (true, _) => vec![Reaction {
+                    emoji_option: Some("👍".to_owned()),
+                    custom_emoji_id_option: None,
+                    from_ids: vec![1],
+                    count: 1,
+                }],
+                (false, _) => vec![
+                    Reaction {
+                        emoji_option: Some("👍".to_owned()),
+                        custom_emoji_id_option: None,
+                        from_ids: vec![1, 2],
+                        count: 2,
+                    },
+                    Reaction {
+                        emoji_option: Some("🔥".to_owned()),
+                        custom_emoji_id_option: None,
+                        from_ids: vec![],
+                        count: 1,
+                    },
+                ],
+            };
+        },
+        rng().random(),
+        rng().random()
+    );
+
+    let chat_merges = vec![
+        ChatMergeDecision::Merge {
+            chat_id: helper.m.cwd().id(),
+            message_merges: vec![
+                MessagesMergeDecision::Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: first_id(&helper.m.msgs),
+                    last_master_msg_id: last_id(&helper.m.msgs),
+                    first_slave_msg_id: first_id(&helper.s.msgs),
+                    last_slave_msg_id: last_id(&helper.s.msgs),
+                }),
+            ],
+        }
+    ];
+    let (new_dao, new_ds, _tmpdir) =
+        merge(&helper, dont_replace_both_users(), chat_merges);
+
+    let new_chats = new_dao.chats(&new_ds.uuid)?;
+    assert_eq!(new_chats.len(), 1);
+
+    let new_messages = new_dao.first_messages(&new_chats[0].chat, usize::MAX)?;
+    assert_eq!(new_messages.len(), 4);
+
+    for (SlaveMessage(s_msg), new_msg) in helper.s.msgs.values().zip(new_messages.iter()) {
+        assert_eq!(new_msg.typed(), s_msg.typed());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn merge_chats_views_updated_on_match() -> EmptyRes {
+    let msgs = (1..=4).map(|idx| create_regular_message(idx as usize, 1)).collect_vec();
+
+    // Master messages: odd messages have no views count, even messages have higher count
+    // Slave messages: have lower views count, but also have a reaction
+    let helper = MergerHelper::new(
+        2, msgs.clone(), msgs,
+        &|is_master: bool, _ds_root: &DatasetRoot, msg: &mut Message| {
+            let source_id = msg.source_id_option.unwrap();
+            let message_regular_pat! { views_option, author_signature_option, reactions, .. } = msg.typed_mut()
+                else { unreachable!() };
+            *views_option = match (is_master, source_id % 2) {
+                (true, 1) => None,
+                (true, _) => Some(30),
+                (false, _) => Some(20),
+            };
+            *author_signature_option = Some("Admin".to_owned());
+            if !is_master {
+                *reactions = vec![Reaction {
+                    emoji_option: Some("👍".to_owned()),
+                    custom_emoji_id_option: None,
+                    from_ids: vec![],
+                    count: 5,
+                }];
+            }
+        },
+        rng().random(),
+        rng().random()
+    );
+
+    let chat_merges = vec![
+        ChatMergeDecision::Merge {
+            chat_id: helper.m.cwd().id(),
+            message_merges: vec![
+                MessagesMergeDecision::Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: first_id(&helper.m.msgs),
+                    last_master_msg_id: last_id(&helper.m.msgs),
+                    first_slave_msg_id: first_id(&helper.s.msgs),
+                    last_slave_msg_id: last_id(&helper.s.msgs),
+                }),
+            ],
+        }
+    ];
+    let (new_dao, new_ds, _tmpdir) =
+        merge(&helper, dont_replace_both_users(), chat_merges);
+
+    let new_chats = new_dao.chats(&new_ds.uuid)?;
+    let new_messages = new_dao.first_messages(&new_chats[0].chat, usize::MAX)?;
+    assert_eq!(new_messages.len(), 4);
+
+    // Views count only affects message equality in a sense that the highest one is kept
+    for (SlaveMessage(s_msg), new_msg) in helper.s.msgs.values().zip(new_messages.iter()) {
+        let mut s_msg = s_msg.clone();
+        let source_id = s_msg.source_id_option.unwrap();
+        let message_regular_pat! { views_option, .. } = s_msg.typed_mut() else { unreachable!() };
+        if source_id % 2 == 0 {
+            *views_option = Some(30);
+        }
+        assert_eq!(new_msg.typed(), s_msg.typed());
+    }
+
+    Ok(())
+}
+
 #[test]
 fn merge_chats_content_adopt_new_filename() -> EmptyRes {
     let msgs = vec![create_regular_message(0, 1), create_regular_message(1, 1)];
@@ -1262,6 +1545,218 @@ fn merge_chats_content_adopt_new_filename() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn merge_chats_slave_time_shifted() -> EmptyRes {
+    let msgs = (0..=3).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let shifted_msgs = msgs[1..].iter().cloned().map(|mut m| {
+        m.timestamp += 3600;
+        m
+    }).collect_vec();
+    let helper = MergerHelper::new_as_is(2, msgs[..3].to_vec(), shifted_msgs);
+
+    let chat_merges = vec![
+        ChatMergeDecision::Merge {
+            chat_id: helper.m.cwd().id(),
+            message_merges: vec![
+                MessagesMergeDecision::Retain(MergeAnalysisSectionRetention {
+                    first_master_msg_id: helper.m.msgs[&src_id(0)].typed_id(),
+                    last_master_msg_id: helper.m.msgs[&src_id(0)].typed_id(),
+                }),
+                MessagesMergeDecision::Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: helper.m.msgs[&src_id(1)].typed_id(),
+                    last_master_msg_id: helper.m.msgs[&src_id(2)].typed_id(),
+                    first_slave_msg_id: helper.s.msgs[&src_id(1)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(2)].typed_id(),
+                }),
+                MessagesMergeDecision::Add(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                }),
+            ],
+        }
+    ];
+    let slave_time_shifts = HashMap::from([(helper.s.cwd().id(), 3600)]);
+    let (new_dao, new_ds, report, _tmpdir) =
+        merge_reported(&helper, dont_replace_both_users(), chat_merges, &slave_time_shifts);
+
+    let new_chats = new_dao.chats(&new_ds.uuid)?;
+    assert_eq!(new_chats.len(), 1);
+    let new_messages = new_dao.first_messages(&new_chats[0].chat, usize::MAX)?;
+    assert_eq!(new_messages.iter().map(|m| (m.source_id_option, m.timestamp)).collect_vec(),
+               msgs.iter().map(|m| (m.source_id_option, m.timestamp)).collect_vec());
+
+    // Report slave ranges are shifted as well
+    let sections = &report.chats[0].sections;
+    assert_eq!(sections[1].slave_range.as_ref().map(|r| (r.first_timestamp, r.last_timestamp)),
+               Some((msgs[1].timestamp, msgs[2].timestamp)));
+    assert_eq!(sections[2].slave_range.as_ref().map(|r| (r.first_timestamp, r.last_timestamp)),
+               Some((msgs[3].timestamp, msgs[3].timestamp)));
+
+    Ok(())
+}
+
+/**
+ * ```text
+ * Master messages - 1  2  3
+ * Slave messages  - 1  2  3  4  5
+ * Result messages - 1  2  3  4  5
+ * ```
+ * `Match(1, 3), Add(4, 5)`, master messages should stay in place
+ */
+#[test]
+fn merge_in_place_appending_messages() -> EmptyRes {
+    let msgs = (1..=5).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let helper = MergerHelper::new_as_is(2, msgs[..3].to_vec(), msgs);
+    let (mut dao, _tmpdir) = copy_master_to_sqlite(&helper);
+    let cwd = dao.chats(&helper.m.ds.uuid)?.remove(0);
+    let old_msgs = dao.first_messages(&cwd.chat, usize::MAX)?;
+    let m_id = |src_id: i64| sqlite_master_id(&old_msgs, src_id);
+
+    let chat_merges = vec![
+        ChatMergeDecision::Merge {
+            chat_id: cwd.id(),
+            message_merges: vec![
+                MessagesMergeDecision::Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: m_id(1),
+                    last_master_msg_id: m_id(3),
+                    first_slave_msg_id: helper.s.msgs[&src_id(1)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                }),
+                MessagesMergeDecision::Add(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: helper.s.msgs[&src_id(4)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(5)].typed_id(),
+                }),
+            ],
+        }
+    ];
+    let report =
+        merge_in_place(&mut dao, &helper.m.ds, helper.s.dao_holder.dao.as_ref(), &helper.s.ds,
+                       dont_replace_both_users(), chat_merges, &HashMap::new())?;
+    let ds_root = dao.dataset_root(&helper.m.ds.uuid)?;
+
+    assert!(report.in_place);
+    assert_eq!(report.new_ds_uuid, helper.m.ds.uuid.value);
+    assert_eq!(report.chats.len(), 1);
+    assert_eq!((report.chats[0].matched_msgs, report.chats[0].added_msgs), (3, 2));
+    assert!(dao.storage_path().read_dir()?
+        .any(|e| e.unwrap().file_name().to_str().unwrap().starts_with("merge_report_")));
+
+    assert_eq!(dao.datasets()?, vec![helper.m.ds.clone()]);
+    let new_chats = dao.chats(&helper.m.ds.uuid)?;
+    assert_eq!(new_chats.len(), 1);
+    let new_chat = &new_chats[0].chat;
+    let new_messages = dao.first_messages(new_chat, usize::MAX)?;
+    assert_eq!(new_messages.len(), 5);
+    assert_eq!(new_chat.msg_count, 5);
+    assert_eq!(&new_messages[..3], old_msgs.as_slice());
+
+    for (slave_msg, new_msg) in helper.s.msgs.values().zip(new_messages.iter()) {
+        assert_cmp_equals(&slave_msg.0, &helper.s.ds_root, helper.s.cwd(),
+                          new_msg, &ds_root, &new_chats[0]);
+    }
+
+    // Backup should have been taken beforehand
+    assert!(dao.backup_path().read_dir()?.next().is_some());
+
+    Ok(())
+}
+
+/// Same as `merge_chats_merge_all_modes`, but in place. Result should be the same as for a regular merge.
+#[test]
+fn merge_in_place_all_modes() -> EmptyRes {
+    let msgs = (1..=6).map(|idx| create_regular_message(idx as usize, 1)).collect_vec();
+    let msgs_a =
+        msgs.cloned([1, 4, 5, 6].map(src_id));
+    let msgs_b = vec![
+        msgs.cloned([2, 3, 4].map(src_id)),
+        msgs.cloned([5, 6].map(src_id)).changed(|_| true),
+    ].into_iter().concat();
+    let helper = MergerHelper::new(
+        2, msgs_a, msgs_b,
+        &|_is_master: bool, ds_root: &DatasetRoot, msg: &mut Message| {
+            let seed = if msg.source_id().0 == 4 {
+                msg.source_id().0 as u64
+            } else {
+                rng().random()
+            };
+            amend_with_content(msg, ContentMode::Full, ds_root, seed)
+        },
+        rng().random(),
+        rng().random()
+    );
+    let (mut dao, _tmpdir) = copy_master_to_sqlite(&helper);
+    let cwd = dao.chats(&helper.m.ds.uuid)?.remove(0);
+    let old_msgs = dao.first_messages(&cwd.chat, usize::MAX)?;
+
+    let chat_merges = |m_id: &dyn Fn(i64) -> MasterInternalId| vec![
+        ChatMergeDecision::Merge {
+            chat_id: cwd.id(),
+            message_merges: vec![
+                MessagesMergeDecision::Retain(MergeAnalysisSectionRetention {
+                    first_master_msg_id: m_id(1),
+                    last_master_msg_id: m_id(1),
+                }),
+                MessagesMergeDecision::Add(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: helper.s.msgs[&src_id(2)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(2)].typed_id(),
+                }),
+                MessagesMergeDecision::DontAdd(MergeAnalysisSectionAddition {
+                    first_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                }),
+                MessagesMergeDecision::Match(MergeAnalysisSectionMatch {
+                    first_master_msg_id: m_id(4),
+                    last_master_msg_id: m_id(4),
+                    first_slave_msg_id: helper.s.msgs[&src_id(4)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(4)].typed_id(),
+                }),
+                MessagesMergeDecision::DontReplace(MergeAnalysisSectionConflict {
+                    first_master_msg_id: m_id(5),
+                    last_master_msg_id: m_id(5),
+                    first_slave_msg_id: helper.s.msgs[&src_id(5)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(5)].typed_id(),
+                }),
+                MessagesMergeDecision::Replace(MergeAnalysisSectionConflict {
+                    first_master_msg_id: m_id(6),
+                    last_master_msg_id: m_id(6),
+                    first_slave_msg_id: helper.s.msgs[&src_id(6)].typed_id(),
+                    last_slave_msg_id: helper.s.msgs[&src_id(6)].typed_id(),
+                }),
+            ],
+        }
+    ];
+    let (expected_dao, expected_ds, _expected_tmpdir) =
+        merge(&helper, dont_replace_both_users(), chat_merges(&|id| helper.m.msgs[&src_id(id)].typed_id()));
+    merge_in_place(&mut dao, &helper.m.ds, helper.s.dao_holder.dao.as_ref(), &helper.s.ds,
+                   dont_replace_both_users(), chat_merges(&|id| sqlite_master_id(&old_msgs, id)),
+                   &HashMap::new())?;
+
+    let expected_ds_root = expected_dao.dataset_root(&expected_ds.uuid)?;
+    let expected_cwd = expected_dao.chats(&expected_ds.uuid)?.remove(0);
+    let expected_messages = expected_dao.first_messages(&expected_cwd.chat, usize::MAX)?;
+
+    let ds_root = dao.dataset_root(&helper.m.ds.uuid)?;
+    let new_chats = dao.chats(&helper.m.ds.uuid)?;
+    assert_eq!(new_chats.len(), 1);
+    let new_chat = &new_chats[0].chat;
+    let new_messages = dao.first_messages(new_chat, usize::MAX)?;
+    assert_eq!(new_messages.len(), 5);
+    assert_eq!(new_chat.msg_count, 5);
+
+    // Retained message is left in place, the rest follow the added one
+    assert_eq!(new_messages[0], old_msgs[0]);
+    assert!(new_messages[1..].iter().all(|m| m.internal_id > old_msgs.last().unwrap().internal_id));
+    // Master messages that were kept as-is are moved rather than re-inserted, so their content rows remain
+    assert_eq!(new_messages[3], Message { internal_id: new_messages[3].internal_id, ..old_msgs[2].clone() });
+
+    for (expected_msg, new_msg) in expected_messages.iter().zip(new_messages.iter()) {
+        assert_cmp_equals(expected_msg, &expected_ds_root, &expected_cwd,
+                          new_msg, &ds_root, &new_chats[0]);
+    }
+
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -1297,9 +1792,18 @@ fn change_users(users: &[User], id_condition: fn(i64) -> bool) -> Vec<User> {
 fn merge(helper: &MergerHelper,
          user_merges: Vec<UserMergeDecision>,
          chat_merges: Vec<ChatMergeDecision>) -> (SqliteDao, Dataset, TmpDir) {
+    let (new_dao, new_ds, _report, new_dao_tmpdir) =
+        merge_reported(helper, user_merges, chat_merges, &HashMap::new());
+    (new_dao, new_ds, new_dao_tmpdir)
+}
+
+fn merge_reported(helper: &MergerHelper,
+                  user_merges: Vec<UserMergeDecision>,
+                  chat_merges: Vec<ChatMergeDecision>,
+                  slave_time_shifts: &HashMap<ChatId, i64>) -> (SqliteDao, Dataset, MergeReport, TmpDir) {
     let new_dao_tmpdir = TmpDir::new();
     log::info!("Using temp dir {} for Sqlite DAO", new_dao_tmpdir.path.display());
-    let (new_dao, new_ds) = merge_datasets(
+    let (new_dao, new_ds, report) = merge_datasets(
         &new_dao_tmpdir.path,
         helper.m.dao_holder.dao.as_ref(),
         &helper.m.ds,
@@ -1307,8 +1811,9 @@ fn merge(helper: &MergerHelper,
         &helper.s.ds,
         user_merges,
         chat_merges,
+        slave_time_shifts,
     ).unwrap();
-    (new_dao, new_ds, new_dao_tmpdir)
+    (new_dao, new_ds, report, new_dao_tmpdir)
 }
 
 fn make_random_video_content(ds_root: &DatasetRoot, none_paths: bool, seed: u64) -> Content {
@@ -1380,3 +1885,16 @@ fn amend_with_content(msg: &mut Message, mode: ContentMode, ds_root: &DatasetRoo
         }
     }
 }
+
+/// Creates a new SQLite database with a copy of the master dataset, to be merged into in place.
+fn copy_master_to_sqlite(helper: &MergerHelper) -> (SqliteDao, TmpDir) {
+    let tmp_dir = TmpDir::new();
+    let dao = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME)).unwrap();
+    dao.copy_datasets_from(helper.m.dao_holder.dao.as_ref(), std::slice::from_ref(&helper.m.ds.uuid)).unwrap();
+    (dao, tmp_dir)
+}
+
+/// Internal IDs are not preserved when copying into SQLite database, so we have to look them up by source ID.
+fn sqlite_master_id(msgs: &[Message], src_id: i64) -> MasterInternalId {
+    MasterInternalId(msgs.iter().find(|m| m.source_id_option == Some(src_id)).unwrap().internal_id)
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+#[path = "multi_merge_tests.rs"]
+mod tests;
+
+use tempfile::TempDir;
+
+use crate::merge::analyzer::*;
+use crate::merge::auto_merge::*;
+use crate::merge::merger::*;
+use crate::merge::report::MergeReport;
+use crate::prelude::*;
+
+/// Snapshot of a history to be merged by `merge_many_datasets`.
+pub struct SnapshotToMerge<'a> {
+    pub dao: &'a dyn ChatHistoryDao,
+    pub ds: &'a Dataset,
+    /// Applied when this snapshot is merged into the preceding ones, see `merger::merge_datasets`.
+    /// Ignored for the first snapshot.
+    pub slave_time_shifts: HashMap<ChatId, i64>,
+}
+
+/// Merges an ordered list of snapshots of the same history (oldest first) into a single dataset of a new database.
+///
+/// Decisions are only made according to the given policy, there's no way to supply them for individual users, chats
+/// or messages - `merger::merge_datasets` should be used for that.
+/// Snapshots are folded into a combined timeline one by one: every snapshot is analyzed against the combined timeline
+/// of all snapshots preceding it, so e.g. `PreferSlave` means later snapshots win conflicts.
+/// Intermediate combined timelines are written into temporary databases within the given directory, each one removed
+/// once the next one is written. The last one is the new database itself.
+/// Other datasets of the first snapshot DAO are copied as-is, like in `merger::merge_datasets`.
+///
+/// Returns a report for every snapshot but the first, describing how it was merged into the combined timeline
+/// (which for the second snapshot is the first snapshot itself). Reports are saved next to the new database.
+pub fn merge_many_datasets(
+    sqlite_dao_dir: &Path,
+    snapshots: &[SnapshotToMerge],
+    policy: AutoMergePolicy,
+    alignment: MessageAlignment,
+) -> Result<(SqliteDao, Dataset, Vec<MergeReport>)> {
+    ensure!(snapshots.len() >= 2, "At least two datasets are needed for a merge, got {}", snapshots.len());
+    measure(|| {
+        let first = &snapshots[0];
+        let mut reports = Vec::with_capacity(snapshots.len() - 1);
+        // Temporary directory is removed when dropped along with the combined timeline DAO, which is dropped first
+        let mut combined_option: Option<(SqliteDao, Dataset, Option<TempDir>)> = None;
+        for (idx, slave) in snapshots.iter().enumerate().skip(1) {
+            let (master_dao, master_ds) = match combined_option {
+                Some((ref dao, ref ds, _)) => (dao as &dyn ChatHistoryDao, ds),
+                None => (first.dao, first.ds),
+            };
+            let (user_merges, chat_merges) = make_decisions(master_dao, master_ds, slave.dao, slave.ds,
+                                                            policy, alignment, &slave.slave_time_shifts)?;
+
+            let is_last = idx == snapshots.len() - 1;
+            let tmp_dir_option = match is_last {
+                true => None,
+                false => Some(tempfile::Builder::new().prefix(".merge_many_").tempdir_in(sqlite_dao_dir)?),
+            };
+            let dir = tmp_dir_option.as_ref().map(|tmp_dir| tmp_dir.path()).unwrap_or(sqlite_dao_dir);
+            let mut dao = SqliteDao::create(&dir.join(SqliteDao::FILENAME))?;
+            let (mut ds, report) = merge_into(&mut dao, master_dao, master_ds, slave.dao, slave.ds,
+                                              user_merges, chat_merges, &slave.slave_time_shifts)?;
+            if !is_last {
+                // Keeping the original alias so that it doesn't accumulate "(merged)" suffixes
+                ds = dao.update_dataset(ds.uuid.clone(), Dataset { alias: first.ds.alias.clone(), ..ds })?;
+                log::info!("Merged snapshot {} into a combined timeline", idx + 1);
+            }
+            reports.push(report);
+            combined_option = Some((dao, ds, tmp_dir_option));
+        }
+        let (new_dao, new_ds, _) = combined_option.expect("At least one snapshot is merged");
+
+        let merged_ds_uuids = snapshots.iter().map(|s| &s.ds.uuid).collect_vec();
+        let other_first_dataset_uuids = first.dao.datasets()?
+            .into_iter()
+            .map(|ds| ds.uuid)
+            .filter(|ds_uuid| !merged_ds_uuids.contains(&ds_uuid))
+            .collect_vec();
+        new_dao.copy_datasets_from(first.dao, &other_first_dataset_uuids)?;
+        for report in reports.iter() {
+            report.save(sqlite_dao_dir)?;
+        }
+        Ok((new_dao, new_ds, reports))
+    }, |_, t| log::info!("{} datasets merged in {t} ms", snapshots.len()))
+}
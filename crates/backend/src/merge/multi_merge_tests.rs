@@ -0,0 +1,203 @@
+#![allow(unused_imports)]
+use super::*;
+
+use std::fs;
+
+use crate::merge::report::ChatMergeKind;
+use crate::prelude::*;
+use crate::utils::test_utils::*;
+use chat_history_manager_core::coerce_enum;
+
+use pretty_assertions::{assert_eq, assert_ne};
+use rand::prelude::*;
+
+/**
+ * ```text
+ * Snapshot #1 chats    - 1
+ * Snapshot #2 chats    - 1
+ * Snapshot #3 chats    - 1  2
+ * Snapshot #1 messages - 1  2  3
+ * Snapshot #2 messages -    2  3* 4  5
+ * Snapshot #3 messages -             5  6  7
+ * ```
+ */
+#[test]
+fn merge_many_snapshots() -> EmptyRes {
+    let msgs = (1..=7).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let snapshots = [
+        create_snapshot("One", msgs.cloned([1, 2, 3].map(src_id)), false, |_, _| {}),
+        create_snapshot("Two", msgs.cloned([2, 3, 4, 5].map(src_id)).changed(|id| *id == 3), false, |_, _| {}),
+        create_snapshot("Three", msgs.cloned([5, 6, 7].map(src_id)), true, |_, _| {}),
+    ];
+    let snapshots = as_snapshots(&snapshots);
+
+    for (policy, expected_third) in [
+        (AutoMergePolicy::PreferMaster, "Hello there, 3! Hey, 3!"),
+        (AutoMergePolicy::PreferSlave, "Different message 3 Hey, 3!"),
+    ] {
+        let new_dao_tmpdir = TmpDir::new();
+        let (new_dao, new_ds, reports) =
+            merge_many_datasets(&new_dao_tmpdir.path, &snapshots, policy, MessageAlignment::Strict)?;
+
+        assert_eq!(new_dao.datasets()?, vec![new_ds.clone()]);
+        assert_eq!(reports.len(), 2);
+        assert_eq!((reports[0].master_ds_uuid.as_str(), reports[0].slave_ds_uuid.as_str()),
+                   (snapshots[0].ds.uuid.value.as_str(), snapshots[1].ds.uuid.value.as_str()));
+        assert_eq!(reports[0].chats.iter().map(|c| (c.chat_id, c.tpe)).collect_vec(), vec![
+            (1, ChatMergeKind::Merge),
+        ]);
+        assert_eq!((reports[1].master_ds_uuid.as_str(), reports[1].slave_ds_uuid.as_str(), reports[1].new_ds_uuid.as_str()),
+                   (reports[0].new_ds_uuid.as_str(), snapshots[2].ds.uuid.value.as_str(), new_ds.uuid.value.as_str()));
+        assert_eq!(reports[1].chats.iter().map(|c| (c.chat_id, c.tpe)).collect_vec(), vec![
+            (1, ChatMergeKind::Merge), (2, ChatMergeKind::Add),
+        ]);
+        assert_eq!(new_ds.alias, format!("{} (merged)", snapshots[0].ds.alias));
+        assert_no_temporary_dirs(&new_dao_tmpdir.path);
+
+        let new_users = new_dao.users(&new_ds.uuid)?;
+        assert_eq!(new_users.iter().map(|u| u.id).collect_vec(), vec![1, 2, 3]);
+
+        let new_chats = new_dao.chats(&new_ds.uuid)?.into_iter().sorted_by_key(|cwd| cwd.chat.id).collect_vec();
+        assert_eq!(new_chats.iter().map(|cwd| cwd.chat.id).collect_vec(), vec![1, 2]);
+
+        let new_msgs = new_dao.first_messages(&new_chats[0].chat, usize::MAX)?;
+        assert_eq!(new_msgs.iter().map(|m| m.searchable_string.as_str()).collect_vec(), vec![
+            "Hello there, 1! Hey, 1!",
+            "Hello there, 2! Hey, 2!",
+            expected_third,
+            "Hello there, 4! Hey, 4!",
+            "Hello there, 5! Hey, 5!",
+            "Hello there, 6! Hey, 6!",
+            "Hello there, 7! Hey, 7!",
+        ]);
+        assert_eq!(new_chats[0].chat.msg_count, 7);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn merge_many_needs_at_least_two_snapshots() {
+    let (dao, ds) = create_snapshot("One", vec![create_regular_message(1, 1)], false, |_, _| {});
+    let new_dao_tmpdir = TmpDir::new();
+    let snapshots = vec![SnapshotToMerge { dao: dao.dao.as_ref(), ds: &ds, slave_time_shifts: HashMap::new() }];
+    let res = merge_many_datasets(&new_dao_tmpdir.path, &snapshots, AutoMergePolicy::PreferMaster, MessageAlignment::Strict);
+    assert!(res.is_err());
+}
+
+/**
+ * ```text
+ * Snapshot #1 messages - 1  2
+ * Snapshot #2 messages -    2  3+
+ * Snapshot #3 messages -          4  5
+ * ```
+ * (+ - has a photo)
+ */
+#[test]
+fn merge_many_copies_files_of_intermediate_snapshots() -> EmptyRes {
+    let msgs = (1..=5).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let seed: u64 = rng().random();
+    let snapshots = [
+        create_snapshot("One", msgs.cloned([1, 2].map(src_id)), false, |_, _| {}),
+        create_snapshot("Two", msgs.cloned([2, 3].map(src_id)), false, |ds_root, m| {
+            if m.source_id_option == Some(3) {
+                let path = create_random_file(&ds_root.0, seed);
+                let mr = coerce_enum!(m.typed, Some(message::Typed::Regular(ref mut mr)) => mr);
+                mr.contents = vec![content!(Photo {
+                    path_option: Some(ds_root.to_relative(&path).unwrap()),
+                    width: 10,
+                    height: 20,
+                    ..Default::default()
+                })];
+            }
+        }),
+        create_snapshot("Three", msgs.cloned([4, 5].map(src_id)), false, |_, _| {}),
+    ];
+    let snapshots = as_snapshots(&snapshots);
+
+    let new_dao_tmpdir = TmpDir::new();
+    let (new_dao, new_ds, _reports) =
+        merge_many_datasets(&new_dao_tmpdir.path, &snapshots, AutoMergePolicy::PreferMaster, MessageAlignment::Strict)?;
+
+    let src_dao = snapshots[1].dao;
+    let src_ds_root = src_dao.dataset_root(&snapshots[1].ds.uuid)?;
+    let src_chat = src_dao.chats(&snapshots[1].ds.uuid)?.remove(0).chat;
+    let src_msg = src_dao.last_messages(&src_chat, 1)?.remove(0);
+    assert_eq!(src_msg.files(&src_ds_root).len(), 1);
+
+    let new_ds_root = new_dao.dataset_root(&new_ds.uuid)?;
+    let new_chat = new_dao.chats(&new_ds.uuid)?.remove(0).chat;
+    let new_msgs = new_dao.first_messages(&new_chat, usize::MAX)?;
+    assert_eq!(new_msgs.iter().map(|m| m.source_id_option.unwrap()).collect_vec(), vec![1, 2, 3, 4, 5]);
+    assert_files(&src_msg.files(&src_ds_root), &new_msgs[2].files(&new_ds_root));
+    assert_no_temporary_dirs(&new_dao_tmpdir.path);
+
+    Ok(())
+}
+
+/**
+ * ```text
+ * Snapshot #1 messages - 1  2  3
+ * Snapshot #2 messages -    2  3  4  (shifted by an hour)
+ * Snapshot #3 messages -             4  5
+ * ```
+ */
+#[test]
+fn merge_many_applies_time_shifts() -> EmptyRes {
+    const SHIFT_SEC: i64 = 3600;
+    let msgs = (1..=5).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let snapshots = [
+        create_snapshot("One", msgs.cloned([1, 2, 3].map(src_id)), false, |_, _| {}),
+        create_snapshot("Two", msgs.cloned([2, 3, 4].map(src_id)), false, |_, m| m.timestamp += SHIFT_SEC),
+        create_snapshot("Three", msgs.cloned([4, 5].map(src_id)), false, |_, _| {}),
+    ];
+    let mut snapshots = as_snapshots(&snapshots);
+    snapshots[1].slave_time_shifts = HashMap::from([(ChatId(1), SHIFT_SEC)]);
+
+    let new_dao_tmpdir = TmpDir::new();
+    let (new_dao, new_ds, reports) =
+        merge_many_datasets(&new_dao_tmpdir.path, &snapshots, AutoMergePolicy::PreferMaster, MessageAlignment::Strict)?;
+    assert_eq!(reports.len(), 2);
+
+    let new_chat = new_dao.chats(&new_ds.uuid)?.remove(0).chat;
+    let new_msgs = new_dao.first_messages(&new_chat, usize::MAX)?;
+    assert_eq!(new_msgs.iter().map(|m| (m.source_id_option.unwrap(), m.timestamp)).collect_vec(),
+               msgs.iter().map(|m| (m.source_id_option.unwrap(), m.timestamp)).collect_vec());
+
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn as_snapshots(snapshots: &[(InMemoryDaoHolder, Dataset)]) -> Vec<SnapshotToMerge<'_>> {
+    snapshots.iter()
+        .map(|(dao, ds)| SnapshotToMerge { dao: dao.dao.as_ref(), ds, slave_time_shifts: HashMap::new() })
+        .collect_vec()
+}
+
+fn assert_no_temporary_dirs(dir: &Path) {
+    let leftovers = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(".merge_many_"))
+        .collect_vec();
+    assert_eq!(leftovers, Vec::<String>::new());
+}
+
+fn create_snapshot(name: &str,
+                   msgs: Vec<Message>,
+                   with_second_chat: bool,
+                   amend_messages: impl Fn(&DatasetRoot, &mut Message)) -> (InMemoryDaoHolder, Dataset) {
+    let users = (1..=3).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let mut cwms = vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "A", vec![1, 2], msgs.len()),
+        messages: msgs,
+    }];
+    if with_second_chat {
+        cwms.push(ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 2, "B", vec![1, 3], 0), messages: vec![] });
+    }
+    let dao = create_dao(name, users, cwms, amend_messages, rng().random());
+    let ds = dao.dao.dataset();
+    (dao, ds)
+}
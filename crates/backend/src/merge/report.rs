@@ -0,0 +1,372 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{Local, Utc};
+use serde::Serialize;
+
+use crate::export::html::{escape, format_timestamp};
+use crate::merge::merger::MessagesMergeDecision;
+use crate::prelude::*;
+
+const REPORT_NAME_PREFIX: &str = "merge_report_";
+
+/// Machine-readable record of what a merge did, meant to be saved next to the merge result for auditing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+    pub master_ds_uuid: String,
+    pub slave_ds_uuid: String,
+    /// Same as master dataset UUID for in-place merges
+    pub new_ds_uuid: String,
+    pub in_place: bool,
+    /// Epoch seconds
+    pub merged_at: i64,
+    /// Only users that were added or replaced
+    pub users: Vec<UserMergeReport>,
+    pub chats: Vec<ChatMergeReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserMergeReport {
+    pub user_id: i64,
+    pub name: String,
+    pub tpe: UserMergeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserMergeKind {
+    Add,
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChatMergeReport {
+    pub chat_id: i64,
+    pub chat_name: String,
+    pub tpe: ChatMergeKind,
+    pub matched_msgs: usize,
+    /// Master messages kept, including ones that won a conflict
+    pub retained_msgs: usize,
+    pub added_msgs: usize,
+    /// Slave messages that replaced conflicting master ones
+    pub replaced_msgs: usize,
+    /// Slave messages that weren't added, including ones that lost a conflict
+    pub skipped_msgs: usize,
+    pub sections: Vec<MessagesSectionReport>,
+    /// Files of slave messages actually copied during the merge, relative to the slave dataset root.
+    /// Files that were already present in the target dataset (e.g. deduplicated ones) are not included.
+    pub copied_files: Vec<String>,
+    /// Files referenced by messages written during the merge which weren't found
+    pub missing_files: Vec<MissingFile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatMergeKind {
+    Retain,
+    Add,
+    DontAdd,
+    Merge,
+    DontMerge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessagesSectionReport {
+    pub tpe: MessagesMergeKind,
+    pub master_range: Option<MessageRangeReport>,
+    /// For merged chats, timestamps have a slave time shift applied, i.e. they're as written to the merged dataset
+    pub slave_range: Option<MessageRangeReport>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessagesMergeKind {
+    Match,
+    Retain,
+    Add,
+    DontAdd,
+    Replace,
+    DontReplace,
+}
+
+/// Messages range described by its boundaries, since internal IDs mean nothing outside the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessageRangeReport {
+    pub msg_count: usize,
+    pub first_source_id: Option<i64>,
+    /// Epoch seconds
+    pub first_timestamp: i64,
+    pub last_source_id: Option<i64>,
+    /// Epoch seconds
+    pub last_timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingFile {
+    pub in_master: bool,
+    /// Relative to the dataset root
+    pub path: String,
+}
+
+impl MergeReport {
+    pub fn new(master_ds: &Dataset, slave_ds: &Dataset, new_ds: &Dataset, in_place: bool) -> Self {
+        MergeReport {
+            master_ds_uuid: master_ds.uuid.value.clone(),
+            slave_ds_uuid: slave_ds.uuid.value.clone(),
+            new_ds_uuid: new_ds.uuid.value.clone(),
+            in_place,
+            merged_at: Utc::now().timestamp(),
+            users: vec![],
+            chats: vec![],
+        }
+    }
+
+    pub fn add_user(&mut self, user: &User, tpe: UserMergeKind) {
+        self.users.push(UserMergeReport { user_id: user.id, name: user.pretty_name(), tpe });
+    }
+
+    /// Writes report as both JSON and HTML into the given directory, returns path to the JSON one.
+    /// Existing reports are never overwritten.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        let now_str = Local::now().format("%Y-%m-%d_%H-%M-%S");
+        let mut name = format!("{REPORT_NAME_PREFIX}{now_str}");
+        let mut suffix = 2;
+        while dir.join(format!("{name}.json")).exists() {
+            name = format!("{REPORT_NAME_PREFIX}{now_str}_{suffix}");
+            suffix += 1;
+        }
+
+        let json_file = dir.join(format!("{name}.json"));
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize merge report")?;
+        fs::write(&json_file, json)
+            .with_context(|| format!("Failed to write merge report to {}", json_file.display()))?;
+
+        let html_file = dir.join(format!("{name}.html"));
+        fs::write(&html_file, self.render_html()?)
+            .with_context(|| format!("Failed to write merge report to {}", html_file.display()))?;
+
+        log::info!("Merge report saved to {}", json_file.display());
+        Ok(json_file)
+    }
+
+    fn render_html(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Merge report</title>\n\
+                       <style>{STYLE}</style>\n</head>\n<body>")?;
+        writeln!(out, "<h2>Merge report</h2>")?;
+        writeln!(out, "<p>Merged at {}{}</p>", format_timestamp(self.merged_at), if self.in_place { ", in place" } else { "" })?;
+        writeln!(out, "<p>Master dataset: {}<br>Slave dataset: {}<br>Resulting dataset: {}</p>",
+                 escape(&self.master_ds_uuid), escape(&self.slave_ds_uuid), escape(&self.new_ds_uuid))?;
+
+        writeln!(out, "<h3>Users</h3>")?;
+        if self.users.is_empty() {
+            writeln!(out, "<p>No users added or replaced</p>")?;
+        } else {
+            writeln!(out, "<table>\n<tr><th>ID</th><th>Name</th><th>Action</th></tr>")?;
+            for u in self.users.iter() {
+                writeln!(out, "<tr><td>{}</td><td>{}</td><td>{:?}</td></tr>", u.user_id, escape(&u.name), u.tpe)?;
+            }
+            writeln!(out, "</table>")?;
+        }
+
+        writeln!(out, "<h3>Chats</h3>")?;
+        writeln!(out, "<table>\n<tr><th>Chat</th><th>Action</th><th>Matched</th><th>Retained</th><th>Added</th>\
+                       <th>Replaced</th><th>Skipped</th><th>Files copied</th><th>Files missing</th></tr>")?;
+        for c in self.chats.iter() {
+            writeln!(out, "<tr><td><a href=\"#chat_{}\">{}</a></td><td>{:?}</td><td>{}</td><td>{}</td><td>{}</td>\
+                           <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                     c.chat_id, escape(&c.chat_name), c.tpe, c.matched_msgs, c.retained_msgs, c.added_msgs,
+                     c.replaced_msgs, c.skipped_msgs, c.copied_files.len(), c.missing_files.len())?;
+        }
+        writeln!(out, "</table>")?;
+
+        for c in self.chats.iter().filter(|c| !c.sections.is_empty() || !c.missing_files.is_empty()) {
+            writeln!(out, "<h4 id=\"chat_{}\">{}</h4>", c.chat_id, escape(&c.chat_name))?;
+            writeln!(out, "<table>\n<tr><th>Action</th><th>Master</th><th>Slave</th></tr>")?;
+            for s in c.sections.iter() {
+                writeln!(out, "<tr><td>{:?}</td><td>{}</td><td>{}</td></tr>",
+                         s.tpe, render_range(&s.master_range), render_range(&s.slave_range))?;
+            }
+            writeln!(out, "</table>")?;
+            if !c.missing_files.is_empty() {
+                writeln!(out, "<p>Missing files:</p>\n<ul>")?;
+                for f in c.missing_files.iter() {
+                    writeln!(out, "<li>{} ({})</li>", escape(&f.path), if f.in_master { "master" } else { "slave" })?;
+                }
+                writeln!(out, "</ul>")?;
+            }
+        }
+        writeln!(out, "</body>\n</html>")?;
+        Ok(out)
+    }
+}
+
+impl ChatMergeReport {
+    pub fn new(chat: &Chat, tpe: ChatMergeKind) -> Self {
+        ChatMergeReport {
+            chat_id: chat.id,
+            chat_name: chat.qualified_name(),
+            tpe,
+            matched_msgs: 0,
+            retained_msgs: 0,
+            added_msgs: 0,
+            replaced_msgs: 0,
+            skipped_msgs: 0,
+            sections: vec![],
+            copied_files: vec![],
+            missing_files: vec![],
+        }
+    }
+
+    /// Records a section spanning the whole chat, for chats that aren't merged message-by-message.
+    pub fn add_whole_chat(&mut self, tpe: MessagesMergeKind, dao: &dyn ChatHistoryDao, cwd: &ChatWithDetails) -> EmptyRes {
+        let range_option = match (dao.first_messages(&cwd.chat, 1)?.first(), cwd.last_msg_option.as_ref()) {
+            (Some(first), Some(last)) => Some(range_report(cwd.chat.msg_count as usize, first, last)),
+            _ => None,
+        };
+        let count = cwd.chat.msg_count as usize;
+        let section = match tpe {
+            MessagesMergeKind::Retain => {
+                self.retained_msgs += count;
+                MessagesSectionReport { tpe, master_range: range_option, slave_range: None }
+            }
+            MessagesMergeKind::Add => {
+                self.added_msgs += count;
+                MessagesSectionReport { tpe, master_range: None, slave_range: range_option }
+            }
+            MessagesMergeKind::DontAdd => {
+                self.skipped_msgs += count;
+                MessagesSectionReport { tpe, master_range: None, slave_range: range_option }
+            }
+            _ => bail!("Whole chat cannot be merged as {tpe:?}")
+        };
+        self.sections.push(section);
+        Ok(())
+    }
+
+    /// Records a section according to messages merge decision.
+    /// Slave range timestamps are shifted back by `slave_time_shift_sec`, same as slave messages themselves.
+    pub fn add_section(&mut self,
+                       decision: &MessagesMergeDecision,
+                       master_dao: &dyn ChatHistoryDao,
+                       master_chat: &Chat,
+                       slave_dao: &dyn ChatHistoryDao,
+                       slave_chat: &Chat,
+                       slave_time_shift_sec: i64) -> EmptyRes {
+        let master_range = |first: MasterInternalId, last: MasterInternalId|
+            slice_range_report(master_dao, master_chat, first.generalize(), last.generalize());
+        let slave_range = |first: SlaveInternalId, last: SlaveInternalId| -> Result<Option<MessageRangeReport>> {
+            let range_option = slice_range_report(slave_dao, slave_chat, first.generalize(), last.generalize())?;
+            Ok(range_option.map(|r| MessageRangeReport {
+                first_timestamp: r.first_timestamp - slave_time_shift_sec,
+                last_timestamp: r.last_timestamp - slave_time_shift_sec,
+                ..r
+            }))
+        };
+        let count = |range: &Option<MessageRangeReport>| range.as_ref().map_or(0, |r| r.msg_count);
+
+        let section = match decision {
+            MessagesMergeDecision::Match(v) => {
+                let master_range = master_range(v.first_master_msg_id, v.last_master_msg_id)?;
+                self.matched_msgs += count(&master_range);
+                MessagesSectionReport {
+                    tpe: MessagesMergeKind::Match,
+                    master_range,
+                    slave_range: slave_range(v.first_slave_msg_id, v.last_slave_msg_id)?,
+                }
+            }
+            MessagesMergeDecision::Retain(v) => {
+                let master_range = master_range(v.first_master_msg_id, v.last_master_msg_id)?;
+                self.retained_msgs += count(&master_range);
+                MessagesSectionReport { tpe: MessagesMergeKind::Retain, master_range, slave_range: None }
+            }
+            MessagesMergeDecision::Add(v) => {
+                let slave_range = slave_range(v.first_slave_msg_id, v.last_slave_msg_id)?;
+                self.added_msgs += count(&slave_range);
+                MessagesSectionReport { tpe: MessagesMergeKind::Add, master_range: None, slave_range }
+            }
+            MessagesMergeDecision::DontAdd(v) => {
+                let slave_range = slave_range(v.first_slave_msg_id, v.last_slave_msg_id)?;
+                self.skipped_msgs += count(&slave_range);
+                MessagesSectionReport { tpe: MessagesMergeKind::DontAdd, master_range: None, slave_range }
+            }
+            MessagesMergeDecision::Replace(v) => {
+                let slave_range = slave_range(v.first_slave_msg_id, v.last_slave_msg_id)?;
+                self.replaced_msgs += count(&slave_range);
+                MessagesSectionReport {
+                    tpe: MessagesMergeKind::Replace,
+                    master_range: master_range(v.first_master_msg_id, v.last_master_msg_id)?,
+                    slave_range,
+                }
+            }
+            MessagesMergeDecision::DontReplace(v) => {
+                let master_range = master_range(v.first_master_msg_id, v.last_master_msg_id)?;
+                let slave_range = slave_range(v.first_slave_msg_id, v.last_slave_msg_id)?;
+                self.retained_msgs += count(&master_range);
+                self.skipped_msgs += count(&slave_range);
+                MessagesSectionReport { tpe: MessagesMergeKind::DontReplace, master_range, slave_range }
+            }
+        };
+        self.sections.push(section);
+        Ok(())
+    }
+
+    /// Records files of messages being written that are missing.
+    pub fn add_missing_files(&mut self, msgs: &[Message], ds_root: &DatasetRoot, from_master: bool) {
+        for m in msgs {
+            for path in m.files_relative() {
+                if !ds_root.to_absolute(path).exists() {
+                    self.missing_files.push(MissingFile { in_master: from_master, path: path.to_owned() });
+                }
+            }
+        }
+    }
+
+    /// Records files reported as copied by `MutableChatHistoryDao::insert_messages`, only slave ones are of interest.
+    pub fn add_copied_files(&mut self, copied_files: Vec<String>, from_master: bool) {
+        if !from_master {
+            self.copied_files.extend(copied_files);
+        }
+    }
+}
+
+fn slice_range_report(dao: &dyn ChatHistoryDao,
+                      chat: &Chat,
+                      first_id: MessageInternalId,
+                      last_id: MessageInternalId) -> Result<Option<MessageRangeReport>> {
+    let first_option = dao.messages_slice(chat, first_id, first_id)?.into_iter().next();
+    let last_option = dao.messages_slice(chat, last_id, last_id)?.into_iter().next();
+    Ok(match (first_option, last_option) {
+        (Some(first), Some(last)) =>
+            Some(range_report(dao.messages_slice_len(chat, first_id, last_id)?, &first, &last)),
+        _ => None,
+    })
+}
+
+fn range_report(msg_count: usize, first: &Message, last: &Message) -> MessageRangeReport {
+    MessageRangeReport {
+        msg_count,
+        first_source_id: first.source_id_option,
+        first_timestamp: first.timestamp,
+        last_source_id: last.source_id_option,
+        last_timestamp: last.timestamp,
+    }
+}
+
+fn render_range(range_option: &Option<MessageRangeReport>) -> String {
+    match range_option {
+        None => "-".to_owned(),
+        Some(r) => {
+            let source_id = |id: Option<i64>| id.map(|id| format!(" #{id}")).unwrap_or_default();
+            format!("{} message(s): {}{} &mdash; {}{}", r.msg_count,
+                    format_timestamp(r.first_timestamp), source_id(r.first_source_id),
+                    format_timestamp(r.last_timestamp), source_id(r.last_source_id))
+        }
+    }
+}
+
+const STYLE: &str = r#"body { font-family: sans-serif; margin: 16px; }
+table { border-collapse: collapse; margin: 8px 0; }
+th, td { border: 1px solid #ddd; padding: 4px 8px; text-align: left; }
+th { background: #f5f5f5; }"#;
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use prost::Message as ProtobufMessage;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "session_tests.rs"]
+mod tests;
+
+const SESSIONS_DIR_NAME: &str = "_merge_sessions";
+const SESSION_FILE_EXT: &str = "binpb";
+
+/// Persists merge sessions - analysis along with merge decisions made so far - in a sidecar directory
+/// next to the master database, so that merge could be resumed after the app is closed.
+pub struct MergeSessionStore {
+    dir: PathBuf,
+}
+
+impl MergeSessionStore {
+    pub fn new(master_storage_path: &Path) -> Self {
+        MergeSessionStore { dir: master_storage_path.join(SESSIONS_DIR_NAME) }
+    }
+
+    /// Datasets chats are fingerprinted, see `ensure_unchanged`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        &self,
+        master_dao_key: &str,
+        master_dao: &dyn ChatHistoryDao,
+        master_ds_uuid: &PbUuid,
+        slave_dao_key: &str,
+        slave_dao: &dyn ChatHistoryDao,
+        slave_ds_uuid: &PbUuid,
+        analysis: Vec<ChatAnalysis>,
+    ) -> Result<MergeSession> {
+        let now = Utc::now().timestamp();
+        let session = MergeSession {
+            id: PbUuid::random().value,
+            master_dao_key: master_dao_key.to_owned(),
+            master_ds_uuid: master_ds_uuid.clone(),
+            slave_dao_key: slave_dao_key.to_owned(),
+            slave_ds_uuid: slave_ds_uuid.clone(),
+            created_at: now,
+            updated_at: now,
+            analysis,
+            user_merges: vec![],
+            chat_merges: vec![],
+            master_fingerprints: fingerprints(master_dao, master_ds_uuid)?,
+            slave_fingerprints: fingerprints(slave_dao, slave_ds_uuid)?,
+        };
+        self.save(&session)?;
+        Ok(session)
+    }
+
+    pub fn load(&self, id: &str) -> Result<MergeSession> {
+        let file = self.session_file(id)?;
+        ensure!(file.exists(), "Merge session {id} not found!");
+        let bytes = fs::read(&file)?;
+        MergeSession::decode(bytes.as_slice())
+            .with_context(|| format!("Merge session file {} is corrupted!", file.display()))
+    }
+
+    /// Most recently updated sessions go first.
+    pub fn list(&self) -> Result<Vec<MergeSession>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut sessions = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != SESSION_FILE_EXT) { continue; }
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else { continue; };
+            match self.load(id) {
+                Ok(session) => sessions.push(session),
+                Err(e) => log::warn!("Skipping merge session {}: {}", path.display(), error_message(&e)),
+            }
+        }
+        sessions.sort_by_key(|s| -s.updated_at);
+        Ok(sessions)
+    }
+
+    /// Analysis and merge decisions replace ones for the same chats/users already stored in the session,
+    /// the rest are added.
+    pub fn update(
+        &self,
+        id: &str,
+        analysis: Vec<ChatAnalysis>,
+        user_merges: Vec<UserMerge>,
+        chat_merges: Vec<ChatMerge>,
+    ) -> Result<MergeSession> {
+        let mut session = self.load(id)?;
+        upsert(&mut session.analysis, analysis, |a| (a.chat_ids.master_chat_id, a.chat_ids.slave_chat_id));
+        upsert(&mut session.user_merges, user_merges, |um| um.user_id);
+        upsert(&mut session.chat_merges, chat_merges, |cm| cm.chat_id);
+        session.updated_at = Utc::now().timestamp();
+        self.save(&session)?;
+        Ok(session)
+    }
+
+    pub fn delete(&self, id: &str) -> EmptyRes {
+        let file = self.session_file(id)?;
+        ensure!(file.exists(), "Merge session {id} not found!");
+        fs::remove_file(file)?;
+        Ok(())
+    }
+
+    fn save(&self, session: &MergeSession) -> EmptyRes {
+        if !self.dir.exists() {
+            fs::create_dir(&self.dir)?;
+        }
+        let file = self.session_file(&session.id)?;
+        // Writing to a temporary file first, so that a crash mid-write won't corrupt the existing session
+        let tmp_file = file.with_extension(format!("{SESSION_FILE_EXT}.tmp"));
+        fs::write(&tmp_file, session.encode_to_vec())?;
+        fs::rename(&tmp_file, &file)?;
+        Ok(())
+    }
+
+    fn session_file(&self, id: &str) -> Result<PathBuf> {
+        // ID comes from the client, it shouldn't be able to point outside the sessions directory
+        ensure!(Uuid::parse_str(id).is_ok(), "Malformed merge session ID {id}");
+        Ok(self.dir.join(format!("{id}.{SESSION_FILE_EXT}")))
+    }
+}
+
+/// Session analysis and decisions refer to messages by their internal IDs, so they make no sense once either
+/// dataset has changed since the session was created.
+pub fn ensure_unchanged(session: &MergeSession,
+                        master_dao: &dyn ChatHistoryDao,
+                        slave_dao: &dyn ChatHistoryDao) -> EmptyRes {
+    for (side, dao, ds_uuid, expected) in [
+        ("master", master_dao, &session.master_ds_uuid, &session.master_fingerprints),
+        ("slave", slave_dao, &session.slave_ds_uuid, &session.slave_fingerprints),
+    ] {
+        let actual = fingerprints(dao, ds_uuid)?;
+        if &actual != expected {
+            let changed_chat_ids = expected.iter().filter(|fp| !actual.contains(fp))
+                .chain(actual.iter().filter(|fp| !expected.contains(fp)))
+                .map(|fp| fp.chat_id)
+                .unique()
+                .sorted()
+                .collect_vec();
+            bail!("Chats {changed_chat_ids:?} of {side} dataset have changed since merge session {} was created, \
+                   it should be started anew", session.id);
+        }
+    }
+    Ok(())
+}
+
+fn fingerprints(dao: &dyn ChatHistoryDao, ds_uuid: &PbUuid) -> Result<Vec<ChatFingerprint>> {
+    let mut res = vec![];
+    for cwd in dao.chats(ds_uuid)?.into_iter().sorted_by_key(|cwd| cwd.chat.id) {
+        let first = dao.first_messages(&cwd.chat, 1)?.into_iter().next();
+        let last = dao.last_messages(&cwd.chat, 1)?.into_iter().next();
+        res.push(ChatFingerprint {
+            chat_id: cwd.chat.id,
+            msg_count: cwd.chat.msg_count,
+            first_internal_id: first.as_ref().map(|m| m.internal_id),
+            first_timestamp: first.as_ref().map(|m| m.timestamp),
+            last_internal_id: last.as_ref().map(|m| m.internal_id),
+            last_timestamp: last.as_ref().map(|m| m.timestamp),
+        });
+    }
+    Ok(res)
+}
+
+fn upsert<T, K: PartialEq>(existing: &mut Vec<T>, new: Vec<T>, key: impl Fn(&T) -> K) {
+    for v in new {
+        match existing.iter_mut().find(|e| key(e) == key(&v)) {
+            Some(e) => *e = v,
+            None => existing.push(v),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+#![allow(unused_imports)]
+use super::*;
+
+use crate::utils::test_utils::*;
+
+use pretty_assertions::assert_eq;
+use rand::prelude::*;
+
+#[test]
+fn create_update_list_delete() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let store = MergeSessionStore::new(&tmp_dir.path);
+    assert_eq!(store.list()?, vec![]);
+
+    let (m_dao, m_ds) = create_simple_dao("One", 3);
+    let (s_dao, s_ds) = create_simple_dao("Two", 4);
+    let analysis = vec![chat_analysis(1, AnalysisSectionType::Match)];
+    let created = store.create("master", m_dao.dao.as_ref(), &m_ds.uuid,
+                               "slave", s_dao.dao.as_ref(), &s_ds.uuid, analysis.clone())?;
+    assert_eq!(store.load(&created.id)?, created);
+    assert_eq!(created.analysis, analysis);
+
+    let updated = store.update(&created.id, vec![], vec![user_merge(1, UserMergeType::Retain)], vec![
+        chat_merge(1, ChatMergeType::Merge),
+        chat_merge(2, ChatMergeType::Add),
+    ])?;
+    let updated = store.update(&updated.id, vec![chat_analysis(1, AnalysisSectionType::Conflict)], vec![
+        user_merge(1, UserMergeType::Replace),
+        user_merge(2, UserMergeType::Add),
+    ], vec![
+        chat_merge(2, ChatMergeType::DontAdd),
+    ])?;
+    assert_eq!(updated.analysis, vec![chat_analysis(1, AnalysisSectionType::Conflict)]);
+    assert_eq!(updated.user_merges, vec![
+        user_merge(1, UserMergeType::Replace),
+        user_merge(2, UserMergeType::Add),
+    ]);
+    assert_eq!(updated.chat_merges, vec![
+        chat_merge(1, ChatMergeType::Merge),
+        chat_merge(2, ChatMergeType::DontAdd),
+    ]);
+    assert!(updated.updated_at >= created.updated_at);
+
+    // Reopening the store (e.g. after the app restart) should see the latest state
+    let store = MergeSessionStore::new(&tmp_dir.path);
+    assert_eq!(store.list()?, vec![updated.clone()]);
+
+    store.delete(&updated.id)?;
+    assert_eq!(store.list()?, vec![]);
+    assert!(store.load(&updated.id).is_err());
+    Ok(())
+}
+
+#[test]
+fn changed_datasets() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let store = MergeSessionStore::new(&tmp_dir.path);
+    let (m_dao, m_ds) = create_simple_dao("One", 3);
+    let (s_dao, s_ds) = create_simple_dao("Two", 4);
+    let session = store.create("master", m_dao.dao.as_ref(), &m_ds.uuid,
+                               "slave", s_dao.dao.as_ref(), &s_ds.uuid, vec![])?;
+    let m_msgs = m_dao.dao.cwms_single_ds().remove(0).messages;
+    assert_eq!(session.master_fingerprints, vec![ChatFingerprint {
+        chat_id: 1,
+        msg_count: 3,
+        first_internal_id: Some(m_msgs[0].internal_id),
+        first_timestamp: Some(m_msgs[0].timestamp),
+        last_internal_id: Some(m_msgs[2].internal_id),
+        last_timestamp: Some(m_msgs[2].timestamp),
+    }]);
+    ensure_unchanged(&session, m_dao.dao.as_ref(), s_dao.dao.as_ref())?;
+
+    // Slave has been re-imported with one more message, but it's still the same dataset
+    let (s_dao, _) = create_simple_dao("Two", 5);
+    let session = MergeSession { slave_ds_uuid: s_dao.dao.dataset().uuid, ..session };
+    let err = ensure_unchanged(&session, m_dao.dao.as_ref(), s_dao.dao.as_ref()).unwrap_err();
+    assert!(error_message(&err).contains("Chats [1] of slave dataset have changed"), "Unexpected error: {err:?}");
+    Ok(())
+}
+
+#[test]
+fn malformed_id() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let store = MergeSessionStore::new(&tmp_dir.path);
+    let err = store.load("../../some_file").unwrap_err();
+    assert!(error_message(&err).contains("Malformed merge session ID"));
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn create_simple_dao(name: &str, msg_count: usize) -> (InMemoryDaoHolder, Dataset) {
+    let users = (1..=2).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let msgs = (1..=msg_count).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let cwms = vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "A", vec![1, 2], msgs.len()),
+        messages: msgs,
+    }];
+    let dao = create_dao(name, users, cwms, |_, _| {}, rng().random());
+    let ds = dao.dao.dataset();
+    (dao, ds)
+}
+
+fn chat_analysis(chat_id: i64, tpe: AnalysisSectionType) -> ChatAnalysis {
+    ChatAnalysis {
+        chat_ids: ChatIdPair { master_chat_id: chat_id, slave_chat_id: chat_id },
+        sections: vec![AnalysisSection {
+            tpe: tpe as i32,
+            range: MessageMergeSectionRange {
+                first_master_msg_id: 1,
+                last_master_msg_id: 2,
+                first_slave_msg_id: 1,
+                last_slave_msg_id: 2,
+            },
+        }],
+        time_shift: None,
+    }
+}
+
+fn user_merge(user_id: i64, tpe: UserMergeType) -> UserMerge {
+    UserMerge { tpe: tpe as i32, user_id }
+}
+
+fn chat_merge(chat_id: i64, tpe: ChatMergeType) -> ChatMerge {
+    ChatMerge { tpe: tpe as i32, chat_id, message_merges: vec![], slave_time_shift_sec: None }
+}
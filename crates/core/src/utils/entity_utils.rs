@@ -379,6 +379,43 @@ impl Message {
         possibilities.into_iter().flatten().collect()
     }
 
+    /// Same as [Self::files_relative], but exposes every file path field for modification.
+    pub fn files_relative_mut(&mut self) -> Vec<&mut Option<String>> {
+        match self.typed_mut() {
+            message::Typed::Regular(mr) => {
+                mr.contents.iter_mut()
+                    .flat_map(|content| {
+                        use content::SealedValueOptional::*;
+                        match content.sealed_value_optional.as_mut().unwrap() {
+                            Sticker(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                            Photo(v) => vec![&mut v.path_option],
+                            VoiceMsg(v) => vec![&mut v.path_option],
+                            Audio(v) => vec![&mut v.path_option],
+                            VideoMsg(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                            Video(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                            File(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                            Location(_) => vec![],
+                            Poll(_) => vec![],
+                            SharedContact(v) => vec![&mut v.vcard_path_option],
+                            TodoList(_) => vec![],
+                        }
+                    })
+                    .collect_vec()
+            }
+            message_service_pat!(ms) => {
+                use message_service::SealedValueOptional::*;
+                match ms {
+                    SuggestProfilePhoto(v) => vec![&mut v.photo.path_option],
+                    GroupEditPhoto(v) => vec![&mut v.photo.path_option],
+                    PhoneCall(_) | PinMessage(_) | ClearHistory(_) | BlockUser(_) | StatusTextChanged(_) |
+                    Notice(_) | GroupCreate(_) | GroupEditTitle(_) | GroupDeletePhoto(_) |
+                    GroupInviteMembers(_) | GroupRemoveMembers(_) | GroupMigrateFrom(_) | GroupMigrateTo(_) => vec![],
+                }
+            }
+            message_service_pat_unreachable!() => { unreachable!() }
+        }
+    }
+
     /// Does not check files existence.
     pub fn files(&self, ds_root: &DatasetRoot) -> Vec<PathBuf> {
         self.files_relative().iter().map(|p| ds_root.to_absolute(p)).collect()
@@ -15,6 +15,7 @@ use crate::utils::files_are_equal;
 ///   Metadata like file name is only compared if files match.
 /// * "Forwarded from" name is ignored (as its changes are not related to this message).
 /// * Edit timestamp is ignored (as it's not interesting unless something else changed too).
+/// * Channel post views are ignored, as they change with every export (merger keeps the highest count).
 /// * Special case: Telegram 2023-11 started exporting double styles (bold+X) as bold instead of an X.
 ///   We want to ignore this change, so Italic, Underline and Strikethrough consideres equal to Bold.
 pub trait EntityCmp<Rhs/*: ?Sized*/ = Self> {
@@ -229,8 +230,11 @@ impl EntityCmp for Tup<'_, MessageRegular> {
                                    edit_timestamp_option: None,
                                    forward_from_name_option: None,
                                    reply_to_message_id_option: None,
-                                   contents: vec![]).into(),
-            self.apply(|v| v.contents.as_slice()).compare(&other.apply(|v| v.contents.as_slice()))?
+                                   contents: vec![],
+                                   reactions: vec![],
+                                   views_option: None).into(),
+            self.apply(|v| v.contents.as_slice()).compare(&other.apply(|v| v.contents.as_slice()))?,
+            compare_reactions(&self.v.reactions, &other.v.reactions),
         ]))
     }
 }
@@ -568,6 +572,35 @@ fn members_practically_equals((members1, cwd1): (&[String], &ChatWithDetails),
     Ok(!disappeared.is_empty() && appeared.is_empty())
 }
 
+/// Reactions are matched by their emoji, order is ignored.
+/// Reaction that is missing on one side, or has lower count and a subset of reacting users,
+/// is considered "less data" - reactions are usually added over time.
+fn compare_reactions(reactions1: &[Reaction], reactions2: &[Reaction]) -> Cmp {
+    fn key(r: &Reaction) -> (Option<&str>, Option<&str>) {
+        (r.emoji_option.as_deref(), r.custom_emoji_id_option.as_deref())
+    }
+    fn is_subset(r1: &Reaction, r2: &Reaction) -> bool {
+        r1.count <= r2.count && r1.from_ids.iter().all(|id| r2.from_ids.contains(id))
+    }
+    let keys = reactions1.iter().chain(reactions2.iter()).map(key).unique().collect_vec();
+    if keys.is_empty() {
+        return Cmp::Equal;
+    }
+    Cmp::chain(keys.into_iter().map(|k| {
+        match (reactions1.iter().find(|r| key(r) == k), reactions2.iter().find(|r| key(r) == k)) {
+            (Some(r1), Some(r2)) => match (is_subset(r1, r2), is_subset(r2, r1)) {
+                (true, true) => Cmp::Equal,
+                (false, true) => Cmp::LeftHasMore,
+                (true, false) => Cmp::RightHasMore,
+                (false, false) => Cmp::Conflict,
+            },
+            (Some(_), None) => Cmp::LeftHasMore,
+            (None, Some(_)) => Cmp::RightHasMore,
+            (None, None) => unreachable!(),
+        }
+    }))
+}
+
 fn sets_are_equal<T: Hash + Eq>(list1: &[T], list2: &[T]) -> bool {
     if list1.len() != list2.len() {
         return false;
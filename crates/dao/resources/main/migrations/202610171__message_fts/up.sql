@@ -0,0 +1,8 @@
+-- External content table, rows are kept in sync with message by SqliteDao itself
+CREATE VIRTUAL TABLE message_fts USING fts5(
+    searchable_string,
+    content = 'message',
+    content_rowid = 'internal_id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO message_fts(message_fts) VALUES ('rebuild');
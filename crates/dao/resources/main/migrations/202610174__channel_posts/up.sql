@@ -0,0 +1,2 @@
+ALTER TABLE message ADD COLUMN views INTEGER;
+ALTER TABLE message ADD COLUMN author_signature TEXT;
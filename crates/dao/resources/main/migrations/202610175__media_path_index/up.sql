@@ -0,0 +1,5 @@
+-- Used to check whether a media file is still referenced before deleting it.
+-- Chats are few, so chat.img_path doesn't need one.
+CREATE INDEX message_content_path_idx ON message_content(path);
+CREATE INDEX message_content_thumbnail_path_idx ON message_content(thumbnail_path);
+CREATE INDEX profile_picture_path_idx ON profile_picture(ds_uuid, path);
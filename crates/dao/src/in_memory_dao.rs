@@ -1,4 +1,5 @@
 use super::*;
+use std::ops::Range;
 use std::path::PathBuf;
 use chat_history_manager_core::err;
 
@@ -86,6 +87,17 @@ impl InMemoryDao {
         }
     }
 
+    /// Path of the given source dataset root relative to the root of the given dataset.
+    /// Since files are never copied here, source has to be located under it.
+    fn relative_prefix(&self, ds_uuid: &PbUuid, src_ds_root: &DatasetRoot) -> Result<PathBuf> {
+        let ds_root = self.dataset_root(ds_uuid)?;
+        let src_ds_root = src_ds_root.0.canonicalize()
+            .with_context(|| format!("Could not canonicalize dataset root: {}", src_ds_root.0.display()))?;
+        let prefix = src_ds_root.strip_prefix(&ds_root.0)
+            .with_context(|| format!("Dataset root {} is not under {}", src_ds_root.display(), ds_root.0.display()))?;
+        Ok(prefix.to_path_buf())
+    }
+
     pub fn remove_orphan_users(&mut self) {
         let member_ids: HashSet<_> =
             self.cwms.values().flatten().flat_map(|cwm| &cwm.chat.member_ids).collect();
@@ -239,6 +251,44 @@ impl ChatHistoryDao for InMemoryDao {
             .iter().find(|m| m.source_id_option.iter().contains(&*source_id)).cloned())
     }
 
+    fn search_messages(&self, query: &MessageSearchQuery) -> Result<MessageSearchResult> {
+        let phrases = query.terms().iter()
+            .map(|t| search_tokens(t).into_iter().map(|(_, token)| token).collect_vec())
+            .collect_vec();
+        if phrases.is_empty() {
+            return Ok(MessageSearchResult { total_count: 0, hits: vec![] });
+        }
+        // Unknown dataset simply has nothing to be found, same as in SqliteDao
+        let Some(cwms) = self.cwms.get(&query.ds_uuid) else {
+            return Ok(MessageSearchResult { total_count: 0, hits: vec![] });
+        };
+
+        // Not an optimal implementation, but in-memory DAO isn't meant for huge histories anyway
+        let matches = cwms.iter()
+            .filter(|cwm| query.chat_id_option.is_none_or(|id| cwm.chat.id == *id))
+            .flat_map(|cwm| cwm.messages.iter().map(move |m| (cwm.chat.id, m)))
+            .filter(|(_, m)| query.from_id_option.is_none_or(|id| m.from_id == *id))
+            .filter(|(_, m)| query.timestamp_from_option.is_none_or(|ts| m.timestamp >= *ts))
+            .filter(|(_, m)| query.timestamp_to_option.is_none_or(|ts| m.timestamp <= *ts))
+            .filter_map(|(chat_id, m)| {
+                phrase_occurrences(&m.searchable_string, &phrases).map(|ranges| (chat_id, m, ranges))
+            })
+            .sorted_by_key(|(_, m, _)| (-m.timestamp, -m.internal_id))
+            .collect_vec();
+
+        let hits = matches.iter()
+            .skip(query.offset)
+            .take(query.limit)
+            .map(|(chat_id, m, ranges)| MessageSearchHit {
+                chat_id: ChatId(*chat_id),
+                message: (*m).clone(),
+                fragment: highlight(&m.searchable_string, ranges),
+            })
+            .collect_vec();
+
+        Ok(MessageSearchResult { total_count: matches.len(), hits })
+    }
+
     fn as_mutable(&mut self) -> Result<&mut dyn MutableChatHistoryDao> {
         Ok(self)
     }
@@ -246,6 +296,10 @@ impl ChatHistoryDao for InMemoryDao {
     fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryDao> {
         Ok(self)
     }
+
+    fn as_verifiable(&mut self) -> Result<&mut dyn VerifiableChatHistoryDao> {
+        bail!("In-memory DAO cannot be verified")
+    }
 }
 
 impl MutableChatHistoryDao for InMemoryDao {
@@ -253,8 +307,21 @@ impl MutableChatHistoryDao for InMemoryDao {
         Ok(std::thread::spawn(|| {})) // NOOP
     }
 
-    fn insert_dataset(&mut self, _ds: Dataset) -> Result<Dataset> {
-        err!("InMemoryDao does not implement inserting dataset")
+    /// Inserted dataset is rooted at the storage path.
+    fn insert_dataset(&mut self, ds: Dataset) -> Result<Dataset> {
+        ensure!(!self.ds_roots.contains_key(&ds.uuid), "Dataset with UUID {} already exists", ds.uuid.value);
+        let ds_root = self.storage_path.canonicalize()
+            .with_context(|| format!("Could not canonicalize storage path: {}", self.storage_path.display()))?;
+
+        let mut cache = self.cache.inner.write().expect("cache write lock");
+        cache.datasets.push(ds.clone());
+        cache.users.insert(ds.uuid.clone(), UserCacheForDataset {
+            myself_id: UserId::INVALID,
+            user_by_id: HashMap::new(),
+        });
+        self.ds_roots.insert(ds.uuid.clone(), DatasetRoot(ds_root));
+        self.cwms.insert(ds.uuid.clone(), vec![]);
+        Ok(ds)
     }
 
     fn update_dataset(&mut self, old_uuid: PbUuid, ds: Dataset) -> Result<Dataset> {
@@ -282,24 +349,65 @@ impl MutableChatHistoryDao for InMemoryDao {
         }
     }
 
-    fn insert_user(&mut self, _user: User, _is_myself: bool) -> Result<User> {
-        err!("InMemoryDao does not implement inserting users")
+    fn insert_user(&mut self, mut user: User, is_myself: bool) -> Result<User> {
+        user.profile_pictures = vec![];
+        let mut cache = self.cache.inner.write().expect("cache write lock");
+        let users_for_ds = cache.users.get_mut(&user.ds_uuid)
+            .with_context(|| format!("Dataset with UUID {} not found", user.ds_uuid.value))?;
+        ensure!(!users_for_ds.user_by_id.contains_key(&user.id()), "User {} already exists", user.id);
+        if is_myself {
+            ensure!(!users_for_ds.myself_id.is_valid(), "Myself was already inserted");
+            users_for_ds.myself_id = user.id();
+        }
+        users_for_ds.user_by_id.insert(user.id(), user.clone());
+        Ok(user)
     }
 
     fn update_user(&mut self, _old_id: UserId, _user: User) -> Result<User> {
         err!("InMemoryDao does not implement updating users")
     }
 
-    fn update_user_profile_pics(&mut self, _user: User, _new_profile_pics: Vec<AbsoluteProfilePicture>) -> Result<User> {
-        err!("InMemoryDao does not implement updating user profile pictures")
-    }
+    /// Pictures aren't copied, so they have to be located under the dataset root.
+    fn update_user_profile_pics(&mut self, mut user: User, new_profile_pics: Vec<AbsoluteProfilePicture>) -> Result<User> {
+        let ds_root = self.dataset_root(&user.ds_uuid)?;
+        user.profile_pictures = new_profile_pics.into_iter()
+            .filter(|pic| pic.absolute_path.exists())
+            .map(|pic| -> Result<ProfilePicture> {
+                Ok(ProfilePicture {
+                    path: ds_root.to_relative(&pic.absolute_path)?,
+                    frame_option: pic.frame_option.clone(),
+                })
+            })
+            .try_collect()?;
 
-    fn insert_chat(&mut self, _chat: Chat, _src_ds_root: &DatasetRoot) -> Result<Chat> {
-        err!("InMemoryDao does not implement inserting chats")
+        let mut cache = self.cache.inner.write().expect("cache write lock");
+        let old_user = cache.users.get_mut(&user.ds_uuid)
+            .and_then(|users_for_ds| users_for_ds.user_by_id.get_mut(&user.id()))
+            .with_context(|| format!("User {} not found", user.id))?;
+        *old_user = user.clone();
+        Ok(user)
+    }
+
+    /// Image isn't copied, so source dataset root has to be located under the dataset root.
+    fn insert_chat(&mut self, mut chat: Chat, src_ds_root: &DatasetRoot) -> Result<Chat> {
+        let prefix = self.relative_prefix(&chat.ds_uuid, src_ds_root)?;
+        if let Some(ref img) = chat.img_path_option {
+            chat.img_path_option = Some(rebase(&prefix, img)?);
+        }
+        let cwms = self.cwms.get_mut(&chat.ds_uuid)
+            .with_context(|| format!("Dataset with UUID {} not found", chat.ds_uuid.value))?;
+        ensure!(!cwms.iter().any(|cwm| cwm.chat.id == chat.id), "Chat {} already exists", chat.qualified_name());
+        cwms.push(ChatWithMessages { chat: chat.clone(), messages: vec![] });
+        Ok(chat)
     }
 
-    fn update_chat(&mut self, _old_id: ChatId, _chat: Chat) -> Result<Chat> {
-        err!("InMemoryDao does not implement updating chats")
+    fn update_chat(&mut self, old_id: ChatId, chat: Chat) -> Result<Chat> {
+        ensure!(*old_id == chat.id, "Changing chat ID is not supported");
+        let cwm = self.cwms.get_mut(&chat.ds_uuid)
+            .and_then(|cwms| cwms.iter_mut().find(|cwm| cwm.chat.id == chat.id))
+            .with_context(|| format!("Chat {} not found", chat.qualified_name()))?;
+        cwm.chat = chat.clone();
+        Ok(chat)
     }
 
     fn delete_chat(&mut self, chat: Chat) -> EmptyRes {
@@ -321,8 +429,28 @@ impl MutableChatHistoryDao for InMemoryDao {
         err!("InMemoryDao does not implement combining chats")
     }
 
-    fn insert_messages(&mut self, _msgs: Vec<Message>, _chat: &Chat, _src_ds_root: &DatasetRoot) -> EmptyRes {
-        err!("InMemoryDao does not implement inserting messages")
+    /// Files aren't copied (so none are reported), source dataset root has to be located under the dataset root.
+    fn insert_messages(&mut self, mut msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>> {
+        let prefix = self.relative_prefix(&chat.ds_uuid, src_ds_root)?;
+        let cwm = self.cwms.get_mut(&chat.ds_uuid)
+            .and_then(|cwms| cwms.iter_mut().find(|cwm| cwm.chat.id == chat.id))
+            .with_context(|| format!("Chat {} not found", chat.qualified_name()))?;
+        let mut next_internal_id = cwm.messages.last().map(|m| m.internal_id + 1).unwrap_or(1);
+        for m in msgs.iter_mut() {
+            m.internal_id = next_internal_id;
+            next_internal_id += 1;
+            for path in m.files_relative_mut().into_iter().flatten() {
+                *path = rebase(&prefix, path)?;
+            }
+        }
+        cwm.messages.extend(msgs);
+        Ok(vec![])
+    }
+
+    fn in_place_merge(&mut self,
+                      _ds_uuid: &PbUuid,
+                      _action: &mut dyn FnMut(&mut dyn InPlaceMergeTarget) -> EmptyRes) -> EmptyRes {
+        err!("InMemoryDao does not implement in-place merge")
     }
 }
 
@@ -353,6 +481,129 @@ pub struct DatasetEntry {
     pub cwms: Vec<ChatWithMessages>,
 }
 
+/// Sink collecting a single dataset, to be turned into [InMemoryDao] once finished.
+#[derive(Default)]
+pub struct InMemorySink {
+    name: String,
+    entry_option: Option<DatasetEntry>,
+    has_users: bool,
+    finished: bool,
+}
+
+impl InMemorySink {
+    pub fn into_dao(self) -> Result<InMemoryDao> {
+        ensure!(self.finished, "Dataset was not finished!");
+        let entry = self.entry_option.unwrap();
+        Ok(InMemoryDao::new(self.name, entry.ds_root.clone(), vec![entry]))
+    }
+
+    fn entry(&mut self) -> Result<&mut DatasetEntry> {
+        ensure!(!self.finished, "Dataset is already finished!");
+        self.entry_option.as_mut().context("Dataset was not started!")
+    }
+}
+
+impl ChatHistorySink for InMemorySink {
+    fn begin_dataset(&mut self, name: String, ds: Dataset, ds_root: &Path) -> EmptyRes {
+        ensure!(self.entry_option.is_none(), "Dataset is already started!");
+        self.name = name;
+        self.entry_option = Some(DatasetEntry {
+            ds,
+            ds_root: ds_root.to_path_buf(),
+            myself_id: UserId::INVALID,
+            users: vec![],
+            cwms: vec![],
+        });
+        Ok(())
+    }
+
+    fn add_users(&mut self, users: Vec<User>, myself_id: UserId) -> EmptyRes {
+        ensure!(!self.has_users, "Users were already added!");
+        let entry = self.entry()?;
+        entry.myself_id = myself_id;
+        entry.users = users;
+        self.has_users = true;
+        Ok(())
+    }
+
+    fn add_chat(&mut self, chat: Chat) -> EmptyRes {
+        let entry = self.entry()?;
+        ensure!(!entry.cwms.iter().any(|cwm| cwm.chat.id == chat.id), "Chat {} was already added!", chat.qualified_name());
+        entry.cwms.push(ChatWithMessages { chat, messages: vec![] });
+        Ok(())
+    }
+
+    fn add_messages(&mut self, chat: &Chat, msgs: Vec<Message>) -> EmptyRes {
+        // Messages are almost always added to the last chat
+        let cwm = self.entry()?.cwms.iter_mut().rfind(|cwm| cwm.chat.id == chat.id)
+            .with_context(|| format!("Chat {} was not added!", chat.qualified_name()))?;
+        cwm.messages.extend(msgs);
+        Ok(())
+    }
+
+    fn finish_chat(&mut self, chat: &Chat) -> EmptyRes {
+        let cwm = self.entry()?.cwms.iter_mut().rfind(|cwm| cwm.chat.id == chat.id)
+            .with_context(|| format!("Chat {} was not added!", chat.qualified_name()))?;
+        cwm.chat.member_ids = chat.member_ids.clone();
+        cwm.chat.msg_count = chat.msg_count;
+        Ok(())
+    }
+
+    fn finish_dataset(&mut self) -> EmptyRes {
+        ensure!(self.has_users, "Users were not added!");
+        self.entry()?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+/// Byte ranges of every occurrence of every phrase (given as tokens) in the text, sorted.
+/// Returns `None` unless all phrases occur in it.
+fn phrase_occurrences(text: &str, phrases: &[Vec<String>]) -> Option<Vec<Range<usize>>> {
+    let tokens = search_tokens(text);
+    let mut res = vec![];
+    for phrase in phrases {
+        if phrase.is_empty() {
+            return None;
+        }
+        let len_before = res.len();
+        for window in tokens.windows(phrase.len()) {
+            if window.iter().zip(phrase).all(|((_, token), phrase_token)| token == phrase_token) {
+                res.push(window[0].0.start..window[phrase.len() - 1].0.end);
+            }
+        }
+        if res.len() == len_before {
+            return None;
+        }
+    }
+    res.sort_by_key(|r| r.start);
+    Some(res)
+}
+
+/// Escaped text with the given (sorted) ranges highlighted, overlapping ones are merged.
+fn highlight(text: &str, ranges: &[Range<usize>]) -> String {
+    let strip_markers = |s: &str| s.replace([RAW_HIGHLIGHT_START, RAW_HIGHLIGHT_END], "");
+    let mut raw = String::with_capacity(text.len());
+    let mut pos = 0;
+    let mut ranges = ranges.iter().cloned().peekable();
+    while let Some(mut range) = ranges.next() {
+        while let Some(next) = ranges.next_if(|next| next.start <= range.end) {
+            range.end = range.end.max(next.end);
+        }
+        raw.push_str(&strip_markers(&text[pos..range.start]));
+        raw.push(RAW_HIGHLIGHT_START);
+        raw.push_str(&strip_markers(&text[range.clone()]));
+        raw.push(RAW_HIGHLIGHT_END);
+        pos = range.end;
+    }
+    raw.push_str(&strip_markers(&text[pos..]));
+    escape_search_fragment(&raw)
+}
+
+fn rebase(prefix: &Path, path: &str) -> Result<String> {
+    Ok(path_to_str(&prefix.join(path))?.to_owned())
+}
+
 fn cutout<T: Clone>(slice: &[T], start_inc: usize, end_exc: usize) -> Vec<T> {
     fn sanitize<T>(idx: usize, slice: &[T]) -> usize {
         std::cmp::min(std::cmp::max(idx, 0), slice.len())
@@ -3,6 +3,7 @@
 use super::*;
 
 use crate::utils::test_utils::*;
+use chat_history_manager_core::{coerce_enum, content};
 
 use pretty_assertions::{assert_eq, assert_ne};
 use rand::prelude::*;
@@ -173,6 +174,58 @@ fn messages_around() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn inserts_reference_files_in_place() -> EmptyRes {
+    let users = (1..=2).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let msgs = (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let cwms = vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "One", vec![1, 2], msgs.len()),
+        messages: msgs,
+    }];
+    let src_holder = create_dao("One", users, cwms, |ds_root, m| {
+        let path = create_random_file(&ds_root.0, m.source_id_option.unwrap() as u64);
+        let mr = coerce_enum!(m.typed, Some(message::Typed::Regular(ref mut mr)) => mr);
+        mr.contents = vec![content!(Photo {
+            path_option: Some(ds_root.to_relative(&path).unwrap()),
+            width: 10,
+            height: 20,
+            ..Default::default()
+        })];
+    }, rng().random());
+    let src_dao = src_holder.dao.as_ref();
+    let src_ds = src_dao.dataset();
+    let src_ds_root = src_dao.dataset_root(&src_ds.uuid)?;
+    let src_cwd = src_dao.chats(&src_ds.uuid)?.remove(0);
+    let src_msgs = src_dao.first_messages(&src_cwd.chat, usize::MAX)?;
+
+    // Rooted at the parent directory, so that paths have to be rebased
+    let root = src_ds_root.0.parent().unwrap().to_path_buf();
+    let mut dao = InMemoryDao::new("Two".to_owned(), root, vec![]);
+    let ds = dao.insert_dataset(Dataset { uuid: PbUuid::random(), alias: "Two".to_owned() })?;
+    let ds_root = dao.dataset_root(&ds.uuid)?;
+    for u in src_dao.users(&src_ds.uuid)? {
+        let is_myself = u.id == 1;
+        dao.insert_user(User { ds_uuid: ds.uuid.clone(), ..u }, is_myself)?;
+    }
+    let chat = dao.insert_chat(Chat { ds_uuid: ds.uuid.clone(), ..src_cwd.chat.clone() }, &src_ds_root)?;
+    assert_eq!(dao.insert_messages(src_msgs.clone(), &chat, &src_ds_root)?, Vec::<String>::new());
+
+    assert_eq!(dao.myself(&ds.uuid)?.id, 1);
+    assert_eq!(chat.get_img_path_option(&ds_root), src_cwd.chat.get_img_path_option(&src_ds_root));
+    let msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(msgs.iter().map(|m| m.internal_id).collect_vec(), vec![1, 2, 3]);
+    for (m, src_m) in msgs.iter().zip(src_msgs.iter()) {
+        assert_eq!(m.files(&ds_root), src_m.files(&src_ds_root));
+    }
+
+    // Files outside of the root can't be referenced
+    let other_tmp_dir = TmpDir::new();
+    let mut other_dao = InMemoryDao::new("Three".to_owned(), other_tmp_dir.path.clone(), vec![]);
+    let other_ds = other_dao.insert_dataset(Dataset { uuid: PbUuid::random(), alias: "Three".to_owned() })?;
+    assert!(other_dao.insert_chat(Chat { ds_uuid: other_ds.uuid, ..src_cwd.chat }, &src_ds_root).is_err());
+    Ok(())
+}
+
 //
 // Helpers
 //
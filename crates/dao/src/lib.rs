@@ -8,6 +8,7 @@ use chat_history_manager_core::utils::*;
 
 use deepsize::DeepSizeOf;
 use std::fmt::{Debug, Display, Formatter};
+use std::ops::Range;
 use std::path::Path;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::thread::JoinHandle;
@@ -16,7 +17,8 @@ use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
 
-const BATCH_SIZE: usize = 5_000;
+/// How many messages are passed around at once when pushing or copying chats.
+pub const BATCH_SIZE: usize = 5_000;
 
 pub trait WithCache {
     /// For internal use
@@ -182,6 +184,12 @@ pub trait ChatHistoryDao: WithCache + Send + Sync {
 
     fn message_option(&self, chat: &Chat, source_id: MessageSourceId) -> Result<Option<Message>>;
 
+    /// Full-text search over messages of a dataset, newest first.
+    /// Every term has to match whole tokens (see `search_tokens`), case-insensitively.
+    /// Fragment text is HTML-escaped, and matched terms in it are wrapped in
+    /// `SEARCH_HIGHLIGHT_START`/`SEARCH_HIGHLIGHT_END`, which is the only markup there is.
+    fn search_messages(&self, query: &MessageSearchQuery) -> Result<MessageSearchResult>;
+
     /** Whether given data path is the one loaded in this DAO */
     fn is_loaded(&self, storage_path: &Path) -> bool {
         self.storage_path() == storage_path
@@ -192,6 +200,9 @@ pub trait ChatHistoryDao: WithCache + Send + Sync {
 
     /// Return self as shiftable if applicable, otherwise error out
     fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryDao>;
+
+    /// Return self as verifiable if applicable, otherwise error out
+    fn as_verifiable(&mut self) -> Result<&mut dyn VerifiableChatHistoryDao>;
 }
 
 pub trait MutableChatHistoryDao: ChatHistoryDao {
@@ -231,7 +242,51 @@ pub trait MutableChatHistoryDao: ChatHistoryDao {
     /// Insert a new message for the given chat.
     /// Internal ID will be ignored.
     /// Content will be resolved based on the given dataset root and copied accordingly.
-    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> EmptyRes;
+    /// Returns source files that were actually copied, relative to the given dataset root;
+    /// missing files and ones already present in the destination are not included.
+    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>>;
+
+    /// Run the given in-place merge action against the given dataset atomically - if it fails,
+    /// all database changes it made are rolled back. Files copied in the meantime are not removed.
+    fn in_place_merge(&mut self,
+                      ds_uuid: &PbUuid,
+                      action: &mut dyn FnMut(&mut dyn InPlaceMergeTarget) -> EmptyRes) -> EmptyRes;
+}
+
+/// Dataset being merged into in place, see [MutableChatHistoryDao::in_place_merge].
+/// Unlike [MutableChatHistoryDao], existing entities keep their IDs, and messages keep their internal IDs
+/// unless they have to be moved.
+/// Dataset isn't readable through the DAO until the merge is over.
+pub trait InPlaceMergeTarget {
+    /// Note that profile pictures are NOT inserted and are discarded instead!
+    fn insert_user(&mut self, user: User, is_myself: bool) -> Result<User>;
+
+    /// Update an existing user with the same ID, same as [MutableChatHistoryDao::update_user].
+    fn update_user(&mut self, user: User) -> EmptyRes;
+
+    /// Replace user profile pictures with the given ones, copying them from the given paths. Excludes those not found.
+    fn replace_user_profile_pics(&mut self, user_id: UserId, profile_pics: Vec<AbsoluteProfilePicture>) -> EmptyRes;
+
+    /// Copies image (if any) from dataset root.
+    fn insert_chat(&mut self, chat: Chat, src_ds_root: &DatasetRoot) -> Result<Chat>;
+
+    /// Update an existing chat with the same ID, replacing its members. Image won't be copied/deleted.
+    fn update_chat(&mut self, chat: Chat) -> EmptyRes;
+
+    /// Append messages to the end of the given chat, see [MutableChatHistoryDao::insert_messages].
+    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>>;
+
+    /// Overwrite an existing message of the given chat, keeping its internal ID.
+    /// Returns source files that were actually copied, same as [Self::insert_messages].
+    fn update_message(&mut self, msg: Message, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>>;
+
+    /// Delete messages of the given chat with internal IDs in the given range (inclusive).
+    /// Content files are left in place since other messages might still refer to them.
+    fn delete_messages(&mut self, chat: &Chat, first_id: MessageInternalId, last_id: MessageInternalId) -> EmptyRes;
+
+    /// Move messages of the given chat with internal IDs in the given range (inclusive) after all the other messages,
+    /// keeping their relative order. This is the only way to put new messages before existing ones.
+    fn move_messages_to_end(&mut self, chat: &Chat, first_id: MessageInternalId, last_id: MessageInternalId) -> EmptyRes;
 }
 
 pub trait ShiftableChatHistoryDao: ChatHistoryDao {
@@ -239,6 +294,243 @@ pub trait ShiftableChatHistoryDao: ChatHistoryDao {
     fn shift_dataset_time(&mut self, uuid: &PbUuid, hours_shift: i32) -> EmptyRes;
 }
 
+pub trait VerifiableChatHistoryDao: ChatHistoryDao {
+    /// Look for inconsistencies in the dataset without changing anything.
+    fn verify(&self, ds_uuid: &PbUuid) -> Result<Vec<IntegrityIssue>>;
+
+    /// Fix issues previously found by [Self::verify], skipping those that can't be fixed automatically.
+    /// Returns the number of issues fixed.
+    fn fix_integrity_issues(&mut self, ds_uuid: &PbUuid, issues: &[IntegrityIssue]) -> Result<usize>;
+
+    /// Look for inconsistencies in the dataset, fixing found issues of the given kinds.
+    /// Returns all found issues (fixed ones included) and the number of issues fixed.
+    fn verify_and_fix(&mut self, ds_uuid: &PbUuid, fix_kinds: &[IntegrityIssueKind]) -> Result<(Vec<IntegrityIssue>, usize)> {
+        let issues = self.verify(ds_uuid)?;
+        let to_fix = issues.iter().filter(|issue| fix_kinds.contains(&issue.kind())).cloned().collect_vec();
+        let fixed_count = if to_fix.is_empty() { 0 } else { self.fix_integrity_issues(ds_uuid, &to_fix)? };
+        Ok((issues, fixed_count))
+    }
+}
+
+/// Receiver of a single dataset, which is pushed into it piece by piece by a loader,
+/// so that the whole dataset doesn't have to be kept in memory at once.
+///
+/// Expected order of calls:
+/// - [Self::begin_dataset] once;
+/// - [Self::add_chat] for every chat, each followed by any number of [Self::add_messages] batches for it;
+/// - [Self::add_users] once, either before or after all the chats;
+/// - [Self::finish_dataset] once.
+pub trait ChatHistorySink {
+    /// Dataset root is the one content paths are relative to.
+    fn begin_dataset(&mut self, name: String, ds: Dataset, ds_root: &Path) -> EmptyRes;
+
+    /// Users should include myself, and should be all users referenced by chats.
+    fn add_users(&mut self, users: Vec<User>, myself_id: UserId) -> EmptyRes;
+
+    /// Chat should have its members and message count already set, unless they're set later by [Self::finish_chat].
+    fn add_chat(&mut self, chat: Chat) -> EmptyRes;
+
+    /// Messages should be in order, with internal IDs already assigned.
+    fn add_messages(&mut self, chat: &Chat, msgs: Vec<Message>) -> EmptyRes;
+
+    /// Sets members and message count of an added chat, for loaders that only know them once all messages are added.
+    /// Other chat fields are left as they were.
+    fn finish_chat(&mut self, chat: &Chat) -> EmptyRes;
+
+    fn finish_dataset(&mut self) -> EmptyRes;
+}
+
+/// Adds a chat along with all its messages into the sink, passing messages in batches.
+pub fn push_chat(sink: &mut dyn ChatHistorySink, chat: Chat, msgs: Vec<Message>) -> EmptyRes {
+    sink.add_chat(chat.clone())?;
+    let mut msgs = msgs.into_iter();
+    loop {
+        let batch = msgs.by_ref().take(BATCH_SIZE).collect_vec();
+        if batch.is_empty() { return Ok(()); }
+        sink.add_messages(&chat, batch)?;
+    }
+}
+
+/// Pushes the whole dataset into the sink, reading messages in batches.
+pub fn push_dataset(src: &dyn ChatHistoryDao, ds_uuid: &PbUuid, sink: &mut dyn ChatHistorySink) -> EmptyRes {
+    let ds = src.datasets()?.into_iter().find(|ds| ds.uuid == *ds_uuid)
+        .with_context(|| format!("Dataset {} not found!", ds_uuid.value))?;
+    let ds_root = src.dataset_root(ds_uuid)?;
+    sink.begin_dataset(src.name().to_owned(), ds, &ds_root.0)?;
+    let myself = src.myself(ds_uuid)?;
+    sink.add_users(src.users(ds_uuid)?, myself.id())?;
+    for cwd in src.chats(ds_uuid)? {
+        sink.add_chat(cwd.chat.clone())?;
+        let mut offset: usize = 0;
+        loop {
+            let msgs = src.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+            let len = msgs.len();
+            if len > 0 {
+                sink.add_messages(&cwd.chat, msgs)?;
+            }
+            if len < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+    }
+    sink.finish_dataset()
+}
+
+pub const SEARCH_HIGHLIGHT_START: &str = "<b>";
+pub const SEARCH_HIGHLIGHT_END: &str = "</b>";
+
+/// Highlight markers as inserted by the search itself, replaced once the fragment is escaped.
+pub(crate) const RAW_HIGHLIGHT_START: char = '\u{2}';
+pub(crate) const RAW_HIGHLIGHT_END: char = '\u{3}';
+
+/// Splits text into lowercased tokens along with their byte ranges, same as FTS5 `unicode61` tokenizer does -
+/// token is a run of alphanumeric characters, anything else is a separator.
+/// Note that unlike SqliteDao index, diacritics are not removed here.
+pub fn search_tokens(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut res = vec![];
+    let mut start_option = None;
+    for (idx, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start_option) {
+            (true, None) => start_option = Some(idx),
+            (false, Some(start)) => {
+                res.push((start..idx, text[start..idx].to_lowercase()));
+                start_option = None;
+            }
+            _ => { /* NOOP */ }
+        }
+    }
+    if let Some(start) = start_option {
+        res.push((start..text.len(), text[start..].to_lowercase()));
+    }
+    res
+}
+
+/// HTML-escapes a fragment containing raw highlight markers, then turns those into
+/// `SEARCH_HIGHLIGHT_START`/`SEARCH_HIGHLIGHT_END`.
+pub(crate) fn escape_search_fragment(raw: &str) -> String {
+    let mut res = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            RAW_HIGHLIGHT_START => res.push_str(SEARCH_HIGHLIGHT_START),
+            RAW_HIGHLIGHT_END => res.push_str(SEARCH_HIGHLIGHT_END),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSearchQuery {
+    pub ds_uuid: PbUuid,
+    /// Whitespace-separated terms, all of which should be present in a message.
+    /// Term consisting of several tokens (e.g. `"well-known"`) matches them as a phrase.
+    pub text: String,
+    pub chat_id_option: Option<ChatId>,
+    pub from_id_option: Option<UserId>,
+    /// Inclusive
+    pub timestamp_from_option: Option<Timestamp>,
+    /// Inclusive
+    pub timestamp_to_option: Option<Timestamp>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl MessageSearchQuery {
+    /// Search terms, lowercased. Empty query has no terms.
+    pub fn terms(&self) -> Vec<String> {
+        self.text.split_whitespace().map(|t| t.to_lowercase()).collect_vec()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSearchHit {
+    pub chat_id: ChatId,
+    pub message: Message,
+    pub fragment: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSearchResult {
+    /// Total number of matched messages, regardless of offset/limit
+    pub total_count: usize,
+    pub hits: Vec<MessageSearchHit>,
+}
+
+/// Inconsistency in a dataset, paths are relative to dataset root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// Referenced file does not exist. Can't be fixed automatically.
+    MissingFile { path: String },
+    /// File under dataset root is not referenced by any message, chat or user.
+    /// Fixed by moving it to backup directory.
+    OrphanFile { path: String },
+    /// Messages author is not a member of the chat. Fixed by adding the author to chat members.
+    NonMemberAuthor { chat_id: ChatId, user_id: UserId, msg_count: usize },
+    /// Replied-to message is not in the chat, e.g. because it wasn't exported or was deleted.
+    /// Reported for information only, as the reference is still valid and there's nothing to fix.
+    DanglingReply { chat_id: ChatId, internal_id: MessageInternalId, reply_to_id: MessageSourceId },
+    /// Pinned message is not in the chat. Can't be fixed automatically.
+    DanglingPin { chat_id: ChatId, internal_id: MessageInternalId, pinned_id: MessageSourceId },
+    /// Stored chat message count doesn't match its messages. Fixed by recounting messages.
+    WrongMessageCount { chat_id: ChatId, stored: i32, actual: i32 },
+    /// Message has empty searchable string while it should have one. Fixed by recomputing it.
+    EmptySearchableString { chat_id: ChatId, internal_id: MessageInternalId },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntegrityIssueKind {
+    MissingFile,
+    OrphanFile,
+    NonMemberAuthor,
+    DanglingReply,
+    DanglingPin,
+    WrongMessageCount,
+    EmptySearchableString,
+}
+
+impl IntegrityIssue {
+    pub fn kind(&self) -> IntegrityIssueKind {
+        match self {
+            IntegrityIssue::MissingFile { .. } => IntegrityIssueKind::MissingFile,
+            IntegrityIssue::OrphanFile { .. } => IntegrityIssueKind::OrphanFile,
+            IntegrityIssue::NonMemberAuthor { .. } => IntegrityIssueKind::NonMemberAuthor,
+            IntegrityIssue::DanglingReply { .. } => IntegrityIssueKind::DanglingReply,
+            IntegrityIssue::DanglingPin { .. } => IntegrityIssueKind::DanglingPin,
+            IntegrityIssue::WrongMessageCount { .. } => IntegrityIssueKind::WrongMessageCount,
+            IntegrityIssue::EmptySearchableString { .. } => IntegrityIssueKind::EmptySearchableString,
+        }
+    }
+
+    pub fn is_fixable(&self) -> bool {
+        !matches!(self, IntegrityIssue::MissingFile { .. } | IntegrityIssue::DanglingReply { .. } |
+                        IntegrityIssue::DanglingPin { .. })
+    }
+}
+
+impl Display for IntegrityIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityIssue::MissingFile { path } =>
+                write!(f, "Referenced file {path} does not exist"),
+            IntegrityIssue::OrphanFile { path } =>
+                write!(f, "File {path} is not referenced"),
+            IntegrityIssue::NonMemberAuthor { chat_id, user_id, msg_count } =>
+                write!(f, "Chat {} has {msg_count} messages from non-member user {}", chat_id.0, user_id.0),
+            IntegrityIssue::DanglingReply { chat_id, internal_id, reply_to_id } =>
+                write!(f, "Message {} in chat {} replies to missing message {}", internal_id.0, chat_id.0, reply_to_id.0),
+            IntegrityIssue::DanglingPin { chat_id, internal_id, pinned_id } =>
+                write!(f, "Message {} in chat {} pins missing message {}", internal_id.0, chat_id.0, pinned_id.0),
+            IntegrityIssue::WrongMessageCount { chat_id, stored, actual } =>
+                write!(f, "Chat {} has message count {stored}, but {actual} messages", chat_id.0),
+            IntegrityIssue::EmptySearchableString { chat_id, internal_id } =>
+                write!(f, "Message {} in chat {} has empty searchable string", internal_id.0, chat_id.0),
+        }
+    }
+}
+
 type UserCache = HashMap<PbUuid, UserCacheForDataset>;
 
 #[derive(DeepSizeOf)]
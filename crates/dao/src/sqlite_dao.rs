@@ -1,4 +1,9 @@
+mod integrity;
 mod mapping;
+mod in_place_merge;
+mod media;
+mod relink;
+mod sink;
 mod utils;
 
 #[cfg(test)]
@@ -7,6 +12,7 @@ mod tests;
 
 use super::*;
 
+use std::cell::RefCell;
 use std::default::Default;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -23,6 +29,11 @@ use itertools::{Either, Itertools};
 use uuid::Uuid;
 
 use mapping::*;
+use media::MediaTarget;
+
+pub use media::{MediaDedupeReport, MediaLayout};
+pub use relink::{MediaRelink, MediaRelinkPlan, RelinkMatchKind};
+pub use sink::SqliteSink;
 
 
 // TODO: Make Send + Sync
@@ -31,6 +42,7 @@ pub struct SqliteDao {
     pub db_file: PathBuf,
     conn_pool: Mutex<Pool<ConnectionManager<SqliteConnection>>>,
     cache: DaoCache,
+    media_layout: MediaLayout,
 }
 
 impl SqliteDao {
@@ -79,11 +91,14 @@ impl SqliteDao {
             }
         }
 
+        let media_layout = media::load_media_layout(&mut conn)?;
+
         Ok(SqliteDao {
             name: format!("{} database", path_file_name(db_file.parent().unwrap())?),
             db_file: db_file.to_path_buf(),
             conn_pool: Mutex::new(conn_pool),
             cache: DaoCache::new(),
+            media_layout,
         })
     }
 
@@ -126,116 +141,120 @@ impl SqliteDao {
             ensure!(!self.datasets()?.iter().any(|ds| src_dataset_uuids.contains(&ds.uuid)),
                     "Some dataset UUIDs are already in use!");
 
-            let mut conn = self.get_conn()?;
+            let res = self.copy_datasets_inner(src, &src_datasets);
+            if res.is_err() {
+                // Sink writes datasets piece by piece, so we don't leave partially copied datasets behind
+                self.invalidate_cache()?;
+                for ds in self.datasets()?.into_iter().filter(|ds| src_dataset_uuids.contains(&ds.uuid)) {
+                    self.delete_dataset_inner(&ds.uuid)?;
+                }
+                return res;
+            }
 
-            for src_ds in src_datasets.iter() {
-                let ds_uuid = &src_ds.uuid;
-                let src_myself = src.myself(ds_uuid)?;
+            self.vacuum()
+        }, |_, t| log::info!("Dao '{}' fully copied {t} ms", src.name()))
+    }
 
-                measure(|| {
-                    use schema::*;
+    fn copy_datasets_inner(&self, src: &dyn ChatHistoryDao, src_datasets: &[Dataset]) -> EmptyRes {
+        for src_ds in src_datasets.iter() {
+            let ds_uuid = &src_ds.uuid;
+            measure(|| {
+                push_dataset(src, ds_uuid, &mut self.sink()?)
+            }, |_, t| log::info!("Dataset '{}' inserted in {t} ms", ds_uuid.value))?;
+        }
 
-                    let raw_ds = utils::dataset::serialize(src_ds);
-
-                    let src_ds_root = src.dataset_root(ds_uuid)?;
-                    let dst_ds_root = self.dataset_root(ds_uuid)?;
-
-                    conn.transaction(|txn| {
-                        insert_into(dataset::table).values(&raw_ds).execute(txn)?;
-
-                        let raw_users_with_pictures: Vec<(RawUser, Vec<RawProfilePicture>)> =
-                            src.users(ds_uuid)?.iter().map(|u| {
-                                ensure!(u.id > 0, "IDs should be positive!");
-                                let raw_user = utils::user::serialize(u, *u == src_myself, &raw_ds.uuid);
-                                let raw_pictures: Vec<RawProfilePicture> =
-                                    u.profile_pictures.iter()
-                                        .map(|pp| (pp, src_ds_root.to_absolute(&pp.path)))
-                                        .filter(|(_, path)| path.exists())
-                                        .enumerate()
-                                        .map(|(idx, (pp, path))| {
-                                            utils::user::profile_picture::serialize_and_copy(
-                                                u.id(), &raw_ds.uuid, &path,
-                                                pp.frame_option.as_ref(), idx, &dst_ds_root,
-                                            )
-                                        })
-                                        .try_collect()?;
-                                Ok((raw_user, raw_pictures))
-                            }).try_collect()?;
-                        let (raw_users, raw_pictures): (Vec<RawUser>, Vec<Vec<RawProfilePicture>>) =
-                            raw_users_with_pictures.into_iter().unzip();
-                        let raw_pictures = raw_pictures.into_iter().flatten().collect_vec();
-                        insert_into(user::table).values(&raw_users).execute(txn)?;
-                        insert_into(profile_picture::table).values(&raw_pictures).execute(txn)?;
-                        ok(())
-                    })?;
-
-                    for src_cwd in src.chats(ds_uuid)?.iter() {
-                        ensure!(src_cwd.chat.id > 0, "IDs should be positive!");
-                        ensure!(src_cwd.chat.member_ids.first() == Some(&src_myself.id),
-                                "First member of chat {} was not myself!", src_cwd.chat.qualified_name());
-
-                        conn.transaction(|txn| {
-                            let mut raw_chat = utils::chat::serialize(&src_cwd.chat, &raw_ds.uuid)?;
-                            if let Some(ref img) = src_cwd.chat.img_path_option {
-                                raw_chat.img_path =
-                                    copy_chat_file(img, None, None, &subpaths::ROOT,
-                                                   src_cwd.chat.id, &src_ds_root, &dst_ds_root)?;
-                            }
-                            insert_into(chat::table).values(raw_chat).execute(txn)?;
-                            insert_into(chat_member::table)
-                                .values(src_cwd.chat.member_ids.iter()
-                                    .enumerate()
-                                    .map(|(order, &user_id)|
-                                        RawChatMember {
-                                            ds_uuid: raw_ds.uuid.clone(),
-                                            chat_id: src_cwd.chat.id,
-                                            user_id,
-                                            order: order as i32,
-                                        })
-                                    .collect_vec())
-                                .execute(txn)?;
-                            ok(())
-                        })?;
-
-                        const BATCH_SIZE: usize = 5_000;
-                        let mut offset: usize = 0;
-                        loop {
-                            let src_msgs = src.scroll_messages(&src_cwd.chat, offset, BATCH_SIZE)?;
-
-                            // Copy messages
-                            conn.transaction(|txn| {
-                                self.copy_messages(txn, &src_msgs, src_cwd.chat.id,
-                                                   &raw_ds.uuid, &src_ds_root, &dst_ds_root)
-                            }).with_context(||
-                                format!("Failed to copy messages #{} through #{} (offset {offset}) of chat {}",
-                                        src_msgs.first().unwrap().internal_id,
-                                        src_msgs.last().unwrap().internal_id,
-                                        src_cwd.chat.qualified_name())
-                            )?;
-
-                            if src_msgs.len() < BATCH_SIZE { break; }
-                            offset += BATCH_SIZE;
-                        }
-                    }
+        self.invalidate_cache()?;
 
-                    vacuum(&mut conn)?;
+        assert!(self.datasets()?.len() >= src_datasets.len(), "Some datasets are missing after merge!");
 
-                    Ok(())
-                }, |_, t| log::info!("Dataset '{}' inserted in {t} ms", ds_uuid.value))?;
-            }
+        for src_ds in src_datasets.iter() {
+            let ds_uuid = &src_ds.uuid;
+            let diff = get_datasets_diff(src, ds_uuid, self, ds_uuid, 1)?;
+            ensure!(diff.is_empty(), "{}", diff.iter().join("\n\n"))
+        }
+
+        Ok(())
+    }
 
-            self.invalidate_cache()?;
+    fn delete_dataset_inner(&self, ds_uuid: &PbUuid) -> EmptyRes {
+        self.invalidate_cache()?;
+        let mut conn = self.get_conn()?;
+
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+        let ds_root = self.dataset_root(ds_uuid)?;
+
+        use schema::*;
+
+        conn.transaction(|conn| {
+            let mut delete_by_ds_uuid = |sql: &str| -> QueryResult<usize> {
+                sql_query(sql)
+                    .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                    .execute(conn)
+            };
+
+            // Messages
+            delete_by_ds_uuid(r"
+                INSERT INTO message_fts(message_fts, rowid, searchable_string)
+                SELECT 'delete', internal_id, searchable_string FROM message
+                WHERE ds_uuid = ?
+            ")?;
+            delete_by_ds_uuid(r"
+                DELETE FROM message_content
+                WHERE message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ?
+                )
+            ")?;
+            delete_by_ds_uuid(r"
+                DELETE FROM message_text_element
+                WHERE message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ?
+                )
+            ")?;
+            delete_by_ds_uuid(r"
+                DELETE FROM message_reaction
+                WHERE message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ?
+                )
+            ")?;
+            delete(message::dsl::message)
+                .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+
+            // Chats
+            delete(chat_member::dsl::chat_member)
+                .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(chat::dsl::chat)
+                .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+
+            // Users
+            delete(profile_picture::dsl::profile_picture)
+                .filter(profile_picture::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(user::dsl::user)
+                .filter(user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
 
-            assert!(self.datasets()?.len() >= src_datasets.len(), "Some datasets are missing after merge!");
+            // Finally, dataset itself
+            let deleted_rows = delete(dataset::dsl::dataset)
+                .filter(dataset::columns::uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            ensure!(deleted_rows == 1, "{deleted_rows} rows changed when deleting dataset with UUID {:?}", ds_uuid);
 
-            for src_ds in src_datasets.iter() {
-                let ds_uuid = &src_ds.uuid;
-                let diff = get_datasets_diff(src, ds_uuid, self, ds_uuid, 1)?;
-                ensure!(diff.is_empty(), "{}", diff.iter().join("\n\n"))
+            // Moving all dataset files to backup directory.
+            // Content-addressed files are only shared within a dataset, so they can go too.
+            if ds_root.0.exists() {
+                let target = self.choose_final_backup_path("")?.join(path_file_name(&ds_root.0)?);
+                fs::create_dir_all(&target)?;
+                fs::rename(&ds_root.0, &target)?;
             }
 
             Ok(())
-        }, |_, t| log::info!("Dao '{}' fully copied {t} ms", src.name()))
+        })
     }
 
     fn fetch_messages<F>(&self, get_raw_messages: F) -> Result<Vec<Message>>
@@ -251,9 +270,9 @@ impl SqliteDao {
                      chat_id: i64,
                      raw_uuid: &[u8],
                      src_ds_root: &DatasetRoot,
-                     dst_ds_root: &DatasetRoot) -> EmptyRes {
+                     dst: &MediaTarget) -> EmptyRes {
         let full_raw_msgs: Vec<FullRawMessage> = src_msgs.iter()
-            .map(|m| utils::message::serialize_and_copy_files(m, chat_id, raw_uuid, src_ds_root, dst_ds_root))
+            .map(|m| utils::message::serialize_and_copy_files(m, chat_id, raw_uuid, src_ds_root, dst))
             .try_collect()?;
 
         // Don't see a way around cloning here.
@@ -272,8 +291,13 @@ impl SqliteDao {
             .load(conn)?;
         internal_ids.reverse();
 
+        if let (Some(&first_id), Some(&last_id)) = (internal_ids.first(), internal_ids.last()) {
+            fts_index(conn, first_id, last_id)?;
+        }
+
         let mut raw_mcs = vec![];
         let mut raw_rtes = vec![];
+        let mut raw_reactions = vec![];
         for (mut raw, internal_id) in full_raw_msgs.into_iter().zip(internal_ids) {
             for mut mc in raw.mc.into_iter() {
                 mc.message_internal_id = Some(internal_id);
@@ -282,10 +306,14 @@ impl SqliteDao {
 
             raw.rtes.iter_mut().for_each(|rte| rte.message_internal_id = Some(internal_id));
             raw_rtes.extend(raw.rtes.into_iter());
+
+            raw.reactions.iter_mut().for_each(|r| r.message_internal_id = Some(internal_id));
+            raw_reactions.extend(raw.reactions);
         }
 
         insert_into(message_content::table).values(raw_mcs).execute(conn)?;
         insert_into(message_text_element::table).values(raw_rtes).execute(conn)?;
+        insert_into(message_reaction::table).values(raw_reactions).execute(conn)?;
         Ok(())
     }
 
@@ -583,6 +611,77 @@ impl ChatHistoryDao for SqliteDao {
         }).map(|mut v| v.pop())
     }
 
+    fn search_messages(&self, query: &MessageSearchQuery) -> Result<MessageSearchResult> {
+        let terms = query.terms();
+        if terms.is_empty() {
+            return Ok(MessageSearchResult { total_count: 0, hits: vec![] });
+        }
+        let match_expr = fts_match_expression(&terms);
+        let uuid = Uuid::parse_str(&query.ds_uuid.value)?;
+        let mut conn = self.get_conn()?;
+
+        const FROM_WHERE: &str = r"
+            FROM message_fts
+            INNER JOIN message m ON m.internal_id = message_fts.rowid
+            WHERE message_fts MATCH ?1
+              AND m.ds_uuid = ?2
+              AND (?3 IS NULL OR m.chat_id = ?3)
+              AND (?4 IS NULL OR m.from_id = ?4)
+              AND (?5 IS NULL OR m.time_sent >= ?5)
+              AND (?6 IS NULL OR m.time_sent <= ?6)
+        ";
+
+        macro_rules! bind_filters {
+            ($sql:expr) => {
+                sql_query($sql)
+                    .bind::<sql_types::Text, _>(&match_expr)
+                    .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                    .bind::<sql_types::Nullable<sql_types::BigInt>, _>(query.chat_id_option.map(|id| *id))
+                    .bind::<sql_types::Nullable<sql_types::BigInt>, _>(query.from_id_option.map(|id| *id))
+                    .bind::<sql_types::Nullable<sql_types::BigInt>, _>(query.timestamp_from_option.map(|ts| *ts))
+                    .bind::<sql_types::Nullable<sql_types::BigInt>, _>(query.timestamp_to_option.map(|ts| *ts))
+            };
+        }
+
+        measure(|| {
+            let total_count = bind_filters!(format!("SELECT COUNT(*) AS count {FROM_WHERE}"))
+                .get_result::<CountWrapper>(&mut conn)?
+                .count as usize;
+
+            let raw_hits = bind_filters!(format!(r"
+                SELECT m.internal_id, m.chat_id, snippet(message_fts, 0, ?7, ?8, '…', 16) AS fragment
+                {FROM_WHERE}
+                ORDER BY m.time_sent DESC, m.internal_id DESC
+                LIMIT ?9 OFFSET ?10
+            "))
+                .bind::<sql_types::Text, _>(RAW_HIGHLIGHT_START.to_string())
+                .bind::<sql_types::Text, _>(RAW_HIGHLIGHT_END.to_string())
+                .bind::<sql_types::BigInt, _>(query.limit as i64)
+                .bind::<sql_types::BigInt, _>(query.offset as i64)
+                .load::<SearchHitWrapper>(&mut conn)?;
+
+            let internal_ids = raw_hits.iter().map(|h| h.internal_id).collect_vec();
+            let mut msgs_by_id: HashMap<i64, Message> = utils::message::fetch(&mut conn, |conn| {
+                use schema::*;
+                Ok(message::table
+                    .filter(message::columns::internal_id.eq_any(&internal_ids))
+                    .select(RawMessage::as_select())
+                    .load(conn)?)
+            })?.into_iter().map(|m| (m.internal_id, m)).collect();
+
+            let hits: Vec<MessageSearchHit> = raw_hits.into_iter()
+                .map(|h| ok(MessageSearchHit {
+                    chat_id: ChatId(h.chat_id),
+                    message: msgs_by_id.remove(&h.internal_id)
+                        .with_context(|| format!("Message with internal ID {} not found", h.internal_id))?,
+                    fragment: escape_search_fragment(&h.fragment),
+                }))
+                .try_collect()?;
+
+            Ok(MessageSearchResult { total_count, hits })
+        }, |_: &Result<_>, t| log::info!("Searched messages for '{}' in {t} ms", query.text))
+    }
+
     fn as_mutable(&mut self) -> Result<&mut dyn MutableChatHistoryDao> {
         Ok(self)
     }
@@ -590,6 +689,10 @@ impl ChatHistoryDao for SqliteDao {
     fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryDao> {
         Ok(self)
     }
+
+    fn as_verifiable(&mut self) -> Result<&mut dyn VerifiableChatHistoryDao> {
+        Ok(self)
+    }
 }
 
 impl MutableChatHistoryDao for SqliteDao {
@@ -706,71 +809,7 @@ impl MutableChatHistoryDao for SqliteDao {
     }
 
     fn delete_dataset(&mut self, ds_uuid: PbUuid) -> EmptyRes {
-        self.invalidate_cache()?;
-        let mut conn = self.get_conn()?;
-
-        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
-        let ds_root = self.dataset_root(&ds_uuid)?;
-
-        use schema::*;
-
-        conn.transaction(|conn| {
-            let mut delete_by_ds_uuid = |sql: &str| -> QueryResult<usize> {
-                sql_query(sql)
-                    .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
-                    .execute(conn)
-            };
-
-            // Messages
-            delete_by_ds_uuid(r"
-                DELETE FROM message_content
-                WHERE message_internal_id IN (
-                    SELECT internal_id FROM message
-                    WHERE ds_uuid = ?
-                )
-            ")?;
-            delete_by_ds_uuid(r"
-                DELETE FROM message_text_element
-                WHERE message_internal_id IN (
-                    SELECT internal_id FROM message
-                    WHERE ds_uuid = ?
-                )
-            ")?;
-            delete(message::dsl::message)
-                .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                .execute(conn)?;
-
-            // Chats
-            delete(chat_member::dsl::chat_member)
-                .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                .execute(conn)?;
-            delete(chat::dsl::chat)
-                .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                .execute(conn)?;
-
-            // Users
-            delete(profile_picture::dsl::profile_picture)
-                .filter(profile_picture::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                .execute(conn)?;
-            delete(user::dsl::user)
-                .filter(user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                .execute(conn)?;
-
-            // Finally, dataset itself
-            let deleted_rows = delete(dataset::dsl::dataset)
-                .filter(dataset::columns::uuid.eq(uuid.as_bytes().as_slice()))
-                .execute(conn)?;
-            ensure!(deleted_rows == 1, "{deleted_rows} rows changed when deleting dataset with UUID {:?}", ds_uuid);
-
-            // Moving all dataset files to backup directory
-            if ds_root.0.exists() {
-                let target = self.choose_final_backup_path("")?.join(path_file_name(&ds_root.0)?);
-                fs::create_dir_all(&target)?;
-                fs::rename(&ds_root.0, &target)?;
-            }
-
-            Ok(())
-        })
+        self.delete_dataset_inner(&ds_uuid)
     }
 
     fn insert_user(&mut self, mut user: User, is_myself: bool) -> Result<User> {
@@ -803,98 +842,15 @@ impl MutableChatHistoryDao for SqliteDao {
         self.invalidate_cache()?;
         let mut conn = self.get_conn()?;
 
-        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
-        let raw_user = utils::user::serialize(&user, is_myself, &Vec::from(uuid.as_bytes().as_slice()));
-        let id_changed = user.id != *old_id;
-
         conn.transaction(|conn| {
-            use schema::*;
-            defer_fk(conn)?;
-
-            let updated_rows = update(user::dsl::user)
-                .filter(user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                .filter(user::columns::id.eq(*old_id))
-                .set((user::columns::id.eq(user.id), &raw_user))
-                .execute(conn)?;
-            ensure!(updated_rows == 1, "{updated_rows} rows changed when updaing user {:?}", user);
-
-            // We assume profile pictures didn't change
-            let src_profile_pics_path = ds_root.to_absolute(&user_root_rel_path(old_id));
-            if id_changed && src_profile_pics_path.exists() {
-                fs::rename(&src_profile_pics_path,
-                           ds_root.to_absolute(&user_root_rel_path(user.id())))?;
-            }
-
-            // After changing user, rename private chat(s) with him accordingly. If user is self, do nothing.
-            if !is_myself {
-                let chat_ids: Vec<i64> = chat_member::table
-                    .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                    .filter(chat_member::columns::user_id.eq(user.id))
-                    .select(chat_member::columns::chat_id)
-                    .load(conn)?;
-
-                use utils::EnumSerialization;
-                update(chat::dsl::chat)
-                    .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                    .filter(chat::columns::id.eq_any(chat_ids))
-                    .filter(chat::columns::tpe.eq(ChatType::serialize(ChatType::Personal as i32)?))
-                    .set(chat::columns::name.eq(user.pretty_name_option()))
-                    .execute(conn)?;
-            }
-
-            // If user ID changed, we need to update membership accordingly
-            if id_changed {
-                update(message::dsl::message)
-                    .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                    .filter(message::columns::from_id.eq(*old_id))
-                    .set(message::columns::from_id.eq(user.id))
-                    .execute(conn)?;
-
-                update(chat_member::dsl::chat_member)
-                    .filter(chat_member::columns::user_id.eq(*old_id))
-                    .set(chat_member::columns::user_id.eq(user.id))
-                    .execute(conn)?;
-            }
-
-            // Update user name in "members" string field
-            if let Some(old_name) = old_name {
-                let new_name = user.pretty_name();
-
-                let old_mc_members: Vec<(i64, Option<String>)> = message_content::table
-                    .inner_join(message::table)
-                    .inner_join(chat::table
-                        .on(chat::columns::ds_uuid.eq(message::columns::ds_uuid)
-                            .and(chat::columns::id.eq(message::columns::chat_id))))
-                    .inner_join(chat_member::table
-                        .on(chat_member::columns::ds_uuid.eq(chat::columns::ds_uuid)
-                            .and(chat_member::columns::chat_id.eq(chat::columns::id))))
-                    .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
-                    .filter(chat_member::columns::user_id.eq(user.id))
-                    .filter(message_content::columns::members.like(format!("%{old_name}%")))
-                    .select((message_content::columns::id, message_content::columns::members))
-                    .load(conn)?;
-
-                for (id, members_string) in old_mc_members {
-                    let new_members_string = utils::serialize_arr(&utils::deserialize_arr(members_string)
-                        .into_iter()
-                        .map(|s| if s == old_name { new_name.clone() } else { s })
-                        .collect_vec());
-
-                    update(message_content::table)
-                        .filter(message_content::columns::id.eq(id))
-                        .set(message_content::columns::members.eq(new_members_string))
-                        .execute(conn)?;
-                }
-            }
-
-            Ok(())
+            update_user_inner(conn, old_id, &user, is_myself, &ds_root, old_name)
         })?;
 
         Ok(user)
     }
 
     fn update_user_profile_pics(&mut self, user: User, new_profile_pics: Vec<AbsoluteProfilePicture>) -> Result<User> {
-        let dst_ds_root = self.dataset_root(&user.ds_uuid)?;
+        let dst = self.media_target(&user.ds_uuid)?;
 
         let uuid = Uuid::parse_str(&user.ds_uuid.value).expect("Invalid UUID!");
         let raw_uuid = Vec::from(uuid.as_bytes().as_slice());
@@ -905,7 +861,7 @@ impl MutableChatHistoryDao for SqliteDao {
             .enumerate()
             .map(|(idx, pic)| {
                 utils::user::profile_picture::serialize_and_copy(
-                    user.id(), &raw_uuid, &pic.absolute_path, pic.frame_option.as_ref(), idx, &dst_ds_root)
+                    user.id(), &raw_uuid, &pic.absolute_path, pic.frame_option.as_ref(), idx, &dst)
             })
             .try_collect()?;
 
@@ -927,9 +883,9 @@ impl MutableChatHistoryDao for SqliteDao {
 
     fn insert_chat(&mut self, mut chat: Chat, src_ds_root: &DatasetRoot) -> Result<Chat> {
         if let Some(ref img) = chat.img_path_option {
-            let dst_ds_root = self.dataset_root(&chat.ds_uuid)?;
+            let dst = self.media_target(&chat.ds_uuid)?;
             chat.img_path_option = copy_chat_file(img, None, None, &subpaths::ROOT,
-                                                  chat.id, src_ds_root, &dst_ds_root)?;
+                                                  chat.id, src_ds_root, &dst)?;
         }
 
         let uuid = Uuid::parse_str(&chat.ds_uuid.value).expect("Invalid UUID!");
@@ -1083,6 +1039,11 @@ impl MutableChatHistoryDao for SqliteDao {
             }
 
             // Messages
+            delete_by_ds_and_chat(r"
+                INSERT INTO message_fts(message_fts, rowid, searchable_string)
+                SELECT 'delete', internal_id, searchable_string FROM message
+                WHERE ds_uuid = ? AND chat_id = ?
+            ", conn)?;
             delete_by_ds_and_chat(r"
                 DELETE FROM message_content
                 WHERE message_internal_id IN (
@@ -1097,6 +1058,13 @@ impl MutableChatHistoryDao for SqliteDao {
                     WHERE ds_uuid = ? AND chat_id = ?
                 )
             ", conn)?;
+            delete_by_ds_and_chat(r"
+                DELETE FROM message_reaction
+                WHERE message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ? AND chat_id = ?
+                )
+            ", conn)?;
             delete(message::dsl::message)
                 .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
                 .filter(message::columns::chat_id.eq(chat.id))
@@ -1145,6 +1113,16 @@ impl MutableChatHistoryDao for SqliteDao {
                 .filter(user::columns::id.eq_any(&orphan_user_ids))
                 .execute(conn)?;
 
+            // Content-addressed files might still be referenced by the rest of the dataset
+            let mut unreferenced_paths = vec![];
+            for relative in relative_paths.into_iter().unique() {
+                if !media::is_content_addressed(&relative) ||
+                    !media::is_referenced(conn, uuid.as_bytes().as_slice(), &relative)? {
+                    unreferenced_paths.push(relative);
+                }
+            }
+            let relative_paths = unreferenced_paths;
+
             // Moving all dataset files to backup directory
             let backup_ds_root = self.choose_final_backup_path("")?.join(path_file_name(&ds_root.0)?);
             for relative in relative_paths.iter() {
@@ -1156,24 +1134,7 @@ impl MutableChatHistoryDao for SqliteDao {
                 }
             }
 
-            let src_paths_parents: HashSet<_> = relative_paths.iter()
-                .filter_map(|relative| ds_root.to_absolute(relative).parent().map(|p| p.to_path_buf()))
-                .collect();
-
-            // Remove all empty parent directories
-            for dir in src_paths_parents {
-                let mut parent_holder = Some(dir.as_path());
-                while let Some(dir) = parent_holder {
-                    if dir.exists() {
-                        if fs::read_dir(dir)?.next().is_some() {
-                            // Directory not empty
-                            break;
-                        }
-                        fs::remove_dir(dir)?
-                    }
-                    parent_holder = dir.parent()
-                }
-            }
+            remove_empty_parent_dirs(&ds_root, &relative_paths)?;
 
             Ok(())
         })
@@ -1198,17 +1159,28 @@ impl MutableChatHistoryDao for SqliteDao {
         Ok(())
     }
 
-    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> EmptyRes {
+    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>> {
         let mut conn = self.get_conn()?;
 
-        let dst_ds_root = self.dataset_root(&chat.ds_uuid)?;
+        let dst = self.media_target(&chat.ds_uuid)?;
         let uuid = Uuid::parse_str(&chat.ds_uuid.value).expect("Invalid UUID!");
         let uuid_bytes = Vec::from(uuid.as_bytes().as_slice());
 
         self.copy_messages(&mut conn, &msgs, chat.id,
-                           &uuid_bytes, src_ds_root, &dst_ds_root)?;
+                           &uuid_bytes, src_ds_root, &dst)?;
 
-        Ok(())
+        dst.copied_files.into_inner().iter()
+            .map(|f| path_to_str(f.strip_prefix(&src_ds_root.0).unwrap_or(f)).map(|s| s.to_owned()))
+            .try_collect()
+    }
+
+    fn in_place_merge(&mut self,
+                      ds_uuid: &PbUuid,
+                      action: &mut dyn FnMut(&mut dyn InPlaceMergeTarget) -> EmptyRes) -> EmptyRes {
+        let res = self.in_place_merge_inner(ds_uuid, action);
+        // Cache is not consulted during merge, but whatever it has is stale either way
+        self.invalidate_cache()?;
+        res
     }
 }
 
@@ -1240,6 +1212,167 @@ impl ShiftableChatHistoryDao for SqliteDao {
 const BACKUPS_DIR_NAME: &str = "_backups";
 const BACKUP_NAME_PREFIX: &str = "backup_";
 
+/// Removes all parent directories of given files that became empty.
+fn remove_empty_parent_dirs(ds_root: &DatasetRoot, relative_paths: &[String]) -> EmptyRes {
+    let parents: HashSet<_> = relative_paths.iter()
+        .filter_map(|relative| ds_root.to_absolute(relative).parent().map(|p| p.to_path_buf()))
+        .collect();
+
+    for dir in parents {
+        let mut parent_holder = Some(dir.as_path());
+        while let Some(dir) = parent_holder {
+            if dir.exists() {
+                if fs::read_dir(dir)?.next().is_some() {
+                    // Directory not empty
+                    break;
+                }
+                fs::remove_dir(dir)?
+            }
+            parent_holder = dir.parent()
+        }
+    }
+    Ok(())
+}
+
+/// Updates user (possibly changing its ID) along with everything referencing it:
+/// personal chat names, message senders, reactions, chat memberships and names in service messages.
+/// Profile pictures are assumed to be unchanged.
+fn update_user_inner(conn: &mut SqliteConnection,
+                     old_id: UserId,
+                     user: &User,
+                     is_myself: bool,
+                     ds_root: &DatasetRoot,
+                     old_name: Option<String>) -> EmptyRes {
+    let uuid = Uuid::parse_str(&user.ds_uuid.value).expect("Invalid UUID!");
+    let raw_user = utils::user::serialize(user, is_myself, &Vec::from(uuid.as_bytes().as_slice()));
+    let id_changed = user.id != *old_id;
+
+    use schema::*;
+    defer_fk(conn)?;
+
+    let updated_rows = update(user::dsl::user)
+        .filter(user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+        .filter(user::columns::id.eq(*old_id))
+        .set((user::columns::id.eq(user.id), &raw_user))
+        .execute(conn)?;
+    ensure!(updated_rows == 1, "{updated_rows} rows changed when updaing user {:?}", user);
+
+    // We assume profile pictures didn't change
+    let src_profile_pics_path = ds_root.to_absolute(&user_root_rel_path(old_id));
+    if id_changed && src_profile_pics_path.exists() {
+        fs::rename(&src_profile_pics_path,
+                   ds_root.to_absolute(&user_root_rel_path(user.id())))?;
+    }
+
+    // After changing user, rename private chat(s) with him accordingly. If user is self, do nothing.
+    if !is_myself {
+        let chat_ids: Vec<i64> = chat_member::table
+            .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(chat_member::columns::user_id.eq(user.id))
+            .select(chat_member::columns::chat_id)
+            .load(conn)?;
+
+        use utils::EnumSerialization;
+        update(chat::dsl::chat)
+            .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(chat::columns::id.eq_any(chat_ids))
+            .filter(chat::columns::tpe.eq(ChatType::serialize(ChatType::Personal as i32)?))
+            .set(chat::columns::name.eq(user.pretty_name_option()))
+            .execute(conn)?;
+    }
+
+    // If user ID changed, we need to update membership accordingly
+    if id_changed {
+        update(message::dsl::message)
+            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(message::columns::from_id.eq(*old_id))
+            .set(message::columns::from_id.eq(user.id))
+            .execute(conn)?;
+
+        update(chat_member::dsl::chat_member)
+            .filter(chat_member::columns::user_id.eq(*old_id))
+            .set(chat_member::columns::user_id.eq(user.id))
+            .execute(conn)?;
+
+        let old_reactions: Vec<(i64, Option<String>)> = message_reaction::table
+            .inner_join(message::table)
+            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(message_reaction::columns::from_ids.like(format!("%{}%", *old_id)))
+            .select((message_reaction::columns::id, message_reaction::columns::from_ids))
+            .load(conn)?;
+
+        let old_id_string = (*old_id).to_string();
+        for (id, from_ids) in old_reactions {
+            let from_ids = from_ids.unwrap_or_default();
+            let new_from_ids = from_ids.split(',')
+                .map(|s| if s == old_id_string { user.id.to_string() } else { s.to_owned() })
+                .join(",");
+            if new_from_ids != from_ids {
+                update(message_reaction::table)
+                    .filter(message_reaction::columns::id.eq(id))
+                    .set(message_reaction::columns::from_ids.eq(new_from_ids))
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    // Update user name in "members" string field
+    if let Some(old_name) = old_name {
+        let new_name = user.pretty_name();
+
+        let old_mc_members: Vec<(i64, Option<i64>, Option<String>)> = message_content::table
+            .inner_join(message::table)
+            .inner_join(chat::table
+                .on(chat::columns::ds_uuid.eq(message::columns::ds_uuid)
+                    .and(chat::columns::id.eq(message::columns::chat_id))))
+            .inner_join(chat_member::table
+                .on(chat_member::columns::ds_uuid.eq(chat::columns::ds_uuid)
+                    .and(chat_member::columns::chat_id.eq(chat::columns::id))))
+            .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(chat_member::columns::user_id.eq(user.id))
+            .filter(message_content::columns::members.like(format!("%{old_name}%")))
+            .select((message_content::columns::id,
+                     message_content::columns::message_internal_id,
+                     message_content::columns::members))
+            .load(conn)?;
+
+        let msg_internal_ids = old_mc_members.iter().filter_map(|(_, id, _)| *id).unique().collect_vec();
+
+        for (id, _, members_string) in old_mc_members {
+            let new_members_string = utils::serialize_arr(&utils::deserialize_arr(members_string)
+                .into_iter()
+                .map(|s| if s == old_name { new_name.clone() } else { s })
+                .collect_vec());
+
+            update(message_content::table)
+                .filter(message_content::columns::id.eq(id))
+                .set(message_content::columns::members.eq(new_members_string))
+                .execute(conn)?;
+        }
+
+        // Members are a part of a searchable string, so it needs to be updated as well
+        let msgs = utils::message::fetch(conn, |conn| {
+            Ok(message::table
+                .filter(message::columns::internal_id.eq_any(&msg_internal_ids))
+                .select(RawMessage::as_select())
+                .load(conn)?)
+        })?;
+        for msg in msgs {
+            let searchable_string = make_searchable_string(&msg.text, msg.typed());
+            if searchable_string != msg.searchable_string {
+                fts_unindex(conn, msg.internal_id, msg.internal_id)?;
+                update(message::table)
+                    .filter(message::columns::internal_id.eq(msg.internal_id))
+                    .set(message::columns::searchable_string.eq(searchable_string))
+                    .execute(conn)?;
+                fts_index(conn, msg.internal_id, msg.internal_id)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn chat_root_rel_path(chat_id: i64) -> String {
     format!("chat_{chat_id}")
 }
@@ -1272,12 +1405,13 @@ mod subpaths {
 /// If source file doesn't exist, return None.
 /// If destination file already exists, check if it's the same as source file.
 /// If source file doesn't have an extension, use MIME type to determine and add it.
+/// With content-addressed layout, subpath prefix and thumbnail main path are ignored.
 fn copy_file(src_file: &Path,
              src_mime: Option<&str>,
              thumbnail_dst_main_path: Option<&str>,
              subpath_prefix: &str,
              subpath: &Subpath,
-             dst_ds_root: &DatasetRoot) -> Result<Option<String>> {
+             dst: &MediaTarget) -> Result<Option<String>> {
     let src_absolute_path = path_to_str(src_file)?;
     let hash_result = file_hash(src_file)?;
     let FileHash::Valid { hash, .. } = hash_result else {
@@ -1293,7 +1427,9 @@ fn copy_file(src_file: &Path,
     let ext_suffix = ext.map(|ext| format!(".{ext}")).unwrap_or_default();
 
     let dst_rel_path: String =
-        if let Some(main_path) = thumbnail_dst_main_path {
+        if dst.layout == MediaLayout::ContentAddressed {
+            media::content_addressed_rel_path(hash, &ext_suffix)
+        } else if let Some(main_path) = thumbnail_dst_main_path {
             let full_name = main_path.rsplit('/').next().unwrap();
             format!("{}{full_name}_thumb{ext_suffix}", main_path.smart_slice(..-(full_name.len() as i32)))
         } else {
@@ -1309,16 +1445,19 @@ fn copy_file(src_file: &Path,
             };
             format!("{subpath_prefix}/{}/{inner_path}", subpath.path_fragment)
         };
-    let dst_file = dst_ds_root.to_absolute(&dst_rel_path);
+    let dst_file = dst.ds_root.to_absolute(&dst_rel_path);
     fs::create_dir_all(dst_file.parent().unwrap()).context("Can't create dataset root path")?;
 
     if dst_file.exists() {
         // Assume hash collisions don't exist
-        ensure!(subpath.use_hashing || files_are_equal(src_file, &dst_file)?.is_eq(),
+        let named_by_hash = subpath.use_hashing || dst.layout == MediaLayout::ContentAddressed;
+        ensure!(named_by_hash || files_are_equal(src_file, &dst_file)?.is_eq(),
                 "File already exists: {}, and it doesn't match source {}",
                 dst_file.display(), src_absolute_path)
     } else {
         fs::copy(src_file, dst_file)?;
+        dst.copied_files.borrow_mut().push(src_file.to_path_buf());
+        dst.written_files.borrow_mut().push(dst_rel_path.clone());
     }
 
     Ok(Some(dst_rel_path))
@@ -1330,17 +1469,49 @@ fn copy_chat_file(src_rel_path: &str,
                   subpath: &Subpath,
                   chat_id: i64,
                   src_ds_root: &DatasetRoot,
-                  dst_ds_root: &DatasetRoot) -> Result<Option<String>> {
+                  dst: &MediaTarget) -> Result<Option<String>> {
     copy_file(&src_ds_root.to_absolute(src_rel_path), src_mime, thumbnail_dst_main_path,
-              &chat_root_rel_path(chat_id), subpath, dst_ds_root)
+              &chat_root_rel_path(chat_id), subpath, dst)
 }
 
 fn copy_user_profile_pic(src_file: &Path,
                          src_mime: Option<&str>,
                          user_id: UserId,
-                         dst_ds_root: &DatasetRoot) -> Result<Option<String>> {
+                         dst: &MediaTarget) -> Result<Option<String>> {
     copy_file(src_file, src_mime, None,
-              &user_root_rel_path(user_id), &subpaths::PROFILE_PICTURES, dst_ds_root)
+              &user_root_rel_path(user_id), &subpaths::PROFILE_PICTURES, dst)
+}
+
+/// Builds FTS5 query matching all the given terms, escaping them.
+fn fts_match_expression(terms: &[String]) -> String {
+    terms.iter().map(|t| format!("\"{}\"", t.replace('"', "\"\""))).join(" ")
+}
+
+/// Adds messages with internal IDs in the given range (inclusive) to the full-text search index.
+fn fts_index(conn: &mut SqliteConnection, first_internal_id: i64, last_internal_id: i64) -> EmptyRes {
+    sql_query(r"
+        INSERT INTO message_fts(rowid, searchable_string)
+        SELECT internal_id, searchable_string FROM message
+        WHERE internal_id BETWEEN ? AND ?
+    ")
+        .bind::<sql_types::BigInt, _>(first_internal_id)
+        .bind::<sql_types::BigInt, _>(last_internal_id)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Removes messages with internal IDs in the given range (inclusive) from the full-text search index.
+/// Must be called before messages are deleted or their searchable strings are changed.
+fn fts_unindex(conn: &mut SqliteConnection, first_internal_id: i64, last_internal_id: i64) -> EmptyRes {
+    sql_query(r"
+        INSERT INTO message_fts(message_fts, rowid, searchable_string)
+        SELECT 'delete', internal_id, searchable_string FROM message
+        WHERE internal_id BETWEEN ? AND ?
+    ")
+        .bind::<sql_types::BigInt, _>(first_internal_id)
+        .bind::<sql_types::BigInt, _>(last_internal_id)
+        .execute(conn)?;
+    Ok(())
 }
 
 fn defer_fk(conn: &mut SqliteConnection) -> EmptyRes {
@@ -1349,6 +1520,6 @@ fn defer_fk(conn: &mut SqliteConnection) -> EmptyRes {
 }
 
 fn vacuum(conn: &mut SqliteConnection) -> EmptyRes {
-    sql_query("PRAGMA defer_foreign_keys = true").execute(conn)?;
+    sql_query("VACUUM").execute(conn)?;
     ok(())
 }
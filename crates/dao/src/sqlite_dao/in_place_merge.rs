@@ -0,0 +1,277 @@
+use super::*;
+
+/// Writes an in-place merge into a dataset using a single connection, on which the transaction is open.
+///
+/// Everything that would otherwise be read from cache is captured beforehand, since reading through other
+/// pooled connections while the transaction holds a write lock might fail.
+struct SqliteInPlaceMergeTarget<'a> {
+    dao: &'a SqliteDao,
+    conn: &'a mut SqliteConnection,
+    raw_uuid: Vec<u8>,
+    dst: &'a MediaTarget,
+    myself_id: UserId,
+    /// User names as of before the merge, to update plaintext mentions of renamed users
+    old_user_names: HashMap<UserId, Option<String>>,
+}
+
+impl SqliteDao {
+    pub(super) fn in_place_merge_inner(&self,
+                                       ds_uuid: &PbUuid,
+                                       action: &mut dyn FnMut(&mut dyn InPlaceMergeTarget) -> EmptyRes) -> EmptyRes {
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+        let dst = self.media_target(ds_uuid)?;
+        let (users, myself_id) = self.users_inner(ds_uuid)?;
+        let old_user_names = users.iter().map(|u| (u.id(), u.pretty_name_option())).collect();
+
+        let mut conn = self.get_conn()?;
+        let res = conn.transaction(|conn| {
+            let mut target = SqliteInPlaceMergeTarget {
+                dao: self,
+                conn,
+                raw_uuid: Vec::from(uuid.as_bytes().as_slice()),
+                dst: &dst,
+                myself_id,
+                old_user_names,
+            };
+            action(&mut target)
+        });
+        if res.is_err() {
+            // Transaction is rolled back, but files copied in the meantime would be left orphaned
+            let written_files = dst.written_files.take();
+            for file in written_files.iter() {
+                if let Err(e) = fs::remove_file(dst.ds_root.to_absolute(file)) {
+                    log::warn!("Failed to remove file {file} copied during failed merge: {e}");
+                }
+            }
+            if let Err(e) = remove_empty_parent_dirs(&dst.ds_root, &written_files) {
+                log::warn!("Failed to remove empty directories left after failed merge: {e:?}");
+            }
+        }
+        res
+    }
+}
+
+impl SqliteInPlaceMergeTarget<'_> {
+    /// Copied files are accumulated in media target, this takes them out relative to the source dataset root.
+    fn take_copied_files(&self, src_ds_root: &DatasetRoot) -> Result<Vec<String>> {
+        self.dst.copied_files.take().iter()
+            .map(|f| path_to_str(f.strip_prefix(&src_ds_root.0).unwrap_or(f)).map(|s| s.to_owned()))
+            .try_collect()
+    }
+
+    fn execute_by_chat_and_range(&mut self, sql: &str, chat: &Chat,
+                                 first_id: MessageInternalId, last_id: MessageInternalId) -> QueryResult<usize> {
+        sql_query(sql)
+            .bind::<sql_types::Binary, _>(self.raw_uuid.as_slice())
+            .bind::<sql_types::BigInt, _>(chat.id)
+            .bind::<sql_types::BigInt, _>(*first_id)
+            .bind::<sql_types::BigInt, _>(*last_id)
+            .execute(self.conn)
+    }
+
+    /// Removes messages of the chat in the given range from FTS index, as well as all their child rows.
+    /// Since internal IDs are shared by all chats, range alone might include messages of other chats.
+    fn unindex_and_delete_children(&mut self, chat: &Chat,
+                                   first_id: MessageInternalId, last_id: MessageInternalId) -> EmptyRes {
+        self.execute_by_chat_and_range(r"
+            INSERT INTO message_fts(message_fts, rowid, searchable_string)
+            SELECT 'delete', internal_id, searchable_string FROM message
+            WHERE ds_uuid = ? AND chat_id = ? AND internal_id BETWEEN ? AND ?
+        ", chat, first_id, last_id)?;
+        for table in ["message_content", "message_text_element", "message_reaction"] {
+            self.execute_by_chat_and_range(&format!(r"
+                DELETE FROM {table}
+                WHERE message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ? AND chat_id = ? AND internal_id BETWEEN ? AND ?
+                )
+            "), chat, first_id, last_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl InPlaceMergeTarget for SqliteInPlaceMergeTarget<'_> {
+    fn insert_user(&mut self, mut user: User, is_myself: bool) -> Result<User> {
+        user.profile_pictures = vec![];
+        let raw_user = utils::user::serialize(&user, is_myself, &self.raw_uuid);
+        insert_into(schema::user::dsl::user)
+            .values(raw_user)
+            .execute(self.conn)?;
+        Ok(user)
+    }
+
+    fn update_user(&mut self, user: User) -> EmptyRes {
+        let old_name = self.old_user_names.get(&user.id()).cloned().flatten();
+        let is_myself = user.id() == self.myself_id;
+        update_user_inner(self.conn, user.id(), &user, is_myself, &self.dst.ds_root, old_name)
+    }
+
+    fn replace_user_profile_pics(&mut self, user_id: UserId, profile_pics: Vec<AbsoluteProfilePicture>) -> EmptyRes {
+        let raw_pics: Vec<_> = profile_pics
+            .into_iter()
+            .filter(|pic| pic.absolute_path.exists())
+            .enumerate()
+            .map(|(idx, pic)| {
+                utils::user::profile_picture::serialize_and_copy(
+                    user_id, &self.raw_uuid, &pic.absolute_path, pic.frame_option.as_ref(), idx, &self.dst)
+            })
+            .try_collect()?;
+        // Profile pictures aren't reported
+        self.dst.copied_files.borrow_mut().clear();
+
+        // Old files are left in place, they might be among the new ones
+        use schema::*;
+        delete(profile_picture::dsl::profile_picture)
+            .filter(profile_picture::columns::ds_uuid.eq(self.raw_uuid.as_slice()))
+            .filter(profile_picture::columns::user_id.eq(*user_id))
+            .execute(self.conn)?;
+        insert_into(profile_picture::dsl::profile_picture)
+            .values(raw_pics)
+            .execute(self.conn)?;
+        Ok(())
+    }
+
+    fn insert_chat(&mut self, mut chat: Chat, src_ds_root: &DatasetRoot) -> Result<Chat> {
+        ensure!(chat.member_ids.first() == Some(&*self.myself_id),
+                "First member of chat {} was not myself!", chat.qualified_name());
+        if let Some(ref img) = chat.img_path_option {
+            chat.img_path_option = copy_chat_file(img, None, None, &subpaths::ROOT,
+                                                  chat.id, src_ds_root, &self.dst)?;
+            self.dst.copied_files.borrow_mut().clear();
+        }
+
+        let raw_chat = utils::chat::serialize(&chat, &self.raw_uuid)?;
+        let chat_members = chat.member_ids.iter().enumerate().map(|(order, &user_id)| RawChatMember {
+            ds_uuid: self.raw_uuid.clone(),
+            chat_id: chat.id,
+            user_id,
+            order: order as i32,
+        }).collect_vec();
+
+        use schema::*;
+        insert_into(chat::dsl::chat)
+            .values(raw_chat)
+            .execute(self.conn)?;
+        insert_into(chat_member::dsl::chat_member)
+            .values(chat_members)
+            .execute(self.conn)?;
+        Ok(chat)
+    }
+
+    fn update_chat(&mut self, chat: Chat) -> EmptyRes {
+        let raw_chat = utils::chat::serialize(&chat, &self.raw_uuid)?;
+        let chat_members = chat.member_ids.iter().enumerate().map(|(order, &user_id)| RawChatMember {
+            ds_uuid: self.raw_uuid.clone(),
+            chat_id: chat.id,
+            user_id,
+            order: order as i32,
+        }).collect_vec();
+
+        use schema::*;
+        let updated_rows = update(chat::dsl::chat)
+            .filter(chat::columns::ds_uuid.eq(self.raw_uuid.as_slice()))
+            .filter(chat::columns::id.eq(chat.id))
+            .set(&raw_chat)
+            .execute(self.conn)?;
+        ensure!(updated_rows == 1, "{updated_rows} rows changed when updaing chat {}", chat.qualified_name());
+
+        delete(chat_member::dsl::chat_member)
+            .filter(chat_member::columns::ds_uuid.eq(self.raw_uuid.as_slice()))
+            .filter(chat_member::columns::chat_id.eq(chat.id))
+            .execute(self.conn)?;
+        insert_into(chat_member::dsl::chat_member)
+            .values(chat_members)
+            .execute(self.conn)?;
+        Ok(())
+    }
+
+    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>> {
+        self.dao.copy_messages(self.conn, &msgs, chat.id, &self.raw_uuid, src_ds_root, &self.dst)?;
+        self.take_copied_files(src_ds_root)
+    }
+
+    fn update_message(&mut self, msg: Message, chat: &Chat, src_ds_root: &DatasetRoot) -> Result<Vec<String>> {
+        let internal_id = msg.internal_id();
+        let mut full_raw =
+            utils::message::serialize_and_copy_files(&msg, chat.id, &self.raw_uuid, src_ds_root, &self.dst)?;
+        full_raw.m.internal_id = Some(*internal_id);
+        full_raw.mc.iter_mut().for_each(|mc| mc.message_internal_id = Some(*internal_id));
+        full_raw.rtes.iter_mut().for_each(|rte| rte.message_internal_id = Some(*internal_id));
+        full_raw.reactions.iter_mut().for_each(|r| r.message_internal_id = Some(*internal_id));
+
+        self.unindex_and_delete_children(chat, internal_id, internal_id)?;
+
+        // Row is re-inserted under the same internal ID, which keeps its place in chat
+        use schema::*;
+        let deleted_rows = delete(message::dsl::message)
+            .filter(message::columns::ds_uuid.eq(self.raw_uuid.as_slice()))
+            .filter(message::columns::chat_id.eq(chat.id))
+            .filter(message::columns::internal_id.eq(*internal_id))
+            .execute(self.conn)?;
+        ensure!(deleted_rows == 1, "{deleted_rows} rows changed when updaing message {} of chat {}",
+                *internal_id, chat.qualified_name());
+
+        insert_into(message::table).values(&full_raw.m).execute(self.conn)?;
+        insert_into(message_content::table).values(full_raw.mc).execute(self.conn)?;
+        insert_into(message_text_element::table).values(full_raw.rtes).execute(self.conn)?;
+        insert_into(message_reaction::table).values(full_raw.reactions).execute(self.conn)?;
+        fts_index(self.conn, *internal_id, *internal_id)?;
+
+        self.take_copied_files(src_ds_root)
+    }
+
+    fn delete_messages(&mut self, chat: &Chat, first_id: MessageInternalId, last_id: MessageInternalId) -> EmptyRes {
+        self.unindex_and_delete_children(chat, first_id, last_id)?;
+
+        use schema::*;
+        delete(message::dsl::message)
+            .filter(message::columns::ds_uuid.eq(self.raw_uuid.as_slice()))
+            .filter(message::columns::chat_id.eq(chat.id))
+            .filter(message::columns::internal_id.between(*first_id, *last_id))
+            .execute(self.conn)?;
+        Ok(())
+    }
+
+    fn move_messages_to_end(&mut self, chat: &Chat, first_id: MessageInternalId, last_id: MessageInternalId) -> EmptyRes {
+        use schema::*;
+        let max_id: Option<i64> = message::table
+            .select(diesel::dsl::max(message::columns::internal_id))
+            .first(self.conn)?;
+        let Some(max_id) = max_id else { return Ok(()) };
+        // New IDs are above any existing ones, so moved messages can't collide with anything
+        let offset = max_id + 1 - *first_id;
+
+        self.execute_by_chat_and_range(r"
+            INSERT INTO message_fts(message_fts, rowid, searchable_string)
+            SELECT 'delete', internal_id, searchable_string FROM message
+            WHERE ds_uuid = ? AND chat_id = ? AND internal_id BETWEEN ? AND ?
+        ", chat, first_id, last_id)?;
+
+        defer_fk(self.conn)?;
+        for table in ["message_content", "message_text_element", "message_reaction"] {
+            sql_query(format!(r"
+                UPDATE {table}
+                SET message_internal_id = message_internal_id + ?
+                WHERE message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ? AND chat_id = ? AND internal_id BETWEEN ? AND ?
+                )
+            "))
+                .bind::<sql_types::BigInt, _>(offset)
+                .bind::<sql_types::Binary, _>(self.raw_uuid.as_slice())
+                .bind::<sql_types::BigInt, _>(chat.id)
+                .bind::<sql_types::BigInt, _>(*first_id)
+                .bind::<sql_types::BigInt, _>(*last_id)
+                .execute(self.conn)?;
+        }
+        update(message::dsl::message)
+            .filter(message::columns::ds_uuid.eq(self.raw_uuid.as_slice()))
+            .filter(message::columns::chat_id.eq(chat.id))
+            .filter(message::columns::internal_id.between(*first_id, *last_id))
+            .set(message::columns::internal_id.eq(message::columns::internal_id + offset))
+            .execute(self.conn)?;
+
+        fts_index(self.conn, *first_id + offset, *last_id + offset)
+    }
+}
@@ -0,0 +1,239 @@
+use super::*;
+
+use std::collections::BTreeSet;
+
+impl VerifiableChatHistoryDao for SqliteDao {
+    fn verify(&self, ds_uuid: &PbUuid) -> Result<Vec<IntegrityIssue>> {
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        let raw_uuid = uuid.as_bytes().as_slice();
+        let ds_root = self.dataset_root(ds_uuid)?;
+        let mut conn = self.get_conn()?;
+        let mut issues = vec![];
+
+        // Files
+        let referenced_paths = referenced_paths(&mut conn, raw_uuid)?;
+        issues.extend(referenced_paths.iter()
+            .filter(|path| !ds_root.to_absolute(path).exists())
+            .map(|path| IntegrityIssue::MissingFile { path: path.clone() }));
+        if ds_root.0.exists() {
+            // Comparing paths rather than strings, as stored ones might contain redundant separators
+            let referenced_files: HashSet<PathBuf> =
+                referenced_paths.iter().map(|path| ds_root.to_absolute(path)).collect();
+            let mut orphan_paths = vec![];
+            for file in list_all_files(&ds_root.0, true)? {
+                if !referenced_files.contains(&file) {
+                    orphan_paths.push(path_to_str(file.strip_prefix(&ds_root.0)?)?.to_owned());
+                }
+            }
+            orphan_paths.sort();
+            issues.extend(orphan_paths.into_iter().map(|path| IntegrityIssue::OrphanFile { path }));
+        }
+
+        // Messages
+        issues.extend(sql_query(r"
+            SELECT m.chat_id, m.from_id AS user_id, COUNT(*) AS msg_count FROM message m
+            WHERE m.ds_uuid = ? AND NOT EXISTS (
+                SELECT 1 FROM chat_member cm
+                WHERE cm.ds_uuid = m.ds_uuid AND cm.chat_id = m.chat_id AND cm.user_id = m.from_id
+            )
+            GROUP BY m.chat_id, m.from_id
+            ORDER BY m.chat_id, m.from_id
+        ")
+            .bind::<sql_types::Binary, _>(raw_uuid)
+            .load::<NonMemberAuthorWrapper>(&mut *conn)?
+            .into_iter()
+            .map(|w| IntegrityIssue::NonMemberAuthor {
+                chat_id: ChatId(w.chat_id),
+                user_id: UserId(w.user_id),
+                msg_count: w.msg_count as usize,
+            }));
+        issues.extend(sql_query(r"
+            SELECT m.chat_id, m.internal_id, m.reply_to_message_id AS ref_source_id FROM message m
+            WHERE m.ds_uuid = ? AND m.reply_to_message_id IS NOT NULL AND NOT EXISTS (
+                SELECT 1 FROM message r
+                WHERE r.ds_uuid = m.ds_uuid AND r.chat_id = m.chat_id AND r.source_id = m.reply_to_message_id
+            )
+            ORDER BY m.internal_id
+        ")
+            .bind::<sql_types::Binary, _>(raw_uuid)
+            .load::<MessageRefWrapper>(&mut *conn)?
+            .into_iter()
+            .map(|w| IntegrityIssue::DanglingReply {
+                chat_id: ChatId(w.chat_id),
+                internal_id: MessageInternalId(w.internal_id),
+                reply_to_id: MessageSourceId(w.ref_source_id),
+            }));
+        issues.extend(sql_query(r"
+            SELECT m.chat_id, m.internal_id, mc.pinned_message_id AS ref_source_id FROM message m
+            INNER JOIN message_content mc ON mc.message_internal_id = m.internal_id
+            WHERE m.ds_uuid = ? AND mc.pinned_message_id IS NOT NULL AND NOT EXISTS (
+                SELECT 1 FROM message p
+                WHERE p.ds_uuid = m.ds_uuid AND p.chat_id = m.chat_id AND p.source_id = mc.pinned_message_id
+            )
+            ORDER BY m.internal_id
+        ")
+            .bind::<sql_types::Binary, _>(raw_uuid)
+            .load::<MessageRefWrapper>(&mut *conn)?
+            .into_iter()
+            .map(|w| IntegrityIssue::DanglingPin {
+                chat_id: ChatId(w.chat_id),
+                internal_id: MessageInternalId(w.internal_id),
+                pinned_id: MessageSourceId(w.ref_source_id),
+            }));
+        issues.extend(sql_query(r"
+            SELECT * FROM (
+                SELECT c.id AS chat_id, c.msg_count AS stored, (
+                    SELECT COUNT(*) FROM message m
+                    WHERE m.ds_uuid = c.ds_uuid AND m.chat_id = c.id
+                ) AS actual FROM chat c
+                WHERE c.ds_uuid = ?
+            )
+            WHERE stored != actual
+            ORDER BY chat_id
+        ")
+            .bind::<sql_types::Binary, _>(raw_uuid)
+            .load::<MessageCountWrapper>(&mut *conn)?
+            .into_iter()
+            .map(|w| IntegrityIssue::WrongMessageCount {
+                chat_id: ChatId(w.chat_id),
+                stored: w.stored,
+                actual: w.actual as i32,
+            }));
+
+        // Searchable string might be legitimately empty, e.g. for a photo without caption
+        use schema::*;
+        let chat_id_by_internal_id: HashMap<i64, i64> = message::table
+            .filter(message::columns::ds_uuid.eq(raw_uuid))
+            .filter(message::columns::searchable_string.eq(""))
+            .select((message::columns::internal_id, message::columns::chat_id))
+            .load::<(i64, i64)>(&mut *conn)?
+            .into_iter()
+            .collect();
+        let internal_ids = chat_id_by_internal_id.keys().copied().sorted().collect_vec();
+        for chunk in internal_ids.chunks(BATCH_SIZE) {
+            let msgs = fetch_by_internal_ids(&mut conn, chunk)?;
+            issues.extend(msgs.iter()
+                .filter(|m| !make_searchable_string(&m.text, m.typed()).is_empty())
+                .map(|m| IntegrityIssue::EmptySearchableString {
+                    chat_id: ChatId(chat_id_by_internal_id[&m.internal_id]),
+                    internal_id: m.internal_id(),
+                }));
+        }
+
+        Ok(issues)
+    }
+
+    fn fix_integrity_issues(&mut self, ds_uuid: &PbUuid, issues: &[IntegrityIssue]) -> Result<usize> {
+        self.invalidate_cache()?;
+
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        let raw_uuid = uuid.as_bytes().as_slice();
+        let ds_root = self.dataset_root(ds_uuid)?;
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+
+        let mut orphan_paths = vec![];
+        let fixed_count = conn.transaction(|conn| {
+            let mut fixed_count = 0;
+            for issue in issues.iter().filter(|issue| issue.is_fixable()) {
+                match issue {
+                    IntegrityIssue::MissingFile { .. } | IntegrityIssue::DanglingReply { .. } |
+                    IntegrityIssue::DanglingPin { .. } =>
+                        unreachable!(),
+                    IntegrityIssue::OrphanFile { path } => {
+                        // Files are moved after the transaction is committed
+                        orphan_paths.push(path.clone());
+                    }
+                    IntegrityIssue::NonMemberAuthor { chat_id, user_id, .. } => {
+                        sql_query(r#"
+                            INSERT INTO chat_member (ds_uuid, chat_id, user_id, "order")
+                            SELECT ?, ?, ?, COALESCE(MAX(cm."order"), -1) + 1 FROM chat_member cm
+                            WHERE cm.ds_uuid = ? AND cm.chat_id = ?
+                        "#)
+                            .bind::<sql_types::Binary, _>(raw_uuid)
+                            .bind::<sql_types::BigInt, _>(chat_id.0)
+                            .bind::<sql_types::BigInt, _>(user_id.0)
+                            .bind::<sql_types::Binary, _>(raw_uuid)
+                            .bind::<sql_types::BigInt, _>(chat_id.0)
+                            .execute(conn)?;
+                    }
+                    IntegrityIssue::WrongMessageCount { chat_id, .. } => {
+                        sql_query(r"
+                            UPDATE chat SET msg_count = (
+                                SELECT COUNT(*) FROM message m
+                                WHERE m.ds_uuid = chat.ds_uuid AND m.chat_id = chat.id
+                            )
+                            WHERE ds_uuid = ? AND id = ?
+                        ")
+                            .bind::<sql_types::Binary, _>(raw_uuid)
+                            .bind::<sql_types::BigInt, _>(chat_id.0)
+                            .execute(conn)?;
+                    }
+                    IntegrityIssue::EmptySearchableString { internal_id, .. } => {
+                        let msg = fetch_by_internal_ids(conn, &[internal_id.0])?.pop()
+                            .with_context(|| format!("Message {} not found!", internal_id.0))?;
+                        let searchable_string = make_searchable_string(&msg.text, msg.typed());
+                        fts_unindex(conn, internal_id.0, internal_id.0)?;
+                        update(message::table)
+                            .filter(message::columns::internal_id.eq(internal_id.0))
+                            .set(message::columns::searchable_string.eq(searchable_string))
+                            .execute(conn)?;
+                        fts_index(conn, internal_id.0, internal_id.0)?;
+                    }
+                }
+                fixed_count += 1;
+            }
+            ok(fixed_count)
+        })?;
+
+        if !orphan_paths.is_empty() {
+            let backup_ds_root = self.choose_final_backup_path("")?.join(path_file_name(&ds_root.0)?);
+            for relative in orphan_paths.iter() {
+                let src = ds_root.to_absolute(relative);
+                if src.exists() {
+                    let dst = backup_ds_root.join(relative);
+                    fs::create_dir_all(dst.parent().unwrap())?;
+                    fs::rename(src, dst)?;
+                }
+            }
+            remove_empty_parent_dirs(&ds_root, &orphan_paths)?;
+        }
+
+        Ok(fixed_count)
+    }
+}
+
+/// All files referenced by messages, chats and profile pictures of a dataset.
+fn referenced_paths(conn: &mut SqliteConnection, raw_uuid: &[u8]) -> Result<BTreeSet<String>> {
+    use schema::*;
+    let mut paths = BTreeSet::new();
+    let content_paths: Vec<(Option<String>, Option<String>)> = message_content::table
+        .inner_join(message::table)
+        .filter(message::columns::ds_uuid.eq(raw_uuid))
+        .select((message_content::columns::path, message_content::columns::thumbnail_path))
+        .load(conn)?;
+    paths.extend(content_paths.into_iter().flat_map(|(path, thumbnail_path)| [path, thumbnail_path]).flatten());
+    paths.extend(chat::table
+        .filter(chat::columns::ds_uuid.eq(raw_uuid))
+        .select(chat::columns::img_path)
+        .load::<Option<String>>(conn)?
+        .into_iter()
+        .flatten());
+    paths.extend(profile_picture::table
+        .filter(profile_picture::columns::ds_uuid.eq(raw_uuid))
+        .select(profile_picture::columns::path)
+        .load::<String>(conn)?);
+    Ok(paths)
+}
+
+fn fetch_by_internal_ids(conn: &mut SqliteConnection, internal_ids: &[i64]) -> Result<Vec<Message>> {
+    utils::message::fetch(conn, |conn| {
+        use schema::*;
+        Ok(message::table
+            .filter(message::columns::internal_id.eq_any(internal_ids))
+            .order_by(message::columns::internal_id)
+            .select(RawMessage::as_select())
+            .load(conn)?)
+    })
+}
@@ -73,6 +73,8 @@ pub mod schema {
             forward_from_name -> Nullable<Text>,
             reply_to_message_id -> Nullable<BigInt>,
             searchable_string -> Text,
+            views -> Nullable<Integer>,
+            author_signature -> Nullable<Text>,
         }
     }
 
@@ -118,6 +120,24 @@ pub mod schema {
         }
     }
 
+    diesel::table! {
+        message_reaction (id) {
+            id -> BigInt,
+            message_internal_id -> Nullable<BigInt>,
+            emoji -> Nullable<Text>,
+            custom_emoji_id -> Nullable<Text>,
+            from_ids -> Nullable<Text>,
+            count -> Integer,
+        }
+    }
+
+    diesel::table! {
+        setting (key) {
+            key -> Text,
+            value -> Text,
+        }
+    }
+
     diesel::table! {
         refinery_schema_history (version) {
             version -> Nullable<Integer>,
@@ -131,6 +151,7 @@ pub mod schema {
     diesel::joinable!(message -> dataset (ds_uuid));
     diesel::joinable!(message_content -> message (message_internal_id));
     diesel::joinable!(message_text_element -> message (message_internal_id));
+    diesel::joinable!(message_reaction -> message (message_internal_id));
     diesel::joinable!(user -> dataset (ds_uuid));
 
     diesel::allow_tables_to_appear_in_same_query!(
@@ -140,7 +161,9 @@ pub mod schema {
         message,
         message_content,
         message_text_element,
+        message_reaction,
         refinery_schema_history,
+        setting,
         user,
         profile_picture,
     );
@@ -158,6 +181,14 @@ pub struct RawDataset {
     pub alias: String,
 }
 
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = schema::setting)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawSetting {
+    pub key: String,
+    pub value: String,
+}
+
 #[derive(Debug, PartialEq, Selectable, Queryable, Insertable, AsChangeset)]
 #[diesel(table_name = schema::user)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -253,6 +284,8 @@ pub struct RawMessage {
     pub forward_from_name: Option<String>,
     pub reply_to_message_id: Option<i64>,
     pub searchable_string: String,
+    pub views: Option<i32>,
+    pub author_signature: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Default, Identifiable, Selectable, Queryable, Insertable, Associations)]
@@ -310,6 +343,85 @@ pub struct UserIdWrapper {
     pub id: i64,
 }
 
+/// Needed specifically for full-text search through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SearchHitWrapper {
+    #[diesel(sql_type = BigInt)]
+    pub internal_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub chat_id: i64,
+    #[diesel(sql_type = Text)]
+    pub fragment: String,
+}
+
+/// Needed specifically for counting rows through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CountWrapper {
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+/// Needed specifically for integrity checks through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NonMemberAuthorWrapper {
+    #[diesel(sql_type = BigInt)]
+    pub chat_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub user_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub msg_count: i64,
+}
+
+/// Needed specifically for integrity checks through sql_query.
+/// Reference is a source ID of another message in the same chat.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct MessageRefWrapper {
+    #[diesel(sql_type = BigInt)]
+    pub chat_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub internal_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub ref_source_id: i64,
+}
+
+/// Needed specifically for integrity checks through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct MessageCountWrapper {
+    #[diesel(sql_type = BigInt)]
+    pub chat_id: i64,
+    #[diesel(sql_type = Integer)]
+    pub stored: i32,
+    #[diesel(sql_type = BigInt)]
+    pub actual: i64,
+}
+
+/// Needed specifically for relinking missing files through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct FileContentWrapper {
+    #[diesel(sql_type = BigInt)]
+    pub content_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub internal_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub chat_id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub time_sent: i64,
+    #[diesel(sql_type = Text)]
+    pub element_type: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub path: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub file_name: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub mime_type: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Identifiable, Selectable, Queryable, Insertable, Associations)]
 #[diesel(belongs_to(RawMessage, foreign_key = message_internal_id))]
 #[diesel(table_name = schema::message_text_element)]
@@ -328,8 +440,26 @@ pub struct RawRichTextElement {
     pub language: Option<String>,
 }
 
+#[derive(Debug, PartialEq, Identifiable, Selectable, Queryable, Insertable, Associations)]
+#[diesel(belongs_to(RawMessage, foreign_key = message_internal_id))]
+#[diesel(table_name = schema::message_reaction)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawMessageReaction {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    // This is not supposed to be Option, but Self::belonging_to(&raw_messages) doesn't typecheck otherwise
+    pub message_internal_id: Option<i64>,
+    pub emoji: Option<String>,
+    pub custom_emoji_id: Option<String>,
+    /// Comma-separated user IDs
+    pub from_ids: Option<String>,
+    pub count: i32,
+}
+
 pub struct FullRawMessage {
     pub m: RawMessage,
     pub mc: Vec<RawMessageContent>,
     pub rtes: Vec<RawRichTextElement>,
+    pub reactions: Vec<RawMessageReaction>,
 }
@@ -0,0 +1,270 @@
+use super::*;
+
+/// Directory inside a dataset root holding content-addressed files.
+/// Deduplication is scoped to a dataset, as every dataset directory is self-contained - it's moved away as a whole
+/// on deletion, and copied as a whole into another database.
+const MEDIA_DIR_NAME: &str = "_media";
+
+const MEDIA_LAYOUT_SETTING_KEY: &str = "media_layout";
+
+/// How files are laid out inside a dataset root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaLayout {
+    /// Files are stored in per-chat (and per-user) directories,
+    /// so a file referenced from several chats is stored several times.
+    #[default]
+    PerChat,
+    /// Every distinct file is stored once per dataset under `_media`, named by its content hash.
+    /// Files are not shared between datasets, so the same file in two datasets is stored twice.
+    /// Since files are shared by chats, they're only removed once nothing in the dataset references them anymore.
+    ContentAddressed,
+}
+
+impl MediaLayout {
+    fn serialize(&self) -> &'static str {
+        match self {
+            MediaLayout::PerChat => "per_chat",
+            MediaLayout::ContentAddressed => "content_addressed",
+        }
+    }
+
+    fn deserialize(s: &str) -> Result<Self> {
+        match s {
+            "per_chat" => Ok(MediaLayout::PerChat),
+            "content_addressed" => Ok(MediaLayout::ContentAddressed),
+            _ => bail!("Unknown media layout {s}"),
+        }
+    }
+}
+
+/// Where and how files copied into a dataset should be stored.
+pub(super) struct MediaTarget {
+    pub ds_root: DatasetRoot,
+    pub layout: MediaLayout,
+    /// Source files actually copied so far, i.e. not counting missing ones and ones already present in the dataset.
+    pub copied_files: RefCell<Vec<PathBuf>>,
+    /// Destination files written so far (relative to the dataset root), to be removed should the changes be rolled back.
+    pub written_files: RefCell<Vec<String>>,
+}
+
+/// Outcome of [`SqliteDao::dedupe_media`], counting distinct referenced files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MediaDedupeReport {
+    pub files_before: usize,
+    pub bytes_before: u64,
+    pub files_after: usize,
+    pub bytes_after: u64,
+    /// Referenced files that don't exist (or are empty), their references are left as-is.
+    pub missing_files: usize,
+}
+
+impl MediaDedupeReport {
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_before - self.bytes_after
+    }
+}
+
+impl SqliteDao {
+    pub fn media_layout(&self) -> MediaLayout {
+        self.media_layout
+    }
+
+    /// Changes layout for files copied from now on, files that are already stored are not moved.
+    /// Use [`Self::dedupe_media`] to move them.
+    pub fn set_media_layout(&mut self, layout: MediaLayout) -> EmptyRes {
+        let raw_setting = RawSetting {
+            key: MEDIA_LAYOUT_SETTING_KEY.to_owned(),
+            value: layout.serialize().to_owned(),
+        };
+        diesel::replace_into(schema::setting::table).values(&raw_setting).execute(&mut *self.get_conn()?)?;
+        self.media_layout = layout;
+        Ok(())
+    }
+
+    pub(super) fn media_target(&self, ds_uuid: &PbUuid) -> Result<MediaTarget> {
+        Ok(MediaTarget {
+            ds_root: self.dataset_root(ds_uuid)?,
+            layout: self.media_layout,
+            copied_files: RefCell::new(vec![]),
+            written_files: RefCell::new(vec![]),
+        })
+    }
+
+    /// One-time migration of all datasets to content-addressed layout, keeping just one copy of every distinct file
+    /// per dataset (files duplicated across datasets stay duplicated). Database is updated in a transaction per dataset, old files are removed after it's committed.
+    pub fn dedupe_media(&mut self) -> Result<MediaDedupeReport> {
+        let mut report = MediaDedupeReport::default();
+        for ds in self.datasets()? {
+            measure(|| {
+                self.dedupe_dataset_media(&ds.uuid, &mut report)
+            }, |_, t| log::info!("Dataset '{}' media deduplicated in {t} ms", ds.uuid.value))?;
+        }
+        self.invalidate_cache()?;
+        self.set_media_layout(MediaLayout::ContentAddressed)?;
+        Ok(report)
+    }
+
+    fn dedupe_dataset_media(&self, ds_uuid: &PbUuid, report: &mut MediaDedupeReport) -> EmptyRes {
+        use schema::*;
+
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        let raw_uuid = uuid.as_bytes().as_slice();
+        let ds_root = self.dataset_root(ds_uuid)?;
+        let mut conn = self.get_conn()?;
+
+        let contents: Vec<(i64, Option<String>, Option<String>)> = message_content::table
+            .inner_join(message::table)
+            .filter(message::columns::ds_uuid.eq(raw_uuid))
+            .filter(message_content::columns::path.is_not_null()
+                .or(message_content::columns::thumbnail_path.is_not_null()))
+            .select((message_content::columns::id,
+                     message_content::columns::path,
+                     message_content::columns::thumbnail_path))
+            .load(&mut *conn)?;
+        let chat_imgs: Vec<(i64, String)> = chat::table
+            .filter(chat::columns::ds_uuid.eq(raw_uuid))
+            .filter(chat::columns::img_path.is_not_null())
+            .select((chat::columns::id, chat::columns::img_path.assume_not_null()))
+            .load(&mut *conn)?;
+        let mut pictures: Vec<RawProfilePicture> = profile_picture::table
+            .filter(profile_picture::columns::ds_uuid.eq(raw_uuid))
+            .select(RawProfilePicture::as_select())
+            .load(&mut *conn)?;
+
+        let referenced_paths: HashSet<&str> = contents.iter()
+            .flat_map(|(_, path, thumbnail_path)| [path.as_deref(), thumbnail_path.as_deref()])
+            .flatten()
+            .chain(chat_imgs.iter().map(|(_, path)| path.as_str()))
+            .chain(pictures.iter().map(|p| p.path.as_str()))
+            .collect();
+
+        // Old relative path -> content-addressed one
+        let mut new_paths: HashMap<String, String> = HashMap::new();
+        // Content-addressed relative path -> file size
+        let mut stored_files: HashMap<String, u64> = HashMap::new();
+        for &old_path in referenced_paths.iter() {
+            let old_file = ds_root.to_absolute(old_path);
+            let FileHash::Valid { hash, len } = file_hash(&old_file)? else {
+                log::info!("Referenced file does not exist: {}", old_file.display());
+                report.missing_files += 1;
+                continue;
+            };
+            report.files_before += 1;
+            report.bytes_before += len;
+
+            if is_content_addressed(old_path) {
+                stored_files.insert(old_path.to_owned(), len);
+                continue;
+            }
+            let ext_suffix = old_file.extension().and_then(|ext| ext.to_str())
+                .map(|ext| format!(".{ext}")).unwrap_or_default();
+            let new_path = content_addressed_rel_path(hash, &ext_suffix);
+            let new_file = ds_root.to_absolute(&new_path);
+            if !new_file.exists() {
+                fs::create_dir_all(new_file.parent().unwrap())?;
+                fs::copy(&old_file, &new_file)?;
+            }
+            stored_files.insert(new_path.clone(), len);
+            new_paths.insert(old_path.to_owned(), new_path);
+        }
+        report.files_after += stored_files.len();
+        report.bytes_after += stored_files.values().sum::<u64>();
+
+        let remap = |path: &Option<String>| path.as_ref().map(|p| new_paths.get(p).unwrap_or(p).clone());
+
+        conn.transaction(|conn| {
+            for (id, path, thumbnail_path) in contents.iter() {
+                let (new_path, new_thumbnail_path) = (remap(path), remap(thumbnail_path));
+                if new_path != *path || new_thumbnail_path != *thumbnail_path {
+                    update(message_content::table.filter(message_content::columns::id.eq(id)))
+                        .set((message_content::columns::path.eq(new_path),
+                              message_content::columns::thumbnail_path.eq(new_thumbnail_path)))
+                        .execute(conn)?;
+                }
+            }
+
+            for (id, img_path) in chat_imgs.iter() {
+                if let Some(new_path) = new_paths.get(img_path) {
+                    update(chat::table)
+                        .filter(chat::columns::ds_uuid.eq(raw_uuid))
+                        .filter(chat::columns::id.eq(id))
+                        .set(chat::columns::img_path.eq(new_path))
+                        .execute(conn)?;
+                }
+            }
+
+            // Path is a part of a primary key, so user can't have the same picture twice.
+            // Pictures that are already content-addressed go first to take precedence.
+            pictures.sort_by_key(|p| new_paths.contains_key(&p.path));
+            let mut seen_pictures = HashSet::new();
+            for pic in pictures.iter() {
+                let new_path = new_paths.get(&pic.path).unwrap_or(&pic.path);
+                let pic_filter = profile_picture::table
+                    .filter(profile_picture::columns::ds_uuid.eq(raw_uuid))
+                    .filter(profile_picture::columns::user_id.eq(pic.user_id))
+                    .filter(profile_picture::columns::path.eq(&pic.path));
+                if !seen_pictures.insert((pic.user_id, new_path)) {
+                    delete(pic_filter).execute(conn)?;
+                } else if *new_path != pic.path {
+                    update(pic_filter).set(profile_picture::columns::path.eq(new_path)).execute(conn)?;
+                }
+            }
+
+            ok(())
+        })?;
+
+        // Nothing references old files anymore
+        let old_paths = new_paths.into_keys().collect_vec();
+        for old_path in old_paths.iter() {
+            fs::remove_file(ds_root.to_absolute(old_path))?;
+        }
+        remove_empty_parent_dirs(&ds_root, &old_paths)?;
+
+        Ok(())
+    }
+}
+
+pub(super) fn load_media_layout(conn: &mut SqliteConnection) -> Result<MediaLayout> {
+    use schema::*;
+    let value: Option<String> = setting::table
+        .filter(setting::columns::key.eq(MEDIA_LAYOUT_SETTING_KEY))
+        .select(setting::columns::value)
+        .first(conn)
+        .optional()?;
+    value.map_or(Ok(MediaLayout::default()), |v| MediaLayout::deserialize(&v))
+}
+
+pub(super) fn content_addressed_rel_path(hash: u128, ext_suffix: &str) -> String {
+    let hash = hash_string(hash);
+    // Using first two characters of hash as a prefix for better file distribution, same what git does
+    let (prefix, name) = hash.split_at(2);
+    format!("{MEDIA_DIR_NAME}/{prefix}/{name}{ext_suffix}")
+}
+
+pub(super) fn is_content_addressed(rel_path: &str) -> bool {
+    rel_path.strip_prefix(MEDIA_DIR_NAME).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Whether any message, chat or profile picture of a dataset still references the given file.
+/// Message and profile picture lookups are backed by path indexes.
+pub(super) fn is_referenced(conn: &mut SqliteConnection, raw_uuid: &[u8], rel_path: &str) -> Result<bool> {
+    use schema::*;
+    let message_refs: i64 = message_content::table
+        .inner_join(message::table)
+        .filter(message::columns::ds_uuid.eq(raw_uuid))
+        .filter(message_content::columns::path.eq(rel_path)
+            .or(message_content::columns::thumbnail_path.eq(rel_path)))
+        .count()
+        .get_result(conn)?;
+    let chat_refs: i64 = chat::table
+        .filter(chat::columns::ds_uuid.eq(raw_uuid))
+        .filter(chat::columns::img_path.eq(rel_path))
+        .count()
+        .get_result(conn)?;
+    let picture_refs: i64 = profile_picture::table
+        .filter(profile_picture::columns::ds_uuid.eq(raw_uuid))
+        .filter(profile_picture::columns::path.eq(rel_path))
+        .count()
+        .get_result(conn)?;
+    Ok(message_refs + chat_refs + picture_refs > 0)
+}
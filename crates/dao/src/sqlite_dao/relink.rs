@@ -0,0 +1,295 @@
+use super::*;
+
+use std::time::UNIX_EPOCH;
+
+/// A file found only by its type is matched to a message if its modification time is this close to the message time.
+const TIME_PROXIMITY_TOLERANCE_SEC: i64 = 5 * 60;
+
+/// Content element types that can reference a file, paired with a subpath these files are stored under.
+static FILE_ELEMENT_TYPES: [(&str, &Subpath); 7] = [
+    ("sticker", &subpaths::STICKERS),
+    ("photo", &subpaths::PHOTOS),
+    ("voice_message", &subpaths::VOICE_MESSAGES),
+    ("audio", &subpaths::AUDIOS),
+    ("video_message", &subpaths::VIDEO_MESSAGES),
+    ("video", &subpaths::VIDEOS),
+    ("file", &subpaths::FILES),
+];
+
+/// How a file was matched to an attachment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelinkMatchKind {
+    /// Content hash is known from a path the file was stored under.
+    Hash,
+    /// File has the same name and compatible type. If several different files have that name,
+    /// the one closest to the message time is chosen.
+    FileName,
+    /// Attachment name is unknown, and this is the only file of a compatible type
+    /// modified around the time message was sent.
+    TimeProximity,
+}
+
+/// File found for an attachment that has no file or whose file is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRelink {
+    pub chat_id: ChatId,
+    pub internal_id: MessageInternalId,
+    pub(super) content_id: i64,
+    pub element_type: String,
+    pub mime_type: Option<String>,
+    /// Path to a missing file, if any.
+    pub old_path: Option<String>,
+    pub found_file: PathBuf,
+    pub match_kind: RelinkMatchKind,
+}
+
+/// Outcome of [`SqliteDao::plan_media_relink`], nothing is changed until it's passed to [`SqliteDao::relink_media`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaRelinkPlan {
+    pub relinks: Vec<MediaRelink>,
+    /// Attachments with several equally good candidates, these are left as-is.
+    pub ambiguous: usize,
+    /// Attachments for which nothing was found.
+    pub unmatched: usize,
+}
+
+impl SqliteDao {
+    /// Looks for files in a search directory (recursively) matching attachments that either have no file
+    /// or reference a missing one.
+    pub fn plan_media_relink(&self, ds_uuid: &PbUuid, search_dir: &Path) -> Result<MediaRelinkPlan> {
+        ensure!(search_dir.is_dir(), "{} is not a directory", search_dir.display());
+
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        let ds_root = self.dataset_root(ds_uuid)?;
+        let contents = sql_query(format!(r"
+            SELECT mc.id AS content_id, m.internal_id, m.chat_id, m.time_sent,
+                   mc.element_type, mc.path, mc.file_name, mc.mime_type
+            FROM message_content mc
+            INNER JOIN message m ON m.internal_id = mc.message_internal_id
+            WHERE m.ds_uuid = ? AND mc.element_type IN ({})
+            ORDER BY m.internal_id, mc.id
+        ", FILE_ELEMENT_TYPES.iter().map(|(tpe, _)| format!("'{tpe}'")).join(", ")))
+            .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+            .load::<FileContentWrapper>(&mut *self.get_conn()?)?;
+
+        let mut index = SearchIndex::new(search_dir)?;
+        let mut plan = MediaRelinkPlan::default();
+        for mc in contents {
+            if mc.path.as_ref().is_some_and(|path| ds_root.to_absolute(path).exists()) {
+                continue;
+            }
+            match index.find(&mc)? {
+                MatchOutcome::Found(idx, match_kind) => plan.relinks.push(MediaRelink {
+                    chat_id: ChatId(mc.chat_id),
+                    internal_id: MessageInternalId(mc.internal_id),
+                    content_id: mc.content_id,
+                    element_type: mc.element_type,
+                    mime_type: mc.mime_type,
+                    old_path: mc.path,
+                    found_file: index.files[idx].path.clone(),
+                    match_kind,
+                }),
+                MatchOutcome::Ambiguous => plan.ambiguous += 1,
+                MatchOutcome::NotFound => plan.unmatched += 1,
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Copies found files into the dataset root (according to the media layout) and updates attachment paths
+    /// in a single transaction. Returns a number of relinked attachments.
+    pub fn relink_media(&mut self, ds_uuid: &PbUuid, plan: &MediaRelinkPlan) -> Result<usize> {
+        let dst = self.media_target(ds_uuid)?;
+
+        let mut new_paths = vec![];
+        for relink in plan.relinks.iter() {
+            let (_, subpath) = FILE_ELEMENT_TYPES.iter()
+                .find(|(tpe, _)| *tpe == relink.element_type)
+                .with_context(|| format!("Unexpected content type {}", relink.element_type))?;
+            let new_path = copy_file(&relink.found_file, relink.mime_type.as_deref(), None,
+                                     &chat_root_rel_path(relink.chat_id.0), subpath, &dst)?;
+            match new_path {
+                Some(new_path) => new_paths.push((relink.content_id, new_path)),
+                None => log::warn!("File is gone since relink was planned: {}", relink.found_file.display()),
+            }
+        }
+
+        use schema::*;
+        self.get_conn()?.transaction(|conn| {
+            for (content_id, new_path) in new_paths.iter() {
+                update(message_content::table.filter(message_content::columns::id.eq(content_id)))
+                    .set(message_content::columns::path.eq(new_path))
+                    .execute(conn)?;
+            }
+            ok(())
+        })?;
+
+        Ok(new_paths.len())
+    }
+}
+
+enum MatchOutcome {
+    /// Index of a found file
+    Found(usize, RelinkMatchKind),
+    Ambiguous,
+    NotFound,
+}
+
+struct SearchFile {
+    path: PathBuf,
+    /// Lowercase
+    ext: Option<String>,
+    len: u64,
+    /// Unix timestamp in seconds
+    modified: Option<i64>,
+    /// None if not yet calculated, Some(None) if file is empty
+    hash: Option<Option<u128>>,
+}
+
+/// Files of a search directory, their hashes are only calculated when needed.
+struct SearchIndex {
+    files: Vec<SearchFile>,
+    /// Lowercase file name -> indices
+    by_name: HashMap<String, Vec<usize>>,
+    by_hash: Option<HashMap<u128, usize>>,
+}
+
+impl SearchIndex {
+    fn new(dir: &Path) -> Result<Self> {
+        let mut files = vec![];
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for path in list_all_files(dir, true)? {
+            let metadata = path.metadata()?;
+            let name = path_file_name(&path)?.to_lowercase();
+            let ext = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+            let modified = metadata.modified().ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64);
+            by_name.entry(name).or_default().push(files.len());
+            files.push(SearchFile { path, ext, len: metadata.len(), modified, hash: None });
+        }
+        Ok(SearchIndex { files, by_name, by_hash: None })
+    }
+
+    fn find(&mut self, mc: &FileContentWrapper) -> Result<MatchOutcome> {
+        let stored_hash = mc.path.as_deref().and_then(hash_from_stored_path);
+        if let Some(idx) = stored_hash.map(|hash| self.find_by_hash(hash)).transpose()?.flatten() {
+            return Ok(MatchOutcome::Found(idx, RelinkMatchKind::Hash));
+        }
+
+        // Hashed file name is not the original one
+        let name = mc.file_name.clone().or_else(||
+            mc.path.as_deref().filter(|_| stored_hash.is_none()).and_then(|p| p.rsplit('/').next()).map(|n| n.to_owned())
+        );
+        let mime_type = mc.mime_type.as_deref().or((mc.element_type == "photo").then_some("image/jpeg"));
+
+        if let Some(name) = name {
+            let candidates = self.by_name.get(&name.to_lowercase()).cloned().unwrap_or_default().into_iter()
+                .filter(|&idx| is_compatible_type(&self.files[idx], mime_type))
+                .collect_vec();
+            let candidates = self.distinct_by_time_proximity(candidates, mc.time_sent)?;
+            return Ok(match candidates.as_slice() {
+                [] => MatchOutcome::NotFound,
+                [idx] => MatchOutcome::Found(*idx, RelinkMatchKind::FileName),
+                [idx1, idx2, ..] => {
+                    if self.time_distance(*idx1, mc.time_sent) < self.time_distance(*idx2, mc.time_sent) {
+                        MatchOutcome::Found(*idx1, RelinkMatchKind::FileName)
+                    } else {
+                        MatchOutcome::Ambiguous
+                    }
+                }
+            });
+        }
+
+        // With no name to go by, we need at least a type to narrow down candidates
+        let Some(mime_type) = mime_type else {
+            return Ok(MatchOutcome::NotFound);
+        };
+        let candidates = (0..self.files.len())
+            .filter(|&idx| self.files[idx].ext.as_deref().is_some_and(|ext| mime_guess::from_ext(ext).first_raw().is_some()))
+            .filter(|&idx| is_compatible_type(&self.files[idx], Some(mime_type)))
+            .filter(|&idx| self.time_distance(idx, mc.time_sent) <= TIME_PROXIMITY_TOLERANCE_SEC)
+            .collect_vec();
+        let candidates = self.distinct_by_time_proximity(candidates, mc.time_sent)?;
+        Ok(match candidates.as_slice() {
+            [] => MatchOutcome::NotFound,
+            [idx] => MatchOutcome::Found(*idx, RelinkMatchKind::TimeProximity),
+            _ => MatchOutcome::Ambiguous,
+        })
+    }
+
+    fn hash(&mut self, idx: usize) -> Result<Option<u128>> {
+        let file = &mut self.files[idx];
+        if file.hash.is_none() {
+            file.hash = Some(match file_hash(&file.path)? {
+                FileHash::Valid { hash, .. } => Some(hash),
+                FileHash::NotFoundOrEmpty => None,
+            });
+        }
+        Ok(file.hash.unwrap())
+    }
+
+    fn find_by_hash(&mut self, hash: u128) -> Result<Option<usize>> {
+        if self.by_hash.is_none() {
+            let mut by_hash = HashMap::new();
+            for idx in 0..self.files.len() {
+                if let Some(hash) = self.hash(idx)? {
+                    by_hash.entry(hash).or_insert(idx);
+                }
+            }
+            self.by_hash = Some(by_hash);
+        }
+        Ok(self.by_hash.as_ref().unwrap().get(&hash).copied())
+    }
+
+    fn time_distance(&self, idx: usize, time_sent: i64) -> i64 {
+        self.files[idx].modified.map_or(i64::MAX, |modified| (modified - time_sent).abs())
+    }
+
+    /// Sorts candidates by time proximity, leaving only the closest of the files with the same content.
+    /// Empty files are dropped.
+    fn distinct_by_time_proximity(&mut self, mut candidates: Vec<usize>, time_sent: i64) -> Result<Vec<usize>> {
+        candidates.sort_by_key(|&idx| self.time_distance(idx, time_sent));
+        let len_counts = candidates.iter().counts_by(|&idx| self.files[idx].len);
+        let mut seen = HashSet::new();
+        let mut result = vec![];
+        for idx in candidates {
+            let len = self.files[idx].len;
+            if len == 0 { continue; }
+            // Files of a unique size can't have the same content, no need to hash them
+            let hash = if len_counts[&len] > 1 {
+                let Some(hash) = self.hash(idx)? else { continue };
+                Some(hash)
+            } else {
+                None
+            };
+            if seen.insert((len, hash)) {
+                result.push(idx);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Whether file extension doesn't contradict the expected MIME type, only comparing top-level types
+/// (e.g. `audio/mpeg` vs `audio/ogg`) since exporters aren't precise about subtypes.
+/// Files without a known extension are always compatible.
+fn is_compatible_type(file: &SearchFile, mime_type: Option<&str>) -> bool {
+    let top_level = |mime: &str| mime.split('/').next().unwrap_or_default().to_lowercase();
+    match (file.ext.as_deref().and_then(|ext| mime_guess::from_ext(ext).first_raw()), mime_type) {
+        (Some(file_mime), Some(mime_type)) => top_level(file_mime) == top_level(mime_type),
+        _ => true,
+    }
+}
+
+/// Files stored under hashed names (per-chat or content-addressed) have their content hash encoded in a path
+/// as `XX/YYYY...YYY.ext`.
+fn hash_from_stored_path(rel_path: &str) -> Option<u128> {
+    let mut segments = rel_path.rsplit('/');
+    let name = segments.next()?;
+    let prefix = segments.next()?;
+    let hash_str = format!("{prefix}{}", name.split('.').next()?);
+    let hash = u128::from_str_radix(&hash_str, 16).ok()?;
+    // Length check rules out short names that happen to look like hex numbers
+    (hash_str.len() > 16 && hash_string(hash) == hash_str).then_some(hash)
+}
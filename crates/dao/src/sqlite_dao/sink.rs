@@ -0,0 +1,245 @@
+use super::*;
+
+/// Writes a dataset pushed into it straight into the database, in a separate transaction per pushed piece.
+///
+/// Since loaders might only know the final set of users after all the chats have been pushed,
+/// foreign keys are not enforced until the dataset is finished, at which point references to users are checked.
+/// If sink is dropped before the dataset is finished, whatever was written so far remains in the database.
+pub struct SqliteSink<'a> {
+    dao: &'a SqliteDao,
+    conn: PooledConnection<ConnectionManager<SqliteConnection>>,
+    state_option: Option<SinkState>,
+    finished: bool,
+}
+
+struct SinkState {
+    ds_uuid: PbUuid,
+    raw_uuid: Vec<u8>,
+    src_ds_root: DatasetRoot,
+    dst: MediaTarget,
+    myself_id_option: Option<UserId>,
+    chat_ids: HashSet<i64>,
+    /// Names and first members of chats added before myself is known, by chat ID
+    unchecked_chats: HashMap<i64, (String, Option<i64>)>,
+}
+
+impl SqliteDao {
+    /// Creates a sink writing a single new dataset into this database.
+    pub fn sink(&self) -> Result<SqliteSink<'_>> {
+        let mut conn = self.get_conn()?;
+        sql_query("PRAGMA foreign_keys = OFF").execute(&mut conn)?;
+        Ok(SqliteSink { dao: self, conn, state_option: None, finished: false })
+    }
+}
+
+impl SqliteSink<'_> {
+    fn state(&mut self) -> Result<&mut SinkState> {
+        ensure!(!self.finished, "Dataset is already finished!");
+        self.state_option.as_mut().context("Dataset was not started!")
+    }
+}
+
+impl SinkState {
+    /// Checks right away if myself is known, otherwise once users are added.
+    fn check_first_member(&mut self, chat: &Chat) -> EmptyRes {
+        let first_member_id = chat.member_ids.first().copied();
+        match self.myself_id_option {
+            Some(myself_id) =>
+                ensure_first_member_is_myself(&chat.qualified_name(), first_member_id, myself_id),
+            None => {
+                self.unchecked_chats.insert(chat.id, (chat.qualified_name(), first_member_id));
+                Ok(())
+            }
+        }
+    }
+
+    fn raw_members(&self, chat: &Chat) -> Vec<RawChatMember> {
+        chat.member_ids.iter()
+            .enumerate()
+            .map(|(order, &user_id)|
+                RawChatMember {
+                    ds_uuid: self.raw_uuid.clone(),
+                    chat_id: chat.id,
+                    user_id,
+                    order: order as i32,
+                })
+            .collect_vec()
+    }
+}
+
+fn ensure_first_member_is_myself(chat_name: &str, first_member_id: Option<i64>, myself_id: UserId) -> EmptyRes {
+    ensure!(first_member_id == Some(*myself_id), "First member of chat {chat_name} was not myself!");
+    Ok(())
+}
+
+impl ChatHistorySink for SqliteSink<'_> {
+    fn begin_dataset(&mut self, _name: String, ds: Dataset, ds_root: &Path) -> EmptyRes {
+        ensure!(self.state_option.is_none(), "Dataset is already started!");
+        ensure!(!self.dao.datasets()?.iter().any(|existing| existing.uuid == ds.uuid),
+                "Dataset UUID {} is already in use!", ds.uuid.value);
+
+        let raw_ds = utils::dataset::serialize(&ds);
+        let src_ds_root = DatasetRoot(ds_root.to_path_buf());
+        let dst = self.dao.media_target(&ds.uuid)?;
+
+        insert_into(schema::dataset::table).values(&raw_ds).execute(&mut self.conn)?;
+        self.dao.invalidate_cache()?;
+
+        self.state_option = Some(SinkState {
+            ds_uuid: ds.uuid,
+            raw_uuid: raw_ds.uuid,
+            src_ds_root,
+            dst,
+            myself_id_option: None,
+            chat_ids: HashSet::new(),
+            unchecked_chats: HashMap::new(),
+        });
+        Ok(())
+    }
+
+    fn add_users(&mut self, users: Vec<User>, myself_id: UserId) -> EmptyRes {
+        let state = self.state_option.as_mut().filter(|_| !self.finished).context("Dataset is not in progress!")?;
+        ensure!(state.myself_id_option.is_none(), "Users were already added!");
+        ensure!(users.iter().any(|u| u.id() == myself_id), "Myself is not among users!");
+
+        let raw_users_with_pictures: Vec<(RawUser, Vec<RawProfilePicture>)> =
+            users.iter().map(|u| {
+                ensure!(u.id > 0, "IDs should be positive!");
+                let raw_user = utils::user::serialize(u, u.id() == myself_id, &state.raw_uuid);
+                let raw_pictures: Vec<RawProfilePicture> =
+                    u.profile_pictures.iter()
+                        .map(|pp| (pp, state.src_ds_root.to_absolute(&pp.path)))
+                        .filter(|(_, path)| path.exists())
+                        .enumerate()
+                        .map(|(idx, (pp, path))| {
+                            utils::user::profile_picture::serialize_and_copy(
+                                u.id(), &state.raw_uuid, &path,
+                                pp.frame_option.as_ref(), idx, &state.dst,
+                            )
+                        })
+                        .try_collect()?;
+                Ok((raw_user, raw_pictures))
+            }).try_collect()?;
+        let (raw_users, raw_pictures): (Vec<RawUser>, Vec<Vec<RawProfilePicture>>) =
+            raw_users_with_pictures.into_iter().unzip();
+        let raw_pictures = raw_pictures.into_iter().flatten().collect_vec();
+
+        self.conn.transaction(|txn| {
+            use schema::*;
+            insert_into(user::table).values(&raw_users).execute(txn)?;
+            insert_into(profile_picture::table).values(&raw_pictures).execute(txn)?;
+            ok(())
+        })?;
+
+        for (_, (chat_name, first_member_id)) in state.unchecked_chats.drain() {
+            ensure_first_member_is_myself(&chat_name, first_member_id, myself_id)?;
+        }
+        state.myself_id_option = Some(myself_id);
+        Ok(())
+    }
+
+    fn add_chat(&mut self, chat: Chat) -> EmptyRes {
+        let state = self.state()?;
+        ensure!(chat.id > 0, "IDs should be positive!");
+        ensure!(state.chat_ids.insert(chat.id), "Chat {} was already added!", chat.qualified_name());
+        state.check_first_member(&chat)?;
+
+        let mut raw_chat = utils::chat::serialize(&chat, &state.raw_uuid)?;
+        if let Some(ref img) = chat.img_path_option {
+            raw_chat.img_path =
+                copy_chat_file(img, None, None, &subpaths::ROOT,
+                               chat.id, &state.src_ds_root, &state.dst)?;
+        }
+        let raw_members = state.raw_members(&chat);
+
+        self.conn.transaction(|txn| {
+            use schema::*;
+            insert_into(chat::table).values(raw_chat).execute(txn)?;
+            insert_into(chat_member::table).values(raw_members).execute(txn)?;
+            ok(())
+        })
+    }
+
+    fn add_messages(&mut self, chat: &Chat, msgs: Vec<Message>) -> EmptyRes {
+        ensure!(self.state()?.chat_ids.contains(&chat.id), "Chat {} was not added!", chat.qualified_name());
+        let Some((first, last)) = msgs.first().zip(msgs.last()) else { return Ok(()) };
+        let context = format!("Failed to copy messages #{} through #{} of chat {}",
+                              first.internal_id, last.internal_id, chat.qualified_name());
+
+        let state = self.state_option.as_ref().unwrap();
+        let dao = self.dao;
+        let res = self.conn.transaction(|txn| {
+            dao.copy_messages(txn, &msgs, chat.id, &state.raw_uuid, &state.src_ds_root, &state.dst)
+        }).context(context);
+        // Sink doesn't report copied files, no point in accumulating them
+        state.dst.copied_files.borrow_mut().clear();
+        res
+    }
+
+    fn finish_chat(&mut self, chat: &Chat) -> EmptyRes {
+        let state = self.state()?;
+        ensure!(state.chat_ids.contains(&chat.id), "Chat {} was not added!", chat.qualified_name());
+        state.check_first_member(chat)?;
+        let raw_members = state.raw_members(chat);
+        let raw_uuid = state.raw_uuid.clone();
+
+        self.conn.transaction(|txn| {
+            use schema::*;
+            update(chat::dsl::chat)
+                .filter(chat::columns::ds_uuid.eq(raw_uuid.as_slice()))
+                .filter(chat::columns::id.eq(chat.id))
+                .set(chat::columns::msg_count.eq(chat.msg_count))
+                .execute(txn)?;
+            delete(chat_member::dsl::chat_member)
+                .filter(chat_member::columns::ds_uuid.eq(raw_uuid.as_slice()))
+                .filter(chat_member::columns::chat_id.eq(chat.id))
+                .execute(txn)?;
+            insert_into(chat_member::table).values(raw_members).execute(txn)?;
+            ok(())
+        })
+    }
+
+    fn finish_dataset(&mut self) -> EmptyRes {
+        let state = self.state()?;
+        ensure!(state.myself_id_option.is_some(), "Users were not added!");
+        let uuid = Uuid::parse_str(&state.ds_uuid.value)?;
+
+        // Since foreign keys were not enforced, we need to make sure all users are present.
+        let count_by_ds_uuid = |conn: &mut SqliteConnection, sql: &str| -> Result<i64> {
+            Ok(sql_query(sql)
+                .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                .get_result::<CountWrapper>(conn)?
+                .count)
+        };
+        let missing_members = count_by_ds_uuid(&mut self.conn, r"
+            SELECT COUNT(*) AS count FROM chat_member cm
+            WHERE cm.ds_uuid = ? AND NOT EXISTS (
+                SELECT 1 FROM user u WHERE u.ds_uuid = cm.ds_uuid AND u.id = cm.user_id
+            )
+        ")?;
+        ensure!(missing_members == 0, "{missing_members} chat members are not among users!");
+        let missing_senders = count_by_ds_uuid(&mut self.conn, r"
+            SELECT COUNT(*) AS count FROM message m
+            WHERE m.ds_uuid = ? AND NOT EXISTS (
+                SELECT 1 FROM user u WHERE u.ds_uuid = m.ds_uuid AND u.id = m.from_id
+            )
+        ")?;
+        ensure!(missing_senders == 0, "{missing_senders} messages have senders that are not among users!");
+
+        sql_query("PRAGMA foreign_keys = ON").execute(&mut self.conn)?;
+        self.finished = true;
+        self.dao.invalidate_cache()
+    }
+}
+
+impl Drop for SqliteSink<'_> {
+    fn drop(&mut self) {
+        // Connection is returned to the pool, so foreign keys should be enforced again
+        if let Err(e) = sql_query("PRAGMA foreign_keys = ON").execute(&mut self.conn) {
+            log::warn!("Failed to re-enable foreign keys: {e}");
+        }
+        if !self.finished && let Some(ref state) = self.state_option {
+            log::warn!("Dataset '{}' was not finished, it's left incomplete!", state.ds_uuid.value);
+        }
+    }
+}
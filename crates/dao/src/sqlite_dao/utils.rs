@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use crate::sqlite_dao::{self, subpaths};
+use crate::sqlite_dao::media::MediaTarget;
 use crate::{DaoCacheInner, UserCacheForDataset};
 use chat_history_manager_core::{message_regular, message_service, message_service_pat, message_service_pat_unreachable};
 use chat_history_manager_core::protobuf::history::*;
@@ -72,12 +73,14 @@ impl_enum_serialization!(SourceType, {
     Signal      => "signal",
     TinderDb    => "tinder",
     BadooDb     => "badoo",
-    Mra         => "mra"
+    Mra         => "mra",
+    Discord     => "discord"
 });
 
 impl_enum_serialization!(ChatType, {
     Personal     => "personal",
-    PrivateGroup => "private_group"
+    PrivateGroup => "private_group",
+    Channel      => "channel"
 });
 
 //
@@ -149,8 +152,8 @@ pub mod user {
                                   path: &Path,
                                   frame: Option<&PictureFrame>,
                                   idx: usize,
-                                  dst_ds_root: &DatasetRoot) -> Result<RawProfilePicture> {
-            let new_path = sqlite_dao::copy_user_profile_pic(path, None, user_id, dst_ds_root)?
+                                  dst: &MediaTarget) -> Result<RawProfilePicture> {
+            let new_path = sqlite_dao::copy_user_profile_pic(path, None, user_id, dst)?
                 .expect("Filter out non-existent paths first!");
             Ok(RawProfilePicture {
                 ds_uuid: raw_ds_uuid.to_vec(),
@@ -304,10 +307,21 @@ pub mod message {
             group.sort_by_key(|rte| rte.id)
         }
 
+        let raw_message_reactions: Vec<RawMessageReaction> =
+            RawMessageReaction::belonging_to(&raw_messages)
+                .select(RawMessageReaction::as_select())
+                .load(conn)?;
+
+        let mut raw_message_reactions_grouped = raw_message_reactions.grouped_by(&raw_messages);
+        for group in raw_message_reactions_grouped.iter_mut() {
+            group.sort_by_key(|r| r.id)
+        }
+
         let messages: Vec<Message> = raw_messages.into_iter()
             .zip(raw_messages_content_grouped)
             .zip(raw_message_rtes_grouped)
-            .map(|((m, mc), rtes)| FullRawMessage { m, mc, rtes })
+            .zip(raw_message_reactions_grouped)
+            .map(|(((m, mc), rtes), reactions)| FullRawMessage { m, mc, rtes, reactions })
             .map(deserialize)
             .try_collect()?;
 
@@ -319,26 +333,29 @@ pub mod message {
                                     chat_id: i64,
                                     raw_uuid: &[u8],
                                     src_ds_root: &DatasetRoot,
-                                    dst_ds_root: &DatasetRoot) -> Result<FullRawMessage> {
-        let (tpe, subtype, mc, time_edited, is_deleted, forward_from_name, reply_to_message_id) =
+                                    dst: &MediaTarget) -> Result<FullRawMessage> {
+        let (tpe, subtype, mc, reactions, time_edited, is_deleted, forward_from_name, reply_to_message_id, views, author_signature) =
             match m.typed.as_ref().unwrap() {
                 crate::message::Typed::Regular(mr) => {
                     let content: Result<Vec<_>> = mr.contents.iter()
                         .map(|mc| serialize_content_and_copy_files(mc.sealed_value_optional.as_ref().unwrap(),
-                                                                   chat_id, src_ds_root, dst_ds_root))
+                                                                   chat_id, src_ds_root, dst))
                         .collect();
                     let content = content?;
                     ("regular",
                      None,
                      content,
+                     mr.reactions.iter().map(serialize_reaction).collect_vec(),
                      mr.edit_timestamp_option,
                      serialize_bool(mr.is_deleted),
                      mr.forward_from_name_option.clone(),
-                     mr.reply_to_message_id_option)
+                     mr.reply_to_message_id_option,
+                     mr.views_option,
+                     mr.author_signature_option.clone())
                 }
                 message_service_pat!(ms) => {
-                    let (subtype, mc) = serialize_service_and_copy_files(ms, chat_id, src_ds_root, dst_ds_root)?;
-                    ("service", Some(subtype), mc.into_iter().collect_vec(), None, serialize_bool(false), None, None)
+                    let (subtype, mc) = serialize_service_and_copy_files(ms, chat_id, src_ds_root, dst)?;
+                    ("service", Some(subtype), mc.into_iter().collect_vec(), vec![], None, serialize_bool(false), None, None, None, None)
                 }
                 message_service_pat_unreachable!() => { unreachable!() }
             };
@@ -357,21 +374,24 @@ pub mod message {
                 forward_from_name,
                 reply_to_message_id,
                 searchable_string: m.searchable_string.clone(),
+                views,
+                author_signature,
             },
             mc,
             rtes: m.text.iter().map(serialize_rte).try_collect()?,
+            reactions,
         })
     }
 
     fn serialize_content_and_copy_files(mc: &content::SealedValueOptional,
                                         chat_id: i64,
                                         src_ds_root: &DatasetRoot,
-                                        dst_ds_root: &DatasetRoot) -> Result<RawMessageContent> {
+                                        dst: &MediaTarget) -> Result<RawMessageContent> {
         use content::SealedValueOptional::*;
         macro_rules! copy_path {
             ($obj:ident.$field:ident, $mime:expr, $thumb:expr, $subpath:expr) => {
                 $obj.$field.as_ref().map(|v|
-                    sqlite_dao::copy_chat_file(&v, $mime, $thumb, $subpath,chat_id, src_ds_root, dst_ds_root)
+                    sqlite_dao::copy_chat_file(&v, $mime, $thumb, $subpath,chat_id, src_ds_root, dst)
                 ).transpose()?.flatten()
             };
         }
@@ -391,7 +411,7 @@ pub mod message {
                     ..Default::default()
                 }
             }
-            Photo(v) => serialize_photo_and_copy_files(v, chat_id, src_ds_root, dst_ds_root)?,
+            Photo(v) => serialize_photo_and_copy_files(v, chat_id, src_ds_root, dst)?,
             VoiceMsg(v) => {
                 let path = copy_path!(v.path_option, Some(&v.mime_type), None, &subpaths::VOICE_MESSAGES);
                 RawMessageContent {
@@ -510,10 +530,10 @@ pub mod message {
     fn serialize_photo_and_copy_files(photo: &ContentPhoto,
                                       chat_id: i64,
                                       src_ds_root: &DatasetRoot,
-                                      dst_ds_root: &DatasetRoot) -> Result<RawMessageContent> {
+                                      dst: &MediaTarget) -> Result<RawMessageContent> {
         let path = photo.path_option.as_ref().map(|path|
             sqlite_dao::copy_chat_file(path, photo.mime_type_option.as_deref(), None, &subpaths::PHOTOS,
-                                       chat_id, src_ds_root, dst_ds_root)
+                                       chat_id, src_ds_root, dst)
         ).transpose()?.flatten();
         Ok(RawMessageContent {
             element_type: "photo".to_owned(),
@@ -538,7 +558,7 @@ pub mod message {
     fn serialize_service_and_copy_files(ms: &message_service::SealedValueOptional,
                                         chat_id: i64,
                                         src_ds_root: &DatasetRoot,
-                                        dst_ds_root: &DatasetRoot) -> Result<(&'static str, Option<RawMessageContent>)> {
+                                        dst: &MediaTarget) -> Result<(&'static str, Option<RawMessageContent>)> {
         use message_service::SealedValueOptional::*;
         let (subtype, mut mc) = match ms {
             PhoneCall(v) =>
@@ -550,7 +570,7 @@ pub mod message {
                 })),
             SuggestProfilePhoto(v) =>
                 ("suggest_profile_photo",
-                 Some(serialize_photo_and_copy_files(&v.photo, chat_id, src_ds_root, dst_ds_root)?)),
+                 Some(serialize_photo_and_copy_files(&v.photo, chat_id, src_ds_root, dst)?)),
             PinMessage(v) =>
                 ("pin_message", Some(RawMessageContent {
                     pinned_message_id: Some(v.message_source_id),
@@ -580,7 +600,7 @@ pub mod message {
                 })),
             GroupEditPhoto(v) =>
                 ("group_edit_photo",
-                 Some(serialize_photo_and_copy_files(&v.photo, chat_id, src_ds_root, dst_ds_root)?)),
+                 Some(serialize_photo_and_copy_files(&v.photo, chat_id, src_ds_root, dst)?)),
             GroupDeletePhoto(_) =>
                 ("group_delete_photo", None),
             GroupInviteMembers(v) =>
@@ -609,6 +629,18 @@ pub mod message {
         Ok((subtype, mc))
     }
 
+    /// Ignores message internal ID.
+    fn serialize_reaction(r: &Reaction) -> RawMessageReaction {
+        RawMessageReaction {
+            id: None,
+            message_internal_id: None, // Discarded
+            emoji: r.emoji_option.clone(),
+            custom_emoji_id: r.custom_emoji_id_option.clone(),
+            from_ids: if r.from_ids.is_empty() { None } else { Some(r.from_ids.iter().join(",")) },
+            count: r.count,
+        }
+    }
+
     /// Ignores message internal ID.
     fn serialize_rte(rte: &RichTextElement) -> Result<RawRichTextElement> {
         use rich_text_element::Val::*;
@@ -661,16 +693,21 @@ pub mod message {
                     }))
                     .collect();
                 let contents = contents?;
+                let reactions = raw.reactions.into_iter().map(deserialize_reaction).try_collect()?;
                 message_regular! {
                     edit_timestamp_option: raw.m.time_edited,
                     is_deleted: deserialize_bool(raw.m.is_deleted),
                     forward_from_name_option: raw.m.forward_from_name,
                     reply_to_message_id_option: raw.m.reply_to_message_id,
                     contents,
+                    reactions,
+                    views_option: raw.m.views,
+                    author_signature_option: raw.m.author_signature,
                 }
             },
             "service" => {
                 assert!(raw.mc.len() <= 1);
+                assert!(raw.reactions.is_empty());
                 message_service!(deserialize_service(
                     raw.m.subtype.as_deref().expect("Service message subtype is empty!"),
                     raw.mc.into_iter().next())?)
@@ -889,6 +926,20 @@ pub mod message {
         })
     }
 
+    fn deserialize_reaction(raw: RawMessageReaction) -> Result<Reaction> {
+        ensure!(raw.emoji.is_some() != raw.custom_emoji_id.is_some(),
+                "Reaction {} should have either emoji or custom emoji ID!",
+                raw.id.map(|id| format!("#{id}")).unwrap_or_else(|| "(no ID)".to_owned()));
+        Ok(Reaction {
+            emoji_option: raw.emoji,
+            custom_emoji_id_option: raw.custom_emoji_id,
+            from_ids: raw.from_ids
+                .map(|s| s.split(',').map(|s| s.parse::<i64>()).try_collect())
+                .unwrap_or(Ok(vec![]))?,
+            count: raw.count,
+        })
+    }
+
     fn deserialize_rte(raw: RawRichTextElement) -> Result<RichTextElement> {
         macro_rules! text_or_bail {
                 () => { raw.text.with_context(|| format!("Text not found for a rich text element #{} ({})!",
@@ -353,6 +353,136 @@ fn messages_around_date() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn search_messages() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, idx % 2 + 1)).collect_vec(),
+        2,
+        &|_, _, _| {},
+        rng().random(),
+    );
+    let daos = init_from(dao_holder.dao,
+                         dao_holder.tmp_dir.path.clone(),
+                         Some(dao_holder.tmp_dir));
+
+    let query = |text: &str| MessageSearchQuery {
+        ds_uuid: daos.ds_uuid.clone(),
+        text: text.to_owned(),
+        chat_id_option: None,
+        from_id_option: None,
+        timestamp_from_option: None,
+        timestamp_to_option: None,
+        offset: 0,
+        limit: usize::MAX >> 1,
+    };
+    let source_ids = |res: &MessageSearchResult|
+        res.hits.iter().map(|h| h.message.source_id_option.unwrap()).collect_vec();
+
+    let dao_vec: Vec<(&dyn ChatHistoryDao, &str)> = vec![
+        (daos.src_dao.as_ref(), "in-memory"),
+        (&daos.dst_dao, "sqlite"),
+    ];
+    for (dao, clue) in dao_vec {
+        let chat = dao.chats(&daos.ds_uuid)?.remove(0).chat;
+        let msgs = dao.first_messages(&chat, usize::MAX)?;
+
+        let res = dao.search_messages(&query("   "))?;
+        assert_eq!(res.total_count, 0, "{clue}");
+
+        let res = dao.search_messages(&query("HELLO 7"))?;
+        assert_eq!(res.total_count, 1, "{clue}");
+        assert_eq!(res.hits[0].chat_id, chat.id(), "{clue}");
+        assert_eq!(res.hits[0].message, msgs[6], "{clue}");
+        assert_eq!(res.hits[0].fragment, "<b>Hello</b> there, <b>7</b>! Hey, <b>7</b>!", "{clue}");
+
+        let res = dao.search_messages(&MessageSearchQuery { offset: 2, limit: 3, ..query("hey") })?;
+        assert_eq!(res.total_count, 10, "{clue}");
+        assert_eq!(source_ids(&res), vec![8, 7, 6], "{clue}");
+
+        let res = dao.search_messages(&MessageSearchQuery {
+            from_id_option: Some(UserId(1)),
+            timestamp_from_option: Some(Timestamp(msgs[2].timestamp)),
+            timestamp_to_option: Some(Timestamp(msgs[7].timestamp)),
+            ..query("hello")
+        })?;
+        assert_eq!(source_ids(&res), vec![8, 6, 4], "{clue}");
+
+        let res = dao.search_messages(&MessageSearchQuery { chat_id_option: Some(ChatId(chat.id + 1)), ..query("hello") })?;
+        assert_eq!(res.total_count, 0, "{clue}");
+
+        // Punctuation should not be treated as a query syntax
+        let res = dao.search_messages(&query("there,"))?;
+        assert_eq!(res.total_count, 10, "{clue}");
+
+        let res = dao.search_messages(&query("goodbye"))?;
+        assert_eq!(res.total_count, 0, "{clue}");
+
+        let res = dao.search_messages(&MessageSearchQuery { ds_uuid: PbUuid::random(), ..query("hello") })?;
+        assert_eq!(res.total_count, 0, "{clue}");
+    }
+
+    // Deleted messages should no longer be found
+    let mut dst_dao = daos.dst_dao;
+    let chat = dst_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    dst_dao.delete_chat(chat)?;
+    assert_eq!(dst_dao.search_messages(&query("hello"))?.total_count, 0);
+
+    Ok(())
+}
+
+#[test]
+fn search_messages_matches_whole_tokens_and_escapes_fragment() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, m| {
+            if m.source_id_option == Some(1) {
+                m.searchable_string = "Use <b>cat</b> to concatenate & a well-known thing".to_owned();
+            }
+        },
+        rng().random(),
+    );
+    let daos = init_from(dao_holder.dao,
+                         dao_holder.tmp_dir.path.clone(),
+                         Some(dao_holder.tmp_dir));
+
+    let query = |text: &str| MessageSearchQuery {
+        ds_uuid: daos.ds_uuid.clone(),
+        text: text.to_owned(),
+        chat_id_option: None,
+        from_id_option: None,
+        timestamp_from_option: None,
+        timestamp_to_option: None,
+        offset: 0,
+        limit: 10,
+    };
+
+    let dao_vec: Vec<(&dyn ChatHistoryDao, &str)> = vec![
+        (daos.src_dao.as_ref(), "in-memory"),
+        (&daos.dst_dao, "sqlite"),
+    ];
+    for (dao, clue) in dao_vec {
+        let res = dao.search_messages(&query("CAT"))?;
+        assert_eq!(res.total_count, 1, "{clue}");
+        assert_eq!(res.hits[0].fragment,
+                   "Use &lt;b&gt;<b>cat</b>&lt;/b&gt; to concatenate &amp; a well-known thing", "{clue}");
+
+        // Only whole tokens are matched
+        assert_eq!(dao.search_messages(&query("concat"))?.total_count, 0, "{clue}");
+        assert_eq!(dao.search_messages(&query("b"))?.total_count, 1, "{clue}");
+
+        // Multi-token term is matched as a phrase
+        assert_eq!(dao.search_messages(&query("well-known"))?.total_count, 1, "{clue}");
+        assert_eq!(dao.search_messages(&query("known-well"))?.total_count, 0, "{clue}");
+    }
+
+    Ok(())
+}
+
 #[test]
 fn inserts() -> EmptyRes {
     let dao_holder = create_simple_dao(
@@ -416,6 +546,217 @@ fn inserts() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn in_place_merge_delete_and_move_messages() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, _| {},
+        rng().random(),
+    );
+    let daos = init_from(dao_holder.dao,
+                         dao_holder.tmp_dir.path.clone(),
+                         Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+
+    let chat = dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let msgs = dao.first_messages(&chat, usize::MAX)?;
+    dao.in_place_merge(&daos.ds_uuid, &mut |target| {
+        target.delete_messages(&chat, msgs[3].internal_id(), msgs[6].internal_id())?;
+        target.move_messages_to_end(&chat, msgs[0].internal_id(), msgs[1].internal_id())
+    })?;
+
+    let remaining_msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(remaining_msgs.len(), 6);
+    // Messages that weren't moved keep their internal IDs
+    assert_eq!(&remaining_msgs[..4], [&msgs[2..3], &msgs[7..]].concat().as_slice());
+    for (moved, original) in remaining_msgs[4..].iter().zip(msgs[..2].iter()) {
+        assert!(moved.internal_id > msgs.last().unwrap().internal_id);
+        assert_eq!(moved, &Message { internal_id: moved.internal_id, ..original.clone() });
+    }
+
+    let res = dao.search_messages(&MessageSearchQuery {
+        ds_uuid: daos.ds_uuid.clone(),
+        text: "hello".to_owned(),
+        chat_id_option: None,
+        from_id_option: None,
+        timestamp_from_option: None,
+        timestamp_to_option: None,
+        offset: 0,
+        limit: usize::MAX >> 1,
+    })?;
+    assert_eq!(res.total_count, 6);
+    assert_eq!(res.hits.iter().map(|h| h.message.internal_id).sorted().collect_vec(),
+               remaining_msgs.iter().map(|m| m.internal_id).sorted().collect_vec());
+
+    Ok(())
+}
+
+#[test]
+fn in_place_merge_rollback() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, _| {},
+        rng().random()
+    );
+    let daos = init_from(dao_holder.dao,
+                         dao_holder.tmp_dir.path.clone(),
+                         Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = &daos.ds_uuid;
+    let src_ds_root = &daos.src_ds_root;
+
+    let cwd = dao.chats(ds_uuid)?.remove(0);
+    let chat = cwd.chat.clone();
+    let msgs = dao.first_messages(&chat, usize::MAX)?;
+    let users = dao.users(ds_uuid)?;
+
+    let merge_all = |target: &mut dyn InPlaceMergeTarget| -> EmptyRes {
+        let mut new_user = users[1].clone();
+        new_user.id = 100500;
+        target.insert_user(new_user, false)?;
+        let new_msgs = msgs[..2].iter().map(|m| Message { source_id_option: None, ..m.clone() }).collect_vec();
+        target.insert_messages(new_msgs, &chat, src_ds_root)?;
+        target.update_message(Message { timestamp: 12345, ..msgs[5].clone() }, &chat, src_ds_root)?;
+        let member_ids = chat.member_ids[..1].to_vec();
+        target.update_chat(Chat { msg_count: 12, member_ids, ..chat.clone() })
+    };
+
+    // Failed merge should leave no trace
+    let err = dao.in_place_merge(ds_uuid, &mut |target| {
+        merge_all(target)?;
+        bail!("Oops")
+    }).unwrap_err();
+    assert_eq!(err.to_string(), "Oops");
+    assert_eq!(dao.users(ds_uuid)?, users);
+    assert_eq!(dao.chats(ds_uuid)?, vec![cwd.clone()]);
+    assert_eq!(dao.first_messages(&chat, usize::MAX)?, msgs);
+
+    dao.in_place_merge(ds_uuid, &mut |target| merge_all(target))?;
+    assert_eq!(dao.users(ds_uuid)?.len(), users.len() + 1);
+    let new_cwd = dao.chats(ds_uuid)?.remove(0);
+    assert_eq!(new_cwd.chat.msg_count, 12);
+    assert_eq!(new_cwd.members, vec![dao.myself(ds_uuid)?]);
+    let new_msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(new_msgs.len(), 12);
+    // Updated message keeps its internal ID
+    assert_eq!(new_msgs[5], Message { timestamp: 12345, ..msgs[5].clone() });
+    assert_eq!(&new_msgs[..5], &msgs[..5]);
+    assert_eq!(&new_msgs[6..10], &msgs[6..]);
+
+    Ok(())
+}
+
+#[test]
+fn in_place_merge_rollback_removes_copied_files() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = &daos.ds_uuid;
+    let dst_ds_root = &daos.dst_ds_root;
+
+    // Per-chat layout stores the same files again for another chat
+    let (chat, other_chat) = chat_with_files_and_other_chat(&dao, ds_uuid)?;
+    let msgs = messages_to_copy(&dao, &chat)?;
+    let list_files = || ok(list_all_files(&dst_ds_root.0, true)?.into_iter().sorted().collect_vec());
+    let files_before = list_files()?;
+
+    let err = dao.in_place_merge(ds_uuid, &mut |target| {
+        let copied_files = target.insert_messages(msgs.clone(), &other_chat, dst_ds_root)?;
+        assert!(!copied_files.is_empty());
+        bail!("Oops")
+    }).unwrap_err();
+    assert_eq!(err.to_string(), "Oops");
+    assert_eq!(list_files()?, files_before);
+
+    Ok(())
+}
+
+#[test]
+fn sink_with_users_pushed_last() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, _| {},
+        rng().random()
+    );
+    let src_dao = dao_holder.dao.as_ref();
+    let ds_uuid = &src_dao.ds_uuid();
+    let src_ds_root = src_dao.dataset_root(ds_uuid)?;
+
+    let (dst_dao, _dst_dao_tmpdir) = create_sqlite_dao();
+    let mut sink = dst_dao.sink()?;
+    sink.begin_dataset("test".to_owned(), src_dao.dataset(), &src_ds_root.0)?;
+    for cwd in src_dao.chats(ds_uuid)? {
+        sink.add_chat(cwd.chat.clone())?;
+        // Pushing messages one by one
+        for msg in src_dao.first_messages(&cwd.chat, usize::MAX)? {
+            sink.add_messages(&cwd.chat, vec![msg])?;
+        }
+    }
+    sink.add_users(src_dao.users_single_ds(), src_dao.myself_single_ds().id())?;
+    sink.finish_dataset()?;
+    drop(sink);
+
+    assert!(get_datasets_diff(src_dao, ds_uuid, &dst_dao, ds_uuid, 10)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn sink_with_missing_user() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, _| {},
+        rng().random()
+    );
+    let src_dao = dao_holder.dao.as_ref();
+    let ds_uuid = &src_dao.ds_uuid();
+    let myself = src_dao.myself_single_ds();
+
+    let (dst_dao, _dst_dao_tmpdir) = create_sqlite_dao();
+    let mut sink = dst_dao.sink()?;
+    sink.begin_dataset("test".to_owned(), src_dao.dataset(), &src_dao.dataset_root(ds_uuid)?.0)?;
+    for cwd in src_dao.chats(ds_uuid)? {
+        sink.add_chat(cwd.chat.clone())?;
+        sink.add_messages(&cwd.chat, src_dao.first_messages(&cwd.chat, usize::MAX)?)?;
+    }
+    sink.add_users(vec![myself.clone()], myself.id())?;
+    let err = sink.finish_dataset().unwrap_err();
+    assert!(err.to_string().contains("are not among users"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn copy_datasets_failure_leaves_nothing_behind() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, m| m.from_id = 3,
+        rng().random()
+    );
+    let src_dao = dao_holder.dao.as_ref();
+
+    let (dst_dao, _dst_dao_tmpdir) = create_sqlite_dao();
+    let err = dst_dao.copy_datasets_from(src_dao, &[src_dao.ds_uuid()]).unwrap_err();
+    assert!(err.to_string().contains("are not among users"), "{err}");
+    assert!(dst_dao.datasets()?.is_empty());
+
+    Ok(())
+}
+
 #[test]
 fn update_dataset_same_uuid() -> EmptyRes {
     let (mut dao, _tmp_dir) = create_sqlite_dao();
@@ -638,6 +979,96 @@ fn update_user_change_id() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn reactions() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        3,
+        &|_, _, m| {
+            if let Typed::Regular(mr) = m.typed_mut() {
+                mr.reactions = vec![
+                    Reaction {
+                        emoji_option: Some("👍".to_owned()),
+                        custom_emoji_id_option: None,
+                        from_ids: vec![1, 2],
+                        count: 3,
+                    },
+                    Reaction {
+                        emoji_option: None,
+                        custom_emoji_id_option: Some("12345".to_owned()),
+                        from_ids: vec![],
+                        count: 1,
+                    },
+                ];
+            }
+        },
+        rng().random(),
+    );
+    let daos = init_from(dao_holder.dao,
+                         dao_holder.tmp_dir.path.clone(),
+                         Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+
+    let src_chat = daos.src_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let src_msgs = daos.src_dao.first_messages(&src_chat, usize::MAX)?;
+    let chat = dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(msgs.iter().map(|m| m.typed()).collect_vec(), src_msgs.iter().map(|m| m.typed()).collect_vec());
+
+    // Changing user ID should be reflected in reactions
+    let user = dao.users(&daos.ds_uuid)?.into_iter().find(|u| u.id == 2).unwrap();
+    dao.update_user(UserId(2), User { id: 22, ..user })?;
+    for m in dao.first_messages(&chat, usize::MAX)? {
+        let Typed::Regular(mr) = m.typed() else { panic!() };
+        assert_eq!(mr.reactions[0].from_ids, vec![1, 22]);
+        assert_eq!(mr.reactions[1].from_ids, Vec::<i64>::new());
+    }
+
+    // Would fail on foreign key check if reactions are left behind
+    dao.delete_chat(chat)?;
+
+    Ok(())
+}
+
+#[test]
+fn channel_posts() -> EmptyRes {
+    let users = (1..=2).map(|i| create_user(&ZERO_PB_UUID, i)).collect_vec();
+    let messages = (1..=3).map(|idx| create_regular_message(idx, 2)).collect_vec();
+    let chat = Chat {
+        tpe: ChatType::Channel as i32,
+        ..create_group_chat(&ZERO_PB_UUID, 1, "Channel", users.iter().map(|u| u.id).collect_vec(), messages.len())
+    };
+    let dao_holder = create_dao(
+        "test",
+        users,
+        vec![ChatWithMessages { chat, messages }],
+        |_, m| {
+            let source_id = m.source_id_option.unwrap();
+            if let Typed::Regular(mr) = m.typed_mut() {
+                mr.views_option = Some(source_id as i32 * 100);
+                mr.author_signature_option = (source_id % 2 == 0).then(|| format!("Admin {source_id}"));
+            }
+        },
+        rng().random(),
+    );
+    let daos = init_from(dao_holder.dao,
+                         dao_holder.tmp_dir.path.clone(),
+                         Some(dao_holder.tmp_dir));
+    let dao = daos.dst_dao;
+
+    let src_chat = daos.src_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let src_msgs = daos.src_dao.first_messages(&src_chat, usize::MAX)?;
+    let chat = dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    assert_eq!(chat.tpe(), ChatType::Channel);
+    let msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(msgs.iter().map(|m| m.typed()).collect_vec(), src_msgs.iter().map(|m| m.typed()).collect_vec());
+    assert!(msgs.iter().any(|m| matches!(m.typed(), message_regular_pat! { author_signature_option: Some(_), .. })));
+
+    Ok(())
+}
+
 #[test]
 fn update_chat_change_id() -> EmptyRes {
     let daos = init();
@@ -771,6 +1202,287 @@ fn delete_chat() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn dedupe_media() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    assert_eq!(dao.media_layout(), MediaLayout::PerChat);
+
+    // Per-chat layout stores the same files again for another chat
+    let (chat, other_chat) = chat_with_files_and_other_chat(&dao, &daos.ds_uuid)?;
+    let msgs = messages_to_copy(&dao, &chat)?;
+    let duplicated_files = msgs.iter().flat_map(|m| m.files(&daos.dst_ds_root)).unique().count();
+    assert!(duplicated_files > 0);
+    dao.insert_messages(msgs.clone(), &other_chat, &daos.dst_ds_root)?;
+
+    let old_files = dataset_files(&dao, &daos.ds_uuid);
+    let old_contents: Vec<_> = old_files.iter().map(fs::read).try_collect()?;
+
+    let report = dao.dedupe_media()?;
+    assert_eq!(dao.media_layout(), MediaLayout::ContentAddressed);
+    assert!(report.files_before - report.files_after >= duplicated_files);
+    assert!(report.bytes_saved() > 0);
+
+    let media_root = daos.dst_ds_root.0.join("_media");
+    let new_files = dataset_files(&dao, &daos.ds_uuid);
+    let new_contents: Vec<_> = new_files.iter().map(fs::read).try_collect()?;
+    assert_eq!(new_contents, old_contents);
+    for f in new_files.iter() {
+        assert!(f.starts_with(&media_root), "{} is not content-addressed", f.display());
+    }
+    for f in old_files.iter() {
+        assert!(!f.exists(), "{} was not removed", f.display());
+    }
+    for u in dao.users(&daos.ds_uuid)? {
+        for pp in u.profile_pictures.iter() {
+            let f = pp.to_absolute(&daos.dst_ds_root).absolute_path;
+            assert!(f.exists());
+            assert!(f.starts_with(&media_root), "{} is not content-addressed", f.display());
+        }
+    }
+    assert_eq!(SqliteDao::load(&dao.db_file)?.media_layout(), MediaLayout::ContentAddressed);
+
+    // Files are not stored again
+    let media_files = list_all_files(&media_root, true)?;
+    dao.insert_messages(msgs, &other_chat, &daos.dst_ds_root)?;
+    assert_eq!(list_all_files(&media_root, true)?.len(), media_files.len());
+
+    Ok(())
+}
+
+#[test]
+fn delete_chat_keeps_shared_media() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+
+    // Files of the last message with files will only be referenced by the deleted chat
+    let (chat, other_chat) = chat_with_files_and_other_chat(&dao, &daos.ds_uuid)?;
+    let mut msgs = messages_to_copy(&dao, &chat)?;
+    let exclusive_msg_idx = msgs.iter().rposition(|m| !m.files(&daos.dst_ds_root).is_empty()).unwrap();
+    msgs.remove(exclusive_msg_idx);
+    dao.insert_messages(msgs, &other_chat, &daos.dst_ds_root)?;
+    dao.dedupe_media()?;
+
+    let files_before = all_referenced_files(&dao, &daos.ds_uuid)?;
+    let chat = dao.chat_option(&daos.ds_uuid, chat.id)?.unwrap().chat;
+    // Test data has a group photo with the same content
+    let img = chat.get_img_path_option(&daos.dst_ds_root).unwrap();
+    dao.delete_chat(chat)?;
+    let files_after = all_referenced_files(&dao, &daos.ds_uuid)?;
+
+    assert!(files_after.contains(&img));
+    for f in files_after.iter() {
+        assert!(f.exists(), "Referenced file {} was removed", f.display());
+    }
+    let removed_files = files_before.difference(&files_after).collect_vec();
+    assert!(!removed_files.is_empty());
+    for f in removed_files {
+        assert!(!f.exists(), "Unreferenced file {} was kept", f.display());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn verify_and_fix() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = &daos.ds_uuid;
+    let ds_root = &daos.dst_ds_root;
+    let uuid = Uuid::parse_str(&ds_uuid.value)?;
+    let raw_uuid = uuid.as_bytes().as_slice();
+
+    // Test data has these by itself
+    let non_member_author = IntegrityIssue::NonMemberAuthor {
+        chat_id: CHAT_ID_TO_DELETE,
+        user_id: UserId(5555),
+        msg_count: 4,
+    };
+    let dangling_pin = IntegrityIssue::DanglingPin {
+        chat_id: CHAT_ID_TO_DELETE,
+        internal_id: MessageInternalId(11),
+        pinned_id: MessageSourceId(4723),
+    };
+    assert_eq!(dao.verify(ds_uuid)?, vec![non_member_author.clone(), dangling_pin.clone()]);
+
+    let chats_with_msgs: Vec<(Chat, Vec<Message>)> = dao.chats(ds_uuid)?.into_iter()
+        .filter(|cwd| cwd.chat.tpe == ChatType::Personal as i32)
+        .map(|cwd| dao.first_messages(&cwd.chat, usize::MAX).map(|msgs| (cwd.chat, msgs)))
+        .try_collect()?;
+    let (chat, msgs) = chats_with_msgs.iter()
+        .find(|(_, msgs)| msgs.iter().any(|m| !m.files(ds_root).is_empty())).unwrap();
+
+    let missing_file = msgs.iter().flat_map(|m| m.files(ds_root)).next().unwrap();
+    fs::remove_file(&missing_file)?;
+    let missing_path = path_to_str(missing_file.strip_prefix(&ds_root.0)?)?.to_owned();
+
+    let orphan_file = ds_root.to_absolute("stray/stray.txt");
+    fs::create_dir_all(orphan_file.parent().unwrap())?;
+    fs::write(&orphan_file, "stray")?;
+
+    let replying_msg = &msgs[1];
+    let first_word = |m: &Message| m.searchable_string.split_whitespace().next()
+        .filter(|w| w.chars().all(char::is_alphanumeric)).map(|w| w.to_owned());
+    let (searchable_chat, searchable_msg) = chats_with_msgs.iter()
+        .flat_map(|(chat, msgs)| msgs.iter().map(move |m| (chat, m)))
+        .find(|(_, m)| first_word(m).is_some()).unwrap();
+    {
+        use schema::*;
+        let mut conn = dao.get_conn()?;
+        update(chat::table)
+            .filter(chat::columns::ds_uuid.eq(raw_uuid))
+            .filter(chat::columns::id.eq(chat.id))
+            .set(chat::columns::msg_count.eq(chat.msg_count + 1))
+            .execute(&mut *conn)?;
+        update(message::table)
+            .filter(message::columns::internal_id.eq(replying_msg.internal_id))
+            .set(message::columns::reply_to_message_id.eq(999_999))
+            .execute(&mut *conn)?;
+        fts_unindex(&mut conn, searchable_msg.internal_id, searchable_msg.internal_id)?;
+        update(message::table)
+            .filter(message::columns::internal_id.eq(searchable_msg.internal_id))
+            .set(message::columns::searchable_string.eq(""))
+            .execute(&mut *conn)?;
+        fts_index(&mut conn, searchable_msg.internal_id, searchable_msg.internal_id)?;
+    }
+
+    let missing_file_issue = IntegrityIssue::MissingFile { path: missing_path };
+    let dangling_reply = IntegrityIssue::DanglingReply {
+        chat_id: chat.id(),
+        internal_id: replying_msg.internal_id(),
+        reply_to_id: MessageSourceId(999_999),
+    };
+    let issues = dao.verify(ds_uuid)?;
+    assert_eq!(issues, vec![
+        missing_file_issue.clone(),
+        IntegrityIssue::OrphanFile { path: "stray/stray.txt".to_owned() },
+        non_member_author,
+        dangling_reply.clone(),
+        dangling_pin.clone(),
+        IntegrityIssue::WrongMessageCount {
+            chat_id: chat.id(),
+            stored: chat.msg_count + 1,
+            actual: chat.msg_count,
+        },
+        IntegrityIssue::EmptySearchableString {
+            chat_id: searchable_chat.id(),
+            internal_id: searchable_msg.internal_id(),
+        },
+    ]);
+
+    assert_eq!(dao.fix_integrity_issues(ds_uuid, &issues)?, 4);
+    assert_eq!(dao.verify(ds_uuid)?, vec![missing_file_issue, dangling_reply, dangling_pin]);
+
+    // Orphan file is moved to backup directory
+    assert!(!orphan_file.parent().unwrap().exists());
+    let backup_paths: Vec<_> = dao.backup_path().read_dir()?.map(|e| e.map(|e| e.path())).try_collect()?;
+    assert_eq!(backup_paths.len(), 1);
+    assert!(backup_paths[0].join(path_file_name(&ds_root.0)?).join("stray/stray.txt").exists());
+
+    // Message is searchable again
+    let result = dao.search_messages(&MessageSearchQuery {
+        ds_uuid: ds_uuid.clone(),
+        text: first_word(searchable_msg).unwrap(),
+        chat_id_option: Some(searchable_chat.id()),
+        from_id_option: None,
+        timestamp_from_option: None,
+        timestamp_to_option: None,
+        offset: 0,
+        limit: usize::MAX,
+    })?;
+    assert!(result.hits.iter().any(|h| h.message.internal_id == searchable_msg.internal_id));
+
+    Ok(())
+}
+
+#[test]
+fn relink_media() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let search_dir = TmpDir::new();
+
+    type ContentRow = (i64, String, Option<String>, i64);
+    let load_contents = |dao: &SqliteDao| -> Result<Vec<ContentRow>> {
+        use schema::*;
+        Ok(message_content::table
+            .inner_join(message::table)
+            .filter(message::columns::ds_uuid.eq(Uuid::parse_str(&daos.ds_uuid.value)?.as_bytes().as_slice()))
+            .select((message_content::columns::id, message_content::columns::element_type,
+                     message_content::columns::path, message::columns::time_sent))
+            .order_by(message_content::columns::id)
+            .load(&mut *dao.get_conn()?)?)
+    };
+    let contents = load_contents(&dao)?;
+    let find_content = |tpe: &str, has_path: bool| contents.iter()
+        .find(|(_, t, p, _)| t == tpe && p.is_some() == has_path).unwrap().clone();
+
+    // Stored under hashed name, found under a different one
+    let hashed = find_content("photo", true);
+    let hashed_file = daos.dst_ds_root.to_absolute(hashed.2.as_ref().unwrap());
+    let hashed_bytes = fs::read(&hashed_file)?;
+    fs::rename(&hashed_file, search_dir.path.join("renamed.jpg"))?;
+
+    // Stored under original name
+    let named = find_content("voice_message", true);
+    let named_file = daos.dst_ds_root.to_absolute(named.2.as_ref().unwrap());
+    fs::create_dir(search_dir.path.join("nested"))?;
+    fs::rename(&named_file, search_dir.path.join("nested").join(path_file_name(&named_file)?))?;
+
+    let set_modified = |path: &Path, time: i64| -> EmptyRes {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(time as u64);
+        File::options().write(true).open(path)?.set_modified(time)?;
+        Ok(())
+    };
+
+    // Never had a file, the only photo around that time
+    let unnamed = find_content("photo", false);
+    let unnamed_file = search_dir.path.join("unnamed.jpg");
+    fs::write(&unnamed_file, b"unnamed photo")?;
+    set_modified(&unnamed_file, unnamed.3 + 60)?;
+
+    // Never had a file, two different videos around that time
+    let ambiguous = find_content("video", false);
+    for (name, shift) in [("video1.mp4", -30), ("video2.mp4", 30)] {
+        let file = search_dir.path.join(name);
+        fs::write(&file, name)?;
+        set_modified(&file, ambiguous.3 + shift)?;
+    }
+    let unmatched_count = contents.iter()
+        .filter(|(id, t, p, _)| p.is_none() && *id != unnamed.0 && *id != ambiguous.0 &&
+            ["sticker", "photo", "voice_message", "audio", "video_message", "video", "file"].contains(&t.as_str()))
+        .count();
+
+    let plan = dao.plan_media_relink(&daos.ds_uuid, &search_dir.path)?;
+    assert_eq!(plan.relinks.iter().map(|r| (r.content_id, r.match_kind)).collect_vec(), vec![
+        (hashed.0, RelinkMatchKind::Hash),
+        (named.0, RelinkMatchKind::FileName),
+        (unnamed.0, RelinkMatchKind::TimeProximity),
+    ]);
+    assert_eq!(plan.relinks[0].found_file, search_dir.path.join("renamed.jpg"));
+    assert_eq!(plan.relinks[1].old_path, named.2);
+    assert_eq!(plan.relinks[2].found_file, unnamed_file);
+    assert_eq!(plan.ambiguous, 1);
+    assert_eq!(plan.unmatched, unmatched_count);
+
+    // Dry run changes nothing
+    assert_eq!(load_contents(&dao)?, contents);
+    assert!(!hashed_file.exists());
+
+    assert_eq!(dao.relink_media(&daos.ds_uuid, &plan)?, 3);
+    let new_contents = load_contents(&dao)?;
+    let new_path = |id: i64| new_contents.iter().find(|c| c.0 == id).unwrap().2.clone().unwrap();
+    assert_eq!(fs::read(daos.dst_ds_root.to_absolute(&new_path(hashed.0)))?, hashed_bytes);
+    assert!(daos.dst_ds_root.to_absolute(&new_path(named.0)).exists());
+    assert_eq!(fs::read(daos.dst_ds_root.to_absolute(&new_path(unnamed.0)))?, b"unnamed photo");
+
+    let plan = dao.plan_media_relink(&daos.ds_uuid, &search_dir.path)?;
+    assert_eq!(plan.relinks, vec![]);
+    assert_eq!(plan.ambiguous, 1);
+    assert_eq!(plan.unmatched, unmatched_count);
+
+    Ok(())
+}
+
 #[test]
 fn combine_chats() -> EmptyRes {
     let daos = init();
@@ -1179,6 +1891,9 @@ fn init() -> TestDaos {
                             is_one_time: false,
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 4. Regular: text message
@@ -1224,6 +1939,9 @@ fn init() -> TestDaos {
                             mime_type_option: None,
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 7. Service: invite_members member777
@@ -1266,6 +1984,9 @@ fn init() -> TestDaos {
                             duration_sec_option: Some(2),
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 10. Regular: text with link
@@ -1302,6 +2023,9 @@ fn init() -> TestDaos {
                             ..Default::default()
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 12. Regular: forwarded message with link
@@ -1320,6 +2044,9 @@ fn init() -> TestDaos {
                     forward_from_name_option: Some("My Channel".to_owned()),
                     reply_to_message_id_option: None,
                     contents: vec![],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 13. Regular: message with various formats
@@ -1358,6 +2085,9 @@ fn init() -> TestDaos {
                             ..Default::default()
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 15. Regular: video_file (file not included)
@@ -1381,6 +2111,9 @@ fn init() -> TestDaos {
                             ..Default::default()
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 16. Regular: emoji text
@@ -1405,6 +2138,9 @@ fn init() -> TestDaos {
                     forward_from_name_option: None,
                     reply_to_message_id_option: Some(39125),
                     contents: vec![],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 18. Regular: forwarded message with a hidden text_link and bold
@@ -1424,6 +2160,9 @@ fn init() -> TestDaos {
                     forward_from_name_option: Some("Some Channel".to_owned()),
                     reply_to_message_id_option: None,
                     contents: vec![],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 19. Regular: edited message with link and code
@@ -1439,6 +2178,9 @@ fn init() -> TestDaos {
                     forward_from_name_option: None,
                     reply_to_message_id_option: None,
                     contents: vec![],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 20. Regular: forwarded photo with bold and italic
@@ -1464,6 +2206,9 @@ fn init() -> TestDaos {
                             ..Default::default()
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 21. Regular: contact message
@@ -1486,6 +2231,9 @@ fn init() -> TestDaos {
                             vcard_path_option: None,
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 22. Regular: forwarded message with a bunch of rich text blocks
@@ -1512,6 +2260,9 @@ fn init() -> TestDaos {
                     forward_from_name_option: Some("My Channel".to_owned()),
                     reply_to_message_id_option: None,
                     contents: vec![],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 23. Regular: contact message with vcard
@@ -1534,6 +2285,9 @@ fn init() -> TestDaos {
                             vcard_path_option: Some("chats/chat_10/contacts/contact_2.vcard".to_owned()),
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 24. Regular: another contact message with vcard
@@ -1556,6 +2310,9 @@ fn init() -> TestDaos {
                             vcard_path_option: Some("chats/chat_08/contacts/contact_1.vcard".to_owned()),
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 25. Regular: live location message
@@ -1579,6 +2336,9 @@ fn init() -> TestDaos {
                             duration_sec_option: Some(132),
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 26. Regular: poll message
@@ -1598,6 +2358,9 @@ fn init() -> TestDaos {
                             question: "What is an answer to ultimate question of life, universe and everything?".to_owned(),
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
         ];
@@ -1649,6 +2412,9 @@ fn init() -> TestDaos {
                             mime_type_option: None,
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 1. Regular: animation (not included)
@@ -1672,6 +2438,9 @@ fn init() -> TestDaos {
                             ..Default::default()
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 2. Service: phone_call missed
@@ -1723,6 +2492,9 @@ fn init() -> TestDaos {
                             mime_type_option: None,
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
             // 5. Regular: pdf file (not included)
@@ -1745,6 +2517,9 @@ fn init() -> TestDaos {
                             thumbnail_path_option: None,
                         })),
                     }],
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 ),
             ),
         ];
@@ -1896,9 +2671,36 @@ fn init_from(src_dao: Box<InMemoryDao>, src_dir: PathBuf, src_dao_tmpdir: Option
     TestDaos { src_dao, src_dir, src_dao_tmpdir, dst_dao, dst_dao_tmpdir, ds_uuid, src_ds_root, dst_ds_root }
 }
 
+/// Chat to be deleted in delete tests (having messages with files) and some other chat.
+fn chat_with_files_and_other_chat(dao: &SqliteDao, ds_uuid: &PbUuid) -> Result<(Chat, Chat)> {
+    let (chats, other_chats): (Vec<_>, Vec<_>) = dao.chats(ds_uuid)?.into_iter()
+        .map(|cwd| cwd.chat)
+        .partition(|c| c.id == *CHAT_ID_TO_DELETE);
+    Ok((chats.into_iter().exactly_one().unwrap(), other_chats.into_iter().next().unwrap()))
+}
+
+/// Messages of a chat, from myself and without source IDs so that they can be inserted into another chat.
+fn messages_to_copy(dao: &SqliteDao, chat: &Chat) -> Result<Vec<Message>> {
+    let myself_id = dao.myself(&chat.ds_uuid)?.id;
+    Ok(dao.first_messages(chat, usize::MAX)?.into_iter()
+        .map(|m| Message { source_id_option: None, from_id: myself_id, ..m })
+        .collect_vec())
+}
+
+/// Files of messages, chats and profile pictures of a dataset.
+fn all_referenced_files(dao: &SqliteDao, ds_uuid: &PbUuid) -> Result<HashSet<PathBuf>> {
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let mut files: HashSet<_> = dataset_files(dao, ds_uuid).into_iter().collect();
+    for u in dao.users(ds_uuid)? {
+        files.extend(u.profile_pictures.iter().map(|pp| pp.to_absolute(&ds_root).absolute_path));
+    }
+    Ok(files)
+}
+
 fn create_sqlite_dao() -> (SqliteDao, TmpDir) {
     let tmp_dir = TmpDir::new();
     log::info!("Using temp dir {} for Sqlite DAO", tmp_dir.path.display());
     let dao = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME)).unwrap();
     (dao, tmp_dir)
 }
+
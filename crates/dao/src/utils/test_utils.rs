@@ -23,6 +23,9 @@ lazy_static! {
         forward_from_name_option: None,
         reply_to_message_id_option: None,
         contents: vec![],
+        reactions: vec![],
+        views_option: None,
+        author_signature_option: None,
     };
 }
 
@@ -287,6 +290,9 @@ pub fn create_regular_message(idx: usize, user_id: usize) -> Message {
         contents: vec![
             content!(Poll { question: format!("Hey, {idx}!") })
         ],
+        reactions: vec![],
+        views_option: None,
+        author_signature_option: None,
     };
 
     let text = vec![RichText::make_plain(format!("Hello there, {idx}!"))];
@@ -0,0 +1,205 @@
+-- Statements are written into a backup file by the test, one statement per line (multi-line statements end with ";").
+-- Schema is a subset of Signal Android v7.x database, only interesting tables and columns are kept.
+
+CREATE TABLE sqlite_stat1(tbl,idx,stat);
+
+CREATE TABLE recipient
+(
+    _id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    type                 INTEGER DEFAULT 0,
+    e164                 TEXT UNIQUE DEFAULT NULL,
+    aci                  TEXT UNIQUE DEFAULT NULL,
+    pni                  TEXT UNIQUE DEFAULT NULL,
+    username             TEXT UNIQUE DEFAULT NULL,
+    email                TEXT UNIQUE DEFAULT NULL,
+    group_id             TEXT UNIQUE DEFAULT NULL,
+    distribution_list_id INTEGER DEFAULT NULL,
+    system_joined_name   TEXT DEFAULT NULL,
+    system_given_name    TEXT DEFAULT NULL,
+    system_family_name   TEXT DEFAULT NULL,
+    profile_given_name   TEXT DEFAULT NULL,
+    profile_family_name  TEXT DEFAULT NULL,
+    profile_joined_name  TEXT DEFAULT NULL
+);
+
+CREATE TABLE thread
+(
+    _id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    date          INTEGER DEFAULT 0,
+    meaningful_messages INTEGER DEFAULT 0,
+    recipient_id  INTEGER NOT NULL UNIQUE REFERENCES recipient (_id) ON DELETE CASCADE,
+    snippet       TEXT,
+    archived      INTEGER DEFAULT 0
+);
+
+CREATE TABLE groups
+(
+    _id          INTEGER PRIMARY KEY,
+    group_id     TEXT NOT NULL UNIQUE,
+    recipient_id INTEGER NOT NULL UNIQUE REFERENCES recipient (_id) ON DELETE CASCADE,
+    title        TEXT DEFAULT NULL,
+    active       INTEGER DEFAULT 1
+);
+
+CREATE TABLE group_membership
+(
+    _id          INTEGER PRIMARY KEY,
+    group_id     TEXT NOT NULL REFERENCES groups (group_id) ON DELETE CASCADE,
+    recipient_id INTEGER NOT NULL REFERENCES recipient (_id) ON DELETE CASCADE,
+    UNIQUE (group_id, recipient_id)
+);
+
+CREATE TABLE message
+(
+    _id                 INTEGER PRIMARY KEY AUTOINCREMENT,
+    date_sent           INTEGER NOT NULL,
+    date_received       INTEGER NOT NULL,
+    date_server         INTEGER DEFAULT -1,
+    thread_id           INTEGER NOT NULL REFERENCES thread (_id) ON DELETE CASCADE,
+    from_recipient_id   INTEGER NOT NULL REFERENCES recipient (_id) ON DELETE CASCADE,
+    from_device_id      INTEGER,
+    to_recipient_id     INTEGER NOT NULL REFERENCES recipient (_id) ON DELETE CASCADE,
+    type                INTEGER NOT NULL,
+    body                TEXT,
+    read                INTEGER DEFAULT 0,
+    quote_id            INTEGER DEFAULT 0,
+    quote_author        INTEGER DEFAULT 0,
+    quote_body          TEXT DEFAULT NULL,
+    remote_deleted      INTEGER DEFAULT 0,
+    view_once           INTEGER DEFAULT 0,
+    message_ranges      BLOB DEFAULT NULL,
+    original_message_id INTEGER DEFAULT NULL REFERENCES message (_id) ON DELETE CASCADE,
+    latest_revision_id  INTEGER DEFAULT NULL REFERENCES message (_id) ON DELETE CASCADE
+);
+
+CREATE INDEX message_thread_date_index ON message (thread_id, date_received);
+
+CREATE VIRTUAL TABLE message_fts USING fts5(body, thread_id UNINDEXED, content=message, content_rowid=_id);
+
+CREATE TRIGGER message_ai AFTER INSERT ON message BEGIN INSERT INTO message_fts(rowid, body, thread_id) VALUES (new._id, new.body, new.thread_id); END;
+
+CREATE TABLE attachment
+(
+    _id             INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id      INTEGER,
+    content_type    TEXT,
+    remote_key      TEXT,
+    data_file       TEXT,
+    data_size       INTEGER,
+    file_name       TEXT,
+    voice_note      INTEGER DEFAULT 0,
+    width           INTEGER DEFAULT 0,
+    height          INTEGER DEFAULT 0,
+    sticker_pack_id TEXT DEFAULT NULL,
+    sticker_id      INTEGER DEFAULT -1,
+    sticker_emoji   STRING DEFAULT NULL,
+    display_order   INTEGER DEFAULT 0
+);
+
+CREATE TABLE mention
+(
+    _id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id    INTEGER,
+    message_id   INTEGER,
+    recipient_id INTEGER,
+    range_start  INTEGER,
+    range_length INTEGER
+);
+
+CREATE TABLE reaction
+(
+    _id           INTEGER PRIMARY KEY,
+    message_id    INTEGER NOT NULL REFERENCES message (_id) ON DELETE CASCADE,
+    author_id     INTEGER NOT NULL REFERENCES recipient (_id) ON DELETE CASCADE,
+    emoji         TEXT NOT NULL,
+    date_sent     INTEGER NOT NULL,
+    date_received INTEGER NOT NULL,
+    UNIQUE (message_id, author_id) ON CONFLICT REPLACE
+);
+
+INSERT INTO recipient (_id, type, e164, aci, username, profile_given_name, profile_family_name)
+VALUES (1, 0, '+998911234567', 'b22bb22b-b22b-b22b-b22b-b22bb22bb22b', NULL, 'Aaaaa', 'Aaaaaaaaaaa');
+INSERT INTO recipient (_id, type, e164, aci, username, profile_given_name, profile_family_name)
+VALUES (2, 0, '+79993334455', '67766776-6776-6776-6776-677667766776', 'eeeee.01', 'Eeeee', 'Eeeeeeeeee');
+-- Profile name is empty, so the name from address book is used
+INSERT INTO recipient (_id, type, aci, profile_given_name, system_joined_name)
+VALUES (3, 0, 'c33cc33c-c33c-c33c-c33c-c33cc33cc33c', '', 'Ccccc Contact');
+INSERT INTO recipient (_id, type, group_id)
+VALUES (4, 3, '__signal_group__v2__!67726f75702d6964');
+-- "My Story"
+INSERT INTO recipient (_id, type, distribution_list_id)
+VALUES (5, 4, 1);
+-- SMS-only contact
+INSERT INTO recipient (_id, type, e164, system_joined_name)
+VALUES (6, 0, '+15550001111', 'Sms Only');
+
+INSERT INTO thread (_id, date, recipient_id) VALUES (1, 1790000600000, 2);
+INSERT INTO thread (_id, date, recipient_id) VALUES (2, 1790001000000, 4);
+INSERT INTO thread (_id, date, recipient_id) VALUES (3, 1790002000000, 5);
+
+INSERT INTO groups (_id, group_id, recipient_id, title)
+VALUES (1, '__signal_group__v2__!67726f75702d6964', 4, 'Test Group');
+
+-- Ccccc has left the group
+INSERT INTO group_membership (group_id, recipient_id) VALUES ('__signal_group__v2__!67726f75702d6964', 1);
+INSERT INTO group_membership (group_id, recipient_id) VALUES ('__signal_group__v2__!67726f75702d6964', 2);
+
+-- Message types: 10485780 = incoming, 10485783 = outgoing (sent), both with SECURE and PUSH bits
+
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (1, 1790000000000, 1790000001000, 1, 2, 1, 10485780, 'Hello there');
+-- Message 2 is inserted by the test, as it has body ranges blob
+-- Incoming audio call
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type)
+VALUES (3, 1790000100000, 1790000100000, 1, 2, 1, 1);
+-- Expiration timer update
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type)
+VALUES (4, 1790000150000, 1790000150000, 1, 1, 2, 10747927);
+-- Message 5 was edited, message 6 is its latest revision
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body, latest_revision_id)
+VALUES (5, 1790000200000, 1790000200000, 1, 1, 2, 10485783, 'Tpyo', 6);
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body, original_message_id)
+VALUES (6, 1790000260000, 1790000260000, 1, 1, 2, 10485783, 'Typo', 5);
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (7, 1790000230000, 1790000231000, 1, 2, 1, 10485780, NULL);
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body, remote_deleted)
+VALUES (8, 1790000300000, 1790000301000, 1, 2, 1, 10485780, NULL, 1);
+
+-- Group update, stored as opaque protobuf
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (9, 1790000500000, 1790000500000, 2, 1, 4, 11075607, 'CgQKAggB');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (10, 1790000600000, 1790000601000, 2, 3, 4, 10485780, 'Hi ￼!');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (11, 1790000700000, 1790000700000, 2, 1, 4, 10485783, NULL);
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body, view_once)
+VALUES (12, 1790000800000, 1790000801000, 2, 2, 4, 10485780, NULL, 1);
+-- Group call
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (13, 1790000900000, 1790000900000, 2, 2, 4, 12, NULL);
+
+-- Story
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (14, 1790002000000, 1790002000000, 3, 1, 5, 10485783, 'Story');
+
+INSERT INTO attachment (_id, message_id, content_type, file_name, width, height)
+VALUES (1, 1, 'image/jpeg', NULL, 150, 100);
+INSERT INTO attachment (_id, message_id, content_type, file_name, voice_note)
+VALUES (2, 7, 'audio/aac', NULL, 1);
+INSERT INTO attachment (_id, message_id, content_type, width, height, sticker_pack_id, sticker_id, sticker_emoji)
+VALUES (3, 11, 'image/webp', 512, 512, 'abcdef0123456789', 3, '👌');
+-- View-once photo, already viewed, so it's not in the backup
+INSERT INTO attachment (_id, message_id, content_type, width, height)
+VALUES (4, 12, 'image/jpeg', 640, 480);
+INSERT INTO attachment (_id, message_id, content_type, file_name)
+VALUES (5, 10, 'application/pdf', 'doc.pdf');
+
+INSERT INTO mention (thread_id, message_id, recipient_id, range_start, range_length)
+VALUES (2, 10, 1, 3, 1);
+
+INSERT INTO reaction (message_id, author_id, emoji, date_sent, date_received)
+VALUES (1, 1, '👍', 1790000010000, 1790000010000);
+INSERT INTO reaction (message_id, author_id, emoji, date_sent, date_received)
+VALUES (1, 2, '👍', 1790000011000, 1790000011000);
+INSERT INTO reaction (message_id, author_id, emoji, date_sent, date_received)
+VALUES (6, 2, '😂', 1790000270000, 1790000270000);
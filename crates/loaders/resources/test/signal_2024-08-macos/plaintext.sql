@@ -235,7 +235,30 @@ INSERT INTO messages VALUES(698,'05fee241-7b89-461b-87be-14b62c5ed0e8',
             "67766776-6776-6776-6776-677667766776"
           ],
           "errors": [],
-          "synced": true
+          "synced": true,
+          "reactions": [
+            {
+              "emoji": "👍",
+              "fromId": "eeeeeeee-eeee-eeee-eeee-eeeeeeeeeeee",
+              "targetAuthorUuid": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b",
+              "targetTimestamp": 1685967643288,
+              "timestamp": 1685967650000
+            },
+            {
+              "emoji": "❤️",
+              "fromId": "eeeeeeee-eeee-eeee-eeee-eeeeeeeeeeee",
+              "targetAuthorUuid": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b",
+              "targetTimestamp": 1685967643288,
+              "timestamp": 1685967651000
+            },
+            {
+              "emoji": "👍",
+              "fromId": "2dd22dd2-2dd2-2dd2-2dd2-2dd22dd22dd2",
+              "targetAuthorUuid": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b",
+              "targetTimestamp": 1685967643288,
+              "timestamp": 1685967652000
+            }
+          ]
         }',
         0,NULL,1685967643288,13,'eeeeeeee-eeee-eeee-eeee-eeeeeeeeeeee',1785,NULL,1,0,1,NULL,1685967645498,
         'outgoing','Photo caption',NULL,NULL,NULL,0,0,
@@ -0,0 +1,395 @@
+--
+-- Schema
+--
+
+CREATE TABLE conversations(
+    id                    STRING PRIMARY KEY ASC,
+    json                  TEXT,
+    active_at             INTEGER,
+    type                  STRING,
+    members               TEXT,
+    name                  TEXT,
+    profileName           TEXT,
+    profileFamilyName     TEXT,
+    profileFullName       TEXT,
+    e164                  TEXT,
+    serviceId             TEXT,
+    groupId               TEXT,
+    profileLastFetchedAt  INTEGER,
+    expireTimerVersion    INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE TABLE messages(
+    rowid                       INTEGER PRIMARY KEY ASC,
+    id                          STRING UNIQUE,
+    json                        TEXT,
+    readStatus                  INTEGER,
+    expires_at                  INTEGER,
+    sent_at                     INTEGER,
+    schemaVersion               INTEGER,
+    conversationId              STRING,
+    received_at                 INTEGER,
+    source                      STRING,
+    hasAttachments              INTEGER,
+    hasFileAttachments          INTEGER,
+    hasVisualMediaAttachments   INTEGER,
+    expireTimer                 INTEGER,
+    expirationStartTimestamp    INTEGER,
+    type                        STRING,
+    body                        TEXT,
+    messageTimer                INTEGER,
+    messageTimerStart           INTEGER,
+    messageTimerExpiresAt       INTEGER,
+    isErased                    INTEGER,
+    isViewOnce                  INTEGER,
+    sourceServiceId             TEXT,
+    serverGuid                  STRING NULL,
+    sourceDevice                INTEGER,
+    storyId                     STRING,
+    isStory                     INTEGER GENERATED ALWAYS AS (type IS 'story'),
+    isChangeCreatedByUs         INTEGER NOT NULL DEFAULT 0,
+    isTimerChangeFromSync       INTEGER GENERATED ALWAYS AS (json_extract(json, '$.expirationTimerUpdate.fromSync') IS 1),
+    seenStatus                  NUMBER DEFAULT 0,
+    storyDistributionListId     STRING,
+    expiresAt                   INT GENERATED ALWAYS AS (ifnull(
+      expirationStartTimestamp + (expireTimer * 1000),
+      9007199254740991
+    )),
+    isUserInitiatedMessage      INTEGER GENERATED ALWAYS AS (
+      type IS NULL
+      OR
+      type NOT IN (
+        'change-number-notification',
+        'contact-removed-notification',
+        'conversation-merge',
+        'group-v1-migration',
+        'group-v2-change',
+        'keychange',
+        'message-history-unsynced',
+        'profile-change',
+        'story',
+        'universal-timer-notification',
+        'verified-change'
+      )
+    ),
+    mentionsMe                  INTEGER NOT NULL DEFAULT 0,
+    isGroupLeaveEvent           INTEGER GENERATED ALWAYS AS (
+      type IS 'group-v2-change' AND
+      json_array_length(json_extract(json, '$.groupV2Change.details')) IS 1 AND
+      json_extract(json, '$.groupV2Change.details[0].type') IS 'member-remove' AND
+      json_extract(json, '$.groupV2Change.from') IS NOT NULL AND
+      json_extract(json, '$.groupV2Change.from') IS json_extract(json, '$.groupV2Change.details[0].aci')
+    ),
+    isGroupLeaveEventFromOther  INTEGER GENERATED ALWAYS AS (isGroupLeaveEvent IS 1 AND isChangeCreatedByUs IS 0),
+    callId                      TEXT GENERATED ALWAYS AS (json_extract(json, '$.callId')),
+    shouldAffectPreview         INTEGER GENERATED ALWAYS AS (
+      type IS NULL
+      OR
+      type NOT IN (
+        'change-number-notification',
+        'contact-removed-notification',
+        'conversation-merge',
+        'group-v1-migration',
+        'keychange',
+        'message-history-unsynced',
+        'profile-change',
+        'story',
+        'universal-timer-notification',
+        'verified-change'
+      )
+      AND NOT (
+        type IS 'message-request-response-event'
+        AND json_extract(json, '$.messageRequestResponseEvent') IN ('ACCEPT', 'BLOCK', 'UNBLOCK')
+      )
+    ),
+    shouldAffectActivity        INTEGER GENERATED ALWAYS AS (
+      type IS NULL
+      OR
+      type NOT IN (
+        'change-number-notification',
+        'contact-removed-notification',
+        'conversation-merge',
+        'group-v1-migration',
+        'keychange',
+        'message-history-unsynced',
+        'profile-change',
+        'story',
+        'universal-timer-notification',
+        'verified-change'
+      )
+      AND NOT (
+        type IS 'message-request-response-event'
+        AND json_extract(json, '$.messageRequestResponseEvent') IN ('ACCEPT', 'BLOCK', 'UNBLOCK')
+      )
+    ),
+    isAddressableMessage        INTEGER GENERATED ALWAYS AS (
+      type IS NULL
+      OR
+      type IN (
+        'incoming',
+        'outgoing'
+      )
+    )
+);
+
+CREATE TABLE callsHistory (
+    callId          TEXT PRIMARY KEY,
+    peerId          TEXT NOT NULL, -- conversation id (legacy) | uuid | groupId | roomId
+    ringerId        TEXT DEFAULT NULL, -- ringer uuid
+    mode            TEXT NOT NULL, -- enum "Direct" | "Group"
+    type            TEXT NOT NULL, -- enum "Audio" | "Video" | "Group"
+    direction       TEXT NOT NULL, -- enum "Incoming" | "Outgoing
+    -- Direct: enum "Pending" | "Missed" | "Accepted" | "Deleted"
+    -- Group: enum "GenericGroupCall" | "OutgoingRing" | "Ringing" | "Joined" | "Missed" | "Declined" | "Accepted" | "Deleted"
+    status          TEXT NOT NULL,
+    timestamp       INTEGER NOT NULL,
+    startedById     TEXT DEFAULT NULL,
+    endedTimestamp  INTEGER DEFAULT NULL,
+    UNIQUE (callId, peerId) ON CONFLICT FAIL
+);
+
+CREATE TABLE items(
+    id STRING PRIMARY KEY ASC,
+    json TEXT
+);
+
+--
+-- Users
+--
+
+INSERT INTO items VALUES('uuid_id','{"id":"uuid_id","value":"b22bb22b-b22b-b22b-b22b-b22bb22bb22b.2"}');
+
+-- JSON is not used for private conversations so it's omitted entirely for simplicity
+INSERT INTO conversations(id, json, active_at, type, profileName, profileFamilyName, e164, serviceId)
+VALUES ('2dd22dd2-2dd2-2dd2-2dd2-2dd22dd22dd2', '', 1790000000000, 'private',
+        'Aaaaa', 'Aaaaaaaaaaa', '+998 91 1234567', 'b22bb22b-b22b-b22b-b22b-b22bb22bb22b'),
+       ('eeeeeeee-eeee-eeee-eeee-eeeeeeeeeeee', '', 1790000000000, 'private',
+        'Eeeee', 'Eeeeeeeeee', '+7 999 333 44 55', '67766776-6776-6776-6776-677667766776'),
+       ('cccccccc-cccc-cccc-cccc-cccccccccccc', '', 1790000000000, 'private',
+        'Ccccc', NULL, NULL, 'c33cc33c-c33c-c33c-c33c-c33cc33cc33c');
+
+-- Ccccc has left the group, so they're not listed in members
+INSERT INTO conversations(id, json, active_at, type, name, groupId)
+VALUES ('99999999-9999-9999-9999-999999999999',
+        '{
+          "id": "99999999-9999-9999-9999-999999999999",
+          "type": "group",
+          "name": "Renamed Group",
+          "groupVersion": 2,
+          "membersV2": [
+            { "aci": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b", "role": 2, "joinedAtVersion": 0 },
+            { "aci": "67766776-6776-6776-6776-677667766776", "role": 1, "joinedAtVersion": 0 }
+          ]
+        }',
+        1790000540000, 'group', 'Renamed Group', 'Z3JvdXAtaWQ=');
+
+--
+-- Group chat
+--
+
+-- Group created
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (1, '11111111-0000-0000-0000-000000000001',
+        '{
+          "id": "11111111-0000-0000-0000-000000000001",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "group-v2-change",
+          "sent_at": 1790000000000,
+          "groupV2Change": {
+            "from": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b",
+            "details": [
+              { "type": "create" },
+              { "type": "member-add", "aci": "67766776-6776-6776-6776-677667766776" }
+            ]
+          }
+        }',
+        1790000000000, '99999999-9999-9999-9999-999999999999', 'group-v2-change', NULL, 0, NULL);
+
+-- Rich text with mention
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (2, '11111111-0000-0000-0000-000000000002',
+        '{
+          "id": "11111111-0000-0000-0000-000000000002",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "incoming",
+          "timestamp": 1790000060000,
+          "sent_at": 1790000060000,
+          "sourceServiceId": "67766776-6776-6776-6776-677667766776",
+          "body": "👋 Hi ￼! Bold, both, mono and secret.",
+          "bodyRanges": [
+            { "start": 6, "length": 1, "mentionAci": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b", "replacementText": "Aaaaa" },
+            { "start": 9, "length": 10, "style": 1 },
+            { "start": 15, "length": 4, "style": 2 },
+            { "start": 21, "length": 4, "style": 5 },
+            { "start": 30, "length": 6, "style": 3 }
+          ],
+          "attachments": [],
+          "reactions": [
+            {
+              "emoji": "👍",
+              "fromId": "2dd22dd2-2dd2-2dd2-2dd2-2dd22dd22dd2",
+              "targetAuthorAci": "67766776-6776-6776-6776-677667766776",
+              "targetTimestamp": 1790000060000,
+              "timestamp": 1790000065000
+            }
+          ]
+        }',
+        1790000060000, '99999999-9999-9999-9999-999999999999', 'incoming',
+        '👋 Hi ￼! Bold, both, mono and secret.', 0, '67766776-6776-6776-6776-677667766776');
+
+-- Same timestamp, different author
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (3, '11111111-0000-0000-0000-000000000003',
+        '{
+          "id": "11111111-0000-0000-0000-000000000003",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "outgoing",
+          "timestamp": 1790000060000,
+          "sent_at": 1790000060000,
+          "body": "Same time",
+          "bodyRanges": []
+        }',
+        1790000060000, '99999999-9999-9999-9999-999999999999', 'outgoing',
+        'Same time', 0, 'b22bb22b-b22b-b22b-b22b-b22bb22bb22b');
+
+-- Sticker, replying to rich text message
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (4, '11111111-0000-0000-0000-000000000004',
+        '{
+          "id": "11111111-0000-0000-0000-000000000004",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "outgoing",
+          "timestamp": 1790000120000,
+          "sent_at": 1790000120000,
+          "quote": {
+            "id": 1790000060000,
+            "authorAci": "67766776-6776-6776-6776-677667766776",
+            "text": "👋 Hi ￼! Bold, both, mono and secret.",
+            "attachments": [],
+            "isViewOnce": false,
+            "referencedMessageNotFound": false
+          },
+          "sticker": {
+            "packId": "0123456789abcdef0123456789abcdef",
+            "packKey": "does-not-matter",
+            "stickerId": 3,
+            "emoji": "👌",
+            "data": {
+              "path": "st/sticker-1",
+              "contentType": "image/webp",
+              "width": 512,
+              "height": 512,
+              "size": 32,
+              "version": 2,
+              "localKey": "XGOXwKiehQV03r50+GMOy7f07V8Mph/Mnvw8OqKeMrk4oge85iJ5lX45766i68yC1LtH4sI/QQHRec0kW0grxQ=="
+            }
+          }
+        }',
+        1790000120000, '99999999-9999-9999-9999-999999999999', 'outgoing',
+        NULL, 0, 'b22bb22b-b22b-b22b-b22b-b22bb22bb22b');
+
+-- Joined via link, author is not specified
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (5, '11111111-0000-0000-0000-000000000005',
+        '{
+          "id": "11111111-0000-0000-0000-000000000005",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "group-v2-change",
+          "sent_at": 1790000180000,
+          "groupV2Change": {
+            "details": [
+              { "type": "member-add-from-link", "aci": "c33cc33c-c33c-c33c-c33c-c33cc33cc33c" }
+            ]
+          }
+        }',
+        1790000180000, '99999999-9999-9999-9999-999999999999', 'group-v2-change', NULL, 0, NULL);
+
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (6, '11111111-0000-0000-0000-000000000006',
+        '{
+          "id": "11111111-0000-0000-0000-000000000006",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "incoming",
+          "timestamp": 1790000240000,
+          "sent_at": 1790000240000,
+          "sourceServiceId": "c33cc33c-c33c-c33c-c33c-c33cc33cc33c",
+          "body": "Hello",
+          "bodyRanges": []
+        }',
+        1790000240000, '99999999-9999-9999-9999-999999999999', 'incoming',
+        'Hello', 0, 'c33cc33c-c33c-c33c-c33c-c33cc33cc33c');
+
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (7, '11111111-0000-0000-0000-000000000007',
+        '{
+          "id": "11111111-0000-0000-0000-000000000007",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "group-v2-change",
+          "sent_at": 1790000300000,
+          "groupV2Change": {
+            "from": "67766776-6776-6776-6776-677667766776",
+            "details": [
+              { "type": "title", "newTitle": "Renamed Group" }
+            ]
+          }
+        }',
+        1790000300000, '99999999-9999-9999-9999-999999999999', 'group-v2-change', NULL, 0, NULL);
+
+-- Left the group
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (8, '11111111-0000-0000-0000-000000000008',
+        '{
+          "id": "11111111-0000-0000-0000-000000000008",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "group-v2-change",
+          "sent_at": 1790000360000,
+          "groupV2Change": {
+            "from": "c33cc33c-c33c-c33c-c33c-c33cc33cc33c",
+            "details": [
+              { "type": "member-remove", "aci": "c33cc33c-c33c-c33c-c33c-c33cc33cc33c" }
+            ]
+          }
+        }',
+        1790000360000, '99999999-9999-9999-9999-999999999999', 'group-v2-change', NULL, 0, NULL);
+
+-- Not interesting, skipped
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (9, '11111111-0000-0000-0000-000000000009',
+        '{
+          "id": "11111111-0000-0000-0000-000000000009",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "group-v2-change",
+          "sent_at": 1790000420000,
+          "groupV2Change": {
+            "from": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b",
+            "details": [
+              { "type": "access-attributes", "newPrivilege": 3 }
+            ]
+          }
+        }',
+        1790000420000, '99999999-9999-9999-9999-999999999999', 'group-v2-change', NULL, 0, NULL),
+       (10, '11111111-0000-0000-0000-000000000010',
+        '{
+          "id": "11111111-0000-0000-0000-000000000010",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "timer-notification",
+          "sent_at": 1790000480000,
+          "expirationTimerUpdate": { "expireTimer": 604800, "sourceServiceId": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b" }
+        }',
+        1790000480000, '99999999-9999-9999-9999-999999999999', 'timer-notification', NULL, 0, NULL);
+
+INSERT INTO messages(rowid, id, json, sent_at, conversationId, type, body, isErased, sourceServiceId)
+VALUES (11, '11111111-0000-0000-0000-000000000011',
+        '{
+          "id": "11111111-0000-0000-0000-000000000011",
+          "conversationId": "99999999-9999-9999-9999-999999999999",
+          "type": "group-v2-change",
+          "sent_at": 1790000540000,
+          "groupV2Change": {
+            "from": "b22bb22b-b22b-b22b-b22b-b22bb22bb22b",
+            "details": [
+              { "type": "avatar", "removed": true }
+            ]
+          }
+        }',
+        1790000540000, '99999999-9999-9999-9999-999999999999', 'group-v2-change', NULL, 0, NULL);
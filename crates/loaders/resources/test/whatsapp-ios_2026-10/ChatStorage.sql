@@ -0,0 +1,160 @@
+CREATE TABLE ZWACHATSESSION (
+    Z_PK              INTEGER PRIMARY KEY,
+    Z_ENT             INTEGER,
+    Z_OPT             INTEGER,
+    ZARCHIVED         INTEGER,
+    ZHIDDEN           INTEGER,
+    ZREMOVED          INTEGER,
+    ZSESSIONTYPE      INTEGER,
+    ZUNREADCOUNT      INTEGER,
+    ZGROUPINFO        INTEGER,
+    ZLASTMESSAGE      INTEGER,
+    ZLASTMESSAGEDATE  TIMESTAMP,
+    ZCONTACTJID       VARCHAR,
+    ZPARTNERNAME      VARCHAR
+);
+
+CREATE TABLE ZWAGROUPMEMBER (
+    Z_PK              INTEGER PRIMARY KEY,
+    Z_ENT             INTEGER,
+    Z_OPT             INTEGER,
+    ZISACTIVE         INTEGER,
+    ZISADMIN          INTEGER,
+    ZCHATSESSION      INTEGER,
+    ZCONTACTNAME      VARCHAR,
+    ZFIRSTNAME        VARCHAR,
+    ZMEMBERJID        VARCHAR
+);
+
+CREATE TABLE ZWAMESSAGE (
+    Z_PK              INTEGER PRIMARY KEY,
+    Z_ENT             INTEGER,
+    Z_OPT             INTEGER,
+    ZISFROMME         INTEGER,
+    ZMESSAGESTATUS    INTEGER,
+    ZMESSAGETYPE      INTEGER,
+    ZGROUPEVENTTYPE   INTEGER,
+    ZSORT             INTEGER,
+    ZSTARRED          INTEGER,
+    ZCHATSESSION      INTEGER,
+    ZGROUPMEMBER      INTEGER,
+    ZMEDIAITEM        INTEGER,
+    ZPARENTMESSAGE    INTEGER,
+    ZMESSAGEDATE      TIMESTAMP,
+    ZSENTDATE         TIMESTAMP,
+    ZFROMJID          VARCHAR,
+    ZPUSHNAME         VARCHAR,
+    ZSTANZAID         VARCHAR,
+    ZTEXT             VARCHAR,
+    ZTOJID            VARCHAR
+);
+
+CREATE TABLE ZWAMEDIAITEM (
+    Z_PK                INTEGER PRIMARY KEY,
+    Z_ENT               INTEGER,
+    Z_OPT               INTEGER,
+    ZFILESIZE           INTEGER,
+    ZMOVIEDURATION      INTEGER,
+    ZMESSAGE            INTEGER,
+    ZLATITUDE           FLOAT,
+    ZLONGITUDE          FLOAT,
+    ZAUTHORNAME         VARCHAR,
+    ZMEDIALOCALPATH     VARCHAR,
+    ZMEDIAURL           VARCHAR,
+    ZTHUMBNAILLOCALPATH VARCHAR,
+    ZTITLE              VARCHAR,
+    ZVCARDNAME          VARCHAR,
+    ZVCARDSTRING        VARCHAR,
+    ZXMPPTHUMBPATH      VARCHAR,
+    ZMEDIAKEY           BLOB,
+    ZMETADATA           BLOB
+);
+
+CREATE TABLE ZWAPROFILEPUSHNAME (
+    Z_PK              INTEGER PRIMARY KEY,
+    Z_ENT             INTEGER,
+    Z_OPT             INTEGER,
+    ZJID              VARCHAR,
+    ZPUSHNAME         VARCHAR
+);
+
+CREATE TABLE ZWAPROFILEPICTUREITEM (
+    Z_PK              INTEGER PRIMARY KEY,
+    Z_ENT             INTEGER,
+    Z_OPT             INTEGER,
+    ZREQUESTDATE      TIMESTAMP,
+    ZJID              VARCHAR,
+    ZPATH             VARCHAR,
+    ZPICTUREID        VARCHAR
+);
+
+-- Personal chat with user 11111, same JID as in whatsapp-android_2026-05
+INSERT INTO ZWACHATSESSION
+VALUES (1, 4, 10, 0, 0, 0, 0, 0, NULL, 3, 798501566.0, '11111@s.whatsapp.net', 'Eeeee Eeeeeeeeee');
+
+-- Group chat, same JID as in whatsapp-android_2026-05
+INSERT INTO ZWACHATSESSION
+VALUES (2, 4, 20, 0, 0, 0, 1, 0, 1, 8, 798501800.0, '100000000000000001@g.us', 'My Group');
+
+-- Status broadcasts are not chats
+INSERT INTO ZWACHATSESSION
+VALUES (3, 4, 5, 0, 0, 0, 3, 0, NULL, 9, 798501900.0, 'status@broadcast', NULL);
+
+-- Chat without messages
+INSERT INTO ZWACHATSESSION
+VALUES (4, 4, 1, 0, 0, 0, 0, 0, NULL, NULL, NULL, '22222@s.whatsapp.net', 'Nobody');
+
+INSERT INTO ZWAGROUPMEMBER VALUES (1, 3, 1, 1, 0, 2, NULL, NULL, '11111@s.whatsapp.net');
+INSERT INTO ZWAGROUPMEMBER VALUES (2, 3, 1, 1, 0, 2, 'Ccccc', 'Ccccc', '33333@s.whatsapp.net');
+
+INSERT INTO ZWAPROFILEPUSHNAME VALUES (1, 5, 1, '33333@s.whatsapp.net', 'Ccccc Push');
+
+INSERT INTO ZWAPROFILEPICTUREITEM VALUES (1, 6, 1, 798500000.0, '11111@s.whatsapp.net', 'Media/Profile/11111-1776000000.thumb', '1776000000');
+INSERT INTO ZWAPROFILEPICTUREITEM VALUES (2, 6, 1, 798500000.0, '100000000000000001@g.us', 'Media/Profile/100000000000000001-1776000000.thumb', '1776000000');
+
+-- Personal chat: text, photo with caption, voice message
+INSERT INTO ZWAMESSAGE
+VALUES (1, 9, 3, 0, 8, 0, 0, 1, 0, 1, NULL, NULL, NULL, 798501500.0, 798501500.0,
+        '11111@s.whatsapp.net', NULL, 'PERSONALMSGIOS001', 'Hello there', NULL);
+INSERT INTO ZWAMESSAGE
+VALUES (2, 9, 3, 1, 8, 1, 0, 2, 0, 1, NULL, 1, NULL, 798501530.0, 798501530.0,
+        NULL, NULL, 'PERSONALMSGIOS002', NULL, '11111@s.whatsapp.net');
+INSERT INTO ZWAMESSAGE
+VALUES (3, 9, 3, 0, 8, 3, 0, 3, 0, 1, NULL, 2, NULL, 798501566.0, 798501566.0,
+        '11111@s.whatsapp.net', NULL, 'PERSONALMSGIOS003', NULL, NULL);
+
+INSERT INTO ZWAMEDIAITEM
+VALUES (1, 8, 2, 81833, 0, 2, 0.0, 0.0, NULL, 'Media/11111@s.whatsapp.net/a/b/photo.jpg', 'https://mmg.whatsapp.net/doesntmatter',
+        'Media/11111@s.whatsapp.net/a/b/photo.thumb', 'Photo caption', NULL, NULL, NULL, NULL, NULL);
+INSERT INTO ZWAMEDIAITEM
+VALUES (2, 8, 2, 12345, 7, 3, 0.0, 0.0, NULL, 'Media/11111@s.whatsapp.net/c/d/voice.opus', 'https://mmg.whatsapp.net/doesntmatter',
+        NULL, NULL, NULL, NULL, NULL, NULL, NULL);
+
+-- Group chat: title change, member text, location, document, unknown type
+INSERT INTO ZWAMESSAGE
+VALUES (4, 9, 3, 1, 0, 6, 1, 1, 0, 2, NULL, NULL, NULL, 798501600.0, 798501600.0,
+        '100000000000000001@g.us', NULL, 'GROUPMSGIOS001', 'My Group', NULL);
+INSERT INTO ZWAMESSAGE
+VALUES (5, 9, 3, 0, 8, 0, 0, 2, 0, 2, 2, NULL, NULL, 798501700.0, 798501700.0,
+        '100000000000000001@g.us', 'Ccccc Push', 'GROUPMSGIOS002', 'Group message', NULL);
+INSERT INTO ZWAMESSAGE
+VALUES (6, 9, 3, 0, 8, 5, 0, 3, 0, 2, 1, 3, NULL, 798501750.0, 798501750.0,
+        '100000000000000001@g.us', NULL, 'GROUPMSGIOS003', NULL, NULL);
+INSERT INTO ZWAMESSAGE
+VALUES (7, 9, 3, 1, 8, 8, 0, 4, 0, 2, NULL, 4, NULL, 798501780.0, 798501780.0,
+        NULL, NULL, 'GROUPMSGIOS004', 'report.pdf', '100000000000000001@g.us');
+INSERT INTO ZWAMESSAGE
+VALUES (8, 9, 3, 0, 8, 999, 0, 5, 0, 2, 2, NULL, NULL, 798501800.0, 798501800.0,
+        '100000000000000001@g.us', NULL, 'GROUPMSGIOS005', NULL, NULL);
+
+INSERT INTO ZWAMEDIAITEM
+VALUES (3, 8, 2, 0, 0, 6, 55.7558123456789, 37.6173, NULL, NULL, NULL,
+        NULL, 'Red Square', NULL, NULL, NULL, NULL, NULL);
+INSERT INTO ZWAMEDIAITEM
+VALUES (4, 8, 2, 54321, 0, 7, 0.0, 0.0, NULL, 'Media/100000000000000001@g.us/e/f/8c1f.pdf', 'https://mmg.whatsapp.net/doesntmatter',
+        'Media/100000000000000001@g.us/e/f/8c1f.thumb', 'report.pdf', NULL, NULL, NULL, NULL, NULL);
+
+-- Status message
+INSERT INTO ZWAMESSAGE
+VALUES (9, 9, 3, 0, 8, 1, 0, 1, 0, 3, NULL, NULL, NULL, 798501900.0, 798501900.0,
+        'status@broadcast', NULL, 'STATUSIOS001', NULL, NULL);
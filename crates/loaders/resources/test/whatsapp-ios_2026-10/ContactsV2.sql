@@ -0,0 +1,12 @@
+CREATE TABLE ZWAADDRESSBOOKCONTACT (
+    Z_PK              INTEGER PRIMARY KEY,
+    Z_ENT             INTEGER,
+    Z_OPT             INTEGER,
+    ZFULLNAME         VARCHAR,
+    ZGIVENNAME        VARCHAR,
+    ZLASTNAME         VARCHAR,
+    ZPHONENUMBER      VARCHAR,
+    ZWHATSAPPID       VARCHAR
+);
+
+INSERT INTO ZWAADDRESSBOOKCONTACT VALUES (1, 2, 1, 'Eeeee Eeeeeeeeee', 'Eeeee', 'Eeeeeeeeee', '11111', '11111@s.whatsapp.net');
@@ -30,10 +30,11 @@ pub mod prelude {
     pub use chat_history_manager_core::utils::entity_utils::*;
     pub use chat_history_manager_core::utils::*;
 
-    pub use chat_history_manager_dao::in_memory_dao::InMemoryDao;
+    pub use chat_history_manager_dao::in_memory_dao::{InMemoryDao, InMemorySink};
     pub use chat_history_manager_dao::sqlite_dao::SqliteDao;
-    pub use chat_history_manager_dao::ChatHistoryDao;
-    pub use chat_history_manager_dao::MutableChatHistoryDao;
+    pub use chat_history_manager_dao::{push_chat, push_dataset, ChatHistoryDao, ChatHistorySink};
+    pub use chat_history_manager_dao::{InPlaceMergeTarget, MutableChatHistoryDao, VerifiableChatHistoryDao};
+    pub use chat_history_manager_dao::{IntegrityIssue, IntegrityIssueKind};
 }
 
 //
@@ -1,11 +1,15 @@
 mod telegram;
+mod telegram_html;
 mod tg_keeper;
 mod tinder_android;
 mod whatsapp_android;
+mod whatsapp_ios;
 mod whatsapp_text;
 mod signal;
+mod signal_android;
 mod badoo_android;
 mod mra;
+mod discord;
 
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
@@ -15,13 +19,17 @@ use chrono::Local;
 use crate::prelude::*;
 
 pub use crate::loader::badoo_android::BadooAndroidDataLoader;
+pub use crate::loader::discord::DiscordDataLoader;
 pub use crate::loader::mra::MailRuAgentDataLoader;
 pub use crate::loader::signal::SignalDataLoader;
+pub use crate::loader::signal_android::SignalAndroidDataLoader;
 pub use crate::loader::telegram::TelegramDataLoader;
+pub use crate::loader::telegram_html::TelegramHtmlDataLoader;
 pub use crate::loader::tg_keeper::TgKeeperDataLoader;
 pub use crate::loader::tg_keeper::LoaderConfig as TgKeeperDataLoaderConfig;
 pub use crate::loader::tinder_android::TinderAndroidDataLoader;
 pub use crate::loader::whatsapp_android::WhatsAppAndroidDataLoader;
+pub use crate::loader::whatsapp_ios::WhatsAppIosDataLoader;
 pub use crate::loader::whatsapp_text::WhatsAppTextDataLoader;
 
 pub trait DataLoader: Send + Sync {
@@ -46,18 +54,50 @@ pub trait DataLoader: Send + Sync {
     fn load(&self, feedback_client: &dyn FeedbackClientSync, path: &Path) -> Result<Box<InMemoryDao>> {
         let root_path_str = ensure_file_presence(path)?;
         let res = measure(|| {
-            let now_str = Local::now().format("%Y-%m-%d");
-            let ds = Dataset {
-                uuid: PbUuid::random(),
-                alias: format!("{}, loaded @ {now_str}", self.src_alias()),
-            };
-            self.load_inner(feedback_client, path, ds)
+            self.load_inner(feedback_client, path, self.new_dataset())
         }, |_, t| log::info!("File {} loaded in {t} ms", root_path_str));
         feedback_client.set_load_status(LoadStatus::new_done());
         res
     }
 
     fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>>;
+
+    /// Same as [Self::load], but pushes a loaded dataset into the given sink instead.
+    fn load_into(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        let root_path_str = ensure_file_presence(path)?;
+        let res = measure(|| {
+            self.load_into_inner(feedback_client, path, self.new_dataset(), sink)
+        }, |_, t| log::info!("File {} loaded in {t} ms", root_path_str));
+        feedback_client.set_load_status(LoadStatus::new_done());
+        res
+    }
+
+    /// Loaders capable of streaming should override this (and implement [Self::load_inner] via [load_in_memory]),
+    /// by default the whole dataset is loaded in memory first.
+    fn load_into_inner(&self,
+                       feedback_client: &dyn FeedbackClientSync,
+                       path: &Path,
+                       ds: Dataset,
+                       sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        let ds_uuid = ds.uuid.clone();
+        let dao = self.load_inner(feedback_client, path, ds)?;
+        push_dataset(dao.as_ref(), &ds_uuid, sink)
+    }
+
+    fn new_dataset(&self) -> Dataset {
+        let now_str = Local::now().format("%Y-%m-%d");
+        Dataset {
+            uuid: PbUuid::random(),
+            alias: format!("{}, loaded @ {now_str}", self.src_alias()),
+        }
+    }
+}
+
+/// Collects a dataset pushed by a streaming loader into an [InMemoryDao].
+fn load_in_memory(load_into: impl FnOnce(&mut dyn ChatHistorySink) -> EmptyRes) -> Result<Box<InMemoryDao>> {
+    let mut sink = InMemorySink::default();
+    load_into(&mut sink)?;
+    Ok(Box::new(sink.into_dao()?))
 }
 
 fn ensure_file_presence(root_file: &Path) -> Result<&str> {
@@ -183,6 +223,8 @@ fn download_if_missing(
 
 // Android-specific helpers.
 pub mod android {
+    use std::io::Write;
+
     use const_format::concatcp;
     use rusqlite::Connection;
 
@@ -197,15 +239,28 @@ pub mod android {
     pub const RELATIVE_MEDIA_DIR: &str = concatcp!(MEDIA_DIR, "/", MEDIA_DOWNLOADED_SUBDIR);
 
     /// Boilerplate for a data loader of salvaged Android sqlite database.
-    /// First construct a custom users structure, use it to read chats, then normalize the structure into
-    /// plain old Vec<User>.
+    /// First construct a custom users structure, use it to read chats (pushing them one by one as they're ready),
+    /// then normalize the structure into plain old Vec<User>.
     /// Produced users should have myself as a first user.
     pub trait AndroidDataLoader: Send + Sync {
         const NAME: &'static str;
         const DB_FILENAME: &'static str;
 
+        /// Extensions of encrypted database backups (e.g. `crypt15` for `msgstore.db.crypt15`),
+        /// these are accepted if [Self::decrypt_db] is implemented.
+        const ENCRYPTED_DB_EXTENSIONS: &'static [&'static str] = &[];
+
         type Users;
 
+        /// Decrypts an encrypted database backup, returning plain sqlite database content.
+        fn decrypt_db(
+            &self,
+            _feedback_client: &dyn FeedbackClientSync,
+            path: &Path,
+        ) -> Result<Vec<u8>> {
+            bail!("Can't decrypt {}", path.display())
+        }
+
         fn tweak_conn(
             &self,
             _conn: &Connection,
@@ -227,7 +282,7 @@ pub mod android {
             &self,
             feedback_client: &dyn FeedbackClientSync,
             users: Self::Users,
-            cwms: &[ChatWithMessages]
+            chats: &[Chat]
         ) -> Result<Vec<User>>;
 
         fn parse_chats(
@@ -236,8 +291,9 @@ pub mod android {
             feedback_client: &dyn FeedbackClientSync,
             ds_uuid: &PbUuid,
             path: &Path,
-            users: &mut Self::Users
-        ) -> Result<Vec<ChatWithMessages>>;
+            users: &mut Self::Users,
+            push_cwm: &mut dyn FnMut(ChatWithMessages) -> EmptyRes,
+        ) -> EmptyRes;
     }
 
     impl<ADL> DataLoader for ADL
@@ -250,39 +306,84 @@ pub mod android {
 
         fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
             let filename = path_file_name(path)?;
-            if filename != ADL::DB_FILENAME { bail!("File is not {}", ADL::DB_FILENAME); }
+            if filename != ADL::DB_FILENAME && encrypted_db_ext::<ADL>(filename).is_none() {
+                bail!("File is not {}", ADL::DB_FILENAME);
+            }
             Ok(())
         }
 
         fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
-            parse_android_db(self, feedback_client, path, ds)
+            super::load_in_memory(|sink| parse_android_db(self, feedback_client, path, ds, sink))
+        }
+
+        fn load_into_inner(&self,
+                           feedback_client: &dyn FeedbackClientSync,
+                           path: &Path,
+                           ds: Dataset,
+                           sink: &mut dyn ChatHistorySink) -> EmptyRes {
+            parse_android_db(self, feedback_client, path, ds, sink)
         }
     }
 
-    fn parse_android_db<ADL: AndroidDataLoader>(adl: &ADL, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+    /// Encrypted backup extension, if the file name is that of an encrypted database backup.
+    fn encrypted_db_ext<ADL: AndroidDataLoader>(filename: &str) -> Option<&'static str> {
+        ADL::ENCRYPTED_DB_EXTENSIONS.iter()
+            .find(|ext| filename.strip_prefix(ADL::DB_FILENAME)
+                .and_then(|s| s.strip_prefix('.')) == Some(ext))
+            .copied()
+    }
+
+    fn parse_android_db<ADL: AndroidDataLoader>(adl: &ADL,
+                                                feedback_client: &dyn FeedbackClientSync,
+                                                path: &Path,
+                                                ds: Dataset,
+                                                sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        // Declared before connection so that it's dropped after it
+        let decrypted_db_file = if encrypted_db_ext::<ADL>(path_file_name(path)?).is_some() {
+            feedback_client.set_load_status(LoadStatus::new_parsing("decrypting", Some(format!("{}", path.display()))));
+            let db_bytes = adl.decrypt_db(feedback_client, path)?;
+            // Temporary file is only accessible by the current user, and is removed once dropped.
+            // It's placed next to the backup, so that it doesn't end up in a shared temp directory.
+            let mut decrypted_db_file = tempfile::Builder::new()
+                .prefix(".decrypted_")
+                .suffix(&format!("_{}", ADL::DB_FILENAME))
+                .tempfile_in(path.parent().unwrap())?;
+            decrypted_db_file.write_all(&db_bytes)?;
+            decrypted_db_file.flush()?;
+            Some(decrypted_db_file)
+        } else {
+            None
+        };
+
         let path = path.parent().unwrap();
 
-        let conn = Connection::open(path.join(ADL::DB_FILENAME))?;
+        let conn = match decrypted_db_file {
+            Some(ref file) => Connection::open(file.path())?,
+            None => Connection::open(path.join(ADL::DB_FILENAME))?,
+        };
         adl.tweak_conn(&conn, feedback_client, path)?;
 
-        let path = if path_file_name(path)? == DATABASES {
+        let path = if path_file_name(path)?.eq_ignore_ascii_case(DATABASES) {
             path.parent().unwrap()
         } else {
             path
         };
 
         feedback_client.set_load_status(LoadStatus::new_parsing("file", Some(format!("{}", path.display()))));
-        let mut users = adl.parse_users(&conn, feedback_client, &ds.uuid, path)?;
-        let cwms = adl.parse_chats(&conn, feedback_client, &ds.uuid, path, &mut users)?;
-
-        let users = adl.normalize_users(feedback_client, users, &cwms)?;
-        Ok(Box::new(InMemoryDao::new_single(
-            format!("{} ({})", ADL::NAME, path_file_name(path)?),
-            ds,
-            path.to_path_buf(),
-            users[0].id(),
-            users,
-            cwms,
-        )))
+        let ds_uuid = ds.uuid.clone();
+        sink.begin_dataset(format!("{} ({})", ADL::NAME, path_file_name(path)?), ds, path)?;
+
+        let mut users = adl.parse_users(&conn, feedback_client, &ds_uuid, path)?;
+        let mut chats = vec![];
+        adl.parse_chats(&conn, feedback_client, &ds_uuid, path, &mut users, &mut |cwm| {
+            chats.push(cwm.chat.clone());
+            push_chat(sink, cwm.chat, cwm.messages)?;
+            Ok(())
+        })?;
+
+        let users = adl.normalize_users(feedback_client, users, &chats)?;
+        let myself_id = users[0].id();
+        sink.add_users(users, myself_id)?;
+        sink.finish_dataset()
     }
 }
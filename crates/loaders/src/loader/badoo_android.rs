@@ -111,7 +111,7 @@ impl<H: HttpClient> AndroidDataLoader for BadooAndroidDataLoader<'_, H> {
         &self,
         _feedback_client: &dyn FeedbackClientSync,
         users: Users,
-        _cwms: &[ChatWithMessages]
+        _chats: &[Chat]
     ) -> Result<Vec<User>> {
         let mut users = users.user_id_to_user.into_values().collect_vec();
         // Set myself to be a first member.
@@ -125,10 +125,9 @@ impl<H: HttpClient> AndroidDataLoader for BadooAndroidDataLoader<'_, H> {
         feedback_client: &dyn FeedbackClientSync,
         ds_uuid: &PbUuid,
         path: &Path,
-        users: &mut Users
-    ) -> Result<Vec<ChatWithMessages>> {
-        let mut cwms = vec![];
-
+        users: &mut Users,
+        push_cwm: &mut dyn FnMut(ChatWithMessages) -> EmptyRes,
+    ) -> EmptyRes {
         let downloaded_media_path = path.join(RELATIVE_MEDIA_DIR);
         fs::create_dir_all(&downloaded_media_path)?;
 
@@ -246,6 +245,9 @@ impl<H: HttpClient> AndroidDataLoader for BadooAndroidDataLoader<'_, H> {
                         forward_from_name_option: None,
                         reply_to_message_id_option,
                         contents,
+                        reactions: vec![],
+                        views_option: None,
+                        author_signature_option: None,
                     }
                 };
 
@@ -261,7 +263,7 @@ impl<H: HttpClient> AndroidDataLoader for BadooAndroidDataLoader<'_, H> {
             messages.iter_mut().enumerate().for_each(|(i, m)| m.internal_id = i as i64);
 
             if !messages.is_empty() {
-                cwms.push(ChatWithMessages {
+                push_cwm(ChatWithMessages {
                     chat: Chat {
                         ds_uuid: ds_uuid.clone(),
                         id: user.id,
@@ -274,10 +276,10 @@ impl<H: HttpClient> AndroidDataLoader for BadooAndroidDataLoader<'_, H> {
                         main_chat_id: None,
                     },
                     messages,
-                });
+                })?;
             }
         }
 
-        Ok(cwms)
+        Ok(())
     }
 }
@@ -90,6 +90,9 @@ fn loading_2026_05() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: Some(4313483375),
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[2], Message {
@@ -112,6 +115,9 @@ fn loading_2026_05() -> EmptyRes {
                         duration_sec_option: Some(23),
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[3], Message {
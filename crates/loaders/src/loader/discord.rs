@@ -0,0 +1,624 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+
+use chrono::DateTime;
+use lazy_static::lazy_static;
+use regex::Regex;
+use simd_json::borrowed::{Object, Value};
+use simd_json::prelude::*;
+
+use super::DataLoader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "discord_tests.rs"]
+mod tests;
+
+/// Loads JSON exports made by DiscordChatExporter (https://github.com/Tyrrrz/DiscordChatExporter).
+///
+/// Each exported file contains a single channel (or a single partition of it). All exports found in the same
+/// directory are loaded together as one dataset, partitions of the same channel are joined.
+///
+/// Exporter does not record who made the export, so user is asked to choose myself.
+pub struct DiscordDataLoader;
+
+const NAME: &str = "Discord";
+
+const JSON_EXT: &str = "json";
+
+/// File name of a voice message attachment, as named by Discord clients
+const VOICE_MESSAGE_FILE_NAME: &str = "voice-message.ogg";
+
+lazy_static! {
+    static ref MASKED_LINK_REGEX: Regex = Regex::new(r"^\[([^\]]+)\]\(<?(https?://[^)>\s]+)>?\)").unwrap();
+    static ref ANGLE_BRACKETED_URL_REGEX: Regex = Regex::new(r"^<(https?://[^>\s]+)>").unwrap();
+    static ref RAW_URL_REGEX: Regex = Regex::new(r"^https?://[^\s<]+[^\s<.,:;!?)\]'\x22]").unwrap();
+    static ref MENTION_REGEX: Regex = Regex::new(r"^<(@!?|@&|#)(\d+)>").unwrap();
+    static ref CUSTOM_EMOJI_REGEX: Regex = Regex::new(r"^<a?:(\w+):\d+>").unwrap();
+}
+
+impl DataLoader for DiscordDataLoader {
+    fn name(&self) -> String { NAME.to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        if path.extension().and_then(|ext| ext.to_str()) != Some(JSON_EXT) {
+            bail!("File is not a .{JSON_EXT} file");
+        }
+        if !is_exporter_json(path)? {
+            bail!("File is not a DiscordChatExporter JSON export");
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+        parse_discord_export(feedback_client, path, ds)
+    }
+}
+
+/// Exporter always writes an indented JSON starting with a guild object.
+fn is_exporter_json(path: &Path) -> Result<bool> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let first_line = lines.next().transpose()?.unwrap_or_default();
+    let second_line = lines.next().transpose()?.unwrap_or_default();
+    Ok(first_line.trim() == "{" && second_line.trim().starts_with(r#""guild":"#))
+}
+
+#[derive(Default)]
+struct Users {
+    id_to_user: HashMap<UserId, User>,
+}
+
+impl Users {
+    /// Registers a user from exporter's author/mention object, keeping the first seen version.
+    fn register(&mut self, json: &Object, path: &str, ds_uuid: &PbUuid, ds_root: &Path) -> Result<UserId> {
+        let id = UserId(parse_snowflake(get_field_str!(json, path, "id"))?);
+        if self.id_to_user.contains_key(&id) {
+            return Ok(id);
+        }
+        let name = get_field_string!(json, path, "name");
+        let username = match get_field_string_missing!(json, path, "discriminator") {
+            // Since 2023, Discord usernames are unique and have no discriminator, which is exported as "0000"
+            Some(discriminator) if discriminator != "0000" && discriminator != "0" => format!("{name}#{discriminator}"),
+            _ => name.clone(),
+        };
+        let nickname_option = get_field_string_missing!(json, path, "nickname");
+        let profile_pictures = get_field_string_missing!(json, path, "avatarUrl")
+            .and_then(|url| resolve_local_path(&url, ds_root))
+            .map(|path| vec![ProfilePicture { path, frame_option: None }])
+            .unwrap_or_default();
+        self.id_to_user.insert(id, User {
+            ds_uuid: ds_uuid.clone(),
+            id: *id,
+            first_name_option: Some(nickname_option.unwrap_or(name)),
+            last_name_option: None,
+            username_option: Some(username),
+            phone_number_option: None,
+            profile_pictures,
+        });
+        Ok(id)
+    }
+
+    fn pretty_name(&self, id: UserId) -> String {
+        self.id_to_user.get(&id).map(|u| u.pretty_name()).unwrap_or_else(|| format!("User {}", *id))
+    }
+}
+
+/// Channel collected from one or more exported files
+struct ChannelBuilder {
+    chat: Chat,
+    member_ids: HashSet<UserId>,
+    messages: Vec<Message>,
+}
+
+fn parse_discord_export(feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+    let ds_root = path.parent().context("File has no parent directory")?.to_path_buf();
+
+    let mut json_paths = vec![];
+    for entry in fs::read_dir(&ds_root)? {
+        let entry_path = entry?.path();
+        let is_json = entry_path.extension().and_then(|ext| ext.to_str()) == Some(JSON_EXT);
+        if entry_path.is_file() && is_json && is_exporter_json(&entry_path)? {
+            json_paths.push(entry_path);
+        }
+    }
+    json_paths.sort();
+
+    let mut users = Users::default();
+    let mut channels: Vec<ChannelBuilder> = vec![];
+    for json_path in json_paths.iter() {
+        feedback_client.set_load_status(LoadStatus::new_parsing("file", Some(format!("{}", json_path.display()))));
+        parse_file(json_path, &ds.uuid, &ds_root, &mut users, &mut channels)
+            .with_context(|| format!("Failed to parse {}", json_path.display()))?;
+    }
+
+    let mut users = users.id_to_user.into_values().sorted_by_key(|u| u.id).collect_vec();
+    ensure!(!users.is_empty(), "No users found in the export!");
+    let myself_idx = feedback_client.choose_myself(&users)?;
+    let myself = users.remove(myself_idx);
+    let myself_id = myself.id();
+    users.insert(0, myself);
+
+    let cwms = channels.into_iter().map(|mut cb| {
+        // Partitions might overlap
+        cb.messages.sort_by_key(|m| (m.timestamp, m.source_id_option));
+        cb.messages.dedup_by_key(|m| m.source_id_option);
+        cb.messages.iter_mut().enumerate().for_each(|(i, m)| m.internal_id = i as i64);
+
+        cb.member_ids.remove(&myself_id);
+        cb.chat.member_ids = std::iter::once(*myself_id).chain(cb.member_ids.iter().map(|id| **id).sorted()).collect_vec();
+        cb.chat.msg_count = cb.messages.len() as i32;
+        ChatWithMessages { chat: cb.chat, messages: cb.messages }
+    }).collect_vec();
+
+    Ok(Box::new(InMemoryDao::new_single(
+        format!("{NAME} ({})", path_file_name(&ds_root)?),
+        ds,
+        ds_root,
+        myself_id,
+        users,
+        cwms,
+    )))
+}
+
+fn parse_file(
+    path: &Path,
+    ds_uuid: &PbUuid,
+    ds_root: &Path,
+    users: &mut Users,
+    channels: &mut Vec<ChannelBuilder>,
+) -> EmptyRes {
+    let mut file_content = fs::read(path)?;
+    let parsed = simd_json::to_borrowed_value(&mut file_content)?;
+    let root_obj = as_object!(parsed, "<root>");
+
+    let guild = get_field_object!(root_obj, "<root>", "guild");
+    let channel = get_field_object!(root_obj, "<root>", "channel");
+
+    let chat_id = parse_snowflake(get_field_str!(channel, "channel", "id"))?;
+    let channel_type = get_field_str!(channel, "channel", "type");
+    let channel_name = get_field_string!(channel, "channel", "name");
+    let (tpe, name) = match channel_type {
+        "DirectTextChat" => (ChatType::Personal, channel_name),
+        "DirectGroupTextChat" => (ChatType::PrivateGroup, channel_name),
+        _ => (ChatType::PrivateGroup, format!("{} / {channel_name}", get_field_str!(guild, "guild", "name"))),
+    };
+    let img_path_option = get_field_string_missing!(guild, "guild", "iconUrl")
+        .and_then(|url| resolve_local_path(&url, ds_root));
+
+    let existing_idx = channels.iter().position(|cb| cb.chat.id == chat_id);
+    let cb_idx = match existing_idx {
+        Some(idx) => idx,
+        None => {
+            channels.push(ChannelBuilder {
+                chat: Chat {
+                    ds_uuid: ds_uuid.clone(),
+                    id: chat_id,
+                    name_option: Some(name),
+                    source_type: SourceType::Discord as i32,
+                    tpe: tpe as i32,
+                    img_path_option,
+                    member_ids: vec![], // Will be set later
+                    msg_count: 0, // Will be set later
+                    main_chat_id: None,
+                },
+                member_ids: HashSet::new(),
+                messages: vec![],
+            });
+            channels.len() - 1
+        }
+    };
+    let cb = &mut channels[cb_idx];
+
+    for (i, msg_json) in get_field_array!(root_obj, "<root>", "messages").iter().enumerate() {
+        let path = format!("messages[{i}]");
+        let msg_json = as_object!(msg_json, path);
+        if let Some(msg) = parse_message(msg_json, &path, ds_uuid, ds_root, users, &mut cb.member_ids)? {
+            cb.messages.push(msg);
+        }
+    }
+    Ok(())
+}
+
+fn parse_message(
+    json: &Object,
+    path: &str,
+    ds_uuid: &PbUuid,
+    ds_root: &Path,
+    users: &mut Users,
+    member_ids: &mut HashSet<UserId>,
+) -> Result<Option<Message>> {
+    let source_id = parse_snowflake(get_field_str!(json, path, "id"))?;
+    let from_id = users.register(get_field_object!(json, path, "author"), &format!("{path}.author"), ds_uuid, ds_root)?;
+    member_ids.insert(from_id);
+    let timestamp = parse_timestamp(get_field_str!(json, path, "timestamp"))?;
+
+    let mut mentions: HashMap<i64, String> = HashMap::new();
+    let mut mentioned_names = vec![];
+    if let Some(mentions_json) = json.get("mentions") {
+        for (i, mention) in as_array!(mentions_json, path, "mentions").iter().enumerate() {
+            let mention_path = format!("{path}.mentions[{i}]");
+            let id = users.register(as_object!(mention, mention_path), &mention_path, ds_uuid, ds_root)?;
+            member_ids.insert(id);
+            let name = users.pretty_name(id);
+            mentions.insert(*id, name.clone());
+            mentioned_names.push(name);
+        }
+    }
+
+    let content = get_field_string_missing!(json, path, "content").unwrap_or_default();
+    let reference_id_option = match json.get("reference") {
+        Some(Value::Object(reference)) => get_field_string_missing!(reference, path, "messageId")
+            .map(|id| parse_snowflake(&id)).transpose()?,
+        _ => None,
+    };
+
+    let mut text = parse_markdown(&content, &mentions);
+
+    let tpe = get_field_str!(json, path, "type");
+    let typed = match tpe {
+        "RecipientAdd" => message_service!(ServiceSvo::GroupInviteMembers(MessageServiceGroupInviteMembers {
+            members: mentioned_names,
+        })),
+        "GuildMemberJoin" => message_service!(ServiceSvo::GroupInviteMembers(MessageServiceGroupInviteMembers {
+            members: vec![users.pretty_name(from_id)],
+        })),
+        "RecipientRemove" => message_service!(ServiceSvo::GroupRemoveMembers(MessageServiceGroupRemoveMembers {
+            members: if mentioned_names.is_empty() { vec![users.pretty_name(from_id)] } else { mentioned_names },
+        })),
+        "Call" => {
+            let ended_timestamp_option = get_field_string_missing!(json, path, "callEndedTimestamp")
+                .map(|ts| parse_timestamp(&ts)).transpose()?;
+            message_service!(ServiceSvo::PhoneCall(MessageServicePhoneCall {
+                duration_sec_option: ended_timestamp_option.map(|ended| (ended - timestamp) as i32),
+                discard_reason_option: None,
+                members: mentioned_names,
+            }))
+        }
+        "ChannelNameChange" => {
+            // Content holds the new name
+            let title = std::mem::take(&mut text).iter().filter_map(|rte| rte.get_text()).join("");
+            message_service!(ServiceSvo::GroupEditTitle(MessageServiceGroupEditTitle { title }))
+        }
+        "ChannelIconChange" => message_service!(ServiceSvo::GroupEditPhoto(MessageServiceGroupEditPhoto {
+            photo: ContentPhoto { path_option: None, width: 0, height: 0, mime_type_option: None, is_one_time: false },
+        })),
+        "ChannelPinnedMessage" => {
+            let Some(message_source_id) = reference_id_option else {
+                log::warn!("{path}: pinned message reference is missing, skipping");
+                return Ok(None);
+            };
+            text.clear();
+            message_service!(ServiceSvo::PinMessage(MessageServicePinMessage { message_source_id }))
+        }
+        "ThreadCreated" => {
+            text.insert(0, RichText::make_plain("Started a thread: ".to_owned()));
+            message_service!(ServiceSvo::Notice(MessageServiceNotice {}))
+        }
+        _ => {
+            if tpe != "Default" && tpe != "Reply" {
+                log::debug!("{path}: unsupported message type {tpe}, treating as a regular message");
+            }
+
+            let edit_timestamp_option = get_field_string_missing!(json, path, "timestampEdited")
+                .map(|ts| parse_timestamp(&ts)).transpose()?;
+
+            let mut contents = vec![];
+            if let Some(attachments) = json.get("attachments") {
+                for (i, attachment) in as_array!(attachments, path, "attachments").iter().enumerate() {
+                    let attachment_path = format!("{path}.attachments[{i}]");
+                    contents.push(parse_attachment(as_object!(attachment, attachment_path), &attachment_path, ds_root)?);
+                }
+            }
+            if let Some(stickers) = json.get("stickers") {
+                for (i, sticker) in as_array!(stickers, path, "stickers").iter().enumerate() {
+                    let sticker_path = format!("{path}.stickers[{i}]");
+                    contents.push(parse_sticker(as_object!(sticker, sticker_path), &sticker_path, ds_root)?);
+                }
+            }
+
+            if let Some(embeds) = json.get("embeds") {
+                for (i, embed) in as_array!(embeds, path, "embeds").iter().enumerate() {
+                    let embed_path = format!("{path}.embeds[{i}]");
+                    let embed = as_object!(embed, embed_path);
+                    let Some(href) = get_field_string_missing!(embed, embed_path, "url") else { continue };
+                    let already_linked = text.iter().any(|rte| matches!(
+                        rte.val, Some(rich_text_element::Val::Link(ref link)) if link.href == href
+                    ));
+                    if !already_linked {
+                        // Embed without a title is just a preview
+                        text.push(RichText::make_link(get_field_string_missing!(embed, embed_path, "title"), href));
+                    }
+                }
+            }
+
+            let mut reactions = vec![];
+            if let Some(reactions_json) = json.get("reactions") {
+                for (i, reaction) in as_array!(reactions_json, path, "reactions").iter().enumerate() {
+                    let reaction_path = format!("{path}.reactions[{i}]");
+                    reactions.push(parse_reaction(as_object!(reaction, reaction_path), &reaction_path, ds_uuid, ds_root, users)?);
+                }
+            }
+
+            message_regular! {
+                edit_timestamp_option,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: if tpe == "Reply" { reference_id_option } else { None },
+                contents,
+                reactions,
+                views_option: None,
+                author_signature_option: None,
+            }
+        }
+    };
+
+    let text = super::normalize_rich_text(text);
+    Ok(Some(Message::new(
+        *NO_INTERNAL_ID, // Will be set later
+        Some(source_id),
+        timestamp,
+        from_id,
+        text,
+        typed,
+    )))
+}
+
+fn parse_attachment(json: &Object, path: &str, ds_root: &Path) -> Result<Content> {
+    let url = get_field_str!(json, path, "url");
+    let file_name = get_field_string!(json, path, "fileName");
+    let path_option = resolve_local_path(url, ds_root);
+    let file_name_option = Some(file_name.clone());
+    let mime_type_option = mime_type_by_file_name(&file_name);
+
+    Ok(match mime_type_option {
+        Some(mime_type) if mime_type.starts_with("image/") => content!(Photo {
+            path_option,
+            width: 0,
+            height: 0,
+            mime_type_option: Some(mime_type.to_owned()),
+            is_one_time: false,
+        }),
+        Some(mime_type) if mime_type.starts_with("video/") => content!(Video {
+            path_option,
+            file_name_option,
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: mime_type.to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+            is_one_time: false,
+        }),
+        Some(mime_type) if file_name == VOICE_MESSAGE_FILE_NAME => content!(VoiceMsg {
+            path_option,
+            file_name_option,
+            mime_type: mime_type.to_owned(),
+            duration_sec_option: None,
+        }),
+        Some(mime_type) if mime_type.starts_with("audio/") => content!(Audio {
+            path_option,
+            file_name_option,
+            title_option: None,
+            performer_option: None,
+            mime_type: mime_type.to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+        }),
+        _ => content!(File {
+            path_option,
+            file_name_option,
+            mime_type_option: mime_type_option.map(|m| m.to_owned()),
+            thumbnail_path_option: None,
+        }),
+    })
+}
+
+fn parse_sticker(json: &Object, path: &str, ds_root: &Path) -> Result<Content> {
+    let mime_type = match get_field_str!(json, path, "format") {
+        "Png" | "PngAnimated" | "Apng" => "image/png",
+        "Gif" => "image/gif",
+        "Lottie" => "application/json",
+        other => bail!("{path}: unknown sticker format {other}"),
+    };
+    let path_option = get_field_string_missing!(json, path, "sourceUrl")
+        .and_then(|url| resolve_local_path(&url, ds_root));
+    Ok(content!(Sticker {
+        path_option,
+        file_name_option: get_field_string_missing!(json, path, "name"),
+        width: 0,
+        height: 0,
+        mime_type_option: Some(mime_type.to_owned()),
+        thumbnail_path_option: None,
+        emoji_option: None,
+    }))
+}
+
+fn parse_reaction(json: &Object, path: &str, ds_uuid: &PbUuid, ds_root: &Path, users: &mut Users) -> Result<Reaction> {
+    let emoji = get_field_object!(json, path, "emoji");
+    let emoji_path = format!("{path}.emoji");
+    // Standard emojis have no ID
+    let (emoji_option, custom_emoji_id_option) = match get_field_string_missing!(emoji, emoji_path, "id") {
+        Some(id) => (None, Some(id)),
+        None => (Some(get_field_string!(emoji, emoji_path, "name")), None),
+    };
+    let mut from_ids = vec![];
+    if let Some(reaction_users) = json.get("users") {
+        for (i, user) in as_array!(reaction_users, path, "users").iter().enumerate() {
+            let user_path = format!("{path}.users[{i}]");
+            from_ids.push(*users.register(as_object!(user, user_path), &user_path, ds_uuid, ds_root)?);
+        }
+    }
+    Ok(Reaction {
+        emoji_option,
+        custom_emoji_id_option,
+        from_ids,
+        count: get_field_i32!(json, path, "count"),
+    })
+}
+
+/// Converts Discord markdown into rich text.
+/// Rich text is flat, so formatting nested inside another formatting is reduced to plain text.
+fn parse_markdown(src: &str, mentions: &HashMap<i64, String>) -> Vec<RichTextElement> {
+    let mut result = vec![];
+    let mut plain = String::new();
+
+    macro_rules! flush_plain {
+        () => {
+            if !plain.is_empty() {
+                result.push(RichText::make_plain(std::mem::take(&mut plain)));
+            }
+        };
+    }
+
+    // Returns text between `marker` at the beginning of `s` and the next `marker`, if any
+    fn enclosed<'a>(s: &'a str, marker: &str) -> Option<&'a str> {
+        let rest = s.strip_prefix(marker)?;
+        let end = rest.find(marker)?;
+        (end > 0).then(|| &rest[..end])
+    }
+
+    fn strip_formatting(s: &str, mentions: &HashMap<i64, String>) -> String {
+        parse_markdown(s, mentions).iter().filter_map(|rte| rte.get_text()).join("")
+    }
+
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        let at_line_start = src.len() == rest.len() || src[..(src.len() - rest.len())].ends_with('\n');
+        if at_line_start && let Some(quote) = rest.strip_prefix(">>> ") {
+            flush_plain!();
+            result.push(RichText::make_blockquote(strip_formatting(quote, mentions)));
+            rest = "";
+        } else if at_line_start && let Some(quote) = rest.strip_prefix("> ") {
+            flush_plain!();
+            let end = quote.find('\n').unwrap_or(quote.len());
+            result.push(RichText::make_blockquote(strip_formatting(&quote[..end], mentions)));
+            rest = &quote[end..];
+        } else if let Some(block) = enclosed(rest, "```") {
+            flush_plain!();
+            let (language_option, code) = match block.split_once('\n') {
+                Some((lang, code)) if !lang.is_empty() && lang.chars().all(|c| c.is_alphanumeric() || "+-#_".contains(c)) =>
+                    (Some(lang.to_owned()), code),
+                _ => (None, block.strip_prefix('\n').unwrap_or(block)),
+            };
+            result.push(RichText::make_prefmt_block(code.to_owned(), language_option));
+            rest = &rest[(block.len() + 6)..];
+        } else if let Some(code) = enclosed(rest, "`") {
+            flush_plain!();
+            result.push(RichText::make_prefmt_inline(code.to_owned()));
+            rest = &rest[(code.len() + 2)..];
+        } else if let Some((marker, inner)) = ["||", "**", "__", "~~", "*", "_"].iter()
+            .filter(|m| **m != "_" || !plain.ends_with(|c: char| c.is_alphanumeric()))
+            .find_map(|m| enclosed(rest, m).map(|inner| (*m, inner)))
+            .filter(|(_, inner)| !inner.starts_with(char::is_whitespace) && !inner.ends_with(char::is_whitespace))
+        {
+            flush_plain!();
+            let text = strip_formatting(inner, mentions);
+            result.push(match marker {
+                "||" => RichText::make_spoiler(text),
+                "**" => RichText::make_bold(text),
+                "__" => RichText::make_underline(text),
+                "~~" => RichText::make_strikethrough(text),
+                _ => RichText::make_italic(text),
+            });
+            rest = &rest[(inner.len() + marker.len() * 2)..];
+        } else if let Some(capt) = MASKED_LINK_REGEX.captures(rest) {
+            flush_plain!();
+            result.push(RichText::make_link(Some(capt[1].to_owned()), capt[2].to_owned()));
+            rest = &rest[capt[0].len()..];
+        } else if let Some(capt) = ANGLE_BRACKETED_URL_REGEX.captures(rest) {
+            flush_plain!();
+            result.push(RichText::make_link(Some(capt[1].to_owned()), capt[1].to_owned()));
+            rest = &rest[capt[0].len()..];
+        } else if let Some(m) = RAW_URL_REGEX.find(rest).filter(|_| !plain.ends_with(|c: char| c.is_alphanumeric())) {
+            flush_plain!();
+            result.push(RichText::make_link(Some(m.as_str().to_owned()), m.as_str().to_owned()));
+            rest = &rest[m.end()..];
+        } else if let Some(capt) = MENTION_REGEX.captures(rest) {
+            let id: i64 = capt[2].parse().unwrap_or_default();
+            match &capt[1] {
+                "#" => plain.push_str(&format!("#{id}")),
+                "@&" => plain.push_str("@role"),
+                _ => plain.push_str(&format!("@{}", mentions.get(&id).cloned().unwrap_or_else(|| id.to_string()))),
+            }
+            rest = &rest[capt[0].len()..];
+        } else if let Some(capt) = CUSTOM_EMOJI_REGEX.captures(rest) {
+            plain.push_str(&format!(":{}:", &capt[1]));
+            rest = &rest[capt[0].len()..];
+        } else if let Some(escaped) = rest.strip_prefix('\\').and_then(|s| s.chars().next()).filter(|c| c.is_ascii_punctuation()) {
+            plain.push(escaped);
+            rest = &rest[(1 + escaped.len_utf8())..];
+        } else {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    flush_plain!();
+    result
+}
+
+/// Exporter downloads media (if asked to) next to the JSON files and references them by relative paths,
+/// otherwise URLs point to Discord CDN.
+fn resolve_local_path(url: &str, ds_root: &Path) -> Option<String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return None;
+    }
+    let decoded = percent_decode(url);
+    [url, decoded.as_str()].into_iter()
+        .map(|p| ds_root.join(p))
+        .find(|p| p.exists())
+        .and_then(|p| p.strip_prefix(ds_root).ok().and_then(|p| p.to_str()).map(|p| p.replace('\\', "/")))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get((i + 1)..(i + 3)).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                res.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                res.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+pub(super) fn mime_type_by_file_name(file_name: &str) -> Option<&'static str> {
+    let ext = file_name.rsplit_once('.')?.1.to_lowercase();
+    Some(match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "mp3" => "audio/mpeg",
+        "ogg" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "m4a" => "audio/mp4",
+        "flac" => "audio/flac",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "txt" => "text/plain",
+        _ => return None,
+    })
+}
+
+fn parse_snowflake(s: &str) -> Result<i64> {
+    s.parse::<i64>().with_context(|| format!("Invalid ID: {s}"))
+}
+
+/// Timestamps are exported in ISO 8601 format with an offset, e.g. `2026-10-01T12:34:56.789+02:00`
+fn parse_timestamp(s: &str) -> Result<i64> {
+    Ok(DateTime::parse_from_rfc3339(s).with_context(|| format!("Invalid timestamp: {s}"))?.timestamp())
+}
@@ -0,0 +1,262 @@
+#![allow(unused_imports)]
+
+use super::*;
+
+use crate::entity_utils::*;
+use chat_history_manager_core::protobuf::history::content::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::message::*;
+use chat_history_manager_core::protobuf::history::message_service::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::User;
+use chat_history_manager_dao::ChatHistoryDao;
+
+use chrono::FixedOffset;
+use pretty_assertions::{assert_eq, assert_ne};
+
+const LOADER: DiscordDataLoader = DiscordDataLoader;
+
+const ALICE_ID: i64 = 100000000000000001;
+const BOB_ID: i64 = 100000000000000002;
+const CAROL_ID: i64 = 100000000000000003;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2026_10() -> EmptyRes {
+    let res = resource("discord_2026-10/Test Guild - Text Channels - general [900000000000000010].json");
+    LOADER.looks_about_right(&res)?;
+
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: Some(ALICE_ID), text: None };
+    let dao = LOADER.load(&feedback_client, &res)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, ALICE_ID);
+
+    let user = |id: i64, first_name: &str, username: &str, profile_pictures: Vec<ProfilePicture>| User {
+        ds_uuid: ds_uuid.clone(),
+        id,
+        first_name_option: Some(first_name.to_owned()),
+        last_name_option: None,
+        username_option: Some(username.to_owned()),
+        phone_number_option: None,
+        profile_pictures,
+    };
+    assert_eq!(dao.users_single_ds(), vec![
+        user(ALICE_ID, "Alice A", "alice", vec![]),
+        user(BOB_ID, "Bob", "bob#1234", vec![ProfilePicture { path: "general_Files/avatar-bob.png".to_owned(), frame_option: None }]),
+        user(CAROL_ID, "Carol", "carol", vec![]),
+    ]);
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 2);
+
+    // Direct messages
+    {
+        let chat = &cwms[0].chat;
+        assert_eq!(chat, &Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 900000000000000020,
+            name_option: Some("bob".to_owned()),
+            source_type: SourceType::Discord as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![ALICE_ID, BOB_ID],
+            msg_count: 2,
+            main_chat_id: None,
+        });
+
+        let msgs = dao.first_messages(chat, 99999)?;
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].typed(), &message_service!(PhoneCall(MessageServicePhoneCall {
+            duration_sec_option: Some(65),
+            discard_reason_option: None,
+            members: vec![],
+        })));
+        assert_eq!(msgs[1].text, vec![
+            RichText::make_blockquote("quoted text".to_owned()),
+            RichText::make_plain("\nreply".to_owned()),
+        ]);
+    }
+
+    // Guild channel, loaded from two overlapping partitions
+    {
+        let chat = &cwms[1].chat;
+        assert_eq!(chat, &Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 900000000000000010,
+            name_option: Some("Test Guild / general".to_owned()),
+            source_type: SourceType::Discord as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![ALICE_ID, BOB_ID, CAROL_ID],
+            msg_count: 6,
+            main_chat_id: None,
+        });
+
+        let msgs = dao.first_messages(chat, 99999)?;
+        assert_eq!(msgs.iter().map(|m| m.source_id_option.unwrap()).collect_vec(),
+                   vec![1001, 1002, 1003, 1004, 1005, 1006]);
+        assert_eq!(msgs.iter().map(|m| m.internal_id).collect_vec(), (0..6).collect_vec());
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            Some(1001),
+            ts("2026-10-01 12:00:00"),
+            UserId(ALICE_ID),
+            vec![
+                RichText::make_plain("Hello ".to_owned()),
+                RichText::make_bold("bold".to_owned()),
+                RichText::make_plain(" ".to_owned()),
+                RichText::make_italic("it".to_owned()),
+                RichText::make_plain(" ".to_owned()),
+                RichText::make_strikethrough("s".to_owned()),
+                RichText::make_plain(" ".to_owned()),
+                RichText::make_spoiler("sp".to_owned()),
+                RichText::make_plain(" ".to_owned()),
+                RichText::make_prefmt_inline("code".to_owned()),
+                RichText::make_plain(", see ".to_owned()),
+                RichText::make_link(Some("https://example.com/x".to_owned()), "https://example.com/x".to_owned()),
+                RichText::make_plain(".".to_owned()),
+            ],
+            MESSAGE_REGULAR_NO_CONTENT.clone(),
+        ));
+
+        assert_eq!(msgs[1], Message::new(
+            1,
+            Some(1002),
+            ts("2026-10-01 12:01:00"),
+            UserId(BOB_ID),
+            vec![
+                RichText::make_plain("@Carol look ".to_owned()),
+                RichText::make_link(Some("here".to_owned()), "https://example.org".to_owned()),
+                RichText::make_plain(" :pepe:".to_owned()),
+            ],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: Some(1001),
+                contents: vec![content!(Photo {
+                    path_option: Some("general_Files/my image-1A2B.png".to_owned()),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: Some("image/png".to_owned()),
+                    is_one_time: false,
+                })],
+                reactions: vec![
+                    Reaction {
+                        emoji_option: Some("👍".to_owned()),
+                        custom_emoji_id_option: None,
+                        from_ids: vec![ALICE_ID, BOB_ID],
+                        count: 2,
+                    },
+                    Reaction {
+                        emoji_option: None,
+                        custom_emoji_id_option: Some("777".to_owned()),
+                        from_ids: vec![],
+                        count: 1,
+                    },
+                ],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+
+        assert_eq!(msgs[2].typed(), &message_service!(PinMessage(MessageServicePinMessage { message_source_id: 1001 })));
+        assert_eq!(msgs[2].text, vec![]);
+
+        assert_eq!(msgs[3], Message::new(
+            3,
+            Some(1004),
+            ts("2026-10-01 12:03:00"),
+            UserId(ALICE_ID),
+            vec![RichText::make_prefmt_block("fn main() {}\n".to_owned(), Some("rust".to_owned()))],
+            message_regular! {
+                edit_timestamp_option: Some(ts("2026-10-01 12:04:30")),
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![
+                    content!(VoiceMsg {
+                        path_option: None,
+                        file_name_option: Some("voice-message.ogg".to_owned()),
+                        mime_type: "audio/ogg".to_owned(),
+                        duration_sec_option: None,
+                    }),
+                    content!(File {
+                        path_option: None,
+                        file_name_option: Some("notes.docx".to_owned()),
+                        mime_type_option: None,
+                        thumbnail_path_option: None,
+                    }),
+                    content!(Sticker {
+                        path_option: None,
+                        file_name_option: Some("Wave".to_owned()),
+                        width: 0,
+                        height: 0,
+                        mime_type_option: Some("application/json".to_owned()),
+                        thumbnail_path_option: None,
+                        emoji_option: None,
+                    }),
+                ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+
+        assert_eq!(msgs[4].from_id, CAROL_ID);
+        assert_eq!(msgs[4].typed(), &message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
+            members: vec!["Carol".to_owned()],
+        })));
+
+        assert_eq!(msgs[5].typed(), &message_service!(GroupEditTitle(MessageServiceGroupEditTitle {
+            title: "general-chat".to_owned(),
+        })));
+        assert_eq!(msgs[5].text, vec![]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rejecting_other_json() -> EmptyRes {
+    let res = resource("telegram_2026-10_reactions/result.json");
+    assert!(LOADER.looks_about_right(&res).is_err());
+    Ok(())
+}
+
+#[test]
+fn markdown() {
+    let mentions = HashMap::from([(42, "Someone".to_owned())]);
+    let parse = |s: &str| super::super::normalize_rich_text(parse_markdown(s, &mentions));
+
+    assert_eq!(parse("2 * 3 * 4 and snake_case_name"), vec![
+        RichText::make_plain("2 * 3 * 4 and snake_case_name".to_owned()),
+    ]);
+    assert_eq!(parse(r"\*not italic\* <@!42> <@&7> <#8>"), vec![
+        RichText::make_plain("*not italic* @Someone @role #8".to_owned()),
+    ]);
+    assert_eq!(parse("**bold _nested_** <https://a.b/c>"), vec![
+        RichText::make_bold("bold nested".to_owned()),
+        RichText::make_plain(" ".to_owned()),
+        RichText::make_link(Some("https://a.b/c".to_owned()), "https://a.b/c".to_owned()),
+    ]);
+    assert_eq!(parse("text\n>>> multi\nline **quote**"), vec![
+        RichText::make_plain("text\n".to_owned()),
+        RichText::make_blockquote("multi\nline quote".to_owned()),
+    ]);
+    assert_eq!(parse("```\nno language\n```"), vec![
+        RichText::make_prefmt_block("no language\n".to_owned(), None),
+    ]);
+}
+
+//
+// Helpers
+//
+
+fn ts(s: &str) -> i64 {
+    dt(s, Some(&FixedOffset::east_opt(0).unwrap())).timestamp()
+}
@@ -6,7 +6,7 @@ use std::fs;
 use base64::prelude::*;
 use cbc::cipher::{BlockDecryptMut, KeyIvInit};
 use hmac::Mac;
-use rusqlite::{Connection, Error, ErrorCode};
+use rusqlite::{Connection, Error, ErrorCode, Row};
 use simd_json::base::*;
 use simd_json::borrowed::{Object, Value};
 use simd_json::derived::*;
@@ -141,12 +141,14 @@ fn parse_users(conn: &Connection, ds_uuid: &PbUuid) -> Result<Users> {
     let mut rows = stmt.query([])?;
 
     while let Some(row) = rows.next()? {
-        // "serviceId" was called "uuid" before Signal v7 (in v6 for sure)
-        let uuid =
-            row.get::<_, String>("serviceId")
-                .or(row.get::<_, String>("uuid"))
-                .map_err(|_| anyhow!(r##"Neither "serviceId" nor "uuid" column found in table "conversations""##))?;
-        let uuid = Uuid::parse_str(&uuid)?;
+        let tpe = row.get::<_, String>("type")?;
+        match tpe.as_str() {
+            PRIVATE_TYPE => {}
+            GROUP_TYPE => continue, // Group members are regular private conversations too
+            _ => bail!("Unknown conversation type: {tpe}"),
+        }
+
+        let uuid = get_service_id(row)?;
         let id = UserId(uuid_to_i64_pos(uuid)?);
 
         let first_name_option = row.get::<_, Option<String>>("profileName")?;
@@ -164,10 +166,6 @@ fn parse_users(conn: &Connection, ds_uuid: &PbUuid) -> Result<Users> {
             profile_pictures: vec![],
         };
 
-        // TODO: group chats
-        let tpe = row.get::<_, String>("type")?;
-        ensure!(tpe == "private", "Only 1-to-1 chats are supported, {} is {tpe}", user.pretty_name());
-
         assert_eq!(users.insert(uuid, user), None, "Duplicate user UUID: {uuid}");
     }
 
@@ -186,10 +184,12 @@ fn parse_cwms(
 ) -> Result<Vec<ChatWithMessages>> {
     let mut cwms = vec![];
 
-    // NOTE: Non-private conversation types (group chats?) are not supported, and it's checked in `parse_users`
-    let mut conv_stmt = conn.prepare(r"SELECT * FROM conversations WHERE type = 'private'")?;
+    let mut conv_stmt = conn.prepare(r"SELECT * FROM conversations WHERE type IN ('private', 'group')")?;
     let mut msg_stmt = conn.prepare(r"SELECT * FROM messages WHERE conversationId = ? ORDER BY sent_at ASC, rowid asc")?;
 
+    // Reactions reference their authors by conversation ID rather than by user UUID
+    let conversation_user_ids = parse_conversation_user_ids(conn, users)?;
+
     // Call details were embedded in JSON in Signal v6, but in v7 they're in separate table
     let mut calls_stmt = conn.prepare(r"SELECT * FROM callsHistory WHERE callId = ?").ok();
 
@@ -197,53 +197,69 @@ fn parse_cwms(
     while let Some(row) = conv_rows.next()? {
         let chat_uuid_string = row.get::<_, String>("id")?;
         let chat_uuid = Uuid::parse_str(&chat_uuid_string)?;
-        let chat_id = ChatId(uuid_to_i64_pos(chat_uuid)?);
-
-        // "serviceId" was called "uuid" before Signal v7 (in v6 for sure)
-        let user_uuid =
-            row.get::<_, String>("serviceId")
-                .or(row.get::<_, String>("uuid"))
-                .map_err(|_| anyhow!(r##"Neither "serviceId" nor "uuid" column found in table "conversations""##))?;
-        let user_uuid = Uuid::parse_str(&user_uuid)?;
-        let user = users.get(&user_uuid).ok_or_else(|| anyhow!("Unknown user"))?;
-        let member_ids = if user.id == *myself_id {
-            vec![*myself_id]
-        } else {
-            vec![*myself_id, user.id]
-        };
 
-        feedback_client.set_load_status(LoadStatus::new_parsing("chat with", Some(user.pretty_name())));
+        // Peer is only defined for private chats
+        let (chat_id, peer_option, chat_type, name_option, mut member_ids) =
+            if row.get::<_, String>("type")? == PRIVATE_TYPE {
+                let user_uuid = get_service_id(row)?;
+                let user = users.get(&user_uuid).ok_or_else(|| anyhow!("Unknown user"))?;
+                let member_ids = if user.id == *myself_id {
+                    vec![*myself_id]
+                } else {
+                    vec![*myself_id, user.id]
+                };
+                feedback_client.set_load_status(LoadStatus::new_parsing("chat with", Some(user.pretty_name())));
+                (user.id, Some(user), ChatType::Personal, user.first_name_option.clone(), member_ids)
+            } else {
+                let name_option = row.get::<_, Option<String>>("name")?;
+                let member_ids = parse_group_member_ids(row, users, myself_id)?;
+                // Legacy (v1) group IDs are not base64-encoded, such groups can't be matched with other clients anyway
+                let group_id_option = row.get::<_, Option<String>>("groupId").ok().flatten()
+                    .and_then(|group_id| BASE64_STANDARD.decode(group_id).ok());
+                let chat_id = match group_id_option {
+                    Some(group_id) => group_chat_id(&group_id),
+                    None => uuid_to_i64_pos(chat_uuid)?,
+                };
+                feedback_client.set_load_status(LoadStatus::new_parsing("group chat", name_option.clone()));
+                (chat_id, None, ChatType::PrivateGroup, name_option, member_ids)
+            };
 
         let mut messages: Vec<Message> = vec![];
+        let mut source_ids = SourceIds::default();
 
         let mut msg_rows = msg_stmt.query([chat_uuid_string])?;
 
-        // TODO: rich text
-        // TODO: forwards
+        // Note: Signal does not record forwards in any way, a forwarded message is just a new message.
 
         while let Some(row) = msg_rows.next()? {
             let source_uuid = row.get::<_, String>("id")?;
             let source_uuid = Uuid::parse_str(&source_uuid)?;
-            let source_id = uuid_to_i64_pos(source_uuid)?;
 
             let direction = row.get::<_, String>("type")?;
 
             let mut service_option = None;
 
-            let mut text = if let Some(text) = row.get::<_, Option<String>>("body")? {
-                vec![RichText::make_plain(text)]
-            } else {
-                vec![]
-            };
-
             // Parsing JSON unconditionally is expensive but there's no way to get e.g. reply-to message ID without it
             let json = row.get::<_, String>("json")?;
             let mut json = json.into_bytes();
             let json = simd_json::to_borrowed_value(&mut json)?;
             let json = as_object!(json, "json");
 
+            let mut text = if let Some(body) = row.get::<_, Option<String>>("body")? {
+                parse_rich_text(&body, json.get(BODY_RANGES_KEY), users)?
+            } else {
+                vec![]
+            };
+
+            // In group chats, message author is stored in the message itself
+            let sender_id_option = match peer_option {
+                Some(peer) => Some(peer.id()),
+                None => get_message_author(row, users)?,
+            };
+            let get_sender_id = || sender_id_option.ok_or_else(|| anyhow!("Unknown author of message {source_uuid}"));
+
             let from_id = match direction.as_str() {
-                "incoming" => user.id(),
+                "incoming" => get_sender_id()?,
                 "outgoing" => myself_id,
                 "call-history" => {
                     let call_id = row.get::<_, String>("callId")?;
@@ -253,17 +269,27 @@ fn parse_cwms(
                             let mut call_row = calls_stmt.query([call_id])?;
                             let call_row = call_row.next()?.ok_or_else(|| anyhow!("Call not found"))?;
                             let call_direction = call_row.get::<_, String>("direction")?;
-                            let from_id = match call_direction.as_str() {
-                                "Incoming" => user.id(),
-                                "Outgoing" => myself_id,
+                            let from_id = match (call_direction.as_str(), peer_option) {
+                                ("Incoming", Some(peer)) => peer.id(),
+                                ("Incoming", None) => {
+                                    // Group call initiator
+                                    let ringer_id = call_row.get::<_, Option<String>>("ringerId")?
+                                        .or(call_row.get::<_, Option<String>>("startedById")?);
+                                    match ringer_id {
+                                        Some(ringer_id) => user_by_service_id(&ringer_id, users)?.id(),
+                                        None => get_sender_id()?,
+                                    }
+                                }
+                                ("Outgoing", _) => myself_id,
                                 _ => bail!("Unknown call direction: {call_direction}"),
                             };
 
                             let discard_reason = call_row.get::<_, String>("status")?;
                             let discard_reason = match discard_reason.as_str() {
-                                "Accepted" => "hangup",
+                                "Accepted" | "Joined" => "hangup",
                                 "Declined" => "declined",
-                                "Missed" => "missed",
+                                // Group call that we haven't joined
+                                "Missed" | "GenericGroupCall" => "missed",
                                 _ => bail!("Unknown call discard reason: {discard_reason}"),
                             };
 
@@ -272,7 +298,7 @@ fn parse_cwms(
                             let details = get_field_object!(json, "<root>", DETAILS_KEY);
                             let was_incoming = get_field_bool!(details, DETAILS_KEY, "wasIncoming");
 
-                            let from_id = if was_incoming { user.id() } else { myself_id };
+                            let from_id = if was_incoming { get_sender_id()? } else { myself_id };
 
                             let discard_reason = if get_field_bool!(details, DETAILS_KEY, "wasDeclined") {
                                 "declined"
@@ -307,16 +333,43 @@ fn parse_cwms(
                     text = vec!(RichText::make_plain(format!("{old_name} changed name to {new_name}")));
                     service_option = Some(message_service!(ServiceSvo::Notice(MessageServiceNotice {})));
 
-                    user.id()
+                    // In group chats, this is the only way to know whose profile has changed
+                    if let Some(changed_id) = json.get("changedId") {
+                        let changed_id = Uuid::parse_str(as_str!(changed_id, "changedId"))?;
+                        *conversation_user_ids.get(&changed_id)
+                            .ok_or_else(|| anyhow!("Unknown profile change conversation {changed_id}"))?
+                    } else {
+                        get_sender_id()?
+                    }
+                }
+                "group-v2-change" => {
+                    let change = get_field_object!(json, "<root>", GROUP_V2_CHANGE_KEY);
+                    let Some((service, affected_id_option)) =
+                        parse_group_v2_change(change, users, name_option.as_deref())? else {
+                        continue; // Permissions, description, admins, etc. - not interesting
+                    };
+                    service_option = Some(message_service!(service));
+
+                    // Author is not always known, e.g. when someone joins via invite link
+                    match change.get("from") {
+                        Some(from) => user_by_service_id(as_str!(from, GROUP_V2_CHANGE_KEY, "from"), users)?.id(),
+                        None => affected_id_option.unwrap_or(myself_id),
+                    }
                 }
                 "keychange" => continue, // Not interesting, also not shown in Signal client
                 "verified-change" => continue, // Not interesting
+                "timer-notification" | "universal-timer-notification" => continue, // Not interesting
                 _ => bail!("Unknown message direction: {direction}"),
             };
 
             // Note: This is timestamp in millis, not in seconds! This is needed to resolve replies, and is
             // divided by 1000 further down.
-            let timestamp_ms = get_field_i64!(json, "<root>", "timestamp");
+            let timestamp_ms = if let Some(timestamp) = json.get("timestamp") {
+                as_i64!(timestamp, "timestamp")
+            } else {
+                // Some service messages do not have it
+                row.get::<_, i64>("sent_at")?
+            };
 
             let is_deleted = row.get::<_, i32>("isErased")? == 1;
 
@@ -339,9 +392,17 @@ fn parse_cwms(
                         // No idea why timestamp is stored in "id" field
                         let reply_to_timestamp = get_field_i64!(quote, QUOTE_KEY, "id");
 
+                        // Timestamp alone is ambiguous in group chats, so we check the author as well.
+                        // "authorAci" was called "authorUuid" before Signal v7.
+                        let reply_to_author_id_option = quote.get("authorAci").or(quote.get("authorUuid"))
+                            .and_then(|v| v.as_str())
+                            .and_then(|v| user_by_service_id(v, users).ok())
+                            .map(|u| u.id);
+
                         let reply_to = messages.iter().rev()
                             .take_while(|m| m.timestamp >= reply_to_timestamp)
-                            .find(|m| m.timestamp == reply_to_timestamp);
+                            .find(|m| m.timestamp == reply_to_timestamp &&
+                                reply_to_author_id_option.is_none_or(|id| m.from_id == id));
 
                         reply_to.and_then(|m| m.source_id_option)
                     } else { None };
@@ -362,12 +423,27 @@ fn parse_cwms(
                     contents.push(c);
                 }
 
+                if let Some(sticker) = json.get(STICKER_KEY) {
+                    let c = parse_sticker(as_object!(sticker, STICKER_KEY),
+                                          attachments_path.zip(attachments_decrypt_path))?;
+                    contents.push(c);
+                }
+
+                let reactions = if let Some(reactions) = json.get(REACTION_KEY) {
+                    parse_reactions(as_array!(reactions, REACTION_KEY), &conversation_user_ids)?
+                } else {
+                    vec![]
+                };
+
                 message_regular! {
                     edit_timestamp_option,
                     is_deleted,
                     forward_from_name_option: None,
                     reply_to_message_id_option,
                     contents,
+                    reactions,
+                    views_option: None,
+                    author_signature_option: None,
                 }
             };
 
@@ -375,7 +451,7 @@ fn parse_cwms(
 
             messages.push(Message::new(
                 *NO_INTERNAL_ID, // Will be set later
-                Some(source_id),
+                Some(source_ids.next(timestamp_ms, from_id)),
                 timestamp_ms, // Will be corrected later
                 from_id,
                 text,
@@ -384,18 +460,26 @@ fn parse_cwms(
         }
 
         if !messages.is_empty() {
+            source_ids.drop_ambiguous(&mut messages);
             messages.iter_mut().enumerate().for_each(|(i, m)| {
                 m.internal_id = i as i64;
                 m.timestamp /= 1000;
             });
 
+            // Former group members are not listed
+            for from_id in messages.iter().map(|m| m.from_id).unique() {
+                if !member_ids.contains(&from_id) {
+                    member_ids.push(from_id);
+                }
+            }
+
             cwms.push(ChatWithMessages {
                 chat: Chat {
                     ds_uuid: ds_uuid.clone(),
-                    id: *chat_id,
-                    name_option: user.first_name_option.clone(),
+                    id: chat_id,
+                    name_option,
                     source_type: SourceType::Signal as i32,
-                    tpe: ChatType::Personal as i32,
+                    tpe: chat_type as i32,
                     img_path_option: None,
                     member_ids,
                     msg_count: messages.len() as i32,
@@ -525,6 +609,316 @@ fn decrypt_linked_file(name: Option<&str>,
 }
 
 const ATTACHMENT_KEY: &str = "attachment";
+const REACTION_KEY: &str = "reactions";
+const STICKER_KEY: &str = "sticker";
+const BODY_RANGES_KEY: &str = "bodyRanges";
+const MEMBERS_V2_KEY: &str = "membersV2";
+const GROUP_V2_CHANGE_KEY: &str = "groupV2Change";
+
+const PRIVATE_TYPE: &str = "private";
+const GROUP_TYPE: &str = "group";
+
+// Body range styles, as defined in SignalService.proto
+const STYLE_BOLD: i32 = 1;
+const STYLE_ITALIC: i32 = 2;
+const STYLE_SPOILER: i32 = 3;
+const STYLE_STRIKETHROUGH: i32 = 4;
+const STYLE_MONOSPACE: i32 = 5;
+
+/// Service ID (ACI) of a private conversation peer.
+fn get_service_id(row: &Row) -> Result<Uuid> {
+    // "serviceId" was called "uuid" before Signal v7 (in v6 for sure)
+    let uuid =
+        row.get::<_, String>("serviceId")
+            .or(row.get::<_, String>("uuid"))
+            .map_err(|_| anyhow!(r##"Neither "serviceId" nor "uuid" column found in table "conversations""##))?;
+    Ok(Uuid::parse_str(&uuid)?)
+}
+
+/// Author of a message as recorded in the message row, only needed for group chats.
+fn get_message_author(row: &Row, users: &Users) -> Result<Option<UserId>> {
+    // "sourceServiceId" was called "sourceUuid" before Signal v7
+    let service_id =
+        row.get::<_, Option<String>>("sourceServiceId")
+            .or(row.get::<_, Option<String>>("sourceUuid"))?;
+    service_id.map(|id| user_by_service_id(&id, users).map(|u| u.id())).transpose()
+}
+
+fn user_by_service_id<'a>(service_id: &str, users: &'a Users) -> Result<&'a User> {
+    let uuid = Uuid::parse_str(service_id)?;
+    users.get(&uuid).ok_or_else(|| anyhow!("Unknown user {uuid}"))
+}
+
+/// Current group members are listed in conversation JSON, legacy (v1) groups instead have them
+/// in a space-separated "members" column. Myself always goes first.
+fn parse_group_member_ids(row: &Row, users: &Users, myself_id: UserId) -> Result<Vec<i64>> {
+    let mut service_ids: Vec<String> = vec![];
+
+    let json = row.get::<_, Option<String>>("json")?.unwrap_or_default();
+    if !json.is_empty() {
+        let mut json = json.into_bytes();
+        let json = simd_json::to_borrowed_value(&mut json)?;
+        let json = as_object!(json, "json");
+        if let Some(members) = json.get(MEMBERS_V2_KEY) {
+            for member in as_array!(members, MEMBERS_V2_KEY) {
+                let member = as_object!(member, MEMBERS_V2_KEY);
+                // "aci" was called "uuid" before Signal v7
+                let aci = member.get("aci").or(member.get("uuid"))
+                    .ok_or_else(|| anyhow!("Group member has no ID"))?;
+                service_ids.push(as_string!(aci, MEMBERS_V2_KEY, "aci"));
+            }
+        }
+    }
+
+    if service_ids.is_empty() && let Some(members) = row.get::<_, Option<String>>("members")? {
+        service_ids.extend(members.split_whitespace().map(|s| s.to_owned()));
+    }
+
+    let mut member_ids = vec![*myself_id];
+    for service_id in service_ids {
+        match user_by_service_id(&service_id, users) {
+            Ok(user) if user.id == *myself_id => {}
+            Ok(user) => member_ids.push(user.id),
+            Err(_) => log::warn!("Unknown group member {service_id}"),
+        }
+    }
+    Ok(member_ids)
+}
+
+/// A single change might contain several details, e.g. group creation comes with title and members.
+/// Only membership, title and avatar changes are taken into account, `None` is returned if there are none.
+///
+/// Also returns the first affected member, as the change author isn't always known.
+fn parse_group_v2_change(
+    change: &Object,
+    users: &Users,
+    chat_name_option: Option<&str>,
+) -> Result<Option<(ServiceSvo, Option<UserId>)>> {
+    const DETAILS_KEY: &str = "groupV2Change.details";
+
+    let mut is_create = false;
+    let mut title_option = None;
+    let mut avatar_removed_option = None;
+    let mut added_members = vec![];
+    let mut removed_members = vec![];
+    let mut affected_id_option = None;
+
+    for detail in get_field_array!(change, GROUP_V2_CHANGE_KEY, "details") {
+        let detail = as_object!(detail, DETAILS_KEY);
+        let tpe = get_field_str!(detail, DETAILS_KEY, "type");
+        match tpe {
+            "create" => is_create = true,
+            "title" => title_option = detail.get("newTitle").and_then(|v| v.as_str()).map(|s| s.to_owned()),
+            "avatar" => avatar_removed_option = Some(detail.get("removed").and_then(|v| v.as_bool()).unwrap_or(false)),
+            "member-add" | "member-add-from-invite" | "member-add-from-link" | "member-add-from-admin-approval" |
+            "member-remove" => {
+                // "aci" was called "uuid" before Signal v7
+                let aci = detail.get("aci").or(detail.get("uuid"))
+                    .ok_or_else(|| anyhow!("No member ID in group change {tpe}"))?;
+                let user = user_by_service_id(as_str!(aci, DETAILS_KEY, "aci"), users)?;
+                affected_id_option.get_or_insert(user.id());
+                if tpe == "member-remove" {
+                    removed_members.push(user.pretty_name());
+                } else {
+                    added_members.push(user.pretty_name());
+                }
+            }
+            _ => { /* Not interesting */ }
+        }
+    }
+
+    let service = if is_create {
+        ServiceSvo::GroupCreate(MessageServiceGroupCreate {
+            title: title_option.or(chat_name_option.map(|s| s.to_owned())).unwrap_or_default(),
+            members: added_members,
+        })
+    } else if !added_members.is_empty() {
+        ServiceSvo::GroupInviteMembers(MessageServiceGroupInviteMembers { members: added_members })
+    } else if !removed_members.is_empty() {
+        ServiceSvo::GroupRemoveMembers(MessageServiceGroupRemoveMembers { members: removed_members })
+    } else if let Some(title) = title_option {
+        ServiceSvo::GroupEditTitle(MessageServiceGroupEditTitle { title })
+    } else if let Some(avatar_removed) = avatar_removed_option {
+        if avatar_removed {
+            ServiceSvo::GroupDeletePhoto(MessageServiceGroupDeletePhoto {})
+        } else {
+            // New avatar is only stored in the conversation itself
+            ServiceSvo::GroupEditPhoto(MessageServiceGroupEditPhoto {
+                photo: ContentPhoto { path_option: None, width: 0, height: 0, mime_type_option: None, is_one_time: false }
+            })
+        }
+    } else {
+        return Ok(None);
+    };
+    Ok(Some((service, affected_id_option)))
+}
+
+/// Body ranges are either styles or mentions, see [make_rich_text].
+fn parse_rich_text(body: &str, body_ranges_option: Option<&Value>, users: &Users) -> Result<Vec<RichTextElement>> {
+    let mut ranges = vec![];
+    if let Some(body_ranges) = body_ranges_option {
+        for range in as_array!(body_ranges, BODY_RANGES_KEY) {
+            let range = as_object!(range, BODY_RANGES_KEY);
+            let start = get_field_i64!(range, BODY_RANGES_KEY, "start").max(0) as usize;
+            let length = get_field_i64!(range, BODY_RANGES_KEY, "length").max(0) as usize;
+
+            let kind = if let Some(style) = range.get("style") {
+                BodyRangeKind::Style(as_i32!(style, BODY_RANGES_KEY, "style"))
+            } else {
+                let name = match range.get("replacementText").and_then(|v| v.as_str()) {
+                    Some(name) => name.to_owned(),
+                    None => {
+                        // "mentionAci" was called "mentionUuid" before Signal v7
+                        let aci = range.get("mentionAci").or(range.get("mentionUuid"))
+                            .ok_or_else(|| anyhow!("Body range is neither a style nor a mention"))?;
+                        user_by_service_id(as_str!(aci, BODY_RANGES_KEY, "mentionAci"), users)?.pretty_name()
+                    }
+                };
+                BodyRangeKind::Mention(name)
+            };
+            ranges.push(BodyRange { start, length, kind });
+        }
+    }
+    Ok(make_rich_text(body, ranges))
+}
+
+pub(super) struct BodyRange {
+    pub start: usize,
+    pub length: usize,
+    pub kind: BodyRangeKind,
+}
+
+pub(super) enum BodyRangeKind {
+    /// One of `STYLE_*` constants
+    Style(i32),
+    /// Name of the mentioned user
+    Mention(String),
+}
+
+/// Body ranges are specified in UTF-16 code units.
+/// Mention replaces a U+FFFC placeholder character in the body.
+///
+/// Styles might overlap, but our rich text elements can't be nested, so the most prominent style wins.
+pub(super) fn make_rich_text(body: &str, ranges: Vec<BodyRange>) -> Vec<RichTextElement> {
+    let units = body.encode_utf16().collect_vec();
+
+    // Bitmask of styles for every code unit
+    let mut styles = vec![0u8; units.len()];
+    // Start -> (end, replacement text)
+    let mut mentions: HashMap<usize, (usize, String)> = HashMap::new();
+
+    for range in ranges {
+        let start = range.start.min(units.len());
+        let end = (start + range.length).min(units.len());
+        match range.kind {
+            BodyRangeKind::Style(style) if (STYLE_BOLD..=STYLE_MONOSPACE).contains(&style) =>
+                styles[start..end].iter_mut().for_each(|s| *s |= 1 << style),
+            BodyRangeKind::Style(_) => { /* Unknown style */ }
+            BodyRangeKind::Mention(name) => {
+                mentions.insert(start, (end, format!("@{name}")));
+            }
+        }
+    }
+
+    let make_rte = |styles: u8, text: String| {
+        let has = |style: i32| styles & (1 << style) != 0;
+        if has(STYLE_MONOSPACE) {
+            RichText::make_prefmt_inline(text)
+        } else if has(STYLE_SPOILER) {
+            RichText::make_spoiler(text)
+        } else if has(STYLE_BOLD) {
+            RichText::make_bold(text)
+        } else if has(STYLE_ITALIC) {
+            RichText::make_italic(text)
+        } else if has(STYLE_STRIKETHROUGH) {
+            RichText::make_strikethrough(text)
+        } else {
+            RichText::make_plain(text)
+        }
+    };
+
+    let mut result = vec![];
+    let mut i = 0;
+    while i < units.len() {
+        if let Some((end, text)) = mentions.remove(&i) && end > i {
+            result.push(make_rte(styles[i], text));
+            i = end;
+            continue;
+        }
+        let mut j = i + 1;
+        while j < units.len() && styles[j] == styles[i] && !mentions.contains_key(&j) {
+            j += 1;
+        }
+        result.push(make_rte(styles[i], String::from_utf16_lossy(&units[i..j])));
+        i = j;
+    }
+    result
+}
+
+/// Stickers from installed packs are copied to attachments directory, so they're encrypted the same way.
+fn parse_sticker(json: &Object, paths_option: Option<(&Path, &Path)>) -> Result<Content> {
+    let emoji_option = json.get("emoji").and_then(|v| v.as_str()).map(|s| s.to_owned());
+    let file_info_option = if let Some(data) = json.get("data") {
+        Some(parse_linked_file_info(as_object!(data, STICKER_KEY, "data"), &format!("{STICKER_KEY}.data"))?)
+    } else { None };
+    let path_option = match (&file_info_option, paths_option) {
+        (Some(file_info), Some((src_path, dst_path))) =>
+            decrypt_linked_file(Some(STICKER_KEY), file_info, src_path, dst_path)?,
+        _ => None,
+    };
+    Ok(content!(Sticker {
+        path_option,
+        file_name_option: None,
+        width: file_info_option.as_ref().and_then(|fi| fi.width).unwrap_or(0),
+        height: file_info_option.as_ref().and_then(|fi| fi.height).unwrap_or(0),
+        mime_type_option: file_info_option.map(|fi| fi.mime_type),
+        thumbnail_path_option: None,
+        emoji_option,
+    }))
+}
+
+fn parse_conversation_user_ids(conn: &Connection, users: &Users) -> Result<HashMap<Uuid, UserId>> {
+    let mut result = HashMap::new();
+
+    let mut stmt = conn.prepare(r"SELECT * FROM conversations WHERE type = 'private'")?;
+    let mut rows = stmt.query([])?;
+
+    while let Some(row) = rows.next()? {
+        let conversation_uuid = Uuid::parse_str(&row.get::<_, String>("id")?)?;
+        let user_uuid = get_service_id(row)?;
+        let user = users.get(&user_uuid).ok_or_else(|| anyhow!("Unknown user {user_uuid}"))?;
+        result.insert(conversation_uuid, user.id());
+    }
+
+    Ok(result)
+}
+
+/// Signal stores one entry per reacting user, we group them by emoji.
+fn parse_reactions(jsons: &[Value], conversation_user_ids: &HashMap<Uuid, UserId>) -> Result<Vec<Reaction>> {
+    let mut reactions: Vec<Reaction> = vec![];
+
+    for json in jsons {
+        let json = as_object!(json, REACTION_KEY);
+        let emoji = get_field_string!(json, REACTION_KEY, "emoji");
+        let from_uuid = Uuid::parse_str(get_field_str!(json, REACTION_KEY, "fromId"))?;
+        let from_id = *conversation_user_ids.get(&from_uuid)
+            .ok_or_else(|| anyhow!("Unknown reaction author conversation {from_uuid}"))?;
+        match reactions.iter_mut().find(|r| r.emoji_option.as_ref() == Some(&emoji)) {
+            Some(reaction) => {
+                reaction.from_ids.push(*from_id);
+                reaction.count += 1;
+            }
+            None => reactions.push(Reaction {
+                emoji_option: Some(emoji),
+                custom_emoji_id_option: None,
+                from_ids: vec![*from_id],
+                count: 1,
+            }),
+        }
+    }
+
+    Ok(reactions)
+}
 
 fn parse_attachments(jsons: &[Value]) -> Result<Vec<LinkedAttachment>> {
     let mut attachments = vec![];
@@ -585,7 +979,7 @@ fn get_myself(conn: &Connection) -> Result<UserId> {
     Ok(id)
 }
 
-fn uuid_to_i64_pos(uuid: Uuid) -> Result<i64> {
+pub(super) fn uuid_to_i64_pos(uuid: Uuid) -> Result<i64> {
     let uuid_bytes = uuid.as_bytes();
     let uuid_parts: Vec<[u8; 8]> = vec![
         uuid_bytes[0..8].try_into()?,
@@ -596,6 +990,45 @@ fn uuid_to_i64_pos(uuid: Uuid) -> Result<i64> {
     Ok((res_u64 / 2) as i64)
 }
 
+/// Group chat ID, derived from the raw group ID shared by all members' clients.
+/// (Personal chat ID is that of a peer.)
+pub(super) fn group_chat_id(group_id: &[u8]) -> i64 {
+    super::hash_to_id(&hex::encode(group_id))
+}
+
+/// Within a chat, Signal itself identifies a message by its sent timestamp and author (that's how quotes and reactions
+/// reference it), so source ID is derived from these rather than from a local message ID.
+pub(super) fn make_source_id(timestamp_ms: i64, author_id: UserId) -> i64 {
+    super::hash_to_id(&format!("{timestamp_ms}_{}", *author_id))
+}
+
+/// Source IDs of a chat, tracking messages that share timestamp and author (e.g. service messages).
+/// Different clients might list such messages in a different order, so there's no way to tell them apart consistently
+/// and they are left without source ID (along with replies to them).
+#[derive(Default)]
+pub(super) struct SourceIds(HashMap<i64, usize>);
+
+impl SourceIds {
+    pub(super) fn next(&mut self, timestamp_ms: i64, author_id: UserId) -> i64 {
+        let source_id = make_source_id(timestamp_ms, author_id);
+        *self.0.entry(source_id).or_default() += 1;
+        source_id
+    }
+
+    pub(super) fn drop_ambiguous(&self, messages: &mut [Message]) {
+        let is_ambiguous = |source_id: &i64| self.0.get(source_id).is_some_and(|&count| count > 1);
+        for m in messages.iter_mut() {
+            if m.source_id_option.take_if(|id| is_ambiguous(id)).is_some() {
+                log::warn!("Several messages were sent at {} by {}, leaving them without source ID",
+                           m.timestamp, m.from_id);
+            }
+            if let message_regular_pat!(reply_to_message_id_option, ..) = m.typed_mut() {
+                reply_to_message_id_option.take_if(|id| is_ambiguous(id));
+            }
+        }
+    }
+}
+
 struct LinkedAttachment {
     name: Option<String>,
 
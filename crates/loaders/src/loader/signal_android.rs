@@ -0,0 +1,1041 @@
+use super::signal::{group_chat_id, make_rich_text, uuid_to_i64_pos, BodyRange, BodyRangeKind, SourceIds};
+use super::DataLoader;
+use crate::prelude::*;
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
+
+use prost::Message as ProtobufMessage;
+use rusqlite::types::{FromSql, Value as SqlValue};
+use rusqlite::{params_from_iter, Connection, Row};
+use uuid::Uuid;
+
+use self::cipher::*;
+use self::protobuf::*;
+
+#[cfg(test)]
+#[path = "signal_android_tests.rs"]
+mod tests;
+
+/// Loads encrypted backup made by Signal for Android (Settings > Chats > Chat backups), tested with database schema
+/// of Signal Android v7.x.
+///
+/// Backup is a stream of encrypted protobuf frames - SQL statements recreating the database, interleaved with
+/// attachments and avatars. We replay statements into an in-memory database, and decrypt attachments and avatars
+/// into a private directory of the dataset, backup directory is never written to.
+///
+/// User, chat and message IDs are derived the same way as in [super::SignalDataLoader], so the resulting datasets
+/// could be merged.
+///
+/// Format is described by `FullBackupExporter` and `BackupRecordInputStream` in Signal Android source code.
+pub struct SignalAndroidDataLoader;
+
+const NAME: &str = "Signal (Android)";
+const BACKUP_EXTENSION: &str = "backup";
+
+const ATTACHMENTS_DIR_NAME: &str = "attachments";
+const AVATARS_DIR_NAME: &str = "avatars";
+
+const MYSELF_ACI_KEY: &str = "account.aci";
+/// Used before key-value store was introduced
+const MYSELF_UUID_PREFERENCE: &str = "pref_local_uuid";
+
+impl DataLoader for SignalAndroidDataLoader {
+    fn name(&self) -> String { NAME.to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        if path.extension().is_none_or(|ext| ext != BACKUP_EXTENSION) {
+            bail!("File is not a .{BACKUP_EXTENSION} file")
+        }
+        read_header(&mut BufReader::new(File::open(path)?))?;
+        Ok(())
+    }
+
+    fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+        let ds_dir = dataset_dir(&ds)?;
+        let dao = load_backup(feedback_client, path, ds, ds_dir.path().to_path_buf())?;
+        // In-memory dataset references decrypted files in place, so they're kept (unless loading failed)
+        let _ = ds_dir.into_path();
+        Ok(dao)
+    }
+
+    fn load_into_inner(&self,
+                       feedback_client: &dyn FeedbackClientSync,
+                       path: &Path,
+                       ds: Dataset,
+                       sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        // Sink copies files into its own dataset directory, so decrypted files are removed afterwards
+        let ds_dir = dataset_dir(&ds)?;
+        let ds_uuid = ds.uuid.clone();
+        let dao = load_backup(feedback_client, path, ds, ds_dir.path().to_path_buf())?;
+        push_dataset(dao.as_ref(), &ds_uuid, sink)
+    }
+}
+
+/// Private directory for decrypted attachments and avatars, serving as dataset root.
+fn dataset_dir(ds: &Dataset) -> Result<tempfile::TempDir> {
+    Ok(tempfile::Builder::new().prefix(&format!("signal_{}_", ds.uuid.value)).tempdir()?)
+}
+
+fn load_backup(feedback_client: &dyn FeedbackClientSync,
+               path: &Path,
+               ds: Dataset,
+               ds_root: PathBuf) -> Result<Box<InMemoryDao>> {
+    let passphrase = feedback_client.ask_for_text("\
+        Input 30-digit passphrase of the backup.\n\
+        It was shown by Signal app when chat backups were enabled.\
+    ")?;
+
+    feedback_client.set_load_status(LoadStatus::new_parsing("backup", Some(format!("{}", path.display()))));
+    let conn = Connection::open_in_memory()?;
+    let backup = restore_backup(path, &passphrase, &conn, &ds_root)?;
+
+    let users = parse_users(&conn, &ds.uuid, &backup)?;
+
+    let myself_uuid = backup.myself_uuid.as_deref().ok_or_else(|| anyhow!("Own account ID not found in backup"))?;
+    let myself_id = UserId(uuid_to_i64_pos(Uuid::parse_str(myself_uuid)?)?);
+    ensure!(users.values().any(|u| u.id == *myself_id), "Own account not found among recipients");
+
+    let cwms = parse_cwms(feedback_client, &conn, &ds.uuid, &users, myself_id, &backup)?;
+
+    let mut users = users.into_values().collect_vec();
+    users.sort_by_key(|u| if u.id == *myself_id { *UserId::MIN } else { u.id });
+
+    Ok(Box::new(InMemoryDao::new_single(
+        format!("{NAME} ({})", path_file_name(path)?),
+        ds,
+        ds_root,
+        myself_id,
+        users,
+        cwms,
+    )))
+}
+
+#[derive(Default)]
+struct RestoredBackup {
+    myself_uuid: Option<String>,
+    /// Attachment row ID -> path relative to dataset root
+    attachment_paths: HashMap<i64, String>,
+    /// Recipient ID -> path relative to dataset root
+    avatar_paths: HashMap<i64, String>,
+}
+
+type RecipientId = i64;
+type Users = HashMap<RecipientId, User>;
+
+//
+// Backup decryption
+//
+
+/// Header frame is the only one that's not encrypted.
+fn read_header(input: &mut impl Read) -> Result<Header> {
+    const MAX_HEADER_LEN: usize = 1024;
+
+    let mut len_bytes = [0u8; 4];
+    input.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    ensure!(len <= MAX_HEADER_LEN, "Not a Signal backup");
+
+    let mut frame = vec![0u8; len];
+    input.read_exact(&mut frame)?;
+    let frame = BackupFrame::decode(frame.as_slice()).map_err(|_| anyhow!("Not a Signal backup"))?;
+    let header = frame.header.ok_or_else(|| anyhow!("Not a Signal backup, header not found"))?;
+    ensure!(header.iv().len() == AES_BLOCK_SIZE && !header.salt().is_empty(), "Malformed backup header");
+    Ok(header)
+}
+
+fn restore_backup(path: &Path, passphrase: &str, conn: &Connection, ds_root: &Path) -> Result<RestoredBackup> {
+    let mut input = BufReader::new(File::open(path)?);
+    let header = read_header(&mut input)?;
+    let mut stream = BackupStream::new(input, &header, passphrase)?;
+
+    // Tables are exported one by one, so references may point to rows that are not yet inserted
+    conn.execute_batch("PRAGMA foreign_keys = OFF;")?;
+
+    let mut result = RestoredBackup::default();
+    loop {
+        let frame = stream.read_frame()?;
+        if let Some(statement) = frame.statement {
+            execute_statement(conn, statement)?;
+        } else if let Some(key_value) = frame.key_value {
+            if key_value.key() == MYSELF_ACI_KEY {
+                result.myself_uuid = key_value.string_value;
+            }
+        } else if let Some(preference) = frame.preference {
+            if preference.key() == MYSELF_UUID_PREFERENCE && result.myself_uuid.is_none() {
+                result.myself_uuid = preference.value;
+            }
+        } else if let Some(attachment) = frame.attachment {
+            let rel_path = format!("{ATTACHMENTS_DIR_NAME}/{}", attachment.row_id());
+            stream.read_blob(attachment.length() as usize, create_file(&ds_root.join(&rel_path))?)?;
+            result.attachment_paths.insert(attachment.row_id() as i64, rel_path);
+        } else if let Some(avatar) = frame.avatar {
+            match avatar.recipient_id().parse::<RecipientId>() {
+                Ok(recipient_id) => {
+                    let rel_path = format!("{AVATARS_DIR_NAME}/{recipient_id}");
+                    stream.read_blob(avatar.length() as usize, create_file(&ds_root.join(&rel_path))?)?;
+                    result.avatar_paths.insert(recipient_id, rel_path);
+                }
+                Err(_) => {
+                    // Legacy avatar, named by phone number or group ID
+                    stream.read_blob(avatar.length() as usize, std::io::sink())?;
+                }
+            }
+        } else if let Some(sticker) = frame.sticker {
+            // Installed sticker packs, sent stickers are attachments themselves
+            stream.read_blob(sticker.length() as usize, std::io::sink())?;
+        } else if frame.end() {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+fn create_file(path: &Path) -> Result<File> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    Ok(File::create(path)?)
+}
+
+fn execute_statement(conn: &Connection, statement: SqlStatement) -> EmptyRes {
+    let SqlStatement { statement: sql, parameters } = statement;
+    let sql = sql.unwrap_or_default();
+
+    // Same as Signal itself, we skip internal SQLite tables and full-text search tables with their triggers,
+    // they can't be (or don't need to be) created this way.
+    let sql_start = sql.chars().take(64).collect::<String>().to_lowercase();
+    if sql_start.starts_with("create trigger") || sql_start.contains(" sqlite_") || sql_start.contains("_fts") {
+        return Ok(());
+    }
+
+    let params = parameters.into_iter().map(|p| {
+        if let Some(v) = p.string_paramter {
+            SqlValue::Text(v)
+        } else if let Some(v) = p.integer_parameter {
+            // Signal stores signed values as unsigned
+            SqlValue::Integer(v as i64)
+        } else if let Some(v) = p.double_parameter {
+            SqlValue::Real(v)
+        } else if let Some(v) = p.blob_parameter {
+            SqlValue::Blob(v)
+        } else {
+            SqlValue::Null
+        }
+    });
+    conn.execute(&sql, params_from_iter(params)).with_context(|| format!("Failed to execute SQL: {sql}"))?;
+    Ok(())
+}
+
+struct BackupStream<R: Read> {
+    input: R,
+    version: u32,
+    cipher: Aes256,
+    mac_key: [u8; MAC_KEY_SIZE],
+    iv: [u8; AES_BLOCK_SIZE],
+    /// Stored in first 4 bytes of IV, incremented with every frame and blob
+    counter: u32,
+}
+
+impl<R: Read> BackupStream<R> {
+    fn new(input: R, header: &Header, passphrase: &str) -> Result<Self> {
+        let (cipher_key, mac_key) = derive_keys(passphrase, header.salt());
+        let iv: [u8; AES_BLOCK_SIZE] = header.iv().try_into()?;
+        let counter = u32::from_be_bytes(iv[..4].try_into()?);
+        Ok(BackupStream {
+            input,
+            version: header.version(),
+            cipher: Aes256::new(&cipher_key.into()),
+            mac_key,
+            iv,
+            counter,
+        })
+    }
+
+    fn next_iv(&mut self) -> [u8; AES_BLOCK_SIZE] {
+        let mut iv = self.iv;
+        iv[..4].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter = self.counter.wrapping_add(1);
+        iv
+    }
+
+    fn read_frame(&mut self) -> Result<BackupFrame> {
+        const MAX_FRAME_LEN: usize = 128 * 1024 * 1024;
+
+        let mut len_bytes = [0u8; 4];
+        self.input.read_exact(&mut len_bytes).context("Unexpected end of backup")?;
+
+        let mut mac = new_mac(&self.mac_key);
+        let version = self.version;
+        let mut frame_bytes = {
+            let iv = self.next_iv();
+            let mut ctr = Aes256Ctr::new(&self.cipher, iv);
+
+            // Starting with version 1, frame length is encrypted too
+            if version >= 1 {
+                mac.update(&len_bytes);
+                ctr.apply(&mut len_bytes);
+            }
+            let len = u32::from_be_bytes(len_bytes) as usize;
+            ensure!((MAC_SIZE..=MAX_FRAME_LEN).contains(&len), "Invalid frame length {len}, wrong passphrase?");
+
+            let mut frame_bytes = vec![0u8; len];
+            self.input.read_exact(&mut frame_bytes).context("Unexpected end of backup")?;
+
+            let (data, their_mac) = frame_bytes.split_at_mut(len - MAC_SIZE);
+            mac.update(data);
+            ensure!(mac.finalize().into_bytes()[..MAC_SIZE] == *their_mac, "Frame MAC mismatch, wrong passphrase?");
+
+            ctr.apply(data);
+            frame_bytes
+        };
+        frame_bytes.truncate(frame_bytes.len() - MAC_SIZE);
+        Ok(BackupFrame::decode(frame_bytes.as_slice())?)
+    }
+
+    /// Blob (e.g. attachment) follows the frame describing it
+    fn read_blob(&mut self, len: usize, mut sink: impl Write) -> EmptyRes {
+        let mut mac = new_mac(&self.mac_key);
+        let mut their_mac = [0u8; MAC_SIZE];
+        {
+            let iv = self.next_iv();
+            let mut ctr = Aes256Ctr::new(&self.cipher, iv);
+            mac.update(&iv);
+
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut remaining = len;
+            while remaining > 0 {
+                let chunk = &mut buf[..remaining.min(64 * 1024)];
+                self.input.read_exact(chunk).context("Unexpected end of backup")?;
+                mac.update(chunk);
+                ctr.apply(chunk);
+                sink.write_all(chunk)?;
+                remaining -= chunk.len();
+            }
+        }
+        self.input.read_exact(&mut their_mac).context("Unexpected end of backup")?;
+        ensure!(mac.finalize().into_bytes()[..MAC_SIZE] == their_mac, "Attachment MAC mismatch");
+        Ok(())
+    }
+}
+
+//
+// Database parsing
+//
+
+/// Signal renames columns every now and then, this returns value of the first column that exists.
+fn get_any<T: FromSql>(row: &Row, columns: &[&str]) -> Result<T> {
+    for column in columns {
+        match row.get::<_, T>(*column) {
+            Err(rusqlite::Error::InvalidColumnName(_)) => continue,
+            res => return Ok(res?),
+        }
+    }
+    bail!("None of the columns {columns:?} found")
+}
+
+fn get_non_empty(row: &Row, columns: &[&str]) -> Result<Option<String>> {
+    Ok(get_any::<Option<String>>(row, columns)?.filter(|s| !s.is_empty()))
+}
+
+/// Parses individual recipients, ignoring groups, distribution lists (stories) and call links.
+fn parse_users(conn: &Connection, ds_uuid: &PbUuid, backup: &RestoredBackup) -> Result<Users> {
+    let mut users = Users::new();
+
+    let mut stmt = conn.prepare(r"SELECT * FROM recipient")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let recipient_id: RecipientId = row.get("_id")?;
+        if row.get::<_, Option<String>>("group_id")?.is_some()
+            || get_any::<Option<i64>>(row, &["distribution_list_id"]).ok().flatten().is_some() {
+            continue;
+        }
+
+        // "aci" and "e164" were called "uuid" and "phone" before 2023
+        let aci_option = get_non_empty(row, &["aci", "uuid"])?;
+        let phone_number_option = get_non_empty(row, &["e164", "phone"])?;
+        if aci_option.is_none() && phone_number_option.is_none() {
+            continue;
+        }
+
+        // Users without ACI (e.g. SMS contacts) can't be matched with Signal Desktop users anyway
+        let id = match aci_option {
+            Some(aci) => uuid_to_i64_pos(Uuid::parse_str(&aci)?)?,
+            None => recipient_id,
+        };
+
+        let profile_first_name_option = get_non_empty(row, &["profile_given_name", "signal_profile_name"])?;
+        let (first_name_option, last_name_option) = if profile_first_name_option.is_some() {
+            (profile_first_name_option, get_non_empty(row, &["profile_family_name"])?)
+        } else {
+            (get_non_empty(row, &["system_joined_name", "system_display_name"])?, None)
+        };
+
+        let profile_pictures = backup.avatar_paths.get(&recipient_id)
+            .map(|path| vec![ProfilePicture { path: path.clone(), frame_option: None }])
+            .unwrap_or_default();
+
+        users.insert(recipient_id, User {
+            ds_uuid: ds_uuid.clone(),
+            id,
+            first_name_option,
+            last_name_option,
+            username_option: get_non_empty(row, &["username"])?,
+            phone_number_option: phone_number_option.map(|pn| PhoneNumber::from_raw(&pn).0),
+            profile_pictures,
+        });
+    }
+
+    ensure!(users.values().map(|u| u.id).unique().count() == users.len(), "Duplicate user IDs");
+    Ok(users)
+}
+
+struct Group {
+    chat_id: i64,
+    title_option: Option<String>,
+    member_ids: Vec<RecipientId>,
+}
+
+/// Members are listed in a separate table in newer versions, and as comma-separated recipient IDs before that.
+fn parse_groups(conn: &Connection) -> Result<HashMap<RecipientId, Group>> {
+    let mut memberships: HashMap<String, Vec<RecipientId>> = HashMap::new();
+    if let Ok(mut stmt) = conn.prepare(r"SELECT * FROM group_membership ORDER BY _id") {
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            memberships.entry(row.get("group_id")?).or_default().push(row.get("recipient_id")?);
+        }
+    }
+
+    let mut result = HashMap::new();
+    let mut stmt = conn.prepare(r"SELECT * FROM groups")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let group_id: String = row.get("group_id")?;
+        let member_ids = match memberships.remove(&group_id) {
+            Some(member_ids) => member_ids,
+            None => get_any::<Option<String>>(row, &["members"]).ok().flatten().unwrap_or_default()
+                .split(',')
+                .filter_map(|id| id.trim().parse().ok())
+                .collect_vec()
+        };
+        // Stored as hex with a prefix denoting group type, e.g. "__signal_group__v2__!0123abcd"
+        let raw_group_id = group_id.rsplit_once('!').map(|(_, hex)| hex::decode(hex)).transpose()?
+            .ok_or_else(|| anyhow!("Malformed group ID {group_id}"))?;
+        result.insert(row.get("recipient_id")?, Group {
+            chat_id: group_chat_id(&raw_group_id),
+            title_option: row.get("title")?,
+            member_ids,
+        });
+    }
+    Ok(result)
+}
+
+// Message types, see MessageTypes.java
+const BASE_TYPE_MASK: i64 = 0x1F;
+
+const INCOMING_AUDIO_CALL_TYPE: i64 = 1;
+const OUTGOING_AUDIO_CALL_TYPE: i64 = 2;
+const MISSED_AUDIO_CALL_TYPE: i64 = 3;
+const MISSED_VIDEO_CALL_TYPE: i64 = 8;
+const INCOMING_VIDEO_CALL_TYPE: i64 = 10;
+const OUTGOING_VIDEO_CALL_TYPE: i64 = 11;
+const GROUP_CALL_TYPE: i64 = 12;
+
+const BASE_INBOX_TYPE: i64 = 20;
+const OUTGOING_MESSAGE_TYPES: [i64; 6] = [21, 22, 23, 24, 25, 26];
+
+const KEY_EXCHANGE_BIT: i64 = 0x8000;
+const IDENTITY_DEFAULT_BIT: i64 = 0x2000;
+const IDENTITY_VERIFIED_BIT: i64 = 0x4000;
+const GROUP_UPDATE_BIT: i64 = 0x10000;
+const EXPIRATION_TIMER_UPDATE_BIT: i64 = 0x40000;
+const END_SESSION_BIT: i64 = 0x400000;
+
+/// Messages with these bits are not interesting, or (for group updates) stored as an opaque protobuf
+const SKIPPED_TYPE_BITS: i64 = KEY_EXCHANGE_BIT | IDENTITY_DEFAULT_BIT | IDENTITY_VERIFIED_BIT |
+    GROUP_UPDATE_BIT | EXPIRATION_TIMER_UPDATE_BIT | END_SESSION_BIT;
+
+// Body range styles in BodyRangeList protobuf are shifted by one compared to SignalService.proto
+const STYLE_OFFSET: i32 = 1;
+
+fn parse_cwms(
+    feedback_client: &dyn FeedbackClientSync,
+    conn: &Connection,
+    ds_uuid: &PbUuid,
+    users: &Users,
+    myself_id: UserId,
+    backup: &RestoredBackup,
+) -> Result<Vec<ChatWithMessages>> {
+    let groups = parse_groups(conn)?;
+    let mut attachments = parse_attachments(conn, backup)?;
+    let mut reactions = parse_reactions(conn, users)?;
+    let mut mentions = parse_mentions(conn, users)?;
+
+    let get_user_id = |recipient_id: RecipientId| -> Result<UserId> {
+        users.get(&recipient_id).map(|u| u.id()).ok_or_else(|| anyhow!("Unknown recipient {recipient_id}"))
+    };
+
+    let mut cwms = vec![];
+
+    let mut thread_stmt = conn.prepare(r"SELECT * FROM thread ORDER BY _id")?;
+    let mut msg_stmt = conn.prepare(r"SELECT * FROM message WHERE thread_id = ? ORDER BY date_sent ASC, _id ASC")?;
+
+    let mut thread_rows = thread_stmt.query([])?;
+    while let Some(row) = thread_rows.next()? {
+        let thread_id: i64 = row.get("_id")?;
+        // "recipient_id" was called "thread_recipient_id" before 2023
+        let recipient_id: RecipientId = get_any(row, &["recipient_id", "thread_recipient_id"])?;
+
+        let (chat_id, chat_type, name_option, mut member_ids, peer_id_option) =
+            if let Some(user) = users.get(&recipient_id) {
+                let member_ids = if user.id == *myself_id {
+                    vec![*myself_id]
+                } else {
+                    vec![*myself_id, user.id]
+                };
+                feedback_client.set_load_status(LoadStatus::new_parsing("chat with", Some(user.pretty_name())));
+                (user.id, ChatType::Personal, user.first_name_option.clone(), member_ids, Some(user.id()))
+            } else if let Some(group) = groups.get(&recipient_id) {
+                let mut member_ids = vec![*myself_id];
+                for user_id in group.member_ids.iter().filter_map(|id| users.get(id)).map(|u| u.id) {
+                    if !member_ids.contains(&user_id) {
+                        member_ids.push(user_id);
+                    }
+                }
+                feedback_client.set_load_status(LoadStatus::new_parsing("group chat", group.title_option.clone()));
+                (group.chat_id, ChatType::PrivateGroup, group.title_option.clone(), member_ids, None)
+            } else {
+                // Story distribution list or something else we don't know about
+                continue;
+            };
+
+        let mut messages: Vec<Message> = vec![];
+
+        // Original timestamps of edited messages, keyed by original message ID
+        let mut edited_timestamps: HashMap<i64, i64> = HashMap::new();
+        let mut source_ids = SourceIds::default();
+        // (timestamp millis, author) -> message source ID, needed to resolve replies
+        let mut reply_targets: HashMap<(i64, UserId), i64> = HashMap::new();
+
+        let mut msg_rows = msg_stmt.query([thread_id])?;
+        while let Some(row) = msg_rows.next()? {
+            let msg_id: i64 = row.get("_id")?;
+            let timestamp_ms: i64 = row.get("date_sent")?;
+
+            // Edits are stored as separate messages, we only keep the latest revision
+            if get_any::<Option<i64>>(row, &["latest_revision_id"]).ok().flatten().is_some() {
+                edited_timestamps.insert(msg_id, timestamp_ms);
+                continue;
+            }
+            let original_id_option = get_any::<Option<i64>>(row, &["original_message_id"]).ok().flatten();
+            let (timestamp_ms, edit_timestamp_option) = match original_id_option {
+                Some(original_id) => {
+                    let original_timestamp_ms = edited_timestamps.get(&original_id).cloned().unwrap_or(timestamp_ms);
+                    (original_timestamp_ms, Some(timestamp_ms / 1000))
+                }
+                None => (timestamp_ms, None)
+            };
+
+            let tpe: i64 = row.get("type")?;
+            if tpe & SKIPPED_TYPE_BITS != 0 {
+                continue;
+            }
+
+            // "from_recipient_id" was called "recipient_id" before 2023
+            let author_id_option = match get_any::<Option<RecipientId>>(row, &["from_recipient_id", "recipient_id"])? {
+                Some(recipient_id) => Some(get_user_id(recipient_id)?),
+                None => peer_id_option,
+            };
+            let get_author_id = || author_id_option.ok_or_else(|| anyhow!("Unknown author of message {msg_id}"));
+
+            let make_call = |discard_reason_option: Option<&str>| message_service!(ServiceSvo::PhoneCall(MessageServicePhoneCall {
+                duration_sec_option: None, // Duration is not recorded
+                discard_reason_option: discard_reason_option.map(|s| s.to_owned()),
+                members: vec![],
+            }));
+
+            let base_type = tpe & BASE_TYPE_MASK;
+            let (from_id, service_option) = match base_type {
+                BASE_INBOX_TYPE => (get_author_id()?, None),
+                t if OUTGOING_MESSAGE_TYPES.contains(&t) => (myself_id, None),
+                INCOMING_AUDIO_CALL_TYPE | INCOMING_VIDEO_CALL_TYPE => (get_author_id()?, Some(make_call(Some("hangup")))),
+                OUTGOING_AUDIO_CALL_TYPE | OUTGOING_VIDEO_CALL_TYPE => (myself_id, Some(make_call(Some("hangup")))),
+                MISSED_AUDIO_CALL_TYPE | MISSED_VIDEO_CALL_TYPE => (get_author_id()?, Some(make_call(Some("missed")))),
+                GROUP_CALL_TYPE => (get_author_id()?, Some(make_call(None))),
+                _ => continue, // Profile changes, safety number changes, etc.
+            };
+
+            let typed = if let Some(service) = service_option {
+                service
+            } else {
+                let is_one_time = row.get::<_, Option<i32>>("view_once")?.unwrap_or(0) == 1;
+                let contents = attachments.remove(&msg_id).unwrap_or_default().into_iter()
+                    .map(|a| a.into_content(is_one_time))
+                    .collect_vec();
+
+                let reply_to_message_id_option = match row.get::<_, Option<i64>>("quote_id")? {
+                    Some(quote_timestamp_ms) if quote_timestamp_ms > 0 => {
+                        let quote_author_id = get_user_id(row.get("quote_author")?)?;
+                        reply_targets.get(&(quote_timestamp_ms, quote_author_id)).cloned()
+                    }
+                    _ => None,
+                };
+
+                message_regular! {
+                    edit_timestamp_option,
+                    is_deleted: row.get::<_, Option<i32>>("remote_deleted")?.unwrap_or(0) == 1,
+                    forward_from_name_option: None,
+                    reply_to_message_id_option,
+                    contents,
+                    reactions: reactions.remove(&msg_id).unwrap_or_default(),
+                    views_option: None,
+                    author_signature_option: None,
+                }
+            };
+
+            let text = match row.get::<_, Option<String>>("body")? {
+                Some(body) if !body.is_empty() => {
+                    let mut ranges = mentions.remove(&msg_id).unwrap_or_default();
+                    if let Some(ranges_blob) = get_any::<Option<Vec<u8>>>(row, &["message_ranges"]).ok().flatten() {
+                        for range in BodyRangeList::decode(ranges_blob.as_slice())?.ranges {
+                            if let Some(style) = range.style {
+                                ranges.push(BodyRange {
+                                    start: range.start.max(0) as usize,
+                                    length: range.length.max(0) as usize,
+                                    kind: BodyRangeKind::Style(style + STYLE_OFFSET),
+                                });
+                            }
+                        }
+                    }
+                    super::normalize_rich_text(make_rich_text(&body, ranges))
+                }
+                _ => vec![],
+            };
+
+            let source_id = source_ids.next(timestamp_ms, from_id);
+            reply_targets.insert((timestamp_ms, from_id), source_id);
+            messages.push(Message::new(
+                *NO_INTERNAL_ID, // Will be set later
+                Some(source_id),
+                timestamp_ms / 1000,
+                from_id,
+                text,
+                typed,
+            ));
+        }
+
+        if !messages.is_empty() {
+            source_ids.drop_ambiguous(&mut messages);
+            // Edited messages are ordered by their edit timestamp
+            messages.sort_by_key(|m| m.timestamp);
+            messages.iter_mut().enumerate().for_each(|(i, m)| m.internal_id = i as i64);
+
+            // Former group members are not listed
+            for from_id in messages.iter().map(|m| m.from_id).unique() {
+                if !member_ids.contains(&from_id) {
+                    member_ids.push(from_id);
+                }
+            }
+
+            cwms.push(ChatWithMessages {
+                chat: Chat {
+                    ds_uuid: ds_uuid.clone(),
+                    id: chat_id,
+                    name_option,
+                    source_type: SourceType::Signal as i32,
+                    tpe: chat_type as i32,
+                    img_path_option: None,
+                    member_ids,
+                    msg_count: messages.len() as i32,
+                    main_chat_id: None,
+                },
+                messages,
+            });
+        }
+    }
+
+    Ok(cwms)
+}
+
+struct Attachment {
+    path_option: Option<String>,
+    file_name_option: Option<String>,
+    mime_type_option: Option<String>,
+    width: i32,
+    height: i32,
+    is_voice_note: bool,
+    /// Only present for stickers
+    sticker_emoji_option: Option<Option<String>>,
+}
+
+impl Attachment {
+    fn into_content(self, is_one_time: bool) -> Content {
+        let mime_type = self.mime_type_option.clone().unwrap_or_default();
+        if let Some(emoji_option) = self.sticker_emoji_option {
+            content!(Sticker {
+                path_option: self.path_option,
+                file_name_option: self.file_name_option,
+                width: self.width,
+                height: self.height,
+                mime_type_option: self.mime_type_option,
+                thumbnail_path_option: None,
+                emoji_option,
+            })
+        } else if self.is_voice_note {
+            content!(VoiceMsg {
+                path_option: self.path_option,
+                file_name_option: self.file_name_option,
+                mime_type,
+                duration_sec_option: None,
+            })
+        } else if mime_type.starts_with("image/") {
+            content!(Photo {
+                path_option: self.path_option,
+                width: self.width,
+                height: self.height,
+                mime_type_option: self.mime_type_option,
+                is_one_time,
+            })
+        } else if mime_type.starts_with("video/") {
+            content!(Video {
+                path_option: self.path_option,
+                file_name_option: self.file_name_option,
+                title_option: None,
+                performer_option: None,
+                width: self.width,
+                height: self.height,
+                mime_type,
+                duration_sec_option: None,
+                thumbnail_path_option: None,
+                is_one_time,
+            })
+        } else if mime_type.starts_with("audio/") {
+            content!(Audio {
+                path_option: self.path_option,
+                file_name_option: self.file_name_option,
+                title_option: None,
+                performer_option: None,
+                mime_type,
+                duration_sec_option: None,
+                thumbnail_path_option: None,
+            })
+        } else {
+            content!(File {
+                path_option: self.path_option,
+                file_name_option: self.file_name_option,
+                mime_type_option: self.mime_type_option,
+                thumbnail_path_option: None,
+            })
+        }
+    }
+}
+
+/// Returns attachments grouped by message ID.
+/// Attachment file is only present in backup if it was downloaded.
+fn parse_attachments(conn: &Connection, backup: &RestoredBackup) -> Result<HashMap<i64, Vec<Attachment>>> {
+    // "attachment" table was called "part" before 2023
+    let mut stmt = conn.prepare(r"SELECT * FROM attachment ORDER BY _id")
+        .or_else(|_| conn.prepare(r"SELECT * FROM part ORDER BY _id"))?;
+
+    let mut result: HashMap<i64, Vec<Attachment>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get("_id")?;
+        let message_id: i64 = get_any(row, &["message_id", "mid"])?;
+        let is_sticker = get_any::<Option<String>>(row, &["sticker_pack_id"]).ok().flatten().is_some();
+        result.entry(message_id).or_default().push(Attachment {
+            path_option: backup.attachment_paths.get(&id).cloned(),
+            file_name_option: get_non_empty(row, &["file_name"])?,
+            mime_type_option: get_non_empty(row, &["content_type", "ct"])?,
+            width: row.get::<_, Option<i32>>("width")?.unwrap_or(0),
+            height: row.get::<_, Option<i32>>("height")?.unwrap_or(0),
+            is_voice_note: row.get::<_, Option<i32>>("voice_note")?.unwrap_or(0) == 1,
+            sticker_emoji_option: if is_sticker { Some(get_non_empty(row, &["sticker_emoji"])?) } else { None },
+        });
+    }
+    Ok(result)
+}
+
+/// Signal stores one entry per reacting user, we group them by emoji.
+fn parse_reactions(conn: &Connection, users: &Users) -> Result<HashMap<i64, Vec<Reaction>>> {
+    let mut result: HashMap<i64, Vec<Reaction>> = HashMap::new();
+
+    let mut stmt = conn.prepare(r"SELECT * FROM reaction ORDER BY _id")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let message_id: i64 = row.get("message_id")?;
+        let emoji: String = row.get("emoji")?;
+        let author_id: RecipientId = row.get("author_id")?;
+        let from_id = users.get(&author_id).ok_or_else(|| anyhow!("Unknown reaction author {author_id}"))?.id;
+
+        let reactions = result.entry(message_id).or_default();
+        match reactions.iter_mut().find(|r| r.emoji_option.as_ref() == Some(&emoji)) {
+            Some(reaction) => {
+                reaction.from_ids.push(from_id);
+                reaction.count += 1;
+            }
+            None => reactions.push(Reaction {
+                emoji_option: Some(emoji),
+                custom_emoji_id_option: None,
+                from_ids: vec![from_id],
+                count: 1,
+            }),
+        }
+    }
+    Ok(result)
+}
+
+/// Unlike styles, mentions are not stored in message body ranges.
+fn parse_mentions(conn: &Connection, users: &Users) -> Result<HashMap<i64, Vec<BodyRange>>> {
+    let mut result: HashMap<i64, Vec<BodyRange>> = HashMap::new();
+
+    let mut stmt = conn.prepare(r"SELECT * FROM mention ORDER BY _id")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let recipient_id: RecipientId = row.get("recipient_id")?;
+        let name = users.get(&recipient_id).map(|u| u.pretty_name()).unwrap_or_else(|| UNNAMED.to_owned());
+        result.entry(row.get("message_id")?).or_default().push(BodyRange {
+            start: row.get::<_, i64>("range_start")?.max(0) as usize,
+            length: row.get::<_, i64>("range_length")?.max(0) as usize,
+            kind: BodyRangeKind::Mention(name),
+        });
+    }
+    Ok(result)
+}
+
+mod cipher {
+    use hmac::Hmac;
+    use sha2::{Digest, Sha256, Sha512};
+
+    pub use aes::cipher::{BlockEncrypt, KeyInit};
+    pub use aes::Aes256;
+    pub use hmac::Mac;
+
+    pub const CIPHER_KEY_SIZE: usize = 32;
+    pub const MAC_KEY_SIZE: usize = 32;
+    pub const AES_BLOCK_SIZE: usize = 16;
+    /// HMAC-SHA256 is truncated to this size
+    pub const MAC_SIZE: usize = 10;
+
+    pub type HmacSha256 = Hmac<Sha256>;
+
+    pub fn new_mac(key: &[u8]) -> HmacSha256 {
+        <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC can take key of any size")
+    }
+
+    /// Passphrase is hashed with salt using many rounds of SHA-512, result is then expanded using HKDF.
+    pub fn derive_keys(passphrase: &str, salt: &[u8]) -> ([u8; CIPHER_KEY_SIZE], [u8; MAC_KEY_SIZE]) {
+        const ROUNDS: usize = 250_000;
+        const HKDF_INFO: &[u8] = b"Backup Export";
+
+        // Passphrase is displayed in groups of 5 digits
+        let passphrase = passphrase.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>();
+        let passphrase = passphrase.as_bytes();
+
+        let mut digest = Sha512::new();
+        digest.update(salt);
+        let mut hash = passphrase.to_vec();
+        for _ in 0..ROUNDS {
+            digest.update(&hash);
+            digest.update(passphrase);
+            hash = digest.finalize_reset().to_vec();
+        }
+
+        let derived = hkdf_sha256(&hash[..32], HKDF_INFO, CIPHER_KEY_SIZE + MAC_KEY_SIZE);
+        (derived[..CIPHER_KEY_SIZE].try_into().unwrap(), derived[CIPHER_KEY_SIZE..].try_into().unwrap())
+    }
+
+    /// HKDF (RFC 5869) with empty salt
+    fn hkdf_sha256(ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+        let prk = {
+            let mut mac = new_mac(&[0u8; 32]);
+            mac.update(ikm);
+            mac.finalize().into_bytes()
+        };
+
+        let mut result = vec![];
+        let mut block: Vec<u8> = vec![];
+        let mut i = 1u8;
+        while result.len() < len {
+            let mut mac = new_mac(&prk);
+            mac.update(&block);
+            mac.update(info);
+            mac.update(&[i]);
+            block = mac.finalize().into_bytes().to_vec();
+            result.extend_from_slice(&block);
+            i += 1;
+        }
+        result.truncate(len);
+        result
+    }
+
+    /// AES-256 in CTR mode with 128-bit big-endian counter, same as Java's `AES/CTR/NoPadding`.
+    /// Encryption and decryption are the same operation.
+    pub struct Aes256Ctr<'a> {
+        cipher: &'a Aes256,
+        counter: u128,
+        keystream: [u8; AES_BLOCK_SIZE],
+        keystream_pos: usize,
+    }
+
+    impl<'a> Aes256Ctr<'a> {
+        pub fn new(cipher: &'a Aes256, iv: [u8; AES_BLOCK_SIZE]) -> Self {
+            Aes256Ctr { cipher, counter: u128::from_be_bytes(iv), keystream: [0; AES_BLOCK_SIZE], keystream_pos: AES_BLOCK_SIZE }
+        }
+
+        pub fn apply(&mut self, data: &mut [u8]) {
+            for byte in data {
+                if self.keystream_pos == AES_BLOCK_SIZE {
+                    let mut block = self.counter.to_be_bytes().into();
+                    self.cipher.encrypt_block(&mut block);
+                    self.keystream = block.into();
+                    self.keystream_pos = 0;
+                    self.counter = self.counter.wrapping_add(1);
+                }
+                *byte ^= self.keystream[self.keystream_pos];
+                self.keystream_pos += 1;
+            }
+        }
+    }
+}
+
+/// Subset of Backups.proto from Signal Android
+mod protobuf {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SqlParameter {
+        // (sic!)
+        #[prost(string, optional, tag = "1")]
+        pub string_paramter: Option<String>,
+        #[prost(uint64, optional, tag = "2")]
+        pub integer_parameter: Option<u64>,
+        #[prost(double, optional, tag = "3")]
+        pub double_parameter: Option<f64>,
+        #[prost(bytes = "vec", optional, tag = "4")]
+        pub blob_parameter: Option<Vec<u8>>,
+        #[prost(bool, optional, tag = "5")]
+        pub nullparameter: Option<bool>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SqlStatement {
+        #[prost(string, optional, tag = "1")]
+        pub statement: Option<String>,
+        #[prost(message, repeated, tag = "2")]
+        pub parameters: Vec<SqlParameter>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SharedPreference {
+        #[prost(string, optional, tag = "1")]
+        pub file: Option<String>,
+        #[prost(string, optional, tag = "2")]
+        pub key: Option<String>,
+        #[prost(string, optional, tag = "3")]
+        pub value: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Attachment {
+        #[prost(uint64, optional, tag = "1")]
+        pub row_id: Option<u64>,
+        #[prost(uint64, optional, tag = "2")]
+        pub attachment_id: Option<u64>,
+        #[prost(uint32, optional, tag = "3")]
+        pub length: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Sticker {
+        #[prost(uint64, optional, tag = "1")]
+        pub row_id: Option<u64>,
+        #[prost(uint32, optional, tag = "2")]
+        pub length: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Avatar {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(string, optional, tag = "3")]
+        pub recipient_id: Option<String>,
+        #[prost(uint32, optional, tag = "2")]
+        pub length: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DatabaseVersion {
+        #[prost(uint32, optional, tag = "1")]
+        pub version: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Header {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub iv: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub salt: Option<Vec<u8>>,
+        #[prost(uint32, optional, tag = "3")]
+        pub version: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KeyValue {
+        #[prost(string, optional, tag = "1")]
+        pub key: Option<String>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub blob_value: Option<Vec<u8>>,
+        #[prost(bool, optional, tag = "3")]
+        pub boolean_value: Option<bool>,
+        #[prost(float, optional, tag = "4")]
+        pub float_value: Option<f32>,
+        #[prost(int32, optional, tag = "5")]
+        pub integer_value: Option<i32>,
+        #[prost(int64, optional, tag = "6")]
+        pub long_value: Option<i64>,
+        #[prost(string, optional, tag = "7")]
+        pub string_value: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BackupFrame {
+        #[prost(message, optional, tag = "1")]
+        pub header: Option<Header>,
+        #[prost(message, optional, tag = "2")]
+        pub statement: Option<SqlStatement>,
+        #[prost(message, optional, tag = "3")]
+        pub preference: Option<SharedPreference>,
+        #[prost(message, optional, tag = "4")]
+        pub attachment: Option<Attachment>,
+        #[prost(message, optional, tag = "5")]
+        pub version: Option<DatabaseVersion>,
+        #[prost(bool, optional, tag = "6")]
+        pub end: Option<bool>,
+        #[prost(message, optional, tag = "7")]
+        pub avatar: Option<Avatar>,
+        #[prost(message, optional, tag = "8")]
+        pub sticker: Option<Sticker>,
+        #[prost(message, optional, tag = "9")]
+        pub key_value: Option<KeyValue>,
+    }
+
+    /// Stored in "message_ranges" column, from Database.proto
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BodyRangeList {
+        #[prost(message, repeated, tag = "1")]
+        pub ranges: Vec<BodyRangeListRange>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BodyRangeListRange {
+        #[prost(int32, tag = "1")]
+        pub start: i32,
+        #[prost(int32, tag = "2")]
+        pub length: i32,
+        #[prost(string, optional, tag = "3")]
+        pub mention_uuid: Option<String>,
+        /// BOLD = 0, ITALIC = 1, SPOILER = 2, STRIKETHROUGH = 3, MONOSPACE = 4
+        #[prost(int32, optional, tag = "4")]
+        pub style: Option<i32>,
+        #[prost(string, optional, tag = "5")]
+        pub link: Option<String>,
+    }
+}
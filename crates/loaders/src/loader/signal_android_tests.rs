@@ -0,0 +1,585 @@
+#![allow(unused_imports)]
+
+use super::*;
+use super::super::signal::{make_source_id, SignalDataLoader};
+
+use crate::entity_utils::*;
+use chat_history_manager_core::protobuf::history::content::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::message::*;
+use chat_history_manager_core::protobuf::history::message_service::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::User;
+use chat_history_manager_dao::ChatHistoryDao;
+
+use std::path::PathBuf;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+const LOADER: SignalAndroidDataLoader = SignalAndroidDataLoader;
+
+const PASSPHRASE: &str = "12345 67890 12345 67890 12345 67890";
+const BACKUP_FILE_NAME: &str = "signal-2026-10-17-12-00-00.backup";
+
+// Same as in Signal Desktop
+const MYSELF_ID: i64 = 3148627133722667954;
+const EEEEE_ID: i64 = 8531918205816895079;
+
+const SMS_ONLY_RECIPIENT_ID: i64 = 6;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2026_10() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let backup_path = create_backup(&tmp_dir.path, PASSPHRASE, "");
+    LOADER.looks_about_right(&backup_path)?;
+
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some(PASSPHRASE.to_owned()) };
+    let dao = LOADER.load(&feedback_client, &backup_path)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, MYSELF_ID);
+
+    // Blobs are decrypted into a directory of their own, nothing is written next to the backup
+    let ds_dir = TmpDir { path: dao.dataset_root(ds_uuid)?.0 };
+    assert_ne!(ds_dir.path, tmp_dir.path);
+    assert_eq!(fs::read_dir(&tmp_dir.path)?.count(), 1);
+    assert_eq!(fs::read(ds_dir.path.join("attachments/1"))?, b"photo");
+    assert_eq!(fs::read(ds_dir.path.join("attachments/2"))?, large_blob());
+    assert_eq!(fs::read(ds_dir.path.join("avatars/2"))?, b"avatar");
+    assert!(!ds_dir.path.join("attachments/4").exists());
+
+    let users = dao.users_single_ds();
+    assert_eq!(users.len(), 4);
+    assert_eq!(users[0], User {
+        ds_uuid: ds_uuid.clone(),
+        id: MYSELF_ID,
+        first_name_option: Some("Aaaaa".to_owned()),
+        last_name_option: Some("Aaaaaaaaaaa".to_owned()),
+        username_option: None,
+        phone_number_option: Some("+998911234567".to_owned()),
+        profile_pictures: vec![],
+    });
+    let eeeee = users.iter().find(|u| u.id == EEEEE_ID).unwrap();
+    assert_eq!(eeeee, &User {
+        ds_uuid: ds_uuid.clone(),
+        id: EEEEE_ID,
+        first_name_option: Some("Eeeee".to_owned()),
+        last_name_option: Some("Eeeeeeeeee".to_owned()),
+        username_option: Some("eeeee.01".to_owned()),
+        phone_number_option: Some("+79993334455".to_owned()),
+        profile_pictures: vec![ProfilePicture { path: "avatars/2".to_owned(), frame_option: None }],
+    });
+    let ccccc = users.iter().find(|u| u.first_name_option.as_deref() == Some("Ccccc Contact")).unwrap();
+    assert_eq!(ccccc.last_name_option, None);
+    let sms_only = users.iter().find(|u| u.id == SMS_ONLY_RECIPIENT_ID).unwrap();
+    assert_eq!(sms_only.first_name_option.as_deref(), Some("Sms Only"));
+    assert_eq!(sms_only.phone_number_option.as_deref(), Some("+15550001111"));
+
+    // Story distribution list is not a chat
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 2);
+
+    // Personal chat
+    {
+        let chat = &cwms[0].chat;
+        assert_eq!(chat, &Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: EEEEE_ID,
+            name_option: Some("Eeeee".to_owned()),
+            source_type: SourceType::Signal as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![MYSELF_ID, EEEEE_ID],
+            msg_count: 6,
+            main_chat_id: None,
+        });
+
+        let msgs = dao.first_messages(chat, 99999)?;
+        // Edited message keeps its original timestamp
+        assert_eq!(msgs.iter().map(|m| m.source_id_option.unwrap()).collect_vec(), vec![
+            make_source_id(1790000000000, UserId(EEEEE_ID)),
+            make_source_id(1790000050000, UserId(MYSELF_ID)),
+            make_source_id(1790000100000, UserId(EEEEE_ID)),
+            make_source_id(1790000200000, UserId(MYSELF_ID)),
+            make_source_id(1790000230000, UserId(EEEEE_ID)),
+            make_source_id(1790000300000, UserId(EEEEE_ID)),
+        ]);
+        assert_eq!(msgs.iter().map(|m| m.internal_id).collect_vec(), (0..6).collect_vec());
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            msgs[0].source_id_option,
+            1790000000,
+            UserId(EEEEE_ID),
+            vec![RichText::make_plain("Hello there".to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(Photo {
+                    path_option: Some("attachments/1".to_owned()),
+                    width: 150,
+                    height: 100,
+                    mime_type_option: Some("image/jpeg".to_owned()),
+                    is_one_time: false,
+                })],
+                reactions: vec![Reaction {
+                    emoji_option: Some("👍".to_owned()),
+                    custom_emoji_id_option: None,
+                    from_ids: vec![MYSELF_ID, EEEEE_ID],
+                    count: 2,
+                }],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+
+        assert_eq!(msgs[1], Message::new(
+            1,
+            msgs[1].source_id_option,
+            1790000050,
+            UserId(MYSELF_ID),
+            vec![
+                RichText::make_bold("Bold".to_owned()),
+                RichText::make_plain(" and ".to_owned()),
+                RichText::make_prefmt_inline("mono".to_owned()),
+            ],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: msgs[0].source_id_option,
+                contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+
+        assert_eq!(msgs[2].from_id, EEEEE_ID);
+        assert_eq!(msgs[2].typed(), &message_service!(PhoneCall(MessageServicePhoneCall {
+            duration_sec_option: None,
+            discard_reason_option: Some("hangup".to_owned()),
+            members: vec![],
+        })));
+
+        // Edited message is placed according to its original timestamp
+        assert_eq!(msgs[3].timestamp, 1790000200);
+        assert_eq!(msgs[3].text, vec![RichText::make_plain("Typo".to_owned())]);
+        let Some(Typed::Regular(mr)) = msgs[3].typed.as_ref() else { panic!() };
+        assert_eq!(mr.edit_timestamp_option, Some(1790000260));
+        assert_eq!(mr.reactions.len(), 1);
+
+        assert_eq!(msgs[4].text, vec![]);
+        assert_eq!(msgs[4].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(VoiceMsg {
+                path_option: Some("attachments/2".to_owned()),
+                file_name_option: None,
+                mime_type: "audio/aac".to_owned(),
+                duration_sec_option: None,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        let Some(Typed::Regular(mr)) = msgs[5].typed.as_ref() else { panic!() };
+        assert!(mr.is_deleted);
+    }
+
+    // Group chat
+    {
+        let chat = &cwms[1].chat;
+        assert_eq!(chat, &Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: group_chat_id(b"group-id"),
+            name_option: Some("Test Group".to_owned()),
+            source_type: SourceType::Signal as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![MYSELF_ID, EEEEE_ID, ccccc.id],
+            msg_count: 4,
+            main_chat_id: None,
+        });
+
+        let msgs = dao.first_messages(chat, 99999)?;
+        assert_eq!(msgs.iter().map(|m| m.source_id_option.unwrap()).collect_vec(), vec![
+            make_source_id(1790000600000, ccccc.id()),
+            make_source_id(1790000700000, UserId(MYSELF_ID)),
+            make_source_id(1790000800000, UserId(EEEEE_ID)),
+            make_source_id(1790000900000, UserId(EEEEE_ID)),
+        ]);
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            msgs[0].source_id_option,
+            1790000600,
+            UserId(ccccc.id),
+            vec![RichText::make_plain("Hi @Aaaaa Aaaaaaaaaaa!".to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(File {
+                    path_option: None,
+                    file_name_option: Some("doc.pdf".to_owned()),
+                    mime_type_option: Some("application/pdf".to_owned()),
+                    thumbnail_path_option: None,
+                })],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+
+        assert_eq!(msgs[1].from_id, MYSELF_ID);
+        assert_eq!(msgs[1].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Sticker {
+                path_option: Some("attachments/3".to_owned()),
+                file_name_option: None,
+                width: 512,
+                height: 512,
+                mime_type_option: Some("image/webp".to_owned()),
+                thumbnail_path_option: None,
+                emoji_option: Some("👌".to_owned()),
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        assert_eq!(msgs[2].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Photo {
+                path_option: None,
+                width: 640,
+                height: 480,
+                mime_type_option: Some("image/jpeg".to_owned()),
+                is_one_time: true,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        assert_eq!(msgs[3].from_id, EEEEE_ID);
+        assert_eq!(msgs[3].typed(), &message_service!(PhoneCall(MessageServicePhoneCall {
+            duration_sec_option: None,
+            discard_reason_option: None,
+            members: vec![],
+        })));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn loading_into_sqlite() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let backup_path = create_backup(&tmp_dir.path, PASSPHRASE, "");
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some(PASSPHRASE.to_owned()) };
+
+    let sqlite_dir = TmpDir::new();
+    let sqlite_dao = SqliteDao::create(&sqlite_dir.path.join(SqliteDao::FILENAME))?;
+    LOADER.load_into(&feedback_client, &backup_path, &mut sqlite_dao.sink()?)?;
+
+    // Blobs are copied into the dataset directory, decrypted ones are gone
+    let ds_uuid = sqlite_dao.datasets()?.remove(0).uuid;
+    let files = dataset_files(&sqlite_dao, &ds_uuid);
+    assert!(files.iter().all(|f| f.starts_with(&sqlite_dir.path)), "{files:?}");
+    let file_contents: Vec<Vec<u8>> = files.iter().filter(|f| f.exists()).map(fs::read).try_collect()?;
+    assert!(file_contents.contains(&b"photo".to_vec()));
+    assert!(file_contents.contains(&large_blob()));
+    assert!(file_contents.contains(&b"avatar".to_vec()));
+    assert_eq!(fs::read_dir(&tmp_dir.path)?.count(), 1);
+    let leftovers = fs::read_dir(std::env::temp_dir())?
+        .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(&format!("signal_{}_", ds_uuid.value)))
+        .count();
+    assert_eq!(leftovers, 0);
+
+    Ok(())
+}
+
+#[test]
+fn ids_match_signal_desktop() -> EmptyRes {
+    // Same messages as in Signal Desktop test databases
+    let tmp_dir = TmpDir::new();
+    let backup_path = create_backup(&tmp_dir.path, PASSPHRASE, r"
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (101, 1790000060000, 1790000061000, 2, 2, 4, 10485780, 'Hi');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (102, 1790000060000, 1790000060000, 2, 1, 4, 10485783, 'Same time');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (103, 1790000120000, 1790000120000, 2, 1, 4, 10485783, NULL);
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (104, 1695792334090, 1695792335000, 1, 2, 1, 10485780, NULL);
+");
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some(PASSPHRASE.to_owned()) };
+    let dao = LOADER.load(&feedback_client, &backup_path)?;
+    let _ds_dir = TmpDir { path: dao.dataset_root(&dao.ds_uuid())?.0 };
+
+    let source_ids = |dao: &InMemoryDao, chat: &Chat| -> Result<Vec<i64>> {
+        Ok(dao.first_messages(chat, 99999)?.iter().map(|m| m.source_id_option.unwrap()).collect_vec())
+    };
+    let android_cwms = dao.cwms_single_ds();
+
+    // Personal chat
+    let desktop_dao = load_signal_desktop("2024-08-macos")?;
+    let desktop_chat = &desktop_dao.cwms_single_ds()[0].chat;
+    let android_chat = &android_cwms.iter().find(|cwm| cwm.chat.id == desktop_chat.id).unwrap().chat;
+    let desktop_source_ids = source_ids(desktop_dao.as_ref(), desktop_chat)?;
+    assert!(source_ids(dao.as_ref(), android_chat)?.contains(&desktop_source_ids[2]));
+
+    // Group chat, messages at the same time are told apart by their authors
+    let desktop_dao = load_signal_desktop("2026-10-macos-groups")?;
+    let desktop_chat = &desktop_dao.cwms_single_ds()[0].chat;
+    let android_chat = &android_cwms.iter().find(|cwm| cwm.chat.id == desktop_chat.id).unwrap().chat;
+    let desktop_source_ids = source_ids(desktop_dao.as_ref(), desktop_chat)?;
+    let android_source_ids = source_ids(dao.as_ref(), android_chat)?;
+    for desktop_source_id in &desktop_source_ids[1..=3] {
+        assert!(android_source_ids.contains(desktop_source_id));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn ambiguous_source_ids() -> EmptyRes {
+    // Clients might list messages sharing timestamp and author in any order, so these can't be identified
+    let tmp_dir = TmpDir::new();
+    let backup_path = create_backup(&tmp_dir.path, PASSPHRASE, r"
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (101, 1790003000000, 1790003000000, 1, 2, 1, 10485780, 'First');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (102, 1790003000000, 1790003000000, 1, 2, 1, 10485780, 'Second');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body)
+VALUES (103, 1790003000000, 1790003000000, 1, 1, 2, 10485783, 'Same time, other author');
+INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, body, quote_id, quote_author)
+VALUES (104, 1790003060000, 1790003060000, 1, 1, 2, 10485783, 'Reply', 1790003000000, 2);
+");
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some(PASSPHRASE.to_owned()) };
+    let dao = LOADER.load(&feedback_client, &backup_path)?;
+    let _ds_dir = TmpDir { path: dao.dataset_root(&dao.ds_uuid())?.0 };
+
+    let cwms = dao.cwms_single_ds();
+    let chat = &cwms.iter().find(|cwm| cwm.chat.id == EEEEE_ID).unwrap().chat;
+    let msgs = dao.first_messages(chat, 99999)?;
+
+    let same_time_msgs = msgs.iter().filter(|m| m.timestamp == 1790003000).collect_vec();
+    assert_eq!(same_time_msgs.len(), 3);
+    for m in same_time_msgs {
+        let expected_source_id = if m.from_id == MYSELF_ID { Some(make_source_id(1790003000000, UserId(MYSELF_ID))) } else { None };
+        assert_eq!(m.source_id_option, expected_source_id);
+    }
+
+    let reply = msgs.iter().find(|m| m.timestamp == 1790003060).unwrap();
+    assert_eq!(reply.source_id_option, Some(make_source_id(1790003060000, UserId(MYSELF_ID))));
+    let message_regular_pat!(reply_to_message_id_option, ..) = reply.typed() else { unreachable!() };
+    assert_eq!(*reply_to_message_id_option, None);
+
+    Ok(())
+}
+
+#[test]
+fn wrong_passphrase() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let backup_path = create_backup(&tmp_dir.path, PASSPHRASE, "");
+    LOADER.looks_about_right(&backup_path)?;
+
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some("0".repeat(30)) };
+    let err = LOADER.load(&feedback_client, &backup_path).err().expect("Loading should fail");
+    assert!(format!("{err:?}").contains("wrong passphrase"), "Unexpected error: {err:?}");
+    Ok(())
+}
+
+#[test]
+fn rejecting_other_files() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("not-signal.backup");
+    fs::write(&path, b"\x00\x00\x00\x10Some other data!")?;
+    assert!(LOADER.looks_about_right(&path).is_err());
+
+    let path = tmp_dir.path.join("too-short.backup");
+    fs::write(&path, b"\x00\x00")?;
+    assert!(LOADER.looks_about_right(&path).is_err());
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+/// Loads Signal Desktop test database without attachments, not touching resources directory.
+fn load_signal_desktop(name_suffix: &str) -> Result<Box<InMemoryDao>> {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("plaintext.sqlite");
+    let sql = fs::read_to_string(resource(&format!("signal_{name_suffix}/plaintext.sql")))?;
+    Connection::open(&path)?.execute_batch(&sql)?;
+    SignalDataLoader.load(&NoFeedbackClient, &path)
+}
+
+/// Larger than a single read buffer
+fn large_blob() -> Vec<u8> {
+    (0..100_000).map(|i| (i % 251) as u8).collect_vec()
+}
+
+/// Creates a backup the same way Signal Android does it (with the same framing and encryption), from statements in
+/// `database.sql` followed by `extra_sql`, and a few extra frames.
+fn create_backup(dir: &Path, passphrase: &str, extra_sql: &str) -> PathBuf {
+    let sql = fs::read_to_string(resource("signal-android_2026-10/database.sql")).unwrap() + extra_sql;
+    let sql = sql.lines().filter(|l| !l.starts_with("--")).join("\n");
+
+    let mut writer = BackupWriter::new(passphrase);
+    writer.write_frame(BackupFrame {
+        version: Some(DatabaseVersion { version: Some(250) }),
+        ..Default::default()
+    });
+    writer.write_frame(BackupFrame {
+        key_value: Some(KeyValue {
+            key: Some(MYSELF_ACI_KEY.to_owned()),
+            string_value: Some("b22bb22b-b22b-b22b-b22b-b22bb22bb22b".to_owned()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    for statement in sql.split(";\n").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        writer.write_frame(statement_frame(statement, vec![]));
+    }
+
+    let int = |v: i64| SqlParameter { integer_parameter: Some(v as u64), ..Default::default() };
+    let ranges = BodyRangeList {
+        ranges: vec![
+            BodyRangeListRange { start: 0, length: 4, style: Some(0 /* BOLD */), ..Default::default() },
+            BodyRangeListRange { start: 9, length: 4, style: Some(4 /* MONOSPACE */), ..Default::default() },
+        ]
+    };
+    writer.write_frame(statement_frame(
+        "INSERT INTO message (_id, date_sent, date_received, thread_id, from_recipient_id, to_recipient_id, type, \
+                              body, quote_id, quote_author, quote_body, message_ranges) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        vec![
+            int(2), int(1790000050000), int(1790000050000), int(1), int(1), int(2), int(10485783),
+            SqlParameter { string_paramter: Some("Bold and mono".to_owned()), ..Default::default() },
+            int(1790000000000), int(2),
+            SqlParameter { nullparameter: Some(true), ..Default::default() },
+            SqlParameter { blob_parameter: Some(ranges.encode_to_vec()), ..Default::default() },
+        ],
+    ));
+
+    let attachment = |row_id: u64, data: &[u8]| BackupFrame {
+        attachment: Some(protobuf::Attachment { row_id: Some(row_id), attachment_id: Some(row_id), length: Some(data.len() as u32) }),
+        ..Default::default()
+    };
+    writer.write_blob(attachment(1, b"photo"), b"photo".to_vec());
+    writer.write_blob(attachment(2, &large_blob()), large_blob());
+    writer.write_blob(attachment(3, b"sticker"), b"sticker".to_vec());
+    writer.write_blob(BackupFrame {
+        avatar: Some(protobuf::Avatar { name: None, recipient_id: Some("2".to_owned()), length: Some(6) }),
+        ..Default::default()
+    }, b"avatar".to_vec());
+    writer.write_blob(BackupFrame {
+        sticker: Some(protobuf::Sticker { row_id: Some(1), length: Some(16) }),
+        ..Default::default()
+    }, b"installed sticker"[..16].to_vec());
+
+    writer.write_frame(BackupFrame { end: Some(true), ..Default::default() });
+
+    let path = dir.join(BACKUP_FILE_NAME);
+    fs::write(&path, writer.output).unwrap();
+    path
+}
+
+fn statement_frame(sql: &str, parameters: Vec<SqlParameter>) -> BackupFrame {
+    BackupFrame {
+        statement: Some(SqlStatement { statement: Some(sql.to_owned()), parameters }),
+        ..Default::default()
+    }
+}
+
+/// Mirror of [BackupStream], producing version 1 backups.
+struct BackupWriter {
+    output: Vec<u8>,
+    cipher: Aes256,
+    mac_key: [u8; MAC_KEY_SIZE],
+    counter: u32,
+}
+
+impl BackupWriter {
+    const IV: &'static [u8; AES_BLOCK_SIZE] = b"\xFF\xFF\xFF\xFEabcdefghijkl";
+    const SALT: &'static [u8] = b"salt-salt-salt-salt-salt-salt-sa";
+
+    fn new(passphrase: &str) -> Self {
+        let header = BackupFrame {
+            header: Some(Header { iv: Some(Self::IV.to_vec()), salt: Some(Self::SALT.to_vec()), version: Some(1) }),
+            ..Default::default()
+        }.encode_to_vec();
+
+        let mut output = (header.len() as u32).to_be_bytes().to_vec();
+        output.extend_from_slice(&header);
+
+        let (cipher_key, mac_key) = derive_keys(passphrase, Self::SALT);
+        BackupWriter {
+            output,
+            cipher: Aes256::new(&cipher_key.into()),
+            mac_key,
+            // Counter overflow is expected to be handled
+            counter: u32::from_be_bytes(Self::IV[..4].try_into().unwrap()),
+        }
+    }
+
+    fn next_iv(&mut self) -> [u8; AES_BLOCK_SIZE] {
+        let mut iv = *Self::IV;
+        iv[..4].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter = self.counter.wrapping_add(1);
+        iv
+    }
+
+    fn write_frame(&mut self, frame: BackupFrame) {
+        let mut data = frame.encode_to_vec();
+        let mut len_bytes = ((data.len() + MAC_SIZE) as u32).to_be_bytes();
+
+        let iv = self.next_iv();
+        let mut ctr = Aes256Ctr::new(&self.cipher, iv);
+        ctr.apply(&mut len_bytes);
+        ctr.apply(&mut data);
+
+        let mut mac = new_mac(&self.mac_key);
+        mac.update(&len_bytes);
+        mac.update(&data);
+
+        self.output.extend_from_slice(&len_bytes);
+        self.output.extend_from_slice(&data);
+        self.output.extend_from_slice(&mac.finalize().into_bytes()[..MAC_SIZE]);
+    }
+
+    fn write_blob(&mut self, frame: BackupFrame, mut data: Vec<u8>) {
+        self.write_frame(frame);
+
+        let iv = self.next_iv();
+        Aes256Ctr::new(&self.cipher, iv).apply(&mut data);
+
+        let mut mac = new_mac(&self.mac_key);
+        mac.update(&iv);
+        mac.update(&data);
+
+        self.output.extend_from_slice(&data);
+        self.output.extend_from_slice(&mac.finalize().into_bytes()[..MAC_SIZE]);
+    }
+}
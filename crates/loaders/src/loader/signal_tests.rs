@@ -54,7 +54,7 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
         let chat = cwm.chat;
         assert_eq!(chat, Chat {
             ds_uuid: ds_uuid.clone(),
-            id: 7993589098607472366,
+            id: member.id,
             name_option: Some("Eeeee".to_owned()),
             source_type: SourceType::Signal as i32,
             tpe: ChatType::Personal as i32,
@@ -69,7 +69,7 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
 
         assert_eq!(msgs[0], Message {
             internal_id: 0,
-            source_id_option: Some(147338767122554438),
+            source_id_option: Some(make_source_id(1685967643288, myself.id())),
             timestamp: 1685967643,
             from_id: myself.id,
             text: vec![RichText::make_plain("Photo caption".to_owned())],
@@ -88,12 +88,28 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![
+                    Reaction {
+                        emoji_option: Some("👍".to_owned()),
+                        custom_emoji_id_option: None,
+                        from_ids: vec![member.id, myself.id],
+                        count: 2,
+                    },
+                    Reaction {
+                        emoji_option: Some("❤️".to_owned()),
+                        custom_emoji_id_option: None,
+                        from_ids: vec![member.id],
+                        count: 1,
+                    },
+                ],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
 
         assert_eq!(msgs[1], Message {
             internal_id: 1,
-            source_id_option: Some(7293848439858989906),
+            source_id_option: Some(make_source_id(1695224029560, myself.id())),
             timestamp: 1695224029,
             from_id: myself.id,
             text: vec![],
@@ -107,7 +123,7 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
 
         assert_eq!(msgs[2], Message {
             internal_id: 2,
-            source_id_option: Some(1675761544200081935),
+            source_id_option: Some(make_source_id(1695792334090, member.id())),
             timestamp: 1695792334,
             from_id: member.id,
             text: vec![],
@@ -125,12 +141,15 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
                         duration_sec_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
 
         assert_eq!(msgs[3], Message {
             internal_id: 3,
-            source_id_option: Some(7443282593181227665),
+            source_id_option: Some(make_source_id(1696176322229, member.id())),
             timestamp: 1696176322,
             from_id: member.id,
             text: vec![],
@@ -166,12 +185,15 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
 
         assert_eq!(msgs[4], Message {
             internal_id: 4,
-            source_id_option: Some(7080603443088336461),
+            source_id_option: Some(make_source_id(1696178282339, myself.id())),
             timestamp: 1696178282,
             from_id: myself.id,
             text: vec![RichText::make_plain("Edited message, final version".to_owned())],
@@ -186,6 +208,131 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_2026_10_macos_groups() -> EmptyRes {
+    let loader = SignalDataLoader;
+    let (res, db_dir) =
+        create_databases(RESOURCE_DIR, "2026-10-macos-groups", "sql", ".sqlite", PLAINTEXT_DB_FILENAME);
+    let root_dir = db_dir.path.parent().unwrap();
+    let _attachments_dir = TmpDir::new_at(root_dir.join(DECRYPTED_ATTACHMENTS_DIR_NAME));
+
+    loader.looks_about_right(&res)?;
+    let dao = loader.load(&NoFeedbackClient, &res)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, expected_myself(ds_uuid));
+
+    let users = dao.users_single_ds();
+    assert_eq!(users.len(), 3);
+    let member = users.iter().find(|u| u.first_name_option.as_deref() == Some("Eeeee")).unwrap();
+    let former_member = users.iter().find(|u| u.first_name_option.as_deref() == Some("Ccccc")).unwrap();
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 1);
+
+    let chat = &cwms[0].chat;
+    assert_eq!(chat, &Chat {
+        ds_uuid: ds_uuid.clone(),
+        id: group_chat_id(b"group-id"),
+        name_option: Some("Renamed Group".to_owned()),
+        source_type: SourceType::Signal as i32,
+        tpe: ChatType::PrivateGroup as i32,
+        img_path_option: None,
+        member_ids: vec![myself.id, member.id, former_member.id],
+        msg_count: 9,
+        main_chat_id: None,
+    });
+
+    let msgs = dao.first_messages(chat, 99999)?;
+    assert_eq!(msgs.len() as i32, chat.msg_count);
+
+    assert_eq!(msgs[0].from_id, myself.id);
+    assert_eq!(msgs[0].timestamp, 1790000000);
+    assert_eq!(msgs[0].typed(), &message_service!(GroupCreate(MessageServiceGroupCreate {
+        title: "Renamed Group".to_owned(),
+        members: vec!["Eeeee Eeeeeeeeee".to_owned()],
+    })));
+
+    assert_eq!(msgs[1], Message::new(
+        1,
+        Some(make_source_id(1790000060000, member.id())),
+        1790000060,
+        member.id(),
+        vec![
+            RichText::make_plain("👋 Hi @Aaaaa! ".to_owned()),
+            RichText::make_bold("Bold, both".to_owned()),
+            RichText::make_plain(", ".to_owned()),
+            RichText::make_prefmt_inline("mono".to_owned()),
+            RichText::make_plain(" and ".to_owned()),
+            RichText::make_spoiler("secret".to_owned()),
+            RichText::make_plain(".".to_owned()),
+        ],
+        message_regular! {
+            reactions: vec![Reaction {
+                emoji_option: Some("👍".to_owned()),
+                custom_emoji_id_option: None,
+                from_ids: vec![myself.id],
+                count: 1,
+            }],
+            ..Default::default()
+        },
+    ));
+
+    assert_eq!(msgs[2].from_id, myself.id);
+    assert_eq!(msgs[2].timestamp, msgs[1].timestamp);
+
+    // Reply is resolved using both timestamp and author
+    assert_eq!(msgs[3], Message::new(
+        3,
+        Some(make_source_id(1790000120000, myself.id())),
+        1790000120,
+        myself.id(),
+        vec![],
+        message_regular! {
+            reply_to_message_id_option: msgs[1].source_id_option,
+            contents: vec![content!(Sticker {
+                path_option: Some(format!("{DECRYPTED_ATTACHMENTS_DIR_NAME}/st/sticker-1")),
+                file_name_option: None,
+                width: 512,
+                height: 512,
+                mime_type_option: Some("image/webp".to_owned()),
+                thumbnail_path_option: None,
+                emoji_option: Some("👌".to_owned()),
+            })],
+            ..Default::default()
+        },
+    ));
+    let sticker_bytes = fs::read(root_dir.join(DECRYPTED_ATTACHMENTS_DIR_NAME).join("st/sticker-1"))?;
+    assert_eq!(sticker_bytes, fs::read(resource("signal_2026-10-macos-groups/_unencrypted_files/st/sticker-1"))?);
+
+    // Author is unknown, so the one who joined is used
+    assert_eq!(msgs[4].from_id, former_member.id);
+    assert_eq!(msgs[4].typed(), &message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
+        members: vec!["Ccccc".to_owned()],
+    })));
+
+    assert_eq!(msgs[5].from_id, former_member.id);
+    assert_eq!(msgs[5].text, vec![RichText::make_plain("Hello".to_owned())]);
+
+    assert_eq!(msgs[6].from_id, member.id);
+    assert_eq!(msgs[6].typed(), &message_service!(GroupEditTitle(MessageServiceGroupEditTitle {
+        title: "Renamed Group".to_owned(),
+    })));
+
+    assert_eq!(msgs[7].from_id, former_member.id);
+    assert_eq!(msgs[7].typed(), &message_service!(GroupRemoveMembers(MessageServiceGroupRemoveMembers {
+        members: vec!["Ccccc".to_owned()],
+    })));
+
+    // Permission change and disappearing messages timer are skipped
+    assert_eq!(msgs[8].from_id, myself.id);
+    assert_eq!(msgs[8].timestamp, 1790000540);
+    assert_eq!(msgs[8].typed(), &message_service!(GroupDeletePhoto(MessageServiceGroupDeletePhoto {})));
+
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -249,6 +396,7 @@ fn encrypt_linked_file(path: &str,
     let target_data_len = AES_BLOCK_SIZE + dst_data_len + SHA256_SIZE;
     let mut target_data = vec![0; target_data_len];
     target_data[..AES_BLOCK_SIZE].copy_from_slice(IV);
+    target_data[AES_BLOCK_SIZE..(AES_BLOCK_SIZE + src_data_len)].copy_from_slice(&src_data);
     let hmac = {
         let data = &mut target_data[AES_BLOCK_SIZE..(target_data_len - SHA256_SIZE)];
         assert_eq!(data.len() % AES_BLOCK_SIZE, 0, "Invalid attachment data length");
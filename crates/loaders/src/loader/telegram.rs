@@ -3,19 +3,23 @@ use crate::prelude::*;
 // Reexporting JSON utils for simplicity.
 pub use crate::utils::json_utils::*;
 
-use std::fs;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
 use std::num::ParseIntError;
-use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use chat_history_manager_dao::in_memory_dao::InMemoryDao;
+use chat_history_manager_dao::BATCH_SIZE;
 use chrono::NaiveDate;
 use lazy_static::lazy_static;
 use simd_json::borrowed::Object;
 use simd_json::prelude::*;
 use simd_json::BorrowedValue;
 
+use crate::utils::json_stream::JsonStreamReader;
+
 mod parser_full;
 mod parser_single;
 #[cfg(test)]
@@ -32,6 +36,10 @@ pub const PERSONAL_CHAT_ID_SHIFT: i64 = 0x100000000_i64;
 /// Starting with Telegram 2021-05, personal chat IDs are un-shifted by this value
 pub const GROUP_CHAT_ID_SHIFT: i64 = PERSONAL_CHAT_ID_SHIFT * 2;
 
+/// Forum topics are represented as separate chats with IDs `forum_chat_id * TOPIC_CHAT_ID_MULTIPLIER + topic_id`,
+/// topic ID being a source ID of a "topic_created" message.
+pub const TOPIC_CHAT_ID_MULTIPLIER: i64 = 100_000_000;
+
 const RESULT_JSON: &str = "result.json";
 
 pub struct TelegramDataLoader;
@@ -51,12 +59,22 @@ impl DataLoader for TelegramDataLoader {
     }
 
     fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
-        parse_telegram_file(feedback_client, path, ds)
+        super::load_in_memory(|sink| parse_telegram_file(feedback_client, path, ds, sink))
+    }
+
+    fn load_into_inner(&self,
+                       feedback_client: &dyn FeedbackClientSync,
+                       path: &Path,
+                       ds: Dataset,
+                       sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        parse_telegram_file(feedback_client, path, ds, sink)
     }
 }
 
 type CB<'a> = ParseCallback<'a>;
 
+type JsonReader = JsonStreamReader<BufReader<File>>;
+
 #[derive(Default, Debug)]
 struct Users {
     id_to_user: HashMap<UserId, User, Hasher>,
@@ -142,13 +160,11 @@ impl Users {
 enum ShouldProceed {
     ProceedMessage { text_prefix: Option<String> },
     SkipMessage,
-    SkipChat,
 }
 
 enum ParsedMessage {
     Ok(Box<Message>),
     SkipMessage,
-    SkipChat,
 }
 
 /// Users whose ID has been normalized according to this parser's rules (see [USER_ID_SHIFT])
@@ -169,7 +185,10 @@ fn get_real_path(path: &Path) -> PathBuf {
     }
 }
 
-fn parse_telegram_file(feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+fn parse_telegram_file(feedback_client: &dyn FeedbackClientSync,
+                       path: &Path,
+                       ds: Dataset,
+                       sink: &mut dyn ChatHistorySink) -> EmptyRes {
     let path = get_real_path(path);
     assert!(path.exists()); // Should be checked by looks_about_right already.
 
@@ -178,27 +197,35 @@ fn parse_telegram_file(feedback_client: &dyn FeedbackClientSync, path: &Path, ds
 
     let start_time = Instant::now();
 
-    let mut file_content = fs::read(&path)?;
-    let parsed = simd_json::to_borrowed_value(&mut file_content)?;
+    // File is never loaded whole, instead it's streamed through - first to get an overview, then to actually parse it
+    let overview = scan_overview(&path)?;
 
-    log::info!("Parsed in {} ms", start_time.elapsed().as_millis());
+    log::info!("Scanned in {} ms", start_time.elapsed().as_millis());
 
     let start_time = Instant::now();
-    let root_obj = as_object!(parsed, "root");
+    let mut json = open_json(&path)?;
 
+    let ds_uuid = ds.uuid.clone();
     let mut myself = User {
-        ds_uuid: ds.uuid.clone(),
+        ds_uuid: ds_uuid.clone(),
         ..Default::default()
     };
 
+    let parent_name = path_file_name(path.parent().unwrap())?;
+    sink.begin_dataset(format!("Telegram ({})", parent_name), ds, path.parent().unwrap())?;
+
+    let mut chats_sink = ChatsSink { sink, chats: vec![] };
     let single_chat_keys = HashSet::from(["name", "type", "id", "messages"]);
-    let keys = root_obj.keys().map(|s| s.deref()).collect::<HashSet<_>>();
-    let (users, chats_with_messages) =
+    let keys = overview.root_keys.iter().map(|s| s.as_str()).collect::<HashSet<_>>();
+    let users =
         if single_chat_keys.is_superset(&keys) {
-            parser_single::parse(feedback_client, root_obj, &ds.uuid, &mut myself)?
+            parser_single::parse(feedback_client, &mut json, overview.single_chat_streamable, &ds_uuid, &mut myself,
+                                 &mut chats_sink)?
         } else {
-            parser_full::parse(feedback_client, root_obj, &ds.uuid, &mut myself)?
+            parser_full::parse(feedback_client, &mut json, overview.chat_headers, &ds_uuid, &mut myself, &mut chats_sink)?
         };
+    let ChatsSink { sink, chats } = chats_sink;
+    json.finish()?;
 
     log::info!("Processed in {} ms", start_time.elapsed().as_millis());
 
@@ -210,32 +237,178 @@ fn parse_telegram_file(feedback_client: &dyn FeedbackClientSync, path: &Path, ds
     }
 
     // Sanity check: every chat member is supposed to have an associated user.
-    for cwm in &chats_with_messages {
-        for member_id in cwm.chat.member_ids() {
+    for chat in &chats {
+        for member_id in chat.member_ids() {
             if !users.id_to_user.contains_key(&member_id) {
                 bail!("No member with id={} found for chat with id={} '{}'",
-                      *member_id, cwm.chat.id, name_or_unnamed(&cwm.chat.name_option));
+                      *member_id, chat.id, name_or_unnamed(&chat.name_option));
             }
         }
     }
 
-    let mut users = users.id_to_user.into_values().collect_vec();
+    // Some users might be added by chats that were skipped from the datasets
+    let member_ids: HashSet<i64, Hasher> = chats.iter().flat_map(|c| &c.member_ids).copied().collect();
+    let mut users = users.id_to_user.into_values()
+        .filter(|u| u.id == myself.id || member_ids.contains(&u.id))
+        .collect_vec();
 
     // Set myself to be a first member (not required by convention but to match existing behaviour).
     users.sort_by_key(|u| if u.id == myself.id { *UserId::MIN } else { u.id });
 
-    let parent_name = path_file_name(path.parent().unwrap())?;
-    let mut result = Box::new(InMemoryDao::new_single(
-        format!("Telegram ({})", parent_name),
-        ds,
-        path.parent().unwrap().to_path_buf(),
-        myself.id(),
-        users,
-        chats_with_messages,
-    ));
-    // Some users might be added by chats that were skipped from the datasets
-    result.remove_orphan_users();
-    Ok(result)
+    sink.add_users(users, myself.id())?;
+    sink.finish_dataset()
+}
+
+/// Pushes parsed chats into the actual sink right away, remembering them for the final checks.
+struct ChatsSink<'a> {
+    sink: &'a mut dyn ChatHistorySink,
+    chats: Vec<Chat>,
+}
+
+impl ChatsSink<'_> {
+    fn push(&mut self, cwm: ChatWithMessages) -> EmptyRes {
+        self.chats.push(cwm.chat.clone());
+        push_chat(self.sink, cwm.chat, cwm.messages)
+    }
+
+    /// Adds a chat before its messages are parsed, it's then completed by [Self::finish_chat].
+    fn add_chat(&mut self, chat: Chat) -> EmptyRes {
+        self.sink.add_chat(chat)
+    }
+
+    fn add_messages(&mut self, chat: &Chat, msgs: Vec<Message>) -> EmptyRes {
+        self.sink.add_messages(chat, msgs)
+    }
+
+    fn finish_chat(&mut self, chat: Chat) -> EmptyRes {
+        self.sink.finish_chat(&chat)?;
+        self.chats.push(chat);
+        Ok(())
+    }
+}
+
+/// What's known about the file before parsing it, gathered by quickly streaming through it.
+struct FileOverview {
+    root_keys: Vec<String>,
+    /// For the full export, headers of all chats in the list, in order
+    chat_headers: Vec<ChatHeader>,
+    /// For the single chat export, whether its messages are streamable (see [scan_messages])
+    single_chat_streamable: bool,
+}
+
+struct ChatHeader {
+    json_path: String,
+    /// Present only for chats having a name, their ID is then a user ID of the other party
+    short_user_option: Option<ShortUser>,
+    /// See [scan_messages]
+    streamable: bool,
+}
+
+fn open_json(path: &Path) -> Result<JsonReader> {
+    Ok(JsonStreamReader::new(BufReader::new(File::open(path)?)))
+}
+
+fn scan_overview(path: &Path) -> Result<FileOverview> {
+    let mut json = open_json(path)?;
+    let mut root_keys = vec![];
+    let mut chat_headers = vec![];
+    let mut single_chat_streamable = false;
+    json.begin_object()?;
+    while let Some(key) = json.next_key()? {
+        if key == "messages" {
+            single_chat_streamable = scan_messages(&mut json)?;
+        } else if key == "chats" {
+            json.begin_object()?;
+            while let Some(key) = json.next_key()? {
+                if key != "list" {
+                    json.skip_value()?;
+                    continue;
+                }
+                json.begin_array()?;
+                while json.has_next_element()? {
+                    chat_headers.push(scan_chat_header(&mut json)?);
+                }
+            }
+        } else {
+            json.skip_value()?;
+        }
+        root_keys.push(key);
+    }
+    json.finish()?;
+    Ok(FileOverview { root_keys, chat_headers, single_chat_streamable })
+}
+
+/// Reads chat's ID and name, and checks its messages, skipping everything else.
+fn scan_chat_header(json: &mut JsonReader) -> Result<ChatHeader> {
+    let json_path = "chats.chat";
+    let mut id_bytes = None;
+    let mut name_bytes = None;
+    let mut streamable = false;
+    json.begin_object()?;
+    while let Some(key) = json.next_key()? {
+        match key.as_str() {
+            "id" => id_bytes = Some(json.read_raw_value()?),
+            "name" => name_bytes = Some(json.read_raw_value()?),
+            "messages" => streamable = scan_messages(json)?,
+            _ => json.skip_value()?,
+        }
+    }
+    let mut id_bytes = id_bytes.with_context(|| format!("{json_path}.id not found"))?;
+    let id = simd_json::to_borrowed_value(&mut id_bytes)?;
+    match name_bytes {
+        // Name will not be present for saved messages
+        None => Ok(ChatHeader { json_path: format!("{json_path}[#{id}]"), short_user_option: None, streamable }),
+        Some(mut name_bytes) => {
+            let name = simd_json::to_borrowed_value(&mut name_bytes)?;
+            let json_path = format!("{json_path}[{name}]");
+            let short_user = ShortUser {
+                id: parse_user_id(&id)?,
+                full_name_option: as_string_option!(&name, json_path, "name"),
+            };
+            // Doesn't really make sense to pre-populate users without names.
+            let short_user_option = short_user.full_name_option.is_some().then_some(short_user);
+            Ok(ChatHeader { json_path, short_user_option, streamable })
+        }
+    }
+}
+
+/// Whether chat messages could be pushed into a sink as they're parsed, without keeping the whole chat in memory.
+/// That's the case unless messages are out of chronological order or duplicated (see [deduplicate]),
+/// or the chat is a forum whose messages are to be split between topics.
+fn scan_messages(json: &mut JsonReader) -> Result<bool> {
+    let read_string = |json: &mut JsonReader| -> Result<String> {
+        Ok(simd_json::from_slice::<String>(&mut json.read_raw_value()?)?)
+    };
+    let mut streamable = true;
+    let mut last_timestamp = i64::MIN;
+    let mut seen_ids = HashSet::with_hasher(hasher());
+    json.begin_array()?;
+    while json.has_next_element()? {
+        if !streamable {
+            json.skip_value()?;
+            continue;
+        }
+        let mut unixtime_option = None;
+        let mut date_option = None;
+        json.begin_object()?;
+        while let Some(key) = json.next_key()? {
+            match key.as_str() {
+                "id" => streamable &= seen_ids.insert(simd_json::from_slice::<i64>(&mut json.read_raw_value()?)?),
+                "date_unixtime" => unixtime_option = Some(read_string(json)?),
+                "date" => date_option = Some(read_string(json)?),
+                "action" => streamable &= read_string(json)? != "topic_created",
+                _ => json.skip_value()?,
+            }
+        }
+        let timestamp = match (unixtime_option, date_option) {
+            (Some(unixtime), _) => parse_timestamp(&unixtime)?,
+            (None, Some(date)) => *parse_datetime(&date)?,
+            (None, None) => bail!("Message has no date!"),
+        };
+        streamable &= timestamp >= last_timestamp;
+        last_timestamp = timestamp;
+    }
+    Ok(streamable)
 }
 
 /** Returns a partially filled user. */
@@ -279,113 +452,262 @@ fn parse_contact(json_path: &str, bw: &BorrowedValue) -> Result<User> {
 
 /// `json_path` includes the chat itself.
 ///
-/// Returns None if the chat is skipped (e.g. is saved_messages).
+/// Pushes the chat into a sink, returning false if the chat is skipped (e.g. is saved_messages).
+/// If `streamable` (see [scan_messages]), messages are pushed in batches as they're parsed.
+/// Otherwise, they're all kept in memory to be sorted and deduplicated, and for forums, split between
+/// the chat itself (containing the "General" topic) and sub-chats for every other topic.
+#[allow(clippy::too_many_arguments)]
 fn parse_chat(
     feedback_client: &dyn FeedbackClientSync,
     json_path: &str,
-    chat_json: &Object,
+    json: &mut JsonReader,
+    streamable: bool,
     ds_uuid: &PbUuid,
     myself_id_option: Option<&UserId>,
-    users: &mut Users
-) -> Result<Option<ChatWithMessages>> {
+    users: &mut Users,
+    chats_sink: &mut ChatsSink,
+) -> Result<bool> {
     let mut chat: Chat = Chat {
         source_type: SourceType::Telegram as i32,
+        ds_uuid: ds_uuid.clone(),
         ..Default::default()
     };
     let mut messages: Vec<Message> = vec![];
+    // Set if messages were already pushed to the sink
+    let mut streamed_msg_count_option: Option<i32> = None;
 
     let mut member_ids: HashSet<UserId, Hasher> =
         HashSet::with_capacity_and_hasher(100, hasher());
 
+    let mut has_type = false;
     let mut skip_processing = false;
 
-    parse_object(chat_json, &json_path, |CB { key, value, wrong_key_action }| match key {
-        "name" => {
-            if value.value_type() != ValueType::Null {
-                chat.name_option = as_string_option!(value, json_path, "name");
+    let mut forum_topics = ForumTopics::default();
+
+    json.begin_object()?;
+    while let Some(key) = json.next_key()? {
+        if key == "messages" {
+            if skip_processing {
+                json.skip_value()?;
+                continue;
             }
-            Ok(())
-        }
-        "type" => {
-            let tpe = match as_str!(value, json_path, "type") {
-                "personal_chat" => Ok(ChatType::Personal),
-                "private_group" => Ok(ChatType::PrivateGroup),
-                "private_supergroup" => Ok(ChatType::PrivateGroup),
-                "saved_messages" | "private_channel" | "public_channel" => {
-                    skip_processing = true;
-                    Ok(ChatType::Personal) // Doesn't matter
-                }
-                other => err!("Unknown chat type: {}", other),
-            }?;
-            chat.tpe = tpe as i32;
-            Ok(())
-        }
-        "id" => {
-            chat.id = as_i64!(value, json_path, "id");
-            Ok(())
-        }
-        "messages" => {
-            if skip_processing { return Ok(()); }
             feedback_client.set_load_status(LoadStatus::new_parsing("chat", Some(chat.qualified_name())));
 
+            // Messages are streamed one by one, chat needs to be fully known to push them right away
+            let stream = streamable && has_type && chat.id != 0;
+            let mut stream_chat = chat.clone();
+            if stream {
+                unshift_chat_id(&mut stream_chat);
+                // Actual members are set once all messages are parsed
+                stream_chat.member_ids = myself_id_option.map(|id| **id).into_iter().collect();
+                chats_sink.add_chat(stream_chat.clone())?;
+            }
+
             let path = format!("{json_path}.messages");
-            let messages_json = as_array!(value, path);
-            for v in messages_json {
-                let parsed = parse_message(&path, v, ds_uuid, users, &mut member_ids)?;
+            let mut msg_count = 0;
+            json.begin_array()?;
+            while json.has_next_element()? {
+                let mut message_bytes = json.read_raw_value()?;
+                let v = simd_json::to_borrowed_value(&mut message_bytes)?;
+                if !stream {
+                    forum_topics.observe(&path, &v)?;
+                }
+                let parsed = parse_message(&path, &v, ds_uuid, users, &mut member_ids)?;
                 match parsed {
+                    ParsedMessage::Ok(mut msg) if stream => {
+                        msg.internal_id = msg_count as i64;
+                        msg_count += 1;
+                        messages.push(*msg);
+                        if messages.len() >= BATCH_SIZE {
+                            chats_sink.add_messages(&stream_chat, std::mem::take(&mut messages))?;
+                        }
+                    }
                     ParsedMessage::Ok(msg) =>
                         messages.push(*msg),
                     ParsedMessage::SkipMessage =>
                         { /* NOOP */ }
-                    ParsedMessage::SkipChat => {
-                        log::warn!("Skipping chat '{}' because it contains topics!", chat.qualified_name());
-                        // Note: the parsing itself might've modified users, so we want to remove orphan users later.
+                }
+            }
+            if stream {
+                chats_sink.add_messages(&stream_chat, std::mem::take(&mut messages))?;
+                streamed_msg_count_option = Some(msg_count);
+            }
+            continue;
+        }
+
+        let mut value_bytes = json.read_raw_value()?;
+        let value = &simd_json::to_borrowed_value(&mut value_bytes)?;
+        match key.as_str() {
+            "name" => {
+                if value.value_type() != ValueType::Null {
+                    chat.name_option = as_string_option!(value, json_path, "name");
+                }
+            }
+            "type" => {
+                let tpe = match as_str!(value, json_path, "type") {
+                    "personal_chat" => Ok(ChatType::Personal),
+                    "private_group" => Ok(ChatType::PrivateGroup),
+                    "private_supergroup" => Ok(ChatType::PrivateGroup),
+                    "private_channel" | "public_channel" | "public_supergroup" => Ok(ChatType::Channel),
+                    "saved_messages" => {
                         skip_processing = true;
-                        break;
+                        Ok(ChatType::Personal) // Doesn't matter
                     }
-                }
+                    other => err!("Unknown chat type: {}", other),
+                }?;
+                chat.tpe = tpe as i32;
+                has_type = true;
             }
-            Ok(())
+            "id" => {
+                chat.id = as_i64!(value, json_path, "id");
+            }
+            _ => bail!("Unexpected key: {}.{}", json_path, key)
         }
-        _ => wrong_key_action()
-    })?;
+    }
+    forum_topics.apply_renames();
 
     if skip_processing {
-        return Ok(None);
+        return Ok(false);
     }
 
-    messages.sort_by_key(|m| (m.timestamp, m.internal_id));
+    unshift_chat_id(&mut chat);
 
-    for (idx, m) in messages.iter_mut().enumerate() {
-        m.internal_id = idx as i64;
+    if let Some(myself_id) = myself_id_option {
+        // Add myself as a first member (not required by convention but to match existing behaviour).
+        member_ids.remove(myself_id);
     }
+    let mut member_ids = member_ids.into_iter().collect_vec();
+    member_ids.sort_by_key(|id| **id);
+    if let Some(myself_id) = myself_id_option {
+        member_ids.insert(0, *myself_id);
+    }
+    chat.member_ids = member_ids.into_iter().map(|s| *s).collect();
 
-    deduplicate(&mut messages)?;
+    if let Some(msg_count) = streamed_msg_count_option {
+        chat.msg_count = msg_count;
+        chats_sink.finish_chat(chat)?;
+        return Ok(true);
+    }
 
-    chat.msg_count = messages.len() as i32;
+    if forum_topics.titles.is_empty() {
+        chats_sink.push(make_cwm(chat, messages)?)?;
+        return Ok(true);
+    }
+
+    // Forum topics become sub-chats of the forum chat, sharing its members
+    let mut topic_messages: BTreeMap<i64, Vec<Message>> =
+        forum_topics.titles.keys().map(|topic_id| (*topic_id, vec![])).collect();
+    let mut general_messages = vec![];
+    for m in messages {
+        match m.source_id_option.and_then(|id| forum_topics.topic_id(id)) {
+            Some(topic_id) => topic_messages.get_mut(&topic_id).unwrap().push(m),
+            None => general_messages.push(m),
+        }
+    }
+
+    let mut topic_cwms = vec![];
+    for (topic_id, messages) in topic_messages {
+        ensure!(topic_id < TOPIC_CHAT_ID_MULTIPLIER, "Topic ID {topic_id} is too large!");
+        let topic_chat = Chat {
+            id: chat.id.checked_mul(TOPIC_CHAT_ID_MULTIPLIER)
+                .and_then(|id| id.checked_add(topic_id))
+                .with_context(|| format!("Chat ID {} is too large to have topics!", chat.id))?,
+            name_option: forum_topics.titles.remove(&topic_id),
+            img_path_option: None,
+            main_chat_id: Some(chat.id),
+            ..chat.clone()
+        };
+        topic_cwms.push(make_cwm(topic_chat, messages)?);
+    }
+
+    chats_sink.push(make_cwm(chat, general_messages)?)?;
+    for cwm in topic_cwms {
+        chats_sink.push(cwm)?;
+    }
+    Ok(true)
+}
 
-    // Undo the shifts introduced by Telegram 2021-05.
+/// Undoes the shifts introduced by Telegram 2021-05, does nothing if chat ID is already unshifted.
+fn unshift_chat_id(chat: &mut Chat) {
     match chat.tpe() {
         ChatType::Personal if chat.id < PERSONAL_CHAT_ID_SHIFT =>
             chat.id += PERSONAL_CHAT_ID_SHIFT,
         ChatType::PrivateGroup if chat.id < GROUP_CHAT_ID_SHIFT =>
             chat.id += GROUP_CHAT_ID_SHIFT,
+        // Channels weren't loaded before, so there are no legacy IDs to match.
         _etc =>
             { /* Don't change anything. */ }
     }
+}
 
-    if let Some(myself_id) = myself_id_option {
-        // Add myself as a first member (not required by convention but to match existing behaviour).
-        member_ids.remove(myself_id);
+fn make_cwm(mut chat: Chat, mut messages: Vec<Message>) -> Result<ChatWithMessages> {
+    messages.sort_by_key(|m| (m.timestamp, m.internal_id));
+
+    for (idx, m) in messages.iter_mut().enumerate() {
+        m.internal_id = idx as i64;
     }
-    let mut member_ids = member_ids.into_iter().collect_vec();
-    member_ids.sort_by_key(|id| **id);
-    if let Some(myself_id) = myself_id_option {
-        member_ids.insert(0, *myself_id);
+
+    deduplicate(&mut messages)?;
+
+    chat.msg_count = messages.len() as i32;
+
+    Ok(ChatWithMessages { chat, messages })
+}
+
+/// Forum topics, built from "topic_created" and "topic_edit" service messages.
+/// Topic ID is a source ID of its "topic_created" message.
+/// Messages belong to a topic if their reply chain leads to its "topic_created" message,
+/// the rest belong to the "General" topic.
+/// Gathered while going through the chat messages, renames take effect once all of them have been observed.
+#[derive(Default)]
+struct ForumTopics {
+    titles: HashMap<i64, String, Hasher>,
+    reply_to_ids: HashMap<i64, i64, Hasher>,
+    renames: Vec<(i64, String)>,
+}
+
+impl ForumTopics {
+    fn observe(&mut self, json_path: &str, v: &BorrowedValue) -> EmptyRes {
+        let message_json = as_object!(v, json_path, "message");
+        let id = get_field_i64!(message_json, json_path, "id");
+        if let Some(reply_to_id) = message_json.get("reply_to_message_id") {
+            self.reply_to_ids.insert(id, as_i64!(reply_to_id, json_path, "reply_to_message_id"));
+        }
+        match message_json.get("action").and_then(|a| a.as_str()) {
+            Some("topic_created") => {
+                self.titles.insert(id, get_field_string!(message_json, json_path, "title"));
+            }
+            Some("topic_edit") => {
+                if let Some(new_title) = message_json.get("new_title") {
+                    self.renames.push((id, as_string!(new_title, json_path, "new_title")));
+                }
+            }
+            _ => { /* NOOP */ }
+        }
+        Ok(())
+    }
+
+    fn apply_renames(&mut self) {
+        // Messages are chronologically ordered, so the last rename wins
+        for (id, new_title) in std::mem::take(&mut self.renames) {
+            if let Some(topic_id) = self.topic_id(id) {
+                self.titles.insert(topic_id, new_title);
+            }
+        }
     }
-    chat.member_ids = member_ids.into_iter().map(|s| *s).collect();
 
-    Ok(Some(ChatWithMessages { chat, messages }))
+    /// Topic ID for a given message, or None if it belongs to the "General" topic.
+    fn topic_id(&self, source_id: i64) -> Option<i64> {
+        let mut id = source_id;
+        // Guarding against reply cycles, just in case
+        for _ in 0..=self.reply_to_ids.len() {
+            if self.titles.contains_key(&id) {
+                return Some(id);
+            }
+            id = *self.reply_to_ids.get(&id)?;
+        }
+        None
+    }
 }
 
 //
@@ -511,9 +833,10 @@ fn parse_message(json_path: &str,
                                        "author", "reactions", "todo_list"]),
         };
 
+        // reply_to_message_id is only used to determine a forum topic
         static ref SERVICE_MSG_FIELDS: ExpectedMessageField<'static> = ExpectedMessageField {
             required_fields: hash_set(["id", "type", "date", "text", "actor", "actor_id", "action"]),
-            optional_fields: hash_set(["date_unixtime", "text_entities", "edited"]),
+            optional_fields: hash_set(["date_unixtime", "text_entities", "edited", "reply_to_message_id"]),
         };
     }
 
@@ -534,11 +857,14 @@ fn parse_message(json_path: &str,
 
             let mut regular: MessageRegular = Default::default();
             parse_regular_message(&mut message_json, &mut regular, users)?;
+            let reactions_path = format!("{}.reactions", message_json.json_path);
+            if let Some(reactions) = message_json.field_opt("reactions")? {
+                regular.reactions = parse_reactions(&reactions_path, reactions, ds_uuid, users, member_ids)?;
+            }
             typed = Typed::Regular(regular);
 
             short_user.id = parse_user_id(message_json.field("from_id")?)?;
             short_user.full_name_option = message_json.field_opt_str("from")?;
-            // If a sender is the channel, "author" contains string alias of an admin who sent a message
         }
         "service" => {
             message_json.expected_fields = Some(SERVICE_MSG_FIELDS.clone());
@@ -553,8 +879,6 @@ fn parse_message(json_path: &str,
                 }
                 ShouldProceed::SkipMessage =>
                     return Ok(ParsedMessage::SkipMessage),
-                ShouldProceed::SkipChat =>
-                    return Ok(ParsedMessage::SkipChat)
             };
             typed = Typed::Service(service);
 
@@ -648,8 +972,6 @@ fn parse_regular_message(message_json: &mut MessageJson,
                          users: &Users) -> EmptyRes {
     let json_path = message_json.json_path.clone();
 
-    // TODO: Reactions
-
     // Telegram has been observed to use 1970-ish edit times, probably signifying message not being edited
     const FIRST_POSSIBLE_VALID_TIMESTAMP: i64 = 650000000;
     if let Some(ref edited) = message_json.field_opt_str("edited_unixtime")? {
@@ -677,6 +999,8 @@ fn parse_regular_message(message_json: &mut MessageJson,
         // Otherwise reply_to_message_id is pointless
         regular_msg.reply_to_message_id_option = message_json.field_opt_i64("reply_to_message_id")?;
     }
+    // If a sender is the channel, "author" contains string alias of an admin who sent a message
+    regular_msg.author_signature_option = message_json.field_opt_str("author")?;
 
     let media_type_option = message_json.field_opt_str("media_type")?;
     let mime_type_option = message_json.field_opt_str("mime_type")?;
@@ -1078,10 +1402,22 @@ fn parse_service_message(message_json: &mut MessageJson,
             // Not really interesting to track.
             return Ok(ShouldProceed::SkipMessage);
         }
-        "topic_created" | "topic_edit" => {
-            // Topic-level division is implemented via repies to "topic_created" messages.
-            // This is a questionable approach that I don't want to track at the moment.
-            return Ok(ShouldProceed::SkipChat);
+        "topic_created" => {
+            // Topic-level division itself is handled by parse_chat
+            let title = message_json.field_str("title")?;
+            (ServiceSvo::Notice(MessageServiceNotice {}),
+             Some(format!("Created topic \"{title}\"")))
+        }
+        "topic_edit" => {
+            let new_title_option = message_json.field_opt_str("new_title")?;
+            let new_icon_option = message_json.field_opt_i64("new_icon_emoji_id")?;
+            let text = match (new_title_option, new_icon_option) {
+                (Some(new_title), _) => format!("Renamed topic to \"{new_title}\""),
+                (None, Some(_)) => "Changed topic icon".to_owned(),
+                (None, None) => "Edited topic".to_owned(),
+            };
+            (ServiceSvo::Notice(MessageServiceNotice {}),
+             Some(text))
         }
         "paid_messages_price_change" => {
             // "Paid messages price changed to X", we expect X to be zero
@@ -1309,6 +1645,70 @@ fn parse_inline_bot_buttons(json_path: &str, json: &BorrowedValue) -> Result<Vec
     Ok(result)
 }
 
+/// Paid reactions have no emoji, but are shown as a star in Telegram clients.
+const PAID_REACTION_EMOJI: &str = "⭐";
+
+/// Reacting users (only the recent ones are exported) are treated as chat members.
+fn parse_reactions(json_path: &str,
+                   json: &BorrowedValue,
+                   ds_uuid: &PbUuid,
+                   users: &mut Users,
+                   member_ids: &mut HashSet<UserId, Hasher>) -> Result<Vec<Reaction>> {
+    let mut result = vec![];
+    for reaction_json in as_array!(json, json_path) {
+        let mut reaction = Reaction::default();
+        parse_bw_as_object(reaction_json, json_path, |CB { key, value, wrong_key_action }| match key {
+            "type" => {
+                match as_str!(value, json_path, "type") {
+                    "emoji" | "custom_emoji" => { /* Determined by other fields */ }
+                    "paid" => reaction.emoji_option = Some(PAID_REACTION_EMOJI.to_owned()),
+                    etc => bail!("Unknown reaction type '{etc}'"),
+                }
+                Ok(())
+            }
+            "count" => {
+                reaction.count = as_i32!(value, json_path, "count");
+                Ok(())
+            }
+            "emoji" => {
+                reaction.emoji_option = Some(as_string!(value, json_path, "emoji"));
+                Ok(())
+            }
+            "document_id" => {
+                reaction.custom_emoji_id_option = Some(as_string!(value, json_path, "document_id"));
+                Ok(())
+            }
+            "recent" => {
+                for recent_json in as_array!(value, json_path, "recent") {
+                    let mut short_user = ShortUser::default();
+                    parse_bw_as_object(recent_json, json_path, |CB { key, value, wrong_key_action }| match key {
+                        "from" => {
+                            short_user.full_name_option = as_string_option!(value, json_path, "from");
+                            Ok(())
+                        }
+                        "from_id" => {
+                            short_user.id = parse_user_id(value)?;
+                            Ok(())
+                        }
+                        "date" | "date_unixtime" => consume(),
+                        _ => wrong_key_action()
+                    })?;
+                    let short_user = normalize_short_user(short_user)?;
+                    member_ids.insert(short_user.0.id);
+                    let user_id = append_user(short_user, users, ds_uuid)?;
+                    reaction.from_ids.push(*user_id);
+                }
+                Ok(())
+            }
+            _ => wrong_key_action()
+        })?;
+        ensure!(reaction.emoji_option.is_some() != reaction.custom_emoji_id_option.is_some(),
+                "{json_path}: reaction should have either emoji or custom emoji ID!");
+        result.push(reaction);
+    }
+    Ok(result)
+}
+
 //
 // Other
 //
@@ -2,22 +2,49 @@ use super::*;
 
 pub(super) fn parse(
     feedback_client: &dyn FeedbackClientSync,
-    root_obj: &Object,
+    json: &mut JsonReader,
+    chat_headers: Vec<ChatHeader>,
     ds_uuid: &PbUuid,
-    myself: &mut User
-) -> Result<(Users, Vec<ChatWithMessages>)> {
+    myself: &mut User,
+    chats_sink: &mut ChatsSink,
+) -> Result<Users> {
     let mut users: Users = Default::default();
-    let mut chats_with_messages: Vec<ChatWithMessages> = vec![];
 
-    parse_object(root_obj, "root", |CB { key, value, wrong_key_action }| match key {
-        "about" => consume(),
-        "profile_pictures" => consume(),
-        "frequent_contacts" => consume(),
-        "other_data" => consume(),
-        "stories" => consume(),
-        "profile_music" => consume(),
-        "sessions" => consume(),
-        "web_sessions" => consume(),
+    json.begin_object()?;
+    while let Some(key) = json.next_key()? {
+        match key.as_str() {
+            "about" | "profile_pictures" | "frequent_contacts" | "other_data" | "stories" | "profile_music" |
+            "sessions" | "web_sessions" | "left_chats" => {
+                // We don't want to import "left_chats" section!
+                json.skip_value()?;
+            }
+            "chats" => {
+                feedback_client.set_load_status(LoadStatus::new_parsing("chats", None));
+                if myself.id == 0 {
+                    bail!("personal_information section is missing!");
+                }
+                parse_chats(feedback_client, json, &chat_headers, ds_uuid, myself, &mut users, chats_sink)?;
+            }
+            _ => {
+                let mut value_bytes = json.read_raw_value()?;
+                let value = simd_json::to_borrowed_value(&mut value_bytes)?;
+                parse_small_section(&key, &value, ds_uuid, myself, &mut users)?;
+            }
+        }
+    }
+
+    users.insert(myself.clone());
+
+    Ok(users)
+}
+
+/// Parses a root section other than "chats", these are small enough to be parsed whole.
+fn parse_small_section(key: &str,
+                       value: &BorrowedValue,
+                       ds_uuid: &PbUuid,
+                       myself: &mut User,
+                       users: &mut Users) -> EmptyRes {
+    match key {
         "contacts" =>
             parse_bw_as_object(value, "personal_information", |CB { key, value, wrong_key_action }| match key {
                 "about" => consume(),
@@ -63,63 +90,39 @@ pub(super) fn parse(
             }
             Ok(())
         }
-        "chats" => {
-            feedback_client.set_load_status(LoadStatus::new_parsing("chats", None));
-            if myself.id == 0 {
-                bail!("personal_information section is missing!");
-            }
-
-            let json_path = "chats";
-
-            let chats_arr: Vec<(&Object, String)> = as_object!(value, "chats")
-                .get("list").context("No chats list in dataset!")?
-                .as_array().with_context(|| format!("{json_path} list is not an array!"))?
-                .iter().map(|chat_json| {
-                    let chat_json = as_object!(chat_json, json_path, "chat");
-                    let json_path = format!("{json_path}.chat");
-                    // Name will not be present for saved messages
-                    let json_path = match chat_json.get("name") {
-                        Some(name) => format!("{json_path}[{}]", name),
-                        None => format!("{json_path}[#{}]", get_field!(chat_json, json_path, "id"))
-                    };
-                    ok((chat_json, json_path))
-                }).try_collect()?;
-
-            // Pre-populate users with users chats.
-            for (chat_json, json_path) in chats_arr.iter() {
-                // Name will not be present for saved messages
-                if !chat_json.contains_key("name") {
-                    continue;
-                }
-                let short_user = ShortUser {
-                    id: parse_user_id(get_field!(chat_json, json_path, "id"))?,
-                    full_name_option: get_field_string_option!(chat_json, json_path, "name"),
-                };
-                // Doesn't really make sense to pre-populate users without names.
-                if short_user.full_name_option.is_none() {
-                    continue;
-                }
-                let short_user = normalize_short_user(short_user)?;
-                append_user(short_user, &mut users, ds_uuid)?;
-            }
+        _ => err!("Unexpected key: root.{key}")
+    }
+}
 
-            for (chat_json, json_path) in chats_arr {
-                if let Some(mut cwm) = parse_chat(feedback_client, &json_path, chat_json, ds_uuid, Some(&myself.id()), &mut users)? {
-                    cwm.chat.ds_uuid = ds_uuid.clone();
-                    chats_with_messages.push(cwm);
-                }
-            }
+fn parse_chats(feedback_client: &dyn FeedbackClientSync,
+               json: &mut JsonReader,
+               chat_headers: &[ChatHeader],
+               ds_uuid: &PbUuid,
+               myself: &User,
+               users: &mut Users,
+               chats_sink: &mut ChatsSink) -> EmptyRes {
+    // Pre-populate users with users chats.
+    for short_user in chat_headers.iter().filter_map(|h| h.short_user_option.clone()) {
+        let short_user = normalize_short_user(short_user)?;
+        append_user(short_user, users, ds_uuid)?;
+    }
 
-            Ok(())
+    let mut has_list = false;
+    json.begin_object()?;
+    while let Some(key) = json.next_key()? {
+        if key != "list" {
+            json.skip_value()?;
+            continue;
         }
-        "left_chats" => {
-            // We don't want to import "left_chats" section!
-            consume()
+        has_list = true;
+        let mut chat_headers = chat_headers.iter();
+        json.begin_array()?;
+        while json.has_next_element()? {
+            let header = chat_headers.next().context("Chats list has changed while parsing!")?;
+            parse_chat(feedback_client, &header.json_path, json, header.streamable, ds_uuid, Some(&myself.id()),
+                       users, chats_sink)?;
         }
-        _ => wrong_key_action()
-    })?;
-
-    users.insert(myself.clone());
-
-    Ok((users, chats_with_messages))
+    }
+    ensure!(has_list, "No chats list in dataset!");
+    Ok(())
 }
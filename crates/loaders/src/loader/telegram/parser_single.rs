@@ -2,22 +2,18 @@ use super::*;
 
 pub(super) fn parse(
     feedback_client: &dyn FeedbackClientSync,
-    root_obj: &Object,
+    json: &mut JsonReader,
+    streamable: bool,
     ds_uuid: &PbUuid,
     myself: &mut User,
-) -> Result<(Users, Vec<ChatWithMessages>)> {
+    chats_sink: &mut ChatsSink,
+) -> Result<Users> {
     let mut users: Users = Default::default();
-    let mut chats_with_messages: Vec<ChatWithMessages> = vec![];
 
-    let cwm_option =
-        parse_chat(feedback_client, "<root>", root_obj, ds_uuid, None, &mut users)?;
-    match cwm_option {
-        None =>
-            bail!("Chat was skipped entirely!"),
-        Some(mut cwm) => {
-            cwm.chat.ds_uuid = ds_uuid.clone();
-            chats_with_messages.push(cwm);
-        }
+    let loaded =
+        parse_chat(feedback_client, "<root>", json, streamable, ds_uuid, None, &mut users, chats_sink)?;
+    if !loaded {
+        bail!("Chat was skipped entirely!");
     }
 
     // In single chat, self section is not present. As such, myself must be populated from users.
@@ -25,5 +21,5 @@ pub(super) fn parse(
     let myself_idx = feedback_client.choose_myself(&users_vec)?;
     *myself = users_vec.swap_remove(myself_idx);
 
-    Ok((users, chats_with_messages))
+    Ok(users)
 }
@@ -0,0 +1,706 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone};
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+
+use crate::loader::DataLoader;
+use crate::prelude::*;
+use chat_history_manager_dao::in_memory_dao::InMemoryDao;
+
+#[cfg(test)]
+#[path = "telegram_html_tests.rs"]
+mod tests;
+
+/// Loads exports made by Telegram Desktop in (default) HTML format, either a single chat (`messages.html`)
+/// or a full export (`export_results.html`, with chats in `chats/chat_XXX` directories).
+///
+/// Message IDs are the same as in machine-readable JSON export, so they're used as source IDs.
+/// User and chat IDs are taken from peer links in sender names and chat header (`tg://user?id=123` for users,
+/// message links like `https://t.me/c/123/45` for groups and channels), matching JSON export.
+/// Without a link, ID is derived from a name instead.
+/// In any case, the user is asked to choose myself.
+pub struct TelegramHtmlDataLoader;
+
+const MESSAGES_HTML: &str = "messages.html";
+const EXPORT_RESULTS_HTML: &str = "export_results.html";
+const CHATS_DIR: &str = "chats";
+
+/// Fallback for media whose MIME type can't be guessed from a file name
+const UNKNOWN_MIME_TYPE: &str = "application/octet-stream";
+
+lazy_static! {
+    /// Long chats are split into `messages.html`, `messages2.html`, ...
+    static ref PAGE_FILENAME_REGEX: Regex = Regex::new(r"^messages(\d*)\.html$").unwrap();
+    /// Negative IDs are used for date separators
+    static ref MESSAGE_ID_REGEX: Regex = Regex::new(r"^message(-?\d+)$").unwrap();
+    /// Replied message might be on another page, e.g. `messages2.html#go_to_message123`
+    static ref GO_TO_MESSAGE_REGEX: Regex = Regex::new(r"#go_to_message(\d+)$").unwrap();
+    static ref DURATION_REGEX: Regex = Regex::new(r"^(?:(\d+):)?(\d{2}):(\d{2})").unwrap();
+    static ref USER_LINK_REGEX: Regex = Regex::new(r"^tg://user\?id=(\d+)$").unwrap();
+    /// Link to a message in a group or a channel, e.g. `https://t.me/c/123/45`
+    static ref MESSAGE_LINK_REGEX: Regex = Regex::new(r"^https://t\.me/c/(\d+)/\d+$").unwrap();
+    /// E.g. `https://maps.google.com/maps?q=55.755812,37.6173&ll=55.755812,37.6173&z=16`
+    static ref LOCATION_REGEX: Regex = Regex::new(r"[?&]q=(-?[\d.]+),(-?[\d.]+)").unwrap();
+
+    static ref CHAT_NAME_SELECTOR: Selector = Selector::parse("div.page_header div.text").unwrap();
+    static ref MESSAGE_SELECTOR: Selector = Selector::parse("div.history > div.message").unwrap();
+}
+
+impl DataLoader for TelegramHtmlDataLoader {
+    fn name(&self) -> String { "Telegram (HTML)".to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        let filename = path_file_name(path)?;
+        if filename != MESSAGES_HTML && filename != EXPORT_RESULTS_HTML {
+            bail!("File is not {MESSAGES_HTML} or {EXPORT_RESULTS_HTML}");
+        }
+        if !super::first_line(path)?.eq_ignore_ascii_case("<!DOCTYPE html>") {
+            bail!("{filename} is not a valid HTML file");
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+        parse_telegram_html(feedback_client, path, ds)
+    }
+}
+
+/// Chat as parsed from HTML pages, with senders yet to be resolved to users.
+struct RawChat {
+    name: String,
+    peer_option: Option<PeerLink>,
+    messages: Vec<RawMessage>,
+}
+
+struct RawMessage {
+    source_id: i64,
+    /// Service messages have no timestamps, these are inferred from neighbouring messages
+    timestamp_option: Option<i64>,
+    /// `None` for service messages, their actor is guessed from the text
+    from_option: Option<RawSender>,
+    text: Vec<RichTextElement>,
+    typed: message::Typed,
+}
+
+/// Message author as shown on the page, ID is only known if the name links to a peer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RawSender {
+    name: String,
+    id_option: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerLink {
+    User(i64),
+    Chat(i64),
+}
+
+impl PeerLink {
+    fn parse(href: &str) -> Option<PeerLink> {
+        let id = |captures: regex::Captures| captures.get(1).unwrap().as_str().parse::<i64>().ok();
+        if let Some(captures) = USER_LINK_REGEX.captures(href) {
+            id(captures).map(PeerLink::User)
+        } else {
+            MESSAGE_LINK_REGEX.captures(href).and_then(id).map(PeerLink::Chat)
+        }
+    }
+
+    fn id(self) -> i64 {
+        match self {
+            PeerLink::User(id) | PeerLink::Chat(id) => id,
+        }
+    }
+}
+
+fn parse_telegram_html(feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+    let ds_root = path.parent().unwrap().to_path_buf();
+
+    let mut raw_chats = vec![];
+    for chat_dir in chat_dirs(path)? {
+        feedback_client.set_load_status(LoadStatus::new_parsing("chat", Some(format!("{}", chat_dir.display()))));
+        // Media paths should be relative to dataset root, same as in JSON export
+        let path_prefix = match chat_dir.strip_prefix(&ds_root)?.to_str().context("Non-UTF-8 path")? {
+            "" => "".to_owned(),
+            rel_path => format!("{}/", rel_path.replace('\\', "/")),
+        };
+        raw_chats.push(parse_chat_dir(&chat_dir, &path_prefix)?);
+    }
+
+    // Users are keyed by their peer links. Sender without a link is matched to a linked one by name, provided
+    // it's unambiguous, otherwise it's only known by the name.
+    let senders = raw_chats.iter()
+        .flat_map(|c| c.messages.iter().filter_map(|m| m.from_option.as_ref()))
+        .unique()
+        .collect_vec();
+    let mut sender_to_id: HashMap<RawSender, UserId> = HashMap::new();
+    let mut users: Vec<User> = vec![];
+    for sender in senders.iter().sorted_by_key(|s| s.id_option.is_none()) {
+        let id = match sender.id_option {
+            Some(id) => id,
+            None => {
+                let linked_ids = users.iter().filter(|u| u.pretty_name() == sender.name).map(|u| u.id).collect_vec();
+                match linked_ids.as_slice() {
+                    [id] => *id,
+                    _ => super::hash_to_id(&sender.name),
+                }
+            }
+        };
+        if !users.iter().any(|u| u.id == id) {
+            users.push(new_user(&ds.uuid, id, &sender.name));
+        }
+        sender_to_id.insert((*sender).clone(), UserId(id));
+    }
+    // Preserving order of appearance
+    users.sort_by_key(|u| senders.iter().position(|s| sender_to_id[*s] == u.id()));
+    ensure!(!users.is_empty(), "No messages found in {}", path.display());
+    let myself_idx = feedback_client.choose_myself(&users)?;
+    let myself = users.remove(myself_idx);
+    let myself_name = myself.pretty_name();
+
+    let mut cwms: Vec<ChatWithMessages> = vec![];
+    for raw_chat in raw_chats {
+        let is_personal = match raw_chat.peer_option {
+            Some(PeerLink::User(id)) => id != myself.id,
+            Some(PeerLink::Chat(_)) => false,
+            None => raw_chat.name != myself_name && raw_chat.messages.iter()
+                .filter_map(|m| m.from_option.as_ref())
+                .all(|from| from.name == myself_name || from.name == raw_chat.name),
+        };
+
+        let chat_id = if is_personal {
+            // Using user ID as a chat ID.
+            // Interlocutor might not have written anything.
+            let id = match raw_chat.peer_option {
+                Some(peer) => peer.id(),
+                None => users.iter().find(|u| u.pretty_name() == raw_chat.name)
+                    .map(|u| u.id)
+                    .unwrap_or_else(|| super::hash_to_id(&raw_chat.name)),
+            };
+            if !users.iter().any(|u| u.id == id) {
+                users.push(new_user(&ds.uuid, id, &raw_chat.name));
+            }
+            id
+        } else if let Some(peer) = raw_chat.peer_option {
+            peer.id()
+        } else {
+            // Groups could share the same name
+            let mut id = super::hash_to_id(&raw_chat.name);
+            while cwms.iter().any(|cwm| cwm.chat.id == id) {
+                log::warn!("Chat ID collision for chat '{}', adjusting", raw_chat.name);
+                id += 1;
+            }
+            id
+        };
+
+        let name_to_id: HashMap<String, UserId> = std::iter::once(&myself).chain(users.iter())
+            .map(|u| (u.pretty_name(), u.id()))
+            .collect();
+        let messages = resolve_messages(raw_chat.messages, &sender_to_id, &name_to_id, myself.id())
+            .with_context(|| format!("Failed to process chat '{}'", raw_chat.name))?;
+
+        let mut member_ids = messages.iter().map(|m| m.from_id).collect::<HashSet<_>>();
+        if is_personal {
+            member_ids.insert(chat_id);
+        }
+        // Myself is a first member (not required by convention but to match existing behaviour).
+        member_ids.remove(&myself.id);
+        let member_ids = std::iter::once(myself.id).chain(member_ids.into_iter().sorted()).collect_vec();
+
+        cwms.push(ChatWithMessages {
+            chat: Chat {
+                ds_uuid: ds.uuid.clone(),
+                id: chat_id,
+                name_option: Some(raw_chat.name),
+                source_type: SourceType::Telegram as i32,
+                tpe: if is_personal { ChatType::Personal } else { ChatType::PrivateGroup } as i32,
+                img_path_option: None,
+                member_ids,
+                msg_count: messages.len() as i32,
+                main_chat_id: None,
+            },
+            messages,
+        });
+    }
+
+    let myself_id = myself.id();
+    let users = std::iter::once(myself).chain(users.into_iter().sorted_by_key(|u| u.id)).collect_vec();
+
+    Ok(Box::new(InMemoryDao::new_single(
+        format!("Telegram ({})", path_file_name(&ds_root)?),
+        ds,
+        ds_root,
+        myself_id,
+        users,
+        cwms,
+    )))
+}
+
+fn new_user(ds_uuid: &PbUuid, id: i64, name: &str) -> User {
+    User {
+        ds_uuid: ds_uuid.clone(),
+        id,
+        first_name_option: Some(name.to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    }
+}
+
+fn chat_dirs(path: &Path) -> Result<Vec<PathBuf>> {
+    let root = path.parent().unwrap();
+    if path_file_name(path)? == MESSAGES_HTML {
+        return Ok(vec![root.to_path_buf()]);
+    }
+    let chats_dir = root.join(CHATS_DIR);
+    ensure!(chats_dir.is_dir(), "Directory {CHATS_DIR} not found next to {EXPORT_RESULTS_HTML}");
+    let mut result = vec![];
+    for entry in fs::read_dir(&chats_dir)? {
+        let chat_dir = entry?.path();
+        if chat_dir.join(MESSAGES_HTML).is_file() {
+            result.push(chat_dir);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn list_pages(chat_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut pages = vec![];
+    for entry in fs::read_dir(chat_dir)? {
+        let entry = entry?;
+        let filename = entry.file_name();
+        if let Some(captures) = filename.to_str().and_then(|name| PAGE_FILENAME_REGEX.captures(name)) {
+            let page_idx: u32 = match captures.get(1).unwrap().as_str() {
+                "" => 1,
+                idx => idx.parse()?,
+            };
+            pages.push((page_idx, entry.path()));
+        }
+    }
+    pages.sort_by_key(|(idx, _)| *idx);
+    Ok(pages.into_iter().map(|(_, path)| path).collect_vec())
+}
+
+fn parse_chat_dir(chat_dir: &Path, path_prefix: &str) -> Result<RawChat> {
+    let mut header_option: Option<(String, Option<PeerLink>)> = None;
+    let mut messages = vec![];
+    // Joined messages omit sender name, it's the same as in a previous message
+    let mut last_from_option: Option<RawSender> = None;
+    for page_path in list_pages(chat_dir)? {
+        let html = Html::parse_document(&fs::read_to_string(&page_path)?);
+        if header_option.is_none() {
+            header_option = html.select(&CHAT_NAME_SELECTOR).next().map(parse_peer);
+        }
+        for el in html.select(&MESSAGE_SELECTOR) {
+            let parsed = parse_message(el, &mut last_from_option, path_prefix).with_context(|| {
+                format!("Failed to parse message {} in {}", el.value().id().unwrap_or("?"), page_path.display())
+            })?;
+            messages.extend(parsed);
+        }
+    }
+    let (name, peer_option) =
+        header_option.with_context(|| format!("Chat name not found in {}", chat_dir.display()))?;
+    Ok(RawChat { name, peer_option, messages })
+}
+
+//
+// Parsing message
+//
+
+fn parse_message(el: ElementRef,
+                 last_from_option: &mut Option<RawSender>,
+                 path_prefix: &str) -> Result<Option<RawMessage>> {
+    let id = el.value().id().context("Message has no ID")?;
+    let source_id: i64 = MESSAGE_ID_REGEX.captures(id)
+        .with_context(|| format!("Unexpected message ID format: {id}"))?
+        .get(1).unwrap().as_str().parse()?;
+    if source_id < 0 {
+        // Date separator
+        return Ok(None);
+    }
+
+    let body = child_with_class(el, "body").context("Message has no body")?;
+
+    if has_class(el, "service") {
+        // Service messages are not structured, so we don't attempt to understand them.
+        return Ok(Some(RawMessage {
+            source_id,
+            timestamp_option: None,
+            from_option: None,
+            text: vec![RichText::make_plain(body.text().collect::<String>().trim().to_owned())],
+            typed: message_service!(ServiceSvo::Notice(MessageServiceNotice {})),
+        }));
+    }
+
+    let date_title = child_with_class(body, "date")
+        .and_then(|date| date.value().attr("title"))
+        .context("Message has no date")?;
+    let (timestamp, edit_timestamp_option) = parse_date_title(date_title)?;
+
+    let from = match child_with_class(body, "from_name") {
+        Some(from_name) => {
+            let (name, peer_option) = parse_peer(from_name);
+            RawSender { name, id_option: peer_option.map(PeerLink::id) }
+        }
+        None => last_from_option.clone().context("Joined message has no preceding message")?,
+    };
+    *last_from_option = Some(from.clone());
+
+    // Forwarded message content is nested in its own body
+    let forwarded_option = child_with_class(body, "forwarded");
+    let forward_from_name_option = forwarded_option.map(|forwarded| {
+        child_with_class(forwarded, "from_name").map(own_text).unwrap_or_else(|| UNKNOWN.to_owned())
+    });
+    let content_el = forwarded_option.unwrap_or(body);
+
+    let reply_to_message_id_option = child_with_class(body, "reply_to")
+        .and_then(|reply_to| reply_to.descendants().filter_map(ElementRef::wrap).find_map(|a| a.value().attr("href")))
+        .and_then(|href| GO_TO_MESSAGE_REGEX.captures(href))
+        .map(|captures| captures.get(1).unwrap().as_str().parse::<i64>())
+        .transpose()?;
+
+    let text = match child_with_class(content_el, "text") {
+        Some(text_el) => super::normalize_rich_text(parse_rich_text(text_el)),
+        None => vec![],
+    };
+
+    let contents = match child_with_class(content_el, "media_wrap") {
+        Some(media_wrap) => parse_media(media_wrap, path_prefix)?.into_iter().collect_vec(),
+        None => vec![],
+    };
+
+    let reactions = match child_with_class(body, "reactions") {
+        Some(reactions_el) => parse_reactions(reactions_el),
+        None => vec![],
+    };
+
+    Ok(Some(RawMessage {
+        source_id,
+        timestamp_option: Some(timestamp),
+        from_option: Some(from),
+        text,
+        typed: message_regular! {
+            edit_timestamp_option,
+            is_deleted: false,
+            forward_from_name_option,
+            reply_to_message_id_option,
+            contents,
+            reactions,
+            views_option: None,
+            author_signature_option: None,
+        },
+    }))
+}
+
+/// Title looks like `30.06.2023 16:14:00 UTC+03:00` (older exports omit the offset, using local time).
+/// Edited messages have their edit timestamp on a separate line.
+fn parse_date_title(title: &str) -> Result<(i64, Option<i64>)> {
+    let mut lines = title.lines();
+    let timestamp = parse_datetime(lines.next().context("Message date is empty")?)?;
+    let edit_timestamp_option = lines
+        .find_map(|line| line.trim().strip_prefix("Edited: "))
+        .map(parse_datetime)
+        .transpose()?;
+    Ok((timestamp, edit_timestamp_option))
+}
+
+fn parse_datetime(s: &str) -> Result<i64> {
+    const DATE_TIME_FMT: &str = "%d.%m.%Y %H:%M:%S";
+    let s = s.trim();
+    match s.split_once(" UTC") {
+        Some((dt, offset)) => {
+            let parsed = DateTime::parse_from_str(&format!("{dt} {offset}"), &format!("{DATE_TIME_FMT} %:z"))
+                .with_context(|| format!("Failed to parse date {s}"))?;
+            Ok(parsed.timestamp())
+        }
+        None => {
+            let naive_dt = NaiveDateTime::parse_from_str(s, DATE_TIME_FMT)
+                .with_context(|| format!("Failed to parse date {s}"))?;
+            // Ambiguous local time (when clocks go back) is resolved to the earlier one
+            let local_dt = LOCAL_TZ.from_local_datetime(&naive_dt).earliest()
+                .with_context(|| format!("Failed to parse date {s}: non-existent local time"))?;
+            Ok(local_dt.timestamp())
+        }
+    }
+}
+
+fn parse_rich_text(text_el: ElementRef) -> Vec<RichTextElement> {
+    text_el.children().filter_map(|node| {
+        if let Some(text) = node.value().as_text() {
+            Some(RichText::make_plain((**text).to_owned()))
+        } else {
+            ElementRef::wrap(node).map(parse_rich_text_element)
+        }
+    }).collect_vec()
+}
+
+/// Nested formatting is flattened, keeping the outermost style.
+fn parse_rich_text_element(el: ElementRef) -> RichTextElement {
+    let text = el.text().collect::<String>();
+    match el.value().name() {
+        "br" => RichText::make_plain("\n".to_owned()),
+        "strong" | "b" => RichText::make_bold(text),
+        "em" | "i" => RichText::make_italic(text),
+        "u" => RichText::make_underline(text),
+        "s" | "strike" | "del" => RichText::make_strikethrough(text),
+        "blockquote" => RichText::make_blockquote(text),
+        "code" => RichText::make_prefmt_inline(text),
+        "pre" => {
+            let language_option = el.descendants()
+                .filter_map(ElementRef::wrap)
+                .flat_map(|e| e.value().classes())
+                .find_map(|class| class.strip_prefix("language-"))
+                .map(|lang| lang.to_owned());
+            RichText::make_prefmt_block(text, language_option)
+        }
+        "span" if has_class(el, "spoiler") => RichText::make_spoiler(text),
+        "a" => {
+            let href = el.value().attr("href").unwrap_or_default();
+            let onclick = el.value().attr("onclick").unwrap_or_default();
+            match href {
+                // Mention by name, same as in JSON export
+                "" if onclick.starts_with("return ShowMentionName") => RichText::make_plain(format!("@{text}")),
+                // Hashtags, bot commands, custom emojis and such - no special treatment
+                "" => RichText::make_plain(text),
+                _ if href.starts_with("mailto:") || href.starts_with("tel:") || text.starts_with('@') =>
+                    RichText::make_plain(text),
+                _ if text.is_empty() => RichText::make_link(None, href.to_owned()),
+                _ => RichText::make_link(Some(text), href.to_owned()),
+            }
+        }
+        _ => RichText::make_plain(text),
+    }
+}
+
+/// Media that wasn't downloaded is represented by a `div` instead of a link.
+fn parse_media(media_wrap: ElementRef, path_prefix: &str) -> Result<Option<Content>> {
+    let Some(el) = media_wrap.children().find_map(ElementRef::wrap) else {
+        return Ok(None);
+    };
+    let href_option = el.value().attr("href").filter(|href| !href.is_empty());
+    let path_option = href_option
+        .filter(|href| !href.contains("://"))
+        .map(|href| format!("{path_prefix}{href}"));
+    let mime_type_option = path_option.as_deref()
+        .and_then(super::discord::mime_type_by_file_name)
+        .map(|mime| mime.to_owned());
+    let thumbnail_path_option = el.descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|e| e.value().name() == "img")
+        .and_then(|img| img.value().attr("src"))
+        .map(|src| format!("{path_prefix}{src}"));
+    let title_option = descendant_with_class(el, "title").map(own_text);
+    let status_option = descendant_with_class(el, "status").map(own_text);
+    let duration_sec_option = descendant_with_class(el, "video_duration").map(own_text)
+        .or(status_option.clone())
+        .and_then(|s| parse_duration(&s));
+    let title_is = |expected: &str| title_option.as_deref() == Some(expected);
+
+    let content = if has_class(el, "sticker_wrap") || (has_class(el, "media_photo") && title_is("Sticker")) {
+        content!(Sticker {
+            path_option,
+            file_name_option: None,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            thumbnail_path_option,
+            emoji_option: None,
+        })
+    } else if has_class(el, "photo_wrap") || has_class(el, "media_photo") {
+        content!(Photo {
+            path_option,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+        })
+    } else if has_class(el, "media_video") && title_is("Video message") {
+        content!(VideoMsg {
+            path_option,
+            file_name_option: None,
+            width: 0,
+            height: 0,
+            mime_type_option,
+            duration_sec_option,
+            thumbnail_path_option,
+            is_one_time: false,
+        })
+    } else if has_class(el, "video_file_wrap") || has_class(el, "animated_wrap") || has_class(el, "media_video") {
+        content!(Video {
+            path_option,
+            file_name_option: None,
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: mime_type_option.unwrap_or_else(|| UNKNOWN_MIME_TYPE.to_owned()),
+            duration_sec_option,
+            thumbnail_path_option,
+            is_one_time: false,
+        })
+    } else if has_class(el, "media_voice_message") {
+        content!(VoiceMsg {
+            path_option,
+            file_name_option: None,
+            mime_type: mime_type_option.unwrap_or_else(|| UNKNOWN_MIME_TYPE.to_owned()),
+            duration_sec_option,
+        })
+    } else if has_class(el, "media_audio_file") {
+        content!(Audio {
+            path_option,
+            file_name_option: None,
+            title_option,
+            performer_option: None,
+            mime_type: mime_type_option.unwrap_or_else(|| UNKNOWN_MIME_TYPE.to_owned()),
+            duration_sec_option,
+            thumbnail_path_option: None,
+        })
+    } else if has_class(el, "media_file") {
+        content!(File {
+            path_option,
+            file_name_option: title_option,
+            mime_type_option,
+            thumbnail_path_option,
+        })
+    } else if has_class(el, "media_location") || has_class(el, "media_live_location") {
+        let captures = href_option.and_then(|href| LOCATION_REGEX.captures(href))
+            .context("Location coordinates not found")?;
+        content!(Location {
+            title_option: title_option.filter(|title| title != "Location" && title != "Live location"),
+            address_option: None,
+            lat_str: captures.get(1).unwrap().as_str().to_owned(),
+            lon_str: captures.get(2).unwrap().as_str().to_owned(),
+            duration_sec_option: None,
+        })
+    } else if has_class(el, "media_contact") {
+        content!(SharedContact {
+            first_name_option: title_option,
+            last_name_option: None,
+            phone_number_option: status_option.map(|pn| PhoneNumber::from_raw(&pn).0),
+            vcard_path_option: path_option,
+        })
+    } else if has_class(el, "media_poll") {
+        content!(Poll {
+            question: descendant_with_class(el, "question").map(own_text).context("Poll has no question")?,
+        })
+    } else {
+        log::warn!("Skipping unsupported media: {:?}", el.value().classes().collect_vec());
+        return Ok(None);
+    };
+    Ok(Some(content))
+}
+
+/// Duration is formatted as `MM:SS` or `H:MM:SS`, possibly followed by file size.
+fn parse_duration(s: &str) -> Option<i32> {
+    let captures = DURATION_REGEX.captures(s)?;
+    let part = |idx: usize| captures.get(idx).map_or(0, |m| m.as_str().parse::<i32>().unwrap());
+    Some(part(1) * 3600 + part(2) * 60 + part(3))
+}
+
+fn parse_reactions(reactions_el: ElementRef) -> Vec<Reaction> {
+    reactions_el.children()
+        .filter_map(ElementRef::wrap)
+        .filter(|r| has_class(*r, "reaction"))
+        .filter_map(|r| {
+            let emoji = descendant_with_class(r, "emoji").map(own_text)?;
+            // Either a count is shown, or reacted users userpics
+            let count = descendant_with_class(r, "count")
+                .and_then(|count| own_text(count).parse::<i32>().ok())
+                .unwrap_or_else(|| {
+                    r.descendants().filter_map(ElementRef::wrap).filter(|e| has_class(*e, "userpic")).count().max(1) as i32
+                });
+            Some(Reaction {
+                emoji_option: Some(emoji),
+                custom_emoji_id_option: None,
+                from_ids: vec![],
+                count,
+            })
+        })
+        .collect_vec()
+}
+
+//
+// Resolving messages
+//
+
+fn resolve_messages(raw_messages: Vec<RawMessage>,
+                    sender_to_id: &HashMap<RawSender, UserId>,
+                    name_to_id: &HashMap<String, UserId>,
+                    myself_id: UserId) -> Result<Vec<Message>> {
+    // Service messages take timestamp of a preceding message, or of a following one if there's none
+    let mut timestamps = raw_messages.iter().map(|m| m.timestamp_option).collect_vec();
+    let mut last_timestamp_option = None;
+    for ts in timestamps.iter_mut() {
+        *ts = ts.or(last_timestamp_option);
+        last_timestamp_option = *ts;
+    }
+    let mut next_timestamp_option = None;
+    for ts in timestamps.iter_mut().rev() {
+        *ts = ts.or(next_timestamp_option);
+        next_timestamp_option = *ts;
+    }
+
+    raw_messages.into_iter().zip(timestamps).enumerate().map(|(internal_id, (m, timestamp_option))| {
+        let from_id = match m.from_option {
+            Some(from) => *sender_to_id.get(&from).context("Unknown sender")?,
+            None => {
+                // Actor is whoever is mentioned first, longest name wins
+                let text = m.text.first().and_then(|rte| rte.get_text()).unwrap_or_default();
+                name_to_id.iter()
+                    .filter(|(name, _)| text.starts_with(&format!("{name} ")))
+                    .max_by_key(|(name, _)| name.len())
+                    .map(|(_, id)| *id)
+                    .unwrap_or(myself_id)
+            }
+        };
+        Ok(Message::new(
+            internal_id as i64,
+            Some(m.source_id),
+            timestamp_option.with_context(|| format!("Message {} has no timestamp", m.source_id))?,
+            from_id,
+            m.text,
+            m.typed,
+        ))
+    }).try_collect()
+}
+
+//
+// HTML helpers
+//
+
+fn has_class(el: ElementRef, class: &str) -> bool {
+    el.value().classes().any(|c| c == class)
+}
+
+fn child_with_class<'a>(el: ElementRef<'a>, class: &str) -> Option<ElementRef<'a>> {
+    el.children().filter_map(ElementRef::wrap).find(|child| has_class(*child, class))
+}
+
+fn descendant_with_class<'a>(el: ElementRef<'a>, class: &str) -> Option<ElementRef<'a>> {
+    el.descendants().filter_map(ElementRef::wrap).find(|child| has_class(*child, class))
+}
+
+/// Peer name, along with a peer it links to (if any). Name might be wrapped in a link, or be a text itself.
+fn parse_peer(el: ElementRef) -> (String, Option<PeerLink>) {
+    let link_option = el.children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "a")
+        .find_map(|a| a.value().attr("href").and_then(PeerLink::parse).map(|peer| (a, peer)));
+    match link_option {
+        Some((a, peer)) => (own_text(a), Some(peer)),
+        None => (own_text(el), None),
+    }
+}
+
+/// Text of the element itself, ignoring nested elements (e.g. "via @bot" in a sender name).
+fn own_text(el: ElementRef) -> String {
+    el.children()
+        .filter_map(|node| node.value().as_text().map(|text| (**text).to_owned()))
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
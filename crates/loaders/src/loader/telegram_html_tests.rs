@@ -0,0 +1,412 @@
+#![allow(unused_imports)]
+
+use super::*;
+
+use crate::entity_utils::*;
+use crate::loader::{hash_to_id, TelegramDataLoader};
+use chat_history_manager_core::protobuf::history::content::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::message::*;
+use chat_history_manager_core::protobuf::history::message_service::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::User;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+const LOADER: TelegramHtmlDataLoader = TelegramHtmlDataLoader;
+
+const MYSELF_NAME: &str = "Aaaaa Aaaaa";
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2026_10() -> EmptyRes {
+    let res = resource("telegram-html_2026-10/export_results.html");
+    LOADER.looks_about_right(&res)?;
+
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: Some(hash_to_id(MYSELF_NAME)), text: None };
+    let dao = LOADER.load(&feedback_client, &res)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, user(ds_uuid, MYSELF_NAME));
+
+    let member_b = user(ds_uuid, "Bbbbb Bbbbb");
+    let member_c = user(ds_uuid, "Ccccc Ccccc");
+    let members = vec![member_b.clone(), member_c.clone()].into_iter().sorted_by_key(|u| u.id).collect_vec();
+    assert_eq!(dao.users_single_ds(), std::iter::once(myself.clone()).chain(members.iter().cloned()).collect_vec());
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 2);
+
+    // Group chat, split across two pages
+    {
+        let cwm = &cwms[0];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id("My Group"),
+            name_option: Some("My Group".to_owned()),
+            source_type: SourceType::Telegram as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: std::iter::once(myself.id).chain(members.iter().map(|u| u.id)).collect_vec(),
+            msg_count: 8,
+            main_chat_id: None,
+        });
+
+        let msgs = &cwm.messages;
+        assert_eq!(msgs.iter().map(|m| m.source_id_option.unwrap()).collect_vec(),
+                   vec![201, 202, 203, 204, 205, 206, 207, 208]);
+        assert_eq!(msgs.iter().map(|m| m.internal_id).collect_vec(), (0..8).collect_vec());
+
+        // Service message timestamp is taken from the following message, actor is guessed from the text
+        assert_eq!(msgs[0], Message::new(
+            0,
+            Some(201),
+            1790924400,
+            myself.id(),
+            vec![RichText::make_plain("Aaaaa Aaaaa created group «My Group»".to_owned())],
+            message_service!(ServiceSvo::Notice(MessageServiceNotice {})),
+        ));
+        assert_eq!(msgs[1].text, vec![RichText::make_plain("Welcome".to_owned())]);
+
+        assert_eq!(msgs[2].from_id, member_c.id);
+        assert_eq!(msgs[2].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Sticker {
+                path_option: Some("chats/chat_001/stickers/sticker.webp".to_owned()),
+                file_name_option: None,
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                thumbnail_path_option: Some("chats/chat_001/stickers/sticker.webp_thumb.jpg".to_owned()),
+                emoji_option: None,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        // Joined message
+        assert_eq!(msgs[3].from_id, member_c.id);
+        assert_eq!(msgs[3].timestamp, 1790924520);
+        assert_eq!(msgs[3].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(File {
+                path_option: Some("chats/chat_001/files/report.pdf".to_owned()),
+                file_name_option: Some("report.pdf".to_owned()),
+                mime_type_option: Some("application/pdf".to_owned()),
+                thumbnail_path_option: None,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        // First message on a second page
+        assert_eq!(msgs[4].from_id, member_c.id);
+        assert_eq!(msgs[4].timestamp, 1790924520);
+        assert_eq!(msgs[4].text, vec![RichText::make_plain("Ccccc Ccccc invited Bbbbb Bbbbb".to_owned())]);
+
+        assert_eq!(msgs[5].from_id, member_b.id);
+        assert_eq!(msgs[5].timestamp, 1790928000);
+        assert_eq!(msgs[5].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Location {
+                title_option: None,
+                address_option: None,
+                lat_str: "55.755812".to_owned(),
+                lon_str: "37.6173".to_owned(),
+                duration_sec_option: None,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        assert_eq!(msgs[6].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Video {
+                path_option: Some("chats/chat_001/video_files/video_1@02-10-2026_11-01-00.mp4".to_owned()),
+                file_name_option: None,
+                title_option: None,
+                performer_option: None,
+                width: 0,
+                height: 0,
+                mime_type: "video/mp4".to_owned(),
+                duration_sec_option: Some(13),
+                thumbnail_path_option: Some("chats/chat_001/video_files/video_1@02-10-2026_11-01-00.mp4_thumb.jpg".to_owned()),
+                is_one_time: false,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        // Photo that wasn't downloaded
+        assert_eq!(msgs[7].text, vec![RichText::make_prefmt_block("fn main() {}".to_owned(), Some("rust".to_owned()))]);
+        assert_eq!(msgs[7].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Photo {
+                path_option: None,
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+    }
+
+    // Personal chat
+    {
+        let cwm = &cwms[1];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: member_b.id,
+            name_option: Some("Bbbbb Bbbbb".to_owned()),
+            source_type: SourceType::Telegram as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, member_b.id],
+            msg_count: 5,
+            main_chat_id: None,
+        });
+
+        let msgs = &cwm.messages;
+        assert_eq!(msgs.len(), 5);
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            Some(101),
+            1790860440,
+            member_b.id(),
+            vec![
+                RichText::make_plain("Hello ".to_owned()),
+                RichText::make_bold("there".to_owned()),
+                RichText::make_plain("!\nHow are you?".to_owned()),
+            ],
+            MESSAGE_REGULAR_NO_CONTENT.clone(),
+        ));
+
+        assert_eq!(msgs[1].from_id, member_b.id);
+        assert_eq!(msgs[1].text, vec![RichText::make_plain("Look".to_owned())]);
+        assert_eq!(msgs[1].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Photo {
+                path_option: Some("chats/chat_002/photos/photo_1@01-10-2026_16-14-05.jpg".to_owned()),
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        assert_eq!(msgs[2], Message::new(
+            2,
+            Some(103),
+            1790860500,
+            myself.id(),
+            vec![
+                RichText::make_plain("See ".to_owned()),
+                RichText::make_link(Some("https://example.com".to_owned()), "https://example.com".to_owned()),
+                RichText::make_plain(", ".to_owned()),
+                RichText::make_italic("it's".to_owned()),
+                RichText::make_plain(" @somebody".to_owned()),
+            ],
+            message_regular! {
+                edit_timestamp_option: Some(1790860800),
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: Some(101),
+                contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+
+        assert_eq!(msgs[3].from_id, myself.id);
+        assert_eq!(msgs[3].typed(), &message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(VoiceMsg {
+                path_option: Some("chats/chat_002/voice_messages/audio_1@01-10-2026_16-16-00.ogg".to_owned()),
+                file_name_option: None,
+                mime_type: "audio/ogg".to_owned(),
+                duration_sec_option: Some(7),
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        });
+
+        assert_eq!(msgs[4], Message::new(
+            4,
+            Some(105),
+            1790860620,
+            member_b.id(),
+            vec![RichText::make_plain("Forwarded text".to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: Some("Ccccc Ccccc".to_owned()),
+                reply_to_message_id_option: None,
+                contents: vec![],
+                reactions: vec![Reaction {
+                    emoji_option: Some("👍".to_owned()),
+                    custom_emoji_id_option: None,
+                    from_ids: vec![],
+                    count: 2,
+                }],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+    }
+
+    Ok(())
+}
+
+/// Same chat exported as both HTML and JSON should yield the same chat, users and messages, save for media details.
+/// Names are linked to peers, so IDs match.
+#[test]
+fn loading_2026_10_matches_json() -> EmptyRes {
+    let html_res = resource("telegram-html_2026-10_both/messages.html");
+    let json_res = resource("telegram-html_2026-10_both/result.json");
+    LOADER.looks_about_right(&html_res)?;
+    assert!(LOADER.looks_about_right(&json_res).is_err());
+
+    let html_dao = LOADER.load(&PredefinedInputFeedbackClient { myself_id: Some(11111111), text: None }, &html_res)?;
+    let json_dao = TelegramDataLoader.load(&PredefinedInputFeedbackClient { myself_id: Some(11111111), text: None }, &json_res)?;
+
+    assert_eq!(html_dao.name, json_dao.name);
+    assert_eq!(html_dao.users_single_ds().iter().map(|u| (u.id, u.pretty_name())).collect_vec(),
+               json_dao.users_single_ds().iter().map(|u| (u.id, u.pretty_name())).collect_vec());
+    assert_eq!(html_dao.myself_single_ds().id, json_dao.myself_single_ds().id);
+
+    let html_cwm = html_dao.cwms_single_ds().remove(0);
+    let json_cwm = json_dao.cwms_single_ds().remove(0);
+    assert_eq!(html_cwm.chat.id, json_cwm.chat.id);
+    assert_eq!(html_cwm.chat.name_option, json_cwm.chat.name_option);
+    assert_eq!(html_cwm.chat.member_ids, json_cwm.chat.member_ids);
+    assert_eq!(html_cwm.chat.tpe, json_cwm.chat.tpe);
+    assert_eq!(html_cwm.messages.len(), json_cwm.messages.len());
+
+    for (html_msg, json_msg) in html_cwm.messages.iter().zip(json_cwm.messages.iter()) {
+        assert_eq!(html_msg.internal_id, json_msg.internal_id);
+        assert_eq!(html_msg.source_id_option, json_msg.source_id_option);
+        assert_eq!(html_msg.timestamp, json_msg.timestamp);
+        assert_eq!(html_msg.from_id, json_msg.from_id);
+        assert_eq!(html_msg.text, json_msg.text);
+
+        let (Typed::Regular(html_mr), Typed::Regular(json_mr)) = (html_msg.typed(), json_msg.typed()) else {
+            panic!("Unexpected message types: {html_msg:?}, {json_msg:?}");
+        };
+        assert_eq!(html_mr.edit_timestamp_option, json_mr.edit_timestamp_option);
+        assert_eq!(html_mr.forward_from_name_option, json_mr.forward_from_name_option);
+        assert_eq!(html_mr.reply_to_message_id_option, json_mr.reply_to_message_id_option);
+        assert_eq!(html_mr.reactions, json_mr.reactions);
+        assert_eq!(html_mr.contents.len(), json_mr.contents.len());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn loading_same_name_users() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("messages.html");
+    let message = |id: i64, from: &str, text: &str| format!(r#"
+        <div class="message default clearfix" id="message{id}">
+         <div class="body">
+          <div class="pull_right date details" title="01.10.2026 16:{id}:00 UTC+03:00">16:{id}</div>
+          <div class="from_name">{from}</div>
+          <div class="text">{text}</div>
+         </div>
+        </div>"#);
+    fs::write(&path, [
+        "<!DOCTYPE html>\n",
+        r#"<html><body><div class="page_wrap">"#,
+        r#"<div class="page_header"><div class="text bold"><a href="https://t.me/c/555/10">Namesakes</a></div></div>"#,
+        r#"<div class="page_body chat_page"><div class="history">"#,
+        message(10, r#"<a href="tg://user?id=111">Aaaaa Aaaaa</a>"#, "Hi").as_str(),
+        message(11, r#"<a href="tg://user?id=222">Bbbbb Bbbbb</a>"#, "Hi from the first Bbbbb").as_str(),
+        message(12, r#"<a href="tg://user?id=333">Bbbbb Bbbbb</a>"#, "Hi from the second Bbbbb").as_str(),
+        message(13, "Aaaaa Aaaaa", "Unlinked name of a unique user").as_str(),
+        "</div></div></div></body></html>",
+    ].concat())?;
+
+    let dao = LOADER.load(&PredefinedInputFeedbackClient { myself_id: Some(111), text: None }, &path)?;
+    assert_eq!(dao.myself_single_ds().id, 111);
+    assert_eq!(dao.users_single_ds().iter().map(|u| (u.id, u.pretty_name())).collect_vec(), vec![
+        (111, "Aaaaa Aaaaa".to_owned()),
+        (222, "Bbbbb Bbbbb".to_owned()),
+        (333, "Bbbbb Bbbbb".to_owned()),
+    ]);
+
+    let cwm = dao.cwms_single_ds().remove(0);
+    assert_eq!(cwm.chat.id, 555);
+    assert_eq!(cwm.chat.tpe, ChatType::PrivateGroup as i32);
+    assert_eq!(cwm.chat.member_ids, vec![111, 222, 333]);
+    assert_eq!(cwm.messages.iter().map(|m| m.from_id).collect_vec(), vec![111, 222, 333, 111]);
+
+    Ok(())
+}
+
+#[test]
+fn rejecting_other_files() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    for (name, content) in [
+        ("messages.html", "{}"),
+        ("export_results.html", "<html></html>"),
+        ("messages2.html", "<!DOCTYPE html>"),
+        ("index.html", "<!DOCTYPE html>"),
+    ] {
+        let path = tmp_dir.path.join(name);
+        fs::write(&path, content)?;
+        assert!(LOADER.looks_about_right(&path).is_err(), "{name} should be rejected");
+    }
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn user(ds_uuid: &PbUuid, name: &str) -> User {
+    User {
+        ds_uuid: ds_uuid.clone(),
+        id: hash_to_id(name),
+        first_name_option: Some(name.to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    }
+}
@@ -8,6 +8,8 @@ use chat_history_manager_core::protobuf::history::message_service::SealedValueOp
 
 use super::*;
 
+use chat_history_manager_dao::get_datasets_diff;
+
 static LOADER: TelegramDataLoader = TelegramDataLoader;
 
 //
@@ -269,6 +271,9 @@ fn loading_2021_06_supergroup() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
 
@@ -290,6 +295,9 @@ fn loading_2021_06_supergroup() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[3], Message {
@@ -312,6 +320,9 @@ fn loading_2021_06_supergroup() -> EmptyRes {
                         vcard_path_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     };
@@ -510,6 +521,9 @@ fn loading_2023_01() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[3], Message {
@@ -632,6 +646,9 @@ fn loading_2023_08() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     };
@@ -708,6 +725,9 @@ fn loading_2023_10_audio_video() -> EmptyRes {
                         thumbnail_path_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[1], Message {
@@ -733,6 +753,9 @@ fn loading_2023_10_audio_video() -> EmptyRes {
                         thumbnail_path_option: Some("audio_file.mp3_thumb.jpg".to_owned()),
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[2], Message {
@@ -761,6 +784,9 @@ fn loading_2023_10_audio_video() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[3], Message {
@@ -789,6 +815,9 @@ fn loading_2023_10_audio_video() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     };
@@ -868,6 +897,9 @@ fn loading_2024_02() -> EmptyRes {
             forward_from_name_option: Some("Forwarded From Name".to_owned()),
             reply_to_message_id_option: None,
             contents: vec![],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -906,6 +938,9 @@ fn loading_2024_05() -> EmptyRes {
                     thumbnail_path_option: None,
                 })
             ],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -932,6 +967,9 @@ fn loading_2024_05() -> EmptyRes {
                     emoji_option: Some("😱".to_owned()),
                 })
             ],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -972,6 +1010,9 @@ fn loading_2024_06() -> EmptyRes {
             forward_from_name_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -1002,6 +1043,9 @@ fn loading_2024_08() -> EmptyRes {
             forward_from_name_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: Some("Admin Nickname".to_owned()),
         }),
     });
 
@@ -1018,6 +1062,9 @@ fn loading_2024_08() -> EmptyRes {
             forward_from_name_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -1054,6 +1101,9 @@ fn loading_2024_08() -> EmptyRes {
                     is_one_time: true,
                 })
             ],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -1102,6 +1152,9 @@ fn loading_2025_02() -> EmptyRes {
                     is_one_time: false,
                 })
             ],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -1192,6 +1245,9 @@ fn loading_2025_12() -> EmptyRes {
             forward_from_name_option: Some("Dummy Private Group".to_owned()),
             reply_to_message_id_option: None,
             contents: vec![],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -1229,6 +1285,9 @@ fn loading_2025_12() -> EmptyRes {
                     ],
                 })
             ],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
@@ -1258,9 +1317,160 @@ fn loading_2025_12() -> EmptyRes {
                     is_one_time: true,
                 })
             ],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        }),
+    });
+
+    let cwm = &dao.cwms_single_ds()[1];
+    assert_eq!(cwm.chat.name_option.as_deref(), Some("Dummy Public Channel"));
+    assert_eq!(cwm.chat.tpe, ChatType::Channel as i32);
+    assert_eq!(cwm.chat.id, 123456789);
+    let msgs = &cwm.messages;
+    assert_eq!(msgs.len(), 1);
+
+    assert_eq!(msgs[0], Message {
+        internal_id: 0,
+        source_id_option: Some(11111000),
+        timestamp: 1665499750,
+        from_id: 1123456789,
+        text: vec![RichText::make_plain("Channel post!".to_owned())],
+        searchable_string: "Channel post!".to_owned(),
+        typed: Some(message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: Some("Channel Admin".to_owned()),
+        }),
+    });
+
+    Ok(())
+}
+
+#[test]
+fn loading_2026_10_forum() -> EmptyRes {
+    let res = resource("telegram_2026-10_forum");
+    LOADER.looks_about_right(&res)?;
+
+    let dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 3);
+
+    let forum_chat_id = 1500000000 + GROUP_CHAT_ID_SHIFT;
+    let topic_chat_id = |topic_id: i64| forum_chat_id * TOPIC_CHAT_ID_MULTIPLIER + topic_id;
+    let chat_tuple = |cwm: &ChatWithMessages| (
+        cwm.chat.id,
+        cwm.chat.name_option.clone(),
+        cwm.chat.main_chat_id,
+        cwm.chat.msg_count,
+        cwm.messages.iter().map(|m| m.source_id_option.unwrap()).collect_vec(),
+    );
+    assert_eq!(chat_tuple(&cwms[0]),
+               (forum_chat_id, Some("Dummy Forum".to_owned()), None, 3, vec![100, 107, 108]));
+    assert_eq!(chat_tuple(&cwms[1]),
+               (topic_chat_id(101), Some("Topic A".to_owned()), Some(forum_chat_id), 3, vec![101, 102, 104]));
+    assert_eq!(chat_tuple(&cwms[2]),
+               (topic_chat_id(103), Some("Topic B2".to_owned()), Some(forum_chat_id), 3, vec![103, 105, 106]));
+
+    for cwm in &cwms {
+        assert_eq!(cwm.chat.tpe, ChatType::PrivateGroup as i32);
+        assert_eq!(cwm.chat.member_ids, vec![11111111, 22222222]);
+        assert_eq!(cwm.messages.iter().map(|m| m.internal_id).collect_vec(), vec![0, 1, 2]);
+    }
+
+    assert_eq!(cwms[1].messages[0], Message {
+        internal_id: 0,
+        source_id_option: Some(101),
+        timestamp: 1665499801,
+        from_id: 22222222,
+        text: vec![RichText::make_plain("Created topic \"Topic A\"".to_owned())],
+        searchable_string: "Created topic \"Topic A\"".to_owned(),
+        typed: Some(message_service!(Notice(MessageServiceNotice {}))),
+    });
+
+    assert_eq!(cwms[2].messages[2], Message {
+        internal_id: 2,
+        source_id_option: Some(106),
+        timestamp: 1665499806,
+        from_id: 11111111,
+        text: vec![RichText::make_plain("Renamed topic to \"Topic B2\"".to_owned())],
+        searchable_string: "Renamed topic to \"Topic B2\"".to_owned(),
+        typed: Some(message_service!(Notice(MessageServiceNotice {}))),
+    });
+
+    Ok(())
+}
+
+#[test]
+fn loading_2026_10_reactions() -> EmptyRes {
+    let res = resource("telegram_2026-10_reactions");
+    LOADER.looks_about_right(&res)?;
+
+    let dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let cwm = &dao.cwms_single_ds()[0];
+    assert_eq!(cwm.chat.member_ids, vec![11111111, 22222222, 33333333]);
+    assert!(dao.users_single_ds().contains(&ShortUser::new_name_str(UserId(33333333), "Ccccc Ccccccccc").to_user(ds_uuid)));
+
+    let msgs = &cwm.messages;
+    assert_eq!(msgs.len(), 2);
+
+    assert_eq!(msgs[0], Message {
+        internal_id: 0,
+        source_id_option: Some(11111),
+        timestamp: 1791759751,
+        from_id: 11111111,
+        text: vec![RichText::make_plain("React to this".to_owned())],
+        searchable_string: "React to this".to_owned(),
+        typed: Some(message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![],
+            reactions: vec![
+                Reaction {
+                    emoji_option: Some("👍".to_owned()),
+                    custom_emoji_id_option: None,
+                    from_ids: vec![22222222, 33333333],
+                    count: 3,
+                },
+                Reaction {
+                    emoji_option: None,
+                    custom_emoji_id_option: Some("5368324170671202286".to_owned()),
+                    from_ids: vec![11111111],
+                    count: 1,
+                },
+                Reaction {
+                    emoji_option: Some(PAID_REACTION_EMOJI.to_owned()),
+                    custom_emoji_id_option: None,
+                    from_ids: vec![],
+                    count: 5,
+                },
+            ],
+            views_option: None,
+            author_signature_option: None,
         }),
     });
 
+    assert_eq!(msgs[1].typed(), &message_regular! {
+        edit_timestamp_option: None,
+        is_deleted: false,
+        forward_from_name_option: None,
+        reply_to_message_id_option: None,
+        contents: vec![],
+        reactions: vec![],
+        views_option: None,
+        author_signature_option: None,
+    });
+
     Ok(())
 }
 
@@ -1306,6 +1516,22 @@ fn inline_bot_buttons() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_into_sqlite() -> EmptyRes {
+    let res = resource("telegram_2020-01");
+    let in_memory_dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let tmp_dir = TmpDir::new();
+    let sqlite_dao = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME))?;
+    LOADER.load_into(&NoFeedbackClient, &res, &mut sqlite_dao.sink()?)?;
+
+    let sqlite_ds_uuid = sqlite_dao.datasets()?.remove(0).uuid;
+    let diff = get_datasets_diff(in_memory_dao.as_ref(), &in_memory_dao.ds_uuid(), &sqlite_dao, &sqlite_ds_uuid, 10)?;
+    assert!(diff.is_empty(), "{}", diff.iter().join("\n\n"));
+
+    Ok(())
+}
+
 //
 // Helpers
 //
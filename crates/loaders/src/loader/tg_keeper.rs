@@ -101,7 +101,10 @@ fn load_everything(
             let tpe = match raw_chat {
                 types::Chat::User(_) => ChatType::Personal,
                 types::Chat::Group(_) => ChatType::PrivateGroup,
-                types::Chat::Channel(_) => return None, // Skip
+                // Private supergroups are channels under the hood, but Telegram export treats them as groups
+                types::Chat::Channel(channel) if channel.raw.megagroup && channel.raw.username.is_none() =>
+                    ChatType::PrivateGroup,
+                types::Chat::Channel(_) => ChatType::Channel,
             };
 
             let raw_id = RawChatId(raw_chat.id());
@@ -129,13 +132,14 @@ fn load_everything(
                 msg_count: 0,       // Will be set by builder
                 main_chat_id: None,
             };
-            Some((raw_id, CwmBuilder::new(chat)))
+            let is_channel = matches!(raw_chat, types::Chat::Channel(_));
+            Some((raw_id, CwmBuilder::new(chat, is_channel)))
         })
         .collect();
 
     for raw_msg in raw_messages {
         if matches!(raw_msg.tpe, RawMessageType::Deleted) {
-            mark_message_deleted(&mut cwm_builders, raw_msg.id)?;
+            mark_message_deleted(&mut cwm_builders, raw_msg.chat_id, raw_msg.id)?;
         } else {
             let Some(chat_id) = raw_msg.chat_id else {
                 bail!(
@@ -268,6 +272,22 @@ fn get_users(
                     },
                 ))
             }
+            types::Chat::Channel(channel) => {
+                // Channel posts are authored by the channel itself, same as in Telegram export
+                let raw_peer_id = RawPeerId(channel.raw.id);
+                Some((
+                    raw_peer_id,
+                    User {
+                        ds_uuid: ds_uuid.clone(),
+                        id: raw_peer_id.normalize_user_id().0,
+                        first_name_option: Some(channel.raw.title.clone()),
+                        last_name_option: None,
+                        username_option: None,
+                        phone_number_option: None,
+                        profile_pictures: vec![],
+                    },
+                ))
+            }
             _ => None,
         })
         .collect();
@@ -285,20 +305,27 @@ fn get_users(
 
 fn mark_message_deleted(
     cwm_builders: &mut CwmBuilders,
+    chat_id: Option<RawChatId>,
     msg_id: MessageInternalId,
 ) -> EmptyRes {
-    // We don't know which chat has this message, let's make sure there's not more than one
     let mut msg: Option<&mut Message> = None;
-    for candidate in cwm_builders.values_mut() {
-        if let Some(candidate_msg) = candidate.messages.get_mut(&msg_id) {
-            if msg.is_none() {
-                msg = Some(candidate_msg);
-            } else {
-                // More than one chat has this message, we can't handle this
-                bail!(
-                    "Message #{} is deleted but it's contained in more than one chat",
-                    msg_id.0
-                );
+    if let Some(chat_id) = chat_id {
+        // Channel message IDs are only unique within a channel
+        msg = cwm_builders.get_mut(&chat_id).and_then(|b| b.messages.get_mut(&msg_id));
+    } else {
+        // We don't know which chat has this message, let's make sure there's not more than one.
+        // Channels are excluded since their deletions always come with a chat ID.
+        for candidate in cwm_builders.values_mut().filter(|b| !b.is_channel) {
+            if let Some(candidate_msg) = candidate.messages.get_mut(&msg_id) {
+                if msg.is_none() {
+                    msg = Some(candidate_msg);
+                } else {
+                    // More than one chat has this message, we can't handle this
+                    bail!(
+                        "Message #{} is deleted but it's contained in more than one chat",
+                        msg_id.0
+                    );
+                }
             }
         }
     }
@@ -370,6 +397,9 @@ fn parse_message(
                 forward_from_name_option,
                 reply_to_message_id_option,
                 contents,
+                reactions: vec![],
+                views_option: inner.views,
+                author_signature_option: inner.post_author,
             );
             (inner.date, text, typed)
         }
@@ -1132,14 +1162,17 @@ impl WithResolvePrettyName for Users {
 
 struct CwmBuilder {
     chat: Chat,
+    /// Whether the chat is a Telegram channel (incl. supergroups) with its own message IDs sequence
+    is_channel: bool,
     member_ids: HashSet<UserId>,
     messages: BTreeMap<MessageInternalId, Message>,
 }
 
 impl CwmBuilder {
-    fn new(chat: Chat) -> Self {
+    fn new(chat: Chat, is_channel: bool) -> Self {
         Self {
             chat,
+            is_channel,
             member_ids: HashSet::new(),
             messages: BTreeMap::new(),
         }
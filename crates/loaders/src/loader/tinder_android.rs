@@ -35,7 +35,7 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
         &self,
         _feedback_client: &dyn FeedbackClientSync,
         users: Self::Users,
-        _cwms: &[ChatWithMessages]
+        _chats: &[Chat]
     ) -> Result<Vec<User>> {
         let mut users = users.into_values().collect_vec();
         // Set myself to be a first member.
@@ -109,10 +109,9 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
         feedback_client: &dyn FeedbackClientSync,
         ds_uuid: &PbUuid,
         path: &Path,
-        users: &mut Users
-    ) -> Result<Vec<ChatWithMessages>> {
-        let mut cwms = vec![];
-
+        users: &mut Users,
+        push_cwm: &mut dyn FnMut(ChatWithMessages) -> EmptyRes,
+    ) -> EmptyRes {
         let downloaded_media_path = path.join(RELATIVE_MEDIA_DIR);
         fs::create_dir_all(&downloaded_media_path)?;
 
@@ -183,12 +182,15 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
                         forward_from_name_option: None,
                         reply_to_message_id_option: None,
                         contents,
+                        reactions: vec![],
+                        views_option: None,
+                        author_signature_option: None,
                     },
                 ));
             }
             messages.iter_mut().enumerate().for_each(|(i, m)| m.internal_id = i as i64);
 
-            cwms.push(ChatWithMessages {
+            push_cwm(ChatWithMessages {
                 chat: Chat {
                     ds_uuid: ds_uuid.clone(),
                     id: user.id,
@@ -201,10 +203,10 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
                     main_chat_id: None,
                 },
                 messages,
-            });
+            })?;
         }
 
-        Ok(cwms)
+        Ok(())
     }
 }
 
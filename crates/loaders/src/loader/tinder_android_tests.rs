@@ -99,6 +99,9 @@ fn loading_2023_11() -> EmptyRes {
                         emoji_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
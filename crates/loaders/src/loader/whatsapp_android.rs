@@ -1,29 +1,51 @@
-use super::android::AndroidDataLoader;
+use super::android::{AndroidDataLoader, MEDIA_DIR};
 use super::*;
 use calcard::vcard::VCardProperty;
 use chat_history_manager_core::utils::sqlite_utils::{JoinTable, JoinType, SelectExpr};
+use const_format::concatcp;
 use lazy_static::lazy_static;
 use num_traits::FromPrimitive;
 use regex::Regex;
 use rusqlite::{Connection, OptionalExtension, Row, Statement};
 use std::collections::hash_map::Entry;
 
+mod crypt;
+
 #[cfg(test)]
 #[path = "whatsapp_android_tests.rs"]
 mod tests;
 
 lazy_static! {
-    static ref PHONE_JID_REGEX: Regex = Regex::new(r"^([\d]{5,})@s.whatsapp.net$").unwrap();
+    pub(super) static ref PHONE_JID_REGEX: Regex = Regex::new(r"^([\d]{5,})@s.whatsapp.net$").unwrap();
 }
 
 /// Some notes about the implementation:
 /// 1. msgstore.db and wa.db file should lie in either in the data root folder, or in ./databases subfolder
 /// 2. Media is resolved using <data_root>/Media
 /// 3. User avatars are looked up in <data_root>/files/Avatars
+/// 4. Instead of msgstore.db, its encrypted backup (msgstore.db.crypt14/crypt15) could be used, see [crypt].
+///    Such backups don't include wa.db, so contact names might be unavailable.
+/// 5. Thumbnails embedded in the database are extracted to <data_root>/Media/_thumbnails
 pub struct WhatsAppAndroidDataLoader;
 
 const NAME: &str = "WhatsApp";
 pub const DB_FILENAME: &str = "msgstore.db";
+const WA_DB_FILENAME: &str = "wa.db";
+
+const AVATARS_DIR: &str = "files/Avatars";
+const THUMBNAILS_DIR: &str = concatcp!(MEDIA_DIR, "/_thumbnails");
+const DOCUMENTS_DIR: &str = concatcp!(MEDIA_DIR, "/WhatsApp Documents");
+const VOICE_NOTES_DIR: &str = concatcp!(MEDIA_DIR, "/WhatsApp Voice Notes");
+
+/// Tables that only exist in some WhatsApp versions, created empty if missing.
+const OPTIONAL_TABLES: &[(&str, &str)] = &[
+    ("message_view_once_media",
+     "CREATE TEMP TABLE message_view_once_media (message_row_id INTEGER PRIMARY KEY, state INTEGER)"),
+    ("message_thumbnails",
+     "CREATE TEMP TABLE message_thumbnails (key_remote_jid TEXT, key_from_me INTEGER, key_id TEXT, thumbnail BLOB, timestamp INTEGER)"),
+    ("media_hash_thumbnail",
+     "CREATE TEMP TABLE media_hash_thumbnail (media_hash TEXT PRIMARY KEY, thumbnail BLOB)"),
+];
 
 type Jid = String;
 type MessageKey = String;
@@ -60,16 +82,36 @@ impl Users {
 impl AndroidDataLoader for WhatsAppAndroidDataLoader {
     const NAME: &'static str = NAME;
     const DB_FILENAME: &'static str = DB_FILENAME;
+    const ENCRYPTED_DB_EXTENSIONS: &'static [&'static str] = &[crypt::CRYPT14_EXT, crypt::CRYPT15_EXT];
 
     type Users = Users;
 
+    fn decrypt_db(&self, feedback_client: &dyn FeedbackClientSync, path: &Path) -> Result<Vec<u8>> {
+        crypt::decrypt_db(feedback_client, path)
+    }
+
     fn tweak_conn(
         &self,
         conn: &Connection,
         _feedback_client: &dyn FeedbackClientSync,
         path: &Path,
     ) -> EmptyRes {
-        conn.execute(r#"ATTACH DATABASE ?1 AS wa_db"#, [path_to_str(&path.join("wa.db"))?])?;
+        let wa_db_path = path.join(WA_DB_FILENAME);
+        if wa_db_path.exists() {
+            conn.execute(r#"ATTACH DATABASE ?1 AS wa_db"#, [path_to_str(&wa_db_path)?])?;
+        } else {
+            // Contacts are unknown, e.g. when loading an encrypted backup
+            conn.execute_batch(r#"
+                ATTACH DATABASE ':memory:' AS wa_db;
+                CREATE TABLE wa_db.wa_contacts (jid TEXT, display_name TEXT, wa_name TEXT, number TEXT, nickname TEXT);
+                CREATE TABLE wa_db.wa_vnames (jid TEXT, verified_name TEXT);
+            "#)?;
+        }
+        for (table_name, create_sql) in OPTIONAL_TABLES {
+            if !sqlite_utils::table_exists(conn, table_name) {
+                conn.execute(create_sql, [])?;
+            }
+        }
         Ok(())
     }
 
@@ -77,13 +119,12 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
         &self,
         _feedback_client: &dyn FeedbackClientSync,
         users: Users,
-        cwms: &[ChatWithMessages],
+        chats: &[Chat],
     ) -> Result<Vec<User>> {
         let myself_id = users.myself_id.unwrap();
         // Filter out users not participating in chats.
-        let participating_user_ids: HashSet<i64, Hasher> = cwms
+        let participating_user_ids: HashSet<i64, Hasher> = chats
             .iter()
-            .map(|cwm| &cwm.chat)
             .flat_map(|c| &c.member_ids)
             .copied()
             .collect();
@@ -102,7 +143,7 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
         conn: &Connection,
         _feedback_client: &dyn FeedbackClientSync,
         ds_uuid: &PbUuid,
-        _path: &Path,
+        path: &Path,
     ) -> Result<Users> {
         let mut users: Users = Default::default();
 
@@ -116,7 +157,7 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
             LEFT JOIN wa_contacts ON wa_contacts.jid = jid.raw_string
             LEFT JOIN wa_vnames ON wa_vnames.jid = jid.raw_string
             GROUP BY jid.raw_string
-        ")?, ds_uuid, &mut users)?;
+        ")?, ds_uuid, path, &mut users)?;
 
         // Group chat users
         parse_users_from_stmt(&mut conn.prepare(r"
@@ -130,7 +171,7 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
             LEFT JOIN wa_vnames ON wa_vnames.jid = jid.raw_string
             WHERE message.sender_jid_row_id > 0
             GROUP BY jid.raw_string
-        ")?, ds_uuid, &mut users)?;
+        ")?, ds_uuid, path, &mut users)?;
 
         // It's not clear how to get own ID from WhatsApp.
         // As such:
@@ -163,14 +204,15 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
         conn: &Connection,
         _feedback_client: &dyn FeedbackClientSync,
         ds_uuid: &PbUuid,
-        _path: &Path,
+        path: &Path,
         users: &mut Users,
-    ) -> Result<Vec<ChatWithMessages>> {
-        parse_chats(conn, ds_uuid, users)
+        push_cwm: &mut dyn FnMut(ChatWithMessages) -> EmptyRes,
+    ) -> EmptyRes {
+        parse_chats(conn, ds_uuid, path, users, push_cwm)
     }
 }
 
-fn parse_users_from_stmt(stmt: &mut Statement, ds_uuid: &PbUuid, users: &mut Users) -> EmptyRes {
+fn parse_users_from_stmt(stmt: &mut Statement, ds_uuid: &PbUuid, root: &Path, users: &mut Users) -> EmptyRes {
     let mut rows = stmt.query([])?;
     while let Some(row) = rows.next()? {
         let jid = row.get::<_, String>("jid")?;
@@ -180,6 +222,14 @@ fn parse_users_from_stmt(stmt: &mut Statement, ds_uuid: &PbUuid, users: &mut Use
             continue;
         }
 
+        // wa.db only knows when the avatar was updated, the picture itself is only stored as a file
+        let avatar_path = format!("{AVATARS_DIR}/{jid}.j");
+        let profile_pictures = if root.join(&avatar_path).exists() {
+            vec![ProfilePicture { path: avatar_path, frame_option: None }]
+        } else {
+            vec![]
+        };
+
         let display_name_option = row.get::<_, Option<String>>("display_name")?;
         let verified_name_option = row.get::<_, Option<String>>("verified_name")?;
         let wa_name_option = row.get::<_, Option<String>>("wa_name")?;
@@ -206,7 +256,7 @@ fn parse_users_from_stmt(stmt: &mut Statement, ds_uuid: &PbUuid, users: &mut Use
             last_name_option: None, // Last name is unreliable
             username_option,
             phone_number_option,
-            profile_pictures: vec![], // TODO
+            profile_pictures,
         });
     }
     Ok(())
@@ -317,6 +367,10 @@ mod columns {
         pub const CAPTION: &str = "media_caption";
     }
 
+    pub mod message_view_once_media {
+        pub const IS_VIEW_ONCE: &str = "is_view_once";
+    }
+
     pub mod message_location {
         pub const LAT: &str = "latitude";
         pub const LON: &str = "longitude";
@@ -356,6 +410,7 @@ mod columns {
     pub const GROUP_USER_JID: &str = "group_user_jid";
     pub const MIGRATE_USER_JID: &str = "migrate_user_jid";
     pub const PARENT_KEY_ID: &str = "parent_key_id";
+    pub const THUMBNAIL: &str = "thumbnail";
 }
 
 enum TextParsingState {
@@ -370,8 +425,10 @@ enum TextParsingState {
 fn parse_chats(
     conn: &Connection,
     ds_uuid: &PbUuid,
+    root: &Path,
     users: &mut Users,
-) -> Result<Vec<ChatWithMessages>> {
+    push_cwm: &mut dyn FnMut(ChatWithMessages) -> EmptyRes,
+) -> EmptyRes {
     // Parent to child
     let assoc_ids = parse_message_associations(conn)?;
     // Child to parent
@@ -382,7 +439,8 @@ fn parse_chats(
         })
         .collect();
 
-    let mut cwms_map: HashMap<Jid, ChatWithMessages> = Default::default();
+    // Chats are processed and pushed one by one, along with their messages count.
+    let mut chats_map: HashMap<Jid, (Chat, usize)> = Default::default();
     let myself_id = users.myself_id.unwrap();
 
     const WA_OFFICIAL_ACCT_JID: &str = "0@s.whatsapp.net";
@@ -420,20 +478,17 @@ fn parse_chats(
             }
         };
 
-        cwms_map.insert(jid.clone(), ChatWithMessages {
-            chat: Chat {
-                ds_uuid: ds_uuid.clone(),
-                id,
-                name_option,
-                source_type: SourceType::WhatsappDb as i32,
-                tpe: tpe as i32,
-                img_path_option: Some(format!("files/Avatars/{jid}.j")),
-                member_ids: vec![],
-                msg_count: 0, // Some messages might be filtered out later, so at this point we're leaving it unset
-                main_chat_id: None,
-            },
-            messages: Vec::with_capacity(row.get::<_, usize>("msgs_count")?),
-        });
+        chats_map.insert(jid.clone(), (Chat {
+            ds_uuid: ds_uuid.clone(),
+            id,
+            name_option,
+            source_type: SourceType::WhatsappDb as i32,
+            tpe: tpe as i32,
+            img_path_option: Some(format!("{AVATARS_DIR}/{jid}.j")),
+            member_ids: vec![],
+            msg_count: 0, // Some messages might be filtered out later, so at this point we're leaving it unset
+            main_chat_id: None,
+        }, row.get::<_, usize>("msgs_count")?));
     }
 
     /*
@@ -444,9 +499,10 @@ fn parse_chats(
      * - Forwarded messages do not specify source.
      * - Call logs are stored separately - in call_log table.
      * - For source_id, we're using hash of `message.key_id` and `call_log.call_id`.
+     * - Thumbnails are either linked by message key or by media hash, a message might have neither.
      */
     let mut msgs_stmt = {
-        use columns::{chat::*, message::*, *};
+        use columns::{chat::*, message::*, message_view_once_media::*, *};
 
         const JOIN_BY_MESSAGE_ID: &str = "message_row_id = message._id";
 
@@ -468,6 +524,14 @@ fn parse_chats(
                         ELSE sender_jid.raw_string
                       END AS {SENDER_JID},
                       chat.{SUBJECT},
+                      COALESCE(
+                        (SELECT thumbnail FROM message_thumbnails mt
+                         WHERE mt.key_id = message.{KEY} LIMIT 1),
+                        (SELECT thumbnail FROM media_hash_thumbnail mht
+                         WHERE mht.media_hash = message_media.file_hash LIMIT 1)
+                      ) AS {THUMBNAIL},
+                      EXISTS (SELECT 1 FROM message_view_once_media vo
+                              WHERE vo.message_row_id = message._id) AS {IS_VIEW_ONCE},
                       message.*"
             ),
             &format!(r"FROM message
@@ -576,14 +640,16 @@ fn parse_chats(
         ))?
     };
 
-    for (jid, cwm) in cwms_map.iter_mut() {
+    for (jid, (chat, msgs_count)) in chats_map {
+        let mut cwm = ChatWithMessages { chat, messages: Vec::with_capacity(msgs_count) };
+
         // Position of parents in message list
         let mut assoc_parent_id_pos: HashMap<MessageSourceId, usize> = Default::default();
         // Temporary storage for child messages
         let mut assocs: HashMap<MessageSourceId, Vec<Message>> = Default::default();
 
-        let mut msg_rows = msgs_stmt.query([jid])?;
-        let mut call_rows = calls_stmt.query([jid])?;
+        let mut msg_rows = msgs_stmt.query([&jid])?;
+        let mut call_rows = calls_stmt.query([&jid])?;
         let chat: &mut Chat = &mut cwm.chat;
 
         let mut member_ids: HashSet<UserId, Hasher> = Default::default();
@@ -596,6 +662,7 @@ fn parse_chats(
             if let Some((row_id, message)) = parse_message(
                 row,
                 chat,
+                root,
                 myself_id,
                 users,
                 &mut member_ids,
@@ -656,6 +723,9 @@ fn parse_chats(
                     forward_from_name_option: parent_typed.forward_from_name_option.clone(),
                     reply_to_message_id_option: parent_typed.reply_to_message_id_option,
                     contents,
+                    reactions: vec![],
+                    views_option: None,
+                    author_signature_option: None,
                 },
             );
         }
@@ -697,14 +767,15 @@ fn parse_chats(
 
         chat.msg_count = cwm.messages.len() as i32;
         chat.member_ids = member_ids.into_iter().map(|id| *id).sorted().collect_vec();
+
+        // WhatsApp has a lot of chats with block/unblock/migration messages only, which might be related to
+        // changing phone number. These chats are not interesting.
+        if cwm.chat.msg_count > 0 && cwm.messages.iter().any(|m| matches!(m.typed(), message::Typed::Regular(_))) {
+            push_cwm(cwm)?;
+        }
     }
 
-    // WhatsApp has a lot of chats with block/unblock/migration messages only, which might be related to
-    // changing phone number. These chats are not interesting.
-    Ok(cwms_map.into_values()
-        .filter(|cwm| cwm.chat.msg_count > 0)
-        .filter(|cwm| cwm.messages.iter().any(|m| matches!(m.typed(), message::Typed::Regular(_))))
-        .collect_vec())
+    Ok(())
 }
 
 fn parse_message_associations(conn: &Connection) -> Result<MessageAssocMap> {
@@ -743,6 +814,7 @@ fn parse_message_associations(conn: &Connection) -> Result<MessageAssocMap> {
 fn parse_message<'a>(
     row: &Row,
     chat: &Chat,
+    root: &Path,
     myself_id: UserId,
     users: &'a mut Users,
     member_ids: &mut HashSet<UserId, Hasher>,
@@ -767,6 +839,7 @@ fn parse_message<'a>(
             None => myself_id,
             Some(sender_jid) => UserId(hash_to_id(sender_jid)),
         },
+        ChatType::Channel => unreachable!("WhatsApp channels are not loaded"),
     };
 
     assert!(users.id_to_user.contains_key(&from_id));
@@ -784,7 +857,7 @@ fn parse_message<'a>(
             MessageType::VideoCall =>
                 None, // Will be processed when parsing call_rows
             _ =>
-                parse_regular_message(row, msg_tpe, msg_key_to_source_id)?
+                parse_regular_message(row, msg_tpe, root, msg_key_to_source_id)?
         };
         match result_option {
             Some(v) => v,
@@ -944,6 +1017,7 @@ fn parse_system_message<'a>(
 fn parse_regular_message(
     row: &Row,
     msg_tpe: MessageType,
+    root: &Path,
     msg_key_to_source_id: &HashMap<MessageKey, MessageSourceId, Hasher>,
 ) -> Result<Option<(message::Typed, TextParsingState)>> {
     let mut text_state = TextParsingState::Column(columns::message::TEXT);
@@ -954,28 +1028,45 @@ fn parse_regular_message(
     macro_rules! get_mandatory_width { () => { get_mandatory_int!(columns::message_media::WIDTH, "width") }; }
     macro_rules! get_mandatory_height { () => { get_mandatory_int!(columns::message_media::HEIGHT, "height") }; }
 
-    fn get_media_path_and_file_name(row: &Row) -> Result<(Option<String>, Option<String>)> {
+    /// If the file path is not known, file might still be found by name in one of the given directories
+    /// (or their immediate subdirectories, e.g. "Sent").
+    fn get_media_path_and_file_name(row: &Row,
+                                    root: &Path,
+                                    lookup_dirs: &[&str]) -> Result<(Option<String>, Option<String>)> {
         let path: Option<String> = row.get(columns::message_media::FILE_PATH)?;
+        let path = path.map(normalize_media_path);
         let name: Option<String> = row.get(columns::message_media::NAME)?;
+        let path = match (path, &name) {
+            (None, Some(name)) => find_media_file(root, lookup_dirs, name)?,
+            (path, _) => path,
+        };
         let name = name.or_else(||
-        path.as_ref().map(|p| p.rsplit_once('/').unwrap_or(("", p)).1.to_owned()));
+            path.as_ref().map(|p| p.rsplit_once('/').unwrap_or(("", p)).1.to_owned()));
         Ok((path, name))
     }
 
+    let get_media_path = || -> Result<Option<String>> {
+        Ok(row.get::<_, Option<String>>(columns::message_media::FILE_PATH)?.map(normalize_media_path))
+    };
+
     let mime_type_option =
         row.get::<_, Option<String>>(columns::message_media::MIME_TYPE)?
             .and_then(|s| if s.is_empty() { None } else { Some(s) });
 
+    // Older one-time media has dedicated message types, newer one is flagged in a separate table
+    let is_one_time = matches!(msg_tpe, MessageType::OneTimePhoto | MessageType::OneTimeVideo) ||
+        row.get::<_, bool>(columns::message_view_once_media::IS_VIEW_ONCE)?;
+
     let get_optional_photo = {
         let mime_type_option = mime_type_option.clone();
         || -> Result<Option<Content>> {
-            Ok(if let Some(path) = row.get(columns::message_media::FILE_PATH)? {
+            Ok(if let Some(path) = get_media_path()? {
                 Some(content!(Photo {
                 path_option: Some(path),
                 width: row.get(columns::message_media::WIDTH)?,
                 height: row.get(columns::message_media::HEIGHT)?,
                 mime_type_option,
-                is_one_time: false,
+                is_one_time,
             }))
             } else {
                 None
@@ -983,16 +1074,15 @@ fn parse_regular_message(
         }
     };
 
-    // TODO: Extract thumbnails from message_thumbnails (not message_thumbnail!) and media_hash_thumbnail
     let contents = match msg_tpe {
         MessageType::Text => vec![],
         MessageType::Picture =>
             vec![content!(Photo  {
-                path_option: row.get(columns::message_media::FILE_PATH)?, // TODO: One-time photos
+                path_option: get_media_path()?,
                 width: get_mandatory_width!(),
                 height: get_mandatory_height!(),
                 mime_type_option,
-                is_one_time: false,
+                is_one_time,
             })],
         MessageType::Album => {
             // This is a parent message of an album, so far it has always been an empty placeholder.
@@ -1030,16 +1120,17 @@ fn parse_regular_message(
         }
         MessageType::OneTimePhoto => {
             text_state = TextParsingState::None;
+            // Media is usually removed once viewed, but not always
             vec![content!(Photo {
-                path_option: None, // TODO!
+                path_option: get_media_path()?,
                 width: get_mandatory_width!(),
                 height: get_mandatory_height!(),
                 mime_type_option,
-                is_one_time: true,
+                is_one_time,
             })]
         }
         MessageType::Audio => {
-            let (path_option, file_name_option) = get_media_path_and_file_name(row)?;
+            let (path_option, file_name_option) = get_media_path_and_file_name(row, root, &[VOICE_NOTES_DIR])?;
             vec![content!(VoiceMsg {
                 path_option,
                 file_name_option,
@@ -1049,8 +1140,7 @@ fn parse_regular_message(
         }
         MessageType::Video | MessageType::AnimatedGif => {
             text_state = TextParsingState::None;
-            // TODO: One-time videos
-            let (path_option, file_name_option) = get_media_path_and_file_name(row)?;
+            let (path_option, file_name_option) = get_media_path_and_file_name(row, root, &[])?;
             vec![content!(VideoMsg {
                 path_option,
                 file_name_option,
@@ -1058,30 +1148,32 @@ fn parse_regular_message(
                 height: get_mandatory_height!(),
                 mime_type_option,
                 duration_sec_option: get_zero_as_null(row, columns::message_media::DURATION)?,
-                thumbnail_path_option: None,
-                is_one_time: false,
+                thumbnail_path_option: extract_thumbnail(row, root)?,
+                is_one_time,
             })]
         }
-        MessageType::OneTimeVideo =>
+        MessageType::OneTimeVideo => {
+            let (path_option, file_name_option) = get_media_path_and_file_name(row, root, &[])?;
             vec![content!(VideoMsg {
-                path_option: None, // TODO!
-                file_name_option: None, // TODO!
+                path_option,
+                file_name_option,
                 width: get_mandatory_width!(),
                 height: get_mandatory_height!(),
                 mime_type_option,
                 duration_sec_option: get_zero_as_null(row, columns::message_media::DURATION)?,
-                thumbnail_path_option: None,
-                is_one_time: true,
-            })],
+                thumbnail_path_option: extract_thumbnail(row, root)?,
+                is_one_time,
+            })]
+        }
         MessageType::Document => {
             // For some reason, text is moved here
             text_state = TextParsingState::Column(columns::message_media::CAPTION);
-            let (path_option, file_name_option) = get_media_path_and_file_name(row)?;
+            let (path_option, file_name_option) = get_media_path_and_file_name(row, root, &[DOCUMENTS_DIR])?;
             vec![content!(File {
                 path_option,
                 file_name_option,
                 mime_type_option,
-                thumbnail_path_option: None,
+                thumbnail_path_option: extract_thumbnail(row, root)?,
             })]
         }
         MessageType::AnimatedSticker => {
@@ -1094,7 +1186,7 @@ fn parse_regular_message(
                 w *= 2;
                 h *= 2;
             }
-            let (path_option, file_name_option) = get_media_path_and_file_name(row)?;
+            let (path_option, file_name_option) = get_media_path_and_file_name(row, root, &[])?;
             vec![content!(Sticker {
                 path_option,
                 file_name_option,
@@ -1186,9 +1278,59 @@ fn parse_regular_message(
         forward_from_name_option,
         reply_to_message_id_option,
         contents,
+        reactions: vec![],
+        views_option: None,
+        author_signature_option: None,
     }, text_state)))
 }
 
+/// Media path relative to data root. WhatsApp might store it as an absolute path on a device
+/// (e.g. `/storage/emulated/0/WhatsApp/Media/...`) or relative to a scoped storage root
+/// (e.g. `Android/media/com.whatsapp/WhatsApp/Media/...`).
+fn normalize_media_path(path: String) -> String {
+    const MEDIA_PATH_MARKER: &str = concatcp!("WhatsApp/", MEDIA_DIR, "/");
+    match path.find(MEDIA_PATH_MARKER) {
+        Some(idx) => path[(idx + "WhatsApp/".len())..].to_owned(),
+        None => path,
+    }
+}
+
+/// Looks for a file with a given name in given directories and their immediate subdirectories.
+fn find_media_file(root: &Path, dirs: &[&str], name: &str) -> Result<Option<String>> {
+    for &dir in dirs {
+        let dir_path = root.join(dir);
+        if !dir_path.is_dir() { continue; }
+        if dir_path.join(name).is_file() {
+            return Ok(Some(format!("{dir}/{name}")));
+        }
+        for entry in fs::read_dir(&dir_path)? {
+            let entry = entry?;
+            if entry.path().join(name).is_file() {
+                return Ok(Some(format!("{dir}/{}/{name}", path_file_name(&entry.path())?)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Writes an embedded thumbnail (always JPEG) to a file named after the message key, unless it's already there.
+fn extract_thumbnail(row: &Row, root: &Path) -> Result<Option<String>> {
+    let Some(thumbnail) = row.get::<_, Option<Vec<u8>>>(columns::THUMBNAIL)? else {
+        return Ok(None);
+    };
+    if thumbnail.is_empty() {
+        return Ok(None);
+    }
+    let key: MessageKey = row.get(columns::message::KEY)?;
+    let rel_path = format!("{THUMBNAILS_DIR}/{key}.jpg");
+    let path = root.join(&rel_path);
+    if !path.exists() {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, thumbnail)?;
+    }
+    Ok(Some(rel_path))
+}
+
 fn get_zero_as_null(row: &Row, col_name: &str) -> Result<Option<i32>> {
     Ok(row.get::<_, Option<i32>>(col_name)?.filter(|&i| i != 0))
 }
@@ -1216,7 +1358,7 @@ fn parse_optional_location(row: &Row) -> Result<Option<Content>> {
     }
 }
 
-fn parse_vcard(vcard_str: &str) -> Result<ContentSharedContact> {
+pub(super) fn parse_vcard(vcard_str: &str) -> Result<ContentSharedContact> {
     let vcard = calcard::vcard::VCard::parse(vcard_str)
         .map_err(|_| anyhow!("Parsed something else instead of vcard from: {vcard_str}"))?;
 
@@ -0,0 +1,177 @@
+//! Decryption of encrypted database backups, made by WhatsApp itself into
+//! `Android/media/com.whatsapp/WhatsApp/Databases` (e.g. `msgstore.db.crypt15`).
+//!
+//! Backup is a zlib-compressed database encrypted with AES-256-GCM, prefixed with a protobuf header containing IV.
+//! - `.crypt14` backups are encrypted with a key stored in a `key` file on a (rooted) phone.
+//! - `.crypt15` backups are encrypted with a key derived from a root key, which is either a 64-digit key of
+//!   end-to-end encrypted backup, or is stored in `encrypted_backup.key` file on a (rooted) phone.
+
+use crate::prelude::*;
+
+use std::fs;
+use std::io::Read;
+
+use aes::Aes256;
+use aes_gcm::aead::consts::U16;
+use aes_gcm::aead::AeadInPlace;
+use aes_gcm::{AesGcm, KeyInit, Nonce, Tag};
+use flate2::read::ZlibDecoder;
+use hmac::{Hmac, Mac};
+use prost::Message;
+use sha2::Sha256;
+
+pub const CRYPT14_EXT: &str = "crypt14";
+pub const CRYPT15_EXT: &str = "crypt15";
+
+/// Both are Java-serialized byte arrays, with the key in the last bytes.
+pub const KEY_FILE_NAMES: &[&str] = &["key", "encrypted_backup.key"];
+
+pub const KEY_SIZE: usize = 32;
+pub const IV_SIZE: usize = 16;
+pub const TAG_SIZE: usize = 16;
+/// MD5 of the whole file, appended to `msgstore.db` backups
+pub const CHECKSUM_SIZE: usize = 16;
+
+/// A second byte of the file, indicating that a protobuf header contains a features table
+pub const FEATURES_FLAG: u8 = 0x01;
+
+const CRYPT15_KEY_INFO: &[u8] = b"backup encryption";
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// WhatsApp uses 16-byte nonce instead of a standard 12-byte one
+pub type Aes256Gcm16 = AesGcm<Aes256, U16>;
+
+pub fn decrypt_db(feedback_client: &dyn FeedbackClientSync, path: &Path) -> Result<Vec<u8>> {
+    let is_crypt15 = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(CRYPT14_EXT) => false,
+        Some(CRYPT15_EXT) => true,
+        _ => bail!("Unsupported backup file {}", path.display()),
+    };
+    let key = find_key(feedback_client, path, is_crypt15)?;
+    decrypt(&fs::read(path)?, &key)
+}
+
+/// Key file is looked up next to the backup and in its parent directory.
+/// If none found, user is asked for a 64-digit end-to-end backup key instead.
+fn find_key(feedback_client: &dyn FeedbackClientSync, path: &Path, is_crypt15: bool) -> Result<[u8; KEY_SIZE]> {
+    let dir = path.parent().unwrap();
+    let key_file_option = [Some(dir), dir.parent()].into_iter().flatten()
+        .flat_map(|dir| KEY_FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file());
+
+    let key = match key_file_option {
+        Some(key_file) => {
+            let bytes = fs::read(&key_file)?;
+            ensure!(bytes.len() >= KEY_SIZE, "Key file {} is too short", key_file.display());
+            bytes[(bytes.len() - KEY_SIZE)..].try_into()?
+        }
+        None => {
+            ensure!(is_crypt15, "Key file not found, it's required to decrypt .{CRYPT14_EXT} backup");
+            let key_string = feedback_client.ask_for_text("\
+                Key file not found.\n\
+                Input 64-digit key of the end-to-end encrypted backup.\
+            ")?;
+            parse_hex_key(&key_string)?
+        }
+    };
+
+    Ok(if is_crypt15 { derive_crypt15_key(&key) } else { key })
+}
+
+/// Key is displayed in groups of 4 hex digits.
+pub fn parse_hex_key(key_string: &str) -> Result<[u8; KEY_SIZE]> {
+    let digits = key_string.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    ensure!(digits.len() == KEY_SIZE * 2 && digits.is_ascii(), "Key should consist of {} hex digits", KEY_SIZE * 2);
+
+    let mut key = [0u8; KEY_SIZE];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[(i * 2)..(i * 2 + 2)], 16)
+            .map_err(|_| anyhow!("Key should consist of {} hex digits", KEY_SIZE * 2))?;
+    }
+    Ok(key)
+}
+
+/// HKDF-SHA256 with empty salt, producing a single block.
+pub fn derive_crypt15_key(root_key: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    let new_mac = |key: &[u8]| <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+
+    let mut mac = new_mac(&[0u8; 32]);
+    mac.update(root_key);
+    let prk = mac.finalize().into_bytes();
+
+    let mut mac = new_mac(&prk);
+    mac.update(CRYPT15_KEY_INFO);
+    mac.update(&[1]);
+    mac.finalize().into_bytes().into()
+}
+
+pub fn decrypt(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vec<u8>> {
+    let (iv, encrypted) = parse_header(data)?;
+    let cipher = Aes256Gcm16::new_from_slice(key)?;
+
+    // We don't know whether there's a checksum at the end, so we try both
+    let compressed = [CHECKSUM_SIZE, 0].into_iter().filter_map(|trailer_size| {
+        let len = encrypted.len().checked_sub(trailer_size + TAG_SIZE)?;
+        let mut buf = encrypted[..len].to_vec();
+        let tag = Tag::from_slice(&encrypted[len..(len + TAG_SIZE)]);
+        cipher.decrypt_in_place_detached(Nonce::<U16>::from_slice(&iv), b"", &mut buf, tag).ok()?;
+        Some(buf)
+    }).next().ok_or_else(|| anyhow!("Failed to decrypt backup, wrong key?"))?;
+
+    let mut result = vec![];
+    ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut result).context("Failed to decompress backup")?;
+    ensure!(result.starts_with(SQLITE_HEADER), "Decrypted backup is not a database");
+    Ok(result)
+}
+
+/// Header is a protobuf prefixed by its size and, optionally, a features flag.
+/// Returns IV and the rest of the data.
+fn parse_header(data: &[u8]) -> Result<(Vec<u8>, &[u8])> {
+    ensure!(data.len() > 2, "Backup file is too short");
+    let header_start = if data[1] == FEATURES_FLAG { 2 } else { 1 };
+    let header_end = header_start + data[0] as usize;
+    ensure!(data.len() > header_end, "Backup file is too short");
+
+    let header = protobuf::BackupPrefix::decode(&data[header_start..header_end])
+        .context("Malformed backup header")?;
+    let iv = header.c15_iv.and_then(|c15| c15.iv)
+        .or(header.c14_cipher.and_then(|c14| c14.iv))
+        .ok_or_else(|| anyhow!("IV not found in backup header"))?;
+    ensure!(iv.len() == IV_SIZE, "Malformed backup header, IV has unexpected size {}", iv.len());
+    Ok((iv, &data[header_end..]))
+}
+
+/// Subset of backup prefix protobuf, as reverse-engineered by wa-crypt-tools
+pub mod protobuf {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BackupPrefix {
+        /// 0 = HSM controlled, 1 = end-to-end
+        #[prost(int32, optional, tag = "1")]
+        pub key_type: Option<i32>,
+        #[prost(message, optional, tag = "2")]
+        pub c14_cipher: Option<C14Cipher>,
+        #[prost(message, optional, tag = "3")]
+        pub c15_iv: Option<C15Iv>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct C14Cipher {
+        #[prost(int32, optional, tag = "1")]
+        pub cipher_version: Option<i32>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub key_version: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "3")]
+        pub server_salt: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "4")]
+        pub google_id: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "5")]
+        pub iv: Option<Vec<u8>>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct C15Iv {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub iv: Option<Vec<u8>>,
+    }
+}
@@ -17,6 +17,8 @@ use pretty_assertions::{assert_eq, assert_ne};
 const RESOURCE_DIR: &str = "whatsapp-android";
 const LOADER: WhatsAppAndroidDataLoader = WhatsAppAndroidDataLoader;
 
+const ROOT_KEY_HEX: &str = "0123 4567 89ab cdef 0123 4567 89ab cdef 0123 4567 89ab cdef 0123 4567 89ab cdef";
+
 //
 // Tests
 //
@@ -161,6 +163,9 @@ fn loading_2023_10() -> EmptyRes {
                 forward_from_name_option: Some(UNKNOWN.to_owned()),
                 reply_to_message_id_option: msgs[0].source_id_option,
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
@@ -204,6 +209,9 @@ fn loading_2023_10() -> EmptyRes {
                         duration_sec_option: Some(123),
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
 
@@ -220,6 +228,9 @@ fn loading_2023_10() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
@@ -293,6 +304,9 @@ fn loading_2026_01() -> EmptyRes {
                         duration_sec_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
@@ -373,6 +387,9 @@ fn loading_2026_05() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
@@ -420,6 +437,9 @@ fn loading_2026_05() -> EmptyRes {
                         is_one_time: false,
                     }),
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
 
@@ -447,6 +467,9 @@ fn loading_2026_05() -> EmptyRes {
                         is_one_time: false,
                     }),
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
@@ -454,6 +477,112 @@ fn loading_2026_05() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_2026_05_crypt15() -> EmptyRes {
+    let (res, _tmp_dir) = create_databases_copy("2026-05");
+    let expected_dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let root_key = crypt::parse_hex_key(ROOT_KEY_HEX)?;
+    let encrypted_path = encrypt_db(&res, crypt::CRYPT15_EXT, &crypt::derive_crypt15_key(&root_key));
+    LOADER.looks_about_right(&encrypted_path)?;
+
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some("0".repeat(64)) };
+    let err = LOADER.load(&feedback_client, &encrypted_path).err().expect("Loading should fail");
+    assert!(format!("{err:?}").contains("wrong key"), "Unexpected error: {err:?}");
+
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: None, text: Some(ROOT_KEY_HEX.to_owned()) };
+    let dao = LOADER.load(&feedback_client, &encrypted_path)?;
+    assert_same_content(&expected_dao, &dao)?;
+
+    // Decrypted database isn't left behind
+    let leftovers = fs::read_dir(encrypted_path.parent().unwrap())?
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(".decrypted_"))
+        .collect_vec();
+    assert_eq!(leftovers, Vec::<String>::new());
+
+    Ok(())
+}
+
+#[test]
+fn loading_2026_05_crypt14_without_wa_db() -> EmptyRes {
+    let (res, tmp_dir) = create_databases_copy("2026-05");
+    let expected_dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let key = [42u8; crypt::KEY_SIZE];
+    let encrypted_path = encrypt_db(&res, crypt::CRYPT14_EXT, &key);
+    fs::remove_file(res.with_file_name("wa.db"))?;
+    LOADER.looks_about_right(&encrypted_path)?;
+
+    // Key file is required for crypt14
+    assert!(LOADER.load(&NoFeedbackClient, &encrypted_path).is_err());
+
+    // Java-serialized byte array, the key is in the end
+    let mut key_file_content = b"\xAC\xED\x00\x05ur\x00\x02[B".to_vec();
+    key_file_content.extend_from_slice(&[0u8; 99]);
+    key_file_content.extend_from_slice(&key);
+    fs::write(tmp_dir.path.join("key"), key_file_content)?;
+
+    let dao = LOADER.load(&NoFeedbackClient, &encrypted_path)?;
+    assert_same_content(&expected_dao, &dao)?;
+
+    Ok(())
+}
+
+#[test]
+fn loading_2026_05_avatars_and_view_once() -> EmptyRes {
+    let (res, tmp_dir) = create_databases_copy("2026-05");
+    let avatars_dir = tmp_dir.path.join(AVATARS_DIR);
+    fs::create_dir_all(&avatars_dir)?;
+    fs::write(avatars_dir.join("11111@s.whatsapp.net.j"), b"avatar")?;
+    Connection::open(&res)?.execute_batch(r"
+        CREATE TABLE message_view_once_media (message_row_id INTEGER PRIMARY KEY, state INTEGER);
+        INSERT INTO message_view_once_media VALUES (12241, 0);
+    ")?;
+
+    let dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let member = dao.users_single_ds().into_iter().find(|u| u.id == 9017079856339592512_i64).unwrap();
+    assert_eq!(member.profile_pictures, vec![ProfilePicture {
+        path: "files/Avatars/11111@s.whatsapp.net.j".to_owned(),
+        frame_option: None,
+    }]);
+
+    let cwm = dao.cwms_single_ds().into_iter().find(|cwm| cwm.chat.tpe == ChatType::Personal as i32).unwrap();
+    let msgs = dao.first_messages(&cwm.chat, 99999)?;
+    let is_one_time = |msg: &Message| match msg.typed {
+        Some(message::Typed::Regular(ref mr)) => match mr.contents[0].sealed_value_optional {
+            Some(Photo(ref photo)) => photo.is_one_time,
+            ref other => panic!("Unexpected content: {other:?}"),
+        },
+        ref other => panic!("Unexpected message: {other:?}"),
+    };
+    assert!(!is_one_time(&msgs[0]));
+    assert!(is_one_time(&msgs[1]));
+
+    Ok(())
+}
+
+#[test]
+fn normalizing_media_paths() {
+    assert_eq!(normalize_media_path("/storage/emulated/0/WhatsApp/Media/WhatsApp Images/a.jpg".to_owned()),
+               "Media/WhatsApp Images/a.jpg");
+    assert_eq!(normalize_media_path("Android/media/com.whatsapp/WhatsApp/Media/WhatsApp Video/b.mp4".to_owned()),
+               "Media/WhatsApp Video/b.mp4");
+    assert_eq!(normalize_media_path("Media/album-1.jpg".to_owned()), "Media/album-1.jpg");
+}
+
+#[test]
+fn rejecting_other_files() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    for name in ["msgstore.db.crypt12", "msgstore.db.crypt15.bak", "wa.db.crypt15", "other.db"] {
+        let path = tmp_dir.path.join(name);
+        fs::write(&path, b"")?;
+        assert!(LOADER.looks_about_right(&path).is_err(), "{name} should be rejected");
+    }
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -470,6 +599,81 @@ fn expected_myself(ds_uuid: &PbUuid) -> User {
     }
 }
 
+/// Unlike [test_android::create_databases], creates databases in a temporary directory rather than in resources,
+/// so that they could be modified without affecting other tests.
+fn create_databases_copy(name_suffix: &str) -> (PathBuf, TmpDir) {
+    let tmp_dir = TmpDir::new();
+    let databases_dir = tmp_dir.path.join(android::DATABASES);
+    fs::create_dir(&databases_dir).unwrap();
+    for db_name in ["msgstore", "wa"] {
+        let sql = fs::read_to_string(resource(&format!("{RESOURCE_DIR}_{name_suffix}/{db_name}.sql"))).unwrap();
+        Connection::open(databases_dir.join(format!("{db_name}.db"))).unwrap().execute_batch(&sql).unwrap();
+    }
+    (databases_dir.join(DB_FILENAME), tmp_dir)
+}
+
+/// Encrypts database the same way WhatsApp does, removing the original.
+/// crypt15 backups are written with features flag and checksum, crypt14 ones - without.
+fn encrypt_db(db_path: &Path, ext: &str, key: &[u8; crypt::KEY_SIZE]) -> PathBuf {
+    use aes_gcm::aead::AeadInPlace;
+    use aes_gcm::{KeyInit, Nonce};
+    use crypt::protobuf::*;
+    use flate2::write::ZlibEncoder;
+    use prost::Message;
+    use std::io::Write;
+
+    let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&fs::read(db_path).unwrap()).unwrap();
+    let mut data = encoder.finish().unwrap();
+
+    let iv = [7u8; crypt::IV_SIZE];
+    let is_crypt15 = ext == crypt::CRYPT15_EXT;
+    let header = if is_crypt15 {
+        BackupPrefix { key_type: Some(1), c15_iv: Some(C15Iv { iv: Some(iv.to_vec()) }), ..Default::default() }
+    } else {
+        BackupPrefix { key_type: Some(0), c14_cipher: Some(C14Cipher { iv: Some(iv.to_vec()), ..Default::default() }), ..Default::default() }
+    }.encode_to_vec();
+
+    let cipher = crypt::Aes256Gcm16::new_from_slice(key).unwrap();
+    let tag = cipher.encrypt_in_place_detached(Nonce::from_slice(&iv), b"", &mut data).unwrap();
+
+    let mut content = vec![header.len() as u8];
+    if is_crypt15 {
+        content.push(crypt::FEATURES_FLAG);
+    }
+    content.extend_from_slice(&header);
+    content.extend_from_slice(&data);
+    content.extend_from_slice(&tag);
+    if is_crypt15 {
+        // Checksum is not verified
+        content.extend_from_slice(&[0u8; crypt::CHECKSUM_SIZE]);
+    }
+
+    let encrypted_path = db_path.with_file_name(format!("{DB_FILENAME}.{ext}"));
+    fs::write(&encrypted_path, content).unwrap();
+    fs::remove_file(db_path).unwrap();
+    encrypted_path
+}
+
+/// Compares everything except dataset UUIDs.
+fn assert_same_content(expected: &InMemoryDao, actual: &InMemoryDao) -> EmptyRes {
+    let strip_user = |u: User| User { ds_uuid: ZERO_PB_UUID.clone(), ..u };
+    assert_eq!(actual.users_single_ds().into_iter().map(strip_user).collect_vec(),
+               expected.users_single_ds().into_iter().map(strip_user).collect_vec());
+
+    // Chats order is not stable
+    let expected_cwms = expected.cwms_single_ds().into_iter().sorted_by_key(|cwm| cwm.chat.id).collect_vec();
+    let actual_cwms = actual.cwms_single_ds().into_iter().sorted_by_key(|cwm| cwm.chat.id).collect_vec();
+    assert_eq!(actual_cwms.len(), expected_cwms.len());
+    for (expected_cwm, actual_cwm) in expected_cwms.iter().zip(actual_cwms.iter()) {
+        let strip_chat = |c: &Chat| Chat { ds_uuid: ZERO_PB_UUID.clone(), ..c.clone() };
+        assert_eq!(strip_chat(&actual_cwm.chat), strip_chat(&expected_cwm.chat));
+        assert_eq!(actual.first_messages(&actual_cwm.chat, 99999)?,
+                   expected.first_messages(&expected_cwm.chat, 99999)?);
+    }
+    Ok(())
+}
+
 fn trim_vcard_string(s: &str) -> String {
     s.trim().lines().map(|s| s.trim()).join("\n")
 }
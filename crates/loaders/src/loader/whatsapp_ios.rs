@@ -0,0 +1,605 @@
+use super::whatsapp_android::{parse_vcard, PHONE_JID_REGEX};
+use super::*;
+use num_traits::FromPrimitive;
+use rusqlite::{Connection, Row};
+
+#[cfg(test)]
+#[path = "whatsapp_ios_tests.rs"]
+mod tests;
+
+/// Loader for WhatsApp for iOS, as found in an unencrypted iTunes/Finder backup
+/// (`AppDomainGroup-group.net.whatsapp.WhatsApp.shared`).
+///
+/// Some notes about the implementation:
+/// 1. ChatStorage.sqlite and (optional) ContactsV2.sqlite should lie in the data root folder
+/// 2. Message media is resolved using <data_root>/Message, profile pictures - using <data_root> itself
+/// 3. Chat, user and message source IDs are derived from JIDs and stanza IDs the same way
+///    [WhatsAppAndroidDataLoader] does, so that datasets from both could be merged.
+pub struct WhatsAppIosDataLoader;
+
+const NAME: &str = "WhatsApp (iOS)";
+pub const DB_FILENAME: &str = "ChatStorage.sqlite";
+const CONTACTS_DB_FILENAME: &str = "ContactsV2.sqlite";
+
+const MESSAGE_DIR: &str = "Message";
+
+/// Core Data timestamps are seconds since 2001-01-01.
+const APPLE_EPOCH_OFFSET_SEC: i64 = 978307200;
+
+const VOICE_MIME_TYPE: &str = "audio/ogg";
+
+/// Tables that only exist in some WhatsApp versions, created empty if missing.
+const OPTIONAL_TABLES: &[(&str, &str)] = &[
+    ("ZWAPROFILEPUSHNAME",
+     "CREATE TEMP TABLE ZWAPROFILEPUSHNAME (Z_PK INTEGER PRIMARY KEY, ZJID VARCHAR, ZPUSHNAME VARCHAR)"),
+    ("ZWAPROFILEPICTUREITEM",
+     "CREATE TEMP TABLE ZWAPROFILEPICTUREITEM (Z_PK INTEGER PRIMARY KEY, ZJID VARCHAR, ZPATH VARCHAR)"),
+];
+
+type Jid = String;
+
+impl DataLoader for WhatsAppIosDataLoader {
+    fn name(&self) -> String { NAME.to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        if path_file_name(path)? != DB_FILENAME {
+            bail!("File is not {DB_FILENAME}");
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+        load_in_memory(|sink| parse_chat_storage(feedback_client, path, ds, sink))
+    }
+
+    fn load_into_inner(&self,
+                       feedback_client: &dyn FeedbackClientSync,
+                       path: &Path,
+                       ds: Dataset,
+                       sink: &mut dyn ChatHistorySink) -> EmptyRes {
+        parse_chat_storage(feedback_client, path, ds, sink)
+    }
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+enum MessageType {
+    Text = 0,
+    Image = 1,
+    Video = 2,
+    Voice = 3,
+    Contact = 4,
+    Location = 5,
+    /// Distinguished by `ZGROUPEVENTTYPE`, see [GroupEventType].
+    GroupEvent = 6,
+    /// Text with a link preview
+    Link = 7,
+    Document = 8,
+    AnimatedGif = 11,
+    Deleted = 14,
+    Sticker = 15,
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+enum GroupEventType {
+    /// New title is in message text
+    TitleChange = 1,
+    MemberJoin = 2,
+    MemberLeave = 3,
+    PhotoChange = 4,
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+enum SessionType {
+    Personal = 0,
+    Group = 1,
+}
+
+mod columns {
+    pub const JID: &str = "jid";
+    pub const SENDER_JID: &str = "sender_jid";
+
+    pub mod user {
+        pub const FULL_NAME: &str = "full_name";
+        pub const PHONE_NUMBER: &str = "phone_number";
+        pub const PARTNER_NAME: &str = "partner_name";
+        pub const MEMBER_NAME: &str = "member_name";
+        pub const PUSH_NAME: &str = "push_name";
+        pub const PICTURE_PATH: &str = "picture_path";
+    }
+
+    pub mod chat {
+        pub const ID: &str = "Z_PK";
+        pub const SESSION_TYPE: &str = "ZSESSIONTYPE";
+        pub const NAME: &str = "ZPARTNERNAME";
+        pub const PICTURE_PATH: &str = "picture_path";
+    }
+
+    pub mod message {
+        pub const FROM_ME: &str = "ZISFROMME";
+        pub const TYPE: &str = "ZMESSAGETYPE";
+        pub const GROUP_EVENT_TYPE: &str = "ZGROUPEVENTTYPE";
+        pub const DATE: &str = "ZMESSAGEDATE";
+        pub const STANZA_ID: &str = "ZSTANZAID";
+        pub const TEXT: &str = "ZTEXT";
+    }
+
+    pub mod media_item {
+        pub const LOCAL_PATH: &str = "ZMEDIALOCALPATH";
+        pub const THUMBNAIL_PATH: &str = "ZTHUMBNAILLOCALPATH";
+        /// Caption for photos and videos, file name for documents, place name for locations
+        pub const TITLE: &str = "ZTITLE";
+        pub const DURATION: &str = "ZMOVIEDURATION";
+        pub const LAT: &str = "ZLATITUDE";
+        pub const LON: &str = "ZLONGITUDE";
+        pub const VCARD_NAME: &str = "ZVCARDNAME";
+        pub const VCARD_STRING: &str = "ZVCARDSTRING";
+    }
+}
+
+fn parse_chat_storage(feedback_client: &dyn FeedbackClientSync,
+                      path: &Path,
+                      ds: Dataset,
+                      sink: &mut dyn ChatHistorySink) -> EmptyRes {
+    let root = path.parent().unwrap();
+
+    let conn = Connection::open(path)?;
+    let contacts_db_path = root.join(CONTACTS_DB_FILENAME);
+    if contacts_db_path.exists() {
+        conn.execute(r#"ATTACH DATABASE ?1 AS contacts_db"#, [path_to_str(&contacts_db_path)?])?;
+    } else {
+        // Address book names will be unavailable, but push names usually compensate for that
+        conn.execute_batch(r#"
+            ATTACH DATABASE ':memory:' AS contacts_db;
+            CREATE TABLE contacts_db.ZWAADDRESSBOOKCONTACT (ZWHATSAPPID VARCHAR, ZFULLNAME VARCHAR, ZPHONENUMBER VARCHAR);
+        "#)?;
+    }
+    for (table_name, create_sql) in OPTIONAL_TABLES {
+        if !sqlite_utils::table_exists(&conn, table_name) {
+            conn.execute(create_sql, [])?;
+        }
+    }
+
+    feedback_client.set_load_status(LoadStatus::new_parsing("file", Some(format!("{}", path.display()))));
+    let ds_uuid = ds.uuid.clone();
+    sink.begin_dataset(format!("{NAME} ({})", path_file_name(root)?), ds, root)?;
+
+    let mut users = parse_users(&conn, &ds_uuid, root)?;
+
+    // Same as in Android loader, own JID is unknown, so using a first legal ID for myself.
+    let myself_id = UserId(UserId::INVALID.0 + 1);
+    assert!(!users.values().any(|u| u.id == *myself_id));
+
+    let mut participating_user_ids: HashSet<UserId, Hasher> = Default::default();
+    parse_chats(&conn, &ds_uuid, myself_id, &mut users, &mut |cwm| {
+        participating_user_ids.extend(cwm.chat.member_ids.iter().map(|id| UserId(*id)));
+        push_chat(sink, cwm.chat, cwm.messages)
+    })?;
+
+    let myself = User {
+        ds_uuid: ds_uuid.clone(),
+        id: *myself_id,
+        first_name_option: Some("Me".to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+    let users = std::iter::once(myself)
+        .chain(users.into_values()
+            .filter(|u| participating_user_ids.contains(&u.id()))
+            .sorted_by_key(|u| u.id))
+        .collect_vec();
+    sink.add_users(users, myself_id)?;
+    sink.finish_dataset()
+}
+
+fn parse_users(conn: &Connection, ds_uuid: &PbUuid, root: &Path) -> Result<HashMap<Jid, User>> {
+    let mut users: HashMap<Jid, User> = Default::default();
+
+    // Address book might store WhatsApp ID either as a full JID or as its user part
+    let mut stmt = {
+        use columns::{user::*, *};
+        conn.prepare(&format!(r"
+            SELECT
+                jids.jid AS {JID},
+                ab.ZFULLNAME AS {FULL_NAME},
+                ab.ZPHONENUMBER AS {PHONE_NUMBER},
+                (SELECT ZPARTNERNAME FROM ZWACHATSESSION
+                 WHERE ZCONTACTJID = jids.jid AND ZSESSIONTYPE = {personal} LIMIT 1) AS {PARTNER_NAME},
+                (SELECT ZCONTACTNAME FROM ZWAGROUPMEMBER
+                 WHERE ZMEMBERJID = jids.jid AND ZCONTACTNAME IS NOT NULL LIMIT 1) AS {MEMBER_NAME},
+                (SELECT ZPUSHNAME FROM ZWAPROFILEPUSHNAME WHERE ZJID = jids.jid LIMIT 1) AS {PUSH_NAME},
+                (SELECT ZPATH FROM ZWAPROFILEPICTUREITEM WHERE ZJID = jids.jid LIMIT 1) AS {PICTURE_PATH}
+            FROM (
+                SELECT ZCONTACTJID AS jid FROM ZWACHATSESSION WHERE ZSESSIONTYPE = {personal}
+                UNION
+                SELECT ZMEMBERJID AS jid FROM ZWAGROUPMEMBER
+            ) jids
+            LEFT JOIN contacts_db.ZWAADDRESSBOOKCONTACT ab
+                ON ab.ZWHATSAPPID = jids.jid OR ab.ZWHATSAPPID || '@s.whatsapp.net' = jids.jid
+            WHERE jids.jid IS NOT NULL
+            GROUP BY jids.jid
+        ", personal = SessionType::Personal as i32))?
+    };
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        use columns::user::*;
+        let jid = row.get::<_, String>(columns::JID)?;
+
+        // Unlike Android, pictures are referenced by the database explicitly
+        let profile_pictures = row.get::<_, Option<String>>(PICTURE_PATH)?
+            .filter(|path| root.join(path).exists())
+            .map(|path| vec![ProfilePicture { path, frame_option: None }])
+            .unwrap_or_default();
+
+        let phone_number_option = row.get::<_, Option<String>>(PHONE_NUMBER)?.or_else(|| {
+            PHONE_JID_REGEX.captures(&jid).map(|c| c.get(1).unwrap().as_str().to_owned())
+        });
+        let phone_number_option = phone_number_option.map(|pn| PhoneNumber::from_raw(&pn).0);
+
+        let first_name_option = row.get::<_, Option<String>>(FULL_NAME)?
+            .or(row.get::<_, Option<String>>(PARTNER_NAME)?)
+            .or(row.get::<_, Option<String>>(MEMBER_NAME)?)
+            .or(row.get::<_, Option<String>>(PUSH_NAME)?)
+            .filter(|name| !name.is_empty());
+
+        // If phone number is left unknown, we're using JID as a username in order to not lose information
+        let username_option = if phone_number_option.is_none() { Some(jid.clone()) } else { None };
+
+        let user = new_user(ds_uuid, &jid, first_name_option, username_option, phone_number_option, profile_pictures);
+        users.insert(jid, user);
+    }
+    Ok(users)
+}
+
+fn new_user(ds_uuid: &PbUuid,
+            jid: &str,
+            first_name_option: Option<String>,
+            username_option: Option<String>,
+            phone_number_option: Option<String>,
+            profile_pictures: Vec<ProfilePicture>) -> User {
+    User {
+        ds_uuid: ds_uuid.clone(),
+        id: hash_to_id(jid),
+        first_name_option,
+        last_name_option: None, // Last name is unreliable
+        username_option,
+        phone_number_option,
+        profile_pictures,
+    }
+}
+
+fn parse_chats(
+    conn: &Connection,
+    ds_uuid: &PbUuid,
+    myself_id: UserId,
+    users: &mut HashMap<Jid, User>,
+    push_cwm: &mut dyn FnMut(ChatWithMessages) -> EmptyRes,
+) -> EmptyRes {
+    const WA_OFFICIAL_ACCT_JID: &str = "0@s.whatsapp.net";
+    const STATUS_JID: &str = "status@broadcast";
+
+    let mut chats_stmt = {
+        use columns::{chat::*, *};
+        conn.prepare(&format!(r"
+            SELECT
+                ZWACHATSESSION.*,
+                ZCONTACTJID AS {JID},
+                (SELECT ZPATH FROM ZWAPROFILEPICTUREITEM WHERE ZJID = ZCONTACTJID LIMIT 1) AS {PICTURE_PATH}
+            FROM ZWACHATSESSION
+            WHERE ZSESSIONTYPE IN ({personal}, {group})
+              AND ZCONTACTJID NOT IN ('{WA_OFFICIAL_ACCT_JID}', '{STATUS_JID}')
+              AND EXISTS (SELECT 1 FROM ZWAMESSAGE WHERE ZWAMESSAGE.ZCHATSESSION = ZWACHATSESSION.Z_PK)
+            ORDER BY Z_PK
+        ", personal = SessionType::Personal as i32, group = SessionType::Group as i32))?
+    };
+    let mut members_stmt = conn.prepare(
+        r"SELECT ZMEMBERJID FROM ZWAGROUPMEMBER WHERE ZCHATSESSION = ?1 AND ZMEMBERJID IS NOT NULL"
+    )?;
+
+    /*
+     * Notes:
+     * - For 1-on-1 chats, sender is the chat partner, otherwise it's referenced by ZWAMESSAGE.ZGROUPMEMBER.
+     * - Media paths are relative to Message directory.
+     * - Quoted message references are stored in a protobuf blob, so replies are not restored for now.
+     * - Calls are stored in a separate database, which is not a part of ChatStorage.
+     */
+    let mut msgs_stmt = {
+        use columns::*;
+        conn.prepare(&format!(r"
+            SELECT
+                ZWAMESSAGE.*,
+                ZWAGROUPMEMBER.ZMEMBERJID AS {SENDER_JID},
+                ZWAMEDIAITEM.ZMEDIALOCALPATH,
+                ZWAMEDIAITEM.ZTHUMBNAILLOCALPATH,
+                ZWAMEDIAITEM.ZTITLE,
+                ZWAMEDIAITEM.ZMOVIEDURATION,
+                ZWAMEDIAITEM.ZLATITUDE,
+                ZWAMEDIAITEM.ZLONGITUDE,
+                ZWAMEDIAITEM.ZVCARDNAME,
+                ZWAMEDIAITEM.ZVCARDSTRING
+            FROM ZWAMESSAGE
+            LEFT JOIN ZWAGROUPMEMBER ON ZWAGROUPMEMBER.Z_PK = ZWAMESSAGE.ZGROUPMEMBER
+            LEFT JOIN ZWAMEDIAITEM ON ZWAMEDIAITEM.Z_PK = ZWAMESSAGE.ZMEDIAITEM
+            WHERE ZWAMESSAGE.ZCHATSESSION = ?1
+            ORDER BY ZWAMESSAGE.ZMESSAGEDATE ASC, ZWAMESSAGE.Z_PK ASC
+        "))?
+    };
+
+    let mut chat_rows = chats_stmt.query([])?;
+    while let Some(row) = chat_rows.next()? {
+        // This is both chat and user ID
+        let jid = row.get::<_, String>(columns::JID)?;
+        let session_pk = row.get::<_, i64>(columns::chat::ID)?;
+        let session_type = row.get::<_, i32>(columns::chat::SESSION_TYPE)?;
+        let session_type: SessionType = FromPrimitive::from_i32(session_type).unwrap();
+
+        let mut member_ids: HashSet<UserId, Hasher> = Default::default();
+        member_ids.insert(myself_id);
+
+        let (name_option, tpe) = match session_type {
+            SessionType::Personal => {
+                let user = &users[&jid];
+                member_ids.insert(user.id());
+                (user.pretty_name_option(), ChatType::Personal)
+            }
+            SessionType::Group => {
+                let mut member_rows = members_stmt.query([session_pk])?;
+                while let Some(member_row) = member_rows.next()? {
+                    let member_jid = member_row.get::<_, String>(0)?;
+                    member_ids.insert(users[&member_jid].id());
+                }
+                (row.get::<_, Option<String>>(columns::chat::NAME)?, ChatType::PrivateGroup)
+            }
+        };
+
+        let mut chat = Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id(&jid),
+            name_option,
+            source_type: SourceType::WhatsappDb as i32,
+            tpe: tpe as i32,
+            img_path_option: row.get(columns::chat::PICTURE_PATH)?,
+            member_ids: vec![],
+            msg_count: 0,
+            main_chat_id: None,
+        };
+
+        let mut messages = vec![];
+        let mut msg_rows = msgs_stmt.query([session_pk])?;
+        while let Some(row) = msg_rows.next()? {
+            if let Some(message) = parse_message(row, &chat, myself_id, users, &mut member_ids)? {
+                messages.push(message);
+            }
+        }
+
+        // We're relying on sort_by_key being stable
+        messages.sort_by_key(|m| m.timestamp);
+        messages.iter_mut().enumerate().for_each(|(i, m)| m.internal_id = i as i64);
+
+        chat.msg_count = messages.len() as i32;
+        chat.member_ids = member_ids.into_iter().map(|id| *id).sorted().collect_vec();
+
+        if chat.msg_count > 0 {
+            push_cwm(ChatWithMessages { chat, messages })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `None` for rows that should be skipped.
+fn parse_message(
+    row: &Row,
+    chat: &Chat,
+    myself_id: UserId,
+    users: &mut HashMap<Jid, User>,
+    member_ids: &mut HashSet<UserId, Hasher>,
+) -> Result<Option<Message>> {
+    use columns::message::*;
+
+    let msg_tpe = row.get::<_, i32>(TYPE)?;
+    let Some(msg_tpe) = FromPrimitive::from_i32(msg_tpe) else {
+        // iOS schema is much less explored than the Android one, so unknown types are not fatal
+        log::warn!("Skipping message of unknown type {msg_tpe} in chat {}", name_or_unnamed(&chat.name_option));
+        return Ok(None);
+    };
+
+    let from_me = row.get::<_, bool>(FROM_ME)?;
+    let sender_jid = row.get::<_, Option<String>>(columns::SENDER_JID)?;
+    let sender_option = match sender_jid {
+        Some(sender_jid) => {
+            // Might be a former member that's not listed anymore
+            let user = users.entry(sender_jid.clone())
+                .or_insert_with(|| new_user(&chat.ds_uuid, &sender_jid, None, Some(sender_jid.clone()), None, vec![]));
+            Some(user.clone())
+        }
+        None => None,
+    };
+
+    let from_id: UserId = if from_me {
+        myself_id
+    } else {
+        match chat.tpe() {
+            ChatType::Personal => UserId(chat.id),
+            ChatType::PrivateGroup => sender_option.as_ref().map(|u| u.id()).unwrap_or(myself_id),
+            ChatType::Channel => unreachable!("WhatsApp channels are not loaded"),
+        }
+    };
+    member_ids.insert(from_id);
+
+    let mut text = row.get::<_, Option<String>>(TEXT)?;
+
+    let typed = if msg_tpe == MessageType::GroupEvent {
+        use message_service::SealedValueOptional::*;
+        let event_tpe = row.get::<_, Option<i32>>(GROUP_EVENT_TYPE)?.unwrap_or_default();
+        let Some(event_tpe) = FromPrimitive::from_i32(event_tpe) else {
+            log::warn!("Skipping group event of unknown type {event_tpe} in chat {}", name_or_unnamed(&chat.name_option));
+            return Ok(None);
+        };
+        let member_names = sender_option.iter().map(|u| u.pretty_name()).collect_vec();
+        let val = match event_tpe {
+            GroupEventType::TitleChange => GroupEditTitle(MessageServiceGroupEditTitle {
+                title: text.take().unwrap_or_default(),
+            }),
+            GroupEventType::MemberJoin => GroupInviteMembers(MessageServiceGroupInviteMembers {
+                members: member_names,
+            }),
+            GroupEventType::MemberLeave => GroupRemoveMembers(MessageServiceGroupRemoveMembers {
+                members: member_names,
+            }),
+            GroupEventType::PhotoChange => {
+                text = None;
+                GroupEditPhoto(MessageServiceGroupEditPhoto {
+                    photo: ContentPhoto {
+                        path_option: None,
+                        width: 0,
+                        height: 0,
+                        mime_type_option: None,
+                        is_one_time: false,
+                    },
+                })
+            }
+        };
+        message_service!(val)
+    } else {
+        let contents = parse_contents(row, msg_tpe, &mut text)?;
+        message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: msg_tpe == MessageType::Deleted,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents,
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        }
+    };
+
+    // Technically, text uses markdown, but oh well
+    let text = match text {
+        Some(text) if !text.is_empty() => normalize_rich_text(vec![RichText::make_plain(text)]),
+        _ => vec![],
+    };
+
+    let stanza_id = row.get::<_, String>(STANZA_ID)?;
+    let ts = row.get::<_, f64>(DATE)? as i64 + APPLE_EPOCH_OFFSET_SEC;
+
+    Ok(Some(Message::new(
+        *NO_INTERNAL_ID,
+        Some(hash_to_id(&stanza_id)),
+        ts,
+        from_id,
+        text,
+        typed,
+    )))
+}
+
+/// Might replace message text, as iOS stores captions separately from it.
+fn parse_contents(row: &Row, msg_tpe: MessageType, text: &mut Option<String>) -> Result<Vec<Content>> {
+    use columns::media_item::*;
+
+    let path_option = row.get::<_, Option<String>>(LOCAL_PATH)?
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("{MESSAGE_DIR}/{p}"));
+    let file_name_option = path_option.as_ref().map(|p| p.rsplit_once('/').unwrap_or(("", p)).1.to_owned());
+    let thumbnail_path_option = row.get::<_, Option<String>>(THUMBNAIL_PATH)?
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("{MESSAGE_DIR}/{p}"));
+    let duration_sec_option = row.get::<_, Option<i32>>(DURATION)?.filter(|&d| d != 0);
+    let mut take_caption = || -> Result<()> {
+        if let Some(caption) = row.get::<_, Option<String>>(TITLE)? {
+            *text = Some(caption);
+        }
+        Ok(())
+    };
+
+    Ok(match msg_tpe {
+        MessageType::Text | MessageType::Link | MessageType::Deleted => vec![],
+        MessageType::Image => {
+            take_caption()?;
+            vec![content!(Photo {
+                path_option,
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+            })]
+        }
+        MessageType::Video | MessageType::AnimatedGif => {
+            take_caption()?;
+            vec![content!(VideoMsg {
+                path_option,
+                file_name_option,
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                duration_sec_option,
+                thumbnail_path_option,
+                is_one_time: false,
+            })]
+        }
+        MessageType::Voice => vec![content!(VoiceMsg {
+            path_option,
+            file_name_option,
+            mime_type: VOICE_MIME_TYPE.to_owned(),
+            duration_sec_option,
+        })],
+        MessageType::Document => {
+            let title_option = row.get::<_, Option<String>>(TITLE)?;
+            if *text == title_option {
+                *text = None; // Text is just a file name
+            }
+            vec![content!(File {
+                path_option,
+                file_name_option: title_option.or(file_name_option),
+                mime_type_option: None,
+                thumbnail_path_option,
+            })]
+        }
+        MessageType::Sticker => vec![content!(Sticker {
+            path_option,
+            file_name_option,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+            emoji_option: None,
+        })],
+        MessageType::Contact => {
+            *text = None; // Text is a contact name, we have it already
+            let contact = match row.get::<_, Option<String>>(VCARD_STRING)? {
+                Some(vcard) => parse_vcard(&vcard)?,
+                None => ContentSharedContact {
+                    first_name_option: row.get(VCARD_NAME)?,
+                    last_name_option: None,
+                    phone_number_option: None,
+                    vcard_path_option: None,
+                },
+            };
+            vec![content!(SharedContact { ..contact })]
+        }
+        MessageType::Location => {
+            match (row.get::<_, Option<f64>>(LAT)?, row.get::<_, Option<f64>>(LON)?) {
+                (Some(lat), Some(lon)) => vec![content!(Location {
+                    title_option: row.get(TITLE)?,
+                    address_option: None,
+                    lat_str: format_coordinate(lat),
+                    lon_str: format_coordinate(lon),
+                    duration_sec_option: None,
+                })],
+                _ => vec![],
+            }
+        }
+        MessageType::GroupEvent => unreachable!(),
+    })
+}
+
+/// Since there's no point in having more than 8 precision digits, we're only storing up to 8.
+fn format_coordinate(v: f64) -> String {
+    let str = format!("{v:.8}");
+    str.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
@@ -0,0 +1,255 @@
+#![allow(unused_imports)]
+
+use super::*;
+
+use crate::entity_utils::*;
+use chat_history_manager_core::protobuf::history::content::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::message::*;
+use chat_history_manager_core::protobuf::history::message_service::SealedValueOptional::*;
+use chat_history_manager_core::protobuf::history::User;
+
+use std::fs;
+use pretty_assertions::{assert_eq, assert_ne};
+
+const RESOURCE_DIR: &str = "whatsapp-ios";
+const LOADER: WhatsAppIosDataLoader = WhatsAppIosDataLoader;
+
+/// Same as in whatsapp-android_2026-05, since JIDs are the same
+const PERSONAL_CHAT_ID: i64 = 9017079856339592512_i64;
+const GROUP_CHAT_ID: i64 = 15668065017168951_i64;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2026_10() -> EmptyRes {
+    let (res, db_dir) = create_databases(RESOURCE_DIR, "2026-10", "sql", ".sqlite", DB_FILENAME);
+    let avatar_path = "Media/Profile/11111-1776000000.thumb";
+    fs::create_dir_all(db_dir.path.join("Media/Profile"))?;
+    fs::write(db_dir.path.join(avatar_path), b"avatar")?;
+
+    LOADER.looks_about_right(&res)?;
+    let dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, User {
+        ds_uuid: ds_uuid.clone(),
+        id: 1_i64,
+        first_name_option: Some("Me".to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    });
+
+    let member1 = User {
+        ds_uuid: ds_uuid.clone(),
+        id: PERSONAL_CHAT_ID,
+        first_name_option: Some("Eeeee Eeeeeeeeee".to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: Some("11111".to_owned()),
+        profile_pictures: vec![ProfilePicture { path: avatar_path.to_owned(), frame_option: None }],
+    };
+    let member2 = User {
+        ds_uuid: ds_uuid.clone(),
+        id: hash_to_id("33333@s.whatsapp.net"),
+        first_name_option: Some("Ccccc".to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: Some("33333".to_owned()),
+        profile_pictures: vec![],
+    };
+    let expected_users =
+        vec![myself.clone(), member1.clone(), member2.clone()].into_iter().sorted_by_key(|u| u.id).collect_vec();
+    assert_eq!(dao.users_single_ds(), expected_users);
+
+    // Status broadcasts and chats without messages are skipped
+    assert_eq!(dao.cwms_single_ds().len(), 2);
+
+    {
+        let cwm = dao.cwms_single_ds().into_iter().find(|cwm| cwm.chat.id == PERSONAL_CHAT_ID).unwrap();
+        let chat = cwm.chat;
+        assert_eq!(chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: PERSONAL_CHAT_ID,
+            name_option: Some("Eeeee Eeeeeeeeee".to_owned()),
+            source_type: SourceType::WhatsappDb as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: Some(avatar_path.to_owned()),
+            member_ids: vec![myself.id, member1.id],
+            msg_count: 3,
+            main_chat_id: None,
+        });
+
+        let msgs = dao.first_messages(&chat, 99999)?;
+        assert_eq!(msgs.len() as i32, chat.msg_count);
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            Some(hash_to_id("PERSONALMSGIOS001")),
+            1776808700,
+            member1.id(),
+            vec![RichText::make_plain("Hello there".to_owned())],
+            MESSAGE_REGULAR_NO_CONTENT.clone(),
+        ));
+        assert_eq!(msgs[1], Message::new(
+            1,
+            Some(hash_to_id("PERSONALMSGIOS002")),
+            1776808730,
+            myself.id(),
+            vec![RichText::make_plain("Photo caption".to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(Photo {
+                    path_option: Some("Message/Media/11111@s.whatsapp.net/a/b/photo.jpg".to_owned()),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: None,
+                    is_one_time: false,
+                })],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+        assert_eq!(msgs[2], Message::new(
+            2,
+            Some(hash_to_id("PERSONALMSGIOS003")),
+            1776808766,
+            member1.id(),
+            vec![],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(VoiceMsg {
+                    path_option: Some("Message/Media/11111@s.whatsapp.net/c/d/voice.opus".to_owned()),
+                    file_name_option: Some("voice.opus".to_owned()),
+                    mime_type: "audio/ogg".to_owned(),
+                    duration_sec_option: Some(7),
+                })],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+    }
+
+    {
+        let cwm = dao.cwms_single_ds().into_iter().find(|cwm| cwm.chat.id == GROUP_CHAT_ID).unwrap();
+        let chat = cwm.chat;
+        assert_eq!(chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: GROUP_CHAT_ID,
+            name_option: Some("My Group".to_owned()),
+            source_type: SourceType::WhatsappDb as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: Some("Media/Profile/100000000000000001-1776000000.thumb".to_owned()),
+            member_ids: vec![myself.id, member2.id, member1.id].into_iter().sorted().collect_vec(),
+            msg_count: 4, // Message of unknown type is skipped
+            main_chat_id: None,
+        });
+
+        let msgs = dao.first_messages(&chat, 99999)?;
+        assert_eq!(msgs.len() as i32, chat.msg_count);
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            Some(hash_to_id("GROUPMSGIOS001")),
+            1776808800,
+            myself.id(),
+            vec![],
+            message_service!(GroupEditTitle(MessageServiceGroupEditTitle { title: "My Group".to_owned() })),
+        ));
+        assert_eq!(msgs[1], Message::new(
+            1,
+            Some(hash_to_id("GROUPMSGIOS002")),
+            1776808900,
+            member2.id(),
+            vec![RichText::make_plain("Group message".to_owned())],
+            MESSAGE_REGULAR_NO_CONTENT.clone(),
+        ));
+        assert_eq!(msgs[2], Message::new(
+            2,
+            Some(hash_to_id("GROUPMSGIOS003")),
+            1776808950,
+            member1.id(),
+            vec![],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(Location {
+                    title_option: Some("Red Square".to_owned()),
+                    address_option: None,
+                    lat_str: "55.75581235".to_owned(),
+                    lon_str: "37.6173".to_owned(),
+                    duration_sec_option: None,
+                })],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+        assert_eq!(msgs[3], Message::new(
+            3,
+            Some(hash_to_id("GROUPMSGIOS004")),
+            1776808980,
+            myself.id(),
+            vec![],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(File {
+                    path_option: Some("Message/Media/100000000000000001@g.us/e/f/8c1f.pdf".to_owned()),
+                    file_name_option: Some("report.pdf".to_owned()),
+                    mime_type_option: None,
+                    thumbnail_path_option: Some("Message/Media/100000000000000001@g.us/e/f/8c1f.thumb".to_owned()),
+                })],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
+            },
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn loading_2026_10_without_contacts() -> EmptyRes {
+    // Using a separate directory so that it won't interfere with other tests
+    let (res, db_dir) = create_databases(RESOURCE_DIR, "2026-10", "sql-no-contacts", ".sqlite", DB_FILENAME);
+    fs::remove_file(db_dir.path.join(CONTACTS_DB_FILENAME))?;
+
+    let dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    // Name falls back to the one stored in chat session, phone number is deduced from JID
+    let member1 = dao.users_single_ds().into_iter().find(|u| u.id == PERSONAL_CHAT_ID).unwrap();
+    assert_eq!(member1.first_name_option, Some("Eeeee Eeeeeeeeee".to_owned()));
+    assert_eq!(member1.phone_number_option, Some("11111".to_owned()));
+    assert_eq!(member1.profile_pictures, vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn rejecting_other_files() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    for name in ["ContactsV2.sqlite", "msgstore.db", "ChatStorage.sqlite-wal"] {
+        let path = tmp_dir.path.join(name);
+        fs::write(&path, b"")?;
+        assert!(LOADER.looks_about_right(&path).is_err(), "{name} should be rejected");
+    }
+    Ok(())
+}
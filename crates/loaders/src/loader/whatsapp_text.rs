@@ -1,6 +1,8 @@
-use std::fs;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
 
-use chrono::{NaiveDateTime, TimeZone};
+use chrono::{NaiveDate, NaiveTime, TimeZone};
 use lazy_static::lazy_static;
 use regex::Regex;
 
@@ -12,13 +14,47 @@ use chat_history_manager_dao::in_memory_dao::InMemoryDao;
 #[path = "whatsapp_text_tests.rs"]
 mod tests;
 
-const TIMESTAMP_REGEX_STR: &str = r"^(\d{1,2}/\d{1,2}/\d{1,4}, \d{2}:\d{2})";
+/// Android uses `d/m/y, HH:MM` or `M/D/YY, h:mm AM` depending on locale, iOS adds seconds and uses dots in some
+/// locales, some locales use ISO dates.
+const TIMESTAMP_REGEX_STR: &str =
+    r"\d{1,4}[./-]\d{1,2}[./-]\d{1,4},? \d{1,2}:\d{2}(?::\d{2})?(?:[ \u{202f}\u{a0}]?[AaPp]\.?[Mm]\.?)?";
+
+/// Left-to-right mark, iOS uses it to mark system lines and attachments.
+const LRM: char = '\u{200e}';
+
+/// Chat exported on iOS is always named like this, chat name is only present in the archive name.
+const IOS_CHAT_FILENAME: &str = "_chat.txt";
 
 lazy_static! {
-    static ref FILENAME_REGEX: Regex = Regex::new(r"^WhatsApp Chat with ([^.]+)\.txt$").unwrap();
-    static ref TIMESTAMP_REGEX: Regex = Regex::new(TIMESTAMP_REGEX_STR).unwrap();
-    static ref MESSAGE_PREFIX_REGEX: Regex = Regex::new(&format!("{}{}", TIMESTAMP_REGEX_STR, " - ([^:]+): (.+)$")).unwrap();
-    static ref ATTACHED_FILE_REGEX: Regex = Regex::new(r"^(([^-]+)-[^ ]+) \(file attached\)$").unwrap();
+    /// Localized variants of "WhatsApp Chat with X.txt", as well as archives (or unpacked directories) named the same way.
+    static ref FILENAME_REGEX: Regex = Regex::new(concat!(
+        r"^(?:WhatsApp Chat (?:with|mit|met|med|-) |Chat de WhatsApp con |Conversa do WhatsApp com |",
+        r"Discussion WhatsApp avec |Chat WhatsApp con |Чат WhatsApp с )(.+?)(?: \(\d+\))?(?:\.(txt|zip))?$"
+    )).unwrap();
+
+    /// Android line is `<timestamp> - <rest>`, iOS one is `[<timestamp>] <rest>`
+    static ref LINE_PREFIX_REGEX: Regex = Regex::new(&format!(
+        r"^(?:\[(?P<ts_ios>{TIMESTAMP_REGEX_STR})\]|(?P<ts>{TIMESTAMP_REGEX_STR}) -) (?P<rest>.*)$"
+    )).unwrap();
+
+    static ref SENDER_REGEX: Regex = Regex::new(r"^([^:]+): (.*)$").unwrap();
+
+    /// Android: `IMG-20230630-WA0000.jpg (file attached)`, localized
+    static ref ATTACHED_FILE_REGEX: Regex = Regex::new(concat!(
+        r"^(.+\.\w+) \((?:file attached|Datei angehängt|archivo adjunto|arquivo anexado|fichier joint|",
+        r"file allegato|bestand bijgevoegd|файл добавлен)\)$"
+    )).unwrap();
+
+    /// iOS: `<attached: 00000012-PHOTO-2023-06-30-16-15-03.jpg>`, localized
+    static ref ATTACHED_FILE_IOS_REGEX: Regex = Regex::new(
+        r"^<(?:attached|Anhang|adjunto|anexo|pièce jointe|allegato|bijlage|вложение) ?: ([^>]+)>$"
+    ).unwrap();
+
+    static ref MEDIA_OMITTED_REGEX: Regex = Regex::new(concat!(
+        r"^(?:null|<Media omitted>|<Medien ausgeschlossen>|<Multimedia omitido>|<Mídia oculta>|<Médias omis>|",
+        r"<Media omessi>|<Media weggelaten>|<Без медиафайлов>|",
+        r"(?:image|video|audio|sticker|document|GIF|Contact card) omitted)$"
+    )).unwrap();
 }
 
 pub struct WhatsAppTextDataLoader;
@@ -28,151 +64,256 @@ impl DataLoader for WhatsAppTextDataLoader {
 
     fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
         let filename = path_file_name(path)?;
-        if !FILENAME_REGEX.is_match(filename) {
-            bail!("File is not \"WhatsApp Chat with X.txt\"");
-        }
-        if !TIMESTAMP_REGEX.is_match(super::first_line(path)?.as_str()) {
-            bail!("File does not start with a timestamp as expected");
+        let ext = FILENAME_REGEX.captures(filename).and_then(|c| c.get(2)).map(|ext| ext.as_str());
+        match ext {
+            Some("zip") => {
+                find_chat_entry(&mut zip::ZipArchive::new(File::open(path)?)?)?;
+            }
+            Some(_) => {
+                ensure_starts_with_timestamp(&super::first_line(path)?)?;
+            }
+            None if filename == IOS_CHAT_FILENAME => {
+                ensure_starts_with_timestamp(&super::first_line(path)?)?;
+            }
+            None => bail!("File is not \"WhatsApp Chat with X.txt\", its localized variant or a zip archive of it"),
         }
         Ok(())
     }
 
     fn load_inner(&self, feedback_client: &dyn FeedbackClientSync, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
         feedback_client.set_load_status(LoadStatus::new_parsing("file", Some(format!("{}", path.display()))));
-        parse_whatsapp_text_file(path, ds)
+        let filename = path_file_name(path)?;
+        if path.extension().is_some_and(|ext| ext == "zip") {
+            let ds_root = path.with_extension("");
+            let content = extract_zip(path, &ds_root)?;
+            parse_whatsapp_text(feedback_client, &content, &chat_name(path)?, ds_root, ds)
+        } else {
+            let content = fs::read_to_string(path)?;
+            let chat_name = if filename == IOS_CHAT_FILENAME {
+                // Unpacked archive, directory might be named after it
+                chat_name(path.parent().unwrap())?
+            } else {
+                chat_name(path)?
+            };
+            parse_whatsapp_text(feedback_client, &content, &chat_name, path.parent().unwrap().to_path_buf(), ds)
+        }
+    }
+}
+
+fn ensure_starts_with_timestamp(first_line: &str) -> EmptyRes {
+    if !LINE_PREFIX_REGEX.is_match(strip_marks(first_line)) {
+        bail!("File does not start with a timestamp as expected");
     }
+    Ok(())
 }
 
-fn parse_whatsapp_text_file(path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
-    let file_content = fs::read_to_string(path)?;
-    let (myself, other) = parse_users(&ds.uuid, path_file_name(path)?, &file_content)?;
+/// Chat name as derived from a file name, falling back to the name itself.
+fn chat_name(path: &Path) -> Result<String> {
+    let filename = path_file_name(path)?;
+    Ok(match FILENAME_REGEX.captures(filename) {
+        Some(captures) => captures.get(1).unwrap().as_str().to_owned(),
+        None => filename.to_owned(),
+    })
+}
 
-    let messages = parse_messages(&file_content, &myself, &other)?;
+fn strip_marks(line: &str) -> &str {
+    line.trim_start_matches(['\u{feff}', LRM])
+}
+
+fn find_chat_entry<R: Read + std::io::Seek>(archive: &mut zip::ZipArchive<R>) -> Result<String> {
+    archive.file_names()
+        .find(|name| *name == IOS_CHAT_FILENAME || FILENAME_REGEX.is_match(name))
+        .map(|name| name.to_owned())
+        .context("Archive does not contain a WhatsApp chat")
+}
+
+/// Extracts media files next to the archive (unless they're already there), returns chat content.
+fn extract_zip(path: &Path, ds_root: &Path) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let chat_entry_name = find_chat_entry(&mut archive)?;
+    let mut content = String::new();
+    archive.by_name(&chat_entry_name)?.read_to_string(&mut content)?;
+
+    fs::create_dir_all(ds_root)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(rel_path) = entry.enclosed_name() else {
+            log::warn!("Skipping suspicious archive entry {}", entry.name());
+            continue;
+        };
+        if entry.is_dir() || entry.name() == chat_entry_name {
+            continue;
+        }
+        let target_path = ds_root.join(rel_path);
+        if !target_path.exists() {
+            fs::create_dir_all(target_path.parent().unwrap())?;
+            std::io::copy(&mut entry, &mut File::create(&target_path)?)?;
+        }
+    }
+    Ok(content)
+}
+
+/// Single message or system line, possibly spanning multiple text lines.
+struct RawEntry<'a> {
+    timestamp: Timestamp,
+    /// iOS prefixes some system lines with a name of a sender (or a group)
+    sender: Option<&'a str>,
+    is_system: bool,
+    lines: Vec<&'a str>,
+}
+
+fn parse_whatsapp_text(feedback_client: &dyn FeedbackClientSync,
+                       content: &str,
+                       chat_name: &str,
+                       ds_root: PathBuf,
+                       ds: Dataset) -> Result<Box<InMemoryDao>> {
+    let entries = parse_entries(content)?;
+
+    let sender_names = entries.iter().filter(|e| !e.is_system).filter_map(|e| e.sender).unique().collect_vec();
+    let is_personal = sender_names.len() <= 2 && sender_names.contains(&chat_name);
+
+    let (myself, others) = if is_personal {
+        // Self ID is set to minimum valid one.
+        let myself = User {
+            ds_uuid: ds.uuid.clone(),
+            id: UserId::INVALID.0 + 1,
+            first_name_option: sender_names.iter().find(|name| **name != chat_name).map(|name| (*name).to_owned()),
+            last_name_option: None,
+            username_option: None,
+            phone_number_option: None,
+            profile_pictures: vec![],
+        };
+        (myself, vec![new_user(&ds.uuid, chat_name)])
+    } else {
+        let mut users = sender_names.iter().map(|name| new_user(&ds.uuid, name)).collect_vec();
+        ensure!(!users.is_empty(), "No messages found in chat {chat_name}");
+        let myself_idx = feedback_client.choose_myself(&users)?;
+        (users.remove(myself_idx), users)
+    };
+
+    let name_to_id: HashMap<&str, UserId> = std::iter::once(&myself).chain(others.iter())
+        .filter_map(|u| u.first_name_option.as_deref().map(|name| (name, u.id())))
+        .collect();
+
+    let messages = convert_entries(entries, &name_to_id, myself.id())?;
 
     let cwms = vec![ChatWithMessages {
         chat: Chat {
             ds_uuid: ds.uuid.clone(),
-            id: other.id, // Using user ID as a chat ID
-            name_option: Some(other.pretty_name()),
+            // Using user ID as a chat ID for personal chats
+            id: if is_personal { others[0].id } else { super::hash_to_id(chat_name) },
+            name_option: Some(if is_personal { others[0].pretty_name() } else { chat_name.to_owned() }),
             source_type: SourceType::TextImport as i32,
-            tpe: ChatType::Personal as i32,
+            tpe: if is_personal { ChatType::Personal } else { ChatType::PrivateGroup } as i32,
             img_path_option: None,
-            member_ids: vec![myself.id, other.id],
+            member_ids: std::iter::once(myself.id).chain(others.iter().map(|u| u.id).sorted()).collect_vec(),
             msg_count: messages.len() as i32,
             main_chat_id: None,
         },
         messages
     }];
 
-    let parent_name = path_file_name(path.parent().unwrap())?;
+    let myself_id = myself.id();
     Ok(Box::new(InMemoryDao::new_single(
-        format!("WhatsApp ({})", parent_name),
+        format!("WhatsApp ({})", path_file_name(&ds_root)?),
         ds,
-        path.parent().unwrap().to_path_buf(),
-        myself.id(),
-        vec![myself, other],
+        ds_root,
+        myself_id,
+        std::iter::once(myself).chain(others).collect_vec(),
         cwms,
     )))
 }
 
-fn parse_users(ds_uuid: &PbUuid, filename: &str, content: &str) -> Result<(User, User)> {
-    let other_name = FILENAME_REGEX.captures(filename).unwrap().get(1).unwrap().as_str();
-
-    let mut user_names = content.lines()
-        .filter_map(|line| MESSAGE_PREFIX_REGEX.captures(line).map(|capt| capt.get(2).unwrap().as_str()))
-        .unique()
-        .collect_vec();
-
-    if user_names.len() != 2 {
-        bail!("Expected just two users, found {:?}", user_names);
-    }
-
-    let self_name = if let Some(other_id) = user_names.iter().position(|name| *name == other_name) {
-        user_names.remove(other_id);
-        user_names[0]
-    } else {
-        bail!("Expected other user to be named '{}', but users were {:?}", other_name, user_names)
-    };
-
-    // Self ID is set to minimum valid one.
-    Ok((User {
-        ds_uuid: ds_uuid.clone(),
-        id: UserId::INVALID.0 + 1,
-        first_name_option: Some(self_name.to_owned()),
-        last_name_option: None,
-        username_option: None,
-        phone_number_option: None,
-        profile_pictures: vec![],
-    }, User {
+fn new_user(ds_uuid: &PbUuid, name: &str) -> User {
+    User {
         ds_uuid: ds_uuid.clone(),
-        id: super::hash_to_id(other_name),
-        first_name_option: Some(other_name.to_owned()), // We do not normalize phone number here
+        id: super::hash_to_id(name),
+        first_name_option: Some(name.to_owned()), // We do not normalize phone number here
         last_name_option: None,
         username_option: None,
-        phone_number_option: if other_name.starts_with('+') { Some(PhoneNumber::from_raw(other_name).0) } else { None },
+        phone_number_option: if name.starts_with('+') { Some(PhoneNumber::from_raw(name).0) } else { None },
         profile_pictures: vec![],
-    }))
+    }
 }
 
-fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Message>> {
+fn parse_entries(content: &str) -> Result<Vec<RawEntry<'_>>> {
     const NOTICE_LINE: &str = "Messages and calls are end-to-end encrypted.";
     const TIMER_LINE: &str = "updated the message timer. New messages will disappear from this chat";
+    const IS_A_CONTACT_SUFFIX: &str = " is a contact";
+
+    let date_order = detect_date_order(content);
+    let mut result: Vec<RawEntry> = vec![];
+    let mut last_timestamp: Timestamp = Timestamp::MIN;
+    let mut skipping = false;
+
+    for line in content.lines() {
+        let Some(captures) = LINE_PREFIX_REGEX.captures(strip_marks(line)) else {
+            // Not the first message line, just text
+            match result.last_mut() {
+                _ if skipping => { /* NOOP */ }
+                Some(entry) => entry.lines.push(line),
+                None => bail!("Message timestamp unknown for line '{line}'"),
+            }
+            continue;
+        };
 
-    let is_a_contact_line: String = format!("- {} is a contact", other.pretty_name());
-
-    let mut result = vec![];
-
-    let mut user_id: Option<UserId> = None;
-    let mut timestamp: Timestamp = Timestamp::MIN;
-    let mut lines: Vec<&str> = Vec::with_capacity(10);
-    let mut last_internal_id = NO_INTERNAL_ID;
-
-    let mut iter = content.lines().peekable();
-    while let Some(line) = iter.next() {
-        if line.contains(NOTICE_LINE) || line.contains(TIMER_LINE) || line.ends_with(&is_a_contact_line) {
+        let rest = captures.name("rest").unwrap().as_str();
+        skipping = rest.contains(NOTICE_LINE) || rest.contains(TIMER_LINE) ||
+            (!rest.contains(": ") && rest.ends_with(IS_A_CONTACT_SUFFIX));
+        if skipping {
             continue;
         }
-        match MESSAGE_PREFIX_REGEX.captures(line) {
-            Some(capture) => {
-                // First message line
-                let timestamp2: Timestamp = parse_datetime(capture.get(1).unwrap().as_str())?;
-                if *timestamp2 > *timestamp {
-                    timestamp = timestamp2;
-                } else {
-                    // Multiple messages have the same timestamp - treat them as 1 second apart
-                    timestamp = Timestamp(*timestamp + 1);
-                }
-
-                let username_str = capture.get(2).unwrap().as_str();
-                user_id = Some(if username_str == myself.pretty_name() {
-                    myself.id()
-                } else {
-                    other.id()
-                });
-
-                lines.push(capture.get(3).unwrap().as_str());
-            }
-            None => {
-                // Not the first message line, just text
-                lines.push(line);
-            }
-        }
-        match iter.peek() {
-            Some(l) if !MESSAGE_PREFIX_REGEX.is_match(l) => {
-                // Multiline message continues, NOOP
+
+        let timestamp_str = captures.name("ts").or(captures.name("ts_ios")).unwrap().as_str();
+        let timestamp = parse_datetime(timestamp_str, date_order)?;
+        let timestamp = if *timestamp > *last_timestamp {
+            timestamp
+        } else {
+            // Multiple messages have the same timestamp - treat them as 1 second apart
+            Timestamp(*last_timestamp + 1)
+        };
+        last_timestamp = timestamp;
+
+        let entry = match SENDER_REGEX.captures(rest) {
+            Some(c) => {
+                // iOS marks system lines with LRM, but attachments too
+                let text = c.get(2).unwrap().as_str();
+                let is_system = is_ios_system_text(text);
+                let text = if is_system { strip_marks(text) } else { text };
+                RawEntry { timestamp, sender: Some(c.get(1).unwrap().as_str()), is_system, lines: vec![text] }
             }
-            _ => {
-                // Time to process collected info
-                let timestamp = if timestamp != Timestamp::MIN { *timestamp } else { bail!("Message timestamp unknown for line '{line}'") };
-                let from_id = user_id.context("Message author unknown for line '{line}'")?;
+            None =>
+                RawEntry { timestamp, sender: None, is_system: true, lines: vec![rest] },
+        };
+        result.push(entry);
+    }
+
+    Ok(result)
+}
 
-                last_internal_id = MessageInternalId(*last_internal_id + 1);
+fn is_ios_system_text(text: &str) -> bool {
+    text.starts_with(LRM) && {
+        let text = strip_marks(text);
+        !ATTACHED_FILE_IOS_REGEX.is_match(text) && !MEDIA_OMITTED_REGEX.is_match(text)
+    }
+}
 
-                let (text, contents) = parse_message_text(&lines)?;
+fn convert_entries(entries: Vec<RawEntry>,
+                   name_to_id: &HashMap<&str, UserId>,
+                   myself_id: UserId) -> Result<Vec<Message>> {
+    let mut result = vec![];
+    for entry in entries {
+        let first_line = entry.lines[0];
+        let internal_id = result.len() as i64;
+        match entry.sender {
+            Some(sender) if !entry.is_system => {
+                let from_id = *name_to_id.get(sender)
+                    .with_context(|| format!("Message author unknown for line '{first_line}'"))?;
+                let (text, contents) = parse_message_text(&entry.lines)?;
                 result.push(Message::new(
-                    *last_internal_id,
+                    internal_id,
                     None /* source_id_option */,
-                    timestamp,
+                    *entry.timestamp,
                     from_id,
                     text,
                     message_regular! {
@@ -181,69 +322,44 @@ fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Mess
                         forward_from_name_option: None,
                         reply_to_message_id_option: None,
                         contents,
+                        reactions: vec![],
+                        views_option: None,
+                        author_signature_option: None,
                     },
                 ));
-                user_id = None;
-                lines.clear();
+            }
+            sender_option => {
+                // System lines are localized, so we don't attempt to understand them.
+                // Their author is whoever is mentioned first, which is "You" when it's myself.
+                let from_id = sender_option.and_then(|sender| name_to_id.get(sender).cloned()).or_else(|| {
+                    name_to_id.iter()
+                        .filter(|(name, _)| first_line.starts_with(&format!("{name} ")))
+                        .max_by_key(|(name, _)| name.len())
+                        .map(|(_, id)| *id)
+                }).unwrap_or(myself_id);
+                let text = entry.lines.iter().join("\n").trim().to_owned();
+                result.push(Message::new(
+                    internal_id,
+                    None /* source_id_option */,
+                    *entry.timestamp,
+                    from_id,
+                    vec![RichText::make_plain(text)],
+                    message_service!(ServiceSvo::Notice(MessageServiceNotice {})),
+                ));
             }
         }
     }
-
     Ok(result)
 }
 
 fn parse_message_text(lines: &[&str]) -> Result<(Vec<RichTextElement>, Vec<Content>)> {
-    let (lines, content) = if let Some(attachment_captures) = ATTACHED_FILE_REGEX.captures(lines[0]) {
-        // First line describes attached file, determine the type
-        let tpe = attachment_captures.get(2).unwrap().as_str();
+    let first_line = strip_marks(lines[0]);
+    let attachment_captures = ATTACHED_FILE_REGEX.captures(first_line)
+        .or_else(|| ATTACHED_FILE_IOS_REGEX.captures(first_line));
+    let (lines, content) = if let Some(attachment_captures) = attachment_captures {
         let filename = attachment_captures.get(1).unwrap().as_str();
-
-        let content_value = match tpe {
-            "IMG" => content!(Photo {
-                path_option: Some(filename.to_owned()),
-                width: 0,
-                height: 0,
-                mime_type_option: None,
-                is_one_time: false,
-            }),
-            "STK" => content!(Sticker {
-                path_option: Some(filename.to_owned()),
-                file_name_option: Some(filename.to_owned()),
-                width: 0,
-                height: 0,
-                mime_type_option: None,
-                thumbnail_path_option: None,
-                emoji_option: None,
-            }),
-            "VID" => {
-                ensure!(filename.ends_with(".mp4"), "Unexpected video file extension: {}", filename);
-                content!(Video {
-                    path_option: Some(filename.to_owned()),
-                    file_name_option: Some(filename.to_owned()),
-                    title_option: None,
-                    performer_option: None,
-                    width: 0,
-                    height: 0,
-                    mime_type: "video/mp4".to_owned(),
-                    duration_sec_option: None,
-                    thumbnail_path_option: None,
-                    is_one_time: false,
-                })
-            }
-            "AUD" => {
-                ensure!(filename.ends_with(".opus"), "Unexpected audio file extension: {}", filename);
-                content!(VoiceMsg {
-                    path_option: Some(filename.to_owned()),
-                    file_name_option: Some(filename.to_owned()),
-                    mime_type: "audio/ogg".to_owned(),
-                    duration_sec_option: None,
-                })
-            }
-            _ => bail!("Unknown file type: {}", filename)
-        };
-
-        (&lines[1..], Some(content_value))
-    } else if lines[0] == "null" || lines[0] == "<Media omitted>" {
+        (&lines[1..], Some(attachment_content(filename)))
+    } else if MEDIA_OMITTED_REGEX.is_match(first_line) {
         // File wasn't present - e.g. one-time photo/video.
         // Since we don't know the type, represent it as a missing file.
         let content_value = content!(File {
@@ -267,18 +383,126 @@ fn parse_message_text(lines: &[&str]) -> Result<(Vec<RichTextElement>, Vec<Conte
     Ok((rtes, content.into_iter().collect_vec()))
 }
 
+/// File type is determined by a file name:
+/// ```text
+/// IMG-20230630-WA0000.jpg                (Android)
+/// 00000012-PHOTO-2023-06-30-16-15-03.jpg (iOS)
+/// Some document.pdf                      (either)
+/// ```
+fn attachment_content(filename: &str) -> Content {
+    let tpe = match filename.split('-').collect_vec().as_slice() {
+        [tpe, ..] if ["IMG", "STK", "VID", "AUD", "PTT"].contains(tpe) => *tpe,
+        [_, "PHOTO", ..] => "IMG",
+        [_, "STICKER", ..] => "STK",
+        [_, "VIDEO", ..] | [_, "GIF", ..] => "VID",
+        [_, "AUDIO", ..] => "AUD",
+        _ => "",
+    };
+    match tpe {
+        "IMG" => content!(Photo {
+            path_option: Some(filename.to_owned()),
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+        }),
+        "STK" => content!(Sticker {
+            path_option: Some(filename.to_owned()),
+            file_name_option: Some(filename.to_owned()),
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+            emoji_option: None,
+        }),
+        // Other extensions (e.g. iOS .mov and .m4a) are treated as generic files
+        "VID" if filename.ends_with(".mp4") => content!(Video {
+            path_option: Some(filename.to_owned()),
+            file_name_option: Some(filename.to_owned()),
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: "video/mp4".to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+            is_one_time: false,
+        }),
+        "AUD" | "PTT" if filename.ends_with(".opus") => content!(VoiceMsg {
+            path_option: Some(filename.to_owned()),
+            file_name_option: Some(filename.to_owned()),
+            mime_type: "audio/ogg".to_owned(),
+            duration_sec_option: None,
+        }),
+        _ => content!(File {
+            path_option: Some(filename.to_owned()),
+            file_name_option: Some(filename.to_owned()),
+            mime_type_option: None,
+            thumbnail_path_option: None,
+        }),
+    }
+}
+
+/// Order of day and month in slash-separated dates, which depends on a locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    MonthFirst,
+    DayFirst,
+}
+
+/// Detects date order once for the whole file, as a single date like `6/7/23` is ambiguous.
+/// Any slash-separated date having a component greater than 12 settles it, otherwise dates are treated as month-first.
+fn detect_date_order(content: &str) -> DateOrder {
+    for line in content.lines() {
+        let Some(captures) = LINE_PREFIX_REGEX.captures(strip_marks(line)) else { continue };
+        let timestamp_str = captures.name("ts").or(captures.name("ts_ios")).unwrap().as_str();
+        let date_str = timestamp_str.split([',', ' ']).next().unwrap();
+        let components = date_str.split('/').map(|c| c.parse::<u32>()).collect_vec();
+        match components.as_slice() {
+            [Ok(first), Ok(_), Ok(_)] if *first > 12 => return DateOrder::DayFirst,
+            [Ok(_), Ok(second), Ok(_)] if *second > 12 => return DateOrder::MonthFirst,
+            _ => { /* Ambiguous or not slash-separated, continue */ }
+        }
+    }
+    DateOrder::MonthFirst
+}
+
 /// Datetime formats used by WhatsApp:
 /// ```text
 /// 6/30/20, 16:14
 /// 30/6/2020, 16:14
+/// 6/30/20, 4:14 PM
+/// 30.06.20, 16:14:05 (iOS)
+/// 2020-06-30, 16:14
 /// ```
-fn parse_datetime(s: &str) -> Result<Timestamp> {
-    // NaiveDateTime::parse_from_str is slow, but we don't usually have a lot of mesages in this format,
+/// Ambiguous slash-separated dates are parsed in a given order, see `detect_date_order`.
+fn parse_datetime(s: &str, date_order: DateOrder) -> Result<Timestamp> {
+    // NaiveDate::parse_from_str is slow, but we don't usually have a lot of mesages in this format,
     // so we're fine with it.
-    const DATE_TIME_SHORT_FMT: &str = "%m/%d/%y, %H:%M"; // 2-digit year
-    const DATE_TIME_LONG_FMT: &str = "%d/%m/%Y, %H:%M";
-    let parse = NaiveDateTime::parse_from_str;
-    let naive_dt = parse(s, DATE_TIME_SHORT_FMT).or(parse(s, DATE_TIME_LONG_FMT))?;
-    let local_dt = LOCAL_TZ.from_local_datetime(&naive_dt).unwrap();
+    // Two-digit year formats should go first, otherwise e.g. "23" will be parsed as a year 23 AD.
+    const MONTH_FIRST_DATE_FMTS: &[&str] = &["%m/%d/%y", "%m/%d/%Y"];
+    const DAY_FIRST_DATE_FMTS: &[&str] = &["%d/%m/%y", "%d/%m/%Y"];
+    const OTHER_DATE_FMTS: &[&str] = &["%d.%m.%y", "%d.%m.%Y", "%Y-%m-%d"];
+    const TIME_FMTS: &[&str] = &["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M:%S %p"];
+    // Normalize non-breaking spaces, "a.m."/"PM" variants and date-time separator
+    let s = s.replace(['\u{202f}', '\u{a0}', ','], " ")
+        .replace("a.m.", " AM").replace("p.m.", " PM")
+        .replace("AM", " AM").replace("PM", " PM")
+        .split_whitespace().join(" ");
+    let (date_str, time_str) = s.split_once(' ')
+        .with_context(|| format!("Unknown timestamp format: {s}"))?;
+    // Date that is invalid in a detected order can only be read the other way around
+    let slash_date_fmts = match date_order {
+        DateOrder::MonthFirst => [MONTH_FIRST_DATE_FMTS, DAY_FIRST_DATE_FMTS],
+        DateOrder::DayFirst => [DAY_FIRST_DATE_FMTS, MONTH_FIRST_DATE_FMTS],
+    };
+    let date = slash_date_fmts.iter().flat_map(|fmts| fmts.iter()).chain(OTHER_DATE_FMTS)
+        .find_map(|fmt| NaiveDate::parse_from_str(date_str, fmt).ok())
+        .with_context(|| format!("Unknown date format: {s}"))?;
+    let time = TIME_FMTS.iter()
+        .find_map(|fmt| NaiveTime::parse_from_str(time_str, fmt).ok())
+        .with_context(|| format!("Unknown time format: {s}"))?;
+    let local_dt = LOCAL_TZ.from_local_datetime(&date.and_time(time)).earliest()
+        .with_context(|| format!("Non-existent local time: {s}"))?;
     Ok(Timestamp(local_dt.timestamp()))
 }
@@ -6,12 +6,15 @@ use lazy_static::lazy_static;
 use pretty_assertions::{assert_eq, assert_ne};
 
 use crate::entity_utils::*;
+use crate::loader::hash_to_id;
 use chat_history_manager_core::protobuf::history::content::SealedValueOptional::*;
 use chat_history_manager_core::protobuf::history::message::*;
 use chat_history_manager_core::protobuf::history::message_service::SealedValueOptional::*;
 use chat_history_manager_core::protobuf::history::User;
 use chat_history_manager_dao::ChatHistoryDao;
 
+use std::io::Write;
+
 const LOADER: WhatsAppTextDataLoader = WhatsAppTextDataLoader;
 
 //
@@ -156,6 +159,9 @@ fn loading_2023_10() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[5], Message {
@@ -184,6 +190,9 @@ fn loading_2023_10() -> EmptyRes {
                         is_one_time: false,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[6], Message {
@@ -206,6 +215,9 @@ fn loading_2023_10() -> EmptyRes {
                         duration_sec_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[7], Message {
@@ -231,6 +243,9 @@ fn loading_2023_10() -> EmptyRes {
                         emoji_option: None,
                     })
                 ],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[8], Message {
@@ -246,6 +261,9 @@ fn loading_2023_10() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![FILE_UNAVAILABLE.clone()],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
         assert_eq!(msgs[9], Message {
@@ -261,12 +279,288 @@ fn loading_2023_10() -> EmptyRes {
                 forward_from_name_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![FILE_UNAVAILABLE.clone()],
+                reactions: vec![],
+                views_option: None,
+                author_signature_option: None,
             }),
         });
     }
     Ok(())
 }
 
+#[test]
+fn loading_2026_10_group() -> EmptyRes {
+    let res = resource("whatsapp-text_2026-10/WhatsApp Chat with My Group.txt");
+    LOADER.looks_about_right(&res)?;
+
+    let aaaaa_id = hash_to_id("Aaaaa");
+    let feedback_client = PredefinedInputFeedbackClient { myself_id: Some(aaaaa_id), text: None };
+    let dao = LOADER.load(&feedback_client, &res)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, aaaaa_id);
+
+    let users = dao.users_single_ds();
+    assert_eq!(users.iter().map(|u| u.pretty_name()).sorted().collect_vec(), vec!["Aaaaa", "Bbbbb", "Ccccc"]);
+    let bbbbb_id = hash_to_id("Bbbbb");
+    let ccccc_id = hash_to_id("Ccccc");
+
+    assert_eq!(dao.cwms_single_ds().len(), 1);
+
+    let chat = dao.cwms_single_ds().remove(0).chat;
+    assert_eq!(chat, Chat {
+        ds_uuid: ds_uuid.clone(),
+        id: hash_to_id("My Group"),
+        name_option: Some("My Group".to_owned()),
+        source_type: SourceType::TextImport as i32,
+        tpe: ChatType::PrivateGroup as i32,
+        img_path_option: None,
+        member_ids: vec![aaaaa_id, bbbbb_id.min(ccccc_id), bbbbb_id.max(ccccc_id)],
+        msg_count: 7,
+        main_chat_id: None,
+    });
+
+    let msgs = dao.first_messages(&chat, 99999)?;
+    let notice = |internal_id: i64, ts: &str, from_id: i64, text: &str| Message::new(
+        internal_id,
+        None,
+        dt(ts, None).timestamp(),
+        UserId(from_id),
+        vec![RichText::make_plain(text.to_owned())],
+        message_service!(Notice(MessageServiceNotice {})),
+    );
+
+    assert_eq!(msgs[0], notice(0, "2026-10-01 09:00:00", aaaaa_id, r#"Aaaaa created group "My Group""#));
+    assert_eq!(msgs[1], notice(1, "2026-10-01 09:00:01", aaaaa_id, "Aaaaa added Bbbbb and Ccccc"));
+    assert_eq!(msgs[2], Message::new(
+        2,
+        None,
+        dt("2026-10-01 09:01:00", None).timestamp(),
+        UserId(bbbbb_id),
+        vec![RichText::make_plain("Hi all".to_owned())],
+        MESSAGE_REGULAR_NO_CONTENT.clone(),
+    ));
+    assert_eq!(msgs[3], Message::new(
+        3,
+        None,
+        dt("2026-10-01 13:05:00", None).timestamp(),
+        UserId(ccccc_id),
+        vec![],
+        message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(VoiceMsg {
+                path_option: Some("PTT-20261001-WA0001.opus".to_owned()),
+                file_name_option: Some("PTT-20261001-WA0001.opus".to_owned()),
+                mime_type: "audio/ogg".to_owned(),
+                duration_sec_option: None,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        },
+    ));
+    assert_eq!(msgs[4], Message::new(
+        4,
+        None,
+        dt("2026-10-01 13:06:00", None).timestamp(),
+        UserId(aaaaa_id),
+        vec![RichText::make_plain("final version".to_owned())],
+        message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(File {
+                path_option: Some("Report 2026.pdf".to_owned()),
+                file_name_option: Some("Report 2026.pdf".to_owned()),
+                mime_type_option: None,
+                thumbnail_path_option: None,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        },
+    ));
+    assert_eq!(msgs[5], Message::new(
+        5,
+        None,
+        dt("2026-10-01 13:06:01", None).timestamp(),
+        UserId(bbbbb_id),
+        vec![],
+        message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![FILE_UNAVAILABLE.clone()],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        },
+    ));
+    assert_eq!(msgs[6], notice(6, "2026-10-01 13:07:00", ccccc_id, "Ccccc left"));
+
+    Ok(())
+}
+
+#[test]
+fn loading_2026_10_ios_zip() -> EmptyRes {
+    const PHOTO_NAME: &str = "00000003-PHOTO-2026-10-01-09-00-10.jpg";
+
+    let tmp_dir = TmpDir::new();
+    let res = tmp_dir.path.join("WhatsApp Chat - Bbbbb Bbbbbbb.zip");
+    {
+        let mut zip = zip::ZipWriter::new(fs::File::create(&res)?);
+        let options = zip::write::FileOptions::<'_, ()>::default();
+        zip.start_file(IOS_CHAT_FILENAME, options)?;
+        zip.write_all(&fs::read(resource("whatsapp-text_2026-10-ios/_chat.txt"))?)?;
+        zip.start_file(PHOTO_NAME, options)?;
+        zip.write_all(b"photo")?;
+        zip.finish()?;
+    }
+    LOADER.looks_about_right(&res)?;
+
+    let dao = LOADER.load(&NoFeedbackClient, &res)?;
+
+    // Media is extracted next to the archive
+    let ds_root = tmp_dir.path.join("WhatsApp Chat - Bbbbb Bbbbbbb");
+    assert_eq!(dao.ds_roots.values().next().map(|r| &r.0), Some(&ds_root.canonicalize()?));
+    assert_eq!(fs::read(ds_root.join(PHOTO_NAME))?, b"photo");
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, User {
+        first_name_option: Some("Aaaaa".to_owned()),
+        ..expected_myself(ds_uuid)
+    });
+    let member_id = hash_to_id("Bbbbb Bbbbbbb");
+
+    let chat = dao.cwms_single_ds().remove(0).chat;
+    assert_eq!(chat.id, member_id);
+    assert_eq!(chat.tpe, ChatType::Personal as i32);
+    assert_eq!(chat.name_option, Some("Bbbbb Bbbbbbb".to_owned()));
+
+    let msgs = dao.first_messages(&chat, 99999)?;
+    assert_eq!(msgs.len(), 4);
+    assert_eq!(msgs[0], Message::new(
+        0,
+        None,
+        dt("2026-10-01 09:00:05", None).timestamp(),
+        UserId(member_id),
+        vec![RichText::make_plain("Hallo!\nWie geht's?".to_owned())],
+        MESSAGE_REGULAR_NO_CONTENT.clone(),
+    ));
+    assert_eq!(msgs[1], Message::new(
+        1,
+        None,
+        dt("2026-10-01 09:00:10", None).timestamp(),
+        myself.id(),
+        vec![],
+        message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![content!(Photo {
+                path_option: Some(PHOTO_NAME.to_owned()),
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+            })],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        },
+    ));
+    assert_eq!(msgs[2], Message::new(
+        2,
+        None,
+        dt("2026-10-01 09:00:11", None).timestamp(),
+        myself.id(),
+        vec![],
+        message_regular! {
+            edit_timestamp_option: None,
+            is_deleted: false,
+            forward_from_name_option: None,
+            reply_to_message_id_option: None,
+            contents: vec![FILE_UNAVAILABLE.clone()],
+            reactions: vec![],
+            views_option: None,
+            author_signature_option: None,
+        },
+    ));
+    // Deleted message is a system line attributed to its sender
+    assert_eq!(msgs[3], Message::new(
+        3,
+        None,
+        dt("2026-10-01 09:01:00", None).timestamp(),
+        UserId(member_id),
+        vec![RichText::make_plain("Diese Nachricht wurde gelöscht.".to_owned())],
+        message_service!(Notice(MessageServiceNotice {})),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn parsing_localized_timestamps() -> EmptyRes {
+    for (s, date_order, expected) in [
+        ("6/30/23, 16:14", DateOrder::MonthFirst, "2023-06-30 16:14:00"),
+        ("30/6/2023, 16:14", DateOrder::DayFirst, "2023-06-30 16:14:00"),
+        ("30/6/2023, 16:14", DateOrder::MonthFirst, "2023-06-30 16:14:00"),
+        ("6/30/23, 4:14\u{202f}PM", DateOrder::MonthFirst, "2023-06-30 16:14:00"),
+        ("6/30/23, 4:14 p.m.", DateOrder::MonthFirst, "2023-06-30 16:14:00"),
+        ("30.06.23, 16:14:05", DateOrder::MonthFirst, "2023-06-30 16:14:05"),
+        ("2023-06-30, 16:14", DateOrder::DayFirst, "2023-06-30 16:14:00"),
+        ("7/6/23, 16:14", DateOrder::MonthFirst, "2023-07-06 16:14:00"),
+        ("7/6/23, 16:14", DateOrder::DayFirst, "2023-06-07 16:14:00"),
+    ] {
+        assert!(LINE_PREFIX_REGEX.is_match(&format!("{s} - A: b")), "{s} should be recognized");
+        assert_eq!(*parse_datetime(s, date_order)?, dt(expected, None).timestamp(), "{s}");
+    }
+    Ok(())
+}
+
+#[test]
+fn detecting_date_order() {
+    // Ambiguous lines come first, order is only revealed later on
+    let day_first = "7/6/23, 16:14 - A: Hi\n8/6/23, 16:15 - B: Hello\n13/6/23, 10:00 - A: Bye\n";
+    assert_eq!(detect_date_order(day_first), DateOrder::DayFirst);
+    let month_first = "[7/6/23, 16:14:00] A: Hi\n[7/13/23, 10:00:00] B: Bye\n";
+    assert_eq!(detect_date_order(month_first), DateOrder::MonthFirst);
+    let ambiguous = "7/6/23, 16:14 - A: Hi\n7/8/23, 16:15 - B: Hello\n";
+    assert_eq!(detect_date_order(ambiguous), DateOrder::MonthFirst);
+    let dotted = "30.06.23, 16:14 - A: Hi\n";
+    assert_eq!(detect_date_order(dotted), DateOrder::MonthFirst);
+
+    // Every line is parsed with the detected order
+    let entries = parse_entries(day_first).unwrap();
+    assert_eq!(entries.iter().map(|e| *e.timestamp).collect_vec(), [
+        dt("2023-06-07 16:14:00", None).timestamp(),
+        dt("2023-06-08 16:15:00", None).timestamp(),
+        dt("2023-06-13 10:00:00", None).timestamp(),
+    ]);
+}
+
+#[test]
+fn matching_localized_file_names() {
+    for (filename, expected) in [
+        ("WhatsApp Chat with +123 45 6789.txt", Some("+123 45 6789")),
+        ("WhatsApp Chat mit Bbbbb.txt", Some("Bbbbb")),
+        ("Chat de WhatsApp con Bbbbb B. (2).txt", Some("Bbbbb B.")),
+        ("WhatsApp Chat - Bbbbb.zip", Some("Bbbbb")),
+        ("Some other chat.txt", None),
+    ] {
+        let actual = FILENAME_REGEX.captures(filename).map(|c| c.get(1).unwrap().as_str());
+        assert_eq!(actual, expected, "{filename}");
+    }
+}
+
 //
 // Helpers
 //
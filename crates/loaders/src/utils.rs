@@ -1,4 +1,5 @@
 pub mod blob_utils;
+pub mod json_stream;
 pub mod json_utils;
 
 #[cfg(test)]
@@ -0,0 +1,189 @@
+use std::io::BufRead;
+
+use crate::prelude::*;
+
+/// Pull-based reader walking through a JSON document without loading it whole.
+///
+/// Objects and arrays can be entered and iterated over, while any value can be read as raw bytes
+/// (to be parsed by `simd_json` as usual) or skipped entirely.
+/// Input is expected to be a well-formed JSON, structure is not fully validated here.
+pub struct JsonStreamReader<R: BufRead> {
+    reader: R,
+    /// For every object/array entered, whether its first entry is yet to be read
+    nesting: Vec<bool>,
+}
+
+impl<R: BufRead> JsonStreamReader<R> {
+    pub fn new(reader: R) -> Self {
+        JsonStreamReader { reader, nesting: vec![] }
+    }
+
+    /// Enters an object, its keys should then be read by [Self::next_key].
+    pub fn begin_object(&mut self) -> EmptyRes {
+        self.expect_byte(b'{')?;
+        self.nesting.push(true);
+        Ok(())
+    }
+
+    /// Reads a next key of the current object, positioning reader at its value.
+    /// Returns `None` (and leaves the object) if there are no more keys.
+    pub fn next_key(&mut self) -> Result<Option<String>> {
+        if !self.has_next(b'}')? {
+            return Ok(None);
+        }
+        let mut key_bytes = vec![];
+        self.skip_whitespace()?;
+        ensure!(self.peek_byte()? == Some(b'"'), "JSON object key is not a string");
+        self.copy_value(&mut key_bytes)?;
+        self.expect_byte(b':')?;
+        Ok(Some(simd_json::from_slice::<String>(&mut key_bytes)?))
+    }
+
+    /// Enters an array, its elements should then be read after every [Self::has_next_element] check.
+    pub fn begin_array(&mut self) -> EmptyRes {
+        self.expect_byte(b'[')?;
+        self.nesting.push(true);
+        Ok(())
+    }
+
+    /// Whether current array has more elements, leaves the array if it doesn't.
+    pub fn has_next_element(&mut self) -> Result<bool> {
+        self.has_next(b']')
+    }
+
+    /// Reads a complete next value as raw bytes.
+    pub fn read_raw_value(&mut self) -> Result<Vec<u8>> {
+        let mut result = vec![];
+        self.skip_whitespace()?;
+        self.copy_value(&mut result)?;
+        Ok(result)
+    }
+
+    /// Skips a complete next value.
+    pub fn skip_value(&mut self) -> EmptyRes {
+        self.skip_whitespace()?;
+        self.scan_value(&mut |_| ())
+    }
+
+    /// Makes sure nothing but whitespace is left after the root value.
+    pub fn finish(mut self) -> EmptyRes {
+        ensure!(self.nesting.is_empty(), "JSON document has not been fully read");
+        self.skip_whitespace()?;
+        ensure!(self.peek_byte()?.is_none(), "Unexpected content after the end of JSON document");
+        Ok(())
+    }
+
+    //
+    // Helpers
+    //
+
+    fn has_next(&mut self, closing: u8) -> Result<bool> {
+        let is_first = *self.nesting.last().context("Not inside a JSON object or array")?;
+        self.skip_whitespace()?;
+        if self.peek_byte()? == Some(closing) {
+            self.reader.consume(1);
+            self.nesting.pop();
+            return Ok(false);
+        }
+        if !is_first {
+            self.expect_byte(b',')?;
+        }
+        *self.nesting.last_mut().unwrap() = false;
+        Ok(true)
+    }
+
+    fn peek_byte(&mut self) -> Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn expect_byte(&mut self, expected: u8) -> EmptyRes {
+        self.skip_whitespace()?;
+        match self.peek_byte()? {
+            Some(b) if b == expected => {
+                self.reader.consume(1);
+                Ok(())
+            }
+            Some(b) => err!("Expected '{}' in JSON, found '{}'", expected as char, b as char),
+            None => err!("Expected '{}' in JSON, found end of file", expected as char),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> EmptyRes {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            let ws_len = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            let buf_len = buf.len();
+            self.reader.consume(ws_len);
+            if ws_len < buf_len {
+                return Ok(());
+            }
+        }
+    }
+
+    fn copy_value(&mut self, target: &mut Vec<u8>) -> EmptyRes {
+        self.scan_value(&mut |chunk| target.extend_from_slice(chunk))
+    }
+
+    /// Goes through a value starting at the current position, passing every chunk of it to `on_chunk`.
+    /// String contents are tracked to not be confused by brackets or quotes in them.
+    fn scan_value(&mut self, on_chunk: &mut dyn FnMut(&[u8])) -> EmptyRes {
+        let first = self.peek_byte()?.context("Expected a value in JSON, found end of file")?;
+        let is_scalar = !matches!(first, b'{' | b'[' | b'"');
+        let mut depth = 0_usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut started = false;
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                ensure!(is_scalar && started, "Unexpected end of JSON file");
+                return Ok(());
+            }
+            let mut end = None;
+            for (i, &b) in buf.iter().enumerate() {
+                if is_scalar {
+                    if matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace() {
+                        end = Some(i);
+                        break;
+                    }
+                    started = true;
+                } else if in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if b == b'\\' {
+                        escaped = true;
+                    } else if b == b'"' {
+                        in_string = false;
+                        if depth == 0 {
+                            end = Some(i + 1);
+                            break;
+                        }
+                    }
+                } else {
+                    match b {
+                        b'"' => in_string = true,
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(i + 1);
+                                break;
+                            }
+                        }
+                        _ => { /* NOOP */ }
+                    }
+                }
+            }
+            let len = end.unwrap_or(buf.len());
+            on_chunk(&buf[..len]);
+            self.reader.consume(len);
+            if end.is_some() {
+                ensure!(!is_scalar || started, "Expected a value in JSON, found '{}'", first as char);
+                return Ok(());
+            }
+        }
+    }
+}
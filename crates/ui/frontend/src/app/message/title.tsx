@@ -24,6 +24,12 @@ export default function MessageTitle(args: {
                         editOrDeleteTimestamp={regular?.editTimestampOption}
                         isDeleted={isDeleted}
                         includeSeconds={false}/>
+      {regular?.authorSignatureOption && (
+        <span className="text-sm font-normal text-gray-500">&nbsp;&mdash; {regular.authorSignatureOption}</span>
+      )}
+      {regular?.viewsOption !== undefined && (
+        <span className="text-sm font-normal text-gray-500">&nbsp;&middot; {regular.viewsOption} views</span>
+      )}
     </ColoredName>
   )
 }
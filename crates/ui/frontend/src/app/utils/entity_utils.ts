@@ -255,6 +255,8 @@ export function ChatTypeToString(tpe: ChatType): string {
       return "Personal (1 to 1)"
     case ChatType.PRIVATE_GROUP:
       return "Private Group"
+    case ChatType.CHANNEL:
+      return "Channel"
     case ChatType.UNRECOGNIZED:
       ReportError(`Unrecognized chat type: ${chatTypeToJSON(tpe)}`);
       return "";
@@ -279,6 +281,8 @@ export function ChatSourceTypeToString(sourceType: SourceType): string {
       return "Badoo"
     case SourceType.MRA:
       return "Mail.Ru Agent"
+    case SourceType.DISCORD:
+      return "Discord"
     case SourceType.UNRECOGNIZED:
       ReportError(`Unrecognized chat source type: ${sourceTypeToJSON(sourceType)}`);
       return "";
@@ -0,0 +1,481 @@
+//! Commands operating on databases directly, without gRPC server or UI.
+
+#[cfg(test)]
+#[path = "headless_tests.rs"]
+mod tests;
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+use serde_json::json;
+
+use chat_history_manager_backend::prelude::*;
+use chat_history_manager_backend::{analyze_datasets, auto_merge_datasets, import_file, load_file, AutoMergePolicy, MergeAnalysisSection, MessageAlignment};
+
+/// Answers to questions loaders might ask while parsing a foreign history
+#[derive(Args, Debug, Clone)]
+pub struct FeedbackArgs {
+    /// ID of the user to be chosen as "myself", if asked
+    #[arg(long)]
+    pub myself_id: Option<i64>,
+    /// Text to be supplied whenever a text input (e.g. a password) is requested
+    #[arg(long)]
+    pub text: Option<String>,
+}
+
+impl FeedbackArgs {
+    fn client(&self) -> Box<dyn FeedbackClientSync> {
+        if self.myself_id.is_none() && self.text.is_none() {
+            Box::new(NoFeedbackClient)
+        } else {
+            Box::new(PredefinedInputFeedbackClient {
+                myself_id: self.myself_id,
+                text: self.text.clone(),
+            })
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum MergePolicyArg {
+    /// Add everything new, keep master version of conflicting users and messages
+    PreferMaster,
+    /// Add everything new, use slave version of conflicting users and messages
+    PreferSlave,
+    /// Same as either prefer-master or prefer-slave, whichever dataset has the latest message
+    PreferNewer,
+    /// Add everything new, keep both versions of conflicting messages
+    KeepBoth,
+    /// Add everything new, never change anything present in master
+    AddOnly,
+}
+
+impl From<MergePolicyArg> for AutoMergePolicy {
+    fn from(value: MergePolicyArg) -> Self {
+        match value {
+            MergePolicyArg::PreferMaster => AutoMergePolicy::PreferMaster,
+            MergePolicyArg::PreferSlave => AutoMergePolicy::PreferSlave,
+            MergePolicyArg::PreferNewer => AutoMergePolicy::PreferNewer,
+            MergePolicyArg::KeepBoth => AutoMergePolicy::KeepBoth,
+            MergePolicyArg::AddOnly => AutoMergePolicy::AddOnly,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityIssueKindArg {
+    /// Move unreferenced files to backup directory
+    OrphanFile,
+    /// Add message authors to chat members
+    NonMemberAuthor,
+    /// Recount chat messages
+    WrongMessageCount,
+    /// Recompute empty searchable strings
+    EmptySearchableString,
+}
+
+impl From<IntegrityIssueKindArg> for IntegrityIssueKind {
+    fn from(value: IntegrityIssueKindArg) -> Self {
+        match value {
+            IntegrityIssueKindArg::OrphanFile => IntegrityIssueKind::OrphanFile,
+            IntegrityIssueKindArg::NonMemberAuthor => IntegrityIssueKind::NonMemberAuthor,
+            IntegrityIssueKindArg::WrongMessageCount => IntegrityIssueKind::WrongMessageCount,
+            IntegrityIssueKindArg::EmptySearchableString => IntegrityIssueKind::EmptySearchableString,
+        }
+    }
+}
+
+fn alignment(fuzzy_tolerance_sec: Option<i64>) -> MessageAlignment {
+    match fuzzy_tolerance_sec {
+        Some(timestamp_tolerance_sec) => MessageAlignment::Fuzzy { timestamp_tolerance_sec },
+        None => MessageAlignment::Strict,
+    }
+}
+
+/// Output of a headless command, either human-readable lines or a JSON value.
+pub struct Output {
+    json: bool,
+    /// Everything printed so far, for tests to inspect
+    #[cfg(test)]
+    printed: std::cell::RefCell<Vec<String>>,
+}
+
+impl Output {
+    pub fn new(json: bool) -> Self {
+        Output {
+            json,
+            #[cfg(test)]
+            printed: Default::default(),
+        }
+    }
+
+    fn print(&self, text: impl FnOnce() -> Vec<String>, json: impl FnOnce() -> Result<serde_json::Value>) -> EmptyRes {
+        if self.json {
+            self.println(serde_json::to_string_pretty(&json()?)?);
+        } else {
+            for line in text() {
+                self.println(line);
+            }
+        }
+        Ok(())
+    }
+
+    fn println(&self, line: String) {
+        println!("{line}");
+        #[cfg(test)]
+        self.printed.borrow_mut().push(line);
+    }
+}
+
+//
+// Commands
+//
+
+/// Imports all datasets of the given history into a database, creating it if it doesn't exist.
+pub fn import(out: &Output, src_path: &Path, db_path: &Path, feedback: &FeedbackArgs) -> EmptyRes {
+    let db_file = sqlite_db_file(db_path);
+    let mut dst_dao = if db_file.exists() {
+        SqliteDao::load(&db_file)?
+    } else {
+        if let Some(parent) = db_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        SqliteDao::create(&db_file)?
+    };
+    let datasets = import_file(src_path, feedback.client().as_ref(), &mut dst_dao)?;
+    out.print(
+        || datasets.iter().map(|ds| format!("Imported dataset {} ({})", ds.uuid.value, ds.alias)).collect_vec(),
+        || Ok(json!({ "db_file": path_to_str(&db_file)?, "datasets": datasets })),
+    )
+}
+
+pub fn list_datasets(out: &Output, path: &Path, feedback: &FeedbackArgs) -> EmptyRes {
+    let dao = load_file(&sqlite_db_file(path), feedback.client().as_ref())?;
+    let datasets = dao.datasets()?;
+    out.print(
+        || datasets.iter().map(|ds| format!("{}  {}", ds.uuid.value, ds.alias)).collect_vec(),
+        || Ok(json!(datasets)),
+    )
+}
+
+pub fn list_chats(out: &Output, path: &Path, ds_uuid: Option<&str>, feedback: &FeedbackArgs) -> EmptyRes {
+    let dao = load_file(&sqlite_db_file(path), feedback.client().as_ref())?;
+    let ds = choose_dataset(dao.as_ref(), ds_uuid)?;
+    let chats = dao.chats(&ds.uuid)?.into_iter().map(|cwd| cwd.chat).collect_vec();
+    out.print(
+        || chats.iter().map(|c| format!("{}  {}  ({} messages)", c.id, c.qualified_name(), c.msg_count)).collect_vec(),
+        || Ok(json!(chats)),
+    )
+}
+
+pub fn list_users(out: &Output, path: &Path, ds_uuid: Option<&str>, feedback: &FeedbackArgs) -> EmptyRes {
+    let dao = load_file(&sqlite_db_file(path), feedback.client().as_ref())?;
+    let ds = choose_dataset(dao.as_ref(), ds_uuid)?;
+    let myself_id = dao.myself(&ds.uuid)?.id;
+    let users = dao.users(&ds.uuid)?;
+    out.print(
+        || users.iter().map(|u| {
+            let myself_suffix = if u.id == myself_id { "  (myself)" } else { "" };
+            format!("{}  {}{myself_suffix}", u.id, u.pretty_name())
+        }).collect_vec(),
+        || Ok(json!({ "myself_id": myself_id, "users": users })),
+    )
+}
+
+pub fn analyze(out: &Output,
+               master: &DatasetArgs,
+               slave: &DatasetArgs,
+               force_conflicts: bool,
+               fuzzy_tolerance_sec: Option<i64>,
+               feedback: &FeedbackArgs) -> EmptyRes {
+    let (m_dao, m_ds) = master.load(feedback)?;
+    let (s_dao, s_ds) = slave.load(feedback)?;
+    let analysis = analyze_datasets(m_dao.as_ref(), &m_ds, s_dao.as_ref(), &s_ds,
+                                    force_conflicts, alignment(fuzzy_tolerance_sec))?;
+
+    fn section_json(section: &MergeAnalysisSection) -> serde_json::Value {
+        match section {
+            MergeAnalysisSection::Match(v) => json!({
+                "type": "match",
+                "first_master_msg_id": *v.first_master_msg_id, "last_master_msg_id": *v.last_master_msg_id,
+                "first_slave_msg_id": *v.first_slave_msg_id, "last_slave_msg_id": *v.last_slave_msg_id,
+            }),
+            MergeAnalysisSection::Retention(v) => json!({
+                "type": "retention",
+                "first_master_msg_id": *v.first_master_msg_id, "last_master_msg_id": *v.last_master_msg_id,
+            }),
+            MergeAnalysisSection::Addition(v) => json!({
+                "type": "addition",
+                "first_slave_msg_id": *v.first_slave_msg_id, "last_slave_msg_id": *v.last_slave_msg_id,
+            }),
+            MergeAnalysisSection::Conflict(v) => json!({
+                "type": "conflict",
+                "first_master_msg_id": *v.first_master_msg_id, "last_master_msg_id": *v.last_master_msg_id,
+                "first_slave_msg_id": *v.first_slave_msg_id, "last_slave_msg_id": *v.last_slave_msg_id,
+            }),
+        }
+    }
+
+    fn section_text(section: &MergeAnalysisSection) -> String {
+        match section {
+            MergeAnalysisSection::Match(v) =>
+                format!("  Match:     master {}..={}, slave {}..={}",
+                        *v.first_master_msg_id, *v.last_master_msg_id, *v.first_slave_msg_id, *v.last_slave_msg_id),
+            MergeAnalysisSection::Retention(v) =>
+                format!("  Retention: master {}..={}", *v.first_master_msg_id, *v.last_master_msg_id),
+            MergeAnalysisSection::Addition(v) =>
+                format!("  Addition:  slave {}..={}", *v.first_slave_msg_id, *v.last_slave_msg_id),
+            MergeAnalysisSection::Conflict(v) =>
+                format!("  Conflict:  master {}..={}, slave {}..={}",
+                        *v.first_master_msg_id, *v.last_master_msg_id, *v.first_slave_msg_id, *v.last_slave_msg_id),
+        }
+    }
+
+    out.print(
+        || analysis.iter().flat_map(|ac| {
+            let header = format!("Chat {} ({})", ac.master_cwd.chat.id, ac.master_cwd.chat.qualified_name());
+            std::iter::once(header).chain(ac.sections.iter().map(section_text))
+        }).collect_vec(),
+        || Ok(json!(analysis.iter().map(|ac| json!({
+            "chat_id": ac.master_cwd.chat.id,
+            "chat_name": ac.master_cwd.chat.qualified_name(),
+            "sections": ac.sections.iter().map(section_json).collect_vec(),
+        })).collect_vec())),
+    )
+}
+
+pub fn merge(out: &Output,
+             master: &DatasetArgs,
+             slave: &DatasetArgs,
+             policy: MergePolicyArg,
+             fuzzy_tolerance_sec: Option<i64>,
+             output_dir: &Path,
+             feedback: &FeedbackArgs) -> EmptyRes {
+    let (m_dao, m_ds) = master.load(feedback)?;
+    let (s_dao, s_ds) = slave.load(feedback)?;
+    if !output_dir.exists() {
+        fs::create_dir_all(output_dir)?;
+    }
+    let (new_dao, new_ds, _report, summary) =
+        auto_merge_datasets(output_dir, m_dao.as_ref(), &m_ds, s_dao.as_ref(), &s_ds,
+                            policy.into(), alignment(fuzzy_tolerance_sec))?;
+    out.print(
+        || {
+            let mut lines = vec![
+                format!("Merged into dataset {} ({}) in {}", new_ds.uuid.value, new_ds.alias, new_dao.db_file.display()),
+                format!("Policy applied: {:?}", summary.effective_policy),
+                format!("Users: {} retained, {} added, {} kept, {} replaced",
+                        summary.users_retained, summary.users_added, summary.users_kept, summary.users_replaced),
+                format!("Chats: {} retained, {} added, {} merged",
+                        summary.chats_retained, summary.chats_added, summary.merged_chats.len()),
+            ];
+            lines.extend(summary.merged_chats.iter().map(|cs| format!(
+                "  Chat {} ({}): {} matched, {} retained, {} added, {} conflicts ({} master vs {} slave messages)",
+                *cs.chat_id, cs.chat_name, cs.matched_msgs, cs.retained_msgs, cs.added_msgs,
+                cs.conflicts, cs.conflicting_master_msgs, cs.conflicting_slave_msgs)));
+            lines
+        },
+        || Ok(json!({
+            "db_file": path_to_str(&new_dao.db_file)?,
+            "dataset": new_ds,
+            "summary": {
+                "effective_policy": format!("{:?}", summary.effective_policy),
+                "users_retained": summary.users_retained,
+                "users_added": summary.users_added,
+                "users_kept": summary.users_kept,
+                "users_replaced": summary.users_replaced,
+                "chats_retained": summary.chats_retained,
+                "chats_added": summary.chats_added,
+                "merged_chats": summary.merged_chats.iter().map(|cs| json!({
+                    "chat_id": *cs.chat_id,
+                    "chat_name": cs.chat_name,
+                    "matched_msgs": cs.matched_msgs,
+                    "retained_msgs": cs.retained_msgs,
+                    "added_msgs": cs.added_msgs,
+                    "conflicts": cs.conflicts,
+                    "conflicting_master_msgs": cs.conflicting_master_msgs,
+                    "conflicting_slave_msgs": cs.conflicting_slave_msgs,
+                })).collect_vec(),
+            },
+        })),
+    )
+}
+
+pub fn export_html(out: &Output,
+                   dataset: &DatasetArgs,
+                   chat_id: Option<i64>,
+                   output_dir: &Path,
+                   messages_per_page: usize,
+                   feedback: &FeedbackArgs) -> EmptyRes {
+    let (dao, ds) = dataset.load(feedback)?;
+    let summary = chat_history_manager_backend::export_html(dao.as_ref(), &ds.uuid, chat_id.map(ChatId), output_dir, messages_per_page)?;
+    out.print(
+        || vec![
+            format!("Exported {} chats ({} messages) to {}", summary.chat_count, summary.message_count, summary.index_file.display()),
+            format!("Copied {} files, {} files missing", summary.files_copied, summary.files_missing),
+        ],
+        || Ok(json!({
+            "index_file": path_to_str(&summary.index_file)?,
+            "chat_count": summary.chat_count,
+            "message_count": summary.message_count,
+            "files_copied": summary.files_copied,
+            "files_missing": summary.files_missing,
+        })),
+    )
+}
+
+pub fn shift_time(out: &Output, db_path: &Path, ds_uuid: Option<&str>, hours: i32) -> EmptyRes {
+    let mut dao = SqliteDao::load(&sqlite_db_file(db_path))?;
+    let ds = choose_dataset(&dao, ds_uuid)?;
+    dao.as_shiftable()?.shift_dataset_time(&ds.uuid, hours)?;
+    out.print(
+        || vec![format!("Shifted dataset {} time by {hours} hrs", ds.uuid.value)],
+        || Ok(json!({ "dataset": ds, "hours_shift": hours })),
+    )
+}
+
+pub fn backup(out: &Output, db_path: &Path) -> EmptyRes {
+    let mut dao = SqliteDao::load(&sqlite_db_file(db_path))?;
+    let join_handle = dao.backup()?;
+    join_handle.join().map_err(|_| anyhow!("Backup thread panicked!"))?;
+    let backup_path = dao.backup_path();
+    out.print(
+        || vec![format!("Backup saved to {}", backup_path.display())],
+        || Ok(json!({ "backup_path": path_to_str(&backup_path)? })),
+    )
+}
+
+pub fn vacuum(out: &Output, db_path: &Path) -> EmptyRes {
+    let db_file = sqlite_db_file(db_path);
+    let dao = SqliteDao::load(&db_file)?;
+    dao.vacuum()?;
+    out.print(
+        || vec![format!("Vacuumed {}", db_file.display())],
+        || Ok(json!({ "db_file": path_to_str(&db_file)? })),
+    )
+}
+
+pub fn verify(out: &Output, db_path: &Path, ds_uuid: Option<&str>, fix: &[IntegrityIssueKindArg]) -> EmptyRes {
+    let mut dao = SqliteDao::load(&sqlite_db_file(db_path))?;
+    let ds = choose_dataset(&dao, ds_uuid)?;
+    let fix_kinds = fix.iter().map(|&kind| IntegrityIssueKind::from(kind)).collect_vec();
+    let (issues, fixed_count) = dao.verify_and_fix(&ds.uuid, &fix_kinds)?;
+    out.print(
+        || {
+            let mut lines = issues.iter()
+                .map(|issue| format!("{}{issue}", if issue.is_fixable() { "[fixable] " } else { "" }))
+                .collect_vec();
+            lines.push(format!("Found {} issues, fixed {fixed_count}", issues.len()));
+            lines
+        },
+        || Ok(json!({
+            "issues": issues.iter().map(|issue| json!({
+                "type": format!("{:?}", issue.kind()),
+                "description": issue.to_string(),
+                "fixable": issue.is_fixable(),
+            })).collect_vec(),
+            "fixed_count": fixed_count,
+        })),
+    )
+}
+
+pub fn dedupe_media(out: &Output, db_path: &Path) -> EmptyRes {
+    let mut dao = SqliteDao::load(&sqlite_db_file(db_path))?;
+    let report = dao.dedupe_media()?;
+    out.print(
+        || vec![
+            format!("Files: {} -> {}", report.files_before, report.files_after),
+            format!("Space saved: {} bytes", report.bytes_saved()),
+            format!("Missing files: {}", report.missing_files),
+        ],
+        || Ok(json!({
+            "files_before": report.files_before,
+            "bytes_before": report.bytes_before,
+            "files_after": report.files_after,
+            "bytes_after": report.bytes_after,
+            "bytes_saved": report.bytes_saved(),
+            "missing_files": report.missing_files,
+        })),
+    )
+}
+
+pub fn relink_media(out: &Output, db_path: &Path, ds_uuid: Option<&str>, search_dir: &Path, apply: bool) -> EmptyRes {
+    let mut dao = SqliteDao::load(&sqlite_db_file(db_path))?;
+    let ds = choose_dataset(&dao, ds_uuid)?;
+    let plan = dao.plan_media_relink(&ds.uuid, search_dir)?;
+    let relinked_count = if apply && !plan.relinks.is_empty() { dao.relink_media(&ds.uuid, &plan)? } else { 0 };
+    out.print(
+        || {
+            let mut lines = plan.relinks.iter()
+                .map(|r| format!("Chat {}, message {}: {} <- {} (by {:?})",
+                                 r.chat_id.0, r.internal_id.0, r.old_path.as_deref().unwrap_or("(none)"),
+                                 r.found_file.display(), r.match_kind))
+                .collect_vec();
+            lines.push(format!("Found: {}, ambiguous: {}, not found: {}",
+                               plan.relinks.len(), plan.ambiguous, plan.unmatched));
+            lines.push(if apply {
+                format!("Relinked: {relinked_count}")
+            } else {
+                "Dry run, use --apply to relink".to_owned()
+            });
+            lines
+        },
+        || Ok(json!({
+            "relinks": plan.relinks.iter().map(|r| json!({
+                "chat_id": r.chat_id.0,
+                "message_internal_id": r.internal_id.0,
+                "old_path": r.old_path,
+                "found_file": r.found_file.to_string_lossy(),
+                "match_kind": format!("{:?}", r.match_kind),
+            })).collect_vec(),
+            "ambiguous": plan.ambiguous,
+            "not_found": plan.unmatched,
+            "relinked_count": relinked_count,
+        })),
+    )
+}
+
+//
+// Helpers
+//
+
+/// A dataset within a database file (or a directory containing it), or within a foreign history.
+/// Dataset UUID can be omitted if there's just one.
+pub struct DatasetArgs {
+    pub path: PathBuf,
+    pub ds_uuid: Option<String>,
+}
+
+impl DatasetArgs {
+    fn load(&self, feedback: &FeedbackArgs) -> Result<(Box<dyn ChatHistoryDao>, Dataset)> {
+        let dao = load_file(&sqlite_db_file(&self.path), feedback.client().as_ref())?;
+        let ds = choose_dataset(dao.as_ref(), self.ds_uuid.as_deref())?;
+        Ok((dao, ds))
+    }
+}
+
+/// Allows passing a database directory instead of the database file itself.
+fn sqlite_db_file(path: &Path) -> PathBuf {
+    let is_existing_db_dir = path.is_dir() && path.join(SqliteDao::FILENAME).exists();
+    let is_new_db_dir = !path.exists() && path.extension().is_none();
+    if is_existing_db_dir || is_new_db_dir {
+        path.join(SqliteDao::FILENAME)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn choose_dataset(dao: &dyn ChatHistoryDao, ds_uuid: Option<&str>) -> Result<Dataset> {
+    let datasets = dao.datasets()?;
+    match ds_uuid {
+        Some(ds_uuid) =>
+            datasets.into_iter().find(|ds| ds.uuid.value == ds_uuid)
+                .with_context(|| format!("Dataset {ds_uuid} not found in {}", dao.name())),
+        None if datasets.len() == 1 =>
+            Ok(datasets.into_iter().next().unwrap()),
+        None =>
+            bail!("{} contains {} datasets, please specify one of them: {}",
+                  dao.name(), datasets.len(), datasets.iter().map(|ds| &ds.uuid.value).join(", ")),
+    }
+}
@@ -0,0 +1,173 @@
+#![allow(unused_imports)]
+
+use super::*;
+use crate::{Args, Command};
+
+use chat_history_manager_backend::prelude::test_utils::TmpDir;
+
+use clap::Parser;
+use pretty_assertions::{assert_eq, assert_ne};
+
+const GROUP_CHAT_PATH: &str = "crates/loaders/resources/test/whatsapp-text_2026-10/WhatsApp Chat with My Group.txt";
+
+//
+// Tests
+//
+
+#[test]
+fn feedback_flags() -> EmptyRes {
+    let Some(Command::Import { feedback, .. }) = parse(&["import", "src", "db"])?.command else { unreachable!() };
+    assert_eq!(feedback.myself_id, None);
+    assert_eq!(feedback.text, None);
+    assert!(feedback.client().choose_myself(&[]).is_err());
+    assert!(feedback.client().ask_for_text("Password?").is_err());
+
+    let Some(Command::Import { feedback, .. }) =
+        parse(&["import", "src", "db", "--myself-id", "123", "--text", "secret"])?.command else { unreachable!() };
+    assert_eq!(feedback.myself_id, Some(123));
+    assert_eq!(feedback.text.as_deref(), Some("secret"));
+    let users = [User { id: 456, ..Default::default() }, User { id: 123, ..Default::default() }];
+    assert_eq!(feedback.client().choose_myself(&users)?, 1);
+    assert_eq!(feedback.client().ask_for_text("Password?")?, "secret");
+
+    // Flags are accepted after any command reading a history
+    let Some(Command::ListUsers { feedback, .. }) =
+        parse(&["--json", "list-users", "src", "--text", "secret"])?.command else { unreachable!() };
+    assert_eq!(feedback.text.as_deref(), Some("secret"));
+    Ok(())
+}
+
+#[test]
+fn import_list_merge_verify() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join(GROUP_CHAT_PATH);
+    let src = path_to_str(&src)?;
+    let master = tmp_dir.path.join("master");
+    let master = path_to_str(&master)?;
+    let slave = tmp_dir.path.join("slave");
+    let slave = path_to_str(&slave)?;
+    let merged = tmp_dir.path.join("merged");
+    let merged = path_to_str(&merged)?;
+
+    // Group chat asks who's "myself"
+    let err = run(&["import", src, master]).expect_err("Import should fail without --myself-id");
+    assert!(format!("{err:?}").contains("No way to choose myself"), "Unexpected error: {err:?}");
+
+    let myself_id = hash_to_id("Aaaaa").to_string();
+    let imported = run_json(&["import", src, master, "--myself-id", &myself_id])?;
+    assert_eq!(imported["db_file"], path_to_str(&Path::new(master).join(SqliteDao::FILENAME))?);
+    assert_eq!(imported["datasets"].as_array().unwrap().len(), 1);
+    run_json(&["import", src, slave, "--myself-id", &myself_id])?;
+
+    // List
+
+    let datasets = run_json(&["list-datasets", master])?;
+    assert_eq!(datasets, json!([imported["datasets"][0]]));
+    let master_ds_uuid = datasets[0]["uuid"]["value"].as_str().unwrap().to_owned();
+
+    let users = run_json(&["list-users", master])?;
+    assert_eq!(users["myself_id"].to_string(), myself_id);
+    let user_names = users["users"].as_array().unwrap().iter()
+        .map(|u| u["first_name_option"].as_str().unwrap())
+        .sorted()
+        .collect_vec();
+    assert_eq!(user_names, vec!["Aaaaa", "Bbbbb", "Ccccc"]);
+
+    let chats = run_json(&["list-chats", master, "--ds-uuid", &master_ds_uuid])?;
+    let chats = chats.as_array().unwrap();
+    assert_eq!(chats.len(), 1);
+    assert_eq!(chats[0]["name_option"], "My Group");
+    let msg_count = chats[0]["msg_count"].as_i64().unwrap();
+    assert!(msg_count > 0);
+
+    let chat_id = chats[0]["id"].as_i64().unwrap();
+    let lines = run(&["list-chats", master])?;
+    assert_eq!(lines, vec![format!("{chat_id}  'My Group' (#{chat_id})  ({msg_count} messages)")]);
+
+    // Merge
+
+    let merge_res = run_json(&["merge", master, slave, merged, "--policy", "prefer-slave"])?;
+    assert_eq!(merge_res["db_file"], path_to_str(&Path::new(merged).join(SqliteDao::FILENAME))?);
+    assert_ne!(merge_res["dataset"]["uuid"]["value"], master_ds_uuid.as_str());
+    let summary = &merge_res["summary"];
+    assert_eq!(summary["effective_policy"], "PreferSlave");
+    assert_eq!(summary["users_added"], 0);
+    assert_eq!(summary["chats_added"], 0);
+    let merged_chats = summary["merged_chats"].as_array().unwrap();
+    assert_eq!(merged_chats.len(), 1);
+    assert_eq!(merged_chats[0]["chat_id"], chat_id);
+    assert_eq!(merged_chats[0]["chat_name"], format!("'My Group' (#{chat_id})"));
+    assert_eq!(merged_chats[0]["matched_msgs"], msg_count);
+    assert_eq!(merged_chats[0]["added_msgs"], 0);
+    assert_eq!(merged_chats[0]["conflicts"], 0);
+
+    let merged_chats = run_json(&["list-chats", merged])?;
+    assert_eq!(merged_chats.as_array().unwrap().len(), 1);
+    assert_eq!(merged_chats[0]["msg_count"], msg_count);
+
+    // Verify
+
+    // Attachments aren't present in the fixture, which can't be fixed
+    let verify_res = run_json(&["verify", merged, "--fix", "wrong-message-count"])?;
+    let issues = verify_res["issues"].as_array().unwrap();
+    assert!(!issues.is_empty());
+    for issue in issues {
+        assert_eq!(issue["type"], "MissingFile");
+        assert_eq!(issue["fixable"], false);
+        assert!(issue["description"].is_string());
+    }
+    assert_eq!(verify_res["fixed_count"], 0);
+
+    let lines = run(&["verify", merged])?;
+    assert_eq!(lines.len(), issues.len() + 1);
+    assert_eq!(lines.last().unwrap(), &format!("Found {} issues, fixed 0", issues.len()));
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn parse(args: &[&str]) -> Result<Args> {
+    Ok(Args::try_parse_from(std::iter::once("chat-history-manager").chain(args.iter().copied()))?)
+}
+
+/// Runs a command the same way `main` does, returning printed lines
+fn run(args: &[&str]) -> Result<Vec<String>> {
+    let args = parse(args)?;
+    let out = Output::new(args.json);
+    match args.command.expect("Command is required") {
+        Command::Import { src, db, feedback } =>
+            import(&out, &src, &db, &feedback)?,
+        Command::ListDatasets { path, feedback } =>
+            list_datasets(&out, &path, &feedback)?,
+        Command::ListChats { path, ds_uuid, feedback } =>
+            list_chats(&out, &path, ds_uuid.as_deref(), &feedback)?,
+        Command::ListUsers { path, ds_uuid, feedback } =>
+            list_users(&out, &path, ds_uuid.as_deref(), &feedback)?,
+        Command::Merge { master, slave, output_dir, master_ds_uuid, slave_ds_uuid, policy, fuzzy_tolerance_sec, feedback } => {
+            let master = DatasetArgs { path: master, ds_uuid: master_ds_uuid };
+            let slave = DatasetArgs { path: slave, ds_uuid: slave_ds_uuid };
+            merge(&out, &master, &slave, policy, fuzzy_tolerance_sec, &output_dir, &feedback)?
+        }
+        Command::Verify { db, ds_uuid, fix } =>
+            verify(&out, &db, ds_uuid.as_deref(), &fix)?,
+        command => panic!("Unsupported command {command:?}"),
+    }
+    Ok(out.printed.into_inner())
+}
+
+fn run_json(args: &[&str]) -> Result<serde_json::Value> {
+    let lines = run(&[&["--json"][..], args].concat())?;
+    assert_eq!(lines.len(), 1, "Expected a single JSON value, got {lines:?}");
+    Ok(serde_json::from_str(&lines[0])?)
+}
+
+/// Same as the one loaders use to derive user IDs from names
+fn hash_to_id(str: &str) -> i64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut h = hasher().build_hasher();
+    h.write(str.as_bytes());
+    h.write_u8(0xff);
+    (h.finish() / 2) as i64
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 use deepsize::DeepSizeOf;
 use log::LevelFilter;
@@ -5,7 +7,11 @@ use mimalloc::MiMalloc;
 use tokio::runtime::Handle;
 
 use chat_history_manager_backend::prelude::*;
-use chat_history_manager_backend::{debug_request_myself, parse_file, start_server};
+use chat_history_manager_backend::{debug_request_myself, parse_file, start_server, DEFAULT_MESSAGES_PER_PAGE};
+
+use crate::headless::*;
+
+mod headless;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -17,6 +23,10 @@ struct Args {
     /// Next port will be used for the user info request server.
     port: Option<u16>,
 
+    /// Print results of headless commands as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -35,6 +45,140 @@ enum Command {
     },
     /// (For debugging purposes only) Ask UI which user is "myself" and print it to the log
     RequestMyself,
+    /// Import all datasets from a foreign history (or another database) into a database,
+    /// creating it if it doesn't exist
+    Import {
+        /// History to import
+        src: PathBuf,
+        /// Target database file, or a directory to contain it
+        db: PathBuf,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// List datasets of a database or a foreign history
+    ListDatasets {
+        path: PathBuf,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// List chats of a dataset
+    ListChats {
+        path: PathBuf,
+        /// Dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        ds_uuid: Option<String>,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// List users of a dataset
+    ListUsers {
+        path: PathBuf,
+        /// Dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        ds_uuid: Option<String>,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// Analyze differences between chats present in both master and slave datasets
+    Analyze {
+        master: PathBuf,
+        slave: PathBuf,
+        /// Master dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        master_ds_uuid: Option<String>,
+        /// Slave dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        slave_ds_uuid: Option<String>,
+        /// Merge everything between the first and the last mismatch into a single conflict
+        #[arg(long)]
+        force_conflicts: bool,
+        /// Align chats without source IDs fuzzily, allowing timestamps to differ by up to this many seconds
+        #[arg(long)]
+        fuzzy_tolerance_sec: Option<i64>,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// Merge slave dataset into master dataset according to a policy, writing result into a new database.
+    /// Prints a summary of what the policy decided.
+    Merge {
+        master: PathBuf,
+        slave: PathBuf,
+        /// Directory for the new database
+        output_dir: PathBuf,
+        /// Master dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        master_ds_uuid: Option<String>,
+        /// Slave dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        slave_ds_uuid: Option<String>,
+        #[arg(long, value_enum, default_value = "prefer-master")]
+        policy: MergePolicyArg,
+        /// Align chats without source IDs fuzzily, allowing timestamps to differ by up to this many seconds
+        #[arg(long)]
+        fuzzy_tolerance_sec: Option<i64>,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// Export a dataset (or a single chat of it) as a static HTML, viewable in any browser
+    ExportHtml {
+        path: PathBuf,
+        /// Directory to write HTML into, should be empty or not exist
+        output_dir: PathBuf,
+        /// Dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        ds_uuid: Option<String>,
+        /// Export just this chat instead of a whole dataset
+        #[arg(long)]
+        chat_id: Option<i64>,
+        #[arg(long, default_value_t = DEFAULT_MESSAGES_PER_PAGE)]
+        messages_per_page: usize,
+        #[command(flatten)]
+        feedback: FeedbackArgs,
+    },
+    /// Shift time of all timestamps in a database dataset by the given number of hours
+    ShiftTime {
+        db: PathBuf,
+        #[arg(allow_negative_numbers = true)]
+        hours: i32,
+        /// Dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        ds_uuid: Option<String>,
+    },
+    /// Back up a database
+    Backup {
+        db: PathBuf,
+    },
+    /// Vacuum a database, reclaiming unused space
+    Vacuum {
+        db: PathBuf,
+    },
+    /// Look for inconsistencies in a database dataset, optionally fixing them
+    Verify {
+        db: PathBuf,
+        /// Dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        ds_uuid: Option<String>,
+        /// Fix issues of this kind, can be repeated
+        #[arg(long, value_enum)]
+        fix: Vec<IntegrityIssueKindArg>,
+    },
+    /// Move all files of a database into content-addressed layout, storing each distinct file once per dataset.
+    /// Files copied into the database afterwards are stored the same way.
+    DedupeMedia {
+        db: PathBuf,
+    },
+    /// Look for missing attachment files in a directory (e.g. WhatsApp's Media or Signal's attachments.noindex),
+    /// matching them by hash, name, type and time. Only reports what's found unless --apply is given.
+    RelinkMedia {
+        db: PathBuf,
+        search_dir: PathBuf,
+        /// Dataset UUID, can be omitted if there's just one
+        #[arg(long)]
+        ds_uuid: Option<String>,
+        /// Copy found files into the database and update attachments
+        #[arg(long)]
+        apply: bool,
+    },
 }
 
 /** Starts a server by default. */
@@ -43,10 +187,10 @@ async fn main() {
     init_logger();
 
     let args = Args::parse();
-    catch_fatal_error(execute_command(args.command, args.port).await)
+    catch_fatal_error(execute_command(args.command, args.port, Output::new(args.json)).await)
 }
 
-async fn execute_command(command: Option<Command>, port: Option<u16>) -> EmptyRes {
+async fn execute_command(command: Option<Command>, port: Option<u16>, out: Output) -> EmptyRes {
     let port = port.unwrap_or(DEFAULT_SERVER_PORT);
     let remote_port = port + 1;
     match command {
@@ -103,10 +247,59 @@ async fn execute_command(command: Option<Command>, port: Option<u16>) -> EmptyRe
             let chosen = debug_request_myself(port).await?;
             log::info!("Picked: {}", chosen);
         }
+        Some(Command::Import { src, db, feedback }) => {
+            run_blocking(move || import(&out, &src, &db, &feedback)).await?;
+        }
+        Some(Command::ListDatasets { path, feedback }) => {
+            run_blocking(move || list_datasets(&out, &path, &feedback)).await?;
+        }
+        Some(Command::ListChats { path, ds_uuid, feedback }) => {
+            run_blocking(move || list_chats(&out, &path, ds_uuid.as_deref(), &feedback)).await?;
+        }
+        Some(Command::ListUsers { path, ds_uuid, feedback }) => {
+            run_blocking(move || list_users(&out, &path, ds_uuid.as_deref(), &feedback)).await?;
+        }
+        Some(Command::Analyze { master, slave, master_ds_uuid, slave_ds_uuid, force_conflicts, fuzzy_tolerance_sec, feedback }) => {
+            let master = DatasetArgs { path: master, ds_uuid: master_ds_uuid };
+            let slave = DatasetArgs { path: slave, ds_uuid: slave_ds_uuid };
+            run_blocking(move || analyze(&out, &master, &slave, force_conflicts, fuzzy_tolerance_sec, &feedback)).await?;
+        }
+        Some(Command::Merge { master, slave, output_dir, master_ds_uuid, slave_ds_uuid, policy, fuzzy_tolerance_sec, feedback }) => {
+            let master = DatasetArgs { path: master, ds_uuid: master_ds_uuid };
+            let slave = DatasetArgs { path: slave, ds_uuid: slave_ds_uuid };
+            run_blocking(move || merge(&out, &master, &slave, policy, fuzzy_tolerance_sec, &output_dir, &feedback)).await?;
+        }
+        Some(Command::ExportHtml { path, output_dir, ds_uuid, chat_id, messages_per_page, feedback }) => {
+            let dataset = DatasetArgs { path, ds_uuid };
+            run_blocking(move || export_html(&out, &dataset, chat_id, &output_dir, messages_per_page, &feedback)).await?;
+        }
+        Some(Command::ShiftTime { db, hours, ds_uuid }) => {
+            run_blocking(move || shift_time(&out, &db, ds_uuid.as_deref(), hours)).await?;
+        }
+        Some(Command::Backup { db }) => {
+            run_blocking(move || backup(&out, &db)).await?;
+        }
+        Some(Command::Vacuum { db }) => {
+            run_blocking(move || vacuum(&out, &db)).await?;
+        }
+        Some(Command::Verify { db, ds_uuid, fix }) => {
+            run_blocking(move || verify(&out, &db, ds_uuid.as_deref(), &fix)).await?;
+        }
+        Some(Command::DedupeMedia { db }) => {
+            run_blocking(move || dedupe_media(&out, &db)).await?;
+        }
+        Some(Command::RelinkMedia { db, search_dir, ds_uuid, apply }) => {
+            run_blocking(move || relink_media(&out, &db, ds_uuid.as_deref(), &search_dir, apply)).await?;
+        }
     }
     Ok(())
 }
 
+/// Loaders might need a Tokio runtime handle (e.g. for HTTP requests), so blocking work can't run on an async thread.
+async fn run_blocking(f: impl FnOnce() -> EmptyRes + Send + 'static) -> EmptyRes {
+    Handle::current().spawn_blocking(f).await?
+}
+
 fn init_logger() {
     env_logger::Builder::new()
         .filter(None, LevelFilter::Debug)